@@ -0,0 +1,68 @@
+//! Record fresh model responses for the contract tests in tests/response_contract_test.rs, after
+//! a change to the prompts. Each output mode's prompt for tests/fixtures/responses/change.diff is
+//! sent to a provider, and the review, as the tool received it, replaces that mode's fixture:
+//!
+//! ```sh
+//! LLMCR_REGENERATE_FIXTURES=1 ANTHROPIC_API_KEY=... cargo run --example regenerate-fixtures
+//! ```
+//!
+//! `LLMCR_FIXTURE_PROVIDER` picks another built-in provider. The structured and failure-mode
+//! fixtures are written by hand and left alone. Read the new responses before committing them:
+//! take out anything that shouldn't be public, and update the tests' expectations to match.
+
+use clap::Parser;
+use llm_code_review::review::{run, Cli};
+use llm_code_review::transcript::Transcript;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// Each fixture and the options that ask for its output mode
+const FIXTURES: &[(&str, &[&str])] = &[
+    ("markdown.md", &["--attention-report", "--canary"]),
+    ("json.txt", &["--output-format", "json"]),
+    ("github_actions.txt", &["--output-format", "github-actions"]),
+    ("docstring_comments.txt", &["--output-format", "docstring-comments", "--language", "rust"]),
+    ("asciidoc.adoc", &["--output-format", "asciidoc"]),
+    ("mediawiki.wiki", &["--output-format", "mediawiki"]),
+];
+
+fn main() {
+    if env::var_os("LLMCR_REGENERATE_FIXTURES").is_none() {
+        eprintln!("This sends requests to a real provider; set LLMCR_REGENERATE_FIXTURES=1 to run it.");
+        process::exit(2);
+    }
+    let provider = env::var("LLMCR_FIXTURE_PROVIDER").unwrap_or_else(|_| "anthropic".to_string());
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/responses");
+    let transcript = env::temp_dir().join(format!("llm_code_review_fixture_{}.json", process::id()));
+    let review = env::temp_dir().join(format!("llm_code_review_fixture_{}.txt", process::id()));
+    let diff = dir.join("change.diff");
+    let (diff, transcript_path, review_path) =
+        (diff.to_string_lossy(), transcript.to_string_lossy(), review.to_string_lossy());
+
+    for (fixture, options) in FIXTURES {
+        let mut args = vec!["llm_code_review", "--provider", &provider, "--no-provenance", "--heartbeat-secs", "0"];
+        args.extend(["--diff-file", &diff, "--save-transcript", &transcript_path, "--output-file", &review_path]);
+        args.extend(options.iter().copied());
+
+        let result = Cli::try_parse_from(&args)
+            .map_err(|e| e.to_string())
+            .map(|cli| {
+                // Invalid JSON can fail the run after the transcript is saved, and is worth keeping too
+                if let Err(e) = run(cli) {
+                    eprintln!("{}: {}", fixture, e);
+                }
+            })
+            .and_then(|()| Transcript::load(&transcript).map_err(|e| e.to_string()));
+        match result {
+            Ok(recorded) => {
+                fs::write(dir.join(fixture), format!("{}\n", recorded.review.trim_end())).unwrap();
+                println!("Wrote {}", fixture);
+            }
+            Err(e) => eprintln!("{}: {}", fixture, e),
+        }
+        let _ = fs::remove_file(&transcript);
+    }
+    let _ = fs::remove_file(&review);
+}
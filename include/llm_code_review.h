@@ -0,0 +1,60 @@
+#ifndef LLM_CODE_REVIEW_H
+#define LLM_CODE_REVIEW_H
+
+/* Generated by cbindgen from src/ffi.rs; don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of each call. The values are part of the C API and must not change.
+typedef enum LlmReviewStatus {
+  // The call succeeded
+  LLM_REVIEW_STATUS_OK = 0,
+  // A required pointer argument was NULL
+  LLM_REVIEW_STATUS_ERR_NULL_ARGUMENT = 1,
+  // A string argument wasn't valid UTF-8
+  LLM_REVIEW_STATUS_ERR_INVALID_UTF8 = 2,
+  // The options weren't valid command line options
+  LLM_REVIEW_STATUS_ERR_INVALID_OPTIONS = 3,
+  // The prompt couldn't be built, e.g. a file named in the options couldn't be read
+  LLM_REVIEW_STATUS_ERR_PROMPT = 4,
+  // The provider callback returned NULL
+  LLM_REVIEW_STATUS_ERR_PROVIDER = 5,
+  // The output contained a NUL byte, so it can't be returned as a C string
+  LLM_REVIEW_STATUS_ERR_NUL_IN_OUTPUT = 6,
+  // Something went wrong inside the library; this is a bug
+  LLM_REVIEW_STATUS_ERR_INTERNAL = 7,
+} LlmReviewStatus;
+
+// Sends a prompt to an LLM and returns its review, or NULL to report a failure.
+typedef const char *(*LlmReviewProvider)(const char *prompt, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Build the review prompt for `diff`.
+enum LlmReviewStatus llm_review_build_prompt(const char *diff,
+                                             const char *const *argv,
+                                             size_t argc,
+                                             char **out_prompt);
+
+// Build the review prompt for `diff` and return the review `provider` gives back for it.
+enum LlmReviewStatus llm_review_run(const char *diff,
+                                    const char *const *argv,
+                                    size_t argc,
+                                    LlmReviewProvider provider,
+                                    void *user_data,
+                                    char **out_review);
+
+// Release a string returned by this library. Passing NULL does nothing.
+void llm_review_free_string(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LLM_CODE_REVIEW_H */
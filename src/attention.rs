@@ -0,0 +1,219 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::diff::{FileDiff, FileKind};
+use crate::error::ReviewError;
+use crate::severity_policy::glob_regex;
+
+/// Below this confidence, in percent, the model's review of a file is left to a human
+pub const ATTENTION_CONFIDENCE_THRESHOLD: u8 = 70;
+
+/// Appended to the system prompt with --attention-report, so the model rates its own review
+pub const CONFIDENCE_INSTRUCTIONS: &str = "\nAfter the review, add a section headed `## Review Confidence` with a line `- path: N` for each changed file, where N is how confident you are, from 0 to 100, that your review of it caught what a careful human reviewer would.\n";
+
+/// Files no one writes by hand, so the LLM's review is enough
+const LOCK_FILES: &[&str] =
+    &["Cargo.lock", "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "go.sum", "Gemfile.lock", "poetry.lock"];
+
+/// Whether a human or the LLM reviews the files matching a glob, from an --attention-rules file.
+#[derive(Debug, Clone)]
+pub struct AttentionRule {
+    /// e.g. `src/billing/**` or `*.sql`
+    pub pattern: String,
+    pub needs_human: bool,
+    pub reason: String,
+    regex: Regex,
+}
+
+impl AttentionRule {
+    pub fn new(pattern: &str, needs_human: bool, reason: &str) -> Self {
+        AttentionRule { pattern: pattern.to_string(), needs_human, reason: reason.to_string(), regex: glob_regex(pattern) }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+impl PartialEq for AttentionRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.needs_human == other.needs_human && self.reason == other.reason
+    }
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    human: Vec<RuleEntry>,
+    #[serde(default)]
+    llm: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+struct RuleEntry {
+    path: String,
+    reason: Option<String>,
+}
+
+/// A team's rules for which files need a human reviewer, on top of the built-in heuristics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttentionRules {
+    pub rules: Vec<AttentionRule>,
+}
+
+impl AttentionRules {
+    /// Read a TOML file of `[[human]]` and `[[llm]]` tables, each a `path` glob and an optional
+    /// `reason`: files a human should look at, and files the LLM's review is enough for.
+    pub fn load(path: &Path) -> Result<Self, ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        let file: RulesFile =
+            toml::from_str(&contents).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+        let rule = |entry: &RuleEntry, needs_human: bool| {
+            let reason = entry.reason.clone().unwrap_or_else(|| format!("matches {}", entry.path));
+            AttentionRule::new(&entry.path, needs_human, &reason)
+        };
+        let mut rules: Vec<AttentionRule> = file.human.iter().map(|entry| rule(entry, true)).collect();
+        rules.extend(file.llm.iter().map(|entry| rule(entry, false)));
+        Ok(AttentionRules { rules })
+    }
+}
+
+/// Whether a file needs a human reviewer, and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileAttention {
+    pub path: String,
+    pub needs_human: bool,
+    #[serde(default)]
+    pub reasons: Vec<String>,
+    /// The model's confidence in its review of the file, in percent, when it gave one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u8>,
+}
+
+/// Decide for each file whether the LLM's review is likely enough or a human should look. A file
+/// needs a human if a `[[human]]` rule matches it, it touches unsafe code, concurrency or an
+/// authentication path, or the model's `confidence` in its review is under
+/// `ATTENTION_CONFIDENCE_THRESHOLD`. Otherwise the reasons say why the LLM is enough, if any
+/// apply: an `[[llm]]` rule, a mechanical change or a test-only change.
+pub fn assess(files: &[FileDiff], rules: &AttentionRules, confidence: &[(String, u8)]) -> Vec<FileAttention> {
+    let unsafe_regex = Regex::new(r"\bunsafe\b").unwrap();
+    let concurrency_regex = Regex::new(
+        r"\b(Mutex|RwLock|Condvar|Semaphore|Atomic[A-Z]\w*|thread::spawn|tokio::spawn|spawn_blocking|synchronized|volatile|threading|sync\.WaitGroup|go func)\b",
+    )
+    .unwrap();
+    let auth_regex =
+        Regex::new(r"(?i)(^|[/_.-])(auth\w*|login|logout|sessions?|passwords?|passwd|oauth|jwt|tokens?|crypto|permissions?|acls?|secrets?)([/_.-]|$)")
+            .unwrap();
+    let test_regex = Regex::new(r"(^|/)(tests?|spec|__tests__)/|_test\.|\.test\.|\.spec\.|(^|/)test_[^/]*$").unwrap();
+
+    files
+        .iter()
+        .map(|file| {
+            let changed: Vec<&str> = file
+                .hunks
+                .iter()
+                .flat_map(|hunk| &hunk.lines)
+                .filter(|line| line.starts_with('+') || line.starts_with('-'))
+                .map(|line| &line[1..])
+                .collect();
+            let confidence = confidence.iter().find(|(path, _)| *path == file.path).map(|(_, confidence)| *confidence);
+
+            let mut human = Vec::new();
+            let mut llm = Vec::new();
+            for rule in rules.rules.iter().filter(|rule| rule.matches(&file.path)) {
+                if rule.needs_human { &mut human } else { &mut llm }.push(rule.reason.clone());
+            }
+            if changed.iter().any(|line| unsafe_regex.is_match(line)) {
+                human.push("unsafe code".to_string());
+            }
+            if changed.iter().any(|line| concurrency_regex.is_match(line)) {
+                human.push("concurrency".to_string());
+            }
+            if auth_regex.is_match(&file.path) {
+                human.push("authentication or authorization path".to_string());
+            }
+            if let Some(confidence) = confidence.filter(|confidence| *confidence < ATTENTION_CONFIDENCE_THRESHOLD) {
+                human.push(format!("the model's confidence in its review is {}%", confidence));
+            }
+            if is_mechanical(file, &changed) {
+                llm.push("mechanical change".to_string());
+            }
+            if test_regex.is_match(&file.path) {
+                llm.push("test-only change".to_string());
+            }
+
+            let needs_human = !human.is_empty();
+            FileAttention {
+                path: file.path.clone(),
+                needs_human,
+                reasons: if needs_human { human } else { llm },
+                confidence,
+            }
+        })
+        .collect()
+}
+
+/// A change no one needs to read closely: a lock file, a deletion, a rename or copy without
+/// edits, or edits to whitespace only
+fn is_mechanical(file: &FileDiff, changed: &[&str]) -> bool {
+    let name = file.path.rsplit('/').next().unwrap_or_default();
+    if LOCK_FILES.contains(&name) || file.kind() == FileKind::Deleted {
+        return true;
+    }
+    if changed.is_empty() {
+        return matches!(file.kind(), FileKind::Renamed | FileKind::Copied);
+    }
+    let lines = |sign: char| {
+        let mut lines: Vec<String> = file
+            .hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .filter(|line| line.starts_with(sign))
+            .map(|line| line[1..].split_whitespace().collect())
+            .filter(|line: &String| !line.is_empty())
+            .collect();
+        lines.sort();
+        lines
+    };
+    lines('-') == lines('+')
+}
+
+/// The confidence the model gave for each file in the `Review Confidence` section of its review,
+/// as `- path: N` lines. Anything over 100 is left out.
+pub fn parse_confidence(review: &str) -> Vec<(String, u8)> {
+    let heading = Regex::new(r"(?i)^#+\s*Review Confidence\s*$").unwrap();
+    let line_regex = Regex::new(r"^\s*[-*]\s*`?([^`]+?)`?\s*:\s*(\d{1,3})\s*%?\s*$").unwrap();
+    let mut lines = review.lines().skip_while(|line| !heading.is_match(line.trim()));
+    if lines.next().is_none() {
+        return Vec::new();
+    }
+    lines
+        .take_while(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let captures = line_regex.captures(line)?;
+            let confidence = captures[2].parse().ok().filter(|confidence| *confidence <= 100)?;
+            Some((captures[1].to_string(), confidence))
+        })
+        .collect()
+}
+
+/// The Human Attention section: the files a human should look at, then those the LLM's review is
+/// likely enough for
+pub fn attention_section(attention: &[FileAttention]) -> String {
+    let needs_human = attention.iter().filter(|file| file.needs_human).count();
+    let mut lines = vec![format!("A human should review {} of {} files.", needs_human, attention.len()), String::new()];
+    let mut sorted: Vec<&FileAttention> = attention.iter().collect();
+    sorted.sort_by_key(|file| !file.needs_human);
+    for file in sorted {
+        let verdict = if file.needs_human { "needs a human" } else { "LLM review is likely enough" };
+        match file.reasons.as_slice() {
+            [] => lines.push(format!("- `{}`: {}", file.path, verdict)),
+            reasons => lines.push(format!("- `{}`: {} ({})", file.path, verdict, reasons.join(", "))),
+        }
+    }
+    lines.join("\n")
+}
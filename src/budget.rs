@@ -0,0 +1,394 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::attention::FileAttention;
+use crate::compress::trim_context;
+use crate::context_arbiter::FeatureDecision;
+use crate::deadline::DeadlineReport;
+use crate::diff::{parse_diff, render_diff};
+use crate::error::ReviewError;
+use crate::provenance::Provenance;
+use crate::provider::ProviderConfig;
+use crate::redact::RedactionSummary;
+use crate::review_length::ReviewTarget;
+use crate::sample::SampledCluster;
+use crate::size_tier::SizeTier;
+use crate::write_atomic::write_atomic;
+
+// Tokens are counted with the `tokens` module's tokenizers where the model's encoding is known,
+// and otherwise estimated from a number of characters per token, staying safely inside the
+// context limit. Both can be overridden per provider with --llm-provider-config.
+
+/// The token budget when no provider is configured. Claude's limit is 100k, so this is a safe amount.
+pub const DEFAULT_MAX_TOKENS: usize = 50_000;
+
+/// Characters per token when no provider is configured; a simple approximation
+pub const DEFAULT_CHARS_PER_TOKEN: usize = 4;
+
+/// However much of the budget is reserved for the response, the prompt always gets at least this much
+pub const MIN_PROMPT_TOKENS: usize = 1_000;
+
+/// Estimates how many tokens a piece of text will use.
+pub trait TokenEstimator {
+    fn estimate_tokens(&self, text: &str) -> usize;
+}
+
+/// The usual estimate: a fixed number of characters (bytes, in fact) per token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharsPerToken(pub usize);
+
+impl CharsPerToken {
+    /// The estimator for `model`'s `chars_per_token`, or the default without a model
+    pub fn for_model(model: Option<&ProviderConfig>) -> Self {
+        CharsPerToken(model.map_or(DEFAULT_CHARS_PER_TOKEN, |model| model.chars_per_token))
+    }
+}
+
+impl TokenEstimator for CharsPerToken {
+    fn estimate_tokens(&self, text: &str) -> usize {
+        text.len() / self.0.max(1)
+    }
+}
+
+/// Estimate how many tokens `text` will use with `model`, the provider from a
+/// `--llm-provider-config` file, or with a typical model if there is none.
+///
+/// ```
+/// use llm_code_review::budget::estimate_tokens;
+///
+/// assert_eq!(estimate_tokens("fn main() { println!(\"hi\"); }", None), 7);
+/// ```
+pub fn estimate_tokens(text: &str, model: Option<&ProviderConfig>) -> usize {
+    CharsPerToken::for_model(model).estimate_tokens(text)
+}
+
+/// A model's token budget: its context window, the part of it set aside for the response, and
+/// how much of the rest the prompt has used so far.
+///
+/// ```
+/// use llm_code_review::budget::Budget;
+///
+/// let mut budget = Budget::new(8_000).reserving_output(0.25);
+/// assert_eq!(budget.prompt_limit(), 6_000);
+/// budget.spend(5_000);
+/// assert!(budget.fits(1_000));
+/// assert!(!budget.fits(1_001));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Budget {
+    /// The whole budget, in tokens
+    pub limit: usize,
+    /// Tokens set aside for the response
+    pub reserved_output: usize,
+    /// Tokens the prompt has used
+    pub used: usize,
+}
+
+impl Budget {
+    /// An unused budget of `limit` tokens with nothing reserved for the response
+    pub fn new(limit: usize) -> Self {
+        Budget {
+            limit,
+            reserved_output: 0,
+            used: 0,
+        }
+    }
+
+    /// The budget of `model`, or the default budget if there is none
+    pub fn for_model(model: Option<&ProviderConfig>) -> Self {
+        Budget::new(model.map_or(DEFAULT_MAX_TOKENS, |model| model.max_tokens))
+    }
+
+    /// Set aside `fraction` (from 0.0 to 1.0) of the limit for the response
+    pub fn reserving_output(self, fraction: f32) -> Self {
+        let fraction = fraction.clamp(0.0, 1.0) as f64;
+        Budget {
+            reserved_output: (self.limit as f64 * fraction).round() as usize,
+            ..self
+        }
+    }
+
+    /// The tokens the prompt may use: the limit less what's reserved for the response, but never
+    /// less than `MIN_PROMPT_TOKENS`
+    pub fn prompt_limit(&self) -> usize {
+        self.limit.saturating_sub(self.reserved_output).max(MIN_PROMPT_TOKENS)
+    }
+
+    /// The tokens the prompt has left
+    pub fn remaining(&self) -> usize {
+        self.prompt_limit().saturating_sub(self.used)
+    }
+
+    /// Whether `tokens` more fit in the prompt
+    pub fn fits(&self, tokens: usize) -> bool {
+        tokens <= self.remaining()
+    }
+
+    /// Count `tokens` as used by the prompt
+    pub fn spend(&mut self, tokens: usize) {
+        self.used += tokens;
+    }
+}
+
+/// A diff cut down by `fit_to_budget`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct FittedDiff {
+    pub diff: String,
+    /// The context lines kept around each change; the original number if nothing was cut
+    pub context_lines: usize,
+    pub estimated_tokens: usize,
+}
+
+/// Fit `diff`, generated with `context_lines` lines of context, into what's left of `budget` by
+/// keeping fewer context lines around each change: as many as will fit. Changed lines are never
+/// dropped, so a diff that's too large even without context is a `DiffTooLarge` error.
+///
+/// ```
+/// use llm_code_review::budget::{fit_to_budget, Budget, CharsPerToken, TokenEstimator};
+///
+/// let context: String = (1..=400).map(|n| format!(" unchanged line {}\n", n)).collect();
+/// let diff = format!("--- a/notes.txt\n+++ b/notes.txt\n@@ -1,401 +1,401 @@\n{}-old\n+new\n", context);
+/// let estimator = CharsPerToken(4);
+/// let budget = Budget::new(1_000);
+/// assert!(!budget.fits(estimator.estimate_tokens(&diff)));
+///
+/// let fitted = fit_to_budget(&diff, 400, &budget, &estimator).unwrap();
+/// assert!(fitted.context_lines < 400);
+/// assert!(budget.fits(fitted.estimated_tokens));
+/// assert!(fitted.diff.ends_with("-old\n+new\n"));
+/// ```
+pub fn fit_to_budget(
+    diff: &str,
+    context_lines: usize,
+    budget: &Budget,
+    estimator: &dyn TokenEstimator,
+) -> Result<FittedDiff, ReviewError> {
+    let estimated_tokens = estimator.estimate_tokens(diff);
+    if budget.fits(estimated_tokens) {
+        return Ok(FittedDiff {
+            diff: diff.to_string(),
+            context_lines,
+            estimated_tokens,
+        });
+    }
+
+    let files = parse_diff(diff);
+    let fit = |context_lines: usize| {
+        let diff = render_diff(&trim_context(files.clone(), context_lines));
+        let estimated_tokens = estimator.estimate_tokens(&diff);
+        FittedDiff {
+            diff,
+            context_lines,
+            estimated_tokens,
+        }
+    };
+
+    let smallest = fit(0);
+    if !budget.fits(smallest.estimated_tokens) {
+        return Err(ReviewError::DiffTooLarge {
+            estimated_tokens: smallest.estimated_tokens,
+            max_tokens: budget.remaining(),
+        });
+    }
+    // The most context that fits. Less context is nearly always smaller, so search for it, but
+    // an elision marker can outweigh the lines it replaces, so check the result all the same.
+    let (mut fits, mut too_big) = (0, context_lines);
+    while too_big - fits > 1 {
+        let middle = fits + (too_big - fits) / 2;
+        if budget.fits(fit(middle).estimated_tokens) {
+            fits = middle;
+        } else {
+            too_big = middle;
+        }
+    }
+    let fitted = (0..=fits).rev().map(fit).find(|fitted| budget.fits(fitted.estimated_tokens));
+    Ok(fitted.unwrap_or(smallest))
+}
+
+/// What happened to a file's diff on the way into the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetAction {
+    Kept,
+    ContextReduced,
+    Dropped,
+}
+
+impl fmt::Display for BudgetAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BudgetAction::Kept => "kept",
+            BudgetAction::ContextReduced => "context-reduced",
+            BudgetAction::Dropped => "dropped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How much of the token budget a single file used.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileBudget {
+    pub path: String,
+    pub bytes: usize,
+    pub estimated_tokens: usize,
+    pub action: BudgetAction,
+}
+
+/// Breakdown of how the prompt was fitted into the token budget. The per-file figures come from
+/// `from_diffs`; the prompt totals are filled in by `record_prompt` once the prompt is built.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetReport {
+    pub total_chars: usize,
+    pub estimated_tokens: usize,
+    pub budget: usize,
+    pub utilization_pct: f64,
+    /// Size in characters of each part of the prompt
+    pub sections: BTreeMap<String, usize>,
+    pub files: Vec<FileBudget>,
+    /// The review length asked for in the prompt
+    pub review_target: Option<ReviewTarget>,
+    /// Groups of similar files that --sample-similar left partly out of the prompt
+    pub sampled: Vec<SampledCluster>,
+    /// Where the diff came from, unless --no-provenance was given
+    pub provenance: Option<Provenance>,
+    /// What --deadline-secs left out, if it was given
+    pub deadline: Option<DeadlineReport>,
+    /// The absolute paths scrubbed from the diff, unless --no-path-scrub was given
+    pub redactions: Option<RedactionSummary>,
+    /// How the diff was handled going by its size, for a diff from the local repository
+    pub size_tier: Option<SizeTier>,
+    /// Which optional context sections were included, and why the others weren't
+    pub context_features: Vec<FeatureDecision>,
+    /// With --attention-report, which files need a human reviewer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attention: Option<Vec<FileAttention>>,
+}
+
+impl BudgetReport {
+    /// Compare the diff as first collected with the one that ended up in the prompt. Files missing
+    /// from the final diff were dropped; if `context_reduced` is set, the rest were re-generated
+    /// with fewer context lines.
+    pub fn from_diffs(
+        original: &str,
+        final_diff: &str,
+        context_reduced: bool,
+        budget: usize,
+        estimator: &dyn TokenEstimator,
+    ) -> Self {
+        let final_files = parse_diff(final_diff);
+
+        let files = parse_diff(original)
+            .iter()
+            .map(|file| {
+                let (text, action) = match final_files.iter().find(|f| f.path == file.path) {
+                    Some(kept) if context_reduced => (kept.render(), BudgetAction::ContextReduced),
+                    Some(kept) => (kept.render(), BudgetAction::Kept),
+                    None => (file.render(), BudgetAction::Dropped),
+                };
+                FileBudget {
+                    path: file.path.clone(),
+                    bytes: text.len(),
+                    estimated_tokens: estimator.estimate_tokens(&text),
+                    action,
+                }
+            })
+            .collect();
+
+        BudgetReport {
+            files,
+            ..BudgetReport::new(budget)
+        }
+    }
+
+    /// An empty report, for prompts that don't contain a diff
+    pub fn new(budget: usize) -> Self {
+        BudgetReport {
+            total_chars: 0,
+            estimated_tokens: 0,
+            budget,
+            utilization_pct: 0.0,
+            sections: BTreeMap::new(),
+            files: Vec::new(),
+            review_target: None,
+            sampled: Vec::new(),
+            provenance: None,
+            deadline: None,
+            redactions: None,
+            size_tier: None,
+            context_features: Vec::new(),
+            attention: None,
+        }
+    }
+
+    /// Record the size of the assembled prompt and of each named part of it
+    pub fn record_prompt(&mut self, prompt: &str, sections: &[(&str, &str)], estimator: &dyn TokenEstimator) {
+        self.total_chars = prompt.len();
+        self.estimated_tokens = estimator.estimate_tokens(prompt);
+        self.utilization_pct = self.percent_of_budget(self.estimated_tokens);
+        self.sections = sections
+            .iter()
+            .map(|(name, text)| (name.to_string(), text.len()))
+            .collect();
+    }
+
+    /// Tokens used by the files that made it into the prompt
+    pub fn used_tokens(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.action != BudgetAction::Dropped)
+            .map(|file| file.estimated_tokens)
+            .sum()
+    }
+
+    fn percent_of_budget(&self, tokens: usize) -> f64 {
+        tokens as f64 * 100.0 / self.budget.max(1) as f64
+    }
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path_width = self
+            .files
+            .iter()
+            .map(|file| file.path.len())
+            .chain(["File".len()])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<path_width$}  {:>10}  {:>8}  {:>8}  Action",
+            "File", "Bytes", "Tokens", "Budget"
+        )?;
+        for file in &self.files {
+            writeln!(
+                f,
+                "{:<path_width$}  {:>10}  {:>8}  {:>7.1}%  {}",
+                file.path,
+                file.bytes,
+                file.estimated_tokens,
+                self.percent_of_budget(file.estimated_tokens),
+                file.action
+            )?;
+        }
+        write!(
+            f,
+            "Total: ~{} of {} tokens ({:.1}%)",
+            self.used_tokens(),
+            self.budget,
+            self.percent_of_budget(self.used_tokens())
+        )
+    }
+}
+
+/// Write the report to `path` as pretty-printed JSON.
+pub fn write_budget_report(report: &BudgetReport, path: &Path) -> Result<(), ReviewError> {
+    let mut json = serde_json::to_vec_pretty(report)
+        .map_err(|e| ReviewError::Write(path.to_path_buf(), e.into()))?;
+    json.push(b'\n');
+    write_atomic(path, json)
+}
@@ -0,0 +1,87 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Starts every canary, so one is easy to tell from the code around it
+pub const CANARY_PREFIX: &str = "LLMCR-";
+
+/// Added to the system prompt with --canary. The code itself only appears after the diff, so a
+/// model can only echo it if it read that far.
+pub const CANARY_INSTRUCTIONS: &str = "\nThe diff is followed by a line giving a verification code. End your response with the line `<!-- end-of-diff: CODE -->`, where CODE is that verification code, and don't mention it anywhere else.\n";
+
+/// The canary for `diff`: derived from its SHA-256, so it can't be guessed without reading the
+/// diff, and checking a saved response needs only the same diff
+pub fn canary_for(diff: &str) -> String {
+    let digest = Sha256::digest(format!("canary\n{}", diff).as_bytes());
+    let hex: String = digest.iter().take(4).map(|byte| format!("{:02X}", byte)).collect();
+    format!("{}{}", CANARY_PREFIX, hex)
+}
+
+/// The line placed after the last file of the diff
+pub fn canary_line(canary: &str) -> String {
+    format!("Verification code: {}", canary)
+}
+
+fn trailer_regex() -> Regex {
+    Regex::new(r"(?m)^[ \t]*<!--\s*end-of-diff:\s*(\S*?)\s*-->[ \t]*\r?$\n?").unwrap()
+}
+
+/// What a response's trailer says about whether the model saw the whole diff.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanaryCheck {
+    /// The response echoes the canary
+    Found,
+    /// The response has no trailer
+    Missing,
+    /// The trailer has another code, so the model made one up
+    Wrong(String),
+}
+
+impl CanaryCheck {
+    pub fn is_found(&self) -> bool {
+        *self == CanaryCheck::Found
+    }
+}
+
+impl fmt::Display for CanaryCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanaryCheck::Found => write!(f, "the response echoes the verification code from the end of the diff"),
+            CanaryCheck::Missing => write!(
+                f,
+                "the model may not have processed the full diff: the response doesn't echo the verification code \
+                 from the end of it"
+            ),
+            CanaryCheck::Wrong(code) => write!(
+                f,
+                "the model may not have processed the full diff: the response gives {} as the verification code \
+                 from the end of it, which is wrong",
+                code
+            ),
+        }
+    }
+}
+
+/// Whether `response` ends with a trailer echoing `canary`
+pub fn check_canary(response: &str, canary: &str) -> CanaryCheck {
+    let codes: Vec<String> = trailer_regex().captures_iter(response).map(|caps| caps[1].to_string()).collect();
+    if codes.iter().any(|code| code == canary) {
+        CanaryCheck::Found
+    } else if let Some(code) = codes.into_iter().next() {
+        CanaryCheck::Wrong(code)
+    } else {
+        CanaryCheck::Missing
+    }
+}
+
+/// `response` without its canary trailers, for showing or posting
+pub fn strip_canary(response: &str) -> String {
+    let stripped = trailer_regex().replace_all(response, "");
+    let mut stripped = stripped.trim_end().to_string();
+    if response.ends_with('\n') {
+        stripped.push('\n');
+    }
+    stripped
+}
@@ -0,0 +1,186 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::doctor::{find_on_path, is_executable, parse_git_version, RANGE_DIFF_GIT_VERSION};
+use crate::network::is_offline;
+
+/// Oldest git with `git diff --color-moved=zebra`
+pub const COLOR_MOVED_GIT_VERSION: (u32, u32) = (2, 15);
+
+/// Commands the prompt can be piped to for the clipboard, by platform
+pub const CLIPBOARD_COMMANDS: &[&str] = &["pbcopy", "wl-copy", "xclip", "xsel", "clip.exe", "clip"];
+
+/// Pagers looked for when `PAGER` isn't set
+const PAGER_COMMANDS: &[&str] = &["less", "more"];
+
+/// The command line front end of the platform's keyring
+#[cfg(target_os = "macos")]
+const KEYRING_COMMANDS: &[&str] = &["security"];
+#[cfg(windows)]
+const KEYRING_COMMANDS: &[&str] = &["cmdkey.exe", "cmdkey"];
+#[cfg(not(any(target_os = "macos", windows)))]
+const KEYRING_COMMANDS: &[&str] = &["secret-tool"];
+
+type GitVersion = Box<dyn Fn() -> Result<String, String> + Send + Sync>;
+
+/// Which optional tools this machine has, probed the first time each is asked about and
+/// remembered after that. Features that need a missing tool fall back rather than fail.
+pub struct Capabilities {
+    path: OsString,
+    pager_var: Option<String>,
+    git_version_output: GitVersion,
+    git: OnceLock<Result<String, String>>,
+    clipboard: OnceLock<Option<String>>,
+    pager: OnceLock<Option<String>>,
+    keyring: OnceLock<Option<String>>,
+}
+
+impl Capabilities {
+    /// Capabilities looked for on `path`, with `pager_var` as `PAGER`'s value and
+    /// `git_version_output` running `git --version`
+    pub fn new(
+        path: OsString,
+        pager_var: Option<String>,
+        git_version_output: impl Fn() -> Result<String, String> + Send + Sync + 'static,
+    ) -> Self {
+        Capabilities {
+            path,
+            pager_var,
+            git_version_output: Box::new(git_version_output),
+            git: OnceLock::new(),
+            clipboard: OnceLock::new(),
+            pager: OnceLock::new(),
+            keyring: OnceLock::new(),
+        }
+    }
+
+    /// The capabilities of the machine the tool runs on
+    pub fn detect() -> Self {
+        Capabilities::new(env::var_os("PATH").unwrap_or_default(), env::var("PAGER").ok(), git_version_output)
+    }
+
+    /// The output of `git --version`, or why it couldn't be run
+    pub fn git(&self) -> &Result<String, String> {
+        self.git.get_or_init(|| (self.git_version_output)())
+    }
+
+    /// The major and minor version of git, if it runs and says
+    pub fn git_version(&self) -> Option<(u32, u32)> {
+        self.git().as_ref().ok().and_then(|output| parse_git_version(output))
+    }
+
+    /// Whether git can show `git range-diff`
+    pub fn range_diff(&self) -> bool {
+        self.git_version().is_some_and(|version| version >= RANGE_DIFF_GIT_VERSION)
+    }
+
+    /// Whether git can mark moved lines with `--color-moved=zebra`
+    pub fn color_moved(&self) -> bool {
+        self.git_version().is_some_and(|version| version >= COLOR_MOVED_GIT_VERSION)
+    }
+
+    /// The command to pipe text to for the clipboard
+    pub fn clipboard(&self) -> Option<&str> {
+        self.clipboard
+            .get_or_init(|| find_on_path(CLIPBOARD_COMMANDS, &self.path).map(str::to_string))
+            .as_deref()
+    }
+
+    /// The pager: `PAGER`'s command if it's installed, or else `less` or `more`
+    pub fn pager(&self) -> Option<&str> {
+        self.pager.get_or_init(|| find_pager(self.pager_var.as_deref(), &self.path)).as_deref()
+    }
+
+    /// The command for the platform's keyring
+    pub fn keyring(&self) -> Option<&str> {
+        self.keyring
+            .get_or_init(|| find_on_path(KEYRING_COMMANDS, &self.path).map(str::to_string))
+            .as_deref()
+    }
+
+    /// Whether the tool may make network connections, which --offline forbids
+    pub fn network(&self) -> bool {
+        !is_offline()
+    }
+
+    /// The result of every probe, for --capabilities
+    pub fn report(&self) -> Vec<Probe> {
+        let git = match self.git() {
+            Ok(output) => Probe::new("git", self.git_version().is_some(), output.as_str()),
+            Err(e) => Probe::new("git", false, format!("could not run git: {}", e)),
+        };
+        let needs_git = |(major, minor): (u32, u32)| format!("needs git {}.{}", major, minor);
+        let found = |name, command: Option<&str>, missing: &str| match command {
+            Some(command) => Probe::new(name, true, command),
+            None => Probe::new(name, false, missing),
+        };
+        vec![
+            git,
+            Probe::new("range-diff", self.range_diff(), needs_git(RANGE_DIFF_GIT_VERSION)),
+            Probe::new("color-moved", self.color_moved(), needs_git(COLOR_MOVED_GIT_VERSION)),
+            found("clipboard", self.clipboard(), &format!("none of {} found", CLIPBOARD_COMMANDS.join(", "))),
+            match (self.pager(), self.pager_var.as_deref()) {
+                (None, Some(pager)) => Probe::new("pager", false, format!("PAGER is {}, which wasn't found", pager)),
+                (pager, _) => found("pager", pager, &format!("none of {} found", PAGER_COMMANDS.join(", "))),
+            },
+            found("keyring", self.keyring(), &format!("none of {} found", KEYRING_COMMANDS.join(", "))),
+            Probe::new("network", self.network(), if self.network() { "allowed" } else { "forbidden by --offline" }),
+        ]
+    }
+}
+
+/// The outcome of one probe, for --capabilities
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    pub name: &'static str,
+    pub available: bool,
+    pub detail: String,
+}
+
+impl Probe {
+    fn new(name: &'static str, available: bool, detail: impl Into<String>) -> Self {
+        Probe { name, available, detail: detail.into() }
+    }
+}
+
+/// The probes as a table
+pub fn render_probes(probes: &[Probe]) -> String {
+    let name_width = probes.iter().map(|probe| probe.name.len()).max().unwrap_or(0).max("Capability".len());
+    let mut lines = vec![format!("{:name_width$}  {:9}  Details", "Capability", "Available")];
+    for probe in probes {
+        let available = if probe.available { "yes" } else { "no" };
+        lines.push(format!("{:name_width$}  {:9}  {}", probe.name, available, probe.detail));
+    }
+    lines.join("\n")
+}
+
+/// The capabilities of this machine, probed as they're needed
+pub fn capabilities() -> &'static Capabilities {
+    static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+    CAPABILITIES.get_or_init(Capabilities::detect)
+}
+
+/// The output of `git --version`, or why it couldn't be run
+pub fn git_version_output() -> Result<String, String> {
+    let output = Command::new("git").arg("--version").output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `pager`'s command if it's installed, or with no `pager`, `less` or `more`
+pub fn find_pager(pager: Option<&str>, path: &OsStr) -> Option<String> {
+    match pager.and_then(|pager| pager.split_whitespace().next()) {
+        Some(command) if Path::new(command).is_absolute() => {
+            is_executable(Path::new(command)).then(|| command.to_string())
+        }
+        Some(command) => find_on_path(&[command], path).map(str::to_string),
+        None => find_on_path(PAGER_COMMANDS, path).map(str::to_string),
+    }
+}
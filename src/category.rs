@@ -0,0 +1,111 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+
+/// One aspect of code a review can focus on, and the value of `Finding::category` for findings
+/// about it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Category {
+    pub name: String,
+    /// What reviewing this aspect covers, for the prompt
+    pub description: String,
+}
+
+/// The categories every taxonomy starts with
+pub const BUILTIN_CATEGORIES: &[(&str, &str)] = &[
+    ("correctness", "logic errors, edge cases, off-by-one mistakes and wrong results"),
+    ("security", "injection, unsafe input handling, secrets, permissions and unsafe code"),
+    ("performance", "needless allocation or copying, algorithmic complexity and blocking calls"),
+    ("concurrency", "data races, deadlocks, lock ordering, atomics and shared state across threads or tasks"),
+    ("error-handling", "ignored errors, panics and unwraps, error propagation and messages"),
+    ("api-design", "naming, signatures, visibility and how easy the API is to misuse"),
+    ("compatibility", "backward compatibility of public APIs, schemas and wire formats, versioning and the impact on existing clients"),
+    ("readability", "structure, naming, duplication and comments that help or mislead"),
+    ("testing", "missing, weak or brittle tests for the changed behavior"),
+    ("documentation", "doc comments, READMEs and changelogs that are missing or out of date"),
+    ("naming", "misspelled identifiers and user-facing strings, and names cased unlike the code around them"),
+    ("infrastructure", "CI pipelines, container images and build scripts: pinning, secrets, caches, permissions and flaky builds"),
+    ("migrations", "database migrations: destructive operations, long-locking DDL, irreversibility and drift from the schema"),
+];
+
+/// The categories `--category` accepts: the built-in ones plus any from a `--categories-file`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Taxonomy {
+    categories: Vec<Category>,
+}
+
+#[derive(Deserialize)]
+struct CategoriesFile {
+    #[serde(default)]
+    category: Vec<Category>,
+}
+
+impl Default for Taxonomy {
+    fn default() -> Self {
+        Taxonomy {
+            categories: BUILTIN_CATEGORIES
+                .iter()
+                .map(|(name, description)| Category {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Taxonomy {
+    /// Add the `[[category]]` tables of a TOML file, each with a `name` and `description`. A
+    /// category with a built-in name replaces the built-in description.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<(), ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        let file: CategoriesFile = toml::from_str(&contents)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+
+        for category in file.category {
+            match self.categories.iter_mut().find(|c| c.name == category.name) {
+                Some(existing) => *existing = category,
+                None => self.categories.push(category),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.categories.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// Look up each of `names`, failing on the first one the taxonomy doesn't have
+    pub fn select(&self, names: &[String]) -> Result<Vec<Category>, ReviewError> {
+        names
+            .iter()
+            .map(|name| {
+                self.categories
+                    .iter()
+                    .find(|c| &c.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        ReviewError::Config(format!(
+                            "unknown category {:?}; the categories are {}",
+                            name,
+                            self.names().join(", ")
+                        ))
+                    })
+            })
+            .collect()
+    }
+}
+
+/// The system prompt instructions for a review limited to `categories`
+pub fn category_instructions(categories: &[Category]) -> String {
+    let list: Vec<String> = categories
+        .iter()
+        .map(|c| format!("- {}: {}", c.name, c.description))
+        .collect();
+    format!(
+        "\nReview only these aspects of the code, in depth, and leave out findings about anything else:\n{}\nLabel each finding with the name of its category.\n",
+        list.join("\n")
+    )
+}
@@ -0,0 +1,195 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::ReviewError;
+use crate::locale::Locale;
+use crate::progress::format_elapsed;
+use crate::write_atomic::write_atomic;
+
+/// The repository's list of files never to send, relative to its root: a glob per line, as
+/// --exclude takes, with blank lines and `#` comments ignored
+pub const EXCLUDE_FILE: &str = ".llm_code_review/exclude";
+
+/// How often a chunk being reviewed checks whether it was skipped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The lines typed at the terminal, read on a thread of their own so that a chunk's request can
+/// be waited on at the same time. The thread reads until standard input ends.
+#[derive(Debug)]
+pub struct KeyWatcher {
+    lines: Receiver<String>,
+}
+
+impl KeyWatcher {
+    pub fn stdin() -> Self {
+        let (send, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if send.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        KeyWatcher { lines }
+    }
+
+    /// Watch the lines from `lines` instead of the terminal's
+    pub fn new(lines: Receiver<String>) -> Self {
+        KeyWatcher { lines }
+    }
+
+    /// Forget what was typed before now, so a key pressed during one chunk doesn't skip the next
+    pub fn clear(&self) {
+        while self.lines.try_recv().is_ok() {}
+    }
+
+    /// Ask `question` on standard error and wait for the answer; `None` when input has ended
+    pub fn ask(&self, question: &str) -> Option<String> {
+        let mut stderr = io::stderr();
+        let _ = write!(stderr, "{} ", question);
+        let _ = stderr.flush();
+        self.lines.recv().ok()
+    }
+
+    /// Whether a line was typed since the last check, or `None` when input has ended
+    fn pressed(&self, timeout: Duration) -> Option<bool> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(_) => Some(true),
+            Err(RecvTimeoutError::Timeout) => Some(false),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+/// Run a chunk's `request` on a thread of its own, reporting on standard error every `heartbeat`
+/// how long the chunk named `label` has taken. Returns `None` as soon as Enter is pressed: the
+/// request is left behind, and whatever it answers is dropped along with the connection, so
+/// nothing of the chunk is recorded.
+pub fn run_skippable<T: Send + 'static>(
+    keys: &KeyWatcher,
+    label: &str,
+    heartbeat: Option<Duration>,
+    request: impl FnOnce() -> Result<T, ReviewError> + Send + 'static,
+) -> Result<Option<T>, ReviewError> {
+    let started = Instant::now();
+    let (send, result) = mpsc::channel();
+    thread::spawn(move || {
+        // The receiver is gone once the chunk is skipped
+        let _ = send.send(request());
+    });
+
+    keys.clear();
+    let _ = writeln!(io::stderr(), "Reviewing {}; press Enter to skip it", label);
+    let mut reported = started;
+    let mut input_open = true;
+    loop {
+        match result.try_recv() {
+            Ok(result) => return result.map(Some),
+            Err(TryRecvError::Disconnected) => {
+                return Err(ReviewError::Provider(format!("the request for {} stopped without an answer", label)));
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+        if input_open {
+            match keys.pressed(POLL_INTERVAL) {
+                Some(true) => {
+                    info!("Skipped {} after {}", label, format_elapsed(started.elapsed()));
+                    return Ok(None);
+                }
+                Some(false) => {}
+                None => input_open = false,
+            }
+        } else {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if let Some(interval) = heartbeat
+            && reported.elapsed() >= interval
+        {
+            reported = Instant::now();
+            let _ = writeln!(
+                io::stderr(),
+                "{}: {} (press Enter to skip it)",
+                label,
+                format_elapsed(started.elapsed())
+            );
+        }
+    }
+}
+
+/// How a chunk is named while it's reviewed, e.g. `chunk 2/5 (dump.sql)`
+pub fn chunk_label(part: usize, parts: usize, paths: &[String]) -> String {
+    format!("chunk {}/{} ({})", part, parts, paths.join(", "))
+}
+
+/// What's printed in place of the review of chunk `part` of `parts`, covering `paths`, when it
+/// was skipped, in `locale`'s language
+pub fn skipped_note(part: usize, parts: usize, paths: &[String], locale: Locale) -> String {
+    let template = if paths.len() == 1 {
+        "_Chunk {} of {} was skipped, so this file was not reviewed: {}._"
+    } else {
+        "_Chunk {} of {} was skipped, so these files were not reviewed: {}._"
+    };
+    locale.format(template, &[&part.to_string(), &parts.to_string(), &paths.join(", ")])
+}
+
+/// The globs in an exclude file's `contents`
+pub fn parse_excludes(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The globs in the exclude file of the repository at `root`; none when it has no exclude file
+pub fn load_excludes(root: &Path) -> Result<Vec<String>, ReviewError> {
+    let path = root.join(EXCLUDE_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(parse_excludes(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(ReviewError::Io(path, e)),
+    }
+}
+
+/// Add `paths` to the exclude file of the repository at `root`, creating it if need be. Each is
+/// anchored to the root, so only that file is left out and not others of the same name. Returns
+/// the globs added, leaving out those already in the file.
+pub fn add_excludes(root: &Path, paths: &[String]) -> Result<Vec<String>, ReviewError> {
+    let path = root.join(EXCLUDE_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ReviewError::Io(path, e)),
+    };
+    let existing = parse_excludes(&contents);
+    let mut added: Vec<String> = Vec::new();
+    for glob in paths.iter().map(|path| format!("/{}", path.trim_start_matches('/'))) {
+        if !existing.contains(&glob) && !added.contains(&glob) {
+            added.push(glob);
+        }
+    }
+    if added.is_empty() {
+        return Ok(added);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| ReviewError::Write(dir.to_path_buf(), e))?;
+    }
+    let mut contents = contents;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    for glob in &added {
+        contents.push_str(glob);
+        contents.push('\n');
+    }
+    write_atomic(&path, contents)?;
+    Ok(added)
+}
@@ -0,0 +1,109 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::ReviewError;
+use crate::github::{fetch_actions_job_log, parse_actions_job_url};
+use crate::network;
+
+/// How much of the end of each log goes into the prompt, in characters
+pub const CI_LOG_MAX_CHARS: usize = 8_000;
+
+/// Remove what makes CI logs noisy without helping explain a failure: ANSI escape codes, the
+/// timestamp GitHub Actions puts at the start of each line, `##[group]` markers and blank runs.
+pub fn denoise_log(text: &str) -> String {
+    let ansi = Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    let timestamp = Regex::new(r"^\x{feff}?\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(?:\.\d+)?Z ?").unwrap();
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = ansi.replace_all(line, "");
+        let line = timestamp.replace(&line, "");
+        let line = line.trim_end();
+        if line == "##[endgroup]" || (line.is_empty() && lines.last().is_some_and(|l| l.is_empty())) {
+            continue;
+        }
+        let line = if let Some(title) = line.strip_prefix("##[group]") {
+            title.to_string()
+        } else if let Some((kind, message)) = line.strip_prefix("##[").and_then(|rest| rest.split_once(']')) {
+            // `##[error]message` becomes `error: message`
+            format!("{}: {}", kind, message)
+        } else {
+            line.to_string()
+        };
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// The last whole lines of `text` that fit in `max_chars`, with a note saying how many were left
+/// out before them.
+pub fn log_tail(text: &str, max_chars: usize) -> String {
+    if text.len() <= max_chars {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let mut kept = 0;
+    let mut size = 0;
+    for line in lines.iter().rev() {
+        if size + line.len() + 1 > max_chars {
+            break;
+        }
+        size += line.len() + 1;
+        kept += 1;
+    }
+    format!(
+        "[… {} earlier lines left out …]\n{}",
+        lines.len() - kept,
+        lines[lines.len() - kept..].join("\n")
+    )
+}
+
+/// Read a log from a file, a GitHub Actions job URL (through the API) or any other URL
+pub fn load_ci_log(source: &str, timeout: Option<Duration>) -> Result<String, ReviewError> {
+    if let Some((owner, repo, job_id)) = parse_actions_job_url(source) {
+        return fetch_actions_job_log(&owner, &repo, &job_id, timeout);
+    }
+    if source.starts_with("http://") || source.starts_with("https://") {
+        debug!("Fetching {}", source);
+        return network::get(source)?
+            .config()
+            .timeout_global(timeout)
+            .build()
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| ReviewError::GitHub(format!("{}: {}", source, e)));
+    }
+    let path = Path::new(source);
+    fs::read(path)
+        .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+        .map_err(|e| ReviewError::Io(path.to_path_buf(), e))
+}
+
+/// The prompt section for the logs in `sources`, each de-noised and cut to its last `max_chars`
+/// characters. Logs that can't be read are left out with a warning.
+pub fn ci_log_section(sources: &[String], max_chars: usize, timeout: Option<Duration>) -> Option<String> {
+    let logs: Vec<String> = sources
+        .iter()
+        .filter_map(|source| {
+            let log = load_ci_log(source, timeout)
+                .map_err(|e| warn!("Leaving out CI log {}: {}", source, e))
+                .ok()?;
+            Some(format!(
+                "### {}\n```\n{}\n```",
+                source,
+                log_tail(&denoise_log(&log), max_chars)
+            ))
+        })
+        .collect();
+    if logs.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "CI is failing for this change. These are the ends of its logs. For each failure, point to the hunks that most likely caused it, or say if it looks unrelated to the change.\n\n{}",
+        logs.join("\n\n")
+    ))
+}
@@ -0,0 +1,47 @@
+use regex::Regex;
+
+use crate::diff::{FileDiff, HunkRange};
+
+/// A reference to a line of a file, like `src/lib.rs:42` or `file=src/lib.rs,line=42`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub path: String,
+    pub line: usize,
+}
+
+/// Every citation in `text`, in the order they appear
+pub fn parse_citations(text: &str) -> Vec<Citation> {
+    let citation = Regex::new(r"file=([^,\s]+),line=(\d+)|([\w./-]+\.\w+):(\d+)").unwrap();
+
+    citation
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let (path, line) = match (caps.get(1), caps.get(2)) {
+                (Some(path), Some(line)) => (path, line),
+                _ => (caps.get(3)?, caps.get(4)?),
+            };
+            Some(Citation {
+                path: path.as_str().to_string(),
+                line: line.as_str().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Whether the cited line is in one of the hunks of `files`, counting lines in the new version of
+/// the file as hunk headers do. Paths match if either ends with the other, so `lib.rs:3` cites
+/// `src/lib.rs`.
+pub fn is_valid_citation(citation: &Citation, files: &[FileDiff]) -> bool {
+    files
+        .iter()
+        .filter(|file| file.path.ends_with(&citation.path) || citation.path.ends_with(&file.path))
+        .flat_map(|file| &file.hunks)
+        .filter_map(|hunk| HunkRange::parse(&hunk.header))
+        .any(|range| citation.line >= range.new_start && citation.line < range.new_start + range.new_len)
+}
+
+/// Whether a finding cites at least one line, and only lines that are in the diff
+pub fn has_valid_citations(finding: &str, files: &[FileDiff]) -> bool {
+    let citations = parse_citations(finding);
+    !citations.is_empty() && citations.iter().all(|citation| is_valid_citation(citation, files))
+}
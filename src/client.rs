@@ -0,0 +1,318 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde_json::{json, Value};
+use std::time::Duration;
+use ureq::typestate::WithBody;
+use ureq::RequestBuilder;
+
+use crate::budget::{DEFAULT_CHARS_PER_TOKEN, DEFAULT_MAX_TOKENS};
+use crate::error::ReviewError;
+use crate::network;
+use crate::progress::{watch_generation, GenerationLimits};
+use crate::provider::{ProviderConfig, ProviderFormat};
+use crate::truncate::truncate_chars;
+
+pub const ANTHROPIC_ENDPOINT: &str = "https://api.anthropic.com/v1/messages";
+pub const OPENAI_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
+pub const OLLAMA_ENDPOINT: &str = "http://localhost:11434/api/chat";
+
+/// The providers --provider accepts without a --llm-provider-config
+pub const BUILTIN_PROVIDERS: &[&str] = &["anthropic", "openai", "ollama"];
+
+// The Messages API version the request and response are written for
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The model asked for when neither --model nor the provider config names one
+pub fn default_model(format: ProviderFormat) -> Option<&'static str> {
+    match format {
+        ProviderFormat::Anthropic => Some("claude-sonnet-4-5"),
+        ProviderFormat::OpenAi => Some("gpt-5"),
+        ProviderFormat::Ollama => Some("llama3.1"),
+        ProviderFormat::Custom => None,
+    }
+}
+
+/// The settings for one of `BUILTIN_PROVIDERS`, talking to its usual endpoint
+pub fn builtin_provider(name: &str) -> Option<ProviderConfig> {
+    let (endpoint, format) = match name {
+        "anthropic" => (ANTHROPIC_ENDPOINT, ProviderFormat::Anthropic),
+        "openai" => (OPENAI_ENDPOINT, ProviderFormat::OpenAi),
+        "ollama" => (OLLAMA_ENDPOINT, ProviderFormat::Ollama),
+        _ => return None,
+    };
+    Some(ProviderConfig {
+        name: name.to_string(),
+        endpoint: endpoint.to_string(),
+        max_tokens: DEFAULT_MAX_TOKENS,
+        chars_per_token: DEFAULT_CHARS_PER_TOKEN,
+        format,
+        thread_ttl_secs: None,
+        api_key_env: None,
+        small_diff_tokens: None,
+        model: None,
+        tokenizer: None,
+    })
+}
+
+/// The provider --send uses without a --llm-provider-config or --provider: Anthropic when
+/// `ANTHROPIC_API_KEY` is set, otherwise OpenAI when `OPENAI_API_KEY` is. `var` looks up an
+/// environment variable.
+pub fn provider_from_env(var: impl Fn(&str) -> Option<String>) -> Option<ProviderConfig> {
+    if var("ANTHROPIC_API_KEY").is_some() {
+        builtin_provider("anthropic")
+    } else if var("OPENAI_API_KEY").is_some() {
+        builtin_provider("openai")
+    } else {
+        None
+    }
+}
+
+/// Add the headers `format`'s API authenticates with
+pub(crate) fn authorize(request: RequestBuilder<WithBody>, format: ProviderFormat, key: &str) -> RequestBuilder<WithBody> {
+    match format {
+        ProviderFormat::Anthropic => request.header("x-api-key", key).header("anthropic-version", ANTHROPIC_VERSION),
+        ProviderFormat::OpenAi | ProviderFormat::Ollama | ProviderFormat::Custom => {
+            request.header("Authorization", &format!("Bearer {}", key))
+        }
+    }
+}
+
+/// How to reach a provider and what to ask of it, whichever API it has.
+#[derive(Debug, Clone)]
+pub struct RequestSettings {
+    pub endpoint: String,
+    pub model: String,
+    pub key: Option<String>,
+    /// Caps the length of the response
+    pub max_tokens: usize,
+    pub limits: GenerationLimits,
+    /// How long the whole request may take, if there's a limit
+    pub timeout: Option<Duration>,
+    /// For estimating the tokens in a response that doesn't say how many it took
+    pub chars_per_token: usize,
+    /// Fields from --extra-param set in the request body, in place of any it already has
+    pub extra_params: Vec<(String, Value)>,
+}
+
+/// An LLM API that can answer a prompt. Each implementation knows its API's request and response;
+/// `complete` sends the request and reads the response the same way for all of them.
+pub trait Provider {
+    fn format(&self) -> ProviderFormat;
+
+    fn settings(&self) -> &RequestSettings;
+
+    /// The request for an answer to `user`, with `system` as the system prompt
+    fn request_body(&self, system: &str, user: &str) -> Value;
+
+    /// The answer in a response body, and the number of tokens it took when the API says
+    fn parse_response(&self, body: &Value) -> Option<(String, Option<usize>)>;
+
+    /// Send the prompt and return the answer. The heartbeat and first-token limit are the
+    /// settings' `limits`, and the request gives up after their `timeout`.
+    fn complete(&self, system: &str, user: &str) -> Result<String, ReviewError> {
+        let settings = self.settings();
+        let body = with_extra_params(self.request_body(system, user), &settings.extra_params).to_string();
+        watch_generation(settings.limits, |progress| {
+            let mut request = network::post(&settings.endpoint)?
+                .config()
+                .timeout_global(settings.timeout)
+                .timeout_recv_response(progress.first_token_timeout(&settings.limits))
+                .http_status_as_error(false)
+                .build()
+                .header("Content-Type", "application/json")
+                .header("User-Agent", "llm_code_review");
+            if let Some(key) = &settings.key {
+                request = authorize(request, self.format(), key);
+            }
+            info!("Sending the prompt to {} ({}): {} bytes", settings.endpoint, settings.model, body.len());
+            let mut response = request.send(&body).map_err(|e| match e {
+                ureq::Error::Timeout(_) => ReviewError::Provider(format!(
+                    "{} didn't answer in time; allow longer with --timeout-secs",
+                    settings.endpoint
+                )),
+                e => ReviewError::Provider(e.to_string()),
+            })?;
+            let status = response.status().as_u16();
+            let retry_after = response.headers().get("retry-after").and_then(|value| value.to_str().ok()).map(str::to_string);
+            let text = response.body_mut().read_to_string().map_err(|e| ReviewError::Provider(e.to_string()))?;
+            info!(
+                "{} answered HTTP {} in {:.1}s: {} bytes",
+                settings.endpoint,
+                status,
+                progress.elapsed().as_secs_f64(),
+                text.len()
+            );
+            if status >= 400 {
+                // The APIs all answer 404 for a model they don't have, and Anthropic's 529 when
+                // it's overloaded
+                let hint = match status {
+                    401 | 403 => "; check the API key".to_string(),
+                    404 => format!("; check that the model `{}` exists, or pick another with --model", settings.model),
+                    429 | 529 => match retry_after {
+                        Some(secs) => format!("; the provider is limiting requests, try again in {} seconds", secs),
+                        None => "; the provider is limiting requests, try again later".to_string(),
+                    },
+                    _ => String::new(),
+                };
+                return Err(ReviewError::Provider(format!(
+                    "{} answered HTTP {}: {}{}",
+                    settings.endpoint,
+                    status,
+                    error_message(&text),
+                    hint
+                )));
+            }
+            let json: Value = serde_json::from_str(&text)
+                .map_err(|e| ReviewError::Provider(format!("the response isn't JSON ({}): {}", e, excerpt(&text))))?;
+            let (answer, tokens) = self
+                .parse_response(&json)
+                .ok_or_else(|| ReviewError::Provider(format!("the response has no review in it: {}", excerpt(&text))))?;
+            progress.received(tokens.unwrap_or(answer.len() / settings.chars_per_token.max(1)));
+            Ok(answer)
+        })
+    }
+}
+
+/// Anthropic's Messages API
+pub struct Anthropic(pub RequestSettings);
+
+impl Provider for Anthropic {
+    fn format(&self) -> ProviderFormat {
+        ProviderFormat::Anthropic
+    }
+
+    fn settings(&self) -> &RequestSettings {
+        &self.0
+    }
+
+    fn request_body(&self, system: &str, user: &str) -> Value {
+        json!({
+            "model": self.0.model,
+            "max_tokens": self.0.max_tokens,
+            "system": system,
+            "messages": [{"role": "user", "content": user}],
+        })
+    }
+
+    fn parse_response(&self, body: &Value) -> Option<(String, Option<usize>)> {
+        let blocks = body["content"].as_array()?;
+        let text = blocks.iter().filter(|block| block["type"] == "text").filter_map(|block| block["text"].as_str()).collect();
+        Some((text, body.pointer("/usage/output_tokens").and_then(Value::as_u64).map(|tokens| tokens as usize)))
+    }
+}
+
+/// OpenAI's Chat Completions API, which many other providers also offer
+pub struct OpenAi(pub RequestSettings);
+
+impl Provider for OpenAi {
+    fn format(&self) -> ProviderFormat {
+        ProviderFormat::OpenAi
+    }
+
+    fn settings(&self) -> &RequestSettings {
+        &self.0
+    }
+
+    fn request_body(&self, system: &str, user: &str) -> Value {
+        // Reasoning models only take the newer name for the limit
+        json!({
+            "model": self.0.model,
+            "max_completion_tokens": self.0.max_tokens,
+            "messages": chat_messages(system, user),
+        })
+    }
+
+    fn parse_response(&self, body: &Value) -> Option<(String, Option<usize>)> {
+        let text = body.pointer("/choices/0/message/content")?.as_str()?.to_string();
+        Some((text, body.pointer("/usage/completion_tokens").and_then(Value::as_u64).map(|tokens| tokens as usize)))
+    }
+}
+
+/// Ollama's chat API, for models run locally
+pub struct Ollama(pub RequestSettings);
+
+impl Provider for Ollama {
+    fn format(&self) -> ProviderFormat {
+        ProviderFormat::Ollama
+    }
+
+    fn settings(&self) -> &RequestSettings {
+        &self.0
+    }
+
+    fn request_body(&self, system: &str, user: &str) -> Value {
+        json!({
+            "model": self.0.model,
+            "messages": chat_messages(system, user),
+            "stream": false,
+            "options": {"num_predict": self.0.max_tokens},
+        })
+    }
+
+    fn parse_response(&self, body: &Value) -> Option<(String, Option<usize>)> {
+        let text = body.pointer("/message/content")?.as_str()?.to_string();
+        Some((text, body["eval_count"].as_u64().map(|tokens| tokens as usize)))
+    }
+}
+
+/// Parse an --extra-param, `KEY=VALUE` with a JSON value, e.g. `top_p=0.9` or
+/// `response_format={"type":"json_object"}`
+pub fn parse_extra_param(arg: &str) -> Result<(String, Value), String> {
+    let (key, value) = arg.split_once('=').ok_or_else(|| format!("expected KEY=VALUE, got `{}`", arg))?;
+    if key.is_empty() {
+        return Err(format!("no key before the `=` in `{}`", arg));
+    }
+    let value = serde_json::from_str(value).map_err(|e| {
+        format!("the value of {} isn't JSON ({}); put a string in quotes, as in {}='\"{}\"'", key, e, key, value)
+    })?;
+    Ok((key.to_string(), value))
+}
+
+/// The request `body` with the `extra` fields set at its top level, replacing those it has. What
+/// they mean to the API isn't checked.
+pub fn with_extra_params(mut body: Value, extra: &[(String, Value)]) -> Value {
+    if let Some(fields) = body.as_object_mut() {
+        for (key, value) in extra {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+    body
+}
+
+/// A system message, when there's a system prompt, and the user's message
+fn chat_messages(system: &str, user: &str) -> Vec<Value> {
+    let mut messages = Vec::new();
+    if !system.is_empty() {
+        messages.push(json!({"role": "system", "content": system}));
+    }
+    messages.push(json!({"role": "user", "content": user}));
+    messages
+}
+
+/// The implementation for `config`'s API format, sending with `settings`
+pub fn provider_for(config: &ProviderConfig, settings: RequestSettings) -> Result<Box<dyn Provider>, ReviewError> {
+    match config.format {
+        ProviderFormat::Anthropic => Ok(Box::new(Anthropic(settings))),
+        ProviderFormat::OpenAi => Ok(Box::new(OpenAi(settings))),
+        ProviderFormat::Ollama => Ok(Box::new(Ollama(settings))),
+        ProviderFormat::Custom => Err(ReviewError::Config(format!(
+            "provider {:?} has format = \"custom\", which the tool can't send prompts to",
+            config.name
+        ))),
+    }
+}
+
+/// The message from an API error response, or the start of the body if it has none. Anthropic and
+/// OpenAI put it in `error.message`, Ollama in `error`.
+fn error_message(body: &str) -> String {
+    let error = serde_json::from_str::<Value>(body).ok();
+    error
+        .as_ref()
+        .and_then(|error| error.pointer("/error/message").or_else(|| error.get("error")))
+        .and_then(Value::as_str)
+        .map_or_else(|| excerpt(body), str::to_string)
+}
+
+fn excerpt(body: &str) -> String {
+    truncate_chars(body.trim(), 200)
+}
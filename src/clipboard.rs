@@ -0,0 +1,66 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::capabilities::{capabilities, CLIPBOARD_COMMANDS};
+use crate::error::ReviewError;
+
+/// The clipboard commands that need a running X11 or Wayland session
+const DISPLAY_COMMANDS: &[&str] = &["wl-copy", "xclip", "xsel"];
+
+/// The options `program`, one of `CLIPBOARD_COMMANDS`, needs to set the clipboard from standard
+/// input rather than the primary selection
+fn clipboard_args(program: &str) -> &'static [&'static str] {
+    match program {
+        "xclip" => &["-selection", "clipboard"],
+        "xsel" => &["--clipboard", "--input"],
+        _ => &[],
+    }
+}
+
+/// Put `text` on the system clipboard with whichever of `CLIPBOARD_COMMANDS` is installed. Fails
+/// with `ReviewError::Clipboard` when none is, or on Linux when there's no display for it to use,
+/// as on a headless machine.
+pub fn copy(text: &str) -> Result<(), ReviewError> {
+    let command = capabilities().clipboard().ok_or_else(|| {
+        ReviewError::Clipboard(format!("none of {} is installed; use --output-file instead", CLIPBOARD_COMMANDS.join(", ")))
+    })?;
+    let has_display = ["DISPLAY", "WAYLAND_DISPLAY"].iter().any(|var| env::var_os(var).is_some_and(|value| !value.is_empty()));
+    if DISPLAY_COMMANDS.contains(&command) && !has_display {
+        return Err(ReviewError::Clipboard(format!(
+            "{} needs a display, and neither DISPLAY nor WAYLAND_DISPLAY is set; use --output-file instead",
+            command
+        )));
+    }
+    copy_with(command, text)
+}
+
+/// Pipe `text` to `command`, a clipboard command and any options of its own
+pub fn copy_with(command: &str, text: &str) -> Result<(), ReviewError> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or_else(|| ReviewError::Clipboard("the clipboard command is empty".to_string()))?;
+    // xclip and wl-copy stay running to serve the clipboard, holding on to any pipe for their
+    // output, so only standard input is piped
+    let mut child = Command::new(program)
+        .args(words)
+        .args(clipboard_args(program))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| ReviewError::Clipboard(format!("could not run {}: {}", program, e)))?;
+    debug!("Copying {} bytes to the clipboard with {}", text.len(), program);
+    let written = child.stdin.take().map(|mut stdin| stdin.write_all(text.as_bytes()));
+    let status = child.wait().map_err(|e| ReviewError::Clipboard(format!("{} failed: {}", program, e)))?;
+    // A command that fails early closes its input before it's all written, so its exit status
+    // says more than the broken pipe does
+    if !status.success() {
+        return Err(ReviewError::Clipboard(format!("{} exited with {}", program, status)));
+    }
+    if let Some(Err(e)) = written {
+        return Err(ReviewError::Clipboard(format!("could not write to {}: {}", program, e)));
+    }
+    Ok(())
+}
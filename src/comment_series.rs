@@ -0,0 +1,197 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+
+use crate::locale::Locale;
+use crate::runaway::section_boundary;
+
+/// The longest comment GitHub accepts, in characters
+pub const GITHUB_COMMENT_LIMIT: usize = 65_536;
+
+// Room left in each comment for its series marker and `**Review N/M**` header
+const HEADER_RESERVE: usize = 100;
+
+// Room left in a comment cut inside a code block for the fence that closes it, of up to six
+// backticks, and its newlines
+const FENCE_RESERVE: usize = 8;
+
+// The hidden line a comment opens with when it reopens the code block the last one was cut in,
+// so `join_series` knows to take out the fences added at the cut; the second is for a cut made
+// mid-line
+const CONTINUED: &str = "<!-- llm-code-review code block continued -->\n";
+const CONTINUED_MID_LINE: &str = "<!-- llm-code-review code block continued mid-line -->\n";
+
+/// The hidden marker that starts each comment of a posted review, so a later run can find the
+/// comments to update
+fn marker(part: usize, total: usize) -> String {
+    format!("<!-- llm-code-review series {}/{} -->\n", part, total)
+}
+
+/// The header of part `part` of a series of `total` comments, e.g. `**Review 1/3**`
+fn header(part: usize, total: usize, locale: Locale) -> String {
+    format!("**{}**\n\n", locale.format("Review {}/{}", &[&part.to_string(), &total.to_string()]))
+}
+
+fn marker_regex() -> Regex {
+    Regex::new(r"^<!-- llm-code-review series (\d+)/(\d+) -->\n").unwrap()
+}
+
+/// Which part of which length of series `comment` is, if the tool posted it
+pub fn series_part(comment: &str) -> Option<(usize, usize)> {
+    let captures = marker_regex().captures(comment)?;
+    Some((captures[1].parse().ok()?, captures[2].parse().ok()?))
+}
+
+/// `review` as the comments to post it in, none of them longer than `limit` bytes (which is never
+/// fewer than its characters). A review that fits is one comment; a longer one is cut into a
+/// numbered series, "Review 1/3" and so on, at headings or else paragraphs, never inside a code
+/// block unless the block alone is too long for a comment. A block cut that way is closed at the
+/// cut and opened again, with the same info string, in the next comment. Cutting prefers headings
+/// in the second half of each comment, so the summary the review opens with stays whole in the
+/// first. The headers are in `locale`'s language.
+pub fn split_review(review: &str, limit: usize, locale: Locale) -> Vec<String> {
+    let budget = limit.saturating_sub(HEADER_RESERVE).max(1);
+    let mut pieces = Vec::new();
+    let mut rest = review;
+    // The opening fence of the code block `rest` starts inside, and whether it was cut mid-line
+    let mut reopened: Option<(String, bool)> = None;
+    loop {
+        let (continued, reopen) = match &reopened {
+            Some((fence, false)) => (CONTINUED, format!("{}\n", fence)),
+            Some((fence, true)) => (CONTINUED_MID_LINE, format!("{}\n", fence)),
+            None => ("", String::new()),
+        };
+        let text = format!("{}{}", reopen, rest);
+        let room = budget.saturating_sub(continued.len()).max(1);
+        if text.len() <= room {
+            pieces.push(format!("{}{}", continued, text));
+            break;
+        }
+        let mut cut = section_boundary(&text, room);
+        if cut <= reopen.len() {
+            // A code block longer than a comment: cut it at a line, or failing that anywhere
+            let room = text.floor_char_boundary(room.saturating_sub(FENCE_RESERVE));
+            cut = text[..room].rfind('\n').map_or(0, |newline| newline + 1);
+            if cut <= reopen.len() {
+                cut = room.max(reopen.len() + rest.chars().next().map_or(1, char::len_utf8));
+            }
+        }
+        let kept = &text[..cut];
+        let mid_line = !kept.ends_with('\n');
+        let open = open_fence(kept);
+        let close = match open {
+            Some(fence) => format!("{}{}\n", if mid_line { "\n" } else { "" }, fence_marker(fence)),
+            None => String::new(),
+        };
+        pieces.push(format!("{}{}{}", continued, kept, close));
+        reopened = open.map(|fence| (fence.to_string(), mid_line));
+        rest = &rest[cut - reopen.len()..];
+    }
+
+    let total = pieces.len();
+    if total == 1 {
+        return vec![format!("{}{}", marker(1, 1), review)];
+    }
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(i, piece)| format!("{}{}{}", marker(i + 1, total), header(i + 1, total, locale), piece))
+        .collect()
+}
+
+/// The opening fence line of the code block `text` ends inside, if it does
+fn open_fence(text: &str) -> Option<&str> {
+    let mut open: Option<&str> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) else {
+            continue;
+        };
+        open = match open {
+            Some(fence) if trimmed.starts_with(fence_marker(fence)) && trimmed.trim_start_matches(&marker[..1]).is_empty() => None,
+            None => Some(line),
+            open => open,
+        };
+    }
+    open
+}
+
+/// The backticks or tildes that open `fence`, which a fence closing its block repeats
+fn fence_marker(fence: &str) -> &str {
+    let fence = fence.trim_start();
+    let Some(first) = fence.chars().next() else {
+        return fence;
+    };
+    &fence[..fence.len() - fence.trim_start_matches(first).len()]
+}
+
+/// The review a series of comments was split from, given them in order, whatever language their
+/// headers are in
+pub fn join_series(comments: &[String]) -> String {
+    let mut review = String::new();
+    for comment in comments {
+        let body = match (series_part(comment), marker_regex().find(comment)) {
+            (Some((part, total)), Some(marker)) if total > 1 => {
+                let body = &comment[marker.end()..];
+                Locale::ALL
+                    .iter()
+                    .find_map(|locale| body.strip_prefix(&header(part, total, *locale)))
+                    .unwrap_or(body)
+            }
+            (Some(_), Some(marker)) => &comment[marker.end()..],
+            _ => comment.as_str(),
+        };
+        let continued = [(CONTINUED, false), (CONTINUED_MID_LINE, true)]
+            .into_iter()
+            .find_map(|(continued, mid_line)| body.strip_prefix(continued).map(|body| (body, mid_line)));
+        let body = match continued {
+            Some((body, mid_line)) => {
+                // Take out the fence that closed the block at the cut, and the one reopening it
+                let closed = review.strip_suffix('\n').unwrap_or(&review);
+                review.truncate(closed.rfind('\n').map_or(0, |newline| newline + 1));
+                if mid_line {
+                    review.pop();
+                }
+                body.split_once('\n').map_or("", |(_, body)| body)
+            }
+            None => body,
+        };
+        review.push_str(body);
+    }
+    review
+}
+
+/// A change to make to a pull request's comments to post a review
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommentAction {
+    Create { body: String },
+    Update { id: u64, body: String },
+    Delete { id: u64 },
+}
+
+/// What to do to post `comments` over the series already on the pull request, given as
+/// `(id, body)` pairs of all its comments: each part replaces the comment that held the same part
+/// before, new parts are added, and parts left over from a longer review are deleted. Comments
+/// the tool didn't post are left alone, as are parts that haven't changed.
+pub fn plan_comments(existing: &[(u64, String)], comments: &[String]) -> Vec<CommentAction> {
+    let mut posted: Vec<(usize, u64, &str)> = existing
+        .iter()
+        .filter_map(|(id, body)| series_part(body).map(|(part, _)| (part, *id, body.as_str())))
+        .collect();
+    posted.sort_by_key(|&(part, id, _)| (part, id));
+
+    let mut actions = Vec::new();
+    for (i, body) in comments.iter().enumerate() {
+        match posted.iter().position(|&(part, _, _)| part == i + 1) {
+            Some(index) => {
+                let (_, id, old) = posted.remove(index);
+                if old != body {
+                    actions.push(CommentAction::Update { id, body: body.clone() });
+                }
+            }
+            None => actions.push(CommentAction::Create { body: body.clone() }),
+        }
+    }
+    actions.extend(posted.into_iter().map(|(_, id, _)| CommentAction::Delete { id }));
+    actions
+}
@@ -0,0 +1,171 @@
+use crate::diff::{FileDiff, Hunk, HunkRange};
+
+/// The context line standing in for `count` unchanged lines that were left out of a hunk. It starts
+/// with a space so diff parsers read it as a context line; hunk headers still count every line.
+pub fn elision_marker(count: usize) -> String {
+    format!(" … {} unchanged lines …", count)
+}
+
+/// How many lines an elision marker stands in for, or `None` if `line` isn't one
+pub fn elided_line_count(line: &str) -> Option<usize> {
+    line.strip_prefix(" … ")?
+        .strip_suffix(" unchanged lines …")?
+        .parse()
+        .ok()
+}
+
+/// Shrink each file's hunks without losing any changes: hunks that touch or overlap are merged, so
+/// shared context appears once, and runs of more than `max_unchanged_run` unchanged lines are cut
+/// down to their first and last lines with an elision marker between them.
+pub fn compress_diff(files: Vec<FileDiff>, max_unchanged_run: usize) -> Vec<FileDiff> {
+    files
+        .into_iter()
+        .map(|mut file| {
+            file.hunks = merge_adjacent_hunks(file.hunks)
+                .into_iter()
+                .map(|hunk| elide_unchanged_runs(hunk, max_unchanged_run))
+                .collect();
+            file
+        })
+        .collect()
+}
+
+/// Cut each hunk's context down to `context_lines` lines around its changes, as if the diff had
+/// been generated with that much context. Leading and trailing context beyond that is dropped and
+/// the hunk header's ranges adjusted; longer runs of context between changes keep `context_lines`
+/// lines at each end, with an elision marker for the rest. Changed lines are always kept.
+pub fn trim_context(files: Vec<FileDiff>, context_lines: usize) -> Vec<FileDiff> {
+    files
+        .into_iter()
+        .map(|mut file| {
+            file.hunks = file.hunks.into_iter().map(|hunk| trim_hunk(hunk, context_lines)).collect();
+            file
+        })
+        .collect()
+}
+
+fn trim_hunk(hunk: Hunk, context_lines: usize) -> Hunk {
+    let Some(range) = HunkRange::parse(&hunk.header) else {
+        return hunk;
+    };
+    let Some(first) = hunk.lines.iter().position(|line| !is_unchanged(line)) else {
+        return hunk;
+    };
+    let last = hunk.lines.iter().rposition(|line| !is_unchanged(line)).unwrap_or(first);
+
+    let leading = &hunk.lines[..first];
+    let trailing = &hunk.lines[last + 1..];
+    let kept_leading = &leading[leading.len().saturating_sub(context_lines)..];
+    let kept_trailing = &trailing[..trailing.len().min(context_lines)];
+    let dropped_before = line_count(&leading[..leading.len() - kept_leading.len()]);
+    let dropped = dropped_before + line_count(&trailing[kept_trailing.len()..]);
+
+    let mut lines: Vec<String> = kept_leading.to_vec();
+    let mut run: Vec<String> = Vec::new();
+    for line in &hunk.lines[first..=last] {
+        if is_unchanged(line) {
+            run.push(line.clone());
+            continue;
+        }
+        // Eliding a single line would only make the hunk longer
+        if run.len() > 2 * context_lines + 1 {
+            let elided = line_count(&run[context_lines..run.len() - context_lines]);
+            lines.extend_from_slice(&run[..context_lines]);
+            lines.push(elision_marker(elided));
+            lines.extend_from_slice(&run[run.len() - context_lines..]);
+        } else {
+            lines.append(&mut run);
+        }
+        run.clear();
+        lines.push(line.clone());
+    }
+    lines.extend_from_slice(kept_trailing);
+
+    let section = hunk.header.splitn(3, "@@").nth(2).unwrap_or_default();
+    Hunk {
+        header: format!(
+            "@@ -{},{} +{},{} @@{}",
+            range.old_start + dropped_before,
+            range.old_len.saturating_sub(dropped),
+            range.new_start + dropped_before,
+            range.new_len.saturating_sub(dropped),
+            section
+        ),
+        lines,
+    }
+}
+
+/// How many lines of the file `lines` of context stand for, counting what markers elide
+fn line_count(lines: &[String]) -> usize {
+    lines.iter().map(|line| elided_line_count(line).unwrap_or(1)).sum()
+}
+
+fn merge_adjacent_hunks(hunks: Vec<Hunk>) -> Vec<Hunk> {
+    let mut merged: Vec<Hunk> = Vec::new();
+
+    for hunk in hunks {
+        if let Some(previous) = merged.last_mut()
+            && let Some(a) = HunkRange::parse(&previous.header)
+            && let Some(b) = HunkRange::parse(&hunk.header)
+            && b.old_start >= a.old_start
+            && b.old_start <= a.old_start + a.old_len
+        {
+            // The lines both hunks cover must be context, or they can't simply be dropped
+            let overlap = a.old_start + a.old_len - b.old_start;
+            if hunk.lines.iter().take(overlap).all(|line| is_unchanged(line)) {
+                // Keep the function name git puts after the second "@@", if any
+                let section = previous.header.splitn(3, "@@").nth(2).unwrap_or_default().to_string();
+                previous.header = format!(
+                    "@@ -{},{} +{},{} @@{}",
+                    a.old_start,
+                    b.old_start + b.old_len - a.old_start,
+                    a.new_start,
+                    b.new_start + b.new_len - a.new_start,
+                    section
+                );
+                previous.lines.extend(hunk.lines.into_iter().skip(overlap));
+                continue;
+            }
+        }
+        merged.push(hunk);
+    }
+
+    merged
+}
+
+fn elide_unchanged_runs(hunk: Hunk, max_unchanged_run: usize) -> Hunk {
+    let mut lines = Vec::new();
+    let mut run = Vec::new();
+
+    for line in hunk.lines {
+        if is_unchanged(&line) {
+            run.push(line);
+        } else {
+            push_run(&mut lines, &mut run, max_unchanged_run);
+            lines.push(line);
+        }
+    }
+    push_run(&mut lines, &mut run, max_unchanged_run);
+
+    Hunk {
+        header: hunk.header,
+        lines,
+    }
+}
+
+fn push_run(lines: &mut Vec<String>, run: &mut Vec<String>, max_unchanged_run: usize) {
+    if run.len() > max_unchanged_run {
+        let last = run.pop().unwrap_or_default();
+        lines.push(run.remove(0));
+        lines.push(elision_marker(run.len()));
+        lines.push(last);
+        run.clear();
+    } else {
+        lines.append(run);
+    }
+}
+
+/// Context lines start with a space, which some tools strip from otherwise empty lines
+fn is_unchanged(line: &str) -> bool {
+    line.is_empty() || line.starts_with(' ')
+}
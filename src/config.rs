@@ -0,0 +1,228 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::client::BUILTIN_PROVIDERS;
+use crate::error::ReviewError;
+use crate::infra::INFRA_CHECKS;
+use crate::locale::REVIEW_LANGUAGES;
+use crate::output::OUTPUT_FORMATS;
+use crate::quality::QualityThresholds;
+
+/// The repository's config file, looked for in the current directory and its parents
+pub const REPO_CONFIG_FILE: &str = ".llm_code_review.toml";
+
+/// Defaults for the command line's options, from a config file. Options given on the command line
+/// take their place.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// A file holding the system prompt, relative to the config file's directory. `load` reads
+    /// it into `system_prompt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_file: Option<PathBuf>,
+    /// A prompt template, as --prompt-template, relative to the config file's directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_template: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unified_context: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_dir: Option<PathBuf>,
+    /// A soft limit on a calendar month's estimated spend, in US dollars
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_budget_usd: Option<f64>,
+    /// Globs of more files to review as CI and build configuration, as --infra-files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infra_files: Option<Vec<String>>,
+    /// Local checks of CI and build configuration not to run, as --skip-infra-check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_infra_checks: Option<Vec<String>>,
+    /// Globs of more files to review as database migrations, as --migration-files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migration_files: Option<Vec<String>>,
+    /// The most findings a review shows for one file before the rest are set apart, as
+    /// --max-findings-per-file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_findings_per_file: Option<usize>,
+    /// The most findings a review shows in all, as --max-findings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_findings: Option<usize>,
+    /// The language of the tool's own text, and of the review, as --review-language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_language: Option<String>,
+    /// The model to re-run a review that fails the quality checks with, as --fallback-model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_model: Option<String>,
+    /// The most one run may spend, in US dollars, as --max-cost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost_usd: Option<f64>,
+    /// What a review must have for --fallback-model not to re-run it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityThresholds>,
+    /// The files the settings were read from, in priority order
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Read a config file's TOML, checking its values as the command line would
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(contents).map_err(|e| e.to_string())?;
+        if config.system_prompt.is_some() && config.system_prompt_file.is_some() {
+            return Err("give system_prompt or system_prompt_file, not both".to_string());
+        }
+        if let Some(format) = &config.output_format
+            && !OUTPUT_FORMATS.contains(&format.as_str())
+        {
+            return Err(format!("output_format `{}` is not one of {}", format, OUTPUT_FORMATS.join(", ")));
+        }
+        if config.max_tokens == Some(0) {
+            return Err("max_tokens must be at least 1".to_string());
+        }
+        if config.monthly_budget_usd.is_some_and(|budget| budget <= 0.0 || budget.is_nan()) {
+            return Err("monthly_budget_usd must be more than 0".to_string());
+        }
+        if config.max_cost_usd.is_some_and(|cost| cost <= 0.0 || cost.is_nan()) {
+            return Err("max_cost_usd must be more than 0".to_string());
+        }
+        if let Some(quality) = &config.quality {
+            quality.validate()?;
+        }
+        if config.max_findings_per_file == Some(0) {
+            return Err("max_findings_per_file must be at least 1".to_string());
+        }
+        if config.max_findings == Some(0) {
+            return Err("max_findings must be at least 1".to_string());
+        }
+        if let Some(language) = &config.review_language
+            && !REVIEW_LANGUAGES.contains(&language.as_str())
+        {
+            return Err(format!("review_language `{}` is not one of {}", language, REVIEW_LANGUAGES.join(", ")));
+        }
+        if let Some(check) = config.skip_infra_checks.iter().flatten().find(|check| !INFRA_CHECKS.contains(&check.as_str())) {
+            return Err(format!("skip_infra_checks: `{}` is not one of {}", check, INFRA_CHECKS.join(", ")));
+        }
+        if let Some(provider) = &config.provider
+            && !BUILTIN_PROVIDERS.contains(&provider.as_str())
+        {
+            return Err(format!("provider `{}` is not one of {}", provider, BUILTIN_PROVIDERS.join(", ")));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        let mut config = Config::parse(&contents).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+        if let Some(file) = &config.system_prompt_file {
+            let file = path.parent().unwrap_or(Path::new("")).join(file);
+            config.system_prompt = Some(fs::read_to_string(&file).map_err(|e| ReviewError::Io(file.clone(), e))?);
+            config.system_prompt_file = Some(file);
+        }
+        if let Some(file) = &config.prompt_template {
+            config.prompt_template = Some(path.parent().unwrap_or(Path::new("")).join(file));
+        }
+        Ok(Config { sources: vec![path.to_path_buf()], ..config })
+    }
+
+    /// These settings, with the ones left out taken from `fallback`
+    pub fn or(self, fallback: Config) -> Config {
+        Config {
+            context: self.context.or(fallback.context),
+            // The file goes with the prompt read from it
+            system_prompt_file: if self.system_prompt.is_some() { self.system_prompt_file } else { fallback.system_prompt_file },
+            system_prompt: self.system_prompt.or(fallback.system_prompt),
+            prompt_template: self.prompt_template.or(fallback.prompt_template),
+            output_format: self.output_format.or(fallback.output_format),
+            unified_context: self.unified_context.or(fallback.unified_context),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            provider: self.provider.or(fallback.provider),
+            model: self.model.or(fallback.model),
+            api_key_env: self.api_key_env.or(fallback.api_key_env),
+            history_dir: self.history_dir.or(fallback.history_dir),
+            monthly_budget_usd: self.monthly_budget_usd.or(fallback.monthly_budget_usd),
+            infra_files: self.infra_files.or(fallback.infra_files),
+            skip_infra_checks: self.skip_infra_checks.or(fallback.skip_infra_checks),
+            migration_files: self.migration_files.or(fallback.migration_files),
+            max_findings_per_file: self.max_findings_per_file.or(fallback.max_findings_per_file),
+            max_findings: self.max_findings.or(fallback.max_findings),
+            review_language: self.review_language.or(fallback.review_language),
+            fallback_model: self.fallback_model.or(fallback.fallback_model),
+            max_cost_usd: self.max_cost_usd.or(fallback.max_cost_usd),
+            quality: match (self.quality, fallback.quality) {
+                (Some(quality), Some(fallback)) => Some(quality.or(fallback)),
+                (quality, fallback) => quality.or(fallback),
+            },
+            sources: [self.sources, fallback.sources].concat(),
+        }
+    }
+
+    /// The settings as TOML, after a comment naming the files they were read from
+    pub fn describe(&self) -> String {
+        let sources = match self.sources.as_slice() {
+            [] => "# No config file found".to_string(),
+            sources => format!(
+                "# Read from {}",
+                sources.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+            ),
+        };
+        format!("{}\n{}", sources, toml::to_string(self).unwrap_or_default())
+    }
+}
+
+/// The user's directory for the tool, `llm_code_review` under `$XDG_CONFIG_HOME` or `~/.config`,
+/// whether or not it exists
+pub fn user_config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    config_home.map(|dir| dir.join("llm_code_review"))
+}
+
+/// The user's config file, `config.toml` in `user_config_dir`, whether or not it exists
+pub fn user_config_path() -> Option<PathBuf> {
+    user_config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// The config files that apply in `dir`, in priority order: the nearest `.llm_code_review.toml`
+/// in it or its parents, then the user's (see `user_config_path`)
+pub fn config_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> =
+        dir.ancestors().map(|dir| dir.join(REPO_CONFIG_FILE)).find(|path| path.is_file()).into_iter().collect();
+    if let Some(path) = user_config_path()
+        && path.is_file()
+        && !paths.contains(&path)
+    {
+        paths.push(path);
+    }
+    paths
+}
+
+/// The settings from the config files that apply in the current directory, merged so the
+/// repository's take the place of the user's
+pub fn load_config() -> Result<Config, ReviewError> {
+    let dir = env::current_dir().unwrap_or_default();
+    let mut config = Config::default();
+    for path in config_paths(&dir) {
+        debug!("Reading config from {}", path.display());
+        config = config.or(Config::load(&path)?);
+    }
+    Ok(config)
+}
@@ -0,0 +1,29 @@
+use std::collections::HashSet;
+
+/// Drop context entries that repeat an earlier one, keeping the first. Entries are compared after
+/// trimming; with `fuzzy`, every run of whitespace also counts as a single space.
+pub fn dedup_context(entries: Vec<String>, fuzzy: bool) -> Vec<String> {
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .filter(|entry| seen.insert(dedup_key(entry, fuzzy)))
+        .collect()
+}
+
+/// `dedup_context` for prompt sections: a section is dropped if its body repeats an earlier
+/// section's, whatever their headings.
+pub fn dedup_sections(sections: Vec<(&str, String)>, fuzzy: bool) -> Vec<(&str, String)> {
+    let mut seen = HashSet::new();
+    sections
+        .into_iter()
+        .filter(|(_, body)| seen.insert(dedup_key(body, fuzzy)))
+        .collect()
+}
+
+fn dedup_key(entry: &str, fuzzy: bool) -> String {
+    if fuzzy {
+        entry.split_whitespace().collect::<Vec<&str>>().join(" ")
+    } else {
+        entry.trim().to_string()
+    }
+}
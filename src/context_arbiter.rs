@@ -0,0 +1,177 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+use crate::budget::TokenEstimator;
+use crate::deadline::Deadline;
+
+/// An optional context section the arbiter may leave out of the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextFeature {
+    /// The name --context-priority knows it by
+    pub name: &'static str,
+    /// The heading of its section in the prompt
+    pub heading: &'static str,
+}
+
+/// The optional context features, in the order their sections appear in the prompt
+pub const CONTEXT_FEATURES: &[ContextFeature] = &[
+    ContextFeature { name: "change-summary", heading: "Change Summary" },
+    ContextFeature { name: "packages", heading: "Packages" },
+    ContextFeature { name: "previous-review", heading: "Changes Since the Previous Review" },
+    ContextFeature { name: "linked-issues", heading: "Linked Issues" },
+    ContextFeature { name: "ci-logs", heading: "CI Failures" },
+    ContextFeature { name: "lint", heading: "Existing Linter Findings" },
+    ContextFeature { name: "related-tests", heading: "Related Tests" },
+    ContextFeature { name: "contracts", heading: "Public Contracts" },
+    ContextFeature { name: "infrastructure", heading: "Infrastructure Changes" },
+    ContextFeature { name: "migrations", heading: "Database Migrations" },
+    ContextFeature { name: "naming", heading: "Naming Candidates" },
+    ContextFeature { name: "history", heading: "Previous Reviews of These Files" },
+    ContextFeature { name: "full-files", heading: "Full File Contents" },
+];
+
+/// The order features get the budget in when --context-priority doesn't say: what explains the
+/// change first, then what helps check it, then what only summarizes it
+pub const DEFAULT_CONTEXT_PRIORITY: &[&str] = &[
+    "ci-logs",
+    "previous-review",
+    "linked-issues",
+    "contracts",
+    "infrastructure",
+    "migrations",
+    "naming",
+    "lint",
+    "related-tests",
+    "history",
+    "change-summary",
+    "packages",
+    "full-files",
+];
+
+/// The features in the order they get the budget: those in `priority` first, as given, then the
+/// rest in the default order. Unknown names are an error.
+pub fn context_priority(priority: &[String]) -> Result<Vec<ContextFeature>, String> {
+    let mut features = Vec::new();
+    for name in priority.iter().map(String::as_str).chain(DEFAULT_CONTEXT_PRIORITY.iter().copied()) {
+        let feature = CONTEXT_FEATURES.iter().find(|feature| feature.name == name).ok_or_else(|| {
+            let names: Vec<&str> = CONTEXT_FEATURES.iter().map(|feature| feature.name).collect();
+            format!("unknown context feature `{}`; expected one of {}", name, names.join(", "))
+        })?;
+        if !features.contains(feature) {
+            features.push(*feature);
+        }
+    }
+    Ok(features)
+}
+
+/// What the arbiter decided about a feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    Included,
+    /// Turned off, or nothing to add for this diff
+    Empty,
+    /// Its section didn't fit in the tokens left, or none were left to build it
+    OverBudget,
+    /// There wasn't time left under --deadline-secs to build it
+    OutOfTime,
+}
+
+/// The arbiter's decision about one feature, for the token budget report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureDecision {
+    pub feature: String,
+    pub decision: Decision,
+    /// The tokens its section took, or would have taken, if it was built
+    pub tokens: Option<usize>,
+}
+
+impl fmt::Display for FeatureDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.decision, self.tokens) {
+            (Decision::Included, Some(tokens)) => write!(f, "included {} ({} tokens)", self.feature, tokens),
+            (Decision::OverBudget, Some(tokens)) => {
+                write!(f, "skipped {}: its {} tokens don't fit in what's left", self.feature, tokens)
+            }
+            (Decision::OverBudget, None) => write!(f, "skipped {}: no tokens left", self.feature),
+            (Decision::OutOfTime, _) => write!(f, "skipped {}: no time left", self.feature),
+            _ => write!(f, "skipped {}: nothing to add", self.feature),
+        }
+    }
+}
+
+/// Hands out the tokens left once the diff is placed to the optional context features, offered
+/// in priority order, until they run out. A feature is only built if there are tokens left and
+/// the deadline allows its expected latency, so skipped features cost no git or network calls.
+pub struct ContextArbiter<'a> {
+    remaining: Option<usize>,
+    deadline: &'a Deadline,
+    estimator: &'a dyn TokenEstimator,
+    decisions: Vec<FeatureDecision>,
+}
+
+impl<'a> ContextArbiter<'a> {
+    /// An arbiter with `remaining` tokens to hand out, or no limit but the deadline if `None`
+    pub fn new(remaining: Option<usize>, deadline: &'a Deadline, estimator: &'a dyn TokenEstimator) -> Self {
+        ContextArbiter {
+            remaining,
+            deadline,
+            estimator,
+            decisions: Vec::new(),
+        }
+    }
+
+    /// The tokens left to hand out, or `None` if only the deadline limits the sections
+    pub fn remaining(&self) -> Option<usize> {
+        self.remaining
+    }
+
+    /// The section for `feature` if `build` makes one and it fits, taking its tokens from the
+    /// budget. `latency` is how long building it may take.
+    pub fn offer(
+        &mut self,
+        feature: ContextFeature,
+        latency: Duration,
+        build: impl FnOnce() -> Option<String>,
+    ) -> Option<String> {
+        let (decision, tokens, section) = if self.remaining == Some(0) {
+            (Decision::OverBudget, None, None)
+        } else if !latency.is_zero() && !self.deadline.allows(&format!("the {} section", feature.name), latency) {
+            (Decision::OutOfTime, None, None)
+        } else {
+            match build() {
+                None => (Decision::Empty, None, None),
+                Some(section) => {
+                    let tokens = self.estimator.estimate_tokens(&format!("## {}\n{}\n\n", feature.heading, section));
+                    match &mut self.remaining {
+                        Some(remaining) if tokens > *remaining => (Decision::OverBudget, Some(tokens), None),
+                        Some(remaining) => {
+                            *remaining -= tokens;
+                            (Decision::Included, Some(tokens), Some(section))
+                        }
+                        None => (Decision::Included, Some(tokens), Some(section)),
+                    }
+                }
+            }
+        };
+        let decision = FeatureDecision {
+            feature: feature.name.to_string(),
+            decision,
+            tokens,
+        };
+        match decision.decision {
+            Decision::Empty => debug!("Context: {}", decision),
+            _ => info!("Context: {}", decision),
+        }
+        self.decisions.push(decision);
+        section
+    }
+
+    /// The decisions taken, in the order the features were offered
+    pub fn into_decisions(self) -> Vec<FeatureDecision> {
+        self.decisions
+    }
+}
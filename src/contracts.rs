@@ -0,0 +1,193 @@
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::diff::FileDiff;
+use crate::diff_filter::changes_public_item;
+
+/// The category findings about contract changes are labeled with
+pub const COMPATIBILITY_CATEGORY: &str = "compatibility";
+
+/// A kind of file that defines a contract other code depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractKind {
+    OpenApi,
+    Protobuf,
+    GraphQl,
+    /// A Rust library crate's `pub` items
+    RustPublicApi,
+    /// A file matching a --contract-files glob
+    Configured,
+}
+
+impl fmt::Display for ContractKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ContractKind::OpenApi => "OpenAPI spec",
+            ContractKind::Protobuf => "protobuf schema",
+            ContractKind::GraphQl => "GraphQL schema",
+            ContractKind::RustPublicApi => "public Rust API",
+            ContractKind::Configured => "contract",
+        })
+    }
+}
+
+/// What kind of contract the changed file `file` is, if any. OpenAPI specs are found by name
+/// (`openapi.yaml`, `swagger.json`, `api/openapi/v2.yml` and so on), protobuf and GraphQL by
+/// extension, and Rust by a `pub` item changed in a library crate, one with a `src/lib.rs` next to
+/// the file's `src` directory under `root`. `configured` are --contract-files globs, as regexes.
+pub fn contract_kind(file: &FileDiff, root: &Path, configured: &[Regex]) -> Option<ContractKind> {
+    let path = file.path.as_str();
+    if configured.iter().any(|glob| glob.is_match(path)) {
+        return Some(ContractKind::Configured);
+    }
+    let lower = path.to_lowercase();
+    let extension = Path::new(&lower).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension {
+        "proto" => Some(ContractKind::Protobuf),
+        "graphql" | "graphqls" | "gql" => Some(ContractKind::GraphQl),
+        "yaml" | "yml" | "json" if lower.contains("openapi") || lower.contains("swagger") => Some(ContractKind::OpenApi),
+        "rs" if in_library_crate(root, path) && file.hunks.iter().any(changes_public_item) => Some(ContractKind::RustPublicApi),
+        _ => None,
+    }
+}
+
+/// Whether the Rust file at `path` is part of a library crate's API: under `src/` in a crate with
+/// a `src/lib.rs`, and not a binary
+fn in_library_crate(root: &Path, path: &str) -> bool {
+    let (crate_dir, in_src) = match path.rfind("src/") {
+        Some(0) => ("", &path[4..]),
+        Some(start) if path[..start].ends_with('/') => (&path[..start], &path[start + 4..]),
+        _ => return false,
+    };
+    in_src != "main.rs" && !in_src.starts_with("bin/") && root.join(crate_dir).join("src/lib.rs").is_file()
+}
+
+/// The changed files in `files`, in the repository at `root`, that define contracts, with their
+/// kinds
+pub fn find_contracts<'a>(files: &'a [FileDiff], root: &Path, configured: &[Regex]) -> Vec<(&'a str, ContractKind)> {
+    files
+        .iter()
+        .filter_map(|file| contract_kind(file, root, configured).map(|kind| (file.path.as_str(), kind)))
+        .collect()
+}
+
+/// A protobuf field as declared on a line of a `.proto` file
+#[derive(Debug, Clone, PartialEq)]
+struct ProtoField {
+    type_name: String,
+    name: String,
+    number: u32,
+}
+
+fn field_regex() -> Regex {
+    Regex::new(r"^\s*(?:optional\s+|required\s+|repeated\s+)?((?:map\s*<[^>]*>)|[\w.]+)\s+(\w+)\s*=\s*(\d+)\s*[;\[]").unwrap()
+}
+
+fn parse_field(line: &str, field: &Regex) -> Option<ProtoField> {
+    let captures = field.captures(line)?;
+    if ["option", "reserved", "extensions"].contains(&&captures[1]) {
+        return None;
+    }
+    Some(ProtoField {
+        type_name: captures[1].split_whitespace().collect(),
+        name: captures[2].to_string(),
+        number: captures[3].parse().ok()?,
+    })
+}
+
+/// The field number ranges and names in `reserved` statements on `lines`
+fn reservations<'a>(lines: impl Iterator<Item = &'a str>) -> (Vec<RangeInclusive<u32>>, BTreeSet<String>) {
+    let statement = Regex::new(r"^\s*reserved\s+([^;]*);").unwrap();
+    let (mut numbers, mut names) = (Vec::new(), BTreeSet::new());
+    for captures in lines.filter_map(|line| statement.captures(line)) {
+        for item in captures[1].split(',').map(str::trim) {
+            if let Some(name) = item.strip_prefix('"').and_then(|item| item.strip_suffix('"')) {
+                names.insert(name.to_string());
+            } else if let Some((low, high)) = item.split_once(" to ") {
+                let high = if high.trim() == "max" { Ok(u32::MAX) } else { high.trim().parse() };
+                if let (Ok(low), Ok(high)) = (low.trim().parse(), high) {
+                    numbers.push(low..=high);
+                }
+            } else if let Ok(number) = item.parse() {
+                numbers.push(number..=number);
+            }
+        }
+    }
+    (numbers, names)
+}
+
+/// Wire-incompatible changes to the fields of a `.proto` file, found without the model: fields
+/// removed without reserving their number, renumbered, given another type, or whose number now
+/// belongs to a different field. Fields are matched by name across the whole diff of the file, so
+/// a field moved between messages of the same name may be missed.
+pub fn proto_field_changes(file: &FileDiff) -> Vec<String> {
+    let field = field_regex();
+    let lines = || file.hunks.iter().flat_map(|hunk| hunk.lines.iter());
+    let removed: BTreeMap<String, ProtoField> = lines()
+        .filter_map(|line| line.strip_prefix('-'))
+        .filter_map(|line| parse_field(line, &field))
+        .map(|field| (field.name.clone(), field))
+        .collect();
+    let added: BTreeMap<String, ProtoField> = lines()
+        .filter_map(|line| line.strip_prefix('+'))
+        .filter_map(|line| parse_field(line, &field))
+        .map(|field| (field.name.clone(), field))
+        .collect();
+    // A reservation in the new file, whether added or already there
+    let (reserved_numbers, reserved_names) = reservations(lines().filter(|line| !line.starts_with('-')).filter_map(|line| line.get(1..)));
+
+    let mut changes = Vec::new();
+    for old in removed.values() {
+        match added.get(&old.name) {
+            Some(new) if new.number != old.number => changes.push(format!(
+                "{}: field `{}` renumbered from {} to {}",
+                file.path, old.name, old.number, new.number
+            )),
+            Some(new) if new.type_name != old.type_name => changes.push(format!(
+                "{}: field `{}` ({}) changed type from {} to {}",
+                file.path, old.name, old.number, old.type_name, new.type_name
+            )),
+            Some(_) => {}
+            None => match added.values().find(|new| new.number == old.number) {
+                Some(new) => changes.push(format!(
+                    "{}: field number {} reused: `{}` is now `{}`",
+                    file.path, old.number, old.name, new.name
+                )),
+                None if !reserved_numbers.iter().any(|range| range.contains(&old.number)) => changes.push(format!(
+                    "{}: field `{}` ({}) removed without reserving its number{}",
+                    file.path,
+                    old.name,
+                    old.number,
+                    if reserved_names.contains(&old.name) { "" } else { " or name" }
+                )),
+                None => {}
+            },
+        }
+    }
+    changes
+}
+
+/// The prompt section asking for a compatibility review of `contracts`, with the protobuf
+/// `problems` the tool found itself
+pub fn compatibility_section(contracts: &[(&str, ContractKind)], problems: &[String]) -> String {
+    let list: Vec<String> = contracts.iter().map(|(path, kind)| format!("- {} ({})", path, kind)).collect();
+    let mut section = format!(
+        "The diff changes files that define public contracts, which code outside this change may depend on:\n{}\n\n\
+         For each, review backward compatibility: whether existing clients keep working (removed or renamed fields, \
+         endpoints or items; changed types; new required fields or parameters; changed defaults or errors), whether the \
+         change needs a new version or a deprecation period, and which clients are affected and how they would migrate. \
+         Label these findings with the category `{}`.",
+        list.join("\n"),
+        COMPATIBILITY_CATEGORY
+    );
+    if !problems.is_empty() {
+        section.push_str(&format!(
+            "\n\nA local check of the protobuf schemas found these wire-incompatible changes:\n{}",
+            problems.iter().map(|problem| format!("- {}", problem)).collect::<Vec<_>>().join("\n")
+        ));
+    }
+    section
+}
@@ -0,0 +1,508 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ReviewError;
+use crate::feedback::{History, HistoryEntry, UNKNOWN};
+use crate::findings::{Finding, Severity};
+use crate::transcript::Transcript;
+use crate::write_atomic::write_atomic;
+
+/// Where the reviews --send got are kept, as transcripts, in the history directory. It's a
+/// directory, so `load_reviews` passes over it.
+pub const REVIEWS_DIR: &str = "reviews";
+
+/// How many runs the dashboard lists on a page, unless told otherwise
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+
+/// Where the run pages go, in the dashboard's directory
+const RUNS_DIR: &str = "runs";
+
+const STYLE: &str = "\
+body { font-family: -apple-system, BlinkMacSystemFont, \"Segoe UI\", Helvetica, Arial, sans-serif; \
+margin: 2em auto; max-width: 70em; padding: 0 1em; color: #1f2328; line-height: 1.5; }
+a { color: #0969da; }
+table { border-collapse: collapse; width: 100%; margin: 1em 0; }
+th, td { border: 1px solid #d0d7de; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+td.count { text-align: right; }
+pre { background: #f6f8fa; padding: 0.8em; overflow-x: auto; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.9em; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; }
+dt { font-weight: bold; }
+dd { margin: 0; }
+nav { margin: 1em 0; }
+.verdict-changes-needed, .severity-error { color: #cf222e; }
+.verdict-comments, .severity-warning { color: #9a6700; }
+.verdict-clean { color: #1a7f37; }
+";
+
+/// What a run's review came to, going by its findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// At least one finding is an error
+    ChangesNeeded,
+    /// There are findings, but no errors
+    Comments,
+    /// The review found nothing
+    Clean,
+    /// The review wasn't in JSON, so there are no findings to go by
+    Unstructured,
+    /// No review was saved: the prompt wasn't sent with --send
+    NotSent,
+}
+
+impl Verdict {
+    fn of(transcript: Option<&Transcript>) -> Self {
+        match transcript.map(|transcript| transcript.findings.as_deref()) {
+            None => Verdict::NotSent,
+            Some(None) => Verdict::Unstructured,
+            Some(Some([])) => Verdict::Clean,
+            Some(Some(findings)) if findings.iter().any(|finding| finding.severity == Severity::Error) => {
+                Verdict::ChangesNeeded
+            }
+            Some(Some(_)) => Verdict::Comments,
+        }
+    }
+
+    fn class(&self) -> String {
+        format!("verdict-{}", self.to_string().replace(' ', "-"))
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::ChangesNeeded => write!(f, "changes needed"),
+            Verdict::Comments => write!(f, "comments"),
+            Verdict::Clean => write!(f, "clean"),
+            Verdict::Unstructured => write!(f, "reviewed"),
+            Verdict::NotSent => write!(f, "not sent"),
+        }
+    }
+}
+
+/// One review recorded in the history, with the review itself when it was saved.
+#[derive(Debug, Clone)]
+pub struct Run {
+    /// The name of the run's page, unique among the runs
+    pub id: String,
+    pub entry: HistoryEntry,
+    pub transcript: Option<Transcript>,
+}
+
+impl Run {
+    pub fn verdict(&self) -> Verdict {
+        Verdict::of(self.transcript.as_ref())
+    }
+
+    fn findings(&self) -> &[Finding] {
+        self.transcript.as_ref().and_then(|transcript| transcript.findings.as_deref()).unwrap_or_default()
+    }
+
+    /// How many findings there are of `severity`
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings().iter().filter(|finding| finding.severity == severity).count()
+    }
+
+    /// The model that wrote the review, or the provider the prompt was for
+    fn model(&self) -> &str {
+        self.transcript
+            .as_ref()
+            .and_then(|transcript| transcript.model.as_deref())
+            .or(self.entry.model.as_deref())
+            .unwrap_or(UNKNOWN)
+    }
+}
+
+/// The name a run's saved review and its dashboard page are given: its time, in digits, and the
+/// start of the diff's SHA-256
+pub fn run_id(entry: &HistoryEntry) -> String {
+    let time: String = entry.timestamp.chars().filter(char::is_ascii_alphanumeric).collect();
+    match &entry.diff_sha256 {
+        Some(sha) => format!("{}-{}", time, &sha[..sha.len().min(8)]),
+        None => time,
+    }
+}
+
+/// The runs recorded in the history directory `dir`, newest first, with the reviews saved in its
+/// `reviews` directory. A saved review that can't be read is left out with a warning.
+pub fn load_runs(dir: &Path) -> Result<Vec<Run>, ReviewError> {
+    let history = History::load(dir)?;
+    let mut ids = BTreeSet::new();
+    let mut runs: Vec<Run> = history
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let transcript = entry.review_file.as_ref().and_then(|file| {
+                let path = dir.join(REVIEWS_DIR).join(file);
+                Transcript::load(&path).map_err(|e| warn!("Leaving out the saved review: {}", e)).ok()
+            });
+            let base = match &entry.review_file {
+                Some(file) => file.strip_suffix(".json").unwrap_or(file).to_string(),
+                None => run_id(&entry),
+            };
+            let base = if base.is_empty() { "run".to_string() } else { base };
+            let mut id = base.clone();
+            let mut n = 1;
+            while !ids.insert(id.clone()) {
+                n += 1;
+                id = format!("{}-{}", base, n);
+            }
+            Run { id, entry, transcript }
+        })
+        .collect();
+    runs.reverse();
+    Ok(runs)
+}
+
+/// The dashboard's pages for `runs`, newest first, by their paths in its directory: `index.html`
+/// and `page-2.html` on listing `page_size` runs each, and a page per run in `runs`. The same runs
+/// always give the same pages.
+pub fn render_dashboard(title: &str, runs: &[Run], page_size: usize) -> BTreeMap<PathBuf, String> {
+    let page_size = page_size.max(1);
+    let pages = runs.len().div_ceil(page_size).max(1);
+    let mut files = BTreeMap::new();
+    for page in 1..=pages {
+        let listed = runs.iter().skip((page - 1) * page_size).take(page_size);
+        files.insert(PathBuf::from(page_file(page)), index_page(title, listed, runs.len(), page, pages));
+    }
+    for (i, run) in runs.iter().enumerate() {
+        let path = Path::new(RUNS_DIR).join(format!("{}.html", run.id));
+        files.insert(path, run_page(title, run, i / page_size + 1));
+    }
+    files
+}
+
+/// Write the dashboard's `files` into `dir`, removing the listing and run pages an earlier
+/// dashboard had there that this one doesn't. Nothing else in `dir` is touched.
+pub fn write_dashboard(dir: &Path, files: &BTreeMap<PathBuf, String>) -> Result<(), ReviewError> {
+    let runs_dir = dir.join(RUNS_DIR);
+    fs::create_dir_all(&runs_dir).map_err(|e| ReviewError::Write(runs_dir.clone(), e))?;
+    remove_stale(dir, dir, files, is_listing_page)?;
+    remove_stale(&runs_dir, dir, files, |name| name.ends_with(".html"))?;
+    for (path, contents) in files {
+        write_atomic(&dir.join(path), contents)?;
+    }
+    Ok(())
+}
+
+/// Remove the files in `stale_dir` that are `generated` pages but not among the dashboard's `files`,
+/// which are relative to `dir`
+fn remove_stale(
+    stale_dir: &Path,
+    dir: &Path,
+    files: &BTreeMap<PathBuf, String>,
+    generated: fn(&str) -> bool,
+) -> Result<(), ReviewError> {
+    let entries = fs::read_dir(stale_dir).map_err(|e| ReviewError::Io(stale_dir.to_path_buf(), e))?;
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let generated = path.file_name().is_some_and(|name| generated(&name.to_string_lossy()));
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        if generated && path.is_file() && !files.contains_key(relative) {
+            fs::remove_file(&path).map_err(|e| ReviewError::Write(path.clone(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` is one of the listing pages after the first
+fn is_listing_page(name: &str) -> bool {
+    name.strip_prefix("page-")
+        .and_then(|rest| rest.strip_suffix(".html"))
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn page_file(page: usize) -> String {
+    match page {
+        1 => "index.html".to_string(),
+        n => format!("page-{}.html", n),
+    }
+}
+
+fn document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n\
+         <style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLE,
+        body
+    )
+}
+
+fn index_page<'a>(title: &str, runs: impl Iterator<Item = &'a Run>, total: usize, page: usize, pages: usize) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_html(title));
+    let nav = navigation(page, pages);
+    if total == 0 {
+        body.push_str("<p>No reviews have been recorded yet.</p>\n");
+        return document(title, &body);
+    }
+    body.push_str(&format!("<p>{} {} recorded.</p>\n", total, if total == 1 { "review" } else { "reviews" }));
+    body.push_str(&nav);
+    body.push_str(
+        "<table>\n<thead>\n<tr><th>Date</th><th>Ref</th><th>Model</th><th>Verdict</th>\
+         <th>Errors</th><th>Warnings</th><th>Info</th><th>Rating</th></tr>\n</thead>\n<tbody>\n",
+    );
+    for run in runs {
+        let verdict = run.verdict();
+        let counts = if run.transcript.as_ref().is_some_and(|transcript| transcript.findings.is_some()) {
+            [Severity::Error, Severity::Warning, Severity::Info]
+                .map(|severity| format!("<td class=\"count\">{}</td>", run.count(severity)))
+                .concat()
+        } else {
+            "<td class=\"count\">-</td>".repeat(3)
+        };
+        body.push_str(&format!(
+            "<tr><td><a href=\"{}/{}.html\">{}</a></td><td>{}</td><td>{}</td><td class=\"{}\">{}</td>{}<td>{}</td></tr>\n",
+            RUNS_DIR,
+            escape_html(&run.id),
+            escape_html(&display_time(&run.entry.timestamp)),
+            escape_html(run.entry.git_ref.as_deref().unwrap_or(UNKNOWN)),
+            escape_html(run.model()),
+            verdict.class(),
+            verdict,
+            counts,
+            run.entry.feedback.as_ref().map_or("-".to_string(), |feedback| feedback.rating.to_string()),
+        ));
+    }
+    body.push_str("</tbody>\n</table>\n");
+    body.push_str(&nav);
+    document(title, &body)
+}
+
+/// Links to the newer and older pages, when there's more than one
+fn navigation(page: usize, pages: usize) -> String {
+    if pages == 1 {
+        return String::new();
+    }
+    let mut links = Vec::new();
+    if page > 1 {
+        links.push(format!("<a href=\"{}\">Newer</a>", page_file(page - 1)));
+    }
+    links.push(format!("Page {} of {}", page, pages));
+    if page < pages {
+        links.push(format!("<a href=\"{}\">Older</a>", page_file(page + 1)));
+    }
+    format!("<nav>{}</nav>\n", links.join(" | "))
+}
+
+fn run_page(title: &str, run: &Run, page: usize) -> String {
+    let entry = &run.entry;
+    let git_ref = entry.git_ref.as_deref().unwrap_or(UNKNOWN);
+    let verdict = run.verdict();
+    let mut body = format!(
+        "<nav><a href=\"../{}\">{}</a></nav>\n<h1>Review of {}</h1>\n<dl>\n",
+        page_file(page),
+        escape_html(title),
+        escape_html(git_ref)
+    );
+    let rating = entry.feedback.as_ref().map(|feedback| match &feedback.note {
+        Some(note) => format!("{}: {}", feedback.rating, note),
+        None => feedback.rating.to_string(),
+    });
+    let settings = [
+        ("Date", Some(display_time(&entry.timestamp))),
+        ("Ref", Some(git_ref.to_string())),
+        ("Model", Some(run.model().to_string())),
+        ("Preset", entry.preset.clone()),
+        ("Review length", entry.depth.clone()),
+        ("Prompt version", entry.prompt_version.clone()),
+        ("Diff SHA-256", entry.diff_sha256.clone()),
+        ("Rating", rating),
+    ];
+    for (name, value) in settings {
+        body.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", name, escape_html(value.as_deref().unwrap_or(UNKNOWN))));
+    }
+    body.push_str(&format!("<dt>Verdict</dt><dd class=\"{}\">{}</dd>\n</dl>\n", verdict.class(), verdict));
+
+    let Some(transcript) = &run.transcript else {
+        body.push_str("<p>The prompt wasn't sent with --send, so there's no review to show.</p>\n");
+        return document(title, &body);
+    };
+    match &transcript.findings {
+        Some(findings) => {
+            if let Some(summary) = &transcript.summary {
+                body.push_str(&format!("<h2>Summary</h2>\n{}", markdown_to_html(summary)));
+            }
+            body.push_str("<h2>Findings</h2>\n");
+            body.push_str(&findings_table(findings));
+        }
+        None => body.push_str(&format!("<h2>Review</h2>\n{}", markdown_to_html(&transcript.review))),
+    }
+    document(title, &body)
+}
+
+fn findings_table(findings: &[Finding]) -> String {
+    if findings.is_empty() {
+        return "<p>No findings.</p>\n".to_string();
+    }
+    let mut table = "<table>\n<thead>\n<tr><th>Severity</th><th>Location</th><th>Category</th><th>Comment</th></tr>\n\
+                     </thead>\n<tbody>\n"
+        .to_string();
+    for finding in findings {
+        let location = match finding.line {
+            Some(line) => format!("{}:{}", finding.path, line),
+            None => finding.path.clone(),
+        };
+        table.push_str(&format!(
+            "<tr><td class=\"severity-{}\">{}</td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>\n",
+            finding.severity,
+            finding.severity,
+            escape_html(&location),
+            escape_html(finding.category.as_deref().unwrap_or("-")),
+            inline_html(&finding.comment)
+        ));
+    }
+    table.push_str("</tbody>\n</table>\n");
+    table
+}
+
+/// An RFC 3339 UTC time as `2025-08-01 10:00:00 UTC`
+fn display_time(timestamp: &str) -> String {
+    match timestamp.strip_suffix('Z') {
+        Some(time) => format!("{} UTC", time.replacen('T', " ", 1)),
+        None if timestamp.is_empty() => UNKNOWN.to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// `text` with the characters HTML gives meaning to escaped
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The Markdown reviews are written in as HTML: headings, fenced code blocks, bulleted and
+/// numbered lists, and paragraphs, with inline code and bold text. Anything else is shown as the
+/// text it is; raw HTML is always escaped.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut list: Option<(&str, Vec<String>)> = None;
+    let mut code: Option<Vec<&str>> = None;
+
+    fn flush(html: &mut String, paragraph: &mut Vec<&str>, list: &mut Option<(&str, Vec<String>)>) {
+        if !paragraph.is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", inline_html(&paragraph.join("\n"))));
+            paragraph.clear();
+        }
+        if let Some((tag, items)) = list.take() {
+            let items: String = items.iter().map(|item| format!("<li>{}</li>\n", inline_html(item))).collect();
+            html.push_str(&format!("<{}>\n{}</{}>\n", tag, items, tag));
+        }
+    }
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if let Some(lines) = &mut code {
+            if trimmed.starts_with("```") {
+                html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&lines.join("\n"))));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") {
+            flush(&mut html, &mut paragraph, &mut list);
+            code = Some(Vec::new());
+        } else if trimmed.is_empty() {
+            flush(&mut html, &mut paragraph, &mut list);
+        } else if let Some((level, heading)) = heading(trimmed) {
+            flush(&mut html, &mut paragraph, &mut list);
+            html.push_str(&format!("<h{}>{}</h{}>\n", level, inline_html(heading), level));
+        } else if let Some((tag, item)) = list_item(trimmed) {
+            if !paragraph.is_empty() || list.as_ref().is_some_and(|(open, _)| *open != tag) {
+                flush(&mut html, &mut paragraph, &mut list);
+            }
+            list.get_or_insert((tag, Vec::new())).1.push(item.to_string());
+        } else if let Some((_, items)) = &mut list
+            && line.starts_with(' ')
+        {
+            // The rest of the item above
+            if let Some(last) = items.last_mut() {
+                last.push('\n');
+                last.push_str(trimmed);
+            }
+        } else {
+            if list.is_some() {
+                flush(&mut html, &mut paragraph, &mut list);
+            }
+            paragraph.push(line);
+        }
+    }
+    // A code block that wasn't closed runs to the end
+    if let Some(lines) = code {
+        html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&lines.join("\n"))));
+    }
+    flush(&mut html, &mut paragraph, &mut list);
+    html
+}
+
+/// A heading's level, from 2 to 6, and its text. The page's title is the only `h1`, so a
+/// review's top-level headings come one level down.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then(|| ((level + 1).min(6), text.trim()))
+}
+
+/// Whether `line` starts a list item: the list's tag and the item's text
+fn list_item(line: &str) -> Option<(&'static str, &str)> {
+    if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return Some(("ul", item));
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let item = line[digits..].strip_prefix(". ").or_else(|| line[digits..].strip_prefix(") "))?;
+    (digits > 0).then_some(("ol", item))
+}
+
+/// A line of Markdown's inline code and bold text as HTML, with everything else escaped
+fn inline_html(text: &str) -> String {
+    let mut html = String::new();
+    let spans: Vec<&str> = text.split('`').collect();
+    // An unmatched backtick is shown as it is
+    let closed = spans.len() % 2 == 1;
+    for (i, span) in spans.iter().enumerate() {
+        if i % 2 == 1 && (closed || i < spans.len() - 1) {
+            html.push_str(&format!("<code>{}</code>", escape_html(span)));
+        } else {
+            if i % 2 == 1 {
+                html.push('`');
+            }
+            html.push_str(&bold_html(&escape_html(span)));
+        }
+    }
+    html
+}
+
+fn bold_html(escaped: &str) -> String {
+    let parts: Vec<&str> = escaped.split("**").collect();
+    if parts.len() < 3 {
+        return escaped.to_string();
+    }
+    let mut html = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        match i % 2 {
+            // The last `**` has no partner when there's an odd number of them
+            1 if i == parts.len() - 1 => html.push_str(&format!("**{}", part)),
+            1 => html.push_str(&format!("<strong>{}</strong>", part)),
+            _ => html.push_str(part),
+        }
+    }
+    html
+}
@@ -0,0 +1,84 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A wall-clock limit on the whole run, from `--deadline-secs`. Optional steps ask it whether
+/// there is time for them, and the steps it turns down are remembered so the prompt and the token
+/// budget report can say what was left out. The default has no limit and allows everything.
+#[derive(Debug, Default)]
+pub struct Deadline {
+    limit: Option<(Instant, Duration)>,
+    decisions: Mutex<Vec<String>>,
+}
+
+/// What happened under a deadline, for the token budget report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeadlineReport {
+    pub deadline_secs: f64,
+    pub elapsed_secs: f64,
+    /// Whether anything was left out to meet the deadline
+    pub partial: bool,
+    pub decisions: Vec<String>,
+}
+
+impl Deadline {
+    /// A deadline `limit` after `started`
+    pub fn new(started: Instant, limit: Duration) -> Self {
+        Deadline {
+            limit: Some((started, limit)),
+            decisions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Time left before the deadline, or `None` without one
+    pub fn remaining(&self) -> Option<Duration> {
+        self.limit
+            .map(|(started, limit)| limit.saturating_sub(started.elapsed()))
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+
+    /// Whether there is time for `step`, expected to take up to `estimate`. If not, the decision to
+    /// skip it is logged and recorded.
+    pub fn allows(&self, step: &str, estimate: Duration) -> bool {
+        match self.remaining() {
+            Some(remaining) if remaining < estimate => {
+                self.record(format!(
+                    "Skipped {}: {:.1}s left, needs up to {:.1}s",
+                    step,
+                    remaining.as_secs_f64(),
+                    estimate.as_secs_f64()
+                ));
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Log and record something done differently to meet the deadline
+    pub fn record(&self, decision: String) {
+        warn!("Deadline: {}", decision);
+        self.decisions.lock().unwrap().push(decision);
+    }
+
+    /// The decisions taken so far, oldest first
+    pub fn decisions(&self) -> Vec<String> {
+        self.decisions.lock().unwrap().clone()
+    }
+
+    /// The report for the token budget report, or `None` without a deadline
+    pub fn report(&self) -> Option<DeadlineReport> {
+        let (started, limit) = self.limit?;
+        let decisions = self.decisions();
+        Some(DeadlineReport {
+            deadline_secs: limit.as_secs_f64(),
+            elapsed_secs: started.elapsed().as_secs_f64(),
+            partial: !decisions.is_empty(),
+            decisions,
+        })
+    }
+}
@@ -0,0 +1,403 @@
+use crate::compress::elided_line_count;
+
+/// One hunk of a unified diff: the `@@ ... @@` header and the lines that follow it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub header: String,
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// The line ranges from the header, or `None` if it can't be parsed
+    pub fn range(&self) -> Option<HunkRange> {
+        HunkRange::parse(&self.header)
+    }
+
+    /// How many parents the hunk compares with: 1, or more in a merge's combined diff, whose
+    /// header starts with one `@` more than that
+    pub fn parents(&self) -> usize {
+        parents(&self.header)
+    }
+
+    /// Number of added and removed lines, not counting context lines
+    pub fn changed_lines(&self) -> usize {
+        let parents = self.parents();
+        self.lines.iter().filter(|line| is_changed(line, parents)).count()
+    }
+}
+
+/// The number of parents a hunk header compares with: one fewer than the `@`s it starts with
+fn parents(header: &str) -> usize {
+    header.chars().take_while(|c| *c == '@').count().saturating_sub(1).max(1)
+}
+
+/// Whether a line of a hunk with `parents` columns of markers adds or removes a line, relative to
+/// any parent
+fn is_changed(line: &str, parents: usize) -> bool {
+    line.chars().take(parents).any(|c| c == '+' || c == '-')
+}
+
+/// The old and new lines a line of a hunk with `parents` columns of markers stands for. In a
+/// combined diff, the old side is the first parent. A removed line is in each parent whose column
+/// is `-`, and any other line is in each parent whose column isn't `+`; only lines with no `-` are
+/// in the result. An elided run of unchanged lines stands for as many lines on each side.
+pub fn line_counts(line: &str, parents: usize) -> (usize, usize) {
+    if let Some(count) = elided_line_count(line) {
+        return (count, count);
+    }
+    let markers: Vec<char> = line.chars().take(parents).collect();
+    match markers.first() {
+        None => (1, 1),
+        Some(' ' | '+' | '-') if markers.iter().all(|c| matches!(c, ' ' | '+' | '-')) => {
+            let removed = markers.contains(&'-');
+            let old = if removed { markers[0] == '-' } else { markers[0] != '+' };
+            (usize::from(old), usize::from(!removed))
+        }
+        _ => (0, 0),
+    }
+}
+
+/// The line ranges from a hunk header, `@@ -<old_start>,<old_len> +<new_start>,<new_len> @@`. In a
+/// merge's combined diff, `@@@ -<first parent> -<second parent> +<result> @@@`, the old range is
+/// the first parent's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HunkRange {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+}
+
+impl HunkRange {
+    /// Parse the ranges out of a hunk header. A missing length means one line, as in `@@ -5 +5 @@`.
+    pub fn parse(header: &str) -> Option<Self> {
+        let parents = parents(header);
+        let marker = "@".repeat(parents + 1);
+        let mut ranges = header.strip_prefix(&marker)?.strip_prefix(' ')?.split(' ');
+        let old = ranges.next()?.strip_prefix('-')?;
+        for _ in 1..parents {
+            ranges.next()?.strip_prefix('-')?;
+        }
+        let new = ranges.next()?.strip_prefix('+')?;
+
+        let range = |range: &str| -> Option<(usize, usize)> {
+            match range.split_once(',') {
+                Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1)),
+            }
+        };
+        let (old_start, old_len) = range(old)?;
+        let (new_start, new_len) = range(new)?;
+
+        Some(HunkRange {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        })
+    }
+}
+
+/// What a file diff does to the file, going by its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+}
+
+/// The diff for a single file: the header block (from `diff --git`, `diff --cc` in a merge's
+/// combined diff, or `---` in a plain unified diff) and its hunks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    /// Path of the file, taken from the new side unless the file was deleted
+    pub path: String,
+    /// Every line from `diff --git` (or `---`) up to the first hunk (index, mode, ---/+++ lines,
+    /// etc). In a plain diff, any lines between the previous file and this one come first, such as
+    /// the `diff -u` command line.
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    fn new(leading: Vec<String>, diff_git_line: &str) -> Self {
+        // "diff --git a/<path> b/<path>" or "diff --cc <path>" - only used if there are no ---/+++ lines
+        let path = git_paths(diff_git_line)
+            .map(|(_, new)| new)
+            .or_else(|| combined_path(diff_git_line))
+            .unwrap_or_default()
+            .to_string();
+        let mut header = leading;
+        header.push(diff_git_line.to_string());
+
+        FileDiff { path, header, hunks: Vec::new() }
+    }
+
+    /// A file from a plain unified diff, starting at its `---` line
+    fn plain(leading: Vec<String>, minus_line: &str) -> Self {
+        let mut file = FileDiff {
+            path: String::new(),
+            header: leading,
+            hunks: Vec::new(),
+        };
+        file.push_header_line(minus_line);
+        file
+    }
+
+    /// The value of the first header line starting with `prefix`, without a trailing carriage return
+    fn header_value(&self, prefix: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .find_map(|line| line.strip_prefix(prefix))
+            .map(|value| value.trim_end_matches('\r'))
+    }
+
+    /// The path on the `---` or `+++` line, without git's `a/` or `b/` and a plain diff's timestamp;
+    /// `None` for `/dev/null`
+    fn marker_path(&self, marker: &str, git_prefix: &str) -> Option<Option<&str>> {
+        let path = self.header_value(marker)?;
+        let path = path.strip_prefix(git_prefix).unwrap_or(path);
+        let path = path.split('\t').next().unwrap_or_default();
+        Some((path != "/dev/null").then_some(path))
+    }
+
+    /// The file's path before the change; `None` for an added file
+    pub fn old_path(&self) -> Option<&str> {
+        if let Some(path) = self.header_value("rename from ").or_else(|| self.header_value("copy from ")) {
+            return Some(path);
+        }
+        match self.marker_path("--- ", "a/") {
+            Some(path) => path,
+            None if self.kind() == FileKind::Added => None,
+            None => self.header.iter().find_map(|line| git_paths(line)).map(|(old, _)| old),
+        }
+    }
+
+    /// The file's path after the change; `None` for a deleted file
+    pub fn new_path(&self) -> Option<&str> {
+        if let Some(path) = self.header_value("rename to ").or_else(|| self.header_value("copy to ")) {
+            return Some(path);
+        }
+        match self.marker_path("+++ ", "b/") {
+            Some(path) => path,
+            None if self.kind() == FileKind::Deleted => None,
+            None => self.header.iter().find_map(|line| git_paths(line)).map(|(_, new)| new),
+        }
+    }
+
+    /// Whether the file was added, deleted, renamed, copied or just modified
+    pub fn kind(&self) -> FileKind {
+        if self.header_value("new file mode ").is_some() || self.marker_path("--- ", "a/") == Some(None) {
+            FileKind::Added
+        } else if self.header_value("deleted file mode ").is_some() || self.marker_path("+++ ", "b/") == Some(None) {
+            FileKind::Deleted
+        } else if self.header_value("rename from ").is_some() {
+            FileKind::Renamed
+        } else if self.header_value("copy from ").is_some() {
+            FileKind::Copied
+        } else {
+            FileKind::Modified
+        }
+    }
+
+    /// The old and new modes, when git reports the mode changed
+    pub fn mode_change(&self) -> Option<(&str, &str)> {
+        Some((self.header_value("old mode ")?, self.header_value("new mode ")?))
+    }
+
+    /// Whether git left out the contents as binary, or gave them as a binary patch
+    pub fn is_binary(&self) -> bool {
+        self.header
+            .iter()
+            .any(|line| line == "GIT binary patch" || (line.starts_with("Binary files ") && line.contains(" differ")))
+    }
+
+    fn push_header_line(&mut self, line: &str) {
+        // Deleted files have "+++ /dev/null", so the old path is all we get. Plain diffs may
+        // follow the path with a tab and a timestamp.
+        let path = line
+            .strip_prefix("+++ ")
+            .map(|path| path.strip_prefix("b/").unwrap_or(path))
+            .or_else(|| {
+                line.strip_prefix("--- ")
+                    .map(|path| path.strip_prefix("a/").unwrap_or(path))
+            })
+            .map(|path| path.split('\t').next().unwrap_or_default().trim_end_matches('\r'))
+            .filter(|path| *path != "/dev/null");
+        if let Some(path) = path {
+            self.path = path.to_string();
+        }
+        self.header.push(line.to_string());
+    }
+
+    /// Whether this file came from `git diff`, as opposed to a plain unified diff
+    fn is_git_diff(&self) -> bool {
+        self.header.iter().any(|line| is_git_file_line(line))
+    }
+
+    /// Whether this is a file from a merge's combined diff, comparing the result with each parent
+    pub fn is_combined(&self) -> bool {
+        self.header.iter().any(|line| combined_path(line).is_some())
+    }
+
+    /// Render this file back into unified diff text
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for line in &self.header {
+            out.push_str(line);
+            out.push('\n');
+        }
+        for hunk in &self.hunks {
+            out.push_str(&hunk.header);
+            out.push('\n');
+            for line in &hunk.lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// The old and new paths from a `diff --git a/<old> b/<new>` line. Only unquoted paths without
+/// ` b/` in them can be told apart.
+fn git_paths(line: &str) -> Option<(&str, &str)> {
+    let paths = line.strip_prefix("diff --git ")?.trim_end_matches('\r');
+    let (old, new) = paths.rsplit_once(" b/")?;
+    Some((old.strip_prefix("a/").unwrap_or(old), new))
+}
+
+/// The path from a combined diff's `diff --cc <path>` or `diff --combined <path>` line
+fn combined_path(line: &str) -> Option<&str> {
+    let path = line.strip_prefix("diff --cc ").or_else(|| line.strip_prefix("diff --combined "))?;
+    Some(path.trim_end_matches('\r'))
+}
+
+/// Whether `line` starts a file in git's output
+fn is_git_file_line(line: &str) -> bool {
+    line.starts_with("diff --git ") || combined_path(line).is_some()
+}
+
+/// A whole diff. Unlike `parse_diff`, nothing in the text is dropped, so rendering a parsed diff
+/// gives back exactly the text it was parsed from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    /// Lines before the first file, such as the commit `git show` prints
+    pub preamble: Vec<String>,
+    pub files: Vec<FileDiff>,
+    /// Lines after the last hunk of a plain diff that aren't part of it, such as `Only in` lines
+    pub epilogue: Vec<String>,
+    /// Whether the text ends with a line break
+    pub final_newline: bool,
+}
+
+impl Diff {
+    /// Parse `git diff` output, or a plain unified diff. Lines are split at line feeds only, so
+    /// any carriage returns stay at the ends of the lines.
+    pub fn parse(diff: &str) -> Self {
+        if diff.is_empty() {
+            return Diff::default();
+        }
+        let final_newline = diff.ends_with('\n');
+        let text = diff.strip_suffix('\n').unwrap_or(diff);
+        let mut parsed = Diff { final_newline, ..Diff::default() };
+        // Lines that don't belong to the file before them, for the file after them
+        let mut pending: Vec<String> = Vec::new();
+        // Old and new lines left in the current hunk. Without `diff --git` lines, this is how a
+        // file's `---` line is told apart from a removed line starting with `--`.
+        let mut remaining = (0, 0);
+        let mut lines = text.split('\n').peekable();
+        let files = &mut parsed.files;
+
+        while let Some(line) = lines.next() {
+            if is_git_file_line(line) {
+                files.push(FileDiff::new(pending.split_off(0), line));
+                remaining = (0, 0);
+                continue;
+            }
+
+            if line.starts_with("--- ")
+                && remaining == (0, 0)
+                && lines.peek().is_some_and(|next| next.starts_with("+++ "))
+                && files
+                    .last()
+                    .is_none_or(|file| !file.is_git_diff() && !file.hunks.is_empty())
+            {
+                files.push(FileDiff::plain(pending.split_off(0), line));
+                continue;
+            }
+
+            let Some(file) = files.last_mut() else {
+                parsed.preamble.push(line.to_string());
+                continue;
+            };
+            let is_git_diff = file.is_git_diff();
+
+            if line.starts_with("@@") {
+                // Whatever came between this hunk and the one before stays with that one
+                if let Some(hunk) = file.hunks.last_mut() {
+                    hunk.lines.append(&mut pending);
+                }
+                // If the header can't be parsed, treat every line up to the next file as in the hunk
+                remaining = HunkRange::parse(line)
+                    .map(|range| (range.old_len, range.new_len))
+                    .unwrap_or((usize::MAX, usize::MAX));
+                file.hunks.push(Hunk {
+                    header: line.to_string(),
+                    lines: Vec::new(),
+                });
+            } else if let Some(hunk) = file.hunks.last_mut() {
+                // Plain diffs may have other lines between files, such as the `diff -u` command line
+                if remaining == (0, 0) && !is_git_diff && !line.starts_with('\\') {
+                    pending.push(line.to_string());
+                    continue;
+                }
+                let (old, new) = line_counts(line, hunk.parents());
+                remaining = (remaining.0.saturating_sub(old), remaining.1.saturating_sub(new));
+                hunk.lines.push(line.to_string());
+            } else {
+                file.push_header_line(line);
+            }
+        }
+
+        parsed.epilogue = pending;
+        parsed
+    }
+
+    /// The diff as text, exactly as it was parsed
+    pub fn render(&self) -> String {
+        let mut lines: Vec<&str> = self.preamble.iter().map(String::as_str).collect();
+        for file in &self.files {
+            lines.extend(file.header.iter().map(String::as_str));
+            for hunk in &file.hunks {
+                lines.push(&hunk.header);
+                lines.extend(hunk.lines.iter().map(String::as_str));
+            }
+        }
+        lines.extend(self.epilogue.iter().map(String::as_str));
+        let mut text = lines.join("\n");
+        if self.final_newline {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Split `git diff` output, or a plain unified diff, into per-file diffs. Anything before the first
+/// file is ignored, as is anything after the last hunk of a plain diff.
+pub fn parse_diff(diff: &str) -> Vec<FileDiff> {
+    Diff::parse(diff).files
+}
+
+/// Split a diff into one diff per file, each with the file's path: the new one, or the old one
+/// for a deleted file. Rename, mode and binary lines stay in their file's diff.
+pub fn split_diff_by_file(diff: &str) -> Vec<(String, String)> {
+    parse_diff(diff).into_iter().map(|file| (file.path.clone(), file.render())).collect()
+}
+
+/// Render a list of file diffs back into a single unified diff
+pub fn render_diff(files: &[FileDiff]) -> String {
+    files.iter().map(FileDiff::render).collect()
+}
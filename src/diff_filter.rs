@@ -0,0 +1,86 @@
+use regex::Regex;
+use std::path::Path;
+
+use crate::diff::{parse_diff, render_diff, FileDiff, Hunk};
+use crate::output::language_for_path;
+
+/// Languages `filter_public_api` knows how to spot public items in
+const API_LANGUAGES: &[&str] = &["rust"];
+
+/// Drop hunks with fewer than `min_changes` added/removed lines. Files left with no hunks are
+/// dropped too, but files that never had hunks (binary files, mode changes) are kept.
+pub fn filter_small_hunks(files: Vec<FileDiff>, min_changes: usize) -> Vec<FileDiff> {
+    files
+        .into_iter()
+        .filter_map(|mut file| {
+            if file.hunks.is_empty() {
+                return Some(file);
+            }
+            file.hunks.retain(|hunk| hunk.changed_lines() >= min_changes);
+            (!file.hunks.is_empty()).then_some(file)
+        })
+        .collect()
+}
+
+/// `diff` with only the files whose paths match one of `include` (if there are any) and none of
+/// `exclude`, which are globs as `glob_regex` turns them into regexes. A file is matched by its
+/// path after the change, or before it if it was deleted, so a renamed file goes by its new name.
+/// Text that isn't a file's diff, such as a patch's mail headers, is dropped along with the files.
+pub fn filter_diff_by_paths(diff: &str, include: &[Regex], exclude: &[Regex]) -> String {
+    if include.is_empty() && exclude.is_empty() {
+        return diff.to_string();
+    }
+    let files: Vec<FileDiff> = parse_diff(diff)
+        .into_iter()
+        .filter(|file| include.is_empty() || include.iter().any(|glob| glob.is_match(&file.path)))
+        .filter(|file| !exclude.iter().any(|glob| glob.is_match(&file.path)))
+        .collect();
+    render_diff(&files)
+}
+
+/// Whether `filter_public_api` can tell public items from private ones in the file at `path`
+pub fn supports_public_api(path: &str) -> bool {
+    language_for_path(Path::new(path)).is_some_and(|language| API_LANGUAGES.contains(&language))
+}
+
+/// Keep only the hunks of supported files that add or remove a line declaring a public item, and
+/// drop supported files left with none. This is a line-based heuristic: for Rust, a changed line
+/// starting with `pub ` (so not `pub(crate)`) or `#[macro_export]`. Files in other languages are
+/// kept whole.
+pub fn filter_public_api(files: Vec<FileDiff>) -> Vec<FileDiff> {
+    files
+        .into_iter()
+        .filter_map(|mut file| {
+            if !supports_public_api(&file.path) {
+                return Some(file);
+            }
+            file.hunks.retain(changes_public_item);
+            (!file.hunks.is_empty()).then_some(file)
+        })
+        .collect()
+}
+
+pub(crate) fn changes_public_item(hunk: &Hunk) -> bool {
+    hunk.lines
+        .iter()
+        .filter_map(|line| line.strip_prefix('+').or_else(|| line.strip_prefix('-')))
+        .map(str::trim_start)
+        .any(|code| code.starts_with("pub ") || code.starts_with("#[macro_export]"))
+}
+
+/// Split `text` into a part of at most `target_chars` bytes and the rest, snapping back to just
+/// after the last newline in the first part so no line is cut in two. A newline exactly at
+/// `target_chars` ends the first part. With no newline to snap to, the split falls at
+/// `target_chars` (moved back to a character boundary).
+pub fn split_at_newline_boundary(text: &str, target_chars: usize) -> (&str, &str) {
+    if text.len() <= target_chars {
+        return (text, "");
+    }
+
+    let search_end = text.floor_char_boundary(target_chars + 1);
+    let split = match text[..search_end].rfind('\n') {
+        Some(newline) => newline + 1,
+        None => text.floor_char_boundary(target_chars),
+    };
+    text.split_at(split)
+}
@@ -0,0 +1,303 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::capabilities::{capabilities, find_pager, CLIPBOARD_COMMANDS};
+use crate::client::authorize;
+use crate::network;
+use crate::provider::ProviderConfig;
+use crate::review::{prepare, Cli};
+use crate::vcs::{git_output, has_commits, Vcs};
+
+/// Oldest git with everything the tool runs (`git rev-parse --git-path`)
+pub const MIN_GIT_VERSION: (u32, u32) = (2, 5);
+
+/// Oldest git with `git range-diff`, which --github-actions-pr-context needs for rebased pull requests
+pub const RANGE_DIFF_GIT_VERSION: (u32, u32) = (2, 19);
+
+/// How long the test request to the provider's API may take
+pub const ROUND_TRIP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of one check. Only `Fail` makes `doctor` exit unsuccessfully.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "SKIP",
+        };
+        f.pad(name)
+    }
+}
+
+/// One row of the `doctor` table: what was checked, how it went, and how to fix it if it didn't.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    pub(crate) fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Check { name, status, detail: detail.into(), hint: None }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Run every check and print the table, returning the exit status: 1 if any check failed.
+pub fn run_doctor(cli: &mut Cli, offline: bool) -> i32 {
+    let checks = checks(cli, offline);
+    println!("{}", render_checks(&checks));
+    if checks.iter().any(|check| check.status == CheckStatus::Fail) { 1 } else { 0 }
+}
+
+fn checks(cli: &mut Cli, offline: bool) -> Vec<Check> {
+    let mut checks = vec![check_git(capabilities().git()), check_repository(cli)];
+
+    let configuration = check_configuration(cli);
+    let configured = configuration.status != CheckStatus::Fail;
+    checks.push(configuration);
+    match cli.provider().filter(|_| configured) {
+        Some(provider) => {
+            let credentials = check_credentials(provider, |var| env::var(var).ok());
+            let round_trip = if offline {
+                Check::new("provider API", CheckStatus::Skip, "--offline")
+            } else if credentials.status == CheckStatus::Fail {
+                Check::new("provider API", CheckStatus::Skip, "no credentials")
+            } else {
+                check_round_trip(&provider.endpoint, round_trip(provider))
+            };
+            checks.push(credentials);
+            checks.push(round_trip);
+        }
+        None => {
+            let reason = if configured { "no provider configured" } else { "the configuration is invalid" };
+            checks.push(Check::new("credentials", CheckStatus::Skip, reason));
+            checks.push(Check::new("provider API", CheckStatus::Skip, reason));
+        }
+    }
+
+    let path = env::var_os("PATH").unwrap_or_default();
+    checks.push(check_clipboard(&path));
+    checks.push(check_pager(env::var("PAGER").ok().as_deref(), &path));
+    checks
+}
+
+/// The major and minor version in `git --version` output such as `git version 2.39.3 (Apple Git-146)`
+pub fn parse_git_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// Whether git can be run, and is new enough
+pub fn check_git(version: &Result<String, String>) -> Check {
+    let output = match version {
+        Ok(output) => output,
+        Err(e) => {
+            return Check::new("git", CheckStatus::Fail, format!("could not run git: {}", e))
+                .hint("Install git and make sure it's on your PATH");
+        }
+    };
+    let upgrade = |(major, minor): (u32, u32)| format!("Upgrade git to {}.{} or newer", major, minor);
+    match parse_git_version(output) {
+        None => Check::new("git", CheckStatus::Warn, format!("unrecognised version: {}", output)),
+        Some(version) if version < MIN_GIT_VERSION => {
+            Check::new("git", CheckStatus::Fail, output.as_str()).hint(upgrade(MIN_GIT_VERSION))
+        }
+        Some(version) if version < RANGE_DIFF_GIT_VERSION => Check::new(
+            "git",
+            CheckStatus::Warn,
+            format!("{}, too old for `git range-diff`", output),
+        )
+        .hint(upgrade(RANGE_DIFF_GIT_VERSION)),
+        Some(_) => Check::new("git", CheckStatus::Pass, output.as_str()),
+    }
+}
+
+/// Whether the current directory is in a repository, and for git, whether it has commits
+fn check_repository(cli: &Cli) -> Check {
+    let forced = cli.vcs.as_deref().and_then(|name| name.parse::<Vcs>().ok());
+    let detected = env::current_dir().ok().and_then(|dir| Vcs::detect(&dir));
+    let Some(vcs) = forced.or(detected) else {
+        return Check::new("repository", CheckStatus::Warn, "not in a git, Mercurial or Jujutsu repository")
+            .hint("Run the tool inside a repository, or review a GitHub URL or --raw-code");
+    };
+    if vcs != Vcs::Git {
+        return Check::new("repository", CheckStatus::Pass, format!("{:?} repository", vcs).to_lowercase());
+    }
+    match git_output(&["rev-parse", "--show-toplevel"]) {
+        Err(e) => Check::new("repository", CheckStatus::Fail, e.to_string())
+            .hint("Check that the repository isn't damaged and that you own it (see `git config safe.directory`)"),
+        Ok(root) if !has_commits() => {
+            Check::new("repository", CheckStatus::Warn, format!("git repository at {} with no commits yet", root.trim()))
+                .hint("Only uncommitted changes can be reviewed until the first commit")
+        }
+        Ok(root) => Check::new("repository", CheckStatus::Pass, format!("git repository at {}", root.trim())),
+    }
+}
+
+/// Whether the files the options name load: the provider config, plugins, categories and personas
+fn check_configuration(cli: &mut Cli) -> Check {
+    if let Err(e) = prepare(cli, Instant::now()) {
+        return Check::new("configuration", CheckStatus::Fail, e.to_string())
+            .hint("Fix the file named above, or leave out the option that names it");
+    }
+    match (cli.provider(), &cli.llm_provider_config) {
+        (Some(provider), Some(path)) => Check::new(
+            "configuration",
+            CheckStatus::Pass,
+            format!("provider {} from {}", provider.name, path.display()),
+        ),
+        _ => Check::new("configuration", CheckStatus::Pass, "no provider configured; prompts are printed"),
+    }
+}
+
+/// Whether the provider's API key is in the environment. `lookup` reads a variable.
+pub fn check_credentials(provider: &ProviderConfig, lookup: impl Fn(&str) -> Option<String>) -> Check {
+    let Some(var) = provider.api_key_var() else {
+        return Check::new("credentials", CheckStatus::Pass, format!("{} takes no API key", provider.name));
+    };
+    match lookup(var) {
+        Some(key) if !key.trim().is_empty() => {
+            Check::new("credentials", CheckStatus::Pass, format!("{} is set", var))
+        }
+        _ => Check::new("credentials", CheckStatus::Fail, format!("{} is not set", var)).hint(format!(
+            "Export {} with your API key, or set api_key_env for provider {} to the variable that holds it",
+            var, provider.name
+        )),
+    }
+}
+
+/// Send the provider an empty request: it costs nothing, but gets through any proxy and is
+/// authenticated, so the status tells whether the endpoint is reachable and the key accepted.
+/// Returns the HTTP status, or why no response came back.
+pub(crate) fn round_trip(provider: &ProviderConfig) -> Result<u16, String> {
+    let key = provider.api_key_var().and_then(|var| env::var(var).ok());
+    round_trip_with_key(provider, key.as_deref())
+}
+
+/// As `round_trip`, authenticated with `key` rather than the one in the environment
+pub(crate) fn round_trip_with_key(provider: &ProviderConfig, key: Option<&str>) -> Result<u16, String> {
+    let mut request = network::post(&provider.endpoint).map_err(|e| e.to_string())?
+        .config()
+        .timeout_global(Some(ROUND_TRIP_TIMEOUT))
+        .build()
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "llm_code_review");
+    if let Some(key) = key {
+        request = authorize(request, provider.format, key);
+    }
+    debug!("Sending a test request to {}", provider.endpoint);
+    match request.send("{}") {
+        Ok(response) => Ok(response.status().as_u16()),
+        Err(ureq::Error::StatusCode(status)) => Ok(status),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Judge the response to the empty request `round_trip` sent. It's invalid, so a 400 or 422 is
+/// what a working setup gets back.
+pub fn check_round_trip(endpoint: &str, result: Result<u16, String>) -> Check {
+    let status = match result {
+        Ok(status) => status,
+        Err(e) => {
+            return Check::new("provider API", CheckStatus::Fail, format!("no response from {}: {}", endpoint, e))
+                .hint("Check your network connection, and set HTTPS_PROXY if you're behind a proxy");
+        }
+    };
+    let detail = format!("{} answered HTTP {}", endpoint, status);
+    match status {
+        401 | 403 => Check::new("provider API", CheckStatus::Fail, detail)
+            .hint("The API key was rejected; check that it's current and allowed to use this endpoint"),
+        407 => Check::new("provider API", CheckStatus::Fail, detail)
+            .hint("Your proxy needs credentials; include them in HTTPS_PROXY"),
+        404 | 405 => Check::new("provider API", CheckStatus::Fail, detail)
+            .hint("Check the endpoint URL in --llm-provider-config"),
+        429 => Check::new("provider API", CheckStatus::Warn, detail)
+            .hint("The key works but is rate limited or out of credit"),
+        500.. => Check::new("provider API", CheckStatus::Warn, detail)
+            .hint("The provider is having problems; try again later"),
+        _ => Check::new("provider API", CheckStatus::Pass, detail),
+    }
+}
+
+/// The first of `names` that's an executable file in one of the `path` directories
+pub fn find_on_path<'a>(names: &[&'a str], path: &OsStr) -> Option<&'a str> {
+    names
+        .iter()
+        .find(|name| env::split_paths(path).any(|dir| is_executable(&dir.join(name))))
+        .copied()
+}
+
+#[cfg(unix)]
+pub(crate) fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Whether there's a command to pipe the prompt to for the clipboard
+pub fn check_clipboard(path: &OsStr) -> Check {
+    match find_on_path(CLIPBOARD_COMMANDS, path) {
+        Some(command) => Check::new("clipboard", CheckStatus::Pass, command),
+        None => Check::new("clipboard", CheckStatus::Warn, "no clipboard command found")
+            .hint("Install xclip or wl-clipboard to pipe prompts to the clipboard, or use --output-file"),
+    }
+}
+
+/// Whether there's a pager to read long prompts with: `PAGER`'s command, or else `less` or `more`
+pub fn check_pager(pager: Option<&str>, path: &OsStr) -> Check {
+    let configured = pager.and_then(|pager| pager.split_whitespace().next());
+    match (find_pager(pager, path), configured) {
+        (Some(command), _) => Check::new("pager", CheckStatus::Pass, command),
+        (None, Some(command)) => Check::new("pager", CheckStatus::Warn, format!("PAGER is {}, which wasn't found", command))
+            .hint("Set PAGER to a pager that's installed"),
+        (None, None) => Check::new("pager", CheckStatus::Warn, "no pager found")
+            .hint("Install less, or set PAGER"),
+    }
+}
+
+/// The checks as a table, hints under the rows they belong to, and a summary line.
+pub fn render_checks(checks: &[Check]) -> String {
+    let name_width = checks.iter().map(|check| check.name.len()).max().unwrap_or(0).max("Check".len());
+    let mut lines = vec![format!("{:name_width$}  {:6}  Details", "Check", "Status")];
+    for check in checks {
+        lines.push(format!("{:name_width$}  {:6}  {}", check.name, check.status, check.detail));
+        if let Some(hint) = &check.hint
+            && check.status != CheckStatus::Pass
+        {
+            lines.push(format!("{:name_width$}  {:6}  hint: {}", "", "", hint));
+        }
+    }
+
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let (warnings, failures) = (count(CheckStatus::Warn), count(CheckStatus::Fail));
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    lines.push(String::new());
+    lines.push(format!("{}, {}", plural(failures, "failure"), plural(warnings, "warning")));
+    lines.join("\n")
+}
@@ -0,0 +1,172 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+
+use crate::compress::elided_line_count;
+use crate::diff::{FileDiff, HunkRange};
+use crate::findings::{Finding, Severity};
+use crate::provenance::sha256_hex;
+use crate::truncate::truncate_graphemes;
+
+/// The label --file-issues gives the issues it opens, unless --file-issues-label says otherwise
+pub const DEFAULT_ISSUE_LABEL: &str = "llm-review-follow-up";
+
+/// How long each request to GitHub --file-issues makes may take
+pub const ISSUE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lines of the hunk shown on each side of the line a finding is about
+pub const EXCERPT_CONTEXT_LINES: usize = 4;
+
+// Longest issue title, in characters, before the finding's summary is cut short
+const MAX_TITLE_CHARS: usize = 100;
+
+// Written into each issue's body so the same finding isn't filed twice, whatever its title was
+// edited to
+const MARKER_PREFIX: &str = "<!-- llm_code_review finding:";
+
+/// The findings to file as issues: those the model marked as follow-ups and, with `below`, those
+/// less serious than it.
+pub fn follow_ups(findings: Vec<Finding>, below: Option<Severity>) -> Vec<Finding> {
+    findings
+        .into_iter()
+        .filter(|finding| finding.follow_up || below.is_some_and(|severity| finding.severity > severity))
+        .collect()
+}
+
+/// An issue ready to be opened for a finding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DraftIssue {
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<String>,
+    /// The hidden comment identifying the finding, also at the end of `body`
+    pub marker: String,
+}
+
+impl DraftIssue {
+    /// The issue as the GitHub API takes it
+    pub fn to_json(&self) -> Value {
+        json!({"title": self.title, "body": self.body, "labels": self.labels})
+    }
+}
+
+impl fmt::Display for DraftIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Title: {}", self.title)?;
+        writeln!(f, "Labels: {}", self.labels.join(", "))?;
+        write!(f, "\n{}", self.body)
+    }
+}
+
+/// Where the reviewed code is on GitHub, for linking to the lines a finding is about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewedCode {
+    /// e.g. `https://github.com/owner/repo`
+    pub repository_url: String,
+    /// The commit that was reviewed
+    pub sha: String,
+}
+
+impl ReviewedCode {
+    fn link(&self, finding: &Finding) -> String {
+        let url = format!("{}/blob/{}/{}", self.repository_url, self.sha, finding.path);
+        match finding.line {
+            Some(line) => format!("[`{}` line {}]({}#L{})", finding.path, line, url, line),
+            None => format!("[`{}`]({})", finding.path, url),
+        }
+    }
+}
+
+/// The hidden comment that identifies `finding` in an issue body. It depends on the file and the
+/// comment, not the line, which moves as the code around it changes.
+pub fn finding_marker(finding: &Finding) -> String {
+    let id = sha256_hex(&format!("{}\n{}", finding.path, finding.comment.trim()));
+    format!("{} {} -->", MARKER_PREFIX, &id[..16])
+}
+
+/// The issue for `finding`: its summary as the title, and its comment, a link to the line at the
+/// reviewed commit and the part of the diff it's about as the body.
+pub fn draft_issue(finding: &Finding, files: &[FileDiff], code: &ReviewedCode, label: &str) -> DraftIssue {
+    let mut body = format!("{}\n\n**Where:** {}\n", finding.comment.trim(), code.link(finding));
+    body.push_str(&format!("**Severity:** {}", finding.severity));
+    if let Some(note) = &finding.policy_note {
+        body.push_str(&format!(" ({})", note));
+    }
+    if let Some(category) = &finding.category {
+        body.push_str(&format!(" | **Category:** {}", category));
+    }
+    body.push('\n');
+    if let Some(excerpt) = hunk_excerpt(files, finding) {
+        body.push_str(&format!("\n```diff\n{}\n```\n", excerpt));
+    }
+    let short_sha = &code.sha[..code.sha.len().min(12)];
+    body.push_str(&format!(
+        "\nFiled for follow-up from a review of {}; it didn't need to block that change.\n",
+        short_sha
+    ));
+    let marker = finding_marker(finding);
+    body.push_str(&marker);
+
+    DraftIssue {
+        title: issue_title(finding),
+        body,
+        labels: vec![label.to_string()],
+        marker,
+    }
+}
+
+/// `path: summary`, the summary being the comment's first sentence, cut short if it's long
+fn issue_title(finding: &Finding) -> String {
+    let comment = finding.comment.trim();
+    let first_line = comment.lines().next().unwrap_or_default();
+    let summary = match first_line.find(". ") {
+        Some(end) => &first_line[..end],
+        None => first_line.trim_end_matches('.'),
+    };
+    truncate_graphemes(&format!("{}: {}", finding.path, summary), MAX_TITLE_CHARS)
+}
+
+/// The lines of the diff around the finding's line: the hunk header, then up to
+/// `EXCERPT_CONTEXT_LINES` lines either side. A finding without a line gets the start of the
+/// file's first hunk. `None` if the diff doesn't show that part of the file.
+pub fn hunk_excerpt(files: &[FileDiff], finding: &Finding) -> Option<String> {
+    let file = files.iter().find(|file| file.path == finding.path)?;
+    let (hunk, at) = match finding.line {
+        Some(line) => file.hunks.iter().find_map(|hunk| {
+            let range = HunkRange::parse(&hunk.header)?;
+            let mut new_line = range.new_start;
+            for (index, text) in hunk.lines.iter().enumerate() {
+                if text.starts_with('-') {
+                    continue;
+                }
+                if new_line == line {
+                    return Some((hunk, index));
+                }
+                new_line += elided_line_count(text).unwrap_or(1);
+            }
+            None
+        })?,
+        None => (file.hunks.first()?, 0),
+    };
+
+    let start = at.saturating_sub(EXCERPT_CONTEXT_LINES);
+    let end = (at + EXCERPT_CONTEXT_LINES + 1).min(hunk.lines.len());
+    let mut excerpt = vec![hunk.header.as_str()];
+    excerpt.extend(hunk.lines[start..end].iter().map(String::as_str));
+    Some(excerpt.join("\n"))
+}
+
+/// The drafts not already filed: those whose marker isn't in any of `existing_bodies`, and only
+/// the first of any that share one.
+pub fn unfiled(drafts: Vec<DraftIssue>, existing_bodies: &[String]) -> Vec<DraftIssue> {
+    let mut seen: HashSet<String> = existing_bodies
+        .iter()
+        .flat_map(|body| body.lines())
+        .filter(|line| line.trim_start().starts_with(MARKER_PREFIX))
+        .map(|line| line.trim().to_string())
+        .collect();
+    drafts.into_iter().filter(|draft| seen.insert(draft.marker.clone())).collect()
+}
@@ -0,0 +1,138 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::Encoding;
+
+use crate::vcs::git_command;
+
+/// Decode raw diff output into UTF-8, one file at a time. Files that are already valid UTF-8 are
+/// left alone. Anything else is transcoded using, in order of preference, a byte order mark, the
+/// file's `working-tree-encoding`/`encoding` git attribute, or a guess based on its content, and a
+/// note naming the original encoding is added to the file's header. If none of those decode
+/// cleanly the file is converted lossily and a warning is logged.
+pub fn decode_diff(raw: &[u8]) -> String {
+    decode_diff_with(raw, git_encoding_attribute)
+}
+
+/// Like `decode_diff`, but with a caller-supplied lookup of the declared encoding for a path.
+pub fn decode_diff_with<F>(raw: &[u8], encoding_attribute: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Ok(text) = std::str::from_utf8(raw) {
+        return text.to_string();
+    }
+
+    split_files(raw)
+        .into_iter()
+        .map(|chunk| decode_file(chunk, &encoding_attribute))
+        .collect()
+}
+
+/// Split raw diff bytes at each `diff --git` line, keeping line endings.
+fn split_files(raw: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+
+    for line in raw.split_inclusive(|&b| b == b'\n') {
+        if line.starts_with(b"diff --git ") && pos > start {
+            chunks.push(&raw[start..pos]);
+            start = pos;
+        }
+        pos += line.len();
+    }
+    if pos > start {
+        chunks.push(&raw[start..pos]);
+    }
+
+    chunks
+}
+
+fn decode_file<F>(chunk: &[u8], encoding_attribute: &F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Ok(text) = std::str::from_utf8(chunk) {
+        return text.to_string();
+    }
+
+    let path = file_path(chunk);
+    let encoding = bom_encoding(chunk)
+        .or_else(|| {
+            encoding_attribute(&path).and_then(|label| Encoding::for_label(label.as_bytes()))
+        })
+        .unwrap_or_else(|| guess_encoding(chunk));
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(chunk);
+    if had_errors {
+        warn!(
+            "Could not detect the encoding of {}; some characters may not display correctly",
+            path
+        );
+        return String::from_utf8_lossy(chunk).to_string();
+    }
+
+    debug!("Transcoded {} from {}", path, encoding.name());
+    add_encoding_note(&text, encoding.name())
+}
+
+/// Path from the `diff --git a/<path> b/<path>` line at the start of a file chunk
+fn file_path(chunk: &[u8]) -> String {
+    let first_line = chunk.split(|&b| b == b'\n').next().unwrap_or_default();
+    let first_line = String::from_utf8_lossy(first_line);
+    first_line
+        .rsplit_once(" b/")
+        .map(|(_, path)| path.trim_end().to_string())
+        .unwrap_or_else(|| "(unknown file)".to_string())
+}
+
+/// Look for a byte order mark at the start of the file contents, i.e. the first hunk line
+/// describing line 1 of either side of the diff.
+fn bom_encoding(chunk: &[u8]) -> Option<&'static Encoding> {
+    chunk
+        .split(|&b| b == b'\n')
+        .skip_while(|line| !line.starts_with(b"@@ -0,") && !line.starts_with(b"@@ -1,"))
+        .nth(1)
+        .and_then(|line| line.get(1..))
+        .and_then(Encoding::for_bom)
+        .map(|(encoding, _)| encoding)
+}
+
+fn guess_encoding(chunk: &[u8]) -> &'static Encoding {
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(chunk, true);
+    detector.guess(None, Utf8Detection::Allow)
+}
+
+/// Insert a note about the original encoding straight after the `diff --git` line
+fn add_encoding_note(text: &str, encoding_name: &str) -> String {
+    let note = format!(
+        "# Note: file content transcoded from {} to UTF-8 for review\n",
+        encoding_name
+    );
+    match text.split_once('\n') {
+        Some((first, rest)) if first.starts_with("diff --git ") => {
+            format!("{}\n{}{}", first, note, rest)
+        }
+        _ => format!("{}{}", note, text),
+    }
+}
+
+/// The encoding declared for `path` in `.gitattributes`, if any
+fn git_encoding_attribute(path: &str) -> Option<String> {
+    let output = git_command()
+        .args(["check-attr", "working-tree-encoding", "encoding", "--", path])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // Output lines look like "<path>: <attribute>: <value>"
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.rsplit(": ").next())
+        .map(|value| value.trim().to_string())
+        .find(|value| value != "unspecified" && value != "unset" && value != "set")
+}
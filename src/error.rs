@@ -0,0 +1,239 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::progress::format_elapsed;
+
+/// Errors that can occur while gathering a diff and assembling the review prompt.
+#[derive(Debug)]
+pub enum ReviewError {
+    /// A git command exited unsuccessfully; holds git's stderr
+    GitFailed(String),
+    /// An hg command exited unsuccessfully; holds hg's stderr
+    HgFailed(String),
+    /// A jj command exited unsuccessfully; holds jj's stderr
+    JjFailed(String),
+    /// The command given with --diff-command exited unsuccessfully; holds its stderr
+    DiffCommandFailed(String),
+    /// A version control command isn't installed or isn't on the PATH
+    CommandNotFound(String),
+    /// No repository was found in the directory or its parents; holds the directory
+    NoRepository(PathBuf),
+    /// The diff has nothing in it to review
+    NoChanges,
+    /// Options that can't be used here, such as a git-only option in an hg repository; holds the
+    /// message
+    Usage(String),
+    /// The user declined --require-approval's prompt
+    Aborted,
+    /// The review isn't the JSON --output-format json asked for; holds why
+    InvalidJsonReview(String),
+    /// --require-todo-issue found new markers without an issue reference
+    TodoWithoutIssue {
+        pattern: String,
+        count: usize,
+        /// The markers, described one per line
+        markers: String,
+    },
+    /// No stash entry exists at the requested index
+    NoSuchStash(usize),
+    /// A revision relative to HEAD was given in a repository with no commits yet
+    NoCommitsYet(String),
+    /// A revision goes back further than the first commit
+    BeforeFirstCommit {
+        revision: String,
+        base: String,
+        /// How many commits `base` has behind it, itself included
+        commits: usize,
+    },
+    /// A request to the GitHub API failed
+    GitHub(String),
+    /// The diff's estimated token count is over the budget
+    DiffTooLarge {
+        estimated_tokens: usize,
+        max_tokens: usize,
+    },
+    /// The finished prompt's token count is over the budget. `system_prompt_tokens` says how much
+    /// of it is the system prompt, which may be over the budget by itself.
+    PromptTooLarge {
+        tokens: usize,
+        system_prompt_tokens: usize,
+        max_tokens: usize,
+    },
+    /// The finished prompt is over --hard-max-chars, whatever the token estimate said
+    PromptOverHardCap {
+        chars: usize,
+        bytes: usize,
+        max_chars: usize,
+        estimated_tokens: usize,
+        max_tokens: usize,
+    },
+    /// The finished prompt failed the checks made before sending it; holds what's wrong
+    BrokenPrompt(Vec<String>),
+    /// --enforce-budget refused to send a prompt, as the month's estimated spend has reached
+    /// --monthly-budget
+    BudgetExceeded { spent: f64, budget: f64, month: String },
+    /// A prompt wasn't sent, as its estimated cost would take the run's spend over --max-cost
+    OverMaxCost { model: String, cost: f64, spent: f64, limit: f64 },
+    /// An --answer-file doesn't match the questions in the previous review
+    Answers(String),
+    /// `feedback` was given a history directory with no reviews recorded in it
+    NoHistoryEntries(PathBuf),
+    /// A configuration file is invalid
+    Config(String),
+    /// A --plugin-dir plugin couldn't be loaded
+    Plugin(PathBuf, String),
+    /// A --preprocess command failed or wrote something that isn't a diff; holds the command and
+    /// what went wrong
+    Preprocess(String, String),
+    /// The --deadline-secs deadline passed before the work could start
+    DeadlineExceeded,
+    /// --offline refused a request; holds its URL
+    OfflineModeViolation(String),
+    /// --copy couldn't put the text on the clipboard; holds why
+    Clipboard(String),
+    /// A request to the LLM provider failed, or its response had no review in it
+    Provider(String),
+    /// The provider sent no tokens within --max-wait-first-token
+    FirstTokenTimeout(Duration),
+    /// Reading or writing a file failed
+    Io(PathBuf, io::Error),
+    /// Writing one of the tool's output files failed
+    Write(PathBuf, io::Error),
+}
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReviewError::GitFailed(stderr) => {
+                write!(f, "Git command failed. Check your arguments:\n{}", stderr.trim_end())
+            }
+            ReviewError::HgFailed(stderr) => {
+                write!(f, "Mercurial command failed. Check your arguments:\n{}", stderr.trim_end())
+            }
+            ReviewError::JjFailed(stderr) => {
+                write!(f, "Jujutsu command failed. Check your arguments:\n{}", stderr.trim_end())
+            }
+            ReviewError::DiffCommandFailed(stderr) => {
+                write!(f, "Diff command failed:\n{}", stderr.trim_end())
+            }
+            ReviewError::CommandNotFound(program) => {
+                write!(f, "Could not run `{}`. Is it installed and on your PATH?", program)
+            }
+            ReviewError::NoRepository(dir) => write!(
+                f,
+                "No repository found in {} or its parents: looked for .jj, .git and .hg. Give --vcs to say which to use, or review a diff from elsewhere with --diff-file, --stdin or --diff-command.",
+                dir.display()
+            ),
+            ReviewError::NoChanges => write!(f, "No changes found to review."),
+            ReviewError::Usage(msg) => write!(f, "{}", msg),
+            ReviewError::Aborted => write!(f, "Aborted"),
+            ReviewError::InvalidJsonReview(e) => {
+                write!(f, "The review isn't the JSON array of findings --output-format json asks for: {}", e)
+            }
+            ReviewError::TodoWithoutIssue { pattern, count, markers } => {
+                write!(f, "{} new markers have no issue reference matching `{}`:\n{}", count, pattern, markers)
+            }
+            ReviewError::NoSuchStash(n) => {
+                write!(f, "No stash entry found at stash@{{{}}}", n)
+            }
+            ReviewError::NoCommitsYet(revision) => write!(
+                f,
+                "The repository has no commits yet, so there is no {} to compare with. Run without a revision to review the staged and unstaged files.",
+                revision
+            ),
+            ReviewError::BeforeFirstCommit { revision, base, commits } => write!(
+                f,
+                "{} is before the first commit: {} has only {} commit{} of history. Use {}~{} to review everything from the first commit.",
+                revision,
+                base,
+                commits,
+                if *commits == 1 { "" } else { "s" },
+                base,
+                commits
+            ),
+            ReviewError::GitHub(msg) => write!(f, "GitHub request failed: {}", msg),
+            ReviewError::DiffTooLarge {
+                estimated_tokens,
+                max_tokens,
+            } => write!(
+                f,
+                "Diff is too large to review: about {} tokens, but the limit is {}. Try reviewing a smaller set of changes.",
+                estimated_tokens, max_tokens
+            ),
+            ReviewError::PromptTooLarge {
+                system_prompt_tokens,
+                max_tokens,
+                ..
+            } if system_prompt_tokens > max_tokens => write!(
+                f,
+                "The system prompt alone is {} tokens, over the limit of {}. Shorten it, or raise the limit with --max-tokens.",
+                system_prompt_tokens, max_tokens
+            ),
+            ReviewError::PromptTooLarge { tokens, max_tokens, .. } => write!(
+                f,
+                "Prompt is too large to review: {} tokens, but the limit is {}. Try reviewing a smaller set of changes, or raise the limit with --max-tokens.",
+                tokens, max_tokens
+            ),
+            ReviewError::PromptOverHardCap {
+                chars,
+                bytes,
+                max_chars,
+                estimated_tokens,
+                max_tokens,
+            } => write!(
+                f,
+                "Prompt is {} characters ({} bytes), over the --hard-max-chars limit of {}. It was estimated at {} tokens against a budget of {}, so the token estimate or the model's limits are probably wrong.",
+                chars, bytes, max_chars, estimated_tokens, max_tokens
+            ),
+            ReviewError::BrokenPrompt(problems) => write!(
+                f,
+                "The prompt looks broken, so it wasn't sent:\n{}\nSend it anyway with --force-send.",
+                problems.iter().map(|problem| format!("- {}", problem)).collect::<Vec<_>>().join("\n")
+            ),
+            ReviewError::BudgetExceeded { spent, budget, month } => write!(
+                f,
+                "Not sending the prompt: ${:.2} of the ${:.2} monthly budget is already spent in {}. Raise --monthly-budget, or leave out --enforce-budget to only be warned.",
+                spent, budget, month
+            ),
+            ReviewError::OverMaxCost { model, cost, spent, limit } => write!(
+                f,
+                "Not sending the prompt to {}: it would cost about ${:.2}, taking the run's spend from ${:.2} over the --max-cost of ${:.2}.",
+                model, cost, spent, limit
+            ),
+            ReviewError::Answers(msg) => write!(f, "Invalid answer file: {}", msg),
+            ReviewError::NoHistoryEntries(dir) => write!(
+                f,
+                "No reviews are recorded in {} yet; build a review prompt with --history-dir {} first",
+                dir.display(),
+                dir.display()
+            ),
+            ReviewError::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            ReviewError::Plugin(path, msg) => {
+                write!(f, "Could not load plugin {}: {}", path.display(), msg)
+            }
+            ReviewError::Preprocess(command, msg) => {
+                write!(f, "The --preprocess command `{}` failed: {}", command, msg)
+            }
+            ReviewError::DeadlineExceeded => write!(f, "The --deadline-secs deadline has passed"),
+            ReviewError::OfflineModeViolation(url) => {
+                write!(f, "Not connecting to {}: --offline allows no network access", url)
+            }
+            ReviewError::Clipboard(msg) => write!(f, "Could not copy to the clipboard: {}", msg),
+            ReviewError::Provider(msg) => write!(f, "LLM request failed: {}", msg),
+            ReviewError::FirstTokenTimeout(max) => write!(
+                f,
+                "The model sent nothing within {}; it may still be loading. Try a smaller model or increase --max-wait-first-token.",
+                format_elapsed(*max)
+            ),
+            ReviewError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            // The kind tells apart e.g. "permission denied" and "read-only filesystem"
+            ReviewError::Write(path, e) => {
+                write!(f, "Could not write {}: {} ({})", path.display(), e.kind(), e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReviewError {}
@@ -0,0 +1,185 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+use crate::write_atomic::write_atomic;
+
+/// Where the reviews built with --history-dir are recorded, in that directory. `load_reviews`
+/// skips it.
+pub const HISTORY_FILE: &str = ".history.json";
+
+/// How many entries the history keeps; the oldest are dropped first
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// What the report shows for a setting an entry doesn't have, e.g. one written before that
+/// setting was recorded
+pub const UNKNOWN: &str = "unknown";
+
+/// Whether a review was useful.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Good,
+    Bad,
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rating::Good => write!(f, "good"),
+            Rating::Bad => write!(f, "bad"),
+        }
+    }
+}
+
+/// A rating given with `feedback good` or `feedback bad`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feedback {
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// UTC, in RFC 3339 format
+    pub timestamp: String,
+}
+
+/// One review built with --history-dir, and the configuration it was built with. Every field
+/// defaults, so entries from older versions still load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// UTC, in RFC 3339 format
+    #[serde(default)]
+    pub timestamp: String,
+    /// SHA-256 of the diff as it went into the prompt
+    #[serde(default)]
+    pub diff_sha256: Option<String>,
+    /// The first 12 hex digits of the system prompt's SHA-256, which changes with the tool's
+    /// prompt, the persona and the section files
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+    /// The --provider the prompt was for, each of which names one model, or `none`
+    #[serde(default)]
+    pub model: Option<String>,
+    /// The language presets in effect, joined with `+`, or `none`
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// The --review-length asked for
+    #[serde(default)]
+    pub depth: Option<String>,
+    /// What was reviewed: the pull request's URL, or the branch and commit checked out, e.g.
+    /// `main @ 1a2b3c4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// With --send, the file the review was saved in as a transcript, in the history directory's
+    /// `reviews` directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+}
+
+/// The reviews recorded in a history directory, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Read the history from `dir`, or start an empty one if there isn't one yet
+    pub fn load(dir: &Path) -> Result<Self, ReviewError> {
+        let path = dir.join(HISTORY_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(History::default()),
+            Err(e) => return Err(ReviewError::Io(path, e)),
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), ReviewError> {
+        let path = dir.join(HISTORY_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+        write_atomic(&path, json)
+    }
+
+    /// Add `entry` as the most recent, dropping the oldest beyond `MAX_HISTORY_ENTRIES`. Returns
+    /// the entries dropped.
+    pub fn record(&mut self, entry: HistoryEntry) -> Vec<HistoryEntry> {
+        self.entries.push(entry);
+        let excess = self.entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+        self.entries.drain(..excess).collect()
+    }
+
+    /// Rate the most recent entry, replacing any rating it already had
+    pub fn rate_latest(&mut self, feedback: Feedback) -> Option<&HistoryEntry> {
+        let entry = self.entries.last_mut()?;
+        entry.feedback = Some(feedback);
+        Some(entry)
+    }
+}
+
+type EntrySetting = fn(&HistoryEntry) -> Option<&String>;
+
+/// The ratings of the entries, counted by each part of their configuration: one table each for
+/// prompt version, model, preset and depth.
+pub fn feedback_report(entries: &[HistoryEntry]) -> String {
+    let dimensions: [(&str, EntrySetting); 4] = [
+        ("Prompt version", |entry| entry.prompt_version.as_ref()),
+        ("Model", |entry| entry.model.as_ref()),
+        ("Preset", |entry| entry.preset.as_ref()),
+        ("Depth", |entry| entry.depth.as_ref()),
+    ];
+    let rated = entries.iter().filter(|entry| entry.feedback.is_some()).count();
+    let mut report = vec![format!("{} reviews, {} rated", entries.len(), rated)];
+
+    for (title, value) in dimensions {
+        let mut counts: BTreeMap<&str, Counts> = BTreeMap::new();
+        for entry in entries {
+            let counts = counts.entry(value(entry).map_or(UNKNOWN, String::as_str)).or_default();
+            match entry.feedback.as_ref().map(|feedback| feedback.rating) {
+                Some(Rating::Good) => counts.good += 1,
+                Some(Rating::Bad) => counts.bad += 1,
+                None => counts.unrated += 1,
+            }
+        }
+
+        let width = counts.keys().map(|value| value.chars().count()).chain([title.len()]).max().unwrap_or(0);
+        report.push(String::new());
+        report.push(format!("{:width$}  {:>4}  {:>4}  {:>7}  {:>5}", title, "good", "bad", "unrated", "liked"));
+        for (value, counts) in counts {
+            report.push(format!(
+                "{:width$}  {:>4}  {:>4}  {:>7}  {:>5}",
+                value,
+                counts.good,
+                counts.bad,
+                counts.unrated,
+                counts.liked()
+            ));
+        }
+    }
+    report.join("\n")
+}
+
+#[derive(Default)]
+struct Counts {
+    good: usize,
+    bad: usize,
+    unrated: usize,
+}
+
+impl Counts {
+    /// The share of ratings that were good, or `-` if there are none
+    fn liked(&self) -> String {
+        match self.good + self.bad {
+            0 => "-".to_string(),
+            rated => format!("{:.0}%", self.good as f64 * 100.0 / rated as f64),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use clap::Parser;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::Instant;
+
+use crate::error::ReviewError;
+use crate::review::{Cli, prepare, try_build_prompt};
+
+// C functions for building review prompts without running the command line tool. Strings passed
+// in are borrowed for the duration of the call and must be NUL-terminated UTF-8. Strings passed
+// out are owned by the caller, who must release each one with llm_review_free_string exactly once.
+
+/// The result of each call. The values are part of the C API and must not change.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmReviewStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A required pointer argument was NULL
+    ErrNullArgument = 1,
+    /// A string argument wasn't valid UTF-8
+    ErrInvalidUtf8 = 2,
+    /// The options weren't valid command line options
+    ErrInvalidOptions = 3,
+    /// The prompt couldn't be built, e.g. a file named in the options couldn't be read
+    ErrPrompt = 4,
+    /// The provider callback returned NULL
+    ErrProvider = 5,
+    /// The output contained a NUL byte, so it can't be returned as a C string
+    ErrNulInOutput = 6,
+    /// Something went wrong inside the library; this is a bug
+    ErrInternal = 7,
+}
+
+/// Sends a prompt to an LLM and returns its review, or NULL to report a failure.
+///
+/// The prompt is only valid during the call. The returned string stays owned by the caller and
+/// must remain valid until `llm_review_run` returns; it is copied before then.
+pub type LlmReviewProvider =
+    Option<extern "C" fn(prompt: *const c_char, user_data: *mut c_void) -> *const c_char>;
+
+/// Build the review prompt for `diff`.
+///
+/// `argv` holds `argc` command line options, one per string as a shell would pass them, e.g.
+/// `{"--review-length", "short"}`, and may be NULL when `argc` is 0, for the defaults. On success
+/// `*out_prompt` is set to a string the caller must free with `llm_review_free_string`; on failure
+/// it is set to NULL.
+///
+/// ```
+/// use llm_code_review::ffi::*;
+/// use std::ffi::{CStr, CString};
+/// use std::ptr;
+///
+/// let diff = CString::new("diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-old\n+new\n").unwrap();
+/// // A value with spaces in it is one string, with no quoting
+/// let argv = [c"--context".as_ptr(), c"Prices are in cents".as_ptr()];
+/// let mut prompt = ptr::null_mut();
+/// let status = unsafe { llm_review_build_prompt(diff.as_ptr(), argv.as_ptr(), argv.len(), &mut prompt) };
+/// assert_eq!(status, LlmReviewStatus::Ok);
+/// let text = unsafe { CStr::from_ptr(prompt) }.to_str().unwrap();
+/// assert!(text.contains("+new") && text.contains("Prices are in cents"));
+/// // The prompt belongs to the caller now, and is released exactly once
+/// unsafe { llm_review_free_string(prompt) };
+/// ```
+///
+/// # Safety
+///
+/// `diff` must point to a NUL-terminated string, `argv` must be NULL or point to `argc` of them,
+/// and `out_prompt` must point to writable memory for a pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn llm_review_build_prompt(
+    diff: *const c_char,
+    argv: *const *const c_char,
+    argc: usize,
+    out_prompt: *mut *mut c_char,
+) -> LlmReviewStatus {
+    guard(|| {
+        if out_prompt.is_null() {
+            return LlmReviewStatus::ErrNullArgument;
+        }
+        // SAFETY: checked for NULL above, and the caller guarantees it is writable
+        unsafe { *out_prompt = ptr::null_mut() };
+
+        // SAFETY: the caller guarantees these are NULL or NUL-terminated
+        let prompt = match unsafe { prompt_for(diff, argv, argc) } {
+            Ok(prompt) => prompt,
+            Err(status) => return status,
+        };
+        // SAFETY: as above
+        unsafe { give(prompt, out_prompt) }
+    })
+}
+
+/// Build the review prompt for `diff` and return the review `provider` gives back for it.
+///
+/// `provider` is called once with the prompt and `user_data`. On success `*out_review` is set to a copy of the review that the caller must free with
+/// `llm_review_free_string`; on failure it is set to NULL. `user_data` is passed through
+/// untouched and may be NULL.
+///
+/// ```
+/// use llm_code_review::ffi::*;
+/// use std::ffi::{c_char, c_void, CStr, CString};
+/// use std::ptr;
+///
+/// extern "C" fn provider(_prompt: *const c_char, _user_data: *mut c_void) -> *const c_char {
+///     // A static string outlives the call, as the caller's string must
+///     c"Looks good.".as_ptr()
+/// }
+///
+/// let diff = CString::new("diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-old\n+new\n").unwrap();
+/// let mut review = ptr::null_mut();
+/// let status = unsafe {
+///     llm_review_run(diff.as_ptr(), ptr::null(), 0, Some(provider), ptr::null_mut(), &mut review)
+/// };
+/// assert_eq!(status, LlmReviewStatus::Ok);
+/// assert_eq!(unsafe { CStr::from_ptr(review) }.to_str(), Ok("Looks good."));
+/// unsafe { llm_review_free_string(review) };
+/// ```
+///
+/// # Safety
+///
+/// As for `llm_review_build_prompt`; in addition, the string `provider` returns must be
+/// NUL-terminated and stay valid until this function returns.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn llm_review_run(
+    diff: *const c_char,
+    argv: *const *const c_char,
+    argc: usize,
+    provider: LlmReviewProvider,
+    user_data: *mut c_void,
+    out_review: *mut *mut c_char,
+) -> LlmReviewStatus {
+    guard(|| {
+        if out_review.is_null() {
+            return LlmReviewStatus::ErrNullArgument;
+        }
+        // SAFETY: checked for NULL above, and the caller guarantees it is writable
+        unsafe { *out_review = ptr::null_mut() };
+        let Some(provider) = provider else {
+            return LlmReviewStatus::ErrNullArgument;
+        };
+
+        // SAFETY: the caller guarantees these are NULL or NUL-terminated
+        let prompt = match unsafe { prompt_for(diff, argv, argc) } {
+            Ok(prompt) => prompt,
+            Err(status) => return status,
+        };
+        let Ok(prompt) = CString::new(prompt) else {
+            return LlmReviewStatus::ErrNulInOutput;
+        };
+
+        let review = provider(prompt.as_ptr(), user_data);
+        if review.is_null() {
+            return LlmReviewStatus::ErrProvider;
+        }
+        // SAFETY: the caller guarantees the provider's string is NUL-terminated and still valid
+        let review = match unsafe { CStr::from_ptr(review) }.to_str() {
+            Ok(review) => review.to_string(),
+            Err(_) => return LlmReviewStatus::ErrInvalidUtf8,
+        };
+        // SAFETY: as above
+        unsafe { give(review, out_review) }
+    })
+}
+
+/// Release a string returned by this library. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by this library that hasn't been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn llm_review_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: the caller guarantees `s` came from `CString::into_raw` in `give`
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Run `f`, turning a panic into a status code so it doesn't unwind into foreign code
+fn guard(f: impl FnOnce() -> LlmReviewStatus) -> LlmReviewStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(LlmReviewStatus::ErrInternal)
+}
+
+/// Parse the arguments and build the prompt, or say which status to return
+///
+/// # Safety
+///
+/// `diff` must be NULL or point to a NUL-terminated string, and `argv` NULL or point to `argc`
+/// of them.
+unsafe fn prompt_for(
+    diff: *const c_char,
+    argv: *const *const c_char,
+    argc: usize,
+) -> Result<String, LlmReviewStatus> {
+    if diff.is_null() {
+        return Err(LlmReviewStatus::ErrNullArgument);
+    }
+    // SAFETY: the caller guarantees `diff` is NUL-terminated
+    let diff = unsafe { CStr::from_ptr(diff) }
+        .to_str()
+        .map_err(|_| LlmReviewStatus::ErrInvalidUtf8)?;
+    // SAFETY: as above
+    let options = unsafe { options(argv, argc) }?;
+
+    let args = std::iter::once("llm_code_review").chain(options.iter().copied());
+    let mut cli = Cli::try_parse_from(args).map_err(|e| {
+        debug!("Invalid options {:?}: {}", options, e);
+        LlmReviewStatus::ErrInvalidOptions
+    })?;
+    prepare(&mut cli, Instant::now())
+        .and_then(|_| try_build_prompt(&cli, diff))
+        .map_err(|e: ReviewError| {
+            debug!("Could not build the prompt: {}", e);
+            LlmReviewStatus::ErrPrompt
+        })
+}
+
+/// The `argc` strings in `argv`, none of which may be NULL
+///
+/// # Safety
+///
+/// `argv` must be NULL or point to `argc` pointers, each NULL or to a NUL-terminated string.
+unsafe fn options<'a>(argv: *const *const c_char, argc: usize) -> Result<Vec<&'a str>, LlmReviewStatus> {
+    if argc == 0 {
+        return Ok(Vec::new());
+    }
+    if argv.is_null() {
+        return Err(LlmReviewStatus::ErrNullArgument);
+    }
+    // SAFETY: the caller guarantees `argv` points to `argc` pointers
+    let argv = unsafe { std::slice::from_raw_parts(argv, argc) };
+    argv.iter()
+        .map(|&arg| {
+            if arg.is_null() {
+                return Err(LlmReviewStatus::ErrNullArgument);
+            }
+            // SAFETY: checked for NULL above, and the caller guarantees it is NUL-terminated
+            unsafe { CStr::from_ptr(arg) }
+                .to_str()
+                .map_err(|_| LlmReviewStatus::ErrInvalidUtf8)
+        })
+        .collect()
+}
+
+/// Hand `s` to the caller through `out`
+///
+/// # Safety
+///
+/// `out` must point to writable memory for a pointer.
+unsafe fn give(s: String, out: *mut *mut c_char) -> LlmReviewStatus {
+    match CString::new(s) {
+        Ok(s) => {
+            // SAFETY: the caller guarantees `out` is writable
+            unsafe { *out = s.into_raw() };
+            LlmReviewStatus::Ok
+        }
+        Err(_) => LlmReviewStatus::ErrNulInOutput,
+    }
+}
@@ -0,0 +1,36 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use crate::error::ReviewError;
+
+/// An exclusive lock on a file shared between runs, such as the review history, held until it's
+/// dropped. The lock is taken on a `.lock` file beside the file rather than the file itself, so
+/// the file can still be replaced with `write_atomic` while it's held.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+/// The lock file for `path`: `.history.json.lock` for `.history.json`
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+/// Wait for other runs to let go of `path`, then lock it. The lock file is left behind
+/// afterwards; removing it could let two runs lock different files of the same name.
+pub fn lock(path: &Path) -> Result<FileLock, ReviewError> {
+    let lock_path = lock_path(path);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| ReviewError::Write(lock_path.clone(), e))?;
+    debug!("Locking {}", lock_path.display());
+    file.lock().map_err(|e| ReviewError::Write(lock_path, e))?;
+    Ok(FileLock { _file: file })
+}
@@ -0,0 +1,109 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff::{FileDiff, FileKind};
+use crate::notebook::{blob_ids, fenced, read_blob_or_file};
+use crate::output::language_for_path;
+use crate::vcs::git_output;
+
+/// The default --full-file-max-lines: files longer than this are mostly context the diff's hunks
+/// already give enough of
+pub const DEFAULT_FULL_FILE_MAX_LINES: usize = 2000;
+
+/// What the section says the files are for, ahead of them
+pub const FULL_FILES_INTRO: &str = "The complete new versions of some of the changed files, for the context the diff's \
+hunks leave out. Review the changes in the diff; use these to understand them, not as code to review in their own right.";
+
+/// The new version of a changed file, in full, for --full-files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullFile {
+    pub path: String,
+    pub contents: String,
+}
+
+/// The files in a diff whose new version can be shown in full, with the blob id the diff gives
+/// for it, if any: those not deleted and not binary
+pub fn full_file_candidates(files: &[FileDiff]) -> Vec<(&str, Option<&str>)> {
+    files
+        .iter()
+        .filter(|file| file.kind() != FileKind::Deleted && !file.is_combined())
+        .filter(|file| {
+            if file.is_binary() {
+                info!("Leaving out the binary file {} from the full files", file.path);
+            }
+            !file.is_binary()
+        })
+        .map(|file| (file.path.as_str(), blob_ids(file).and_then(|(_, new)| new)))
+        .collect()
+}
+
+/// The new version of each of `files` as `read` gives it from a blob id and path. Files that
+/// can't be read, turn out to be binary or are longer than `max_lines` lines are left out.
+pub fn full_files(files: &[FileDiff], max_lines: usize, read: impl Fn(Option<&str>, &str) -> Option<String>) -> Vec<FullFile> {
+    let mut full_files = Vec::new();
+    for (path, id) in full_file_candidates(files) {
+        let Some(contents) = read(id, path) else {
+            warn!("Leaving out {} from the full files: it could not be read", path);
+            continue;
+        };
+        if contents.contains('\0') {
+            info!("Leaving out the binary file {} from the full files", path);
+            continue;
+        }
+        let lines = contents.lines().count();
+        if lines > max_lines {
+            info!("Leaving out {} from the full files: its {} lines are more than {}", path, lines, max_lines);
+            continue;
+        }
+        full_files.push(FullFile { path: path.to_string(), contents });
+    }
+    full_files
+}
+
+/// The contents of blob `id`, or of the file at `path` in the working tree when the blob isn't in
+/// the repository or the diff gives none, as for changes not yet committed or staged
+pub fn read_new_version(id: Option<&str>, path: &str) -> Option<String> {
+    match id {
+        Some(id) => read_blob_or_file(id, path),
+        None => {
+            let root = git_output(&["rev-parse", "--show-toplevel"]).map(|root| PathBuf::from(root.trim())).unwrap_or_default();
+            fs::read_to_string(root.join(path)).ok()
+        }
+    }
+}
+
+/// Split `files` into those that fit in `budget` tokens, as `cost` counts them, and those that
+/// don't. The smallest are taken first, so as many files as possible fit; each group keeps the
+/// order `files` were in.
+pub fn pack_files(files: Vec<FullFile>, budget: usize, cost: impl Fn(&FullFile) -> usize) -> (Vec<FullFile>, Vec<FullFile>) {
+    let costs: Vec<usize> = files.iter().map(&cost).collect();
+    let mut order: Vec<usize> = (0..files.len()).collect();
+    order.sort_by_key(|&i| costs[i]);
+    let mut fits = vec![false; files.len()];
+    let mut left = budget;
+    for i in order {
+        if costs[i] <= left {
+            left -= costs[i];
+            fits[i] = true;
+        }
+    }
+    let (included, skipped): (Vec<_>, Vec<_>) = files.into_iter().zip(fits).partition(|(_, fits)| *fits);
+    (included.into_iter().map(|(file, _)| file).collect(), skipped.into_iter().map(|(file, _)| file).collect())
+}
+
+/// One file's part of the section: its path, then its contents in a fenced block
+pub fn full_file_block(file: &FullFile) -> String {
+    let language = language_for_path(Path::new(&file.path)).unwrap_or_default();
+    format!("### {}\n{}\n", file.path, fenced(&file.contents, language))
+}
+
+/// The prompt section giving `files` in full, or `None` if there are none
+pub fn full_files_section(files: &[FullFile]) -> Option<String> {
+    if files.is_empty() {
+        return None;
+    }
+    let blocks: Vec<String> = files.iter().map(full_file_block).collect();
+    Some(format!("{}\n\n{}", FULL_FILES_INTRO, blocks.join("\n")))
+}
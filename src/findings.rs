@@ -0,0 +1,682 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dashboard::escape_html;
+use crate::locale::Locale;
+use crate::output::{unfence, OutputFormat};
+
+/// Findings the model gave less confidence than this are listed apart, as possibly wrong
+pub const LOW_CONFIDENCE: f64 = 0.5;
+
+/// The heading of the section listing low-confidence findings
+const UNSURE_HEADING: &str = "Possibly wrong, please verify";
+
+/// The heading of the appendix of findings over the limits on findings
+const LOWER_PRIORITY_HEADING: &str = "Additional lower-priority notes";
+
+/// How serious a finding is, most serious first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Error, Severity::Warning, Severity::Info];
+    pub const NAMES: [&'static str; 3] = ["error", "warning", "info"];
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Severity::ALL
+            .into_iter()
+            .find(|severity| severity.to_string() == s)
+            .ok_or_else(|| format!("unknown severity `{}`", s))
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One issue raised by a review, tied to a file and optionally a line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    /// `file` in --output-format json's findings
+    #[serde(alias = "file")]
+    pub path: String,
+    pub line: Option<usize>,
+    pub severity: Severity,
+    /// One of the names in the category taxonomy, when the finding was labeled with one
+    #[serde(default)]
+    pub category: Option<String>,
+    pub comment: String,
+    /// Whether the model marked this as work for later that shouldn't hold up the change
+    #[serde(default)]
+    pub follow_up: bool,
+    /// Why the severity isn't what the model said, when a severity policy raised it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_note: Option<String>,
+    /// How likely the model thinks the issue is real, from 0 to 1, when it said
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// Whether this was read out of a review's prose because it wasn't the JSON asked for, so it
+    /// may be incomplete or misplaced
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub extracted: bool,
+    /// Whether this was over --max-findings-per-file or --max-findings, so it's shown apart, after
+    /// the findings that count
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub lower_priority: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Finding {
+    /// Whether the model gave this less than `LOW_CONFIDENCE`
+    pub fn is_unsure(&self) -> bool {
+        self.confidence.is_some_and(|confidence| confidence < LOW_CONFIDENCE)
+    }
+}
+
+/// The findings in any of `categories`, or all of them if no categories are given. Findings
+/// without a category are dropped when filtering.
+pub fn filter_by_category(findings: Vec<Finding>, categories: &[String]) -> Vec<Finding> {
+    if categories.is_empty() {
+        return findings;
+    }
+    findings
+        .into_iter()
+        .filter(|finding| {
+            finding
+                .category
+                .as_ref()
+                .is_some_and(|category| categories.contains(category))
+        })
+        .collect()
+}
+
+/// The findings whose confidence is at least `min`. Findings the model gave no confidence are
+/// kept.
+pub fn filter_by_confidence(findings: Vec<Finding>, min: f64) -> Vec<Finding> {
+    findings
+        .into_iter()
+        .filter(|finding| finding.confidence.is_none_or(|confidence| confidence >= min))
+        .collect()
+}
+
+/// The findings with the ones the model repeated, at the same place with the same comment, made
+/// one. The one kept has the highest severity and confidence of the repeats, and is where the
+/// first of them was.
+pub fn merge_findings(findings: Vec<Finding>) -> Vec<Finding> {
+    let key = |finding: &Finding| {
+        (finding.path.clone(), finding.line, finding.comment.split_whitespace().collect::<Vec<_>>().join(" "))
+    };
+    let mut merged: Vec<Finding> = Vec::new();
+    for finding in findings {
+        let Some(kept) = merged.iter_mut().find(|kept| key(kept) == key(&finding)) else {
+            merged.push(finding);
+            continue;
+        };
+        kept.severity = kept.severity.min(finding.severity);
+        kept.confidence = match (kept.confidence, finding.confidence) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        kept.follow_up &= finding.follow_up;
+        kept.category = kept.category.take().or(finding.category);
+        kept.policy_note = kept.policy_note.take().or(finding.policy_note);
+        kept.extracted &= finding.extracted;
+        kept.lower_priority &= finding.lower_priority;
+    }
+    merged
+}
+
+/// How many findings a review shows before the rest are set apart as lower priority, from
+/// --max-findings-per-file and --max-findings
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FindingLimits {
+    pub per_file: Option<usize>,
+    pub total: Option<usize>,
+}
+
+impl FindingLimits {
+    /// Appended to the system prompt when there's a limit
+    pub fn instructions(&self) -> Option<String> {
+        let limit = match (self.per_file, self.total) {
+            (None, None) => return None,
+            (Some(per_file), None) => format!("at most {} findings for any one file", per_file),
+            (None, Some(total)) => format!("at most {} findings in all", total),
+            (Some(per_file), Some(total)) => {
+                format!("at most {} findings for any one file, and at most {} in all", per_file, total)
+            }
+        };
+        Some(format!(
+            "\nReport {}, so the review stays actionable: choose the most serious, and leave out the rest.\n",
+            limit
+        ))
+    }
+}
+
+/// The findings most important first: by severity, then work for now before follow-ups, then
+/// the model's confidence, counting findings it gave none as certain. Ties are broken by path,
+/// line and comment, so the same findings always come out in the same order.
+pub fn rank_findings(mut findings: Vec<Finding>) -> Vec<Finding> {
+    findings.sort_by(|a, b| {
+        a.severity
+            .cmp(&b.severity)
+            .then(a.follow_up.cmp(&b.follow_up))
+            .then(b.confidence.unwrap_or(1.0).total_cmp(&a.confidence.unwrap_or(1.0)))
+            .then_with(|| a.path.cmp(&b.path))
+            .then(a.line.cmp(&b.line))
+            .then_with(|| a.comment.cmp(&b.comment))
+    });
+    findings
+}
+
+/// The findings ranked by `rank_findings`, with those over `limits` marked lower priority and
+/// moved after the rest, still in order. Without limits the findings are left as they are.
+pub fn cap_findings(findings: Vec<Finding>, limits: &FindingLimits) -> Vec<Finding> {
+    if limits.per_file.is_none() && limits.total.is_none() {
+        return findings;
+    }
+    let mut per_file: BTreeMap<String, usize> = BTreeMap::new();
+    let (mut kept, mut over) = (Vec::new(), Vec::new());
+    for mut finding in rank_findings(findings) {
+        let in_file = per_file.entry(finding.path.clone()).or_default();
+        if limits.per_file.is_some_and(|max| *in_file >= max) || limits.total.is_some_and(|max| kept.len() >= max) {
+            finding.lower_priority = true;
+            over.push(finding);
+        } else {
+            *in_file += 1;
+            kept.push(finding);
+        }
+    }
+    kept.extend(over);
+    kept
+}
+
+/// A finding as --output-format json gives it: the shape CI steps can rely on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonFinding {
+    #[serde(alias = "path")]
+    pub file: String,
+    #[serde(default)]
+    pub line: Option<usize>,
+    pub severity: Severity,
+    pub comment: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// Whether the finding was read out of prose rather than given as JSON
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub extracted: bool,
+    /// Whether the finding was over the limits on findings, so it can wait
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub lower_priority: bool,
+}
+
+impl From<&Finding> for JsonFinding {
+    fn from(finding: &Finding) -> Self {
+        JsonFinding {
+            file: finding.path.clone(),
+            line: finding.line,
+            severity: finding.severity,
+            comment: finding.comment.clone(),
+            confidence: finding.confidence,
+            extracted: finding.extracted,
+            lower_priority: finding.lower_priority,
+        }
+    }
+}
+
+impl From<JsonFinding> for Finding {
+    fn from(finding: JsonFinding) -> Self {
+        Finding {
+            path: finding.file,
+            line: finding.line,
+            severity: finding.severity,
+            category: None,
+            comment: finding.comment,
+            follow_up: false,
+            policy_note: None,
+            confidence: finding.confidence,
+            extracted: finding.extracted,
+            lower_priority: finding.lower_priority,
+        }
+    }
+}
+
+/// The findings in a review written for --output-format json: a JSON array of objects with
+/// `file`, `line`, `severity`, `comment` and perhaps `confidence`, perhaps in a code block. Fails
+/// if it's anything else.
+pub fn parse_json_findings(review: &str) -> Result<Vec<JsonFinding>, String> {
+    let findings: Vec<JsonFinding> = serde_json::from_str(unfence(review.trim())).map_err(|e| e.to_string())?;
+    if let Some(finding) =
+        findings.iter().find(|finding| finding.confidence.is_some_and(|confidence| !(0.0..=1.0).contains(&confidence)))
+    {
+        return Err(format!(
+            "the confidence of the finding in {} is {}, not between 0 and 1",
+            finding.file,
+            finding.confidence.unwrap_or_default()
+        ));
+    }
+    Ok(findings)
+}
+
+/// The findings as --output-format json prints them
+pub fn render_json(findings: &[JsonFinding]) -> String {
+    serde_json::to_string_pretty(findings).unwrap_or_default()
+}
+
+// Anchor of the summary table each file's section links back to
+const SUMMARY_ANCHOR: &str = "findings";
+
+/// Lay out findings grouped by file: a table of files with their finding counts by severity,
+/// then a section per file listing its findings, linking back to the table. Files are sorted by
+/// path and findings by line, then severity, so the same findings always render the same way.
+/// Findings the model was unsure of follow in a section of their own, and repeated findings are
+/// merged first. Findings over the limits on findings come last, in a collapsed appendix, in the
+/// order they were ranked. Formats other than AsciiDoc, MediaWiki, HTML, Jira and JSON get
+/// Markdown. The
+/// headings, table and labels are in `locale`'s language; JSON is the same in every one.
+pub fn render_findings(findings: &[Finding], format: OutputFormat, locale: Locale) -> String {
+    let findings = merge_findings(findings.to_vec());
+    if format == OutputFormat::Json {
+        return render_json(&findings.iter().map(JsonFinding::from).collect::<Vec<_>>());
+    }
+    let (lower, findings): (Vec<&Finding>, Vec<&Finding>) = findings.iter().partition(|finding| finding.lower_priority);
+    let mut by_file: BTreeMap<&str, Vec<&Finding>> = BTreeMap::new();
+    for finding in findings.iter().filter(|finding| !finding.is_unsure()) {
+        by_file.entry(finding.path.as_str()).or_default().push(finding);
+    }
+    for file_findings in by_file.values_mut() {
+        file_findings.sort_by_key(|finding| (finding.line, finding.severity));
+    }
+    let mut unsure: Vec<&Finding> = findings.iter().copied().filter(|finding| finding.is_unsure()).collect();
+    unsure.sort_by_key(|finding| (finding.path.as_str(), finding.line, finding.severity));
+
+    let mut out = match format {
+        OutputFormat::AsciiDoc => render_asciidoc(&by_file, &unsure, locale),
+        OutputFormat::MediaWiki => render_mediawiki(&by_file, &unsure, locale),
+        OutputFormat::Html => render_html(&by_file, &unsure, locale),
+        OutputFormat::Jira => render_jira(&by_file, &unsure, locale),
+        _ => render_markdown(&by_file, &unsure, locale),
+    };
+    if !lower.is_empty() {
+        out.push_str(&render_appendix(&lower, format, locale));
+    }
+    out
+}
+
+/// The findings over the limits, in a section readers open if they want it
+fn render_appendix(lower: &[&Finding], format: OutputFormat, locale: Locale) -> String {
+    let title = format!("{} ({})", locale.text(LOWER_PRIORITY_HEADING), lower.len());
+    match format {
+        OutputFormat::AsciiDoc => {
+            let mut out = format!("\n.{}\n[%collapsible]\n====\n", title);
+            for finding in lower {
+                out.push_str(&format!(
+                    "* *{}* `{}`{}: {}\n",
+                    severity_name(finding.severity, locale),
+                    finding.path,
+                    at_line(finding, locale),
+                    finding.comment
+                ));
+            }
+            out.push_str("====\n");
+            out
+        }
+        OutputFormat::MediaWiki => {
+            let mut out = format!(
+                "\n<div class=\"mw-collapsible mw-collapsed\">\n'''{}'''\n<div class=\"mw-collapsible-content\">\n",
+                title
+            );
+            for finding in lower {
+                out.push_str(&format!(
+                    "* '''{}''' <code>{}</code>{}: {}\n",
+                    severity_name(finding.severity, locale),
+                    finding.path,
+                    at_line(finding, locale),
+                    finding.comment
+                ));
+            }
+            out.push_str("</div>\n</div>\n");
+            out
+        }
+        OutputFormat::Html => {
+            let mut out = format!("\n<details>\n<summary>{}</summary>\n<ul>\n", escape_html(&title));
+            for finding in lower {
+                out.push_str(&format!(
+                    "<li><strong>{}</strong> <code>{}</code>{}: {}</li>\n",
+                    severity_name(finding.severity, locale),
+                    escape_html(&finding.path),
+                    at_line(finding, locale),
+                    escape_html(&finding.comment)
+                ));
+            }
+            out.push_str("</ul>\n</details>\n");
+            out
+        }
+        OutputFormat::Jira => {
+            let mut out = format!("\n{{panel:title={}}}\n", title);
+            for finding in lower {
+                out.push_str(&format!(
+                    "* *{}* {{{{{}}}}}{}: {}\n",
+                    severity_name(finding.severity, locale),
+                    finding.path,
+                    at_line(finding, locale),
+                    finding.comment
+                ));
+            }
+            out.push_str("{panel}\n");
+            out
+        }
+        _ => {
+            let mut out = format!("\n<details>\n<summary>{}</summary>\n\n", title);
+            for finding in lower {
+                out.push_str(&format!(
+                    "- **{}** `{}`{}: {}\n",
+                    severity_name(finding.severity, locale),
+                    finding.path,
+                    at_line(finding, locale),
+                    finding.comment
+                ));
+            }
+            out.push_str("\n</details>\n");
+            out
+        }
+    }
+}
+
+fn render_markdown(by_file: &BTreeMap<&str, Vec<&Finding>>, unsure: &[&Finding], locale: Locale) -> String {
+    let mut out = format!(
+        "<a id=\"{}\"></a>\n## {}\n\n| {} |",
+        SUMMARY_ANCHOR,
+        locale.text("Findings"),
+        locale.text("File")
+    );
+    for severity in Severity::ALL {
+        out.push_str(&format!(" {} |", severity_heading(severity, locale)));
+    }
+    out.push_str("\n|---|---:|---:|---:|\n");
+    for (path, findings) in by_file {
+        out.push_str(&format!("| [`{}`](#{}) |", path, file_anchor(path)));
+        for count in severity_counts(findings) {
+            out.push_str(&format!(" {} |", count));
+        }
+        out.push('\n');
+    }
+
+    for (path, findings) in by_file {
+        out.push_str(&format!("\n<a id=\"{}\"></a>\n### `{}`\n\n", file_anchor(path), path));
+        for finding in findings {
+            out.push_str(&format!("- **{}**{}: {}", severity_name(finding.severity, locale), at_line(finding, locale), finding.comment));
+            if let Some(note) = &finding.policy_note {
+                out.push_str(&format!(" _({})_", note));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("\n[{}](#{})\n", locale.text("Back to summary"), SUMMARY_ANCHOR));
+    }
+    if !unsure.is_empty() {
+        out.push_str(&format!("\n### {}\n\n", locale.text(UNSURE_HEADING)));
+        for finding in unsure {
+            out.push_str(&format!(
+                "- **{}** `{}`{}: {} _({})_\n",
+                severity_name(finding.severity, locale),
+                finding.path,
+                at_line(finding, locale),
+                finding.comment,
+                confidence_note(finding, locale)
+            ));
+        }
+    }
+    out
+}
+
+fn render_asciidoc(by_file: &BTreeMap<&str, Vec<&Finding>>, unsure: &[&Finding], locale: Locale) -> String {
+    let mut out = format!("[[{}]]\n== {}\n\n|===\n|{}", SUMMARY_ANCHOR, locale.text("Findings"), locale.text("File"));
+    for severity in Severity::ALL {
+        out.push_str(&format!(" |{}", severity_heading(severity, locale)));
+    }
+    out.push('\n');
+    for (path, findings) in by_file {
+        out.push_str(&format!("\n|<<{},`{}`>>\n", file_anchor(path), path));
+        for count in severity_counts(findings) {
+            out.push_str(&format!("|{}\n", count));
+        }
+    }
+    out.push_str("|===\n");
+
+    for (path, findings) in by_file {
+        out.push_str(&format!("\n[[{}]]\n=== `{}`\n\n", file_anchor(path), path));
+        for finding in findings {
+            out.push_str(&format!("* *{}*{}: {}", severity_name(finding.severity, locale), at_line(finding, locale), finding.comment));
+            if let Some(note) = &finding.policy_note {
+                out.push_str(&format!(" _({})_", note));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("\n<<{},{}>>\n", SUMMARY_ANCHOR, locale.text("Back to summary")));
+    }
+    if !unsure.is_empty() {
+        out.push_str(&format!("\n=== {}\n\n", locale.text(UNSURE_HEADING)));
+        for finding in unsure {
+            out.push_str(&format!(
+                "* *{}* `{}`{}: {} _({})_\n",
+                severity_name(finding.severity, locale),
+                finding.path,
+                at_line(finding, locale),
+                finding.comment,
+                confidence_note(finding, locale)
+            ));
+        }
+    }
+    out
+}
+
+fn render_mediawiki(by_file: &BTreeMap<&str, Vec<&Finding>>, unsure: &[&Finding], locale: Locale) -> String {
+    let mut out = format!(
+        "<span id=\"{}\"></span>\n== {} ==\n\n{{| class=\"wikitable\"\n! {}",
+        SUMMARY_ANCHOR,
+        locale.text("Findings"),
+        locale.text("File")
+    );
+    for severity in Severity::ALL {
+        out.push_str(&format!(" !! {}", severity_heading(severity, locale)));
+    }
+    out.push('\n');
+    for (path, findings) in by_file {
+        out.push_str(&format!("|-\n| [[#{}|{}]]", file_anchor(path), path));
+        for count in severity_counts(findings) {
+            out.push_str(&format!(" || {}", count));
+        }
+        out.push('\n');
+    }
+    out.push_str("|}\n");
+
+    for (path, findings) in by_file {
+        out.push_str(&format!(
+            "\n<span id=\"{}\"></span>\n=== <code>{}</code> ===\n\n",
+            file_anchor(path),
+            path
+        ));
+        for finding in findings {
+            out.push_str(&format!("* '''{}'''{}: {}", severity_name(finding.severity, locale), at_line(finding, locale), finding.comment));
+            if let Some(note) = &finding.policy_note {
+                out.push_str(&format!(" ''({})''", note));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("\n[[#{}|{}]]\n", SUMMARY_ANCHOR, locale.text("Back to summary")));
+    }
+    if !unsure.is_empty() {
+        out.push_str(&format!("\n=== {} ===\n\n", locale.text(UNSURE_HEADING)));
+        for finding in unsure {
+            out.push_str(&format!(
+                "* '''{}''' <code>{}</code>{}: {} ''({})''\n",
+                severity_name(finding.severity, locale),
+                finding.path,
+                at_line(finding, locale),
+                finding.comment,
+                confidence_note(finding, locale)
+            ));
+        }
+    }
+    out
+}
+
+fn render_html(by_file: &BTreeMap<&str, Vec<&Finding>>, unsure: &[&Finding], locale: Locale) -> String {
+    let mut out = format!(
+        "<h2 id=\"{}\">{}</h2>\n\n<table>\n<tr><th>{}</th>",
+        SUMMARY_ANCHOR,
+        locale.text("Findings"),
+        locale.text("File")
+    );
+    for severity in Severity::ALL {
+        out.push_str(&format!("<th>{}</th>", severity_heading(severity, locale)));
+    }
+    out.push_str("</tr>\n");
+    for (path, findings) in by_file {
+        out.push_str(&format!("<tr><td><a href=\"#{}\"><code>{}</code></a></td>", file_anchor(path), escape_html(path)));
+        for count in severity_counts(findings) {
+            out.push_str(&format!("<td>{}</td>", count));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+
+    for (path, findings) in by_file {
+        out.push_str(&format!("\n<h3 id=\"{}\"><code>{}</code></h3>\n\n<ul>\n", file_anchor(path), escape_html(path)));
+        for finding in findings {
+            out.push_str(&format!(
+                "<li><strong>{}</strong>{}: {}",
+                severity_name(finding.severity, locale),
+                at_line(finding, locale),
+                escape_html(&finding.comment)
+            ));
+            if let Some(note) = &finding.policy_note {
+                out.push_str(&format!(" <em>({})</em>", escape_html(note)));
+            }
+            out.push_str("</li>\n");
+        }
+        out.push_str(&format!("</ul>\n\n<p><a href=\"#{}\">{}</a></p>\n", SUMMARY_ANCHOR, locale.text("Back to summary")));
+    }
+    if !unsure.is_empty() {
+        out.push_str(&format!("\n<h3>{}</h3>\n\n<ul>\n", locale.text(UNSURE_HEADING)));
+        for finding in unsure {
+            out.push_str(&format!(
+                "<li><strong>{}</strong> <code>{}</code>{}: {} <em>({})</em></li>\n",
+                severity_name(finding.severity, locale),
+                escape_html(&finding.path),
+                at_line(finding, locale),
+                escape_html(&finding.comment),
+                confidence_note(finding, locale)
+            ));
+        }
+        out.push_str("</ul>\n");
+    }
+    out
+}
+
+fn render_jira(by_file: &BTreeMap<&str, Vec<&Finding>>, unsure: &[&Finding], locale: Locale) -> String {
+    let mut out = format!("{{anchor:{}}}\nh2. {}\n\n||{}||", SUMMARY_ANCHOR, locale.text("Findings"), locale.text("File"));
+    for severity in Severity::ALL {
+        out.push_str(&format!("{}||", severity_heading(severity, locale)));
+    }
+    out.push('\n');
+    for (path, findings) in by_file {
+        out.push_str(&format!("|[{}|#{}]|", path, file_anchor(path)));
+        for count in severity_counts(findings) {
+            out.push_str(&format!("{}|", count));
+        }
+        out.push('\n');
+    }
+
+    for (path, findings) in by_file {
+        out.push_str(&format!("\n{{anchor:{}}}\nh3. {{{{{}}}}}\n\n", file_anchor(path), path));
+        for finding in findings {
+            out.push_str(&format!("* *{}*{}: {}", severity_name(finding.severity, locale), at_line(finding, locale), finding.comment));
+            if let Some(note) = &finding.policy_note {
+                out.push_str(&format!(" _({})_", note));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("\n[{}|#{}]\n", locale.text("Back to summary"), SUMMARY_ANCHOR));
+    }
+    if !unsure.is_empty() {
+        out.push_str(&format!("\nh3. {}\n\n", locale.text(UNSURE_HEADING)));
+        for finding in unsure {
+            out.push_str(&format!(
+                "* *{}* {{{{{}}}}}{}: {} _({})_\n",
+                severity_name(finding.severity, locale),
+                finding.path,
+                at_line(finding, locale),
+                finding.comment,
+                confidence_note(finding, locale)
+            ));
+        }
+    }
+    out
+}
+
+/// Number of findings of each severity, in `Severity::ALL` order
+fn severity_counts(findings: &[&Finding]) -> Vec<usize> {
+    Severity::ALL
+        .iter()
+        .map(|severity| findings.iter().filter(|finding| finding.severity == *severity).count())
+        .collect()
+}
+
+/// An anchor for the file's section, e.g. `findings-src-lib-rs` for `src/lib.rs`
+fn file_anchor(path: &str) -> String {
+    let slug: String = path
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("{}-{}", SUMMARY_ANCHOR, slug)
+}
+
+fn at_line(finding: &Finding, locale: Locale) -> String {
+    finding
+        .line
+        .map(|line| locale.format(" (line {})", &[&line.to_string()]))
+        .unwrap_or_default()
+}
+
+/// How confident the model was, e.g. `confidence 0.30`
+pub fn confidence_note(finding: &Finding, locale: Locale) -> String {
+    finding
+        .confidence
+        .map(|confidence| locale.format("confidence {}", &[&format!("{:.2}", confidence)]))
+        .unwrap_or_default()
+}
+
+/// The severity as a finding is labelled with, e.g. `error`
+fn severity_name(severity: Severity, locale: Locale) -> String {
+    locale.text(&severity.to_string()).to_string()
+}
+
+/// The severity as a column of the summary table is headed, e.g. `Error`
+fn severity_heading(severity: Severity, locale: Locale) -> String {
+    let name = severity.to_string();
+    let mut chars = name.chars();
+    let capitalized: String = chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect());
+    locale.text(&capitalized).to_string()
+}
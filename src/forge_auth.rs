@@ -0,0 +1,158 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::env;
+use std::fmt;
+use std::io;
+use std::process::Command;
+
+use crate::issues::Forge;
+
+/// What running a forge's command line tool printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A token for a forge's API, and where it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForgeToken {
+    pub token: String,
+    /// `GITHUB_TOKEN`, `gh auth token` and so on, for messages
+    pub source: String,
+}
+
+/// Why no token could be found for a forge.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The environment variable isn't set and the forge's tool isn't installed
+    Missing { var: &'static str, cli: &'static str },
+    /// The forge's tool is installed but isn't logged in to the host
+    NotLoggedIn { cli: &'static str, host: String },
+    /// The forge's tool is too old to print its token; holds what it said
+    Unsupported { cli: &'static str, detail: String },
+    /// The forge's tool failed some other way; holds what it said
+    Failed { cli: &'static str, detail: String },
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Missing { var, cli } => write!(f, "set {} or log in with `{} auth login`", var, cli),
+            AuthError::NotLoggedIn { cli, host } => write!(f, "{} isn't logged in to {}; run `{} auth login`", cli, host, cli),
+            AuthError::Unsupported { cli, detail } => {
+                write!(f, "{} is too old to print its token ({}); upgrade it or set {}", cli, detail, forge_var(cli))
+            }
+            AuthError::Failed { cli, detail } => write!(f, "{} failed: {}", cli, detail),
+        }
+    }
+}
+
+fn forge_var(cli: &str) -> &'static str {
+    if cli == "glab" { "GITLAB_TOKEN" } else { "GITHUB_TOKEN" }
+}
+
+/// The environment variable and the command line tool a token for `forge` can come from
+fn token_sources(forge: Forge) -> (&'static str, &'static str) {
+    match forge {
+        Forge::GitHub => ("GITHUB_TOKEN", "gh"),
+        Forge::GitLab => ("GITLAB_TOKEN", "glab"),
+    }
+}
+
+/// The arguments that make `cli` print its token for `host`. `glab config get token` is older
+/// than `glab auth token` and works in every release.
+fn token_args<'a>(cli: &str, host: &'a str) -> Vec<&'a str> {
+    match cli {
+        "glab" => vec!["config", "get", "token", "--host", host],
+        _ => vec!["auth", "token", "--hostname", host],
+    }
+}
+
+/// Read the token from what `cli` printed for `host`.
+pub fn parse_cli_token(cli: &'static str, host: &str, output: &CliOutput) -> Result<String, AuthError> {
+    let stderr = output.stderr.trim();
+    let lowered = stderr.to_lowercase();
+    if lowered.contains("unknown command") || lowered.contains("unknown flag") || lowered.contains("unknown shorthand flag") {
+        return Err(AuthError::Unsupported { cli, detail: stderr.lines().next().unwrap_or_default().to_string() });
+    }
+    if !output.success {
+        return if lowered.contains("not logged") || lowered.contains("no oauth token") || lowered.contains("auth login") {
+            Err(AuthError::NotLoggedIn { cli, host: host.to_string() })
+        } else {
+            Err(AuthError::Failed { cli, detail: stderr.to_string() })
+        };
+    }
+    // glab prints nothing when it has no token for the host
+    match output.stdout.trim() {
+        "" => Err(AuthError::NotLoggedIn { cli, host: host.to_string() }),
+        token if token.contains(char::is_whitespace) => {
+            Err(AuthError::Unsupported { cli, detail: "it printed something other than a token".to_string() })
+        }
+        token => Ok(token.to_string()),
+    }
+}
+
+/// The token for `forge` on `host`: its environment variable when that's set, and otherwise the
+/// token `gh` or `glab` is logged in with. `var` reads the environment and `run` runs the tool,
+/// returning `None` when it isn't installed.
+pub fn lookup_token(
+    forge: Forge,
+    host: &str,
+    var: impl Fn(&str) -> Option<String>,
+    run: impl Fn(&str, &[&str]) -> Option<io::Result<CliOutput>>,
+) -> Result<ForgeToken, AuthError> {
+    let (name, cli) = token_sources(forge);
+    if let Some(token) = var(name).filter(|token| !token.is_empty()) {
+        return Ok(ForgeToken { token, source: name.to_string() });
+    }
+    let args = token_args(cli, host);
+    let output = match run(cli, &args) {
+        None => return Err(AuthError::Missing { var: name, cli }),
+        Some(Err(e)) => return Err(AuthError::Failed { cli, detail: e.to_string() }),
+        Some(Ok(output)) => output,
+    };
+    let token = parse_cli_token(cli, host, &output)?;
+    Ok(ForgeToken { token, source: format!("{} {}", cli, args[..args.len() - 2].join(" ")) })
+}
+
+/// The token for `forge` on `host` from the environment or the forge's command line tool,
+/// logging which was used.
+pub fn forge_token(forge: Forge, host: &str) -> Result<ForgeToken, AuthError> {
+    let found = lookup_token(forge, host, |name| env::var(name).ok(), run_cli);
+    if let Ok(token) = &found {
+        info!("Authenticating to {} with the token from {}", host, token.source);
+    }
+    found
+}
+
+/// The token for `forge` on `host` where one is optional, as for public repositories: a tool
+/// that's missing or logged out means no token, and any other failure is warned about.
+pub fn optional_forge_token(forge: Forge, host: &str) -> Option<String> {
+    match forge_token(forge, host) {
+        Ok(token) => Some(token.token),
+        Err(e @ (AuthError::Missing { .. } | AuthError::NotLoggedIn { .. })) => {
+            debug!("No token for {}: {}", host, e);
+            None
+        }
+        Err(e) => {
+            warn!("Continuing without a token for {}: {}", host, e);
+            None
+        }
+    }
+}
+
+/// Run `cli` with `args`, or `None` if it isn't installed
+fn run_cli(cli: &str, args: &[&str]) -> Option<io::Result<CliOutput>> {
+    debug!("Running {} {}", cli, args.join(" "));
+    match Command::new(cli).args(args).output() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => Some(Err(e)),
+        Ok(output) => Some(Ok(CliOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })),
+    }
+}
@@ -0,0 +1,426 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::encoding::decode_diff_with;
+use crate::error::ReviewError;
+use crate::forge_auth::optional_forge_token;
+use crate::issues::Forge;
+use crate::network;
+use crate::pr_config::strip_config_block;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+// The largest job log fetch_actions_job_log will read
+const MAX_LOG_BYTES: u64 = 100 * 1024 * 1024;
+
+// open_issue_bodies reads up to this many pages of this many issues
+const MAX_ISSUE_PAGES: usize = 10;
+const ISSUES_PER_PAGE: usize = 100;
+
+/// What a GitHub web URL points at.
+#[derive(Debug, PartialEq)]
+pub enum GitHubTarget {
+    /// `https://github.com/<owner>/<repo>/compare/<base>...<head>`
+    Compare { base: String, head: String },
+    /// `https://github.com/<owner>/<repo>/commit/<sha>`
+    Commit { sha: String },
+}
+
+/// A GitHub compare or commit URL, broken into its parts.
+#[derive(Debug, PartialEq)]
+pub struct GitHubUrl {
+    pub owner: String,
+    pub repo: String,
+    pub target: GitHubTarget,
+}
+
+impl GitHubUrl {
+    /// A short description of what is being reviewed, for the prompt
+    pub fn describe(&self) -> String {
+        match &self.target {
+            GitHubTarget::Compare { base, head } => format!(
+                "GitHub repository {}/{}, comparing `{}` to `{}`",
+                self.owner, self.repo, base, head
+            ),
+            GitHubTarget::Commit { sha } => {
+                format!("GitHub repository {}/{}, commit `{}`", self.owner, self.repo, sha)
+            }
+        }
+    }
+
+    fn api_url(&self) -> String {
+        match &self.target {
+            GitHubTarget::Compare { base, head } => format!(
+                "{}/repos/{}/{}/compare/{}...{}",
+                GITHUB_API_URL, self.owner, self.repo, base, head
+            ),
+            GitHubTarget::Commit { sha } => format!(
+                "{}/repos/{}/{}/commits/{}",
+                GITHUB_API_URL, self.owner, self.repo, sha
+            ),
+        }
+    }
+}
+
+/// Parse a GitHub compare or commit URL. Returns `None` for anything else.
+pub fn parse_github_url(url: &str) -> Option<GitHubUrl> {
+    let re = Regex::new(
+        r"^https?://(?:www\.)?github\.com/([^/]+)/([^/]+)/(compare|commit)/([^?#]+?)(?:\.diff|\.patch)?/?(?:[?#].*)?$",
+    )
+    .unwrap();
+    let caps = re.captures(url.trim())?;
+
+    let target = if &caps[3] == "compare" {
+        // Both `base...head` and `base..head` are accepted by GitHub
+        let (base, head) = caps[4]
+            .split_once("...")
+            .or_else(|| caps[4].split_once(".."))?;
+        if base.is_empty() || head.is_empty() {
+            return None;
+        }
+        GitHubTarget::Compare {
+            base: base.to_string(),
+            head: head.to_string(),
+        }
+    } else {
+        GitHubTarget::Commit {
+            sha: caps[4].to_string(),
+        }
+    };
+
+    Some(GitHubUrl {
+        owner: caps[1].to_string(),
+        repo: caps[2].to_string(),
+        target,
+    })
+}
+
+/// Fetch the unified diff for a compare or commit URL from the GitHub API. Uses `GITHUB_TOKEN`,
+/// or the token `gh` is logged in with, which private repositories need. The request gives up
+/// after `timeout`, if one is given.
+pub fn fetch_github_diff(url: &GitHubUrl, timeout: Option<Duration>) -> Result<String, ReviewError> {
+    let api_url = url.api_url();
+    let mut request = network::get(&api_url)?
+        .config()
+        .timeout_global(timeout)
+        .build()
+        .header("Accept", "application/vnd.github.diff")
+        .header("User-Agent", "llm_code_review");
+
+    let token = optional_forge_token(Forge::GitHub, "github.com");
+    if let Some(token) = &token {
+        request = request.header("Authorization", &format!("Bearer {}", token));
+    }
+
+    debug!("Fetching {}", api_url);
+    match request.call() {
+        // The local repository's git attributes don't apply to a remote diff
+        Ok(mut response) => response
+            .body_mut()
+            .read_to_vec()
+            .map(|bytes| decode_diff_with(&bytes, |_| None))
+            .map_err(|e| ReviewError::GitHub(e.to_string())),
+        Err(ureq::Error::StatusCode(404)) => {
+            let hint = if token.is_some() {
+                "check that the token has access to this repository (the `repo` scope for private repositories)"
+            } else {
+                "if this is a private repository, set GITHUB_TOKEN to a token with the `repo` scope or log in with `gh auth login`"
+            };
+            Err(ReviewError::GitHub(format!(
+                "{}/{} returned 404 Not Found; {}",
+                url.owner, url.repo, hint
+            )))
+        }
+        Err(e) => Err(ReviewError::GitHub(e.to_string())),
+    }
+}
+
+/// Parse a GitHub Actions job URL, `https://github.com/<owner>/<repo>/actions/runs/<run>/job/<job>`,
+/// into the owner, repository and job id.
+pub fn parse_actions_job_url(url: &str) -> Option<(String, String, String)> {
+    let re = Regex::new(r"^https?://(?:www\.)?github\.com/([^/]+)/([^/]+)/actions/runs/\d+/jobs?/(\d+)/?(?:[?#].*)?$").unwrap();
+    let caps = re.captures(url.trim())?;
+    Some((caps[1].to_string(), caps[2].to_string(), caps[3].to_string()))
+}
+
+/// Fetch the log of a GitHub Actions job through the API, which needs `GITHUB_TOKEN`, or `gh`
+/// logged in, with read access to the repository's actions.
+pub fn fetch_actions_job_log(owner: &str, repo: &str, job_id: &str, timeout: Option<Duration>) -> Result<String, ReviewError> {
+    let api_url = format!("{}/repos/{}/{}/actions/jobs/{}/logs", GITHUB_API_URL, owner, repo, job_id);
+    let mut request = network::get(&api_url)?
+        .config()
+        .timeout_global(timeout)
+        .build()
+        .header("User-Agent", "llm_code_review");
+    if let Some(token) = optional_forge_token(Forge::GitHub, "github.com") {
+        request = request.header("Authorization", &format!("Bearer {}", token));
+    }
+
+    debug!("Fetching {}", api_url);
+    request
+        .call()
+        // Logs of long jobs can be over ureq's default 10MB limit
+        .and_then(|mut response| response.body_mut().with_config().limit(MAX_LOG_BYTES).read_to_string())
+        .map_err(|e| ReviewError::GitHub(format!("job {} log: {}", job_id, e)))
+}
+
+/// The REST API for issues on `host`: api.github.com, or `/api/v3` on GitHub Enterprise Server
+pub fn api_base(host: &str) -> String {
+    if host == "github.com" {
+        GITHUB_API_URL.to_string()
+    } else {
+        format!("https://{}/api/v3", host)
+    }
+}
+
+/// The bodies of the open issues in `project` with `label`, reading at most `MAX_ISSUE_PAGES`
+/// pages of them.
+pub fn open_issue_bodies(
+    api: &str,
+    project: &str,
+    label: &str,
+    token: Option<&str>,
+    timeout: Duration,
+) -> Result<Vec<String>, ReviewError> {
+    let url = format!("{}/repos/{}/issues", api, project);
+    let mut bodies = Vec::new();
+    for page in 1..=MAX_ISSUE_PAGES {
+        let mut request = network::get(&url)?
+            .config()
+            .timeout_global(Some(timeout))
+            .build()
+            .query("state", "open")
+            .query("labels", label)
+            .query("per_page", ISSUES_PER_PAGE.to_string())
+            .query("page", page.to_string())
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "llm_code_review");
+        if let Some(token) = token {
+            request = request.header("Authorization", &format!("Bearer {}", token));
+        }
+
+        debug!("Fetching page {} of {}", page, url);
+        let body = request
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| ReviewError::GitHub(format!("listing the issues of {}: {}", project, e)))?;
+        let issues: Vec<Value> = serde_json::from_str(&body)
+            .map_err(|e| ReviewError::GitHub(format!("listing the issues of {}: {}", project, e)))?;
+        bodies.extend(issues.iter().filter_map(|issue| issue["body"].as_str()).map(str::to_string));
+        if issues.len() < ISSUES_PER_PAGE {
+            break;
+        }
+    }
+    Ok(bodies)
+}
+
+/// Open an issue in `project`, returning its URL
+pub fn create_issue(
+    api: &str,
+    project: &str,
+    token: &str,
+    issue: &Value,
+    timeout: Duration,
+) -> Result<String, ReviewError> {
+    let url = format!("{}/repos/{}/issues", api, project);
+    debug!("Creating an issue at {}", url);
+    let body = network::post(&url)?
+        .config()
+        .timeout_global(Some(timeout))
+        .build()
+        .header("Accept", "application/vnd.github+json")
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "llm_code_review")
+        .header("Authorization", &format!("Bearer {}", token))
+        .send(issue.to_string())
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| ReviewError::GitHub(e.to_string()))?;
+    let created: Value = serde_json::from_str(&body).map_err(|e| ReviewError::GitHub(e.to_string()))?;
+    Ok(created["html_url"].as_str().unwrap_or_default().to_string())
+}
+
+/// The REST API a GitHub Actions job talks to, from `GITHUB_API_URL`, which GitHub Enterprise
+/// Server runners set to their own
+pub fn actions_api_url() -> String {
+    env::var("GITHUB_API_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map_or_else(|| GITHUB_API_URL.to_string(), |url| url.trim_end_matches('/').to_string())
+}
+
+fn comments_request<B>(request: ureq::RequestBuilder<B>, token: &str, timeout: Duration) -> ureq::RequestBuilder<B> {
+    request
+        .config()
+        .timeout_global(Some(timeout))
+        .build()
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "llm_code_review")
+        .header("Authorization", &format!("Bearer {}", token))
+}
+
+/// The comments on issue or pull request `number` in `project`, as `(id, body)` pairs, reading at
+/// most `MAX_ISSUE_PAGES` pages of them
+pub fn issue_comments(
+    api: &str,
+    project: &str,
+    number: u64,
+    token: &str,
+    timeout: Duration,
+) -> Result<Vec<(u64, String)>, ReviewError> {
+    let url = format!("{}/repos/{}/issues/{}/comments", api, project, number);
+    let mut comments = Vec::new();
+    for page in 1..=MAX_ISSUE_PAGES {
+        debug!("Fetching page {} of {}", page, url);
+        let body = comments_request(network::get(&url)?, token, timeout)
+            .query("per_page", ISSUES_PER_PAGE.to_string())
+            .query("page", page.to_string())
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| ReviewError::GitHub(format!("listing the comments on #{}: {}", number, e)))?;
+        let page: Vec<Value> = serde_json::from_str(&body)
+            .map_err(|e| ReviewError::GitHub(format!("listing the comments on #{}: {}", number, e)))?;
+        comments.extend(page.iter().filter_map(|comment| {
+            Some((comment["id"].as_u64()?, comment["body"].as_str().unwrap_or_default().to_string()))
+        }));
+        if page.len() < ISSUES_PER_PAGE {
+            break;
+        }
+    }
+    Ok(comments)
+}
+
+/// Add a comment to issue or pull request `number` in `project`
+pub fn create_issue_comment(
+    api: &str,
+    project: &str,
+    number: u64,
+    token: &str,
+    body: &str,
+    timeout: Duration,
+) -> Result<(), ReviewError> {
+    let url = format!("{}/repos/{}/issues/{}/comments", api, project, number);
+    debug!("Commenting at {}", url);
+    comments_request(network::post(&url)?, token, timeout)
+        .header("Content-Type", "application/json")
+        .send(serde_json::json!({ "body": body }).to_string())
+        .map(|_| ())
+        .map_err(|e| ReviewError::GitHub(format!("commenting on #{}: {}", number, e)))
+}
+
+/// Replace the body of comment `id` in `project`
+pub fn update_issue_comment(api: &str, project: &str, id: u64, token: &str, body: &str, timeout: Duration) -> Result<(), ReviewError> {
+    let url = format!("{}/repos/{}/issues/comments/{}", api, project, id);
+    debug!("Updating {}", url);
+    comments_request(network::patch(&url)?, token, timeout)
+        .header("Content-Type", "application/json")
+        .send(serde_json::json!({ "body": body }).to_string())
+        .map(|_| ())
+        .map_err(|e| ReviewError::GitHub(format!("updating comment {}: {}", id, e)))
+}
+
+/// Delete comment `id` in `project`
+pub fn delete_issue_comment(api: &str, project: &str, id: u64, token: &str, timeout: Duration) -> Result<(), ReviewError> {
+    let url = format!("{}/repos/{}/issues/comments/{}", api, project, id);
+    debug!("Deleting {}", url);
+    comments_request(network::delete(&url)?, token, timeout)
+        .call()
+        .map(|_| ())
+        .map_err(|e| ReviewError::GitHub(format!("deleting comment {}: {}", id, e)))
+}
+
+/// Pull request details from a GitHub Actions `pull_request` event payload.
+#[derive(Debug, PartialEq)]
+pub struct PullRequestContext {
+    pub title: String,
+    pub body: Option<String>,
+    pub base_ref: String,
+    pub head_ref: String,
+    pub base_sha: Option<String>,
+    pub head_sha: Option<String>,
+    /// The head before this push, present in `synchronize` events
+    pub before_sha: Option<String>,
+    pub number: Option<u64>,
+    /// `owner/repo`
+    pub repository: Option<String>,
+}
+
+impl PullRequestContext {
+    /// Prompt sections describing the pull request, as `(heading, body)` pairs
+    pub fn sections(&self) -> Vec<(&'static str, String)> {
+        let mut sections = vec![(
+            "Pull Request",
+            format!(
+                "Title: {}\nBase branch: {}\nHead branch: {}",
+                self.title, self.base_ref, self.head_ref
+            ),
+        )];
+        // The settings block is for the tool, not the model
+        if let Some(body) = &self.body
+            && let body = strip_config_block(body)
+            && !body.is_empty()
+        {
+            sections.push(("Pull Request Description", body));
+        }
+        sections
+    }
+}
+
+/// Read the pull request from the event payload GitHub Actions leaves at `GITHUB_EVENT_PATH`.
+/// Returns `None` (with a warning) when the workflow wasn't triggered by a pull request.
+pub fn github_actions_pr_context() -> Result<Option<PullRequestContext>, ReviewError> {
+    let event_name = env::var("GITHUB_EVENT_NAME").unwrap_or_default();
+    if event_name != "pull_request" && event_name != "pull_request_target" {
+        warn!(
+            "GITHUB_EVENT_NAME is {:?}, not pull_request; no pull request context added",
+            event_name
+        );
+        return Ok(None);
+    }
+
+    let path = env::var("GITHUB_EVENT_PATH").map_err(|_| {
+        ReviewError::GitHub("GITHUB_EVENT_PATH is not set; is this running in GitHub Actions?".to_string())
+    })?;
+    load_pr_event(Path::new(&path)).map(Some)
+}
+
+/// Parse the pull request fields out of a GitHub Actions event payload file.
+pub fn load_pr_event(path: &Path) -> Result<PullRequestContext, ReviewError> {
+    let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+    let event: Value = serde_json::from_str(&contents).map_err(|e| {
+        ReviewError::GitHub(format!("could not parse {}: {}", path.display(), e))
+    })?;
+
+    let pull_request = &event["pull_request"];
+    let field = |pointer: &str| {
+        pull_request
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                ReviewError::GitHub(format!(
+                    "{} has no pull_request{} field",
+                    path.display(),
+                    pointer.replace('/', ".")
+                ))
+            })
+    };
+
+    Ok(PullRequestContext {
+        title: field("/title")?,
+        // The body is null when the description is left empty
+        body: field("/body").ok().filter(|body| !body.trim().is_empty()),
+        base_ref: field("/base/ref")?,
+        head_ref: field("/head/ref")?,
+        base_sha: field("/base/sha").ok(),
+        head_sha: field("/head/sha").ok(),
+        before_sha: event["before"].as_str().map(str::to_string),
+        number: pull_request["number"].as_u64(),
+        repository: event.pointer("/repository/full_name").and_then(Value::as_str).map(str::to_string),
+    })
+}
@@ -0,0 +1,89 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::ReviewError;
+use crate::feedback::HISTORY_FILE;
+use crate::threads::THREADS_FILE;
+
+/// A previously saved review, read from the history directory.
+#[derive(Debug, Clone)]
+pub struct PastReview {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub text: String,
+}
+
+/// Read every file in `dir` as a past review, newest first. Unreadable or non-UTF-8 files are
+/// skipped with a warning, as are the conversation store and the review history.
+pub fn load_reviews(dir: &Path) -> Result<Vec<PastReview>, ReviewError> {
+    let entries = fs::read_dir(dir).map_err(|e| ReviewError::Io(dir.to_path_buf(), e))?;
+
+    let mut reviews: Vec<PastReview> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && !path.ends_with(THREADS_FILE) && !path.ends_with(HISTORY_FILE))
+        .filter_map(|path| {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| warn!("Skipping {}: {}", path.display(), e))
+                .ok()?;
+            let modified = fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            Some(PastReview {
+                path,
+                modified,
+                text,
+            })
+        })
+        .collect();
+
+    reviews.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.path.cmp(&b.path)));
+    Ok(reviews)
+}
+
+/// Excerpts from past reviews that mention any of `changed_paths`: the paragraphs naming those
+/// files, from the reviews touching the most of them first and the most recent after that.
+/// Stops before the result would exceed `max_chars`.
+pub fn related_excerpts(reviews: &[PastReview], changed_paths: &[String], max_chars: usize) -> String {
+    let mut related: Vec<(usize, &PastReview)> = reviews
+        .iter()
+        .map(|review| {
+            let overlap = changed_paths
+                .iter()
+                .filter(|path| review.text.contains(path.as_str()))
+                .count();
+            (overlap, review)
+        })
+        .filter(|(overlap, _)| *overlap > 0)
+        .collect();
+    // Stable sort, so reviews with the same overlap stay newest first
+    related.sort_by_key(|(overlap, _)| Reverse(*overlap));
+
+    let mut excerpts = String::new();
+    for (_, review) in related {
+        let paragraphs: Vec<&str> = review
+            .text
+            .split("\n\n")
+            .filter(|paragraph| changed_paths.iter().any(|path| paragraph.contains(path.as_str())))
+            .collect();
+
+        let name = review
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let excerpt = format!("### {}\n{}\n\n", name, paragraphs.join("\n\n").trim());
+
+        if excerpts.len() + excerpt.len() > max_chars {
+            debug!("History budget reached, skipping {}", name);
+            break;
+        }
+        excerpts.push_str(&excerpt);
+    }
+
+    excerpts.trim_end().to_string()
+}
@@ -0,0 +1,247 @@
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+use crate::compress::elided_line_count;
+use crate::diff::{FileDiff, HunkRange};
+use crate::findings::{Finding, Severity};
+
+/// The category findings about CI and build configuration are labeled with
+pub const INFRASTRUCTURE_CATEGORY: &str = "infrastructure";
+
+/// The checks --skip-infra-check can turn off, run on the lines the diff adds to infrastructure
+/// files
+pub const INFRA_CHECKS: &[&str] = &["unpinned-action", "pipe-to-shell", "secret-echo"];
+
+/// The directories whose YAML files are CI pipelines or the actions they use
+const CI_DIRS: &[&str] = &[".github/workflows/", ".github/actions/", ".circleci/", ".buildkite/"];
+
+/// A kind of file that configures how the code is built, tested or shipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfraKind {
+    /// A CI pipeline: GitHub Actions workflows and actions, GitLab CI, CircleCI, Jenkins and the like
+    Ci,
+    /// A Dockerfile, Containerfile or Compose file
+    Container,
+    /// A Makefile or justfile
+    Make,
+    /// A script or file the build runs, such as `build.rs`, `build.gradle` or `scripts/*.sh`
+    BuildScript,
+    /// A file matching an --infra-files glob
+    Configured,
+}
+
+impl fmt::Display for InfraKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InfraKind::Ci => "CI pipeline",
+            InfraKind::Container => "container image",
+            InfraKind::Make => "Makefile",
+            InfraKind::BuildScript => "build script",
+            InfraKind::Configured => "infrastructure file",
+        })
+    }
+}
+
+/// What kind of infrastructure file `path` is, if any. `configured` are --infra-files globs, as
+/// regexes.
+pub fn infra_kind(path: &str, configured: &[Regex]) -> Option<InfraKind> {
+    if configured.iter().any(|glob| glob.is_match(path)) {
+        return Some(InfraKind::Configured);
+    }
+    let lower = path.to_lowercase();
+    let name = lower.rsplit('/').next().unwrap_or_default();
+    let dir = lower.strip_suffix(name).unwrap_or_default();
+    let extension = Path::new(name).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let yaml = matches!(extension, "yml" | "yaml");
+    if (yaml && CI_DIRS.iter().any(|ci| dir.starts_with(ci)))
+        || matches!(
+            name,
+            ".gitlab-ci.yml" | ".travis.yml" | "azure-pipelines.yml" | "bitbucket-pipelines.yml" | "jenkinsfile" | "action.yml" | "action.yaml"
+        )
+    {
+        return Some(InfraKind::Ci);
+    }
+    if name == "dockerfile"
+        || name.starts_with("dockerfile.")
+        || name == "containerfile"
+        || extension == "dockerfile"
+        || (yaml && (name.starts_with("docker-compose") || name.starts_with("compose.")))
+    {
+        return Some(InfraKind::Container);
+    }
+    if matches!(name, "makefile" | "gnumakefile" | "justfile") || extension == "mk" {
+        return Some(InfraKind::Make);
+    }
+    let in_scripts = ["scripts/", "script/", "ci/", "build/"].iter().any(|scripts| dir.ends_with(scripts));
+    if matches!(name, "build.rs" | "build.sh" | "build.gradle" | "build.gradle.kts" | "cmakelists.txt")
+        || (in_scripts && matches!(extension, "sh" | "bash" | "ps1"))
+    {
+        return Some(InfraKind::BuildScript);
+    }
+    None
+}
+
+/// The changed files in `files` that are infrastructure, with their kinds
+pub fn find_infra<'a>(files: &'a [FileDiff], configured: &[Regex]) -> Vec<(&'a str, InfraKind)> {
+    files
+        .iter()
+        .filter(|file| !file.is_binary())
+        .filter_map(|file| infra_kind(&file.path, configured).map(|kind| (file.path.as_str(), kind)))
+        .collect()
+}
+
+/// The lines `file` adds, with their line numbers in the new file
+fn added_lines(file: &FileDiff) -> Vec<(usize, &str)> {
+    let mut added = Vec::new();
+    for hunk in &file.hunks {
+        let Some(range) = HunkRange::parse(&hunk.header) else {
+            continue;
+        };
+        let mut line_number = range.new_start;
+        for line in &hunk.lines {
+            if let Some(text) = line.strip_prefix('+') {
+                added.push((line_number, text));
+                line_number += 1;
+            } else if let Some(count) = elided_line_count(line) {
+                line_number += count;
+            } else if !line.starts_with('-') && !line.starts_with('\\') {
+                line_number += 1;
+            }
+        }
+    }
+    added
+}
+
+/// The local checks, each a name from `INFRA_CHECKS`, the severity of what it finds and a function
+/// giving the problem with a line, if it has one
+type Check = (&'static str, Severity, Box<dyn Fn(&str) -> Option<String>>);
+
+fn checks() -> Vec<Check> {
+    let uses = Regex::new(r#"^\s*(?:-\s*)?uses:\s*["']?([^\s"'#]+)"#).unwrap();
+    let pipe = Regex::new(r"\b(?:curl|wget)\b[^|#]*\|\s*(?:sudo\s+(?:-\S+\s+)*)?(?:env\s+)?(?:ba|z|k|da)?sh\b").unwrap();
+    let echo = Regex::new(r"\b(?:echo|printf|print|Write-Host|Write-Output)\b").unwrap();
+    let secret = Regex::new(
+        r"\$\{\{\s*secrets\.|\$\{?[A-Za-z_]*(?i:secret|token|password|passwd|api_key|apikey|private_key|credential)[A-Za-z_]*\}?",
+    )
+    .unwrap();
+    vec![
+        (
+            "unpinned-action",
+            Severity::Warning,
+            Box::new(move |line: &str| {
+                let action = &uses.captures(line)?[1];
+                // Actions in the repository, and images pinned by digest, can't be moved
+                if action.starts_with("./") || action.contains("@sha256:") {
+                    return None;
+                }
+                let pinned = action
+                    .rsplit_once('@')
+                    .is_some_and(|(_, git_ref)| git_ref.len() == 40 && git_ref.chars().all(|c| c.is_ascii_hexdigit()));
+                (!pinned).then(|| {
+                    format!(
+                        "`{}` isn't pinned to a full commit SHA, so whoever controls its tags and branches decides \
+                         what runs here; pin it to a SHA, with the version in a comment",
+                        action
+                    )
+                })
+            }),
+        ),
+        (
+            "pipe-to-shell",
+            Severity::Warning,
+            Box::new(move |line: &str| {
+                pipe.is_match(line).then(|| {
+                    "Downloads a script and runs it without checking it; a compromised or changed download runs \
+                     with the build's access. Download it, check its checksum or signature, then run it"
+                        .to_string()
+                })
+            }),
+        ),
+        (
+            "secret-echo",
+            Severity::Error,
+            Box::new(move |line: &str| {
+                // Masking a secret, or piping or redirecting it somewhere other than the log, is fine
+                let logged = !line.contains("::add-mask::") && !line.contains('|') && !line.contains('>');
+                (logged && echo.is_match(line) && secret.is_match(line)).then(|| {
+                    "Prints what looks like a secret; log masking only hides the exact value, so any transformed or \
+                     partial copy ends up in the logs"
+                        .to_string()
+                })
+            }),
+        ),
+    ]
+}
+
+/// The problems the local checks find on the lines the diff adds to the `infra` files, except the
+/// `skipped` checks, as findings labeled with `INFRASTRUCTURE_CATEGORY`. Comments are skipped.
+pub fn check_infra(files: &[FileDiff], infra: &[(&str, InfraKind)], skipped: &[String]) -> Vec<Finding> {
+    let checks: Vec<Check> = checks().into_iter().filter(|(name, _, _)| !skipped.iter().any(|skip| skip == name)).collect();
+    let mut findings = Vec::new();
+    for file in files.iter().filter(|file| infra.iter().any(|(path, _)| *path == file.path)) {
+        for (line_number, line) in added_lines(file) {
+            let code = line.trim_start();
+            if code.starts_with('#') || code.starts_with("//") {
+                continue;
+            }
+            for (name, severity, check) in &checks {
+                if let Some(problem) = check(line) {
+                    findings.push(Finding {
+                        path: file.path.clone(),
+                        line: Some(line_number),
+                        severity: *severity,
+                        category: Some(INFRASTRUCTURE_CATEGORY.to_string()),
+                        comment: format!("{} (found by the local `{}` check)", problem, name),
+                        follow_up: false,
+                        policy_note: None,
+                        confidence: None,
+                        extracted: false,
+                        lower_priority: false,
+                    });
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// The prompt section asking for an infrastructure review of `infra`, with the `findings` the
+/// local checks made, which the model is told not to repeat
+pub fn infrastructure_section(infra: &[(&str, InfraKind)], findings: &[Finding]) -> String {
+    let list: Vec<String> = infra.iter().map(|(path, kind)| format!("- {} ({})", path, kind)).collect();
+    let mut section = format!(
+        "The diff changes CI, container or build configuration, which runs on every build with more access than \
+         the code it builds, so a mistake here can break every build or leak secrets:\n{}\n\n\
+         Review these changes for: actions, images and tools pinned to tags or branches rather than commit SHAs or \
+         digests; scripts downloaded and run unchecked; secrets printed to logs, passed on command lines or exposed \
+         to untrusted code, such as pull requests from forks; caches that untrusted runs can write and trusted runs \
+         read; tokens and permissions wider than the job needs; and anything that makes builds flaky or \
+         unreproducible, such as unpinned dependencies, network access during the build or steps that depend on \
+         timing. Label these findings with the category `{}`.",
+        list.join("\n"),
+        INFRASTRUCTURE_CATEGORY
+    );
+    if !findings.is_empty() {
+        section.push_str(&format!(
+            "\n\nThe tool's local checks already report these, so don't repeat them:\n{}",
+            findings
+                .iter()
+                .map(|finding| format!("- {}:{}: {}", finding.path, finding.line.unwrap_or_default(), finding.comment))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+    }
+    section
+}
+
+/// The local checks' `findings` as a Markdown section to follow a review that isn't in JSON
+pub fn local_findings_section(findings: &[Finding]) -> String {
+    let items: Vec<String> = findings
+        .iter()
+        .map(|finding| {
+            format!("- **{}** `{}:{}`: {}", finding.severity, finding.path, finding.line.unwrap_or_default(), finding.comment)
+        })
+        .collect();
+    format!("## Local Checks\n\n{}", items.join("\n"))
+}
@@ -0,0 +1,336 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::time::Duration;
+
+use crate::forge_auth::optional_forge_token;
+use crate::github::api_base;
+use crate::network;
+use crate::truncate::truncate_chars;
+
+/// The most linked issues fetched for one review
+pub const MAX_LINKED_ISSUES: usize = 5;
+
+/// How much of each issue's description goes into the prompt
+pub const ISSUE_DESCRIPTION_MAX_CHARS: usize = 600;
+
+/// How long one issue tracker gets to answer each request
+pub const ISSUE_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A reference to an issue found in a commit message or branch name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IssueRef {
+    /// `#482` or `GH-482`: an issue in the repository `origin` points at
+    Local(u64),
+    /// `owner/repo#482`: an issue in another repository on the same host
+    Project { project: String, number: u64 },
+    /// A Jira key such as `JIRA-123`
+    Jira(String),
+}
+
+impl fmt::Display for IssueRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueRef::Local(number) => write!(f, "#{}", number),
+            IssueRef::Project { project, number } => write!(f, "{}#{}", project, number),
+            IssueRef::Jira(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+/// The issue references in a commit message, in order of first appearance: `#482`, `GH-482`,
+/// `owner/repo#482` and Jira keys such as `JIRA-123`.
+pub fn parse_issue_refs(text: &str) -> Vec<IssueRef> {
+    let re = Regex::new(
+        r"(?:^|[^\w/&#.:-])(?:([\w.-]+/[\w.-]+)#(\d+)|#(\d+)|GH-(\d+)|([A-Z][A-Z0-9]{1,9}-\d+))\b",
+    )
+    .unwrap();
+    let refs = re.captures_iter(text).filter_map(|caps| {
+        let number = |i: usize| caps.get(i).and_then(|m| m.as_str().parse().ok());
+        if let (Some(project), Some(n)) = (caps.get(1), number(2)) {
+            Some(IssueRef::Project { project: project.as_str().to_string(), number: n })
+        } else if let Some(n) = number(3).or_else(|| number(4)) {
+            Some(IssueRef::Local(n))
+        } else {
+            caps.get(5).map(|key| IssueRef::Jira(key.as_str().to_string()))
+        }
+    });
+    dedup(refs)
+}
+
+/// The issue references in a branch name: a leading Jira key (`JIRA-123-fix-timeouts`, as Jira
+/// names branches) or issue number (`482-fix-timeouts`, `issue-482`), after an optional
+/// `feature/` style prefix. Lowercase keys aren't recognised, since `release-2024` would be one.
+pub fn parse_branch_refs(branch: &str) -> Vec<IssueRef> {
+    let name = branch.rsplit('/').next().unwrap_or(branch);
+    let number = Regex::new(r"^(?i:issue-|gh-)?(\d+)(?:[-_]|$)").unwrap();
+    let jira = Regex::new(r"^([A-Z][A-Z0-9]{1,9}-\d+)(?:[-_]|$)").unwrap();
+    if let Some(caps) = number.captures(name) {
+        return caps[1].parse().map(IssueRef::Local).into_iter().collect();
+    }
+    jira.captures(name)
+        .map(|caps| IssueRef::Jira(caps[1].to_string()))
+        .into_iter()
+        .collect()
+}
+
+fn dedup(refs: impl Iterator<Item = IssueRef>) -> Vec<IssueRef> {
+    let mut seen = HashSet::new();
+    refs.filter(|r| seen.insert(r.clone())).collect()
+}
+
+/// An issue fetched from a tracker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedIssue {
+    pub reference: String,
+    pub title: String,
+    pub state: Option<String>,
+    pub description: Option<String>,
+}
+
+/// The issue in a GitHub API `issues/<number>` response
+pub fn parse_github_issue(reference: &IssueRef, json: &Value) -> Option<LinkedIssue> {
+    Some(LinkedIssue {
+        reference: reference.to_string(),
+        title: json["title"].as_str()?.to_string(),
+        state: json["state"].as_str().map(str::to_string),
+        description: json["body"].as_str().map(str::to_string),
+    })
+}
+
+/// The issue in a GitLab API `issues/<iid>` response
+pub fn parse_gitlab_issue(reference: &IssueRef, json: &Value) -> Option<LinkedIssue> {
+    Some(LinkedIssue {
+        reference: reference.to_string(),
+        title: json["title"].as_str()?.to_string(),
+        state: json["state"].as_str().map(str::to_string),
+        description: json["description"].as_str().map(str::to_string),
+    })
+}
+
+/// The issue in a Jira REST API v2 `issue/<key>` response, whose description is plain text
+pub fn parse_jira_issue(reference: &IssueRef, json: &Value) -> Option<LinkedIssue> {
+    let fields = &json["fields"];
+    Some(LinkedIssue {
+        reference: reference.to_string(),
+        title: fields["summary"].as_str()?.to_string(),
+        state: fields.pointer("/status/name").and_then(Value::as_str).map(str::to_string),
+        description: fields["description"].as_str().map(str::to_string),
+    })
+}
+
+/// Which kind of forge a git remote is on, which decides the API used for `#482` references.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+}
+
+/// The host and project (`owner/repo`) a git remote URL points at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remote {
+    pub forge: Forge,
+    pub host: String,
+    pub project: String,
+}
+
+/// Parse `git@host:owner/repo.git`, `ssh://git@host/owner/repo.git` or `https://host/owner/repo`.
+/// Hosts with `gitlab` in their name are GitLab; any other host is taken to be GitHub or GitHub
+/// Enterprise.
+pub fn parse_remote_url(url: &str) -> Option<Remote> {
+    let re = Regex::new(r"^(?:[a-z+]+://)?(?:[^@/]+@)?([^/:]+)(?::\d+)?[:/](.+?)(?:\.git)?/?$").unwrap();
+    let caps = re.captures(url.trim())?;
+    let host = caps[1].to_lowercase();
+    let project = caps[2].trim_start_matches('/').to_string();
+    if !project.contains('/') {
+        return None;
+    }
+    let forge = if host.contains("gitlab") { Forge::GitLab } else { Forge::GitHub };
+    Some(Remote { forge, host, project })
+}
+
+/// The issue trackers configured in the environment, and how to reach them.
+///
+/// - `#482` and `owner/repo#482` go to the forge `origin` is on: GitHub with `GITHUB_TOKEN`, or
+///   GitLab with `GITLAB_TOKEN`. Without those, the token `gh` or `glab` is logged in with is used.
+/// - Jira keys go to `JIRA_BASE_URL` with `JIRA_API_TOKEN`, sent as a bearer token, or with basic
+///   authentication when `JIRA_USER` is set too, as Jira Cloud needs.
+#[derive(Debug, Default)]
+pub struct IssueSources {
+    remote: Option<Remote>,
+    forge_token: Option<String>,
+    jira: Option<(String, String)>,
+    timeout: Duration,
+}
+
+impl IssueSources {
+    /// The sources configured for `remote`, each request giving up after `timeout`
+    pub fn from_env(remote: Option<Remote>, timeout: Duration) -> Self {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        let forge_token = remote.as_ref().and_then(|remote| optional_forge_token(remote.forge, &remote.host));
+        let jira = var("JIRA_BASE_URL").zip(var("JIRA_API_TOKEN")).map(|(url, token)| {
+            let authorization = match var("JIRA_USER") {
+                Some(user) => format!("Basic {}", base64(format!("{}:{}", user, token).as_bytes())),
+                None => format!("Bearer {}", token),
+            };
+            (url.trim_end_matches('/').to_string(), authorization)
+        });
+        IssueSources { remote, forge_token, jira, timeout }
+    }
+
+    /// Whether any tracker is configured
+    pub fn any(&self) -> bool {
+        self.forge_token.is_some() || self.jira.is_some()
+    }
+
+    /// Fetch the first `max` of `refs` that a tracker is configured for. This is best-effort:
+    /// failures are logged and skipped, and a tracker that fails other than by not finding the
+    /// issue isn't asked again, so a slow one costs one timeout at most.
+    pub fn fetch_all(&self, refs: &[IssueRef], max: usize) -> Vec<LinkedIssue> {
+        let mut issues = Vec::new();
+        let (mut forge_failed, mut jira_failed) = (false, false);
+        for reference in refs {
+            let failed = match reference {
+                IssueRef::Jira(_) => &mut jira_failed,
+                _ => &mut forge_failed,
+            };
+            if issues.len() == max || *failed {
+                continue;
+            }
+            match self.fetch(reference) {
+                None => debug!("No issue tracker configured for {}", reference),
+                Some(Ok(issue)) => issues.push(issue),
+                Some(Err(FetchError::NotFound)) => debug!("{} was not found", reference),
+                Some(Err(FetchError::Failed(e))) => {
+                    warn!("Could not fetch {}, leaving out other issues from the same tracker: {}", reference, e);
+                    *failed = true;
+                }
+            }
+        }
+        issues
+    }
+
+    /// Fetch the issue `reference` points at, or `None` when no tracker for it is configured
+    pub fn fetch(&self, reference: &IssueRef) -> Option<Result<LinkedIssue, FetchError>> {
+        let (url, headers, parse): (String, Vec<(&str, String)>, ParseIssue) = match reference {
+            IssueRef::Jira(key) => {
+                let (base_url, authorization) = self.jira.as_ref()?;
+                (
+                    format!("{}/rest/api/2/issue/{}?fields=summary,description,status", base_url, key),
+                    vec![("Authorization", authorization.clone())],
+                    parse_jira_issue,
+                )
+            }
+            IssueRef::Local(number) | IssueRef::Project { number, .. } => {
+                let (remote, token) = self.remote.as_ref().zip(self.forge_token.as_ref())?;
+                let project = match reference {
+                    IssueRef::Project { project, .. } => project,
+                    _ => &remote.project,
+                };
+                match remote.forge {
+                    Forge::GitHub => {
+                        (
+                            format!("{}/repos/{}/issues/{}", api_base(&remote.host), project, number),
+                            vec![
+                                ("Authorization", format!("Bearer {}", token)),
+                                ("Accept", "application/vnd.github+json".to_string()),
+                            ],
+                            parse_github_issue,
+                        )
+                    }
+                    Forge::GitLab => (
+                        format!(
+                            "https://{}/api/v4/projects/{}/issues/{}",
+                            remote.host,
+                            project.replace('/', "%2F"),
+                            number
+                        ),
+                        vec![("PRIVATE-TOKEN", token.clone())],
+                        parse_gitlab_issue,
+                    ),
+                }
+            }
+        };
+        Some(self.get(&url, &headers).and_then(|json| {
+            parse(reference, &json).ok_or_else(|| FetchError::Failed("unexpected response".to_string()))
+        }))
+    }
+
+    fn get(&self, url: &str, headers: &[(&str, String)]) -> Result<Value, FetchError> {
+        let mut request = network::get(url)
+            .map_err(|e| FetchError::Failed(e.to_string()))?
+            .config()
+            .timeout_global(Some(self.timeout))
+            .build()
+            .header("User-Agent", "llm_code_review");
+        for (name, value) in headers {
+            request = request.header(*name, value);
+        }
+        debug!("Fetching {}", url);
+        let body = request
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| match e {
+                ureq::Error::StatusCode(404) => FetchError::NotFound,
+                e => FetchError::Failed(e.to_string()),
+            })?;
+        serde_json::from_str(&body).map_err(|e| FetchError::Failed(e.to_string()))
+    }
+}
+
+/// Why an issue couldn't be fetched.
+#[derive(Debug, PartialEq)]
+pub enum FetchError {
+    /// The tracker has no such issue, as with a Jira-like word that isn't a key, e.g. `UTF-8`
+    NotFound,
+    /// The tracker couldn't be reached or refused the request
+    Failed(String),
+}
+
+type ParseIssue = fn(&IssueRef, &Value) -> Option<LinkedIssue>;
+
+/// The "Linked Issues" section: each issue's reference, state and title, and the start of its
+/// description with whitespace collapsed.
+pub fn linked_issues_section(issues: &[LinkedIssue], max_description_chars: usize) -> Option<String> {
+    if issues.is_empty() {
+        return None;
+    }
+    let lines: Vec<String> = issues
+        .iter()
+        .map(|issue| {
+            let state = issue.state.as_ref().map(|state| format!(" ({})", state)).unwrap_or_default();
+            let mut line = format!("- {}{}: {}", issue.reference, state, issue.title.trim());
+            let description = issue
+                .description
+                .as_deref()
+                .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|text| !text.is_empty());
+            if let Some(description) = description {
+                line.push_str("\n  ");
+                line.push_str(&truncate_chars(&description, max_description_chars));
+            }
+            line
+        })
+        .collect();
+    Some(lines.join("\n"))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
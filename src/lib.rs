@@ -1 +1,89 @@
+pub mod attention;
+pub mod budget;
+pub mod capabilities;
+pub mod canary;
+pub mod category;
+pub mod ci_log;
+pub mod chunk_skip;
+pub mod citations;
+pub mod client;
+pub mod clipboard;
+pub mod comment_series;
+pub mod compress;
+pub mod config;
+pub mod context;
+pub mod context_arbiter;
+pub mod contracts;
+pub mod dashboard;
+pub mod deadline;
+pub mod diff;
+pub mod diff_filter;
+pub mod doctor;
+pub mod draft_issues;
+pub mod encoding;
+pub mod error;
+pub mod feedback;
+pub mod file_lock;
+pub mod files;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod findings;
+pub mod forge_auth;
+pub mod github;
+pub mod history;
+pub mod infra;
+pub mod issues;
+pub mod lint;
+pub mod locale;
+pub mod markers;
+pub mod merges;
+pub mod migrations;
+pub mod naming;
+pub mod network;
+pub mod normalize;
+pub mod notebook;
+pub mod notify;
+pub mod output;
+pub mod partial_clone;
+pub mod persona;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod pr_config;
+pub mod preflight;
+pub mod preprocess;
+pub mod progress;
+pub mod prompt_schema;
+pub mod provenance;
+pub mod provider;
+pub mod quality;
+pub mod questions;
+pub mod rate_limit;
+pub mod rebase;
+pub mod redact;
+pub mod repo_language;
 pub mod review;
+pub mod review_length;
+pub mod review_type;
+pub mod rust_edition;
+pub mod runaway;
+pub mod salvage;
+pub mod sample;
+pub mod selection;
+pub mod severity_policy;
+pub mod size_tier;
+pub mod smoke;
+pub mod stat;
+pub mod status;
+pub mod system_prompt;
+pub mod template;
+pub mod threads;
+pub mod tokens;
+pub mod transcript;
+pub mod truncate;
+pub mod strip;
+pub mod usage;
+pub mod vcs;
+pub mod wizard;
+pub mod workspace;
+pub mod wrap;
+pub mod write_atomic;
@@ -0,0 +1,154 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a linter may run before it's stopped and left out
+pub const LINT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How many diagnostics go into the prompt; the rest are counted
+pub const LINT_MAX_DIAGNOSTICS: usize = 100;
+
+/// The linter --auto-lint runs for each language, writing one `path:line:col: message` line per
+/// diagnostic
+const AUTO_LINT_COMMANDS: &[(&str, &str)] = &[
+    ("go", "go vet ./..."),
+    ("javascript", "npx --no-install eslint --format unix ."),
+    ("python", "ruff check --output-format concise ."),
+    ("ruby", "rubocop --format emacs"),
+    ("rust", "cargo clippy --quiet --message-format=short"),
+    ("typescript", "npx --no-install eslint --format unix ."),
+];
+
+/// The --auto-lint command for `language`, if there is one
+pub fn auto_lint_command(language: &str) -> Option<&'static str> {
+    AUTO_LINT_COMMANDS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(language))
+        .map(|(_, command)| *command)
+}
+
+/// One problem a linter reported.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+/// The diagnostics `command` reports, run as `run_lint` does. A linter exits with an error when it
+/// finds problems, so that only fails if it reported none.
+pub fn lint(command: &str, timeout: Duration, root: &Path) -> Result<Vec<Diagnostic>, String> {
+    let (success, output) = run_lint(command, timeout)?;
+    let diagnostics = parse_diagnostics(&output, root);
+    if !success && diagnostics.is_empty() {
+        let last_line = output.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        return Err(format!("it failed without reporting any problems: {}", last_line.trim()));
+    }
+    Ok(diagnostics)
+}
+
+/// Run `command`, split on whitespace, in the current directory and return whether it succeeded
+/// and what it wrote on standard output and standard error. Fails if it can't be run or takes
+/// longer than `timeout`, in which case it's stopped.
+pub fn run_lint(command: &str, timeout: Duration) -> Result<(bool, String), String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("the command is empty")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => format!("{} is not installed", program),
+            _ => e.to_string(),
+        })?;
+
+    // Read both pipes as the linter writes, so a full pipe can't stall it
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            String::from_utf8_lossy(&bytes).to_string()
+        })
+    };
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("it took longer than {:.1}s", timeout.as_secs_f64()));
+            }
+            None => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    let output = format!("{}{}", stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default());
+    Ok((status.success(), output))
+}
+
+/// The diagnostics in a linter's output, from lines like `src/lib.rs:10:5: warning: unused`. An
+/// absolute path under `root` is made relative to it; other lines are ignored.
+pub fn parse_diagnostics(output: &str, root: &Path) -> Vec<Diagnostic> {
+    let line_regex = Regex::new(r"^(?:\./)?([^\s:][^:]*):(\d+):(?:\d+:)?\s*(.+)$").unwrap();
+    let root = format!("{}/", root.display().to_string().trim_end_matches('/'));
+    output
+        .lines()
+        .filter_map(|line| {
+            let captures = line_regex.captures(line.trim_end())?;
+            let path = &captures[1];
+            Some(Diagnostic {
+                path: path.strip_prefix(&root).unwrap_or(path).to_string(),
+                line: captures[2].parse().ok()?,
+                message: captures[3].trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The diagnostics on the files in `paths`, sorted and without duplicates. A linter run in a
+/// subdirectory gives paths relative to it, so a path matches the end of a changed one too.
+pub fn on_changed_files(diagnostics: Vec<Diagnostic>, paths: &[String]) -> Vec<Diagnostic> {
+    let mut kept: Vec<Diagnostic> = diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            paths
+                .iter()
+                .any(|path| *path == diagnostic.path || path.ends_with(&format!("/{}", diagnostic.path)))
+        })
+        .collect();
+    kept.sort();
+    kept.dedup();
+    kept
+}
+
+/// The section listing what the linters found on the changed files, at most `max` diagnostics,
+/// asking the model not to repeat them. `None` if they found nothing there.
+pub fn lint_section(diagnostics: &[Diagnostic], max: usize) -> Option<String> {
+    if diagnostics.is_empty() {
+        return None;
+    }
+    let mut lines: Vec<String> = diagnostics
+        .iter()
+        .take(max)
+        .map(|diagnostic| format!("{}:{}: {}", diagnostic.path, diagnostic.line, diagnostic.message))
+        .collect();
+    if diagnostics.len() > max {
+        lines.push(format!("[… {} more …]", diagnostics.len() - max));
+    }
+    Some(format!(
+        "The project's linters already report these problems in the changed files. Don't repeat them in the review; look for what a linter can't catch.\n\n```\n{}\n```",
+        lines.join("\n")
+    ))
+}
@@ -0,0 +1,195 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+/// What --review-language takes: a language's code, or `auto` to take it from the locale
+pub const REVIEW_LANGUAGES: [&str; 4] = ["auto", "en", "ja", "de"];
+
+/// The environment variables naming the locale, in the order they take precedence
+const LOCALE_VARS: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Each message the tool writes itself, in English, Japanese and German. The English text is what
+/// a message is looked up by, and what's used for one missing from the table. `{}` marks where
+/// `Locale::format` puts its arguments, in order.
+const MESSAGES: &[(&str, &str, &str)] = &[
+    // Prompt section headings
+    ("PR Code", "PRのコード", "PR-Code"),
+    ("Code to Review", "レビュー対象のコード", "Zu prüfender Code"),
+    ("Additional Context", "追加のコンテキスト", "Zusätzlicher Kontext"),
+    ("Provenance", "出所", "Herkunft"),
+    ("Diff Source", "差分の出所", "Quelle des Diffs"),
+    ("Merge Commits", "マージコミット", "Merge-Commits"),
+    ("Omitted Files", "除外されたファイル", "Ausgelassene Dateien"),
+    ("Representative Sample", "代表的なサンプル", "Repräsentative Auswahl"),
+    ("New TODO Markers", "新しいTODOマーカー", "Neue TODO-Markierungen"),
+    ("Answers to Previous Questions", "前回の質問への回答", "Antworten auf frühere Fragen"),
+    ("Partial Context", "不完全なコンテキスト", "Unvollständiger Kontext"),
+    ("Previous Reviews of These Files", "これらのファイルの過去のレビュー", "Frühere Reviews dieser Dateien"),
+    ("Commit Message", "コミットメッセージ", "Commit-Nachricht"),
+    ("Pull Request", "プルリクエスト", "Pull-Request"),
+    ("Pull Request Description", "プルリクエストの説明", "Beschreibung des Pull-Requests"),
+    ("Part of a Larger Change", "大きな変更の一部", "Teil einer größeren Änderung"),
+    ("Change Summary", "変更の概要", "Zusammenfassung der Änderungen"),
+    ("Packages", "パッケージ", "Pakete"),
+    ("Changes Since the Previous Review", "前回のレビュー以降の変更", "Änderungen seit dem letzten Review"),
+    ("Linked Issues", "関連するイシュー", "Verknüpfte Issues"),
+    ("CI Failures", "CIの失敗", "CI-Fehlschläge"),
+    ("Existing Linter Findings", "リンターの既存の指摘", "Bisherige Linter-Befunde"),
+    ("Related Tests", "関連するテスト", "Zugehörige Tests"),
+    ("Public Contracts", "公開インターフェースの契約", "Öffentliche Schnittstellenverträge"),
+    ("Infrastructure Changes", "インフラの変更", "Infrastrukturänderungen"),
+    ("Database Migrations", "データベースのマイグレーション", "Datenbankmigrationen"),
+    ("Naming Candidates", "命名の候補", "Namenskandidaten"),
+    // Provenance labels
+    ("Repository", "リポジトリ", "Repository"),
+    ("Branch", "ブランチ", "Branch"),
+    ("Merge base", "マージベース", "Merge-Basis"),
+    ("Worktree", "作業ツリー", "Arbeitsverzeichnis"),
+    ("dirty", "未コミットの変更あり", "mit Änderungen"),
+    ("clean", "変更なし", "sauber"),
+    ("Generated", "生成日時", "Erstellt"),
+    ("Provider", "プロバイダー", "Anbieter"),
+    // Omission notes
+    (
+        "The following files were changed but omitted from this review: {}.",
+        "次のファイルは変更されていますが、このレビューからは除外されています: {}。",
+        "Die folgenden Dateien wurden geändert, sind aber nicht Teil dieses Reviews: {}.",
+    ),
+    (" and {} more", " ほか{}件", " und {} weitere"),
+    (
+        "_Chunk {} of {} was skipped, so this file was not reviewed: {}._",
+        "_チャンク {}/{} はスキップされたため、このファイルはレビューされていません: {}_",
+        "_Teil {} von {} wurde übersprungen, daher wurde diese Datei nicht geprüft: {}._",
+    ),
+    (
+        "_Chunk {} of {} was skipped, so these files were not reviewed: {}._",
+        "_チャンク {}/{} はスキップされたため、これらのファイルはレビューされていません: {}_",
+        "_Teil {} von {} wurde übersprungen, daher wurden diese Dateien nicht geprüft: {}._",
+    ),
+    // Rendered findings
+    ("Findings", "指摘事項", "Befunde"),
+    ("File", "ファイル", "Datei"),
+    ("Error", "エラー", "Fehler"),
+    ("Warning", "警告", "Warnung"),
+    ("Info", "情報", "Info"),
+    ("error", "エラー", "Fehler"),
+    ("warning", "警告", "Warnung"),
+    ("info", "情報", "Info"),
+    (" (line {})", " ({}行目)", " (Zeile {})"),
+    ("Back to summary", "概要に戻る", "Zurück zur Übersicht"),
+    ("Possibly wrong, please verify", "誤りの可能性あり、要確認", "Möglicherweise falsch, bitte prüfen"),
+    ("confidence {}", "確信度 {}", "Konfidenz {}"),
+    ("Additional lower-priority notes", "優先度の低いその他の指摘", "Weitere Hinweise mit niedrigerer Priorität"),
+    ("Lower priority: {}", "優先度低: {}", "Niedrigere Priorität: {}"),
+    ("Review {}/{}", "レビュー {}/{}", "Review {}/{}"),
+    (
+        "_Reviewed again by {}, as the review by {} failed the quality checks: {}._",
+        "_{} で再レビューしました。{} のレビューが品質チェックを通らなかったためです: {}_",
+        "_Erneut geprüft von {}, da das Review von {} die Qualitätsprüfung nicht bestanden hat: {}._",
+    ),
+];
+
+/// The language of the text the tool writes itself: the prompt's section headings and notes, and
+/// the headings, tables and labels of the findings it renders. What the tool reads back, such as
+/// the canary, the hidden comment markers and the elided-lines markers, is the same in every one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+    De,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::Ja, Locale::De];
+
+    /// The locale for --review-language `name`, detecting it with `auto`
+    pub fn select(name: &str) -> Result<Locale, String> {
+        match name {
+            "auto" => Ok(Locale::from_env()),
+            name => name.parse(),
+        }
+    }
+
+    /// The locale the environment names, in LC_ALL, LC_MESSAGES or LANG
+    pub fn from_env() -> Locale {
+        Locale::from_vars(|name| env::var(name).ok())
+    }
+
+    /// The locale named by the first of LC_ALL, LC_MESSAGES and LANG that `var` gives a value for,
+    /// e.g. `ja_JP.UTF-8`; English if it's another language, or none is set
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Locale {
+        let Some(value) = LOCALE_VARS.iter().find_map(|name| var(name).filter(|value| !value.is_empty())) else {
+            return Locale::En;
+        };
+        let language = value.split(['_', '.', '@', '-']).next().unwrap_or_default().to_lowercase();
+        language.parse().unwrap_or_default()
+    }
+
+    /// The language's name in English, as the model is asked to write in it
+    pub fn english_name(&self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::Ja => "Japanese",
+            Locale::De => "German",
+        }
+    }
+
+    /// `message`, English text from the table of messages, in this language
+    pub fn text<'a>(&self, message: &'a str) -> &'a str {
+        let Some(&(en, ja, de)) = MESSAGES.iter().find(|(en, _, _)| *en == message) else {
+            return message;
+        };
+        match self {
+            Locale::En => en,
+            Locale::Ja => ja,
+            Locale::De => de,
+        }
+    }
+
+    /// `template`, as `text` looks it up, with each `{}` replaced by the next of `args`
+    pub fn format(&self, template: &str, args: &[&str]) -> String {
+        let mut pieces = self.text(template).split("{}");
+        let mut out = pieces.next().unwrap_or_default().to_string();
+        for (piece, arg) in pieces.zip(args.iter().chain(std::iter::repeat(&""))) {
+            out.push_str(arg);
+            out.push_str(piece);
+        }
+        out
+    }
+
+    /// The instruction to write the review in this language, or `None` for English. What the tool
+    /// reads back out of the review is kept in English.
+    pub fn instructions(&self) -> Option<String> {
+        (*self != Locale::En).then(|| {
+            format!(
+                "\nWrite the review in {}. Keep code, identifiers and file paths as they are, and keep the \
+                 headings, field names and severity names asked for above in English, as tools read them \
+                 back.\n",
+                self.english_name()
+            )
+        })
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+            Locale::De => "de",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.to_string() == s)
+            .ok_or_else(|| format!("unknown review language `{}`; it may be {}", s, REVIEW_LANGUAGES.join(", ")))
+    }
+}
@@ -1,7 +1,16 @@
-use clap::Parser;
-use llm_code_review::review::{run, Cli};
+use llm_code_review::notify;
+use llm_code_review::review::{parse_cli, run};
+use log::error;
+use std::process;
 
 fn main() {
-    let cli = Cli::parse();
-    run(cli);
+    let cli = parse_cli();
+    match run(cli) {
+        Ok(status) => process::exit(status),
+        Err(e) => {
+            error!("{}", e);
+            notify::finished(false, &e.to_string());
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,70 @@
+use regex::Regex;
+use serde::Serialize;
+
+use crate::compress::elided_line_count;
+use crate::diff::{FileDiff, HunkRange};
+
+/// The issue reference `--require-todo-issue` looks for when it isn't given a pattern
+pub const DEFAULT_TODO_ISSUE_PATTERN: &str = r"TODO\(#\d+\)";
+
+/// A TODO, FIXME, HACK or XXX comment on a line added by the diff.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Marker {
+    pub path: String,
+    /// Line number in the new version of the file
+    pub line: usize,
+    /// The added line, trimmed
+    pub text: String,
+}
+
+/// Every marker on an added line of `files`. Removed and context lines are ignored, so markers
+/// that were already there don't count.
+pub fn find_new_markers(files: &[FileDiff]) -> Vec<Marker> {
+    let marker = Regex::new(r"\b(TODO|FIXME|HACK|XXX)\b").unwrap();
+    let mut markers = Vec::new();
+
+    for file in files {
+        for hunk in &file.hunks {
+            let Some(range) = HunkRange::parse(&hunk.header) else {
+                continue;
+            };
+            let mut line_number = range.new_start;
+            for line in &hunk.lines {
+                if let Some(added) = line.strip_prefix('+') {
+                    if marker.is_match(added) {
+                        markers.push(Marker {
+                            path: file.path.clone(),
+                            line: line_number,
+                            text: added.trim().to_string(),
+                        });
+                    }
+                    line_number += 1;
+                } else if let Some(count) = elided_line_count(line) {
+                    line_number += count;
+                } else if !line.starts_with('-') && !line.starts_with('\\') {
+                    line_number += 1;
+                }
+            }
+        }
+    }
+
+    markers
+}
+
+/// The markers whose text doesn't match `issue_pattern`
+pub fn markers_without_issue(markers: &[Marker], issue_pattern: &Regex) -> Vec<Marker> {
+    markers
+        .iter()
+        .filter(|marker| !issue_pattern.is_match(&marker.text))
+        .cloned()
+        .collect()
+}
+
+/// One `path:line: text` line per marker, for the prompt and the log
+pub fn describe_markers(markers: &[Marker]) -> String {
+    markers
+        .iter()
+        .map(|marker| format!("- {}:{}: {}", marker.path, marker.line, marker.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,145 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::error::ReviewError;
+use crate::selection::{combine_commits, parse_log, CombinedCommits, SelectedCommit, LOG_FORMAT};
+use crate::vcs::git_output;
+
+/// How to read a merge's combined diff, placed above the merges' diffs with --include-merges
+pub const COMBINED_DIFF_INSTRUCTIONS: &str = "\
+Each merge's combined diff shows only the places where the merge's result differs from every one \
+of its parents: its conflict resolutions and any changes made in the merge itself. Each line starts \
+with one column per parent. `+` in a parent's column means the line isn't in that parent and was \
+added, `-` that it's in that parent and was removed, and a space that it's unchanged from that \
+parent. So `++` is a line the merge wrote itself, in neither parent; `+ ` a line from the second \
+parent; ` +` a line from the first parent; and `--` a line both parents had that the merge removed. \
+Review these lines as changes made by whoever made the merge.";
+
+/// A merge commit in the range under review.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    pub commit: SelectedCommit,
+    /// The merge's combined diff, as `git show --cc` prints it; empty when it merged cleanly
+    pub combined_diff: String,
+}
+
+impl Merge {
+    /// Whether the merge changed code itself, in resolving conflicts or otherwise, so that its
+    /// result isn't just its parents' changes put together
+    pub fn changes_code(&self) -> bool {
+        !self.combined_diff.trim().is_empty()
+    }
+}
+
+/// The commits in the `git log` `range` that `options` pick out, oldest first
+fn commits_in(range: &str, options: &[&str]) -> Result<Vec<SelectedCommit>, ReviewError> {
+    let mut args = vec!["log", "--reverse", LOG_FORMAT];
+    args.extend(options);
+    args.extend([range, "--"]);
+    Ok(parse_log(&git_output(&args)?))
+}
+
+/// The merge commits in the `git log` `range`, oldest first, with their combined diffs
+pub fn merges_in(range: &str) -> Result<Vec<Merge>, ReviewError> {
+    commits_in(range, &["--merges"])?
+        .into_iter()
+        .map(|commit| {
+            let combined_diff = git_output(&["show", "--cc", "--format=", "--no-color", &commit.sha])?;
+            Ok(Merge { commit, combined_diff })
+        })
+        .collect()
+}
+
+/// What reviewing a range with merge commits in it comes to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeAdaptation {
+    /// The commits on the first-parent line other than the merges, combined into one diff; `None`
+    /// when they can't be, and the whole range is reviewed
+    pub combined: Option<CombinedCommits>,
+    /// The prompt section about the merges
+    pub section: String,
+}
+
+/// Leave the changes merge commits in the `git log` `range` brought in out of the review, with
+/// `--first-parent` semantics: only the other commits on the first-parent line are combined into
+/// the diff. With `include_merges`, the merges' combined diffs go in the section. `None` when
+/// there are no merges in the range.
+pub fn adapt_to_merges(range: &str, include_merges: bool) -> Result<Option<MergeAdaptation>, ReviewError> {
+    let merges = merges_in(range)?;
+    if merges.is_empty() {
+        return Ok(None);
+    }
+    let commits = commits_in(range, &["--first-parent", "--no-merges"])?;
+    let combined = if commits.is_empty() {
+        warn!("The range has only merge commits in it, so the changes they merged in are reviewed");
+        None
+    } else {
+        combine_commits(&commits)
+            .map_err(|e| warn!("Reviewing the changes the merge commits brought in too, as they can't be left out: {}", e))
+            .ok()
+    };
+    if combined.is_some() {
+        warn!(
+            "Leaving out {} merge {} in the range, and the changes merged in by {}",
+            merges.len(),
+            if merges.len() == 1 { "commit" } else { "commits" },
+            if merges.len() == 1 { "it" } else { "them" }
+        );
+    }
+    let evil = merges.iter().filter(|merge| merge.changes_code()).count();
+    if evil > 0 && !include_merges {
+        warn!("{} of the merges change code themselves; --include-merges reviews those changes", evil);
+    }
+    let section = merges_section(&merges, combined.is_some(), include_merges);
+    Ok(Some(MergeAdaptation { combined, section }))
+}
+
+/// The prompt section describing the `merges` in the range: whether the changes they merged in
+/// were `left_out`, which ones change code themselves and, with `include_merges`, their combined
+/// diffs
+pub fn merges_section(merges: &[Merge], left_out: bool, include_merges: bool) -> String {
+    let count = match merges.len() {
+        1 => "1 merge commit".to_string(),
+        n => format!("{} merge commits", n),
+    };
+    let mut section = if left_out {
+        format!(
+            "The {} in the range {} left out: the diff has only the changes made by the other commits on the \
+             first-parent line, not those merged in from other branches.\n",
+            count,
+            if merges.len() == 1 { "is" } else { "are" }
+        )
+    } else {
+        format!("The range has {} in it, and the diff includes the changes merged in from other branches.\n", count)
+    };
+    for merge in merges {
+        let commit = &merge.commit;
+        section.push_str(&format!("\n- {} {} ({})", commit.short(), commit.subject, commit.author));
+        if merge.changes_code() {
+            section.push_str(": changes code itself, in resolving conflicts or otherwise");
+        }
+    }
+
+    let changing: Vec<&Merge> = merges.iter().filter(|merge| merge.changes_code()).collect();
+    if include_merges && !changing.is_empty() {
+        section.push_str(&format!("\n\n{}", COMBINED_DIFF_INSTRUCTIONS));
+        for merge in changing {
+            let diff = merge.combined_diff.trim_end_matches('\n');
+            let mut fence = "```".to_string();
+            while diff.contains(&fence) {
+                fence.push('`');
+            }
+            section.push_str(&format!(
+                "\n\n### Merge {}: {}\n\n{}diff\n{}\n{}",
+                merge.commit.short(),
+                merge.commit.subject,
+                fence,
+                diff,
+                fence
+            ));
+        }
+    } else if !changing.is_empty() {
+        section.push_str("\n\nWhat the merges changed themselves isn't shown.");
+    }
+    section
+}
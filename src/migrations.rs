@@ -0,0 +1,257 @@
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+use crate::compress::elided_line_count;
+use crate::diff::{FileDiff, FileKind, HunkRange};
+use crate::findings::{Finding, Severity};
+
+/// The category findings about database migrations are labeled with
+pub const MIGRATIONS_CATEGORY: &str = "migrations";
+
+/// The name of the local check for dropped tables and columns
+pub const DESTRUCTIVE_CHECK: &str = "destructive-ddl";
+
+/// A comment with this on the line dropping a table or column, or the line before it, says the
+/// data loss is intended, so the local check doesn't report it
+pub const DESTRUCTIVE_GUARD: &str = "allow-destructive";
+
+/// The schema files migration tools keep in step with the migrations, or generate them from
+const SCHEMA_FILES: &[&str] = &["models.py", "schema.rb", "structure.sql", "schema.sql", "schema.prisma", "schema.rs"];
+
+/// The framework a migration belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationKind {
+    /// A Python file in an app's `migrations` directory
+    Django,
+    /// A Ruby file in `db/migrate`
+    Rails,
+    /// A Python file in an Alembic `versions` directory
+    Alembic,
+    /// A SQL file in a `migrations` directory, as sqlx, diesel, Flyway and others keep them
+    Sql,
+    /// A file matching a --migration-files glob
+    Configured,
+}
+
+impl fmt::Display for MigrationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MigrationKind::Django => "Django migration",
+            MigrationKind::Rails => "Rails migration",
+            MigrationKind::Alembic => "Alembic revision",
+            MigrationKind::Sql => "SQL migration",
+            MigrationKind::Configured => "migration",
+        })
+    }
+}
+
+/// What kind of migration `path` is, if any. `configured` are --migration-files globs, as regexes.
+pub fn migration_kind(path: &str, configured: &[Regex]) -> Option<MigrationKind> {
+    if configured.iter().any(|glob| glob.is_match(path)) {
+        return Some(MigrationKind::Configured);
+    }
+    let lower = path.to_lowercase();
+    let name = lower.rsplit('/').next().unwrap_or_default();
+    let dir = lower.strip_suffix(name).unwrap_or_default();
+    let extension = Path::new(name).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let in_dir = |names: &[&str]| dir.split('/').any(|component| names.contains(&component));
+    match extension {
+        "rb" if dir.ends_with("db/migrate/") => Some(MigrationKind::Rails),
+        "py" if dir.ends_with("/versions/") && (dir.contains("alembic/") || dir.contains("migrations/")) => {
+            Some(MigrationKind::Alembic)
+        }
+        "py" if (dir == "migrations/" || dir.ends_with("/migrations/")) && name != "__init__.py" => Some(MigrationKind::Django),
+        "sql" if in_dir(&["migrations", "migration", "migrate"]) => Some(MigrationKind::Sql),
+        _ => None,
+    }
+}
+
+/// Whether `path` holds the schema or models migrations are generated from: a file such as
+/// `models.py`, `schema.rb` or `schema.prisma`, or a Python or Ruby file in a `models` directory
+pub fn is_schema_source(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    let name = lower.rsplit('/').next().unwrap_or_default();
+    let dir = lower.strip_suffix(name).unwrap_or_default();
+    SCHEMA_FILES.contains(&name)
+        || (dir.split('/').any(|component| component == "models") && (name.ends_with(".py") || name.ends_with(".rb")))
+}
+
+/// A changed migration, with the schema and model changes in the same diff it should match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration<'a> {
+    pub path: &'a str,
+    pub kind: MigrationKind,
+    /// Whether the diff adds the file, rather than changing one that may already have run
+    pub added: bool,
+    /// The changed schema and model files it comes from: those beside its migrations directory,
+    /// as a Django app's are, or else all of them
+    pub sources: Vec<&'a FileDiff>,
+}
+
+/// The migrations `files` add or change, each paired with the schema and model changes it comes
+/// from. Deleted and binary files are left out.
+pub fn find_migrations<'a>(files: &'a [FileDiff], configured: &[Regex]) -> Vec<Migration<'a>> {
+    let migrations: Vec<(&FileDiff, MigrationKind)> = files
+        .iter()
+        .filter(|file| !file.is_binary() && file.kind() != FileKind::Deleted)
+        .filter_map(|file| migration_kind(&file.path, configured).map(|kind| (file, kind)))
+        .collect();
+    let sources: Vec<&FileDiff> = files
+        .iter()
+        .filter(|file| is_schema_source(&file.path) && !migrations.iter().any(|(migration, _)| migration.path == file.path))
+        .collect();
+    migrations
+        .into_iter()
+        .map(|(file, kind)| {
+            // The directory holding the migrations directory, such as a Django app's
+            let app = file.path.rsplitn(3, '/').nth(2).map(|app| format!("{}/", app)).unwrap_or_default();
+            let beside: Vec<&FileDiff> = sources.iter().copied().filter(|source| source.path.starts_with(&app)).collect();
+            Migration {
+                path: &file.path,
+                kind,
+                added: file.kind() == FileKind::Added,
+                sources: if beside.is_empty() { sources.clone() } else { beside },
+            }
+        })
+        .collect()
+}
+
+/// The lines of `file`'s hunks on the new side, with their line numbers, whether the diff adds
+/// them, and whether they're in a `down` or `downgrade` function, which undoes the migration
+fn new_lines(file: &FileDiff) -> Vec<(usize, &str, bool, bool)> {
+    let definition = Regex::new(r"^\s*def\s+(?:self\.)?(\w+)").unwrap();
+    let is_down = |name: &str| matches!(name, "down" | "downgrade");
+    let mut lines = Vec::new();
+    for hunk in &file.hunks {
+        let Some(range) = HunkRange::parse(&hunk.header) else {
+            continue;
+        };
+        // The function the hunk starts in, as git names it after the header's ranges
+        let context = hunk.header.splitn(3, "@@").nth(2).unwrap_or_default();
+        let mut down = definition.captures(context).is_some_and(|captures| is_down(&captures[1]));
+        let mut line_number = range.new_start;
+        for line in &hunk.lines {
+            if let Some(count) = elided_line_count(line) {
+                line_number += count;
+                continue;
+            }
+            if line.starts_with('-') || line.starts_with('\\') {
+                continue;
+            }
+            let (text, added) = match line.strip_prefix('+') {
+                Some(text) => (text, true),
+                None => (line.strip_prefix(' ').unwrap_or(line), false),
+            };
+            if let Some(captures) = definition.captures(text) {
+                down = is_down(&captures[1]);
+            }
+            lines.push((line_number, text, added, down));
+            line_number += 1;
+        }
+    }
+    lines
+}
+
+/// The tables and columns the lines the diff adds to `migrations` drop, as findings labeled with
+/// `MIGRATIONS_CATEGORY`. What undoes a migration, in a `down` function or a `.down.sql` file,
+/// comments, and lines guarded by a `DESTRUCTIVE_GUARD` comment on them or the line before are
+/// left out.
+pub fn check_migrations(files: &[FileDiff], migrations: &[Migration]) -> Vec<Finding> {
+    let sql = Regex::new(r"(?i)\bdrop\s+(?:table|column)\b").unwrap();
+    let orm = Regex::new(r"\b(?:migrations\.DeleteModel|migrations\.RemoveField|drop_table|remove_columns?|op\.drop_table|op\.drop_column)\b").unwrap();
+    let mut findings = Vec::new();
+    for file in files.iter().filter(|file| migrations.iter().any(|migration| migration.path == file.path)) {
+        let name = file.path.rsplit('/').next().unwrap_or_default().to_lowercase();
+        if name == "down.sql" || name.ends_with(".down.sql") {
+            continue;
+        }
+        let lines = new_lines(file);
+        for (i, &(line_number, line, added, down)) in lines.iter().enumerate() {
+            let code = line.trim_start();
+            if !added || down || code.starts_with('#') || code.starts_with("--") || code.starts_with("//") {
+                continue;
+            }
+            let guarded = line.contains(DESTRUCTIVE_GUARD)
+                || (i > 0 && lines[i - 1].0 + 1 == line_number && lines[i - 1].1.contains(DESTRUCTIVE_GUARD));
+            let Some(found) = sql.find(line).or_else(|| orm.find(line)) else {
+                continue;
+            };
+            if guarded {
+                continue;
+            }
+            findings.push(Finding {
+                path: file.path.clone(),
+                line: Some(line_number),
+                severity: Severity::Error,
+                category: Some(MIGRATIONS_CATEGORY.to_string()),
+                comment: format!(
+                    "`{}` deletes the data with the table or column, and rolling back won't bring it back; check \
+                     nothing still reads it and the data is kept elsewhere if it's needed, then say it's intended \
+                     with an `{}` comment (found by the local `{}` check)",
+                    found.as_str(),
+                    DESTRUCTIVE_GUARD,
+                    DESTRUCTIVE_CHECK
+                ),
+                follow_up: false,
+                policy_note: None,
+                confidence: None,
+                extracted: false,
+                lower_priority: false,
+            });
+        }
+    }
+    findings
+}
+
+/// The prompt section asking for a review of `migrations` against the schema and model changes
+/// they come from, with the `findings` the local check made, which the model is told not to repeat
+pub fn migrations_section(migrations: &[Migration], findings: &[Finding]) -> String {
+    let list: Vec<String> = migrations
+        .iter()
+        .map(|migration| {
+            let mut item = if migration.added {
+                format!("- {} (new {})", migration.path, migration.kind)
+            } else {
+                format!(
+                    "- {} (changed {}; one that already ran won't run again with the change)",
+                    migration.path, migration.kind
+                )
+            };
+            if migration.sources.is_empty() {
+                item.push_str("\n  - no schema or model change in the diff; check the migration matches them");
+            }
+            for source in &migration.sources {
+                let hunks: Vec<&str> = source.hunks.iter().map(|hunk| hunk.header.as_str()).collect();
+                item.push_str(&format!("\n  - {}: {}", source.path, hunks.join(", ")));
+            }
+            item
+        })
+        .collect();
+    let mut section = format!(
+        "The diff changes database migrations. They are generated from the schema or models, but run against \
+         production data, where a mistake can lose data or lock a busy table for minutes. Each migration, with the \
+         hunks of the schema and model changes it should match, which are in the diff:\n{}\n\n\
+         Review each migration against those changes, for: destructive operations, such as dropped tables or \
+         columns, truncation, and type or length changes that lose data, which need the data copied and the code \
+         that reads it deployed first; DDL that locks a table for long, such as indexes built without \
+         CONCURRENTLY, columns added as NOT NULL without a default or with a volatile one, type changes that \
+         rewrite the table, and constraints or foreign keys validated in the same step; migrations that can't be \
+         reversed, with no down step, a reverse that can't restore the data, or data migrations without one; and \
+         drift, where a migration doesn't do what the schema or model change says, or a schema change has no \
+         migration. Label these findings with the category `{}`.",
+        list.join("\n"),
+        MIGRATIONS_CATEGORY
+    );
+    if !findings.is_empty() {
+        section.push_str(&format!(
+            "\n\nThe tool's local checks already report these, so don't repeat them:\n{}",
+            findings
+                .iter()
+                .map(|finding| format!("- {}:{}: {}", finding.path, finding.line.unwrap_or_default(), finding.comment))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+    }
+    section
+}
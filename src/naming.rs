@@ -0,0 +1,270 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::compress::elided_line_count;
+use crate::diff::{FileDiff, HunkRange};
+use crate::output::language_for_path;
+
+/// The category findings about naming are labeled with
+pub const NAMING_CATEGORY: &str = "naming";
+
+/// The misspellings --check-naming knows, as `wrong->right` lines
+const MISSPELLINGS: &str = include_str!("rsc/misspellings.txt");
+
+/// Declarations any language is checked for
+const GENERIC_DECLARATIONS: &[&str] =
+    &[r"\b(?:fn|func|function|def|class|struct|enum|interface|trait|type|module|const|let|var|val)\s+([A-Za-z_]\w*)"];
+
+/// Declarations by language, as regexes whose first group is the name declared
+const DECLARATIONS: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            r"\b(?:fn|struct|enum|trait|type|const|static|mod|union)\s+([A-Za-z_]\w*)",
+            r"\blet\s+(?:mut\s+)?([A-Za-z_]\w*)",
+            r"^\s*pub(?:\([^)]*\))?\s+([a-z_]\w*)\s*:",
+        ],
+    ),
+    ("python", &[r"\b(?:def|class)\s+([A-Za-z_]\w*)", r"^\s*([A-Za-z_]\w*)\s*(?::[^=]*)?=[^=]"]),
+    (
+        "go",
+        &[r"\bfunc\s+(?:\([^)]*\)\s*)?([A-Za-z_]\w*)", r"\b(?:type|var|const)\s+([A-Za-z_]\w*)", r"([A-Za-z_]\w*)\s*:="],
+    ),
+    ("javascript", &[r"\b(?:function|class|const|let|var)\s+([A-Za-z_$][\w$]*)"]),
+    ("typescript", &[r"\b(?:function|class|const|let|var|interface|type|enum)\s+([A-Za-z_$][\w$]*)"]),
+    ("ruby", &[r"\b(?:def|class|module)\s+(?:self\.)?([A-Za-z_]\w*[?!]?)"]),
+    (
+        "java",
+        &[
+            r"\b(?:class|interface|enum|record)\s+([A-Za-z_]\w*)",
+            r"\b(?:public|private|protected|static|final)\s+[\w<>\[\], ]+?\s+([A-Za-z_]\w*)\s*[(=;]",
+        ],
+    ),
+    ("kotlin", &[r"\b(?:class|interface|object|fun|val|var)\s+([A-Za-z_]\w*)"]),
+    ("swift", &[r"\b(?:class|struct|enum|protocol|func|let|var)\s+([A-Za-z_]\w*)"]),
+];
+
+/// Languages where single quotes delimit strings, rather than characters or lifetimes
+const SINGLE_QUOTED_STRINGS: &[&str] = &["javascript", "typescript", "python", "ruby", "php", "shell", "bash"];
+
+/// A name or string the diff adds that may be misspelled or inconsistently cased.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NamingCandidate {
+    pub path: String,
+    /// Line number in the new version of the file
+    pub line: usize,
+    /// The identifier, or the string literal with its quotes
+    pub text: String,
+    /// What looks wrong with it
+    pub problem: String,
+}
+
+/// Known misspellings and their corrections.
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    corrections: HashMap<String, String>,
+}
+
+impl Default for Dictionary {
+    fn default() -> Self {
+        Dictionary::parse(MISSPELLINGS)
+    }
+}
+
+impl Dictionary {
+    /// Read `wrong->right` lines, skipping blank lines and `#` comments
+    pub fn parse(contents: &str) -> Self {
+        let corrections = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once("->"))
+            .map(|(wrong, right)| (wrong.trim().to_lowercase(), right.trim().to_string()))
+            .collect();
+        Dictionary { corrections }
+    }
+
+    /// The correction for `word`, if it's a known misspelling
+    pub fn correction(&self, word: &str) -> Option<&str> {
+        self.corrections.get(&word.to_lowercase()).map(String::as_str)
+    }
+
+    /// The misspelled words in `words`, each with its correction
+    fn misspellings<'a>(&'a self, words: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+        words.iter().filter_map(|word| self.correction(word).map(|right| (*word, right))).collect()
+    }
+}
+
+/// The names declared on `line`, for a file in `language` (as `language_for_path` names them)
+pub fn declared_identifiers(language: Option<&str>, line: &str) -> Vec<String> {
+    declaration_regexes(language)
+        .iter()
+        .flat_map(|regex| regex.captures_iter(line).map(|captures| captures[1].to_string()).collect::<Vec<_>>())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn declaration_regexes(language: Option<&str>) -> Vec<Regex> {
+    let patterns = DECLARATIONS
+        .iter()
+        .find(|(name, _)| Some(*name) == language)
+        .map_or(GENERIC_DECLARATIONS, |(_, patterns)| *patterns);
+    patterns.iter().map(|pattern| Regex::new(pattern).unwrap()).collect()
+}
+
+/// The string literals on `line`, with their quotes
+pub fn string_literals(language: Option<&str>, line: &str) -> Vec<String> {
+    let pattern = if language.is_some_and(|language| SINGLE_QUOTED_STRINGS.contains(&language)) {
+        r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#
+    } else {
+        r#""(?:[^"\\]|\\.)*""#
+    };
+    Regex::new(pattern).unwrap().find_iter(line).map(|m| m.as_str().to_string()).collect()
+}
+
+/// The words an identifier is made of: `parseHTTPResponse_v2` is `parse`, `HTTP`, `Response` and
+/// `v`. Digits separate words and are dropped.
+pub fn identifier_words(identifier: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = identifier.char_indices().collect();
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphabetic() {
+            if let Some(begin) = start.take() {
+                words.push(&identifier[begin..offset]);
+            }
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| chars[i].1);
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        // A new word starts at an uppercase letter after a lowercase one, or at the last capital
+        // of an acronym followed by a lowercase letter
+        let boundary = c.is_uppercase()
+            && previous.is_some_and(|previous| {
+                previous.is_lowercase() || (previous.is_uppercase() && next.is_some_and(char::is_lowercase))
+            });
+        match start {
+            Some(begin) if boundary => {
+                words.push(&identifier[begin..offset]);
+                start = Some(offset);
+            }
+            Some(_) => {}
+            None => start = Some(offset),
+        }
+    }
+    if let Some(begin) = start {
+        words.push(&identifier[begin..]);
+    }
+    words
+}
+
+/// `identifier` with case and separators ignored, to find names differing only in those
+fn folded(identifier: &str) -> String {
+    identifier.chars().filter(|c| *c != '_' && *c != '-').flat_map(char::to_lowercase).collect()
+}
+
+/// Whether two names are written in the same style: both with underscores or neither, and
+/// starting with the same case. `UserId` and `user_id` are different styles and may both be
+/// right; `UserId` and `UserID` are the same style, cased two ways.
+fn same_style(a: &str, b: &str) -> bool {
+    let style = |name: &str| (name.contains('_'), name.chars().next().is_some_and(char::is_uppercase));
+    style(a) == style(b)
+}
+
+/// The names declared and string literals on the lines `files` add that look misspelled, or
+/// that are cased differently from a name already in the file. `existing` gives a file's contents
+/// in the new version, when they can be read, for the names it had before; otherwise only the
+/// diff's own context and removed lines are looked at.
+pub fn check_naming(
+    files: &[FileDiff],
+    existing: impl Fn(&str) -> Option<String>,
+    dictionary: &Dictionary,
+) -> Vec<NamingCandidate> {
+    let word = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+    let mut candidates = Vec::new();
+    for file in files.iter().filter(|file| !file.is_binary()) {
+        let language = language_for_path(Path::new(&file.path));
+        let mut added = Vec::new();
+        let mut old_names = BTreeSet::new();
+        for hunk in &file.hunks {
+            let Some(range) = HunkRange::parse(&hunk.header) else {
+                continue;
+            };
+            let mut line_number = range.new_start;
+            for line in &hunk.lines {
+                if let Some(text) = line.strip_prefix('+') {
+                    added.push((line_number, text));
+                    line_number += 1;
+                } else if let Some(count) = elided_line_count(line) {
+                    line_number += count;
+                } else if let Some(text) = line.strip_prefix('-').or_else(|| line.strip_prefix(' ')) {
+                    old_names.extend(word.find_iter(text).map(|m| m.as_str().to_string()));
+                    if line.starts_with(' ') {
+                        line_number += 1;
+                    }
+                }
+            }
+        }
+        if added.is_empty() {
+            continue;
+        }
+        if let Some(contents) = existing(&file.path) {
+            let added_lines: BTreeSet<usize> = added.iter().map(|(line, _)| *line).collect();
+            for (i, text) in contents.lines().enumerate() {
+                if !added_lines.contains(&(i + 1)) {
+                    old_names.extend(word.find_iter(text).map(|m| m.as_str().to_string()));
+                }
+            }
+        }
+
+        let mut seen = BTreeSet::new();
+        let mut push = |line: usize, text: &str, problem: String| {
+            if seen.insert((text.to_string(), problem.clone())) {
+                candidates.push(NamingCandidate { path: file.path.clone(), line, text: text.to_string(), problem });
+            }
+        };
+        for (line, text) in &added {
+            for name in declared_identifiers(language, text) {
+                if old_names.contains(&name) {
+                    continue;
+                }
+                for (wrong, right) in dictionary.misspellings(&identifier_words(&name)) {
+                    push(*line, &name, format!("`{}` may be a misspelling of `{}`", wrong, right));
+                }
+                let folded_name = folded(&name);
+                if let Some(other) =
+                    old_names.iter().find(|old| folded(old) == folded_name && same_style(old, &name))
+                {
+                    push(*line, &name, format!("cased differently from `{}`, already in the file", other));
+                }
+            }
+            for literal in string_literals(language, text) {
+                let words: Vec<&str> = literal.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).collect();
+                for (wrong, right) in dictionary.misspellings(&words) {
+                    push(*line, &literal, format!("`{}` may be a misspelling of `{}`", wrong, right));
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// The prompt section asking the model to confirm or dismiss `candidates`
+pub fn naming_section(candidates: &[NamingCandidate]) -> String {
+    let list: Vec<String> = candidates
+        .iter()
+        .map(|candidate| format!("- {}:{}: {}: {}", candidate.path, candidate.line, candidate.text, candidate.problem))
+        .collect();
+    format!(
+        "A local spelling and naming check flagged these new names and strings:\n{}\n\n\
+         The check only knows common misspellings and compares casing with names already in the file, so it can be \
+         wrong. For each, say whether it's a real problem, and if so, report it as a finding labeled with the category \
+         `{}`. Dismiss the rest briefly, such as deliberate spellings, words from another language or names that \
+         follow an outside API.",
+        list.join("\n"),
+        NAMING_CATEGORY
+    )
+}
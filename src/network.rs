@@ -0,0 +1,68 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use ureq::config::Config;
+use ureq::typestate::{WithBody, WithoutBody};
+use ureq::unversioned::resolver::DefaultResolver;
+use ureq::unversioned::transport::Connector;
+use ureq::{Agent, RequestBuilder};
+
+use crate::error::ReviewError;
+use crate::rate_limit::pace;
+
+// Set once by `go_offline`, for --offline, and read before every request
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+// The agent every request is made with, set up the first time one is made
+static AGENT: OnceLock<Agent> = OnceLock::new();
+
+/// Refuse every request from now on, with `ReviewError::OfflineModeViolation`
+pub fn go_offline() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Whether `go_offline` was called
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Make requests through `connector` rather than ureq's TCP and TLS, as tests do to see every
+/// connection. Only works before the first request; returns whether it did.
+pub fn set_transport(connector: impl Connector) -> bool {
+    AGENT.set(Agent::with_parts(config(), connector, DefaultResolver::default())).is_ok()
+}
+
+/// The settings every request is made with: ureq's defaults, paced by the hosts' rate limits
+fn config() -> Config {
+    Config::builder().middleware(pace).build()
+}
+
+/// The agent to send a request to `url` with, or `OfflineModeViolation` after `go_offline`
+fn agent(url: &str) -> Result<&'static Agent, ReviewError> {
+    if is_offline() {
+        debug!("Refusing to connect to {} with --offline", url);
+        return Err(ReviewError::OfflineModeViolation(url.to_string()));
+    }
+    Ok(AGENT.get_or_init(|| config().new_agent()))
+}
+
+/// A GET request for `url`; every part of the tool that reads from the network starts here
+pub fn get(url: &str) -> Result<RequestBuilder<WithoutBody>, ReviewError> {
+    Ok(agent(url)?.get(url))
+}
+
+/// A POST request to `url`
+pub fn post(url: &str) -> Result<RequestBuilder<WithBody>, ReviewError> {
+    Ok(agent(url)?.post(url))
+}
+
+/// A PATCH request to `url`
+pub fn patch(url: &str) -> Result<RequestBuilder<WithBody>, ReviewError> {
+    Ok(agent(url)?.patch(url))
+}
+
+/// A DELETE request for `url`
+pub fn delete(url: &str) -> Result<RequestBuilder<WithoutBody>, ReviewError> {
+    Ok(agent(url)?.delete(url))
+}
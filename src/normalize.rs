@@ -0,0 +1,107 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::output::{unfence, OutputFormat};
+
+/// Lines a model opens with before the review itself, such as "Sure! Here is my review:"
+const LEADING_CHATTER: &str = r"(?i)^(sure|certainly|of course|absolutely|okay)\b[^\n]*$|^(here('s| is| are)|below (is|are)|i('ve| have) reviewed|after reviewing)\b[^\n]*(:|\breview\b[^\n]*[.!])\s*$";
+
+/// Lines a model closes with after the review, such as "Let me know if you have any questions!"
+const TRAILING_CHATTER: &str = r"(?i)^(let me know|please let me know|i hope this helps|hope this helps|feel free to (ask|reach out|let me know)|if you have any (other |more |further )?(questions|concerns)|happy to help)\b[^\n]*$";
+
+/// The longest line taken for chatter; anything longer is part of the review
+const MAX_CHATTER_CHARS: usize = 200;
+
+/// The keys a model puts a review under when it answers with a JSON object by mistake
+const REVIEW_KEYS: &[&str] = &["review", "content", "text", "markdown", "output"];
+
+/// Tidy up a review in `format` before it's rendered: drop the chatter around it, close a code
+/// block left open, take it out of a code block or JSON string wrapped round all of it, and trim
+/// it. A Markdown review is only taken out of an untagged or `markdown` code block, since a
+/// review may be nothing but a code block of the language reviewed.
+pub fn normalize_review(review: &str, format: Option<OutputFormat>) -> String {
+    let review = strip_chatter(review);
+    let review = close_fences(&review);
+    let review = match format {
+        None | Some(OutputFormat::Markdown) => unfence_markdown(&review),
+        Some(_) => unfence(&review),
+    };
+    let review = match format {
+        Some(OutputFormat::Json) => review.to_string(),
+        _ => unwrap_json_string(review),
+    };
+    review.trim().to_string()
+}
+
+/// `review` without the lines of chatter before and after it, and the blank lines around those
+pub fn strip_chatter(review: &str) -> String {
+    let (leading, trailing) = (Regex::new(LEADING_CHATTER).unwrap(), Regex::new(TRAILING_CHATTER).unwrap());
+    let is_chatter = |re: &Regex, line: &str| line.len() <= MAX_CHATTER_CHARS && re.is_match(line.trim());
+    let mut lines: Vec<&str> = review.lines().collect();
+    loop {
+        while lines.first().is_some_and(|line| line.trim().is_empty()) {
+            lines.remove(0);
+        }
+        match lines.first() {
+            Some(line) if is_chatter(&leading, line) => lines.remove(0),
+            _ => break,
+        };
+    }
+    loop {
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        match lines.last() {
+            Some(line) if is_chatter(&trailing, line) => lines.pop(),
+            _ => break,
+        };
+    }
+    lines.join("\n")
+}
+
+/// `review` with a code block that's still open at the end closed, as a response cut short or a
+/// forgotten fence leaves it, so the rest of the page doesn't render as code
+pub fn close_fences(review: &str) -> String {
+    let mut open: Option<usize> = None;
+    for line in review.lines() {
+        let trimmed = line.trim_start();
+        let ticks = trimmed.len() - trimmed.trim_start_matches('`').len();
+        if ticks < 3 {
+            continue;
+        }
+        match open {
+            None => open = Some(ticks),
+            // Only a bare fence at least as long closes the block
+            Some(len) if ticks >= len && trimmed.trim_end().len() == ticks => open = None,
+            Some(_) => {}
+        }
+    }
+    match open {
+        Some(len) => format!("{}\n{}", review.trim_end(), "`".repeat(len)),
+        None => review.to_string(),
+    }
+}
+
+/// The inside of `review` when all of it is one untagged or `markdown` code block
+fn unfence_markdown(review: &str) -> &str {
+    let info = review.trim_start().lines().next().unwrap_or("").trim_start_matches('`').trim();
+    match info {
+        "" | "markdown" | "md" => unfence(review),
+        _ => review,
+    }
+}
+
+/// The review inside `review` when it's a JSON string, or a JSON object holding it under one of
+/// `REVIEW_KEYS`, as a model confused about the format may answer; otherwise `review` as it is
+pub fn unwrap_json_string(review: &str) -> String {
+    match serde_json::from_str::<Value>(review.trim()) {
+        Ok(Value::String(text)) => text,
+        Ok(Value::Object(object)) => REVIEW_KEYS
+            .iter()
+            .find_map(|key| object.get(*key).and_then(Value::as_str))
+            .map_or_else(|| review.to_string(), str::to_string),
+        _ => review.to_string(),
+    }
+}
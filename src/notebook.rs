@@ -0,0 +1,189 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::diff::{Diff, FileDiff, Hunk};
+use crate::vcs::{git_command, git_output};
+
+/// The extension of Jupyter notebooks
+pub const NOTEBOOK_EXTENSION: &str = ".ipynb";
+
+/// Whether `path` is a Jupyter notebook
+pub fn is_notebook(path: &str) -> bool {
+    path.to_lowercase().ends_with(NOTEBOOK_EXTENSION)
+}
+
+/// A notebook's cells as readable text: markdown cells as they are, code cells as fenced code
+/// blocks in the notebook's language, and each code cell's outputs summarized in a line, so
+/// base64 images and long results don't reach the prompt. Cells aren't numbered, so adding one
+/// doesn't change the text of every cell after it.
+pub fn notebook_text(json: &str) -> Result<String, String> {
+    let notebook: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let cells = notebook["cells"].as_array().ok_or("no cells; only nbformat 4 notebooks can be read")?;
+    let language = notebook
+        .pointer("/metadata/language_info/name")
+        .or_else(|| notebook.pointer("/metadata/kernelspec/language"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    let mut blocks = Vec::new();
+    for cell in cells {
+        let source = joined(&cell["source"]);
+        let block = match cell["cell_type"].as_str() {
+            Some("code") => {
+                let mut block = format!("## Code cell\n\n{}", fenced(&source, language));
+                let outputs = cell["outputs"].as_array().map(Vec::as_slice).unwrap_or_default();
+                if !outputs.is_empty() {
+                    block.push_str(&format!("\n\nOutput: {}", summarize_outputs(outputs)));
+                }
+                block
+            }
+            Some("markdown") => format!("## Markdown cell\n\n{}", source.trim_end()),
+            _ => format!("## Raw cell\n\n{}", fenced(&source, "")),
+        };
+        blocks.push(block);
+    }
+    Ok(format!("{}\n", blocks.join("\n\n")))
+}
+
+/// A notebook string, which may be split into an array of lines
+fn joined(value: &Value) -> String {
+    match value {
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        value => value.as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// `code` in a fenced block, with a fence longer than any run of backticks in it
+pub fn fenced(code: &str, language: &str) -> String {
+    let mut fence = "```".to_string();
+    while code.contains(&fence) {
+        fence.push('`');
+    }
+    format!("{}{}\n{}\n{}", fence, language, code.trim_end_matches('\n'), fence)
+}
+
+/// What a code cell's outputs are, in a line: `3 lines of stdout, image/png (12 KB)`
+fn summarize_outputs(outputs: &[Value]) -> String {
+    let mut parts = Vec::new();
+    for output in outputs {
+        match output["output_type"].as_str() {
+            Some("stream") => {
+                let lines = joined(&output["text"]).lines().count();
+                let name = output["name"].as_str().unwrap_or("output");
+                parts.push(format!("{} line{} of {}", lines, if lines == 1 { "" } else { "s" }, name));
+            }
+            Some("error") => parts.push(format!(
+                "error {}: {}",
+                output["ename"].as_str().unwrap_or_default(),
+                output["evalue"].as_str().unwrap_or_default()
+            )),
+            _ => {
+                let Some(data) = output["data"].as_object() else {
+                    continue;
+                };
+                // A rich result comes with a plain text version too, which is left out for it
+                let rich: Vec<_> = data.iter().filter(|(mime, _)| *mime != "text/plain").collect();
+                if rich.is_empty() {
+                    let lines = joined(&data["text/plain"]).lines().count();
+                    parts.push(format!("{} line{} of text", lines, if lines == 1 { "" } else { "s" }));
+                }
+                for (mime, value) in rich {
+                    let content = joined(value);
+                    if mime.starts_with("image/") && mime != "image/svg+xml" {
+                        // Base64 takes 4 characters for every 3 bytes
+                        let bytes = content.chars().filter(|c| !c.is_whitespace()).count() * 3 / 4;
+                        parts.push(format!("{} ({} KB)", mime, bytes.div_ceil(1024)));
+                    } else {
+                        parts.push(mime.to_string());
+                    }
+                }
+            }
+        }
+    }
+    parts.join(", ")
+}
+
+/// The hunks of a diff between the text of two versions of a notebook, either of which may be
+/// missing, with `context` lines of context. The hunks are made by `git diff --no-index`.
+pub fn notebook_hunks(old: Option<&str>, new: Option<&str>, context: usize) -> Result<Vec<Hunk>, String> {
+    let text = |json: Option<&str>| json.map_or(Ok(String::new()), notebook_text);
+    let (old, new) = (text(old)?, text(new)?);
+    // Each conversion gets its own directory, as notebooks may be converted on several threads
+    static CONVERSIONS: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "llm_code_review_notebook_{}_{}",
+        std::process::id(),
+        CONVERSIONS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let (old_path, new_path) = (dir.join("old"), dir.join("new"));
+    fs::write(&old_path, old).and_then(|_| fs::write(&new_path, new)).map_err(|e| e.to_string())?;
+
+    let output = git_command()
+        .args(["diff", "--no-index", "--no-color", "--no-ext-diff", &format!("-U{}", context)])
+        .arg(&old_path)
+        .arg(&new_path)
+        .output();
+    let _ = fs::remove_dir_all(&dir);
+    let output = output.map_err(|e| e.to_string())?;
+    // git diff --no-index exits with 1 when the files differ
+    if output.status.code().is_none_or(|code| code > 1) {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let diff = String::from_utf8_lossy(&output.stdout);
+    Ok(Diff::parse(&diff).files.into_iter().flat_map(|file| file.hunks).collect())
+}
+
+/// The old and new blob ids from a git diff's `index` line; `None` for a side the file is missing
+/// from, whose id is all zeros
+pub fn blob_ids(file: &FileDiff) -> Option<(Option<&str>, Option<&str>)> {
+    let ids = file.header.iter().find_map(|line| line.strip_prefix("index "))?;
+    let (old, new) = ids.split_whitespace().next()?.split_once("..")?;
+    let present = |id: &&str| !id.chars().all(|c| c == '0');
+    Some((Some(old).filter(present), Some(new).filter(present)))
+}
+
+/// Replace the hunks of each notebook in `diff` with a diff between the readable text of its two
+/// versions. `read` gives the contents of a blob id, and the file at a path in the working tree
+/// for when the id is of changes that aren't in a commit or the index. A notebook that can't be
+/// read or converted keeps its JSON diff, with a warning.
+pub fn convert_notebooks(diff: &str, context: usize, read: impl Fn(&str, &str) -> Option<String>) -> String {
+    let mut parsed = Diff::parse(diff);
+    let mut converted = 0;
+    for file in parsed.files.iter_mut().filter(|file| is_notebook(&file.path) && !file.is_binary()) {
+        let Some((old_id, new_id)) = blob_ids(file) else {
+            continue;
+        };
+        let old_path = file.old_path().unwrap_or(&file.path).to_string();
+        let old = old_id.map(|id| read(id, &old_path).ok_or(id));
+        let new = new_id.map(|id| read(id, &file.path).ok_or(id));
+        let hunks = match (old.transpose(), new.transpose()) {
+            (Ok(old), Ok(new)) => notebook_hunks(old.as_deref(), new.as_deref(), context),
+            (Err(id), _) | (_, Err(id)) => Err(format!("could not read {}", id)),
+        };
+        match hunks {
+            Ok(hunks) => {
+                file.hunks = hunks;
+                converted += 1;
+            }
+            Err(e) => warn!("Reviewing the JSON of {}, which could not be converted: {}", file.path, e),
+        }
+    }
+    if converted > 0 {
+        info!("Showed {} notebooks as their cells, with outputs summarized", converted);
+    }
+    parsed.render()
+}
+
+/// The contents of blob `id`, or else of the file at `path` in the working tree, which is where a
+/// diff of uncommitted, unstaged changes has its new version
+pub fn read_blob_or_file(id: &str, path: &str) -> Option<String> {
+    git_output(&["cat-file", "-p", id]).ok().or_else(|| {
+        let root = git_output(&["rev-parse", "--show-toplevel"]).ok()?;
+        fs::read_to_string(PathBuf::from(root.trim()).join(path)).ok()
+    })
+}
@@ -0,0 +1,68 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::env;
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set once by `enable`, for --notify, and read wherever the run can end
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Send a notification from `finished` from now on
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// If notifications are enabled, send a desktop notification that the run finished, falling back
+/// to ringing the terminal bell. Never fails: problems are logged at debug level.
+pub fn finished(success: bool, message: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let title = if success {
+        "llm_code_review finished"
+    } else {
+        "llm_code_review failed"
+    };
+    if let Err(e) = desktop_notification(title, message) {
+        debug!("Could not send a desktop notification ({}), ringing the bell instead", e);
+        // stdout may be the prompt, so ring on stderr
+        let _ = io::stderr().write_all(b"\x07");
+        let _ = io::stderr().flush();
+    }
+}
+
+fn desktop_notification(title: &str, message: &str) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(message),
+            applescript_string(title)
+        );
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else if cfg!(unix) {
+        if env::var_os("DISPLAY").is_none() && env::var_os("WAYLAND_DISPLAY").is_none() {
+            return Err("no graphical session".to_string());
+        }
+        let mut command = Command::new("notify-send");
+        command.args([title, message]);
+        command
+    } else {
+        return Err("desktop notifications aren't supported on this platform".to_string());
+    };
+
+    debug!("Running command: {:?}", command);
+    let status = command.status().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("notifier exited with {}", status))
+    }
+}
+
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,370 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Values accepted by `--output-format`: the names of `OutputFormat::ALL`, so the two can't differ
+pub const OUTPUT_FORMATS: &[&str] = &{
+    let mut names = [""; OutputFormat::ALL.len()];
+    let mut i = 0;
+    while i < names.len() {
+        names[i] = OutputFormat::ALL[i].name();
+        i += 1;
+    }
+    names
+};
+
+/// What --output-format json asks for: the shape of the JSON array of findings
+pub const JSON_OUTPUT_SCHEMA: &str = include_str!("rsc/prompt/json_output.txt");
+
+/// Values accepted by `--github-annotation-level`
+pub const ANNOTATION_LEVELS: &[&str] = &["error", "warning", "notice"];
+
+/// How each markup format writes a heading, and an example of one, for telling the formats apart
+const HEADING_STYLES: &[(&str, &str, &str)] = &[
+    ("Markdown", r"^#{1,6} \S", "# Title"),
+    ("AsciiDoc", r"^=+ \S(.*[^=\s])?\s*$", "= Title"),
+    ("MediaWiki", r"^=+ .*\S =+\s*$", "== Title =="),
+    ("Jira", r"^h[1-6]\. \S", "h1. Title"),
+];
+
+/// How each language writes a review comment for `--output-format docstring-comments`
+const DOCSTRING_COMMENT_FORMATS: &[(&str, &str)] = &[
+    ("bash", "# REVIEW(line N): <comment>"),
+    ("c", "// REVIEW(line N): <comment>"),
+    ("cpp", "// REVIEW(line N): <comment>"),
+    ("csharp", "// REVIEW(line N): <comment>"),
+    ("go", "// REVIEW(line N): <comment>"),
+    ("haskell", "-- REVIEW(line N): <comment>"),
+    ("java", "// REVIEW(line N): <comment>"),
+    ("javascript", "// REVIEW(line N): <comment>"),
+    ("kotlin", "// REVIEW(line N): <comment>"),
+    ("lua", "-- REVIEW(line N): <comment>"),
+    ("perl", "# REVIEW(line N): <comment>"),
+    ("php", "// REVIEW(line N): <comment>"),
+    ("python", "# REVIEW(line N): <comment>"),
+    ("ruby", "# REVIEW(line N): <comment>"),
+    ("rust", "// REVIEW(line N): <comment>"),
+    ("shell", "# REVIEW(line N): <comment>"),
+    ("sql", "-- REVIEW(line N): <comment>"),
+    ("swift", "// REVIEW(line N): <comment>"),
+    ("typescript", "// REVIEW(line N): <comment>"),
+    ("yaml", "# REVIEW(line N): <comment>"),
+];
+
+/// Language names, as used by `--language`, for common file extensions
+const LANGUAGE_EXTENSIONS: &[(&str, &str)] = &[
+    ("bash", "bash"),
+    ("c", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("cs", "csharp"),
+    ("go", "go"),
+    ("h", "c"),
+    ("hpp", "cpp"),
+    ("hs", "haskell"),
+    ("java", "java"),
+    ("js", "javascript"),
+    ("kt", "kotlin"),
+    ("lua", "lua"),
+    ("php", "php"),
+    ("pl", "perl"),
+    ("py", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sh", "shell"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("ts", "typescript"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+];
+
+/// The level of a GitHub Actions annotation, which decides how it is shown and whether it fails
+/// the check.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AnnotationLevel {
+    Error,
+    #[default]
+    Warning,
+    Notice,
+}
+
+impl FromStr for AnnotationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(AnnotationLevel::Error),
+            "warning" => Ok(AnnotationLevel::Warning),
+            "notice" => Ok(AnnotationLevel::Notice),
+            _ => Err(format!("unknown annotation level `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for AnnotationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AnnotationLevel::Error => "error",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Notice => "notice",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Markdown,
+    AsciiDoc,
+    MediaWiki,
+    /// An HTML fragment, to go inside a page
+    Html,
+    /// Jira's wiki markup
+    Jira,
+    DocstringComments,
+    /// GitHub Actions workflow commands, with the default annotation level
+    GitHubActions(AnnotationLevel),
+    /// A JSON array of findings, checked before it's printed
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OutputFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown output format `{}`", s))
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl OutputFormat {
+    /// Every format, with GitHub Actions at the default annotation level
+    pub const ALL: [OutputFormat; 8] = [
+        OutputFormat::Markdown,
+        OutputFormat::AsciiDoc,
+        OutputFormat::MediaWiki,
+        OutputFormat::Html,
+        OutputFormat::Jira,
+        OutputFormat::DocstringComments,
+        OutputFormat::GitHubActions(AnnotationLevel::Warning),
+        OutputFormat::Json,
+    ];
+
+    /// The name --output-format takes for the format
+    pub const fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::AsciiDoc => "asciidoc",
+            OutputFormat::MediaWiki => "mediawiki",
+            OutputFormat::Html => "html",
+            OutputFormat::Jira => "jira",
+            OutputFormat::DocstringComments => "docstring-comments",
+            OutputFormat::GitHubActions(_) => "github-actions",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// Set the default annotation level for `GitHubActions`. Other formats are returned as-is.
+    pub fn with_annotation_level(self, level: AnnotationLevel) -> Self {
+        match self {
+            OutputFormat::GitHubActions(_) => OutputFormat::GitHubActions(level),
+            other => other,
+        }
+    }
+
+    /// The instruction appended to the system prompt to request this format. `language` picks the
+    /// comment syntax for `DocstringComments`.
+    pub fn instructions(&self, language: Option<&str>) -> String {
+        match self {
+            OutputFormat::DocstringComments => {
+                let comment_format = language.and_then(docstring_comment_format);
+                if let (Some(language), None) = (language, comment_format) {
+                    warn!("No comment format known for language {:?}", language);
+                }
+                let comment_format = comment_format.unwrap_or(
+                    "REVIEW(line N): <comment>, using the comment syntax of the file's language",
+                );
+                format!(
+                    "\nOutput the review as inline code comments, one per issue, each formatted as `{}` where N is the line number in the new version of the file. Group the comments by file.\n",
+                    comment_format
+                )
+            }
+            OutputFormat::GitHubActions(level) => format!(
+                "\nOutput the review as GitHub Actions workflow commands and nothing else, one line per issue, each formatted as `::{level} file=PATH,line=N::MESSAGE` where PATH is the file's path in the diff and N is the line number in the new version of the file. Use `{level}` as the level unless an issue clearly calls for another: `error` for problems that must be fixed before merging, `warning` for likely problems, `notice` for minor suggestions.\n",
+                level = level
+            ),
+            OutputFormat::Json => format!("\n{}", JSON_OUTPUT_SCHEMA),
+            OutputFormat::Html => "\nOutput the review in HTML format, as a fragment to go inside a page's <body>.\n".to_string(),
+            OutputFormat::Jira => "\nOutput the review in Jira wiki markup.\n".to_string(),
+            _ => format!("\nOutput the review in {:?} format.\n", self),
+        }
+    }
+}
+
+/// The inside of `text` when all of it is one fenced code block, or else `text` as it is
+pub fn unfence(text: &str) -> &str {
+    let trimmed = text.trim();
+    let Some(opening) = trimmed.lines().next().filter(|line| line.starts_with("```")) else {
+        return text;
+    };
+    let fence_len = opening.len() - opening.trim_start_matches('`').len();
+    let Some(rest) = trimmed.strip_suffix(&"`".repeat(fence_len)) else {
+        return text;
+    };
+    let inner = rest[opening.len()..].trim_matches('\n');
+    // Another fence inside means this was more than one block
+    if trimmed.lines().count() < 2 || inner.lines().any(|line| line.trim_start().starts_with(&"`".repeat(fence_len))) {
+        return text;
+    }
+    inner
+}
+
+/// The lines of `text` outside fenced code blocks, where a review's own markup is
+fn prose_lines(text: &str) -> Vec<&str> {
+    let mut in_fence = false;
+    text.lines()
+        .filter(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                return false;
+            }
+            !in_fence
+        })
+        .collect()
+}
+
+/// How many headings of each of `HEADING_STYLES` `review` has outside code blocks, with the
+/// style's name and example
+fn heading_counts(review: &str) -> Vec<(&'static str, usize, &'static str)> {
+    let lines = prose_lines(review);
+    HEADING_STYLES
+        .iter()
+        .map(|&(name, pattern, example)| {
+            let re = Regex::new(pattern).unwrap();
+            (name, lines.iter().filter(|line| re.is_match(line)).count(), example)
+        })
+        .collect()
+}
+
+/// What's missing of the structure every review in `format` has, or `None` if nothing is: a
+/// heading in Markdown, a `= Title` or section heading in AsciiDoc, and an `== Section ==` in
+/// MediaWiki. The other formats aren't checked.
+pub fn missing_structure(review: &str, format: &OutputFormat) -> Option<String> {
+    let name = match format {
+        OutputFormat::Markdown => "Markdown",
+        OutputFormat::AsciiDoc => "AsciiDoc",
+        OutputFormat::MediaWiki => "MediaWiki",
+        _ => return None,
+    };
+    let (_, count, example) = heading_counts(review).into_iter().find(|(style, _, _)| *style == name)?;
+    (count == 0).then(|| format!("no {} headings (`{}`)", name, example))
+}
+
+/// How `review` doesn't look like `format`, going by its headings and markup, or `None` if it
+/// does. Code blocks are skipped, so a quoted `# comment` isn't taken for a heading. JSON is
+/// checked by parsing it instead, and docstring comments have nothing to tell them by.
+pub fn format_mismatch(review: &str, format: &OutputFormat) -> Option<String> {
+    let headings = heading_counts(review);
+    let foreign = |own: usize, own_name: &str| {
+        headings
+            .iter()
+            .filter(|&&(name, count, _)| name != own_name && count > own)
+            .map(|(name, count, example)| format!("{} {} heading{} (`{}`)", count, name, if *count == 1 { "" } else { "s" }, example))
+            .collect::<Vec<_>>()
+    };
+    let mut problems = Vec::new();
+    match format {
+        OutputFormat::Markdown | OutputFormat::AsciiDoc | OutputFormat::MediaWiki | OutputFormat::Jira => {
+            let name = match format {
+                OutputFormat::Markdown => "Markdown",
+                OutputFormat::AsciiDoc => "AsciiDoc",
+                OutputFormat::MediaWiki => "MediaWiki",
+                _ => "Jira",
+            };
+            let &(_, own, example) = headings.iter().find(|(style, _, _)| *style == name).unwrap();
+            let found = foreign(own, name);
+            if !found.is_empty() {
+                problems.push(format!("{}, but {} {} ones (`{}`)", found.join(" and "), own, name, example));
+            }
+            if *format == OutputFormat::Jira && review.lines().any(|line| line.trim_start().starts_with("```")) {
+                problems.push("Markdown code fences, where Jira's are {code}".to_string());
+            }
+        }
+        OutputFormat::Html => {
+            let tag = Regex::new(r"(?i)</?(h[1-6]|p|ul|ol|li|div|table|pre|code|strong|em|br)\b").unwrap();
+            if !tag.is_match(review) {
+                let found = foreign(0, "HTML");
+                problems.push(if found.is_empty() { "no HTML tags".to_string() } else { format!("no HTML tags, and {}", found.join(" and ")) });
+            }
+        }
+        OutputFormat::GitHubActions(_) => {
+            let other = review.lines().filter(|line| !line.trim().is_empty() && !line.starts_with("::")).count();
+            if other > 0 {
+                problems.push(format!("{} line{} workflow commands (`::warning file=PATH,line=N::MESSAGE`)", other, if other == 1 { " that isn't one of the" } else { "s that aren't" }));
+            }
+        }
+        OutputFormat::Json | OutputFormat::DocstringComments => {}
+    }
+    (!problems.is_empty()).then(|| problems.join("; "))
+}
+
+/// Whether `response`, a review in `format`, flags any issue, for --fail-on-issues. JSON counts
+/// its findings, GitHub Actions its `::error` and `::warning` commands, and docstring comments
+/// their `REVIEW(line N)` comments. Prose is searched for an `[ISSUE]` marker or a high or
+/// critical severity, as in `severity: high`. A SARIF log, in any format, counts the `results` of
+/// its runs.
+pub fn has_review_issues(response: &str, format: &OutputFormat) -> bool {
+    let json: Option<serde_json::Value> = serde_json::from_str(unfence(response).trim()).ok();
+    if let Some(runs) = json.as_ref().and_then(|json| json.get("runs")).and_then(|runs| runs.as_array()) {
+        return runs.iter().any(|run| run["results"].as_array().is_some_and(|results| !results.is_empty()));
+    }
+    match format {
+        OutputFormat::Json => match &json {
+            Some(serde_json::Value::Array(findings)) => !findings.is_empty(),
+            Some(json) => json["findings"].as_array().is_some_and(|findings| !findings.is_empty()),
+            None => issue_marker(response),
+        },
+        OutputFormat::GitHubActions(_) => {
+            response.lines().any(|line| line.starts_with("::error") || line.starts_with("::warning"))
+        }
+        OutputFormat::DocstringComments => response.contains("REVIEW(line"),
+        _ => issue_marker(response),
+    }
+}
+
+/// Whether prose names an issue the way reviews mark them: `[ISSUE]`, or a high or critical
+/// severity such as `severity: high` or `**Severity:** Critical`
+fn issue_marker(text: &str) -> bool {
+    let re = Regex::new(r"(?i)\[issue\]|severity\W{0,4}\s*(high|critical)([^\w-]|$)").unwrap();
+    re.is_match(text)
+}
+
+/// The review comment format for `language` (case-insensitive), if known
+pub fn docstring_comment_format(language: &str) -> Option<&'static str> {
+    let language = language.to_lowercase();
+    DOCSTRING_COMMENT_FORMATS
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, format)| *format)
+}
+
+/// The language of a file, guessed from its extension (case-insensitive)
+pub fn language_for_path(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    LANGUAGE_EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
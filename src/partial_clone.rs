@@ -0,0 +1,189 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::encoding::decode_diff;
+use crate::error::ReviewError;
+use crate::vcs::{git_command, git_output};
+
+/// Tells git not to fetch missing objects from a partial clone's promisor remote; commands that
+/// need one fail straight away instead.
+pub const NO_LAZY_FETCH_ENV: &str = "GIT_NO_LAZY_FETCH";
+
+// Set once by `disable_lazy_fetch`, when a partial clone is reviewed without --allow-fetch
+static LAZY_FETCH_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Stop the git commands that only gather context, such as the range-diff and the diff stat,
+/// from fetching objects the partial clone doesn't have. The diff under review still fetches
+/// what it needs.
+pub fn disable_lazy_fetch() {
+    LAZY_FETCH_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Turn off lazy fetching for `command` if `disable_lazy_fetch` was called
+pub(crate) fn limit_lazy_fetch(command: &mut Command) {
+    if LAZY_FETCH_DISABLED.load(Ordering::Relaxed) {
+        command.env(NO_LAZY_FETCH_ENV, "1");
+    }
+}
+
+/// How much of the repository is on disk: whether it's a partial clone, whose missing objects
+/// are fetched on demand, and whether only some paths are checked out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Checkout {
+    /// The filter the partial clone was made with, such as `blob:none`, or `unknown` when a
+    /// promisor remote doesn't say
+    pub partial_clone_filter: Option<String>,
+    /// The directories or patterns a sparse checkout includes
+    pub sparse_paths: Option<Vec<String>>,
+}
+
+impl Checkout {
+    /// Read the current repository's configuration. Anything git can't answer counts as a full
+    /// clone and checkout.
+    pub fn detect() -> Self {
+        let config = git_output(&["config", "--get-regexp", r"^(remote\..*\.(promisor|partialclonefilter)|extensions\.partialclone)$"])
+            .unwrap_or_default();
+        let sparse = git_output(&["config", "--bool", "core.sparseCheckout"])
+            .is_ok_and(|value| value.trim() == "true");
+        Checkout {
+            partial_clone_filter: parse_partial_clone_config(&config),
+            sparse_paths: sparse.then(|| {
+                git_output(&["sparse-checkout", "list"])
+                    .map(|list| list.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            }),
+        }
+    }
+
+    /// What to warn about before the review starts, one message per line
+    pub fn warnings(&self, allow_fetch: bool) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(filter) = &self.partial_clone_filter {
+            warnings.push(format!(
+                "This is a partial clone ({}): diffing commits whose files haven't been fetched downloads \
+                 them, and files that can't be fetched are left out of the review",
+                filter
+            ));
+            if !allow_fetch {
+                warnings.push(
+                    "Context that would need objects the clone doesn't have, such as the range-diff and the diff \
+                     stat, is left out; pass --allow-fetch to fetch them"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(paths) = &self.sparse_paths {
+            warnings.push(format!(
+                "This is a sparse checkout of {} {}: language detection and the Rust edition only see the \
+                 files checked out",
+                paths.len(),
+                if paths.len() == 1 { "path" } else { "paths" }
+            ));
+        }
+        warnings
+    }
+}
+
+/// The partial clone filter in `git config --get-regexp` output for the promisor settings
+pub fn parse_partial_clone_config(config: &str) -> Option<String> {
+    let settings: Vec<(String, &str)> = config
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(key, value)| (key.to_lowercase(), value.trim()))
+        .collect();
+    let filter = settings
+        .iter()
+        .find(|(key, _)| key.ends_with(".partialclonefilter"))
+        .map(|(_, value)| value.to_string());
+    let promisor = settings.iter().any(|(key, value)| {
+        key == "extensions.partialclone" || (key.ends_with(".promisor") && *value == "true")
+    });
+    filter.or_else(|| promisor.then(|| "unknown".to_string()))
+}
+
+/// Whether git failed because a partial clone is missing an object it couldn't fetch
+pub fn is_missing_object_error(stderr: &str) -> bool {
+    ["from promisor remote", "lazy fetching disabled", "missing blob object"]
+        .iter()
+        .any(|message| stderr.contains(message))
+}
+
+/// A file in `git diff --raw` output, with the objects of both sides
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawChange {
+    pub path: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl RawChange {
+    /// The objects a patch for the file needs. All zeroes stands for a side that doesn't exist
+    /// or is in the working tree.
+    fn objects(&self) -> impl Iterator<Item = &str> {
+        [self.old.as_str(), self.new.as_str()].into_iter().filter(|sha| !sha.bytes().all(|b| b == b'0'))
+    }
+}
+
+/// Parse `git diff --raw --no-renames --no-abbrev` output, lines like
+/// `:100644 100644 <old> <new> M<tab>path`
+pub fn parse_raw_diff(raw: &str) -> Vec<RawChange> {
+    raw.lines()
+        .filter_map(|line| {
+            let (meta, path) = line.strip_prefix(':')?.split_once('\t')?;
+            let fields: Vec<&str> = meta.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, _, old, new, _] => {
+                    Some(RawChange { path: path.to_string(), old: old.to_string(), new: new.to_string() })
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// After the diff failed on objects a partial clone couldn't fetch, diff the files whose objects
+/// are all here, without fetching anything. Returns the diff and the paths left out, or `None`
+/// when no file can be diffed.
+pub(crate) fn diff_available_files(git_args: &[String]) -> Result<Option<(String, Vec<String>)>, ReviewError> {
+    let args: Vec<&str> = git_args.iter().map(String::as_str).collect();
+    let (options, pathspecs) = match args.iter().position(|arg| *arg == "--") {
+        Some(separator) => (&args[..separator], &args[separator + 1..]),
+        None => (&args[..], &[][..]),
+    };
+
+    // Listing the files needs only trees, as long as git isn't asked to detect renames or patch
+    let listing_options: Vec<&str> = options.iter().copied().filter(|arg| !arg.starts_with("-U")).collect();
+    let raw = run_without_fetch(
+        &[&["diff", "--no-ext-diff", "--raw", "--no-renames", "--no-abbrev"][..], &listing_options, &["--"], pathspecs]
+            .concat(),
+    )?;
+    let (available, missing): (Vec<RawChange>, Vec<RawChange>) =
+        parse_raw_diff(&String::from_utf8_lossy(&raw)).into_iter().partition(|change| {
+            change.objects().all(|sha| run_without_fetch(&["cat-file", "-e", sha]).is_ok())
+        });
+    if available.is_empty() {
+        return Ok(None);
+    }
+
+    let paths: Vec<&str> = available.iter().map(|change| change.path.as_str()).collect();
+    let diff = run_without_fetch(&[&["diff", "--no-ext-diff"][..], options, &["--"], &paths].concat())?;
+    Ok(Some((decode_diff(&diff), missing.into_iter().map(|change| change.path).collect())))
+}
+
+fn run_without_fetch(args: &[&str]) -> Result<Vec<u8>, ReviewError> {
+    let mut command_binding = git_command();
+    let command = command_binding.args(args).env(NO_LAZY_FETCH_ENV, "1");
+    debug!("Running command: {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(ReviewError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
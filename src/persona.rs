@@ -0,0 +1,125 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+use crate::system_prompt::{PromptSection, SystemPrompt};
+
+/// A reviewer persona: replacements for the role and tone sections of the system prompt. What to
+/// look for and how to lay out the review are left alone, so a persona combines with the other
+/// section options without repeating their instructions.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Persona {
+    pub name: String,
+    /// One line for --list-personas
+    pub description: String,
+    pub role: Option<String>,
+    pub tone: Option<String>,
+}
+
+impl Persona {
+    /// `prompt` with this persona's role and tone in place of its own
+    pub fn apply(&self, mut prompt: SystemPrompt) -> SystemPrompt {
+        if let Some(role) = &self.role {
+            prompt = prompt.with_section(PromptSection::Role, role.as_str());
+        }
+        if let Some(tone) = &self.tone {
+            prompt = prompt.with_section(PromptSection::Tone, tone.as_str());
+        }
+        prompt
+    }
+}
+
+// Name, description, role and tone of each built-in persona
+const BUILTIN_PERSONAS: &[(&str, &str, &str, &str)] = &[
+    (
+        "mentor",
+        "Patient and explanatory, for developers still learning the codebase",
+        "Please review this PR as a mentor helping a less experienced developer grow.\n",
+        "Explain the reasoning behind each suggestion and link it to the underlying principle. Point out what was done well. Be encouraging, and separate what must change from what is worth learning for next time.\n",
+    ),
+    (
+        "strict-gatekeeper",
+        "Holds the line before a release: anything risky blocks the merge",
+        "Please review this PR as the gatekeeper of a release branch, approving only changes that are safe to ship.\n",
+        "Be direct and uncompromising. Say plainly whether the change should be merged, and treat anything that risks a regression, lacks tests or is unclear as blocking. Skip praise and style nitpicks.\n",
+    ),
+    (
+        "security-auditor",
+        "Reads the change as an attacker would",
+        "Please review this PR as a security auditor looking for ways the change could be exploited.\n",
+        "For each issue, describe how it could be exploited and how serious that would be, and give a concrete fix. Mention other issues only if they affect security.\n",
+    ),
+    (
+        "terse-senior",
+        "Short, to the point, no hand-holding",
+        "Please review this PR as a busy senior engineer.\n",
+        "Be terse: one line per issue where possible, most important first, no preamble or praise.\n",
+    ),
+];
+
+#[derive(Deserialize)]
+struct PersonasFile {
+    #[serde(default)]
+    persona: Vec<Persona>,
+}
+
+/// The personas --persona accepts: the built-in ones plus any from a --personas-file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Personas(Vec<Persona>);
+
+impl Default for Personas {
+    fn default() -> Self {
+        Personas(
+            BUILTIN_PERSONAS
+                .iter()
+                .map(|(name, description, role, tone)| Persona {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    role: Some(role.to_string()),
+                    tone: Some(tone.to_string()),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Personas {
+    /// Add the `[[persona]]` tables of a TOML file, each with a `name`, `description` and optional
+    /// `role` and `tone`. A persona with a built-in name replaces the built-in one.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<(), ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        let file: PersonasFile = toml::from_str(&contents)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+
+        for persona in file.persona {
+            match self.0.iter_mut().find(|p| p.name == persona.name) {
+                Some(existing) => *existing = persona,
+                None => self.0.push(persona),
+            }
+        }
+        Ok(())
+    }
+
+    /// The persona called `name`
+    pub fn get(&self, name: &str) -> Result<Persona, ReviewError> {
+        self.0.iter().find(|p| p.name == name).cloned().ok_or_else(|| {
+            let names: Vec<&str> = self.0.iter().map(|p| p.name.as_str()).collect();
+            ReviewError::Config(format!(
+                "unknown persona {:?}; the personas are {}",
+                name,
+                names.join(", ")
+            ))
+        })
+    }
+
+    /// One `name - description` line per persona, for --list-personas
+    pub fn list(&self) -> String {
+        let width = self.0.iter().map(|p| p.name.len()).max().unwrap_or_default();
+        self.0
+            .iter()
+            .map(|p| format!("{:width$}  {}", p.name, p.description, width = width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
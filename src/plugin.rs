@@ -0,0 +1,115 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use libloading::{Library, Symbol};
+use std::env::consts::DLL_EXTENSION;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ReviewError;
+
+/// The name of the function each plugin library must export. Its signature must be
+/// `CreatePlugin`, so declare it as:
+///
+/// ```ignore
+/// #[unsafe(no_mangle)]
+/// pub fn create_plugin() -> Box<dyn Plugin> { Box::new(MyPlugin) }
+/// ```
+pub const CREATE_PLUGIN_SYMBOL: &[u8] = b"create_plugin";
+
+/// A transformation loaded from a dynamic library in `--plugin-dir`. Plugins must be built with
+/// the same compiler as llm_code_review, since `Box<dyn Plugin>` has no stable ABI.
+pub trait Plugin {
+    /// Change the diff before it goes into the prompt
+    fn transform_diff(&self, diff: &str) -> String;
+
+    /// Change the final, assembled prompt
+    fn transform_prompt(&self, prompt: &str) -> String;
+}
+
+pub type CreatePlugin = fn() -> Box<dyn Plugin>;
+
+/// A plugin and the library its code lives in, which must outlive it.
+struct LoadedPlugin {
+    // Declared first so it is dropped before the library is unloaded
+    plugin: Box<dyn Plugin>,
+    path: PathBuf,
+    _library: Library,
+}
+
+/// The plugins from `--plugin-dir`, in the order they are applied.
+#[derive(Default)]
+pub struct Plugins(Vec<LoadedPlugin>);
+
+impl fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|loaded| &loaded.path))
+            .finish()
+    }
+}
+
+impl Plugins {
+    /// Load every dynamic library in `dir`, in lexicographic order of file name.
+    pub fn load(dir: &Path) -> Result<Self, ReviewError> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(|e| ReviewError::Io(dir.to_path_buf(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && is_plugin_library(path))
+            .collect();
+        paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        let plugins = paths
+            .into_iter()
+            .map(|path| load_plugin(&path))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Plugins(plugins))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Pass the diff through each plugin in turn
+    pub fn transform_diff(&self, diff: String) -> String {
+        self.0
+            .iter()
+            .fold(diff, |diff, loaded| loaded.plugin.transform_diff(&diff))
+    }
+
+    /// Pass the prompt through each plugin in turn
+    pub fn transform_prompt(&self, prompt: String) -> String {
+        self.0
+            .iter()
+            .fold(prompt, |prompt, loaded| loaded.plugin.transform_prompt(&prompt))
+    }
+}
+
+/// `.so` on Linux, `.dylib` on macOS and `.dll` on Windows
+fn is_plugin_library(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == DLL_EXTENSION)
+}
+
+fn load_plugin(path: &Path) -> Result<LoadedPlugin, ReviewError> {
+    let plugin_error = |e: libloading::Error| ReviewError::Plugin(path.to_path_buf(), e.to_string());
+
+    // SAFETY: loading a library runs its initialisers, and calling `create_plugin` trusts it to
+    // have the declared signature. Plugins are code the user chose to run, like the binary itself.
+    let (library, plugin) = unsafe {
+        let library = Library::new(path).map_err(plugin_error)?;
+        let create: Symbol<CreatePlugin> = library.get(CREATE_PLUGIN_SYMBOL).map_err(plugin_error)?;
+        let plugin = create();
+        (library, plugin)
+    };
+    info!("Loaded plugin {}", path.display());
+
+    Ok(LoadedPlugin {
+        plugin,
+        path: path.to_path_buf(),
+        _library: library,
+    })
+}
@@ -0,0 +1,220 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use toml::{Table, Value};
+
+use crate::error::ReviewError;
+use crate::findings::Severity;
+use crate::review_length::REVIEW_LENGTHS;
+
+/// The info string of the fenced block in a pull request description that holds review settings
+pub const PR_CONFIG_FENCE: &str = "llm-review";
+
+/// The settings a pull request description may change, and the flags they stand in for. Anyone
+/// who can edit the description can set these, so nothing here reads files, runs commands or
+/// picks a provider.
+pub const PR_CONFIG_KEYS: &[(&str, &str)] = &[
+    ("preset", "--persona"),
+    ("depth", "--review-length"),
+    ("exclude", "--exclude"),
+    ("min-severity", "--min-severity"),
+];
+
+/// Review settings from a ```llm-review block in a pull request description, written as TOML or
+/// as simple YAML:
+///
+/// ```text
+/// preset: security-auditor
+/// depth: short
+/// exclude: [docs/**, "*.snap"]
+/// min-severity: warning
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrConfig {
+    pub preset: Option<String>,
+    pub depth: Option<String>,
+    pub exclude: Vec<String>,
+    pub min_severity: Option<Severity>,
+    /// Keys that aren't in `PR_CONFIG_KEYS`, which were left out
+    pub ignored: Vec<String>,
+}
+
+impl PrConfig {
+    /// The settings in `description`'s ```llm-review block, or `None` when it has none
+    pub fn from_description(description: &str) -> Result<Option<Self>, ReviewError> {
+        let Some(block) = config_block(description) else {
+            return Ok(None);
+        };
+        PrConfig::parse(&block).map(Some).map_err(|e| {
+            ReviewError::Config(format!("the ```{} block in the pull request description: {}", PR_CONFIG_FENCE, e))
+        })
+    }
+
+    /// Parse the body of a block, as TOML or, when it isn't TOML but looks like YAML, as YAML
+    pub fn parse(block: &str) -> Result<Self, String> {
+        let table = match block.parse::<Table>() {
+            Ok(table) => table,
+            Err(_) if looks_like_yaml(block) => parse_yaml(block)?,
+            Err(e) => {
+                let line = e.span().map_or(1, |span| block[..span.start].matches('\n').count() + 1);
+                return Err(format!("line {}: {}", line, e.message().trim_end()));
+            }
+        };
+
+        let mut config = PrConfig::default();
+        for (key, value) in table {
+            match key.replace('_', "-").as_str() {
+                "preset" => config.preset = Some(string(&key, &value)?),
+                "depth" => {
+                    let depth = string(&key, &value)?;
+                    if !REVIEW_LENGTHS.contains(&depth.as_str()) {
+                        return Err(format!("`{}` must be one of {}, not `{}`", key, REVIEW_LENGTHS.join(", "), depth));
+                    }
+                    config.depth = Some(depth);
+                }
+                "exclude" => {
+                    config.exclude = match &value {
+                        Value::Array(values) => values.iter().map(|value| string(&key, value)).collect::<Result<_, _>>()?,
+                        value => vec![string(&key, value)?],
+                    }
+                }
+                "min-severity" => {
+                    config.min_severity = Some(string(&key, &value)?.parse().map_err(|e| format!("`{}`: {}", key, e))?)
+                }
+                _ => config.ignored.push(key),
+            }
+        }
+        Ok(config)
+    }
+
+    /// What the block changes, as `key = value` pairs for the log
+    pub fn describe(&self) -> String {
+        let mut settings = Vec::new();
+        if let Some(preset) = &self.preset {
+            settings.push(format!("preset = {}", preset));
+        }
+        if let Some(depth) = &self.depth {
+            settings.push(format!("depth = {}", depth));
+        }
+        if !self.exclude.is_empty() {
+            settings.push(format!("exclude = {}", self.exclude.join(", ")));
+        }
+        if let Some(severity) = self.min_severity {
+            settings.push(format!("min-severity = {}", severity));
+        }
+        settings.join("; ")
+    }
+}
+
+fn string(key: &str, value: &Value) -> Result<String, String> {
+    value.as_str().map(str::to_string).ok_or_else(|| format!("`{}` must be a string, not {}", key, value))
+}
+
+fn fence_regex() -> Regex {
+    Regex::new(&format!(r"^\s*(```+|~~~+)\s*{}\s*$", regex::escape(PR_CONFIG_FENCE))).unwrap()
+}
+
+/// Where the first ```llm-review block is: the line of its opening fence, the line its closing
+/// fence would be on, and the line after the block. A block that is never closed runs to the end
+/// of the description.
+fn block_lines(lines: &[&str]) -> Option<(usize, usize, usize)> {
+    let fence = fence_regex();
+    let start = lines.iter().position(|line| fence.is_match(line))?;
+    let marker = fence.captures(lines[start]).unwrap()[1].to_string();
+    match lines[start + 1..].iter().position(|line| line.trim() == marker) {
+        Some(offset) => Some((start, start + 1 + offset, start + 2 + offset)),
+        None => Some((start, lines.len(), lines.len())),
+    }
+}
+
+/// The body of the first ```llm-review block in `description`
+pub fn config_block(description: &str) -> Option<String> {
+    let lines: Vec<&str> = description.lines().collect();
+    let (start, close, _) = block_lines(&lines)?;
+    Some(lines[start + 1..close].iter().map(|line| format!("{}\n", line)).collect())
+}
+
+/// `description` without its ```llm-review block, which is for the tool and not the model
+pub fn strip_config_block(description: &str) -> String {
+    let lines: Vec<&str> = description.lines().collect();
+    let Some((start, _, end)) = block_lines(&lines) else {
+        return description.trim().to_string();
+    };
+    let mut kept = lines[..start].to_vec();
+    kept.extend_from_slice(&lines[end..]);
+    kept.join("\n").trim().to_string()
+}
+
+fn looks_like_yaml(block: &str) -> bool {
+    Regex::new(r"(?m)^[A-Za-z][\w-]*:(\s|$)").unwrap().is_match(block)
+}
+
+/// The small part of YAML a settings block needs: `key: value` lines, with lists either as
+/// `[a, b]` or as `- item` lines under the key
+fn parse_yaml(block: &str) -> Result<Table, String> {
+    let entry = Regex::new(r"^([A-Za-z][\w-]*):(?:\s+(.*))?$").unwrap();
+    let mut table = Table::new();
+    let mut list: Option<String> = None;
+    for (n, line) in block.lines().enumerate() {
+        let line = strip_comment(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(item) = line.trim().strip_prefix("- ")
+            && let Some(key) = &list
+        {
+            if let Some(Value::Array(items)) = table.get_mut(key) {
+                items.push(Value::String(unquote(item)));
+            }
+            continue;
+        }
+        let captures = entry
+            .captures(line.trim_end())
+            .ok_or_else(|| format!("line {}: expected `key: value`, found `{}`", n + 1, line.trim()))?;
+        let key = captures[1].to_string();
+        let value = captures.get(2).map_or("", |value| value.as_str()).trim();
+        if table.contains_key(&key) {
+            return Err(format!("line {}: `{}` is given twice", n + 1, key));
+        }
+        list = None;
+        let value = if value.is_empty() {
+            list = Some(key.clone());
+            Value::Array(Vec::new())
+        } else if let Some(items) = value.strip_prefix('[') {
+            let items = items
+                .strip_suffix(']')
+                .ok_or_else(|| format!("line {}: the list for `{}` has no closing `]`", n + 1, key))?;
+            Value::Array(
+                items.split(',').map(str::trim).filter(|item| !item.is_empty()).map(|item| Value::String(unquote(item))).collect(),
+            )
+        } else {
+            Value::String(unquote(value))
+        };
+        table.insert(key, value);
+    }
+    Ok(table)
+}
+
+/// `line` without a trailing `# comment`, leaving a `#` inside quotes alone
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' | '\'' if quote == Some(c) => quote = None,
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            '#' if quote.is_none() && (i == 0 || line[..i].ends_with(char::is_whitespace)) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|value| value.strip_suffix(quote)) {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
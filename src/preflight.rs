@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::diff::{line_counts, parse_diff, HunkRange};
+
+/// Something wrong with a finished prompt that would waste the request to the model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptProblem {
+    /// The section the diff or code goes in has nothing in it
+    EmptyCode { heading: String },
+    /// The diff has hunks, but none of them adds or removes a line
+    NoChangedLines,
+    /// A hunk has a different number of lines than its header says
+    UnbalancedHunk {
+        path: String,
+        header: String,
+        /// Old and new lines the header says the hunk has
+        expected: (usize, usize),
+        /// Old and new lines it has
+        found: (usize, usize),
+    },
+    /// The prompt has terminal escape codes in it, from a diff run with color
+    AnsiEscapes {
+        /// How many lines of the prompt have them
+        lines: usize,
+        /// The first line with one, counting from 1
+        first_line: usize,
+    },
+}
+
+impl fmt::Display for PromptProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptProblem::EmptyCode { heading } => write!(f, "the \"# {}\" section is empty", heading),
+            PromptProblem::NoChangedLines => write!(f, "the diff has only context lines, with no line added or removed"),
+            PromptProblem::UnbalancedHunk { path, header, expected, found } => write!(
+                f,
+                "{}: hunk `{}` should have {} old and {} new lines, but has {} and {}",
+                path, header, expected.0, expected.1, found.0, found.1
+            ),
+            PromptProblem::AnsiEscapes { lines, first_line } => write!(
+                f,
+                "{} line{} of the prompt have terminal escape codes, the first at line {}; was the diff made with --color?",
+                lines,
+                if *lines == 1 { "" } else { "s" },
+                first_line
+            ),
+        }
+    }
+}
+
+/// Check the finished `prompt`, whose code section under `heading` is `code`, for the problems
+/// that would make the model's review worthless. `is_diff` is false for --raw-code, which isn't
+/// checked as a diff.
+pub fn check_prompt(prompt: &str, heading: &str, code: &str, is_diff: bool) -> Vec<PromptProblem> {
+    let mut problems = Vec::new();
+    if code.trim().is_empty() {
+        problems.push(PromptProblem::EmptyCode { heading: heading.to_string() });
+    } else if is_diff {
+        problems.extend(check_diff(code));
+    }
+    let escapes: Vec<usize> = prompt
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains("\x1b["))
+        .map(|(i, _)| i + 1)
+        .collect();
+    if let Some(&first_line) = escapes.first() {
+        problems.push(PromptProblem::AnsiEscapes { lines: escapes.len(), first_line });
+    }
+    problems
+}
+
+/// The problems with `diff`'s hunks: ones whose lines don't add up to their headers, and no
+/// changed line in any of them
+fn check_diff(diff: &str) -> Vec<PromptProblem> {
+    let mut problems = Vec::new();
+    let mut hunks = 0;
+    let mut changed = false;
+    for file in parse_diff(diff) {
+        for hunk in &file.hunks {
+            hunks += 1;
+            let Some(range) = HunkRange::parse(&hunk.header) else {
+                continue;
+            };
+            let expected = (range.old_len, range.new_len);
+            let parents = hunk.parents();
+            let mut lines = hunk.lines.as_slice();
+            let mut found = count_lines(lines, parents);
+            // Blank lines after the last hunk are the diff's end, not context lines
+            while found != expected && lines.last().is_some_and(String::is_empty) {
+                lines = &lines[..lines.len() - 1];
+                found = count_lines(lines, parents);
+            }
+            changed |= lines.iter().any(|line| line.chars().take(parents).any(|c| c == '+' || c == '-'));
+            if found != expected {
+                problems.push(PromptProblem::UnbalancedHunk {
+                    path: file.path.clone(),
+                    header: hunk.header.clone(),
+                    expected,
+                    found,
+                });
+            }
+        }
+    }
+    if hunks > 0 && !changed {
+        problems.push(PromptProblem::NoChangedLines);
+    }
+    problems
+}
+
+/// The old and new lines `lines` of a hunk with `parents` columns of markers stand for, counting
+/// elided runs of unchanged lines
+fn count_lines(lines: &[String], parents: usize) -> (usize, usize) {
+    lines.iter().fold((0, 0), |(old, new), line| {
+        let (line_old, line_new) = line_counts(line, parents);
+        (old + line_old, new + line_new)
+    })
+}
@@ -0,0 +1,139 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::diff::{line_counts, parse_diff};
+use crate::encoding::decode_diff;
+use crate::error::ReviewError;
+use crate::progress::format_elapsed;
+
+/// How long a --preprocess command may run before it's stopped
+pub const PREPROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pass `diff` through each of `commands` in turn, as --preprocess does, logging how long each
+/// took and how much it changed the diff's size. An empty diff is left as it is. Each command's
+/// time is `timeout` at most. Fails with `ReviewError::Preprocess`, naming the command, if one
+/// can't be run, fails, runs out of time, or writes something that isn't a unified diff.
+pub fn preprocess(commands: &[String], diff: String, timeout: Duration) -> Result<String, ReviewError> {
+    if diff.is_empty() {
+        return Ok(diff);
+    }
+    commands.iter().try_fold(diff, |diff, command| {
+        let started = Instant::now();
+        let output = run_preprocessor(command, &diff, timeout)
+            .and_then(|output| validate_diff(&output).map(|()| output))
+            .map_err(|message| ReviewError::Preprocess(command.clone(), message))?;
+        info!(
+            "--preprocess `{}` took {} and turned {} bytes into {} ({:+})",
+            command,
+            format_elapsed(started.elapsed()),
+            diff.len(),
+            output.len(),
+            output.len() as i64 - diff.len() as i64
+        );
+        Ok(output)
+    })
+}
+
+/// Run `command`, split on whitespace, in the current directory with `diff` on standard input, and
+/// return what it wrote on standard output. Fails if it can't be run, exits unsuccessfully, or
+/// takes longer than `timeout`, in which case it's stopped.
+pub fn run_preprocessor(command: &str, diff: &str, timeout: Duration) -> Result<String, String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("the command is empty")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => format!("{} is not installed", program),
+            _ => e.to_string(),
+        })?;
+
+    // Write and read on threads of their own, so neither a full pipe nor a command that stops
+    // reading early can stall it. A command may ignore its input, so a broken pipe isn't an error.
+    let writer = child.stdin.take().map(|mut stdin| {
+        let diff = diff.to_string();
+        thread::spawn(move || {
+            let _ = stdin.write_all(diff.as_bytes());
+        })
+    });
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            bytes
+        })
+    };
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("it took longer than {:.1}s", timeout.as_secs_f64()));
+            }
+            None => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = String::from_utf8_lossy(&stderr.join().unwrap_or_default()).trim_end().to_string();
+    if !status.success() {
+        return Err(match stderr.lines().last() {
+            Some(last_line) => format!("it exited with {}: {}", status, last_line.trim()),
+            None => format!("it exited with {}", status),
+        });
+    }
+    if !stderr.is_empty() {
+        debug!("--preprocess `{}` wrote on standard error:\n{}", command, stderr);
+    }
+    Ok(decode_diff(&stdout))
+}
+
+/// Check that a preprocessor's `output` is still a unified diff: each file has a path, and each
+/// hunk a header that parses and as many lines as it says. Empty output, leaving nothing to
+/// review, is allowed.
+pub fn validate_diff(output: &str) -> Result<(), String> {
+    if output.trim().is_empty() {
+        return Ok(());
+    }
+    let files = parse_diff(output);
+    if files.is_empty() {
+        return Err("its output isn't a unified diff".to_string());
+    }
+    for file in &files {
+        if file.path.is_empty() {
+            return Err(format!("its output has a file without a path: {}", file.header.join(" ")));
+        }
+        for hunk in &file.hunks {
+            let range = hunk
+                .range()
+                .ok_or_else(|| format!("its output has a hunk header that doesn't parse in {}: {}", file.path, hunk.header))?;
+            let counted = hunk.lines.iter().fold((0, 0), |(old, new), line| {
+                let (line_old, line_new) = line_counts(line, hunk.parents());
+                (old + line_old, new + line_new)
+            });
+            if counted != (range.old_len, range.new_len) {
+                return Err(format!(
+                    "its output has a hunk in {} with {} old and {} new lines where the header `{}` says {} and {}",
+                    file.path, counted.0, counted.1, hunk.header, range.old_len, range.new_len
+                ));
+            }
+        }
+    }
+    Ok(())
+}
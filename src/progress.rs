@@ -0,0 +1,154 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::ReviewError;
+
+/// How often, in seconds, the heartbeat reports on a review being generated, unless
+/// --heartbeat-secs says otherwise
+pub const DEFAULT_HEARTBEAT_SECS: u64 = 10;
+
+/// What to report and enforce while a provider generates a review.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationLimits {
+    /// How often to report progress on standard error; `None` for no heartbeat
+    pub heartbeat: Option<Duration>,
+    /// How long to wait for the first token before giving up; `None` to wait as long as it takes
+    pub max_wait_first_token: Option<Duration>,
+}
+
+/// How a request to a provider is going, shared between the request, which counts the tokens it
+/// receives, and the heartbeat, which reports them.
+#[derive(Debug)]
+pub struct Progress {
+    started: Instant,
+    tokens: AtomicUsize,
+    // Milliseconds from `started` to the first token, plus one so zero can mean none yet
+    first_token_millis: AtomicU64,
+}
+
+impl Progress {
+    pub fn new(started: Instant) -> Self {
+        Progress { started, tokens: AtomicUsize::new(0), first_token_millis: AtomicU64::new(0) }
+    }
+
+    /// Count `tokens` more received. A streaming request calls this for each chunk, and a
+    /// non-streaming one once, with the whole response.
+    pub fn received(&self, tokens: usize) {
+        if tokens == 0 {
+            return;
+        }
+        let millis = self.started.elapsed().as_millis() as u64 + 1;
+        let _ = self.first_token_millis.compare_exchange(0, millis, Ordering::Relaxed, Ordering::Relaxed);
+        self.tokens.fetch_add(tokens, Ordering::Relaxed);
+    }
+
+    pub fn tokens(&self) -> usize {
+        self.tokens.load(Ordering::Relaxed)
+    }
+
+    /// How long the first token took, or `None` if none has arrived
+    pub fn first_token_after(&self) -> Option<Duration> {
+        match self.first_token_millis.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(Duration::from_millis(millis - 1)),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// How long the request may still wait for the first token, for the HTTP client's timeout on
+    /// receiving the response. `None` when there's no limit or the first token arrived.
+    pub fn first_token_timeout(&self, limits: &GenerationLimits) -> Option<Duration> {
+        if self.first_token_after().is_some() {
+            return None;
+        }
+        limits.max_wait_first_token.map(|max| max.saturating_sub(self.elapsed()))
+    }
+
+    /// Fail if the first token is overdue. A streaming request checks this between chunks that
+    /// carry no tokens, such as keep-alives.
+    pub fn check_first_token(&self, limits: &GenerationLimits) -> Result<(), ReviewError> {
+        match limits.max_wait_first_token {
+            Some(max) if self.first_token_after().is_none() && self.elapsed() >= max => {
+                Err(ReviewError::FirstTokenTimeout(max))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Run `request` with a heartbeat on standard error every `limits.heartbeat`, reporting the
+/// time taken and the tokens received so far. When the request fails with no tokens received
+/// after waiting `limits.max_wait_first_token`, as when it's cut off by the timeout from
+/// `Progress::first_token_timeout`, the error says the model may still be loading.
+pub fn watch_generation<T>(
+    limits: GenerationLimits,
+    request: impl FnOnce(&Progress) -> Result<T, ReviewError>,
+) -> Result<T, ReviewError> {
+    let progress = Progress::new(Instant::now());
+    let result = thread::scope(|scope| {
+        let (done, stop) = mpsc::channel::<()>();
+        if let Some(interval) = limits.heartbeat {
+            let progress = &progress;
+            scope.spawn(move || {
+                // Returns as soon as `done` is dropped, when the request ends
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+                    let message = heartbeat_message(progress.elapsed(), progress.tokens(), progress.first_token_after());
+                    let _ = writeln!(io::stderr(), "{}", message);
+                }
+            });
+        }
+        let result = request(&progress);
+        drop(done);
+        result
+    });
+
+    match (result, limits.max_wait_first_token) {
+        (Err(e), Some(max)) if progress.tokens() == 0 && progress.elapsed() >= max => {
+            debug!("The request failed waiting for the first token: {}", e);
+            Err(ReviewError::FirstTokenTimeout(max))
+        }
+        (result, _) => {
+            if let Some(first_token) = progress.first_token_after() {
+                debug!(
+                    "First token after {}, {} tokens in {}",
+                    format_elapsed(first_token),
+                    progress.tokens(),
+                    format_elapsed(progress.elapsed())
+                );
+            }
+            result
+        }
+    }
+}
+
+/// One heartbeat line, e.g. `Waiting for the first token: 1m 05s` or
+/// `Generating: 2m 30s, 412 tokens so far (first after 1m 50s)`
+pub fn heartbeat_message(elapsed: Duration, tokens: usize, first_token_after: Option<Duration>) -> String {
+    match first_token_after {
+        None => format!("Waiting for the first token: {}", format_elapsed(elapsed)),
+        Some(first) => format!(
+            "Generating: {}, {} {} so far (first after {})",
+            format_elapsed(elapsed),
+            tokens,
+            if tokens == 1 { "token" } else { "tokens" },
+            format_elapsed(first)
+        ),
+    }
+}
+
+/// Whole seconds, with minutes once there are any: `45s`, `3m 07s`
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match seconds / 60 {
+        0 => format!("{}s", seconds),
+        minutes => format!("{}m {:02}s", minutes, seconds % 60),
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::system_prompt::SystemPrompt;
+use crate::template::{PromptTemplate, DEFAULT_TEMPLATE};
+
+/// A versioned layout of the prompt, which --stable-prompt pins so the same diff and options give
+/// the same prompt, byte for byte, from one release to the next. The golden files in
+/// `tests/fixtures/stable_prompt` hold what each version produces. A change to the default prompt
+/// or its layout that would change them needs a new version, with the old one kept as it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptSchema {
+    /// The system prompt, the context sections, then the diff under its heading
+    V1,
+}
+
+impl PromptSchema {
+    /// The version used when --prompt-schema isn't given
+    pub const CURRENT: PromptSchema = PromptSchema::V1;
+
+    pub const ALL: [PromptSchema; 1] = [PromptSchema::V1];
+
+    /// The system prompt the version starts from, before personas and section files
+    pub fn system_prompt(&self) -> SystemPrompt {
+        match self {
+            PromptSchema::V1 => SystemPrompt::default(),
+        }
+    }
+
+    /// The template the version lays the prompt out with
+    pub fn template(&self) -> PromptTemplate {
+        match self {
+            // The system prompt, then the context, then the diff under its heading
+            PromptSchema::V1 => PromptTemplate::parse(DEFAULT_TEMPLATE).expect("the v1 template is valid"),
+        }
+    }
+}
+
+impl fmt::Display for PromptSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptSchema::V1 => write!(f, "v1"),
+        }
+    }
+}
+
+impl FromStr for PromptSchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PromptSchema::ALL.into_iter().find(|schema| schema.to_string() == s).ok_or_else(|| {
+            let versions: Vec<String> = PromptSchema::ALL.iter().map(ToString::to_string).collect();
+            format!("unknown prompt schema `{}`; the versions are {}", s, versions.join(", "))
+        })
+    }
+}
@@ -0,0 +1,167 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::locale::Locale;
+use crate::vcs::{current_branch, default_branch, git_output};
+
+/// Where a reviewed diff came from, so a shared review can be traced back to exactly what was
+/// reviewed. Repository fields are `None` when the diff didn't come from the local git repository.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Provenance {
+    pub repository: Option<String>,
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub merge_base: Option<String>,
+    /// Whether tracked files had uncommitted changes
+    pub dirty: Option<bool>,
+    /// SHA-256 of the diff as it went into the prompt
+    pub diff_sha256: String,
+    /// UTC, in RFC 3339 format
+    pub timestamp: String,
+    pub tool_version: String,
+    /// The --provider the prompt was sized for, if any
+    pub provider: Option<String>,
+}
+
+impl Provenance {
+    /// Provenance for `diff`, without any repository details
+    pub fn new(diff: &str, provider: Option<String>) -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Provenance {
+            repository: None,
+            branch: None,
+            head: None,
+            merge_base: None,
+            dirty: None,
+            diff_sha256: sha256_hex(diff),
+            timestamp: rfc3339_utc(seconds),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            provider,
+        }
+    }
+
+    /// Fill in the repository details from git in the current directory. `base` is the revision
+    /// the diff starts from, for the merge base, or else the default branch `origin/HEAD` points
+    /// at; changes to `ignored_paths` (the tool's own output files) don't make the worktree dirty.
+    /// With HEAD detached, the branch is the one the CI environment names, or `detached at <sha>`.
+    pub fn with_git_details(mut self, base: Option<&str>, ignored_paths: &[PathBuf]) -> Self {
+        let git = |args: &[&str]| {
+            git_output(args)
+                .map(|output| output.trim().to_string())
+                .map_err(|e| debug!("Provenance: {}", e))
+                .ok()
+        };
+
+        let top_level = git(&["rev-parse", "--show-toplevel"]);
+        self.repository = top_level.as_ref().and_then(|top_level| {
+            Path::new(top_level)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        });
+        self.branch = current_branch(|var| env::var(var).ok())
+            .or_else(|| git(&["rev-parse", "--short", "HEAD"]).map(|sha| format!("detached at {}", sha)));
+        self.head = git(&["rev-parse", "HEAD"]);
+        let base = base.map(str::to_string).or_else(default_branch);
+        self.merge_base = base.and_then(|base| git(&["merge-base", &base, "HEAD"]));
+        // Not trimmed, since each line starts with a two-column status that may begin with a space
+        self.dirty = git_output(&["status", "--porcelain", "--untracked-files=no"]).ok().map(|status| {
+            status
+                .lines()
+                .filter_map(|line| line.get(3..))
+                .any(|path| !is_ignored(path, top_level.as_deref(), ignored_paths))
+        });
+        self
+    }
+
+    /// The provenance as a short list, for the prompt, labelled in `locale`'s language
+    pub fn describe(&self, locale: Locale) -> String {
+        self.lines(true, locale).join("\n")
+    }
+
+    /// The description without the time and tool version, which differ between runs of the same
+    /// diff, for --stable-prompt
+    pub fn describe_inputs(&self, locale: Locale) -> String {
+        self.lines(false, locale).join("\n")
+    }
+
+    fn lines(&self, with_run: bool, locale: Locale) -> Vec<String> {
+        let mut lines = Vec::new();
+        // Names like HEAD and the tool's are the same in every language
+        let mut field = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                lines.push(format!("- {}: {}", locale.text(name), value));
+            }
+        };
+        field("Repository", self.repository.clone());
+        field("Branch", self.branch.clone());
+        field("HEAD", self.head.clone());
+        field("Merge base", self.merge_base.clone());
+        field(
+            "Worktree",
+            self.dirty
+                .map(|dirty| locale.text(if dirty { "dirty" } else { "clean" }).to_string()),
+        );
+        field("Diff SHA-256", Some(self.diff_sha256.clone()));
+        field("Generated", Some(self.timestamp.clone()).filter(|_| with_run));
+        field("llm_code_review", Some(self.tool_version.clone()).filter(|_| with_run));
+        field("Provider", self.provider.clone());
+        lines
+    }
+}
+
+/// Whether `path`, relative to the repository's top level, is one of `ignored_paths`
+fn is_ignored(path: &str, top_level: Option<&str>, ignored_paths: &[PathBuf]) -> bool {
+    let Some(top_level) = top_level else {
+        return false;
+    };
+    let path = Path::new(top_level).join(path);
+    ignored_paths.iter().any(|ignored| {
+        // Output files may not have been written yet, so can't always be canonicalized
+        let ignored = ignored
+            .canonicalize()
+            .or_else(|_| env::current_dir().map(|dir| dir.join(ignored)))
+            .unwrap_or_else(|_| ignored.clone());
+        path.starts_with(&ignored)
+    })
+}
+
+/// The SHA-256 of `text`, as lowercase hex
+pub fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn rfc3339_utc(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
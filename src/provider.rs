@@ -0,0 +1,118 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+use crate::tokens::Tokenizer;
+
+/// The request and response envelope an LLM provider's API uses.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderFormat {
+    OpenAi,
+    Anthropic,
+    /// Ollama's own chat API, for local models
+    Ollama,
+    Custom,
+}
+
+/// Settings for one LLM provider, from a `[[provider]]` table in a `--llm-provider-config` file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub endpoint: String,
+    /// Token budget for the prompt
+    pub max_tokens: usize,
+    /// Characters per token, for estimating the size of the prompt
+    pub chars_per_token: usize,
+    pub format: ProviderFormat,
+    /// How long, in seconds, a conversation the provider keeps server-side may be continued. Unset
+    /// for providers without server-side conversations, which are always sent the whole prompt.
+    #[serde(default)]
+    pub thread_ttl_secs: Option<u64>,
+    /// The environment variable holding the API key, if not the format's usual one
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// The largest diff, in estimated tokens, that goes into the prompt as it is, without context
+    /// reduction being tried
+    #[serde(default)]
+    pub small_diff_tokens: Option<usize>,
+    /// The model --send asks for, unless --model names another
+    #[serde(default)]
+    pub model: Option<String>,
+    /// How to count tokens, as --tokenizer takes; chars_per_token is used without it
+    #[serde(default)]
+    pub tokenizer: Option<String>,
+}
+
+impl ProviderConfig {
+    /// The environment variable the API key is read from: `api_key_env`, or `OPENAI_API_KEY` and
+    /// `ANTHROPIC_API_KEY` for those formats. Ollama and custom providers without `api_key_env`
+    /// take no key.
+    pub fn api_key_var(&self) -> Option<&str> {
+        match (&self.api_key_env, self.format) {
+            (Some(var), _) => Some(var),
+            (None, ProviderFormat::OpenAi) => Some("OPENAI_API_KEY"),
+            (None, ProviderFormat::Anthropic) => Some("ANTHROPIC_API_KEY"),
+            (None, ProviderFormat::Ollama | ProviderFormat::Custom) => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProviderConfigFile {
+    #[serde(default)]
+    provider: Vec<ProviderConfig>,
+}
+
+/// Read the `[[provider]]` tables from a TOML file.
+pub fn load_provider_config(path: &Path) -> Result<Vec<ProviderConfig>, ReviewError> {
+    let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+    let config_error = |msg: String| ReviewError::Config(format!("{}: {}", path.display(), msg));
+
+    let file: ProviderConfigFile = toml::from_str(&contents).map_err(|e| config_error(e.to_string()))?;
+    if file.provider.is_empty() {
+        return Err(config_error("no [[provider]] tables found".to_string()));
+    }
+    if let Some(provider) = file.provider.iter().find(|p| p.chars_per_token == 0) {
+        return Err(config_error(format!(
+            "provider {:?} has chars_per_token = 0",
+            provider.name
+        )));
+    }
+    for provider in &file.provider {
+        if let Some(tokenizer) = &provider.tokenizer {
+            Tokenizer::parse(tokenizer, provider.chars_per_token)
+                .map_err(|e| config_error(format!("provider {:?}: {}", provider.name, e)))?;
+        }
+    }
+
+    Ok(file.provider)
+}
+
+/// Pick the provider called `name` from `providers`, or the first one if no name is given.
+pub fn select_provider(
+    providers: Vec<ProviderConfig>,
+    name: Option<&str>,
+) -> Result<ProviderConfig, ReviewError> {
+    let Some(name) = name else {
+        return providers
+            .into_iter()
+            .next()
+            .ok_or_else(|| ReviewError::Config("no providers configured".to_string()));
+    };
+
+    let names: Vec<String> = providers.iter().map(|p| p.name.clone()).collect();
+    providers
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| {
+            ReviewError::Config(format!(
+                "no provider named {:?}; the configured providers are {}",
+                name,
+                names.join(", ")
+            ))
+        })
+}
@@ -0,0 +1,154 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::diff::FileDiff;
+use crate::locale::Locale;
+
+/// Changes smaller than this many lines aren't checked, as a short review may be all they need
+pub const DEFAULT_MIN_CHANGED_LINES: usize = 50;
+
+/// How many characters of review a changed line calls for
+pub const DEFAULT_MIN_CHARS_PER_LINE: f64 = 2.0;
+
+/// However large the change, a review this long is long enough
+pub const MAX_REQUIRED_CHARS: usize = 4000;
+
+/// What a review must have for --fallback-model not to re-run it, from the `[quality]` table of a
+/// config file. Each one left out takes its default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QualityThresholds {
+    /// Reviews of fewer changed lines than this aren't checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_changed_lines: Option<usize>,
+    /// The fewest characters of review for each changed line, up to `MAX_REQUIRED_CHARS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_chars_per_line: Option<f64>,
+    /// Whether the review must name a changed file or a line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_reference: Option<bool>,
+    /// Headings the review must have, such as `Summary`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_sections: Option<Vec<String>>,
+}
+
+impl QualityThresholds {
+    /// Check the values as a config file's
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_chars_per_line.is_some_and(|chars| chars < 0.0 || chars.is_nan()) {
+            return Err("quality.min_chars_per_line must be 0 or more".to_string());
+        }
+        if let Some(section) = self.required_sections.iter().flatten().find(|section| section.trim().is_empty()) {
+            return Err(format!("quality.required_sections has an empty heading: {:?}", section));
+        }
+        Ok(())
+    }
+
+    /// These thresholds, with the ones left out taken from `fallback`
+    pub fn or(self, fallback: QualityThresholds) -> QualityThresholds {
+        QualityThresholds {
+            min_changed_lines: self.min_changed_lines.or(fallback.min_changed_lines),
+            min_chars_per_line: self.min_chars_per_line.or(fallback.min_chars_per_line),
+            require_reference: self.require_reference.or(fallback.require_reference),
+            required_sections: self.required_sections.or(fallback.required_sections),
+        }
+    }
+}
+
+/// Why a review looks too shallow to keep
+#[derive(Debug, Clone, PartialEq)]
+pub enum QualityProblem {
+    /// Fewer characters than the change's size calls for
+    TooShort { chars: usize, required: usize, changed_lines: usize },
+    /// Neither a changed file nor a line is named
+    NoReference,
+    /// A heading in `required_sections` is missing
+    MissingSection(String),
+}
+
+impl fmt::Display for QualityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QualityProblem::TooShort { chars, required, changed_lines } => write!(
+                f,
+                "it has {} characters where {} changed lines call for {}",
+                chars, changed_lines, required
+            ),
+            QualityProblem::NoReference => write!(f, "it names no changed file or line"),
+            QualityProblem::MissingSection(section) => write!(f, "it has no {} section", section),
+        }
+    }
+}
+
+/// A review re-run with --fallback-model, as its transcript records it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Escalation {
+    /// The model whose review failed the quality checks
+    pub from_model: String,
+    /// What was wrong with its review
+    pub problems: Vec<String>,
+}
+
+impl Escalation {
+    /// A line for the end of the review by `model`, saying why it was re-run
+    pub fn note(&self, model: &str, locale: Locale) -> String {
+        locale.format(
+            "_Reviewed again by {}, as the review by {} failed the quality checks: {}._",
+            &[model, &self.from_model, &self.problems.join("; ")],
+        )
+    }
+}
+
+/// What's wrong with `review` of the changes to `files` by `thresholds`, or nothing if it passes or
+/// the change is too small to check. A review in JSON has no headings to look for.
+pub fn check_quality(review: &str, files: &[FileDiff], thresholds: &QualityThresholds, json: bool) -> Vec<QualityProblem> {
+    let changed_lines: usize = files.iter().flat_map(|file| &file.hunks).map(|hunk| hunk.changed_lines()).sum();
+    if changed_lines < thresholds.min_changed_lines.unwrap_or(DEFAULT_MIN_CHANGED_LINES) {
+        return Vec::new();
+    }
+    let mut problems = Vec::new();
+    let chars = review.trim().chars().count();
+    let per_line = thresholds.min_chars_per_line.unwrap_or(DEFAULT_MIN_CHARS_PER_LINE);
+    let required = ((changed_lines as f64 * per_line).ceil() as usize).min(MAX_REQUIRED_CHARS);
+    if chars < required {
+        problems.push(QualityProblem::TooShort { chars, required, changed_lines });
+    }
+    if thresholds.require_reference.unwrap_or(true) && !has_reference(review, files) {
+        problems.push(QualityProblem::NoReference);
+    }
+    if !json {
+        let headings: Vec<String> = review.lines().filter_map(heading).collect();
+        for section in thresholds.required_sections.iter().flatten() {
+            if !headings.iter().any(|heading| heading.eq_ignore_ascii_case(section.trim())) {
+                problems.push(QualityProblem::MissingSection(section.trim().to_string()));
+            }
+        }
+    }
+    problems
+}
+
+/// Whether `review` names one of `files`, by its path or file name, or cites a line
+fn has_reference(review: &str, files: &[FileDiff]) -> bool {
+    let names_file = files.iter().any(|file| {
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        !name.is_empty() && (review.contains(&file.path) || review.contains(name))
+    });
+    // A line number, as a review would cite one: `line 12`, `lines 3-4`, `L12`, `path:12`, or a
+    // JSON finding's `"line": 12`
+    let line = Regex::new(r#"(?i)\blines? \d+|\bL\d+\b|\w:\d+\b|"line":\s*\d+"#).unwrap();
+    names_file || line.is_match(review)
+}
+
+/// The text of a Markdown heading, or of a line that's only bold text, without a trailing colon
+fn heading(line: &str) -> Option<String> {
+    let line = line.trim();
+    let text = if line.starts_with('#') {
+        line.trim_start_matches('#')
+    } else if line.len() > 4 && line.starts_with("**") && line.ends_with("**") {
+        line
+    } else {
+        return None;
+    };
+    Some(text.trim().trim_matches('*').trim().trim_end_matches(':').trim().to_string())
+}
@@ -0,0 +1,105 @@
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+
+/// A question for the author from a previous review, e.g. `Q2: Why is the retry limit 3?`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub id: String,
+    pub text: String,
+}
+
+/// The numbered questions (`Q1`, `Q2`, ...) in a review. List markers and bold around the ID are
+/// allowed, as in `- **Q1.** Why ...?`
+pub fn parse_questions(review: &str) -> Vec<Question> {
+    let re = Regex::new(r"(?m)^\s*(?:[-*]\s+)?(?:\*\*)?(Q\d+)(?:\*\*)?[.:)](?:\*\*)?\s+(.+)$").unwrap();
+    re.captures_iter(review)
+        .map(|caps| Question {
+            id: caps[1].to_string(),
+            text: caps[2].trim().to_string(),
+        })
+        .collect()
+}
+
+/// Answers from an answer file, as `(id, answer)` pairs. Each answer starts with its question's
+/// ID, as in `Q1: It matches the upstream timeout`, and runs until the next ID.
+pub fn parse_answers(text: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r"^(Q\d+)[.:)]\s*(.*)$").unwrap();
+    let mut answers: Vec<(String, String)> = Vec::new();
+
+    for line in text.lines() {
+        if let Some(caps) = re.captures(line.trim_start()) {
+            answers.push((caps[1].to_string(), caps[2].to_string()));
+        } else if let Some((_, answer)) = answers.last_mut() {
+            answer.push('\n');
+            answer.push_str(line);
+        }
+    }
+
+    answers
+        .into_iter()
+        .map(|(id, answer)| (id, answer.trim().to_string()))
+        .collect()
+}
+
+/// Pair each question with its answer. Every question must be answered, and every answer must
+/// belong to a question.
+pub fn match_answers(
+    questions: &[Question],
+    answers: &[(String, String)],
+) -> Result<Vec<(Question, String)>, ReviewError> {
+    let unknown: Vec<&str> = answers
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .filter(|id| !questions.iter().any(|q| q.id == *id))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(ReviewError::Answers(format!(
+            "no question with the ID {} in the previous review",
+            unknown.join(", ")
+        )));
+    }
+
+    let mut unanswered = Vec::new();
+    let mut matched = Vec::new();
+    for question in questions {
+        match answers.iter().find(|(id, answer)| *id == question.id && !answer.is_empty()) {
+            Some((_, answer)) => matched.push((question.clone(), answer.clone())),
+            None => unanswered.push(question.id.as_str()),
+        }
+    }
+    if !unanswered.is_empty() {
+        return Err(ReviewError::Answers(format!(
+            "no answer given for {}",
+            unanswered.join(", ")
+        )));
+    }
+
+    Ok(matched)
+}
+
+/// The prompt section giving the author's answers to the questions in `previous_review`.
+pub fn answers_section(previous_review: &Path, answer_file: &Path) -> Result<String, ReviewError> {
+    let review = fs::read_to_string(previous_review)
+        .map_err(|e| ReviewError::Io(previous_review.to_path_buf(), e))?;
+    let answers =
+        fs::read_to_string(answer_file).map_err(|e| ReviewError::Io(answer_file.to_path_buf(), e))?;
+
+    let questions = parse_questions(&review);
+    if questions.is_empty() {
+        return Err(ReviewError::Answers(format!(
+            "{} has no numbered questions (Q1, Q2, ...)",
+            previous_review.display()
+        )));
+    }
+
+    let mut section = String::from(
+        "The author has answered the questions from the previous review. For each one, say whether the answer resolves it, or raise it as an issue if it doesn't.\n",
+    );
+    for (question, answer) in match_answers(&questions, &parse_answers(&answers))? {
+        section.push_str(&format!("\n{}: {}\nAnswer: {}\n", question.id, question.text, answer));
+    }
+    Ok(section)
+}
@@ -0,0 +1,149 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ureq::http::{Request, Response};
+use ureq::middleware::MiddlewareNext;
+use ureq::{Body, SendBody};
+
+/// With this few requests left before a host's limit resets, the rest are spread out until then
+pub const PACE_BELOW: u64 = 5;
+
+/// The longest gap pacing puts between two requests to a host that still has some left
+pub const MAX_PACE: Duration = Duration::from_secs(10);
+
+/// The longest wait for a limit to reset; a request that would wait longer is sent anyway, for the
+/// host to refuse with its own error
+pub const MAX_WAIT: Duration = Duration::from_secs(300);
+
+// The limits every request the tool makes has heard of, by host
+static LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+
+/// What a host last said about its rate limit: the requests left, and when that goes back up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub remaining: u64,
+    pub reset: SystemTime,
+}
+
+/// Parse the rate limit headers of a response that arrived at `now`; `header` looks up a header by
+/// its lowercase name. Understands `retry-after` in seconds, GitHub's `x-ratelimit-remaining` and
+/// `x-ratelimit-reset`, OpenAI's `x-ratelimit-remaining-requests` and `x-ratelimit-reset-requests`,
+/// and Anthropic's `anthropic-ratelimit-requests-remaining` and `anthropic-ratelimit-requests-reset`.
+pub fn parse_quota(header: impl Fn(&str) -> Option<String>, now: SystemTime) -> Option<Quota> {
+    if let Some(secs) = header("retry-after").and_then(|value| value.trim().parse::<u64>().ok()) {
+        return Some(Quota { remaining: 0, reset: now + Duration::from_secs(secs) });
+    }
+    let number = |name: &str| header(name).and_then(|value| value.trim().parse::<u64>().ok());
+    if let Some(remaining) = number("x-ratelimit-remaining") {
+        let reset = UNIX_EPOCH + Duration::from_secs(number("x-ratelimit-reset")?);
+        return Some(Quota { remaining, reset });
+    }
+    if let Some(remaining) = number("x-ratelimit-remaining-requests") {
+        let reset = now + parse_reset_duration(&header("x-ratelimit-reset-requests")?)?;
+        return Some(Quota { remaining, reset });
+    }
+    if let Some(remaining) = number("anthropic-ratelimit-requests-remaining") {
+        let reset = parse_rfc3339(&header("anthropic-ratelimit-requests-reset")?)?;
+        return Some(Quota { remaining, reset });
+    }
+    None
+}
+
+/// Parse a duration as OpenAI writes them, e.g. `20ms`, `1.5s` or `6m0s`
+pub fn parse_reset_duration(text: &str) -> Option<Duration> {
+    let re = Regex::new(r"^(?:(\d+)h)?(?:(\d+)m)?(?:(\d+(?:\.\d+)?)s)?(?:(\d+)ms)?$").unwrap();
+    let caps = re.captures(text.trim()).filter(|caps| caps.iter().skip(1).any(|group| group.is_some()))?;
+    let part = |i: usize| caps.get(i).map_or(0.0, |group| group.as_str().parse::<f64>().unwrap_or(0.0));
+    Some(Duration::from_secs_f64(part(1) * 3_600.0 + part(2) * 60.0 + part(3) + part(4) / 1_000.0))
+}
+
+/// Parse a UTC time written `YYYY-MM-DDTHH:MM:SSZ`, with or without fractions of a second
+pub fn parse_rfc3339(text: &str) -> Option<SystemTime> {
+    let re = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})T(\d{2}):(\d{2}):(\d{2})(?:\.\d+)?Z$").unwrap();
+    let caps = re.captures(text.trim())?;
+    let field = |i: usize| caps[i].parse::<i64>().unwrap();
+    let (year, month, day) = (field(1), field(2), field(3));
+
+    // Days since 1970-01-01, after Howard Hinnant's `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + field(4) * 3_600 + field(5) * 60 + field(6);
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+/// The rate limits hosts have reported, for pacing the requests made to them.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    quotas: HashMap<String, Quota>,
+}
+
+impl RateLimiter {
+    /// Note the rate limit in a response from `host` that arrived at `now`. A response that says
+    /// nothing about it leaves what was known as it was.
+    pub fn observe(&mut self, host: &str, header: impl Fn(&str) -> Option<String>, now: SystemTime) {
+        if let Some(quota) = parse_quota(header, now) {
+            self.quotas.insert(host.to_string(), quota);
+        }
+    }
+
+    /// How long to wait, at `now`, before the next request to `host`: until its limit resets when
+    /// none are left, or a share of that when only a few are, so they last until then. `None` to
+    /// send it straight away, as when nothing is known, the limit has reset, or the reset is more
+    /// than `MAX_WAIT` away.
+    pub fn delay(&self, host: &str, now: SystemTime) -> Option<Duration> {
+        let quota = self.quotas.get(host)?;
+        let until_reset = quota.reset.duration_since(now).ok().filter(|wait| !wait.is_zero())?;
+        match quota.remaining {
+            0 if until_reset <= MAX_WAIT => Some(until_reset),
+            0 => None,
+            remaining if remaining < PACE_BELOW => Some((until_reset / (remaining as u32 + 1)).min(MAX_PACE)),
+            _ => None,
+        }
+    }
+
+    /// Count a request about to be sent to `host` against what it has left, so requests sent
+    /// before its responses say so are paced too
+    pub fn reserve(&mut self, host: &str) {
+        if let Some(quota) = self.quotas.get_mut(host) {
+            quota.remaining = quota.remaining.saturating_sub(1);
+        }
+    }
+}
+
+/// Middleware for every request the tool makes: wait as the host's rate limit calls for, saying
+/// so on standard error when it's for long, and note the limit its response reports
+pub(crate) fn pace(request: Request<SendBody>, next: MiddlewareNext) -> Result<Response<Body>, ureq::Error> {
+    let host = request.uri().authority().map_or_else(String::new, |authority| authority.to_string());
+    let delay = {
+        let mut limiter = LIMITER.lock().unwrap_or_else(|e| e.into_inner());
+        let limiter = limiter.get_or_insert_with(RateLimiter::default);
+        let delay = limiter.delay(&host, SystemTime::now());
+        limiter.reserve(&host);
+        delay
+    };
+    if let Some(delay) = delay {
+        if delay >= Duration::from_secs(1) {
+            warn!("Waiting {}s for {}'s rate limit to reset", delay.as_secs_f64().ceil(), host);
+        } else {
+            debug!("Pacing requests to {}: waiting {}ms", host, delay.as_millis());
+        }
+        thread::sleep(delay);
+    }
+    let response = next.handle(request)?;
+    let header = |name: &str| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    LIMITER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(RateLimiter::default)
+        .observe(&host, header, SystemTime::now());
+    Ok(response)
+}
@@ -0,0 +1,79 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+
+use crate::error::ReviewError;
+use crate::vcs::{git_command, git_output};
+
+/// Whether the branch was rewritten between two pushes: true when `old_head` is no longer an
+/// ancestor of `new_head`, as after a rebase and force-push.
+pub fn was_rebased(old_head: &str, new_head: &str) -> Result<bool, ReviewError> {
+    let mut command_binding = git_command();
+    let command = command_binding.args(["merge-base", "--is-ancestor", old_head, new_head]);
+    debug!("Running command: {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+
+    // Exit status 1 means "not an ancestor"; anything else non-zero is an error
+    match output.status.code() {
+        Some(0) => Ok(false),
+        Some(1) => Ok(true),
+        _ => Err(ReviewError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )),
+    }
+}
+
+/// `git range-diff` between the branch as it was at `old_head` and as it is at `new_head`, each
+/// taken from where it forked from `base`.
+pub fn range_diff(base: &str, old_head: &str, new_head: &str) -> Result<String, ReviewError> {
+    let old_base = git_output(&["merge-base", base, old_head])?;
+    let new_base = git_output(&["merge-base", base, new_head])?;
+    git_output(&[
+        "range-diff",
+        "--no-color",
+        &format!("{}..{}", old_base.trim(), old_head),
+        &format!("{}..{}", new_base.trim(), new_head),
+    ])
+}
+
+/// Describe a range-diff for the prompt: commits that are the same as before are listed on one
+/// line, while changed, added and dropped commits are kept in full, with their interdiffs.
+pub fn summarize_range_diff(range_diff: &str) -> String {
+    // e.g. "1:  a1b2c3d = 1:  e4f5a6b Subject", or "-:  ------- > 2:  c7d8e9f Subject"
+    let pair = Regex::new(r"^\s*(?:\d+|-):\s+\S+\s+([=!<>])\s+(?:\d+|-):\s+\S+\s+(.*)$").unwrap();
+
+    let mut unchanged = Vec::new();
+    let mut changed = Vec::new();
+    let mut in_changed_commit = false;
+    for line in range_diff.lines() {
+        if let Some(caps) = pair.captures(line) {
+            in_changed_commit = &caps[1] != "=";
+            if in_changed_commit {
+                changed.push(line.to_string());
+            } else {
+                unchanged.push(caps[2].to_string());
+            }
+        } else if in_changed_commit {
+            changed.push(line.to_string());
+        }
+    }
+
+    let mut summary = String::from(
+        "The branch was rebased since the previous review, which already covered the commits that are \
+         the same as before. Focus on the commits that were changed (!), added (>) or dropped (<), \
+         and mention the unchanged ones in a single line at most.\n\n",
+    );
+    if unchanged.is_empty() {
+        summary.push_str("No commits are unchanged.\n");
+    } else {
+        summary.push_str(&format!("Unchanged commits: {}\n", unchanged.join("; ")));
+    }
+    if changed.is_empty() {
+        summary.push_str("\nOnly the base changed; every commit is the same as before.");
+    } else {
+        summary.push_str(&format!("\n```\n{}\n```", changed.join("\n").trim_end()));
+    }
+    summary
+}
@@ -0,0 +1,143 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::fmt;
+
+// A path separator as written in the code: `/`, `\`, or `\\` inside a string literal
+const SEPARATOR: &str = r"(?:\\\\|\\|/)";
+
+// What can't come right before an absolute path, so `https://host/home/x` and `a/home/x` aren't
+// taken for one
+const BEFORE_PATH: &str = r"(^|[^\w.~/\\:-])";
+
+// The separator after a path prefix, if there is one
+const AFTER_PREFIX: &str = r"(\\\\|\\|/)?";
+
+/// What was scrubbed from the diff before it went into the prompt.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RedactionSummary {
+    /// Absolute paths under the repository, made relative to it
+    pub repo_paths: usize,
+    /// Home directory prefixes replaced with `~`
+    pub home_paths: usize,
+}
+
+impl RedactionSummary {
+    pub fn total(&self) -> usize {
+        self.repo_paths + self.home_paths
+    }
+}
+
+impl fmt::Display for RedactionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scrubbed {} absolute {}: {} made relative to the repository, {} home {} replaced with ~",
+            self.total(),
+            if self.total() == 1 { "path" } else { "paths" },
+            self.repo_paths,
+            self.home_paths,
+            if self.home_paths == 1 { "directory" } else { "directories" }
+        )
+    }
+}
+
+/// Rewrites absolute paths in a diff's lines so they don't give away usernames or depend on where
+/// the repository was checked out: paths under the repository root become relative to it, and
+/// home directories, whoever's they are, become `~`. Both POSIX and Windows paths are recognized.
+#[derive(Debug, Clone)]
+pub struct PathScrubber {
+    repo_root: Option<Regex>,
+    homes: Vec<Regex>,
+}
+
+impl PathScrubber {
+    /// A scrubber for a repository checked out at `repo_root`, on a machine where the user's home
+    /// is `home`. Home directories under `/home`, `/Users` and `C:\Users` are recognized anyway.
+    pub fn new(repo_root: Option<&str>, home: Option<&str>) -> Self {
+        let mut homes = vec![Regex::new(&format!(
+            r"{}((?:/home|/Users)/[\w.-]+|[A-Za-z]:{}(?i:Users){}[\w.-]+){}",
+            BEFORE_PATH, SEPARATOR, SEPARATOR, AFTER_PREFIX
+        ))
+        .unwrap()];
+        // Homes elsewhere, such as /root or /var/lib/jenkins
+        if let Some(home) = home.and_then(path_pattern) {
+            homes.push(Regex::new(&format!(r"{}({}){}", BEFORE_PATH, home, AFTER_PREFIX)).unwrap());
+        }
+        PathScrubber {
+            repo_root: repo_root
+                .and_then(path_pattern)
+                .map(|root| Regex::new(&format!(r"{}({}){}", BEFORE_PATH, root, AFTER_PREFIX)).unwrap()),
+            homes,
+        }
+    }
+
+    /// Scrub the lines of `diff`'s hunks, leaving the file headers alone. Returns the scrubbed
+    /// diff and what was changed.
+    pub fn scrub_diff(&self, diff: &str) -> (String, RedactionSummary) {
+        let mut summary = RedactionSummary::default();
+        let mut in_hunk = false;
+        let mut scrubbed = String::with_capacity(diff.len());
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("diff ") {
+                in_hunk = false;
+            } else if line.starts_with("@@") {
+                in_hunk = true;
+            } else if in_hunk {
+                scrubbed.push_str(&self.scrub_line(line, &mut summary));
+                continue;
+            }
+            scrubbed.push_str(line);
+        }
+        (scrubbed, summary)
+    }
+
+    /// Scrub one line, counting what was replaced in `summary`
+    pub fn scrub_line(&self, line: &str, summary: &mut RedactionSummary) -> String {
+        let mut line = line.to_string();
+        if let Some(root) = &self.repo_root {
+            line = replace_prefixes(root, &line, |caps| {
+                summary.repo_paths += 1;
+                // The root itself becomes `.`; paths under it lose the root and its separator
+                match caps.get(3) {
+                    Some(_) => caps[1].to_string(),
+                    None => format!("{}.", &caps[1]),
+                }
+            });
+        }
+        for home in &self.homes {
+            line = replace_prefixes(home, &line, |caps| {
+                summary.home_paths += 1;
+                format!("{}~{}", &caps[1], caps.get(3).map_or("", |separator| separator.as_str()))
+            });
+        }
+        line
+    }
+}
+
+/// Replace the matches of `prefix` in `line` with `replace`, except where the last name in the
+/// prefix goes on, as `/home/alice/app` does in `/home/alice/application`
+fn replace_prefixes(prefix: &Regex, line: &str, mut replace: impl FnMut(&Captures) -> String) -> String {
+    prefix
+        .replace_all(line, |caps: &Captures| {
+            let whole = caps.get(0).unwrap();
+            let goes_on = caps.get(3).is_none()
+                && line[whole.end()..].starts_with(|c: char| c.is_alphanumeric() || "_.-".contains(c));
+            if goes_on { whole.as_str().to_string() } else { replace(caps) }
+        })
+        .into_owned()
+}
+
+/// A pattern matching `path` with any of the separators, case-insensitively for a Windows path.
+/// `None` for a root directory, which would match every absolute path.
+fn path_pattern(path: &str) -> Option<String> {
+    let components: Vec<&str> = path.split(['/', '\\']).filter(|component| !component.is_empty()).collect();
+    let windows = components.first().is_some_and(|first| first.len() == 2 && first.ends_with(':'));
+    if components.len() < if windows { 2 } else { 1 } {
+        return None;
+    }
+    let escaped: Vec<String> = components.iter().map(|component| regex::escape(component)).collect();
+    let joined = escaped.join(SEPARATOR);
+    Some(if windows { format!("(?i:{})", joined) } else { format!("/{}", joined) })
+}
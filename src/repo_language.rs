@@ -0,0 +1,267 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+use crate::output::language_for_path;
+use crate::vcs::{git_common_dir, git_output};
+use crate::write_atomic::write_atomic;
+
+/// A language with more than this share of a repository's source files is its main language
+pub const DOMINANT_SHARE: f64 = 0.5;
+
+/// Languages with less than this share of the source files don't contribute presets
+pub const MIN_SHARE: f64 = 0.2;
+
+/// The value of --repo-language that turns the presets off
+pub const NO_REPO_LANGUAGE: &str = "none";
+
+/// Where the detected languages are cached, relative to the git directory
+pub const LANGUAGE_CACHE_FILE: &str = "llm_code_review_languages.json";
+
+/// Maps a changed source file to the test files that usually cover it. `source` is matched
+/// against the whole path, and `$1` etc. in each of `tests` are replaced with its groups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestRule {
+    pub source: &'static str,
+    pub tests: &'static [&'static str],
+}
+
+/// The defaults used for repositories written mostly in one language.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LanguagePreset {
+    /// The name `language_for_path` gives the language
+    pub language: &'static str,
+    /// Directories of build output and vendored code, dropped from the diff wherever they appear
+    pub excludes: &'static [&'static str],
+    pub test_rules: &'static [TestRule],
+}
+
+pub const LANGUAGE_PRESETS: &[LanguagePreset] = &[
+    LanguagePreset {
+        language: "go",
+        excludes: &["vendor/"],
+        test_rules: &[TestRule { source: r"^(.*)\.go$", tests: &["${1}_test.go"] }],
+    },
+    LanguagePreset {
+        language: "java",
+        excludes: &["target/", "build/"],
+        test_rules: &[TestRule {
+            source: r"^(.*)src/main/java/(.*)\.java$",
+            tests: &["${1}src/test/java/${2}Test.java"],
+        }],
+    },
+    LanguagePreset {
+        language: "javascript",
+        excludes: &["dist/", "build/", "node_modules/"],
+        test_rules: &[TestRule {
+            source: r"^(.*/)?([^/]*)\.js$",
+            tests: &["${1}${2}.test.js", "${1}${2}.spec.js", "${1}__tests__/${2}.js"],
+        }],
+    },
+    LanguagePreset {
+        language: "python",
+        excludes: &["build/", "dist/", "__pycache__/", ".venv/"],
+        test_rules: &[TestRule {
+            source: r"^(.*/)?([^/]*)\.py$",
+            tests: &["tests/test_${2}.py", "${1}test_${2}.py"],
+        }],
+    },
+    LanguagePreset {
+        language: "ruby",
+        excludes: &["vendor/"],
+        test_rules: &[TestRule {
+            source: r"^lib/(.*)\.rb$",
+            tests: &["spec/${1}_spec.rb", "test/${1}_test.rb"],
+        }],
+    },
+    LanguagePreset {
+        language: "rust",
+        excludes: &["target/"],
+        test_rules: &[TestRule {
+            source: r"^(.*/)?src/(.*)\.rs$",
+            tests: &["${1}tests/${2}_test.rs", "${1}tests/${2}.rs"],
+        }],
+    },
+    LanguagePreset {
+        language: "typescript",
+        excludes: &["dist/", "build/", "node_modules/"],
+        test_rules: &[TestRule {
+            source: r"^(.*/)?([^/]*)\.ts$",
+            tests: &["${1}${2}.test.ts", "${1}${2}.spec.ts", "${1}__tests__/${2}.ts"],
+        }],
+    },
+];
+
+/// The preset for `language` (case-insensitive), if there is one
+pub fn preset(language: &str) -> Option<&'static LanguagePreset> {
+    let language = language.to_lowercase();
+    LANGUAGE_PRESETS.iter().find(|preset| preset.language == language)
+}
+
+/// How many of a repository's files are in each language, recognised by their extensions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LanguageProfile {
+    /// The commit the files were listed at, for caching
+    pub head: Option<String>,
+    /// Languages and their file counts, most files first
+    pub counts: Vec<(String, usize)>,
+}
+
+impl LanguageProfile {
+    /// Count the languages of `paths`. Files in languages `language_for_path` doesn't know are
+    /// left out, so documentation and data files don't dilute the shares.
+    pub fn from_paths<'a>(paths: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for path in paths {
+            if let Some(language) = language_for_path(Path::new(path)) {
+                *counts.entry(language).or_default() += 1;
+            }
+        }
+        let mut counts: Vec<(String, usize)> =
+            counts.into_iter().map(|(language, count)| (language.to_string(), count)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        LanguageProfile { head: None, counts }
+    }
+
+    fn share(&self, count: usize) -> f64 {
+        let total: usize = self.counts.iter().map(|(_, count)| count).sum();
+        if total == 0 { 0.0 } else { count as f64 / total as f64 }
+    }
+
+    /// The language with more than `DOMINANT_SHARE` of the files, if any
+    pub fn dominant(&self) -> Option<&str> {
+        self.counts
+            .first()
+            .filter(|(_, count)| self.share(*count) > DOMINANT_SHARE)
+            .map(|(language, _)| language.as_str())
+    }
+
+    /// The languages with at least `MIN_SHARE` of the files, most files first
+    pub fn significant(&self) -> Vec<&str> {
+        self.counts
+            .iter()
+            .filter(|(_, count)| self.share(*count) >= MIN_SHARE)
+            .map(|(language, _)| language.as_str())
+            .collect()
+    }
+
+    /// The languages and their shares, e.g. `rust 80%, shell 20%`
+    pub fn describe(&self) -> String {
+        if self.counts.is_empty() {
+            return "no recognised source files".to_string();
+        }
+        self.counts
+            .iter()
+            .map(|(language, count)| format!("{} {:.0}%", language, self.share(*count) * 100.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The defaults chosen from a repository's languages: a language hint for output formats and code
+/// fences, and the presets whose excludes and test rules apply.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoDefaults {
+    /// The main language, used when --language isn't given and a file's own isn't known
+    pub language: Option<String>,
+    pub presets: Vec<&'static LanguagePreset>,
+}
+
+impl RepoDefaults {
+    /// The main language, if there is one, and the presets of every language with a significant
+    /// share. A repository where no language dominates still gets their excludes and test rules,
+    /// but no language hint.
+    pub fn from_profile(profile: &LanguageProfile) -> Self {
+        RepoDefaults {
+            language: profile.dominant().map(str::to_string),
+            presets: profile.significant().into_iter().filter_map(preset).collect(),
+        }
+    }
+
+    /// The defaults for a language given with --repo-language, or none at all for `none`
+    pub fn for_language(language: &str) -> Self {
+        if language.eq_ignore_ascii_case(NO_REPO_LANGUAGE) {
+            return RepoDefaults::default();
+        }
+        RepoDefaults {
+            language: Some(language.to_lowercase()),
+            presets: preset(language).into_iter().collect(),
+        }
+    }
+
+    /// Whether `path` is inside one of the presets' excluded directories, at any depth
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.presets.iter().flat_map(|preset| preset.excludes).any(|dir| {
+            path.starts_with(dir) || path.contains(&format!("/{}", dir))
+        })
+    }
+
+    /// The test files that usually cover `path`, by the presets' test rules. Files that look like
+    /// tests themselves have none.
+    pub fn related_tests(&self, path: &str) -> Vec<String> {
+        let mut tests = Vec::new();
+        if looks_like_test(path) {
+            return tests;
+        }
+        for rule in self.presets.iter().flat_map(|preset| preset.test_rules) {
+            let source = Regex::new(rule.source).unwrap();
+            let Some(captures) = source.captures(path) else {
+                continue;
+            };
+            for template in rule.tests {
+                let mut test = String::new();
+                captures.expand(template, &mut test);
+                if !tests.contains(&test) {
+                    tests.push(test);
+                }
+            }
+        }
+        tests
+    }
+}
+
+/// Whether `path` is in a test directory or named like a test file in one of the presets
+fn looks_like_test(path: &str) -> bool {
+    let test_dir = Regex::new(r"(^|/)(tests?|spec|__tests__)/").unwrap();
+    let test_name = Regex::new(r"(^|/)test_[^/]*$|[_.](test|spec)\.[^/.]+$|Test\.java$").unwrap();
+    test_dir.is_match(path) || test_name.is_match(path)
+}
+
+/// The languages of the files tracked at HEAD, read from the cache in the git directory shared by
+/// the repository's worktrees when it was written for the same commit.
+pub fn detect_repo_languages() -> Result<LanguageProfile, ReviewError> {
+    let head = git_output(&["rev-parse", "--verify", "--quiet", "HEAD"]).ok().map(|head| head.trim().to_string());
+    let cache = git_common_dir()?.join(LANGUAGE_CACHE_FILE);
+
+    if head.is_some()
+        && let Some(profile) = read_cache(&cache)
+        && profile.head == head
+    {
+        debug!("Using the languages cached in {}", cache.display());
+        return Ok(profile);
+    }
+
+    let files = git_output(&["ls-files"])?;
+    let mut profile = LanguageProfile::from_paths(files.lines());
+    profile.head = head;
+    // Without a commit there's nothing stable to cache against
+    if profile.head.is_some() {
+        let json = serde_json::to_vec_pretty(&profile).unwrap();
+        if let Err(e) = write_atomic(&cache, &json) {
+            warn!("Could not cache the repository's languages: {}", e);
+        }
+    }
+    Ok(profile)
+}
+
+fn read_cache(path: &Path) -> Option<LanguageProfile> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents)
+        .inspect_err(|e| debug!("Ignoring the language cache {}: {}", path.display(), e))
+        .ok()
+}
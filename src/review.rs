@@ -1,22 +1,198 @@
 use clap::builder::PossibleValuesParser;
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser};
 #[allow(unused_imports)]
-use log::{debug, error, info, trace, warn, LevelFilter};
+use log::{debug, error, info, log_enabled, trace, warn, Level, LevelFilter};
 // Rust log levels: Error = 1, Warn = 2, Info = 3, Debug = 4, Trace = 5
 use regex::Regex;
+use serde::Serialize;
 use simple_logger::SimpleLogger;
-use std::process::{self, Command}; // Import the logging macros
+use std::cell::Cell;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-// I wish there were a simple consistent method to count tokens, but there isn't
-// as far as I can tell, so we're gonna use a poor estimation and keep safely
-// inside the context limit
-const MAX_TOKENS: usize = 50_000; // Claude's limit is 100k, this should be a safe amount
-const CHARS_PER_TOKEN: usize = 4; // simple approximation
+use crate::attention::{assess, attention_section, parse_confidence, AttentionRules, CONFIDENCE_INSTRUCTIONS};
+use crate::budget::{
+    fit_to_budget, write_budget_report, Budget, BudgetReport, CharsPerToken, TokenEstimator, DEFAULT_CHARS_PER_TOKEN,
+    DEFAULT_MAX_TOKENS,
+};
+use crate::canary::{canary_for, canary_line, check_canary, strip_canary, CANARY_INSTRUCTIONS};
+use crate::capabilities::{capabilities, render_probes};
+use crate::category::{category_instructions, Category, Taxonomy};
+use crate::chunk_skip::{add_excludes, chunk_label, load_excludes, run_skippable, skipped_note, KeyWatcher, EXCLUDE_FILE};
+use crate::ci_log::{ci_log_section, CI_LOG_MAX_CHARS};
+use crate::clipboard::copy;
+use crate::client::{
+    builtin_provider, default_model, parse_extra_param, provider_for, provider_from_env, RequestSettings, BUILTIN_PROVIDERS,
+};
+use crate::comment_series::{plan_comments, split_review, CommentAction, GITHUB_COMMENT_LIMIT};
+use crate::config::{load_config, user_config_path, Config};
+use crate::compress::{compress_diff, elided_line_count};
+use crate::context::dedup_sections;
+use crate::context_arbiter::{context_priority, ContextArbiter, ContextFeature, CONTEXT_FEATURES};
+use crate::contracts::{compatibility_section, find_contracts, proto_field_changes, ContractKind};
+use crate::deadline::Deadline;
+use crate::doctor::{round_trip_with_key, run_doctor};
+use crate::dashboard::{load_runs, render_dashboard, run_id, write_dashboard, DEFAULT_PAGE_SIZE, REVIEWS_DIR};
+use crate::diff::{parse_diff, render_diff, split_diff_by_file, FileDiff, FileKind, Hunk};
+use crate::diff_filter::{filter_diff_by_paths, filter_public_api, filter_small_hunks, supports_public_api};
+use crate::draft_issues::{
+    draft_issue, follow_ups, unfiled, DraftIssue, ReviewedCode, DEFAULT_ISSUE_LABEL, ISSUE_REQUEST_TIMEOUT,
+};
+use crate::encoding::{decode_diff, decode_diff_with};
+use crate::error::ReviewError;
+use crate::feedback::{feedback_report, Feedback, History, HistoryEntry, Rating, HISTORY_FILE};
+use crate::file_lock::lock;
+use crate::files::{
+    full_file_block, full_files, full_files_section, pack_files, read_new_version, FullFile, DEFAULT_FULL_FILE_MAX_LINES,
+    FULL_FILES_INTRO,
+};
+use crate::findings::{
+    cap_findings, filter_by_confidence, merge_findings, parse_json_findings, render_json, Finding, FindingLimits,
+    JsonFinding, Severity,
+};
+use crate::forge_auth::forge_token;
+use crate::github::{
+    actions_api_url, api_base, create_issue, create_issue_comment, delete_issue_comment, fetch_github_diff,
+    github_actions_pr_context, issue_comments, open_issue_bodies, parse_github_url, update_issue_comment,
+    PullRequestContext,
+};
+use crate::infra::{check_infra, find_infra, infrastructure_section, local_findings_section, InfraKind, INFRA_CHECKS};
+use crate::migrations::{check_migrations, find_migrations, migrations_section, Migration};
+use crate::issues::{
+    linked_issues_section, parse_branch_refs, parse_issue_refs, parse_remote_url, Forge, IssueSources, ISSUE_DESCRIPTION_MAX_CHARS,
+    ISSUE_FETCH_TIMEOUT, MAX_LINKED_ISSUES,
+};
+use crate::history::{load_reviews, related_excerpts};
+use crate::lint::{auto_lint_command, lint, lint_section, on_changed_files, LINT_MAX_DIAGNOSTICS, LINT_TIMEOUT};
+use crate::locale::{Locale, REVIEW_LANGUAGES};
+use crate::merges::{adapt_to_merges, merges_in, MergeAdaptation};
+use crate::markers::{describe_markers, find_new_markers, markers_without_issue, DEFAULT_TODO_ISSUE_PATTERN};
+use crate::naming::{check_naming, naming_section, Dictionary};
+use crate::network;
+use crate::normalize::normalize_review;
+use crate::notebook::{convert_notebooks, read_blob_or_file, NOTEBOOK_EXTENSION};
+use crate::notify;
+#[cfg(feature = "plugins")]
+use crate::plugin::Plugins;
+use crate::output::{
+    format_mismatch, has_review_issues, language_for_path, missing_structure, AnnotationLevel, OutputFormat,
+    ANNOTATION_LEVELS, OUTPUT_FORMATS,
+};
+use crate::partial_clone::{disable_lazy_fetch, Checkout};
+use crate::persona::{Persona, Personas};
+use crate::pr_config::{PrConfig, PR_CONFIG_KEYS};
+use crate::preflight::check_prompt;
+use crate::preprocess::{preprocess, PREPROCESS_TIMEOUT};
+use crate::progress::{GenerationLimits, DEFAULT_HEARTBEAT_SECS};
+use crate::prompt_schema::PromptSchema;
+use crate::provenance::{rfc3339_utc, sha256_hex, Provenance};
+use crate::provider::{load_provider_config, select_provider, ProviderConfig, ProviderFormat};
+use crate::quality::{check_quality, Escalation, QualityThresholds};
+use crate::questions::answers_section;
+use crate::rebase::{range_diff, summarize_range_diff, was_rebased};
+use crate::redact::{PathScrubber, RedactionSummary};
+use crate::repo_language::{detect_repo_languages, RepoDefaults};
+use crate::review_length::{diff_changed_lines, ReviewLength, ReviewTarget, REVIEW_LENGTHS};
+use crate::review_type::{ReviewType, REVIEW_TYPES};
+use crate::runaway::{cap_response, RunawayLimits};
+use crate::rust_edition::{detect_rust_edition, edition_note};
+use crate::salvage::{extract_findings, salvage_banner};
+use crate::sample::{sample_note, sample_similar, SampledCluster};
+use crate::severity_policy::{glob_regex, min_severity_instructions, SeverityFloor, SeverityPolicy};
+use crate::selection::{combine_commits, later_overlaps, selected_commits, selection_note, CombinedCommits, CommitFilter};
+use crate::smoke::run_smoke_test;
+use crate::size_tier::{choose_tier, group_into_chunks, split_into_parts, ChunkMode, SizeTier, TierThresholds, CHUNK_MODES};
+use crate::stat::format_stat_as_table;
+use crate::status::{parse_status_address, StatusBoard};
+use crate::system_prompt::{PromptSection, SystemPrompt};
+use crate::template::{Placeholder, PromptTemplate};
+use crate::tokens::{context_window, save_token_cache, use_token_cache, Encoding, Tokenizer};
+use crate::transcript::Transcript;
+use crate::truncate::truncate_graphemes;
+use crate::usage::{budget_banner, estimate_cost, month_spend, usage_report, Ledger, UsageEntry};
+use crate::vcs::{
+    context_arg, current_branch, get_untracked_diff, git_command, git_output, has_commits, require_repository, resolve_git_revisions,
+    try_command_diff, try_diff,
+    untracked_files, untracked_pathspecs, Vcs, EMPTY_TREE, VCS_NAMES,
+};
+use crate::wizard::{equivalent_flags, run_wizard, save_config, Tty};
+use crate::workspace::{packages_section, restrict_to_package, Package, Workspace};
+use crate::wrap::{terminal_width, wrap_markdown, wrap_prose_sections};
+use crate::write_atomic::write_atomic;
 
-pub const DEFAULT_SYSTEM_PROMPT: &str = include_str!("rsc/default_system_prompt.txt");
+// Moved to the vcs and system_prompt modules; re-exported for existing callers
+pub use crate::system_prompt::{
+    assemble_system_prompt, DEFAULT_SYSTEM_PROMPT, PROMPT_OUTPUT_RULES, PROMPT_REVIEW_RULES,
+    PROMPT_ROLE, PROMPT_TONE,
+};
+pub use crate::vcs::try_git_diff;
+
+// Percentage of the token budget that excerpts from --history-dir may use
+const HISTORY_BUDGET_SHARE: usize = 10;
+
+// How many of the files --include and --exclude leave out are named in the prompt
+const OMITTED_FILES_LISTED: usize = 50;
+
+// Runs of more unchanged lines than this inside a hunk are elided, unless --no-compress is given
+const MAX_UNCHANGED_RUN: usize = 5;
+
+// How often --watch checks the working tree, and how long a change must stay put before we re-run
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Rough worst-case durations of optional steps, for deciding what --deadline-secs leaves time for
+const GIT_STEP_ESTIMATE: Duration = Duration::from_secs(2);
+const RANGE_DIFF_ESTIMATE: Duration = Duration::from_secs(5);
+const HISTORY_ESTIMATE: Duration = Duration::from_secs(2);
+const LINT_ESTIMATE: Duration = Duration::from_secs(30);
+
+// The default --hard-max-chars: far more than any model's context window should need
+const DEFAULT_HARD_MAX_CHARS: usize = 2_000_000;
+
+// The default -U, which a config file's unified_context takes the place of
+const DEFAULT_UNIFIED_CONTEXT: usize = 3;
+
+// The default --untracked-max-lines: enough for a new module, not for a generated file or data
+const DEFAULT_UNTRACKED_MAX_LINES: usize = 1000;
+
+// Exit status of an --each run that wrote some prompts but not all, unless --allow-partial is given
+pub const EXIT_PARTIAL: i32 = 3;
+
+// The --timeout-secs of a --pre-commit run, so a slow provider can't hold up a commit for long
+const DEFAULT_PRE_COMMIT_TIMEOUT_SECS: usize = 120;
+
+// The default --confirm-threshold: well past a typical pull request, short of a release's worth
+const DEFAULT_CONFIRM_THRESHOLD: usize = 50_000;
+
+// Written to --output-dir when --each couldn't write a prompt for some commits
+const FAILED_COMMITS_FILE: &str = "failed.json";
+
+// Appended to the system prompt with --raw-code, whose prompt contains source code and not a diff
+const RAW_CODE_INSTRUCTIONS: &str = "\nThe code to review is complete source code, not a diff. Review all of it, and refer to locations by line number, counting the first line of the code block as line 1.\n";
+
+// Appended to the system prompt when unchanged lines were elided from the diff
+const COMPRESSED_DIFF_INSTRUCTIONS: &str = "\nSome runs of unchanged lines have been left out of the diff. A context line reading `… N unchanged lines …` stands in for N such lines, and the line numbers in hunk headers still count them.\n";
+
+// Appended to the system prompt when the diff was limited to the public API with --api-only
+const API_ONLY_INSTRUCTIONS: &str = "\nThe diff has been limited to changes that touch the public API, so private code it depends on may be missing. Focus on what downstream users will notice: signatures, types, visibility, documentation and compatibility.\n";
+
+// Appended to the system prompt with --require-citations
+const REQUIRE_CITATIONS_INSTRUCTIONS: &str = "\nEvery finding must cite the file and line it is about, as `path/to/file.rs:42`, using the line numbers of the new version of the file from the hunk headers. Only cite lines that appear in the diff. Leave out any finding you can't tie to a specific line.\n";
+
+// Headings placed above the diff, or the code given with --raw-code
+pub const DIFF_HEADING: &str = "PR Code";
+pub const RAW_CODE_HEADING: &str = "Code to Review";
 
 pub const REVIEW_EXAMPLES: &str = include_str!("rsc/review_examples.txt");
 
+// Shown at the end of --help in place of the examples, unless --verbose is also given
+const SHORT_HELP_FOOTER: &str = "Run with --help --verbose to see examples.";
+
 /// Ask an LLM to review code changes. This tool passes arguments directly to 'git diff',
 /// allowing you to use any git diff syntax or options.
 #[derive(Parser, Debug, Default)]
@@ -24,29 +200,327 @@ pub const REVIEW_EXAMPLES: &str = include_str!("rsc/review_examples.txt");
     name = "llm_code_review",
     version = "1.0",
     author = "Matthew Nielsen <xunker@pyxidis.org>",
-    after_help = REVIEW_EXAMPLES,
-    about)]
+    about,
+    disable_help_subcommand = true)]
 pub struct Cli {
     /// Add additional context for the review, appended to the system prompt
     #[arg(short, long, value_name = "TEXT")]
     pub context: Option<String>,
 
+    /// Leave out context sections (--context, PR description, history, ...) that repeat another
+    #[arg(long = "context-dedup", action = ArgAction::SetTrue)]
+    pub context_dedup: bool,
+
+    /// With --context-dedup, also treat sections differing only in whitespace as duplicates
+    #[arg(long = "context-dedup-fuzzy", action = ArgAction::SetTrue, requires = "context_dedup")]
+    pub context_dedup_fuzzy: bool,
+
     /// Override the default system prompt
     #[arg(short = 's', long = "system-prompt", value_name = "TEXT")]
     system_prompt: Option<String>,
 
+    /// Replace the review criteria section of the default system prompt with this file
+    #[arg(long = "rules-file", value_name = "PATH", conflicts_with = "system_prompt")]
+    pub rules_file: Option<PathBuf>,
+
+    /// What the review looks at: general, or only security, performance, tests or style.
+    /// Ignored with --system-prompt
+    #[arg(long = "review-type", value_name = "TYPE", default_value = "general", value_parser = PossibleValuesParser::new(REVIEW_TYPES), conflicts_with = "rules_file")]
+    pub review_type: String,
+
+    /// Replace the output format rules section of the default system prompt with this file
+    #[arg(long = "output-rules-file", value_name = "PATH", conflicts_with = "system_prompt")]
+    pub output_rules_file: Option<PathBuf>,
+
+    /// Replace the tone section of the default system prompt with this file
+    #[arg(long = "tone-file", value_name = "PATH", conflicts_with = "system_prompt")]
+    pub tone_file: Option<PathBuf>,
+
+    /// Use this file as the severity rubric section of the system prompt, defining what each
+    /// severity means; it takes the place of any rubric in --severity-policy
+    #[arg(long = "severity-rubric", value_name = "PATH", conflicts_with = "system_prompt")]
+    pub severity_rubric: Option<PathBuf>,
+
+    /// TOML file with the team's severity `rubric` and [[floor]] tables (`path` glob and least
+    /// `severity`) enforced on the findings
+    #[arg(long = "severity-policy", value_name = "PATH")]
+    pub severity_policy_file: Option<PathBuf>,
+
+    /// Raise findings on paths matching GLOB to at least SEVERITY, whatever the model said; may be
+    /// repeated, e.g. --severity-floor 'billing/**=error'
+    #[arg(long = "severity-floor", value_name = "GLOB=SEVERITY")]
+    pub severity_floor: Vec<SeverityFloor>,
+
+    /// Ask the model to leave out findings less serious than SEVERITY
+    #[arg(long = "min-severity", value_name = "SEVERITY", value_parser = PossibleValuesParser::new(Severity::NAMES))]
+    pub min_severity: Option<String>,
+
+    /// Show at most N findings for any one file; the rest are set apart in a collapsed appendix of
+    /// lower-priority notes. The model is asked to keep to it too.
+    #[arg(long = "max-findings-per-file", value_name = "N", value_parser = parse_positive)]
+    pub max_findings_per_file: Option<usize>,
+
+    /// Show at most N findings in all, the most serious first; the rest are set apart as with
+    /// --max-findings-per-file
+    #[arg(long = "max-findings", value_name = "N", value_parser = parse_positive)]
+    pub max_findings: Option<usize>,
+
+    /// The policy from --severity-policy and --severity-floor, filled in by `run`
+    #[arg(skip)]
+    pub severity_policy: SeverityPolicy,
+
+    /// Say which files a human should review and which the LLM's review is likely enough for,
+    /// from path and content heuristics and a confidence the model gives for each file. Added to
+    /// the review with --send, and to the --token-budget-report and --save-transcript JSON
+    #[arg(long = "attention-report")]
+    pub attention_report: bool,
+
+    /// TOML file of [[human]] and [[llm]] tables (`path` glob and `reason`) adding to
+    /// --attention-report's heuristics
+    #[arg(long = "attention-rules", value_name = "PATH", requires = "attention_report")]
+    pub attention_rules_file: Option<PathBuf>,
+
+    /// The rules from --attention-rules, filled in by `run`
+    #[arg(skip)]
+    pub attention_rules: AttentionRules,
+
+    /// Review as a persona: mentor, strict-gatekeeper, security-auditor, terse-senior or one from --personas-file
+    #[arg(long, value_name = "NAME", conflicts_with = "system_prompt")]
+    pub persona: Option<String>,
+
+    /// TOML file of [[persona]] tables (name, description, role and tone) adding personas
+    #[arg(long = "personas-file", value_name = "PATH")]
+    pub personas_file: Option<PathBuf>,
+
+    /// Review only the files of this member of the repository's Cargo or npm workspace, or of
+    /// --packages-file
+    #[arg(long, value_name = "NAME")]
+    pub package: Option<String>,
+
+    /// TOML file of [[package]] tables (name and path) listing the repository's packages, in place
+    /// of the members of its Cargo or npm workspace
+    #[arg(long = "packages-file", value_name = "PATH")]
+    pub packages_file: Option<PathBuf>,
+
+    /// The repository's packages, filled in by `run`
+    #[arg(skip)]
+    pub workspace: Option<Workspace>,
+
+    /// Review only the files matching GLOB, e.g. --include 'src/**/*.rs' (may be repeated). Files
+    /// that also match an --exclude are still left out.
+    #[arg(long, value_name = "GLOB", action = ArgAction::Append)]
+    pub include: Vec<String>,
+
+    /// Leave the files matching GLOB out of the review, e.g. --exclude 'docs/**' (may be repeated)
+    #[arg(long, value_name = "GLOB", action = ArgAction::Append)]
+    pub exclude: Vec<String>,
+
+    /// List the personas --persona accepts and exit
+    #[arg(long = "list-personas", action = ArgAction::SetTrue)]
+    pub list_personas: bool,
+
+    /// Review a built-in sample change with a mock provider, without reading the repository,
+    /// printing PASS or FAIL for each stage of the pipeline, and exit
+    #[arg(long = "smoke-test", action = ArgAction::SetTrue)]
+    pub smoke_test: bool,
+
+    /// With --smoke-test, send the configured provider a test request instead of using the mock
+    #[arg(long, action = ArgAction::SetTrue, requires = "smoke_test")]
+    pub live: bool,
+
+    /// Filled in from --persona when the run starts
+    #[arg(skip)]
+    pub selected_persona: Option<Persona>,
+
     /// Print the current default system prompt and exit
     #[arg(short = 'S', long = "show-system-prompt", action = ArgAction::SetTrue)]
     pub show_system_prompt: bool,
 
+    /// Print the prompt template in use, from --prompt-template or else the built-in one, and exit
+    #[arg(long = "show-template", action = ArgAction::SetTrue)]
+    pub show_template: bool,
+
+    /// Print the settings from the config files merged with the command line, and the files they
+    /// came from, and exit
+    #[arg(long = "show-config", action = ArgAction::SetTrue)]
+    pub show_config: bool,
+
+    /// Print which optional tools this machine has (git features, clipboard, pager, keyring) and
+    /// exit. Features needing one that's missing fall back rather than fail.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub capabilities: bool,
+
+    /// Don't offer to set up a config file when none is found
+    #[arg(long = "no-wizard", action = ArgAction::SetTrue)]
+    pub no_wizard: bool,
+
+    /// Take defaults from this config file alone, instead of the repository's .llm_code_review.toml
+    /// and the user's config.toml
+    #[arg(long = "config", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Read no config file, so only the command line and the built-in defaults apply
+    #[arg(long = "no-config", action = ArgAction::SetTrue, conflicts_with = "config")]
+    pub no_config: bool,
+
+    /// Leave out the provenance section (repository, branch, commits, diff hash, ...) and report entry
+    #[arg(long = "no-provenance", action = ArgAction::SetTrue)]
+    pub no_provenance: bool,
+
+    /// Keep the prompt byte for byte the same for the same diff and options, from run to run and
+    /// release to release, for caching it: its layout is pinned to --prompt-schema, the provenance
+    /// section leaves out the time and tool version, and it's only wrapped to --wrap, never to the
+    /// terminal
+    #[arg(long = "stable-prompt", action = ArgAction::SetTrue, conflicts_with = "deadline_secs")]
+    pub stable_prompt: bool,
+
+    /// Lay the prompt out with the template in this file, placing {{system_prompt}},
+    /// {{output_format}}, {{context}}, {{diff_heading}}, {{diff}}, {{branch}} and {{diff_args}},
+    /// e.g. to put the diff before the instructions. `\{{` is a literal `{{`
+    #[arg(long = "prompt-template", value_name = "PATH", conflicts_with = "stable_prompt")]
+    pub prompt_template: Option<PathBuf>,
+
+    /// Read from --prompt-template when the run starts
+    #[arg(skip)]
+    pub template: Option<PromptTemplate>,
+
+    /// With --stable-prompt, the version of the prompt's layout to keep to [default: v1]
+    #[arg(long = "prompt-schema", value_name = "VERSION", value_parser = parse_prompt_schema, requires = "stable_prompt")]
+    pub prompt_schema: Option<PromptSchema>,
+
+    /// Send a desktop notification (or ring the terminal bell) when the run finishes
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub notify: bool,
+
+    /// Refuse to output a prompt of more than N characters, whatever the token estimate says
+    #[arg(long = "hard-max-chars", value_name = "N", default_value_t = DEFAULT_HARD_MAX_CHARS, value_parser = parse_positive)]
+    pub hard_max_chars: usize,
+
+    /// While the provider generates a review, report the time taken and tokens received every N
+    /// seconds on standard error, or never with 0
+    #[arg(long = "heartbeat-secs", value_name = "N", default_value_t = DEFAULT_HEARTBEAT_SECS)]
+    pub heartbeat_secs: u64,
+
+    /// Serve the run's progress as JSON on `GET /status` at ADDRESS, a port or HOST:PORT. A port
+    /// alone listens on localhost only.
+    #[arg(long = "status-server", value_name = "ADDRESS", value_parser = parse_status_address)]
+    pub status_server: Option<String>,
+
+    /// Filled in from --status-server when the run starts
+    #[arg(skip)]
+    pub status: StatusBoard,
+
+    /// Give up if the provider sends no tokens within SECS, as when a large local model is still loading
+    #[arg(long = "max-wait-first-token", value_name = "SECS", value_parser = parse_positive)]
+    pub max_wait_first_token: Option<usize>,
+
+    /// Finish within N seconds, leaving out optional context that wouldn't be ready in time
+    #[arg(long = "deadline-secs", value_name = "N", value_parser = parse_positive, conflicts_with = "watch")]
+    pub deadline_secs: Option<usize>,
+
+    /// Filled in from --deadline-secs when the run starts
+    #[arg(skip)]
+    pub deadline: Deadline,
+
+    /// Show a preview of the prompt on stderr and wait for Enter before printing it
+    #[arg(long = "require-approval", action = ArgAction::SetTrue)]
+    pub require_approval: bool,
+
+    /// Print the prompt with the start and end of each of its sections marked, for debugging
+    #[arg(long = "debug-prompt", action = ArgAction::SetTrue)]
+    pub debug_prompt: bool,
+
     /// Request review output be in a specific format
-    #[arg(short = 'F', long = "output-format", value_name = "FORMAT", value_parser = PossibleValuesParser::new(["markdown", "asciidoc", "mediawiki"]))]
+    #[arg(short = 'F', long = "output-format", value_name = "FORMAT", value_parser = PossibleValuesParser::new(OUTPUT_FORMATS))]
     pub output_format: Option<String>,
 
+    /// Leave out findings the model gave less than this confidence, from 0 to 1, e.g. 0.7 so CI
+    /// only acts on the ones it's sure of. Findings without a confidence are kept.
+    #[arg(long = "min-confidence", value_name = "FRACTION", value_parser = parse_fraction)]
+    pub min_confidence: Option<f32>,
+
+    /// Fail when a review asked for with --output-format json isn't JSON, instead of reading
+    /// best-effort findings out of its prose
+    #[arg(long = "strict-json", action = ArgAction::SetTrue)]
+    pub strict_json: bool,
+
+    /// With --send, check the review is in the --output-format asked for, going by its headings
+    /// and markup, and warn with what doesn't match when it isn't
+    #[arg(long = "format-check", action = ArgAction::SetTrue)]
+    pub format_check: bool,
+
+    /// With --send, ask again, once, for a review --format-check finds isn't in the
+    /// --output-format asked for. Implies --format-check
+    #[arg(long = "enforce-format", action = ArgAction::SetTrue)]
+    pub enforce_format: bool,
+
+    /// Tell the LLM which Rust edition the code targets, read from the nearest Cargo.toml
+    #[arg(long = "inject-rust-edition", action = ArgAction::SetTrue)]
+    pub inject_rust_edition: bool,
+
+    /// Default annotation level for --output-format github-actions; the LLM may pick another per issue
+    #[arg(long = "github-annotation-level", value_name = "LEVEL", default_value = "warning", value_parser = PossibleValuesParser::new(ANNOTATION_LEVELS))]
+    pub github_annotation_level: String,
+
+    /// How long the review should be; auto scales it with the number of changed lines
+    #[arg(long = "review-length", value_name = "LENGTH", default_value = "auto", value_parser = PossibleValuesParser::new(REVIEW_LENGTHS))]
+    pub review_length: String,
+
+    /// Write the tool's own headings, notes and labels in LANGUAGE, and ask for the review in it;
+    /// auto takes it from LC_ALL, LC_MESSAGES or LANG
+    #[arg(long = "review-language", value_name = "LANGUAGE", value_parser = PossibleValuesParser::new(REVIEW_LANGUAGES))]
+    pub review_language: Option<String>,
+
+    /// Language of the code under review, used by language-specific output formats
+    #[arg(long, value_name = "LANGUAGE")]
+    pub language: Option<String>,
+
+    /// Use the presets for LANGUAGE instead of detecting the repository's main language, or none to turn them off
+    #[arg(long = "repo-language", value_name = "LANGUAGE")]
+    pub repo_language: Option<String>,
+
+    /// Filled in from --repo-language or the detected languages when the run starts
+    #[arg(skip)]
+    pub repo_defaults: RepoDefaults,
+
     /// Number of lines given as context to the LLM
-    #[arg(short = 'U', long = "unified", default_value_t = 3)]
+    #[arg(short = 'U', long = "unified", default_value_t = DEFAULT_UNIFIED_CONTEXT)]
     pub unified_context: usize,
 
+    /// Wrap lines of the prompt outside the diff at N characters
+    #[arg(long = "format-width", value_name = "N")]
+    pub format_width: Option<usize>,
+
+    /// Wrap what's printed at COLS columns for reading, leaving the diff, code blocks and tables alone [default: the terminal's width when printing to one]
+    #[arg(long = "wrap", value_name = "COLS", value_parser = parse_positive)]
+    pub wrap: Option<usize>,
+
+    /// Don't wrap what's printed to the terminal
+    #[arg(long = "no-wrap", action = ArgAction::SetTrue, conflicts_with = "wrap")]
+    pub no_wrap: bool,
+
+    /// Write the prompt, or with --send the review, to this file instead of standard output, which
+    /// gets a summary instead. `-` is standard output. An existing file is only replaced with --force.
+    #[arg(long = "output-file", visible_alias = "output", value_name = "PATH")]
+    pub output_file: Option<PathBuf>,
+
+    /// Replace the --output-file if it already exists
+    #[arg(long = "force", action = ArgAction::SetTrue, requires = "output_file")]
+    pub force: bool,
+
+    /// Put the prompt, or with --send the review, on the clipboard instead of printing it, which
+    /// gets a summary instead
+    #[arg(long = "copy", action = ArgAction::SetTrue, conflicts_with_all = ["watch", "each"])]
+    pub copy: bool,
+
+    /// With --output-file, also write a JSON token budget report to <PATH>.budget.json
+    #[arg(long = "token-budget-report", action = ArgAction::SetTrue, requires = "output_file")]
+    pub token_budget_report: bool,
+
+    /// Finish by printing a one-line summary to stderr, for CI to parse
+    #[arg(long = "summary-line", action = ArgAction::SetTrue)]
+    pub summary_line: bool,
+
     /// Enable verbose output
     #[arg(short, long, action = ArgAction::SetTrue)]
     pub verbose: bool,
@@ -55,188 +529,4429 @@ pub struct Cli {
     #[arg(short = 'D', long, action = ArgAction::SetTrue)]
     pub debug: bool,
 
+    /// Review the Nth stash entry instead of running `git diff` (defaults to the most recent)
+    #[arg(long = "from-stash", value_name = "N", num_args = 0..=1, default_missing_value = "0")]
+    pub from_stash: Option<usize>,
+
+    /// Also review the files git doesn't track yet, which `git diff` leaves out, as new files.
+    /// Ignored and binary files are left out
+    #[arg(long = "include-untracked", action = ArgAction::SetTrue, conflicts_with_all = ["diff_file", "stdin", "from_stash", "raw_code", "each", "diff_command", "author", "since"])]
+    pub include_untracked: bool,
+
+    /// With --include-untracked, the most lines of an untracked file to review; the rest are left
+    /// out with a note [default: 1000]
+    #[arg(long = "untracked-max-lines", value_name = "N", requires = "include_untracked")]
+    pub untracked_max_lines: Option<usize>,
+
+    /// Review only the commits by authors matching PATTERN, as `git log --author` matches them,
+    /// combined into one diff. The arguments, if any, are the revisions to look through.
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["from_stash", "each", "watch", "diff_command", "raw_code"])]
+    pub author: Option<String>,
+
+    /// Review only the commits made since DATE, e.g. `2025-08-01` or `1 week ago`, combined into
+    /// one diff. Can be combined with --author.
+    #[arg(long, value_name = "DATE", conflicts_with_all = ["from_stash", "each", "watch", "diff_command", "raw_code"])]
+    pub since: Option<String>,
+
+    /// Review each merge commit in the range of commits as its combined diff: the conflict
+    /// resolutions and other changes it made itself. Merges are otherwise left out, along with
+    /// the changes they merged in, and only the rest of the first-parent line is reviewed.
+    #[arg(long = "include-merges", action = ArgAction::SetTrue, conflicts_with_all = ["author", "since"])]
+    pub include_merges: bool,
+
+    /// Review the complete source code in PATH, or standard input if no PATH is given, instead of a diff
+    #[arg(long = "raw-code", value_name = "PATH", num_args = 0..=1, default_missing_value = "-", conflicts_with_all = ["from_stash", "watch"])]
+    pub raw_code: Option<PathBuf>,
+
+    /// Review the unified diff in PATH, or standard input if PATH is `-`, instead of running `git
+    /// diff`, e.g. a diff a CI job has already produced
+    #[arg(long = "diff-file", value_name = "PATH", conflicts_with_all = ["from_stash", "raw_code", "each", "watch", "diff_command", "author", "since"])]
+    pub diff_file: Option<PathBuf>,
+
+    /// Review the unified diff on standard input, as `--diff-file -` does, e.g. `git show` output
+    /// or a pull request's `.diff`. A lone `-` in place of the diff arguments does the same.
+    #[arg(long = "stdin", action = ArgAction::SetTrue, conflicts_with_all = ["diff_file", "from_stash", "raw_code", "each", "watch", "diff_command", "author", "since"])]
+    pub stdin: bool,
+
+    /// TOML file of [[provider]] tables giving each LLM provider's endpoint and token limits
+    #[arg(long = "llm-provider-config", value_name = "PATH")]
+    pub llm_provider_config: Option<PathBuf>,
+
+    /// Name of the provider to use from --llm-provider-config (defaults to the first one). Without
+    /// a config, anthropic, openai or ollama, to send the prompt to that API as --send does.
+    #[arg(long, value_name = "NAME")]
+    pub provider: Option<String>,
+
+    /// The provider selected from --llm-provider-config, filled in by `run`
+    #[arg(skip)]
+    provider_config: Option<ProviderConfig>,
+
+    /// Send the prompt to the LLM and print its review instead of the prompt. Uses the provider
+    /// from --llm-provider-config, or else Anthropic or OpenAI when ANTHROPIC_API_KEY or
+    /// OPENAI_API_KEY is set. Without an API key the prompt is printed as usual.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub send: bool,
+
+    /// With --send, send the prompt even when the checks made first find it broken: an empty
+    /// code section, a diff with no added or removed lines, hunks that don't match their headers,
+    /// or terminal escape codes
+    #[arg(long = "force-send", action = ArgAction::SetTrue)]
+    pub force_send: bool,
+
+    /// The model --send asks for, in place of the provider config's or the provider's default
+    #[arg(long, value_name = "MODEL")]
+    pub model: Option<String>,
+
+    /// With --send, re-run a review that looks too shallow with MODEL, once: one much shorter than
+    /// the change calls for, naming no changed file or line, or without a heading the config
+    /// file's `[quality]` table requires
+    #[arg(long = "fallback-model", value_name = "MODEL")]
+    pub fallback_model: Option<String>,
+
+    /// The most one run may spend with --send, in US dollars estimated from list prices. A prompt
+    /// that would cost more on its own isn't sent, and a --fallback-model re-run that would take
+    /// the run over it isn't made
+    #[arg(long = "max-cost", value_name = "USD", value_parser = parse_dollars)]
+    pub max_cost: Option<f64>,
+
+    /// What --fallback-model checks reviews against, from the config file's `[quality]` table
+    #[arg(skip)]
+    pub quality: QualityThresholds,
+
+    /// The estimated spend of the run's requests so far, in US dollars, for --max-cost
+    #[arg(skip)]
+    spent: Cell<f64>,
+
+    /// With --send, show the estimated tokens and cost and ask before sending a prompt that takes
+    /// the run over TOKENS to a paid provider. Only asked when standard output is a terminal
+    #[arg(long = "confirm-threshold", value_name = "TOKENS", default_value_t = DEFAULT_CONFIRM_THRESHOLD)]
+    pub confirm_threshold: usize,
+
+    /// Send without asking, however far over --confirm-threshold the run goes
+    #[arg(short = 'y', long = "yes", action = ArgAction::SetTrue)]
+    pub yes: bool,
+
+    /// The prompt tokens the run has sent so far, and whether going over --confirm-threshold was
+    /// confirmed, so it's asked at most once
+    #[arg(skip)]
+    tokens_sent: Cell<usize>,
+    #[arg(skip)]
+    send_confirmed: Cell<bool>,
+
+    /// With --send, exit with status 1 when the review flags an issue, and 0 when it's clean, for CI
+    /// to fail on. What counts as an issue depends on --output-format: a finding in JSON, an error
+    /// or warning in GitHub Actions commands, a SARIF result, or an `[ISSUE]` or `severity: high`
+    /// marker in prose
+    #[arg(long = "fail-on-issues", action = ArgAction::SetTrue)]
+    pub fail_on_issues: bool,
+
+    /// Whether a review has flagged an issue, for --fail-on-issues and --fail-on
+    #[arg(skip)]
+    issues_found: Cell<bool>,
+
+    /// Run as a pre-commit framework hook: review the staged changes to the files given as
+    /// arguments, with --send, print the findings one per line, never stop to ask anything, and
+    /// exit with status 1 only when there are findings at the --fail-on severity or above. The
+    /// request to the provider times out after 120 seconds unless --timeout-secs says otherwise
+    #[arg(long = "pre-commit", action = ArgAction::SetTrue, conflicts_with_all = ["watch", "each", "diff_file", "stdin", "from_stash", "raw_code", "diff_command", "output_format", "fail_on_issues"])]
+    pub pre_commit: bool,
+
+    /// With --pre-commit, the least severe findings that fail the hook
+    #[arg(long = "fail-on", value_name = "SEVERITY", default_value = "error", value_parser = PossibleValuesParser::new(Severity::NAMES), requires = "pre_commit")]
+    pub fail_on: String,
+
+    /// The environment variable holding the provider's API key, in place of ANTHROPIC_API_KEY,
+    /// OPENAI_API_KEY or the provider config's api_key_env
+    #[arg(long = "api-key-env", value_name = "VAR")]
+    pub api_key_env: Option<String>,
+
+    /// Print the prompt instead of sending it, even with --send or --provider
+    #[arg(long = "dry-run", visible_alias = "no-send", action = ArgAction::SetTrue)]
+    pub dry_run: bool,
+
+    /// Give up on the request to the provider if it takes more than SECS
+    #[arg(long = "timeout-secs", value_name = "SECS", value_parser = parse_positive)]
+    pub timeout_secs: Option<usize>,
+
+    /// With --send, set KEY to the JSON VALUE in the request body, for what the API takes that
+    /// there's no option for, e.g. --extra-param top_p=0.9 or --extra-param
+    /// reasoning_effort='"high"' (may be repeated). Replaces what the tool sets under the same key;
+    /// whether the API accepts it is up to you
+    #[arg(long = "extra-param", value_name = "KEY=VALUE", value_parser = parse_extra_param, action = ArgAction::Append)]
+    pub extra_param: Vec<(String, serde_json::Value)>,
+
+    /// With --send and --github-actions-pr-context, post the review as a comment on the pull
+    /// request, using GITHUB_TOKEN or the token `gh` is logged in with. A review too long for one comment is posted as a numbered
+    /// series, and running again updates the same comments.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub post: bool,
+
+    /// With --send, also save the review as a JSON transcript that --replay can render again
+    #[arg(long = "save-transcript", value_name = "PATH")]
+    pub save_transcript: Option<PathBuf>,
+
+    /// Render a transcript saved with --save-transcript in --output-format, printing or posting
+    /// it as --send would, without calling the provider. A review saved as plain text is shown
+    /// as it is.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["send", "save_transcript", "diff_file", "each", "watch"])]
+    pub replay: Option<PathBuf>,
+
+    /// Where --send sends the prompt, filled in by `run`; `None` when it prints it instead
+    #[arg(skip)]
+    send_to: Option<ProviderConfig>,
+
+    /// The repository and pull request --post comments on, and the token to comment with, filled
+    /// in by `run`
+    #[arg(skip)]
+    post_to: Option<(String, u64, String)>,
+
+    /// Load plugins from the dynamic libraries in DIR and apply them in order of file name
+    #[cfg(feature = "plugins")]
+    #[arg(long = "plugin-dir", value_name = "DIR")]
+    pub plugin_dir: Option<PathBuf>,
+
+    /// The plugins loaded from --plugin-dir, filled in by `run`
+    #[cfg(feature = "plugins")]
+    #[arg(skip)]
+    plugins: Plugins,
+
+    /// Review each commit in REV_RANGE on its own, writing one prompt per commit to --output-dir
+    #[arg(long, value_name = "REV_RANGE", requires = "output_dir", conflicts_with_all = ["from_stash", "watch", "raw_code", "output_file"])]
+    pub each: Option<String>,
+
+    /// Directory for the prompts and index written by --each
+    #[arg(long = "output-dir", value_name = "DIR", requires = "each")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Exit successfully even if --each couldn't write a prompt for some commits
+    #[arg(long = "allow-partial", action = ArgAction::SetTrue, requires = "each")]
+    pub allow_partial: bool,
+
+    /// Run CMD, with the remaining arguments appended, to produce the diff instead of `git diff`
+    #[arg(long = "diff-command", value_name = "CMD", conflicts_with_all = ["from_stash", "each", "watch_incremental"])]
+    pub diff_command: Option<String>,
+
+    /// Pass the diff through COMMAND before it's reviewed: it gets the diff on standard input and
+    /// must write a unified diff on standard output. Split on whitespace and run in the current
+    /// directory; may be repeated, to run each in turn
+    #[arg(long = "preprocess", value_name = "COMMAND", action = ArgAction::Append)]
+    pub preprocess: Vec<String>,
+
+    /// Version control system to take the diff from (detected from the current directory if omitted)
+    #[arg(long, value_name = "VCS", value_parser = PossibleValuesParser::new(VCS_NAMES))]
+    pub vcs: Option<String>,
+
+    /// In a partial clone, let the context around the diff, such as the range-diff and the diff
+    /// stat, fetch the objects it needs instead of leaving them out
+    #[arg(long = "allow-fetch", action = ArgAction::SetTrue)]
+    pub allow_fetch: bool,
+
+    /// Make no network connection at all, whatever the config says: requests to the provider,
+    /// GitHub, issue trackers and CI log URLs fail, context that needs one is left out, and git
+    /// doesn't fetch what a partial clone is missing. The prompt is built as usual.
+    #[arg(long = "offline", action = ArgAction::SetTrue, conflicts_with = "allow_fetch")]
+    pub offline: bool,
+
+    /// In a GitHub Actions pull_request workflow, add the PR title, description and branches, and
+    /// take the settings in the description's ```llm-review block
+    #[arg(long = "github-actions-pr-context", action = ArgAction::SetTrue)]
+    pub github_actions_pr_context: bool,
+
+    /// Review the whole pull request even if it was rebased since the last push, instead of
+    /// focusing on the commits the rebase changed
+    #[arg(long = "full-rereview", action = ArgAction::SetTrue, requires = "github_actions_pr_context")]
+    pub full_rereview: bool,
+
+    /// Add the new version of each changed file to the prompt in full, as many as fit in the tokens
+    /// left after the diff, smallest first. Deleted and binary files are left out
+    #[arg(long = "full-files", action = ArgAction::SetTrue)]
+    pub full_files: bool,
+
+    /// With --full-files, leave out files longer than N lines [default: 2000]
+    #[arg(long = "full-file-max-lines", value_name = "N", requires = "full_files")]
+    pub full_file_max_lines: Option<usize>,
+
+    /// The order the optional context sections get the tokens left after the diff, as a comma
+    /// separated list of change-summary, packages, previous-review, linked-issues, ci-logs, lint,
+    /// related-tests, contracts, infrastructure, migrations, naming, history and full-files. Those
+    /// not listed follow in that default order: ci-logs, previous-review, linked-issues, contracts,
+    /// infrastructure, migrations, naming, lint, related-tests, history, change-summary, packages,
+    /// full-files.
+    #[arg(long = "context-priority", value_name = "FEATURES", value_delimiter = ',', value_parser = parse_context_feature)]
+    pub context_priority: Vec<String>,
+
+    /// Don't look up the issues that commit messages and the branch name refer to. Otherwise they
+    /// are fetched from GitHub, GitLab or Jira when a token for it is set in the environment
+    #[arg(long = "no-issue-context", action = ArgAction::SetTrue)]
+    pub no_issue_context: bool,
+
+    /// Directory of previously saved reviews; excerpts discussing the same files are included, and
+    /// each review built is recorded there for `feedback` to rate
+    #[arg(long = "history-dir", value_name = "DIR")]
+    pub history_dir: Option<PathBuf>,
+
+    /// Warn when the estimated spend of this calendar month's runs, as recorded in the usage
+    /// ledger, reaches USD US dollars
+    #[arg(long = "monthly-budget", value_name = "USD", value_parser = parse_dollars)]
+    pub monthly_budget: Option<f64>,
+
+    /// With --monthly-budget, refuse to send a prompt once the month's budget is spent
+    #[arg(long = "enforce-budget", action = ArgAction::SetTrue)]
+    pub enforce_budget: bool,
+
+    /// Answers to the questions in --previous-review, one per question ID (`Q1: ...`)
+    #[arg(long = "answer-file", value_name = "PATH", requires = "previous_review")]
+    pub answer_file: Option<PathBuf>,
+
+    /// A saved review whose questions for the author are answered in --answer-file
+    #[arg(long = "previous-review", value_name = "PATH", requires = "answer_file")]
+    pub previous_review: Option<PathBuf>,
+
+    /// Include the end of a CI log: a file, a GitHub Actions job URL or another URL (may be repeated)
+    #[arg(long = "ci-log", value_name = "PATH_OR_URL", action = ArgAction::Append)]
+    pub ci_log: Vec<String>,
+
+    /// Run this linter and put what it reports on the changed files in the prompt, so the review
+    /// doesn't repeat it. Split on whitespace and run in the current directory; may be repeated
+    #[arg(long = "lint-context", value_name = "COMMAND", action = ArgAction::Append)]
+    pub lint_context: Vec<String>,
+
+    /// As --lint-context, with the usual linter of each of the repository's languages, such as
+    /// cargo clippy for Rust or ruff for Python
+    #[arg(long = "auto-lint", action = ArgAction::SetTrue)]
+    pub auto_lint: bool,
+
+    /// Require every finding to cite a file and line from the diff
+    #[arg(long = "require-citations", action = ArgAction::SetTrue)]
+    pub require_citations: bool,
+
+    /// Only review changes to public items (Rust only; other files are reviewed in full)
+    #[arg(long = "api-only", action = ArgAction::SetTrue)]
+    pub api_only: bool,
+
+    /// Review only this aspect of the code, e.g. concurrency or error-handling (may be repeated)
+    #[arg(long = "category", value_name = "NAME", action = ArgAction::Append)]
+    pub category: Vec<String>,
+
+    /// TOML file of [[category]] tables (name and description) adding team-specific categories
+    #[arg(long = "categories-file", value_name = "PATH", requires = "category")]
+    pub categories_file: Option<PathBuf>,
+
+    /// Filled in from --category and --categories-file when the run starts
+    #[arg(skip)]
+    pub selected_categories: Vec<Category>,
+
+    /// Open a GitHub issue for each follow-up finding in this JSON file of findings, instead of
+    /// writing a prompt. The diff is taken as usual, for the excerpts
+    #[arg(long = "file-issues", value_name = "FINDINGS")]
+    pub file_issues: Option<PathBuf>,
+
+    /// End the diff with a verification code and ask the model to echo it, to tell whether it read
+    /// the whole diff
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub canary: bool,
+
+    /// Check a saved model response for the verification code --canary put after the diff under
+    /// review, and print the response without it, instead of writing a prompt
+    #[arg(long = "check-response", value_name = "PATH")]
+    pub check_response: Option<PathBuf>,
+
+    /// With --check-response, write the prompt again split into parts, as --chunked does, if the
+    /// response doesn't echo the verification code
+    #[arg(long = "strict-canary", action = ArgAction::SetTrue, requires = "check_response")]
+    pub strict_canary: bool,
+
+    /// Print the issues --file-issues would open without opening them
+    #[arg(long = "file-issues-dry-run", action = ArgAction::SetTrue, requires = "file_issues")]
+    pub file_issues_dry_run: bool,
+
+    /// Also file the findings less serious than SEVERITY, not just those marked as follow-ups
+    #[arg(long = "file-issues-below", value_name = "SEVERITY", requires = "file_issues", value_parser = PossibleValuesParser::new(Severity::NAMES))]
+    pub file_issues_below: Option<String>,
+
+    /// The label given to the issues --file-issues opens, and used to find the ones it opened before
+    #[arg(long = "file-issues-label", value_name = "LABEL", default_value = DEFAULT_ISSUE_LABEL)]
+    pub file_issues_label: String,
+
+    /// List the TODO, FIXME, HACK and XXX markers the diff adds, in the prompt and on standard error
+    #[arg(long = "new-todos", action = ArgAction::SetTrue)]
+    pub new_todos: bool,
+
+    /// Fail if a marker the diff adds has no issue reference matching REGEX [default: TODO\(#\d+\)]; implies --new-todos
+    #[arg(long = "require-todo-issue", value_name = "REGEX", num_args = 0..=1, default_missing_value = DEFAULT_TODO_ISSUE_PATTERN)]
+    pub require_todo_issue: Option<String>,
+
+    /// Check the names and string literals the diff adds for common misspellings and for casing
+    /// unlike the names already in the file, offline, and ask the model to confirm or dismiss each
+    #[arg(long = "check-naming", action = ArgAction::SetTrue)]
+    pub check_naming: bool,
+
+    /// Treat the files matching GLOB as public contracts, as OpenAPI, protobuf and GraphQL schemas
+    /// and the pub items of Rust libraries are, and ask for a compatibility review (may be repeated)
+    #[arg(long = "contract-files", value_name = "GLOB", action = ArgAction::Append)]
+    pub contract_files: Vec<String>,
+
+    /// Don't ask for a compatibility review of changed schemas and public APIs
+    #[arg(long = "no-compatibility-review", action = ArgAction::SetTrue)]
+    pub no_compatibility_review: bool,
+
+    /// Treat the files matching GLOB as CI or build configuration, as GitHub Actions workflows,
+    /// Dockerfiles, Makefiles and build scripts are, and review them with the infrastructure rubric
+    /// (may be repeated)
+    #[arg(long = "infra-files", value_name = "GLOB", action = ArgAction::Append)]
+    pub infra_files: Vec<String>,
+
+    /// Don't run this local check on the lines added to CI and build configuration: one of
+    /// unpinned-action, pipe-to-shell or secret-echo (may be repeated)
+    #[arg(long = "skip-infra-check", value_name = "NAME", action = ArgAction::Append, value_parser = parse_infra_check)]
+    pub skip_infra_check: Vec<String>,
+
+    /// Don't review changed CI and build configuration with the infrastructure rubric, or run the
+    /// local checks on it
+    #[arg(long = "no-infra-review", action = ArgAction::SetTrue)]
+    pub no_infra_review: bool,
+
+    /// Treat the files matching GLOB as database migrations, as Django, Rails, Alembic and SQL
+    /// migration directories are, and review them against the schema and model changes they come
+    /// from (may be repeated)
+    #[arg(long = "migration-files", value_name = "GLOB", action = ArgAction::Append)]
+    pub migration_files: Vec<String>,
+
+    /// Don't review changed database migrations with the migration rubric, or check them for
+    /// dropped tables and columns
+    #[arg(long = "no-migration-review", action = ArgAction::SetTrue)]
+    pub no_migration_review: bool,
+
+    /// Of files making near-identical changes, include only N of each group and list the rest
+    #[arg(long = "sample-similar", value_name = "N", value_parser = parse_positive)]
+    pub sample_similar: Option<usize>,
+
+    /// Add a Markdown table of the files changed, with their insertions and deletions (git only)
+    #[arg(long = "diff-stat-as-table", action = ArgAction::SetTrue)]
+    pub diff_stat_as_table: bool,
+
+    /// Skip hunks with fewer than N added/removed lines
+    #[arg(long = "min-change-size", value_name = "N")]
+    pub min_change_size: Option<usize>,
+
+    /// With --watch, only review what changed since the previous run
+    #[arg(long = "watch-incremental", action = ArgAction::SetTrue, requires = "watch")]
+    pub watch_incremental: bool,
+
+    /// Fraction of the token budget to reserve for the LLM's response, e.g. 0.25
+    #[arg(long = "context-window-padding", value_name = "FRACTION", default_value_t = 0.0, value_parser = parse_fraction)]
+    pub context_window_padding: f32,
+
+    /// The token budget, in place of the provider's max_tokens or the model's context window
+    #[arg(long = "max-tokens", visible_alias = "model-context", value_name = "TOKENS", value_parser = parse_positive)]
+    pub token_limit: Option<usize>,
+
+    /// Characters per token for estimating the prompt's size, in place of the provider's
+    /// chars_per_token. Counts tokens this way unless --tokenizer says otherwise
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    pub chars_per_token: Option<usize>,
+
+    /// How to count tokens: cl100k, o200k, or chars for the provider's chars_per_token estimate.
+    /// The default is the model's encoding, or the provider config's `tokenizer` or
+    /// chars_per_token
+    #[arg(long, value_name = "TOKENIZER", value_parser = parse_tokenizer)]
+    pub tokenizer: Option<String>,
+
+    /// Keep the token counts of long texts, such as each file's diff, in FILE between runs, so an
+    /// unchanged file isn't tokenized again, as --watch and large diffs otherwise do
+    #[arg(long = "token-cache", value_name = "FILE")]
+    pub token_cache: Option<PathBuf>,
+
+    /// Re-run the review whenever the diff changes, printing each prompt, until interrupted with
+    /// Ctrl-C
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["from_stash", "send", "provider", "output_file"])]
+    pub watch: bool,
+
+    /// Fail if the diff is too large, instead of reducing the context lines to make it fit
+    #[arg(long = "no-reduce-context", action = ArgAction::SetTrue, conflicts_with = "force_reduced")]
+    pub no_reduce_context: bool,
+
+    /// Keep every unchanged context line, instead of eliding long runs of them
+    #[arg(long = "no-compress", action = ArgAction::SetTrue)]
+    pub no_compress: bool,
+
+    /// Review Jupyter notebooks as the JSON they're stored as, instead of as their cells with the
+    /// outputs summarized
+    #[arg(long = "raw-notebooks", action = ArgAction::SetTrue)]
+    pub raw_notebooks: bool,
+
+    /// Leave absolute paths in the diff as they are, instead of making those under the repository
+    /// relative to it and replacing home directories with ~
+    #[arg(long = "no-path-scrub", action = ArgAction::SetTrue)]
+    pub no_path_scrub: bool,
+
     /// Force context to be reduced, for testing
     #[arg(long, action = ArgAction::SetTrue)]
     force_reduced: bool,
 
+    /// Split the diff into parts that each fit in the token budget and write a prompt for each,
+    /// whatever its size. Diffs too large to fit even without context lines are split anyway.
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["force_reduced", "no_reduce_context", "watch"])]
+    pub chunked: bool,
+
+    /// Write a prompt for each changed file, instead of one for the whole diff. A file too large
+    /// for a prompt of its own has its context reduced, and then its hunks split, as --chunked does.
+    #[arg(long = "split-per-file", action = ArgAction::SetTrue, conflicts_with_all = ["chunked", "force_reduced", "no_reduce_context", "watch"])]
+    pub split_per_file: bool,
+
+    /// Review a diff too large for the budget in chunks of whole files, each with all its context,
+    /// instead of reducing its context: per-file always does, auto only when the diff doesn't fit
+    #[arg(long = "chunk-mode", value_name = "MODE", default_value = "none", value_parser = PossibleValuesParser::new(CHUNK_MODES), conflicts_with_all = ["chunked", "split_per_file", "force_reduced", "no_reduce_context", "watch"])]
+    pub chunk_mode: String,
+
     /// Arguments that will be passed in to `git diff`
     #[arg(value_name = "remaining_args", allow_hyphen_values = true)]
     remaining_args: Vec<String>,
-}
 
-#[derive(Debug)]
-enum OutputFormat {
-    Markdown,
-    AsciiDoc,
-    MediaWiki,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
 }
 
-impl OutputFormat {
-    fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "markdown" => Some(OutputFormat::Markdown),
-            "asciidoc" => Some(OutputFormat::AsciiDoc),
-            "mediawiki" => Some(OutputFormat::MediaWiki),
-            _ => None,
-        }
-    }
+/// Commands run instead of a review
+#[derive(clap::Subcommand, Debug, Clone, PartialEq)]
+pub enum CliCommand {
+    /// Check that everything the tool needs is set up and working
+    ///
+    /// Checks git, the repository, the configuration, the provider's credentials and its API, and
+    /// the clipboard and pager commands, and prints a table of the results with hints for fixing
+    /// problems. Exits with status 1 if a requirement fails.
+    Doctor {
+        /// Don't send the test request to the provider's API
+        #[arg(long, action = ArgAction::SetTrue)]
+        offline: bool,
+    },
+    /// Rate the most recent review recorded in --history-dir, or report the ratings
+    Feedback {
+        #[command(subcommand)]
+        action: FeedbackAction,
+    },
+    /// Publish the reviews recorded in --history-dir as a static HTML dashboard
+    Report {
+        #[command(subcommand)]
+        action: ReportAction,
+    },
+    /// Report the token usage and estimated cost recorded by each run that sent a prompt
+    Usage {
+        #[command(subcommand)]
+        action: UsageAction,
+    },
 }
 
-pub fn get_git_diff(git_args: &String) -> String {
-    let mut command_binding = Command::new("git");
-    let command = command_binding.arg("diff");
-
-    // Split git_args by space and append them each to command as arg()
-    let git_args_split: Vec<&str> = git_args.trim().split_whitespace().collect();
-    for git_arg in git_args_split.iter() {
-        command.arg(git_arg);
-    }
+/// What `usage` does
+#[derive(clap::Subcommand, Debug, Clone, PartialEq)]
+pub enum UsageAction {
+    /// Total the tokens and estimated cost by model, repository and preset
+    Report {
+        /// Only count runs on or after DATE, given as YYYY-MM-DD
+        #[arg(long, value_name = "DATE", value_parser = parse_date)]
+        since: Option<String>,
+    },
+}
 
-    debug!("Running command: {:?}", command);
-    let output = command.output().expect("");
-    let diff_output = format!("{}", String::from_utf8_lossy(&output.stdout));
+/// What `report` does
+#[derive(clap::Subcommand, Debug, Clone, PartialEq)]
+pub enum ReportAction {
+    /// Write the dashboard: an index of the reviews, newest first, and a page for each review
+    ///
+    /// The pages have no external assets, so the directory can be served as it is, e.g. from CI
+    /// artifacts or GitHub Pages. The same history always gives the same pages, so they can be
+    /// committed. Reviews are saved for the dashboard when they're sent with --send.
+    Generate {
+        /// The directory to write the dashboard to
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+        /// How many reviews each page of the index lists
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_PAGE_SIZE, value_parser = clap::value_parser!(usize))]
+        page_size: usize,
+        /// The dashboard's title
+        #[arg(long, value_name = "TEXT", default_value = "Code reviews")]
+        title: String,
+    },
+}
 
-    if !output.status.success() {
-        error!("Git diff command failed. Check your arguments:");
-        error!("Stderr: {}", String::from_utf8_lossy(&output.stderr));
-        process::exit(1);
-    }
-    if diff_output.len() == 0 {
-        println!("No changes found to review.");
-        process::exit(0);
-    }
+/// What `feedback` does
+#[derive(clap::Subcommand, Debug, Clone, PartialEq)]
+pub enum FeedbackAction {
+    /// The most recent review was useful
+    Good {
+        /// A note to keep with the rating
+        #[arg(long, value_name = "TEXT")]
+        note: Option<String>,
+    },
+    /// The most recent review wasn't useful
+    Bad {
+        /// A note to keep with the rating, such as what was wrong
+        #[arg(long, value_name = "TEXT")]
+        note: Option<String>,
+    },
+    /// Count the ratings by prompt version, model, preset and review length
+    Report,
+}
 
-    return diff_output;
+/// The command line parser for `args`. The examples are only added to the end of `--help` when
+/// `--verbose` is given as well, to keep the help short in CI logs and small terminals.
+pub fn command(args: &[String]) -> clap::Command {
+    let verbose = args.iter().any(|arg| arg == "-v" || arg == "--verbose");
+    Cli::command().after_help(if verbose {
+        REVIEW_EXAMPLES
+    } else {
+        SHORT_HELP_FOOTER
+    })
 }
 
-fn reduce_context_if_needed(
-    git_args: &[String],
-    unified_context: usize,
-    force_reduced: bool,
-    diff_output: &str,
-    max_tokens: usize,
-    chars_per_token: usize,
-) -> Option<Vec<String>> {
-    // Estimate token count and reduce context if needed
-    let estimated_tokens = diff_output.len() / chars_per_token;
+/// Parse the process's command line, exiting with a message if it is invalid.
+pub fn parse_cli() -> Cli {
+    let args: Vec<String> = env::args().collect();
+    let matches = command(&args).get_matches_from(&args);
+    Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+}
 
-    if estimated_tokens <= max_tokens && !force_reduced {
-        return None;
+impl Cli {
+    /// Token budget for the prompt, before any --context-window-padding is set aside: --max-tokens,
+    /// or the provider config's max_tokens, or the context window of a --provider's model
+    pub fn max_tokens(&self) -> usize {
+        if let Some(limit) = self.token_limit {
+            return limit;
+        }
+        match &self.provider_config {
+            Some(provider) if self.llm_provider_config.is_some() => provider.max_tokens,
+            Some(provider) => self.model_name().and_then(|model| context_window(&model)).unwrap_or(provider.max_tokens),
+            None => self.model.as_deref().and_then(context_window).unwrap_or(DEFAULT_MAX_TOKENS),
+        }
     }
 
-    let reduced_context = (unified_context * max_tokens / estimated_tokens).max(1);
-
-    info!(
-        "Reducing context to {} lines to fit token limits",
-        reduced_context
-    );
+    /// The model the review is for: --model, or the provider's model or its default one
+    pub fn model_name(&self) -> Option<String> {
+        let provider = self.provider().or(self.send_to.as_ref());
+        self.model
+            .clone()
+            .or_else(|| provider.and_then(|provider| provider.model.clone()))
+            .or_else(|| provider.and_then(|provider| default_model(provider.format)).map(str::to_string))
+    }
 
-    let new_git_args: Vec<String> = git_args
-        .iter()
-        .map(|arg| {
-            if arg.starts_with("-U") {
-                format!("-U{}", reduced_context)
-            } else if arg.starts_with("--unified=") {
-                format!("--unified={}", reduced_context)
-            } else {
-                arg.clone()
-            }
+    /// Characters per token, for estimating the size of the prompt
+    pub fn chars_per_token(&self) -> usize {
+        self.chars_per_token.unwrap_or_else(|| {
+            self.provider_config
+                .as_ref()
+                .map_or(DEFAULT_CHARS_PER_TOKEN, |provider| provider.chars_per_token)
         })
-        .collect();
+    }
 
-    let new_estimated_tokens = diff_output.len() / chars_per_token;
-    if new_estimated_tokens > max_tokens {
-        error!("Diff is too large to process even with minimal context. Try reviewing a smaller set of changes.");
-        process::exit(1);
+    /// How tokens are counted: --tokenizer, or a provider config's `tokenizer` or else its
+    /// chars_per_token, or the encoding of the model
+    pub fn token_estimator(&self) -> Tokenizer {
+        let chars_per_token = self.chars_per_token();
+        let configured = self.provider().filter(|_| self.llm_provider_config.is_some());
+        match (&self.tokenizer, configured) {
+            (Some(name), _) => Tokenizer::parse(name, chars_per_token),
+            (None, _) if self.chars_per_token.is_some() => Ok(Tokenizer::Chars(CharsPerToken(chars_per_token))),
+            (None, Some(provider)) => match &provider.tokenizer {
+                Some(name) => Tokenizer::parse(name, chars_per_token),
+                None => Ok(Tokenizer::Chars(CharsPerToken(chars_per_token))),
+            },
+            (None, None) => Ok(Tokenizer::Bpe(self.model_name().map_or(Encoding::Cl100k, |model| Encoding::for_model(&model)))),
+        }
+        // --tokenizer and the provider config were both checked when they were read
+        .unwrap_or(Tokenizer::Chars(CharsPerToken(chars_per_token)))
     }
 
-    Some(new_git_args)
-}
+    /// The provider selected from --llm-provider-config, once `prepare` has run
+    pub fn provider(&self) -> Option<&ProviderConfig> {
+        self.provider_config.as_ref()
+    }
 
-fn setup_logging(cli: &Cli) {
-    let log_level = if cli.debug {
-        LevelFilter::Trace
-    } else if cli.verbose {
-        LevelFilter::Info
-    } else {
-        LevelFilter::Warn
-    };
+    /// Token budget left for the prompt once --context-window-padding is set aside
+    pub(crate) fn effective_budget(&self) -> usize {
+        compute_effective_budget(self.max_tokens(), self.context_window_padding)
+    }
 
-    SimpleLogger::new()
-        .with_level(log_level)
-        .init()
-        .unwrap_or_else(|e| {
-            eprintln!("Failed to initialize logger: {}", e);
-            process::exit(1);
-        });
+    /// The budget for the diff: the effective budget, with the rest of the prompt, `sections`
+    /// included, already spent
+    pub(crate) fn diff_budget(&self, sections: &[(&str, String)]) -> Budget {
+        let mut budget = Budget::new(self.max_tokens()).reserving_output(self.context_window_padding);
+        if let Ok(parts) = prompt_parts(self, "", sections) {
+            budget.spend(self.token_estimator().estimate_tokens(&render_prompt(self, &parts)));
+        }
+        budget
+    }
 
-    if cli.verbose {
-        info!("Verbose mode enabled.");
+    /// The --output-format, with the --github-annotation-level for GitHub Actions
+    pub fn output_format(&self) -> Option<OutputFormat> {
+        let output_format: OutputFormat = self.output_format.as_deref()?.parse().ok()?;
+        let level: AnnotationLevel = self.github_annotation_level.parse().unwrap_or_default();
+        Some(output_format.with_annotation_level(level))
     }
-    if cli.debug {
-        trace!("Debug mode enabled.");
+
+    /// The status to exit with once the prompts are written or the reviews printed: 1 with
+    /// --fail-on-issues when a review flagged an issue, or with --pre-commit when one had findings
+    /// at the --fail-on severity, and otherwise 0
+    pub fn exit_status(&self) -> i32 {
+        i32::from((self.fail_on_issues || self.pre_commit) && self.issues_found.get())
     }
-}
 
-fn build_prompt(cli: &Cli, diff: &str) -> String {
-    let mut prompt = cli
-        .system_prompt
-        .clone()
-        .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string());
+    /// The prompt schema to keep to, with --stable-prompt
+    pub fn stable_schema(&self) -> Option<PromptSchema> {
+        self.stable_prompt.then(|| self.prompt_schema.unwrap_or(PromptSchema::CURRENT))
+    }
 
-    // Append output format instructions
-    if let Some(format_str) = &cli.output_format {
-        if let Some(output_format) = OutputFormat::from_str(format_str) {
-            prompt.push_str(&format!(
-                "\nOutput the review in {:?} format.\n",
-                output_format
-            ));
+    /// Whether the prompt ends the diff with a verification code
+    pub fn canary(&self) -> bool {
+        self.canary || self.check_response.is_some()
+    }
+
+    /// Where the size tiers start, from the provider config
+    pub fn tier_thresholds(&self) -> TierThresholds {
+        match self.provider().and_then(|provider| provider.small_diff_tokens) {
+            Some(small_max_tokens) => TierThresholds { small_max_tokens },
+            None => TierThresholds::default(),
         }
     }
 
-    // Append additional context
-    if let Some(ctx) = &cli.context {
-        prompt.push_str(&format!("\n## Additional Context\n{}\n", ctx));
+    /// The limits on findings from --max-findings-per-file and --max-findings
+    pub fn finding_limits(&self) -> FindingLimits {
+        FindingLimits { per_file: self.max_findings_per_file, total: self.max_findings }
     }
 
-    // Append the diff content
-    prompt.push_str("\n\n# PR Code\n\n");
-    prompt.push_str(diff);
+    /// The language of the tool's own text from --review-language, English if it wasn't given
+    pub fn locale(&self) -> Locale {
+        self.review_language.as_deref().and_then(|name| Locale::select(name).ok()).unwrap_or_default()
+    }
 
-    prompt
+    /// The --chunk-mode asked for
+    pub fn chunk_mode(&self) -> ChunkMode {
+        self.chunk_mode.parse().unwrap_or_default()
+    }
+
+    /// The tier asked for on the command line: --chunked, --split-per-file or --chunk-mode
+    /// per-file for large, and --force-reduced or --no-reduce-context for medium, which fails if
+    /// the diff is too large
+    fn forced_tier(&self) -> Option<SizeTier> {
+        if self.chunked || self.split_per_file || self.chunk_mode() == ChunkMode::PerFile {
+            Some(SizeTier::Large)
+        } else if self.force_reduced || self.no_reduce_context {
+            Some(SizeTier::Medium)
+        } else {
+            None
+        }
+    }
+
+    /// The linters to run: --lint-context, then with --auto-lint the usual one for each of the
+    /// repository's languages
+    pub fn lint_commands(&self) -> Vec<String> {
+        let mut commands = self.lint_context.clone();
+        if self.auto_lint {
+            for preset in &self.repo_defaults.presets {
+                if let Some(command) = auto_lint_command(preset.language)
+                    && !commands.iter().any(|known| known == command)
+                {
+                    commands.push(command.to_string());
+                }
+            }
+        }
+        commands
+    }
+
+    /// The --review-type asked for
+    pub fn review_type(&self) -> ReviewType {
+        self.review_type.parse().unwrap_or_default()
+    }
+
+    /// The heartbeat and first-token limit for requests to the provider
+    pub fn generation_limits(&self) -> GenerationLimits {
+        GenerationLimits {
+            heartbeat: (self.heartbeat_secs > 0).then(|| Duration::from_secs(self.heartbeat_secs)),
+            max_wait_first_token: self.max_wait_first_token.map(|secs| Duration::from_secs(secs as u64)),
+        }
+    }
+
+    /// The optional context features in the order they get the budget, from --context-priority
+    pub fn context_features(&self) -> Result<Vec<ContextFeature>, ReviewError> {
+        context_priority(&self.context_priority).map_err(ReviewError::Config)
+    }
+
+    /// The language from --language, or else the repository's main language
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref().or(self.repo_defaults.language.as_deref())
+    }
+}
+
+/// Run `git diff` with the given arguments, failing with `ReviewError::NoChanges` if there's
+/// nothing to review.
+pub fn get_git_diff(git_args: &[String]) -> Result<String, ReviewError> {
+    let diff_output = try_git_diff(git_args)?;
+    if diff_output.is_empty() {
+        return Err(ReviewError::NoChanges);
+    }
+    Ok(diff_output)
+}
+
+/// Run --diff-command if one was given, and otherwise the diff command for `vcs`, then pass the
+/// diff through any --preprocess commands.
+fn run_diff(cli: &Cli, vcs: Vcs, args: &[String]) -> Result<String, ReviewError> {
+    let diff = match &cli.diff_command {
+        Some(diff_command) => try_command_diff(diff_command, args),
+        None => try_diff(vcs, args),
+    }?;
+    // Only git diffs name the blobs the notebook's two versions can be read from
+    let diff = if cli.raw_notebooks || vcs != Vcs::Git || !diff.contains(NOTEBOOK_EXTENSION) {
+        diff
+    } else {
+        let context = args.iter().find_map(|arg| context_arg(arg)).unwrap_or(cli.unified_context);
+        convert_notebooks(&diff, context, read_blob_or_file)
+    };
+    let diff = if cli.include_untracked { add_untracked(cli, vcs, args, diff)? } else { diff };
+    preprocess_diff(cli, diff)
+}
+
+/// `diff` with the untracked files `args` cover added to it as new files, for
+/// --include-untracked. Diffs of the index or between commits are left as they are.
+fn add_untracked(cli: &Cli, vcs: Vcs, args: &[String], mut diff: String) -> Result<String, ReviewError> {
+    if vcs != Vcs::Git {
+        return Err(ReviewError::Usage("--include-untracked is only supported in git repositories".to_string()));
+    }
+    let Some(pathspecs) = untracked_pathspecs(args) else {
+        warn!("--include-untracked: the diff doesn't include the working tree, so untracked files are left out");
+        return Ok(diff);
+    };
+    let files = untracked_files(&pathspecs)?;
+    if files.is_empty() {
+        return Ok(diff);
+    }
+    debug!("Adding {} untracked files: {}", files.len(), files.iter().map(|file| file.display().to_string()).collect::<Vec<_>>().join(", "));
+    let untracked = get_untracked_diff(&files, cli.untracked_max_lines.unwrap_or(DEFAULT_UNTRACKED_MAX_LINES))?;
+    if !diff.is_empty() && !diff.ends_with('\n') {
+        diff.push('\n');
+    }
+    diff.push_str(&untracked);
+    Ok(diff)
+}
+
+/// Pass a diff just collected through the --preprocess commands, each given what's left of
+/// --deadline-secs at most
+fn preprocess_diff(cli: &Cli, diff: String) -> Result<String, ReviewError> {
+    if cli.preprocess.is_empty() {
+        return Ok(diff);
+    }
+    let timeout = cli.deadline.remaining().map_or(PREPROCESS_TIMEOUT, |remaining| remaining.min(PREPROCESS_TIMEOUT));
+    preprocess(&cli.preprocess, diff, timeout)
+}
+
+/// Get the patch for the stash entry `stash@{n}`, as shown by `git stash show -p`.
+pub fn get_stash_diff(n: usize) -> Result<String, ReviewError> {
+    let refspec = format!("stash@{{{}}}", n);
+
+    let mut verify_binding = git_command();
+    let verify = verify_binding
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(&refspec);
+    debug!("Running command: {:?}", verify);
+    let verify_output = verify
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+    if !verify_output.status.success() {
+        return Err(ReviewError::NoSuchStash(n));
+    }
+
+    let mut command_binding = git_command();
+    let command = command_binding.args(["stash", "show", "-p"]).arg(&refspec);
+    debug!("Running command: {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(ReviewError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    Ok(decode_diff(&output.stdout))
+}
+
+/// The commits in `range`, oldest first, as listed by `git rev-list --reverse`.
+pub fn list_commits(range: &str) -> Result<Vec<String>, ReviewError> {
+    let output = git_output(&["rev-list", "--reverse", range])?;
+    Ok(output.lines().map(str::to_string).collect())
+}
+
+/// The full commit message of `commit`.
+pub fn commit_message(commit: &str) -> Result<String, ReviewError> {
+    git_output(&["log", "-1", "--format=%B", commit])
+}
+
+fn parse_positive(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(value) => Ok(value),
+        Err(_) => Err(format!("`{}` is not a whole number", s)),
+    }
+}
+
+fn parse_tokenizer(s: &str) -> Result<String, String> {
+    Tokenizer::parse(s, DEFAULT_CHARS_PER_TOKEN).map(|_| s.to_string())
+}
+
+fn parse_context_feature(s: &str) -> Result<String, String> {
+    context_priority(&[s.to_string()]).map(|_| s.to_string())
+}
+
+fn parse_infra_check(s: &str) -> Result<String, String> {
+    if INFRA_CHECKS.contains(&s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!("unknown check `{}`; the checks are {}", s, INFRA_CHECKS.join(", ")))
+    }
+}
+
+fn parse_prompt_schema(s: &str) -> Result<PromptSchema, String> {
+    s.parse()
+}
+
+fn parse_dollars(s: &str) -> Result<f64, String> {
+    match s.trim_start_matches('$').parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err(format!("`{}` must be more than 0", s)),
+        Err(_) => Err(format!("`{}` is not an amount of dollars", s)),
+    }
+}
+
+fn parse_date(s: &str) -> Result<String, String> {
+    if Regex::new(r"^\d{4}-(0[1-9]|1[0-2])-(0[1-9]|[12]\d|3[01])$").unwrap().is_match(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!("`{}` is not a date written as YYYY-MM-DD", s))
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|_| format!("`{}` is not a number", s))?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(format!("`{}` must be between 0.0 and 1.0", s));
+    }
+    Ok(value)
+}
+
+/// The number of tokens left for the prompt once `padding` (a fraction of `max_tokens`) has been
+/// set aside for the response. Never less than `MIN_PROMPT_TOKENS`.
+pub fn compute_effective_budget(max_tokens: usize, padding: f32) -> usize {
+    Budget::new(max_tokens).reserving_output(padding).prompt_limit()
+}
+
+/// Check the diff fits in `max_tokens` as-is.
+pub fn check_diff_size(
+    diff_output: &str,
+    max_tokens: usize,
+    estimator: &dyn TokenEstimator,
+) -> Result<(), ReviewError> {
+    let estimated_tokens = estimator.estimate_tokens(diff_output);
+    if estimated_tokens > max_tokens {
+        return Err(ReviewError::DiffTooLarge {
+            estimated_tokens,
+            max_tokens,
+        });
+    }
+    Ok(())
+}
+
+/// The last check before a prompt leaves the tool: one over `max_chars` characters is refused even
+/// if `estimator` put it within `max_tokens`, in case the estimator or the model's limits are wrong.
+pub fn check_hard_cap(
+    prompt: &str,
+    max_chars: usize,
+    estimator: &dyn TokenEstimator,
+    max_tokens: usize,
+) -> Result<(), ReviewError> {
+    let chars = prompt.chars().count();
+    if chars > max_chars {
+        return Err(ReviewError::PromptOverHardCap {
+            chars,
+            bytes: prompt.len(),
+            max_chars,
+            estimated_tokens: estimator.estimate_tokens(prompt),
+            max_tokens,
+        });
+    }
+    Ok(())
+}
+
+/// Refuse to send a prompt that would waste the request, unless --force-send says to send it anyway
+fn check_before_sending(cli: &Cli, parts: &PromptParts, prompt: &str) -> Result<(), ReviewError> {
+    let problems = check_prompt(prompt, parts.diff_heading, &parts.diff, cli.raw_code.is_none());
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if cli.force_send {
+        for problem in &problems {
+            warn!("Sending anyway: {}", problem);
+        }
+        return Ok(());
+    }
+    Err(ReviewError::BrokenPrompt(problems.iter().map(ToString::to_string).collect()))
+}
+
+/// Check the whole prompt, system prompt and context included, fits in `max_tokens` going by
+/// `estimator`. Returns its tokens.
+pub fn check_prompt_tokens(
+    system_prompt: &str,
+    prompt: &str,
+    estimator: &dyn TokenEstimator,
+    max_tokens: usize,
+) -> Result<usize, ReviewError> {
+    let tokens = estimator.estimate_tokens(prompt);
+    if tokens > max_tokens {
+        return Err(ReviewError::PromptTooLarge {
+            tokens,
+            system_prompt_tokens: estimator.estimate_tokens(system_prompt),
+            max_tokens,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Check the prompt around the diff, system prompt included, leaves room in `budget` for a diff,
+/// so a long system prompt is reported as such rather than as a diff too large for no tokens.
+fn check_prompt_overhead(cli: &Cli, budget: &Budget) -> Result<(), ReviewError> {
+    if budget.remaining() > 0 {
+        return Ok(());
+    }
+    Err(ReviewError::PromptTooLarge {
+        tokens: budget.used,
+        system_prompt_tokens: cli.token_estimator().estimate_tokens(&prompt_parts(cli, "", &[])?.system_prompt),
+        max_tokens: budget.prompt_limit(),
+    })
+}
+
+/// The diff arguments with the context reduced to what `fit_to_budget` finds fits in
+/// `max_tokens`, and that context; `None` if the diff fits as it is and `force_reduced` isn't set.
+/// `diff_output` was generated with the last `-U`, `--unified` or `--context` in `git_args`, or
+/// else `unified_context` lines of context. The caller re-runs the diff with the new arguments and
+/// measures that, since the VCS can hunk the diff differently than trimming does.
+pub fn reduce_context_if_needed(
+    git_args: &[String],
+    unified_context: usize,
+    force_reduced: bool,
+    diff_output: &str,
+    max_tokens: usize,
+    estimator: &dyn TokenEstimator,
+) -> Result<Option<(Vec<String>, usize)>, ReviewError> {
+    // Everything after `--` is a pathspec, however it looks, and is passed on as it is
+    let options = git_args.iter().position(|arg| arg == "--").unwrap_or(git_args.len());
+    let unified_context =
+        git_args[..options].iter().rev().find_map(|arg| context_arg(arg)).unwrap_or(unified_context);
+    let fitted = fit_to_budget(diff_output, unified_context, &Budget::new(max_tokens), estimator)?;
+    if fitted.context_lines == unified_context && !force_reduced {
+        return Ok(None);
+    }
+    let reduced_context = fitted.context_lines;
+    info!("Reducing context to {} lines to fit token limits", reduced_context);
+
+    let new_git_args: Vec<String> = git_args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            if i >= options {
+                arg.clone()
+            } else if arg.starts_with("-U") {
+                format!("-U{}", reduced_context)
+            } else if arg.starts_with("--unified=") {
+                format!("--unified={}", reduced_context)
+            } else if arg.starts_with("--context=") {
+                format!("--context={}", reduced_context)
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+    Ok(Some((new_git_args, reduced_context)))
+}
+
+/// `diff_output`, or the diff `diff_with` regenerates with the arguments `reduce_context_if_needed`
+/// finds, counted again and trimmed further if the VCS hunked it larger than trimming predicted.
+/// Fails only when even the regenerated diff without context doesn't fit in `max_tokens`.
+pub fn reduce_context_with(
+    git_args: &[String],
+    unified_context: usize,
+    force_reduced: bool,
+    diff_output: String,
+    max_tokens: usize,
+    estimator: &dyn TokenEstimator,
+    diff_with: impl FnOnce(&[String]) -> Result<String, ReviewError>,
+) -> Result<String, ReviewError> {
+    let Some((new_args, context_lines)) =
+        reduce_context_if_needed(git_args, unified_context, force_reduced, &diff_output, max_tokens, estimator)?
+    else {
+        return Ok(diff_output);
+    };
+    let diff_output = diff_with(&new_args)?;
+    Ok(fit_to_budget(&diff_output, context_lines, &Budget::new(max_tokens), estimator)?.diff)
+}
+
+/// The VCS named on the command line, or else the one the current directory belongs to. Falls back
+/// to git, for a bare repository or one GIT_DIR points to; `check_repository` stops a run outside
+/// any repository before it gets that far.
+fn vcs(cli: &Cli) -> Vcs {
+    if let Some(vcs) = cli.vcs.as_deref().and_then(|name| name.parse().ok()) {
+        return vcs;
+    }
+    env::current_dir()
+        .ok()
+        .and_then(|dir| Vcs::detect(&dir))
+        .unwrap_or(Vcs::Git)
+}
+
+/// Before taking the diff from a repository, check there is one when --vcs doesn't name it, since
+/// git outside one would take the arguments as paths to compare instead
+fn check_repository(cli: &Cli) -> Result<(), ReviewError> {
+    if cli.vcs.is_some() || cli.diff_command.is_some() {
+        return Ok(());
+    }
+    require_repository(&env::current_dir().unwrap_or_default())
+}
+
+fn setup_logging(cli: &Cli) {
+    let log_level = if cli.debug {
+        LevelFilter::Trace
+    } else if cli.verbose {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Warn
+    };
+
+    // A host application may have set up its own logger, which is left in place
+    if SimpleLogger::new().with_level(log_level).init().is_err() {
+        debug!("A logger is already set up; using it");
+    }
+
+    if cli.verbose {
+        info!("Verbose mode enabled.");
+    }
+    if cli.debug {
+        trace!("Debug mode enabled.");
+    }
+}
+
+/// The default system prompt, with any sections replaced from files given on the command line
+fn try_system_prompt(cli: &Cli) -> Result<String, ReviewError> {
+    let section = |path: &Option<PathBuf>, default: &str| match path {
+        Some(path) => fs::read_to_string(path).map_err(|e| ReviewError::Io(path.clone(), e)),
+        None => Ok(default.to_string()),
+    };
+
+    // The persona replaces the defaults, and section files replace the persona
+    let base = cli.stable_schema().map_or_else(SystemPrompt::default, |schema| schema.system_prompt());
+    let prompt = match &cli.selected_persona {
+        Some(persona) => persona.apply(base),
+        None => base,
+    };
+    let tone = section(&cli.tone_file, prompt.section(PromptSection::Tone))?;
+    let rubric = cli.severity_policy.rubric.as_deref().unwrap_or(prompt.section(PromptSection::SeverityRubric));
+    let rubric = section(&cli.severity_rubric, rubric)?;
+    Ok(prompt
+        .with_section(PromptSection::ReviewRules, section(&cli.rules_file, cli.review_type().rules())?)
+        .with_section(PromptSection::OutputRules, section(&cli.output_rules_file, PROMPT_OUTPUT_RULES)?)
+        .with_section(PromptSection::SeverityRubric, rubric)
+        .with_section(PromptSection::Tone, tone)
+        .build())
+}
+
+/// The three parts of a review prompt, kept apart so their sizes can be reported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptParts {
+    /// The system prompt, including any output format instructions
+    pub system_prompt: String,
+    /// Additional context and any other sections that come before the diff
+    pub context: String,
+    /// Heading placed above the diff
+    pub diff_heading: &'static str,
+    /// The diff, or the code given with --raw-code
+    pub diff: String,
+    /// The review length asked for in the system prompt
+    pub review_target: ReviewTarget,
+    /// With --canary, the verification code placed after the diff
+    pub canary: Option<String>,
+    /// The layout the parts are assembled in
+    pub schema: PromptSchema,
+    /// With --prompt-template, the template the parts are assembled with in place of `schema`
+    pub template: Option<PromptTemplate>,
+    /// The output format instructions at the end of `system_prompt`, for a template to place
+    /// apart from it
+    pub output_format: String,
+    /// The branch and the diff arguments, for a template to place
+    pub branch: String,
+    pub diff_args: String,
+}
+
+impl PromptParts {
+    pub fn assemble(&self) -> String {
+        let template = self.template.clone().unwrap_or_else(|| self.schema.template());
+        let mut prompt = template.render(|placeholder| match placeholder {
+            Placeholder::SystemPrompt if template.places(Placeholder::OutputFormat) => {
+                self.system_prompt.replacen(&self.output_format, "", 1)
+            }
+            Placeholder::SystemPrompt => self.system_prompt.clone(),
+            Placeholder::OutputFormat => self.output_format.clone(),
+            Placeholder::Context => self.context.clone(),
+            Placeholder::DiffHeading => self.diff_heading.to_string(),
+            Placeholder::Diff => self.diff.clone(),
+            Placeholder::Branch => self.branch.clone(),
+            Placeholder::DiffArgs => self.diff_args.clone(),
+        });
+        if let Some(canary) = &self.canary {
+            if !prompt.ends_with('\n') {
+                prompt.push('\n');
+            }
+            prompt.push_str(&format!("\n{}\n", canary_line(canary)));
+        }
+        prompt
+    }
+
+    /// `(name, text)` for each part, for budget reporting
+    pub fn named(&self) -> [(&str, &str); 3] {
+        [
+            ("system_prompt", &self.system_prompt),
+            ("context", &self.context),
+            ("diff", &self.diff),
+        ]
+    }
+}
+
+/// Mark the start and end of each `(name, text)` section with its size, for --debug-prompt. The
+/// marker lines start with `|` so they stand out from the prompt's own lines.
+pub fn annotate_sections(sections: &[(&str, String)], estimator: &dyn TokenEstimator) -> String {
+    sections
+        .iter()
+        .map(|(name, text)| {
+            format!(
+                "|====BEGIN {} ({} chars, ~{} tokens)====\n{}\n|====END {}====",
+                name,
+                text.len(),
+                estimator.estimate_tokens(text),
+                text,
+                name
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The Rust edition from the Cargo.toml in the current directory or the nearest parent with one
+fn rust_edition() -> Option<String> {
+    let dir = env::current_dir().ok()?;
+    let root = dir.ancestors().find(|dir| dir.join("Cargo.toml").is_file())?;
+    detect_rust_edition(root)
+}
+
+/// Split the prompt into its parts. `sections` are extra `(heading, body)` sections placed after
+/// the additional context and before the diff.
+pub(crate) fn try_prompt_parts(cli: &Cli, diff: &str, sections: &[(&str, String)]) -> Result<PromptParts, ReviewError> {
+    let diff = plugin_transform_diff(cli, diff.to_string());
+    if cli.inject_rust_edition && rust_edition().is_none() {
+        warn!("--inject-rust-edition: no Cargo.toml found");
+    }
+    let parts = prompt_parts(cli, &diff, sections)?;
+    info!(
+        "Review length for {} changed lines: {}",
+        parts.review_target.changed_lines,
+        parts
+            .review_target
+            .words
+            .map_or("unlimited".to_string(), |words| format!("{} words", words))
+    );
+    Ok(parts)
+}
+
+/// `try_prompt_parts` without plugins or logging, for measuring the prompt around a diff without
+/// the notes and plugin calls of a prompt that's put together
+fn prompt_parts(cli: &Cli, diff: &str, sections: &[(&str, String)]) -> Result<PromptParts, ReviewError> {
+    let mut system_prompt = match &cli.system_prompt {
+        Some(system_prompt) => system_prompt.clone(),
+        None => try_system_prompt(cli)?,
+    };
+
+    if let Some(edition) = cli.inject_rust_edition.then(rust_edition).flatten() {
+        system_prompt.insert_str(0, &edition_note(&edition));
+    }
+
+    // Append output format instructions
+    let output_format = cli.output_format().map(|format| format.instructions(cli.language())).unwrap_or_default();
+    system_prompt.push_str(&output_format);
+
+    // Raw code is all new, so every line counts as changed
+    let changed_lines = match cli.raw_code {
+        Some(_) => diff.lines().count(),
+        None => diff_changed_lines(diff),
+    };
+    let review_length: ReviewLength = cli.review_length.parse().unwrap_or_default();
+    let review_target = ReviewTarget::new(review_length, changed_lines);
+    if let Some(instructions) = review_target.instructions() {
+        system_prompt.push_str(&instructions);
+    }
+    if !cli.selected_categories.is_empty() {
+        system_prompt.push_str(&category_instructions(&cli.selected_categories));
+    }
+    if let Some(instructions) =
+        cli.min_severity.as_deref().and_then(|severity| severity.parse().ok()).and_then(min_severity_instructions)
+    {
+        system_prompt.push_str(&instructions);
+    }
+    if let Some(instructions) = cli.finding_limits().instructions() {
+        system_prompt.push_str(&instructions);
+    }
+    if cli.attention_report {
+        system_prompt.push_str(CONFIDENCE_INSTRUCTIONS);
+    }
+    let locale = cli.locale();
+    if let Some(instructions) = locale.instructions() {
+        system_prompt.push_str(&instructions);
+    }
+
+    let diff_heading = if cli.raw_code.is_some() {
+        system_prompt.push_str(RAW_CODE_INSTRUCTIONS);
+        locale.text(RAW_CODE_HEADING)
+    } else {
+        if cli.api_only {
+            system_prompt.push_str(API_ONLY_INSTRUCTIONS);
+        }
+        if cli.require_citations {
+            system_prompt.push_str(REQUIRE_CITATIONS_INSTRUCTIONS);
+        }
+        if diff.lines().any(|line| elided_line_count(line).is_some()) {
+            system_prompt.push_str(COMPRESSED_DIFF_INSTRUCTIONS);
+        }
+        locale.text(DIFF_HEADING)
+    };
+
+    // Append additional context
+    let mut context_sections: Vec<(&str, String)> = Vec::new();
+    if let Some(ctx) = &cli.context {
+        context_sections.push(("Additional Context", ctx.clone()));
+    }
+    context_sections.extend(sections.iter().cloned());
+    if cli.context_dedup {
+        let before = context_sections.len();
+        context_sections = dedup_sections(context_sections, cli.context_dedup_fuzzy);
+        debug!("Dropped {} duplicate context sections", before - context_sections.len());
+    }
+
+    let mut context = String::new();
+    for (heading, body) in context_sections {
+        context.push_str(&format!("\n## {}\n{}\n", locale.text(heading), body));
+    }
+
+    let canary = cli.canary().then(|| canary_for(diff));
+    if canary.is_some() {
+        system_prompt.push_str(CANARY_INSTRUCTIONS);
+    }
+
+    Ok(PromptParts {
+        system_prompt,
+        context,
+        diff_heading,
+        diff: diff.to_string(),
+        review_target,
+        canary,
+        schema: cli.stable_schema().unwrap_or(PromptSchema::CURRENT),
+        template: cli.template.clone(),
+        output_format,
+        branch: match &cli.template {
+            Some(template) if template.places(Placeholder::Branch) => current_branch(|var| env::var(var).ok()).unwrap_or_default(),
+            _ => String::new(),
+        },
+        diff_args: cli.remaining_args.join(" "),
+    })
+}
+
+/// Assemble the prompt for `diff`, with no size checks, for callers putting together their own
+/// pipeline. `sections` are extra `(heading, body)` sections placed after the additional context
+/// and before the diff.
+pub fn build_prompt(cli: &Cli, diff: &str, sections: &[(&str, String)]) -> Result<String, ReviewError> {
+    Ok(render_prompt(cli, &try_prompt_parts(cli, diff, sections)?))
+}
+
+/// The prompt for reviewing `diff` with the options in `cli`, which must have been through
+/// `prepare`. Unlike the command line, this returns errors rather than exiting.
+pub fn try_build_prompt(cli: &Cli, diff: &str) -> Result<String, ReviewError> {
+    checked_prompt(cli, diff).map(|(prompt, _)| prompt)
+}
+
+/// `try_build_prompt`'s prompt, with the parts it was assembled from
+fn checked_prompt(cli: &Cli, diff: &str) -> Result<(String, PromptParts), ReviewError> {
+    let parts = try_prompt_parts(cli, diff, &[])?;
+    let prompt = render_prompt(cli, &parts);
+    check_hard_cap(&prompt, cli.hard_max_chars, &cli.token_estimator(), cli.effective_budget())?;
+    check_prompt_tokens(&parts.system_prompt, &prompt, &cli.token_estimator(), cli.effective_budget())?;
+    Ok((prompt, parts))
+}
+
+/// The prompt for reviewing the diff of `cli.remaining_args` in the current directory's
+/// repository, with its context reduced to fit the budget as the command line does. `cli` must
+/// have been through `prepare`. Fails with `ReviewError::NoChanges` if --include, --exclude and
+/// the diff arguments leave nothing to review, and `ReviewError::DiffTooLarge` if it doesn't fit
+/// even without context; a diff the command line would split into parts is one of those.
+pub fn build_review_prompt(cli: &Cli) -> Result<String, ReviewError> {
+    review_prompt(cli).map(|(prompt, _)| prompt)
+}
+
+/// Review the diff of `cli.remaining_args` as `build_review_prompt` describes, for a host
+/// application: the options are prepared, and with --send the prompt is sent and the review
+/// returned, or else the prompt. Nothing is printed, logged to the usage ledger or posted, and a
+/// logger is left to the host to set up.
+pub fn run_review(mut cli: Cli) -> Result<String, ReviewError> {
+    if cli.offline {
+        network::go_offline();
+    }
+    prepare(&mut cli, Instant::now())?;
+    let (prompt, parts) = review_prompt(&cli)?;
+    if !cli.send {
+        return Ok(prompt);
+    }
+    let Some(provider) = send_to(&cli)? else {
+        return Ok(prompt);
+    };
+    let review = send_prompt(&cli, &provider, &sent_model(&cli, &provider), &prompt, &parts, None)?;
+    Ok(review.unwrap_or_default())
+}
+
+/// `build_review_prompt`'s prompt, with the parts it was assembled from
+fn review_prompt(cli: &Cli) -> Result<(String, PromptParts), ReviewError> {
+    check_repository(cli)?;
+    let vcs = vcs(cli);
+    let args = match vcs {
+        Vcs::Git if cli.diff_command.is_none() => resolve_git_revisions(&cli.remaining_args)?,
+        _ => cli.remaining_args.clone(),
+    };
+    let git_args_vec = match cli.diff_command {
+        Some(_) => args,
+        None => vcs.diff_args(cli.unified_context, &args),
+    };
+    let diff_output = filter_by_paths(cli, &run_diff(cli, vcs, &git_args_vec)?);
+    if diff_output.is_empty() {
+        return Err(ReviewError::NoChanges);
+    }
+    let diff_output = try_reduce_context(cli, vcs, &git_args_vec, diff_output)?;
+    checked_prompt(cli, &filter_diff(cli, &diff_output))
+}
+
+/// Assemble the parts into the final prompt, wrapped to --format-width if given
+pub(crate) fn render_prompt(cli: &Cli, parts: &PromptParts) -> String {
+    let prompt = parts.assemble();
+    let prompt = match cli.format_width {
+        Some(width) => wrap_prose_sections(&prompt, width),
+        None => prompt,
+    };
+    plugin_transform_prompt(cli, prompt)
+}
+
+#[cfg(feature = "plugins")]
+fn plugin_transform_diff(cli: &Cli, diff: String) -> String {
+    cli.plugins.transform_diff(diff)
+}
+
+#[cfg(not(feature = "plugins"))]
+fn plugin_transform_diff(_cli: &Cli, diff: String) -> String {
+    diff
+}
+
+#[cfg(feature = "plugins")]
+fn plugin_transform_prompt(cli: &Cli, prompt: String) -> String {
+    cli.plugins.transform_prompt(prompt)
+}
+
+#[cfg(not(feature = "plugins"))]
+fn plugin_transform_prompt(_cli: &Cli, prompt: String) -> String {
+    prompt
+}
+
+/// Fill in the settings the options refer to: --stdin as --diff-file -, --output-file - as
+/// standard output, the deadline, the provider from --llm-provider-config, plugins, categories and
+/// the persona. Fails if the --output-file exists and --force wasn't given.
+pub fn prepare(cli: &mut Cli, started: Instant) -> Result<(), ReviewError> {
+    if cli.stdin || (cli.diff_file.is_none() && cli.remaining_args == ["-"]) {
+        cli.diff_file = Some(PathBuf::from("-"));
+        cli.remaining_args.clear();
+    }
+    if cli.output_file.as_deref() == Some(Path::new("-")) {
+        cli.output_file = None;
+    }
+    if let Some(path) = &cli.output_file
+        && !cli.force
+        && let Some(existing) = [path.clone(), part_path(path, 1)].into_iter().find(|path| path.exists())
+    {
+        return Err(ReviewError::Usage(format!("{} already exists; give --force to replace it", existing.display())));
+    }
+    if let Some(path) = &cli.prompt_template {
+        if cli.stable_prompt {
+            warn!("--stable-prompt keeps to its own layout; leaving out the prompt template {}", path.display());
+        } else {
+            cli.template = Some(PromptTemplate::load(path)?);
+        }
+    }
+    if let Some(secs) = cli.deadline_secs {
+        cli.deadline = Deadline::new(started, Duration::from_secs(secs as u64));
+    }
+    if let Some(address) = &cli.status_server {
+        cli.status = StatusBoard::serve(address, started)?;
+    }
+
+    if let Some(path) = &cli.llm_provider_config {
+        let mut provider = load_provider_config(path)
+            .and_then(|providers| select_provider(providers, cli.provider.as_deref()))?;
+        provider.api_key_env = cli.api_key_env.clone().or(provider.api_key_env);
+        info!(
+            "Using provider {}: {} tokens at {} characters per token",
+            provider.name, provider.max_tokens, provider.chars_per_token
+        );
+        cli.provider_config = Some(provider);
+    } else if let Some(name) = &cli.provider {
+        let mut provider = builtin_provider(name).ok_or_else(|| {
+            ReviewError::Config(format!(
+                "no provider named {:?}; without --llm-provider-config, --provider takes {}",
+                name,
+                BUILTIN_PROVIDERS.join(", ")
+            ))
+        })?;
+        provider.api_key_env = cli.api_key_env.clone();
+        if let Some(var) = provider.api_key_var()
+            && api_key(var).is_none()
+            && !cli.dry_run
+        {
+            return Err(ReviewError::Config(format!("--provider {} needs an API key in {}", name, var)));
+        }
+        cli.provider_config = Some(provider);
+        cli.send = true;
+    }
+
+    #[cfg(feature = "plugins")]
+    if let Some(dir) = &cli.plugin_dir {
+        cli.plugins = Plugins::load(dir)?;
+        info!("Loaded {} plugins from {}", cli.plugins.len(), dir.display());
+    }
+
+    if !cli.category.is_empty() {
+        let mut taxonomy = Taxonomy::default();
+        if let Some(path) = &cli.categories_file {
+            taxonomy.extend_from_file(path)?;
+        }
+        cli.selected_categories = taxonomy.select(&cli.category)?;
+    }
+
+    if let Some(name) = &cli.persona {
+        cli.selected_persona = Some(personas(cli)?.get(name)?);
+    }
+
+    if let Some(path) = &cli.severity_policy_file {
+        cli.severity_policy = SeverityPolicy::load(path)?;
+    }
+    if let Some(path) = &cli.attention_rules_file {
+        cli.attention_rules = AttentionRules::load(path)?;
+    }
+    cli.severity_policy.floors.extend(cli.severity_floor.iter().cloned());
+
+    if cli.system_prompt.is_some() && cli.review_type() != ReviewType::General {
+        warn!("--system-prompt replaces the whole system prompt, so --review-type {} is ignored", cli.review_type);
+    }
+
+    // pre-commit passes the staged files it's checking; review what's staged of them, as JSON
+    // findings to print one per line
+    if cli.pre_commit {
+        let paths = std::mem::take(&mut cli.remaining_args);
+        cli.remaining_args = ["--cached", "--"].into_iter().map(String::from).chain(paths).collect();
+        cli.output_format = Some(OutputFormat::Json.to_string());
+        cli.send = true;
+        cli.require_approval = false;
+        cli.timeout_secs.get_or_insert(DEFAULT_PRE_COMMIT_TIMEOUT_SECS);
+    }
+
+    if cli.send && cli.dry_run {
+        info!("--dry-run: printing the prompt instead of sending it");
+    } else if cli.send {
+        cli.send_to = send_to(cli)?;
+    }
+    if cli.post && cli.send_to.is_none() {
+        warn!("--post only posts reviews from --send or --provider; nothing will be posted");
+    }
+    if cli.pre_commit && cli.send_to.is_none() && !cli.dry_run {
+        return Err(ReviewError::Usage(
+            "--pre-commit needs a provider to review the staged changes: set an API key, or give --provider".to_string(),
+        ));
+    }
+    if cli.fail_on_issues && cli.send_to.is_none() && !cli.dry_run {
+        return Err(ReviewError::Usage(
+            "--fail-on-issues needs a review to check: give --send or --provider, with an API key".to_string(),
+        ));
+    }
+    if cli.save_transcript.is_some() && cli.send_to.is_none() {
+        warn!("--save-transcript only saves reviews from --send or --provider; nothing will be saved");
+    }
+    if cli.min_confidence.is_some() && cli.output_format() != Some(OutputFormat::Json) {
+        warn!("--min-confidence only filters findings given as JSON, with --output-format json or --replay");
+    }
+    Ok(())
+}
+
+/// The API key in the environment variable `var`, if it's set and not empty
+fn api_key(var: &str) -> Option<String> {
+    env::var(var).ok().filter(|key| !key.is_empty())
+}
+
+/// The provider --send sends the prompt to, or `None` with a warning when there's no API key for
+/// one, so the prompt is printed as it is without --send
+fn send_to(cli: &Cli) -> Result<Option<ProviderConfig>, ReviewError> {
+    let Some(provider) = cli.provider().cloned().or_else(|| provider_from_env(api_key)) else {
+        warn!("--send: set ANTHROPIC_API_KEY or OPENAI_API_KEY, or give --llm-provider-config; printing the prompt instead");
+        return Ok(None);
+    };
+    if provider.format == ProviderFormat::Custom {
+        return Err(ReviewError::Config(format!(
+            "--send can't talk to provider {:?}: its format is custom",
+            provider.name
+        )));
+    }
+    if let Some(var) = provider.api_key_var()
+        && api_key(var).is_none()
+    {
+        warn!("--send: {} is not set; printing the prompt instead", var);
+        return Ok(None);
+    }
+    Ok(Some(provider))
+}
+
+/// The model a prompt sent to `provider` goes to. Only custom providers have no default model, and
+/// --send refuses those.
+fn sent_model(cli: &Cli, provider: &ProviderConfig) -> String {
+    cli.model
+        .clone()
+        .or_else(|| provider.model.clone())
+        .unwrap_or_else(|| default_model(provider.format).unwrap_or_default().to_string())
+}
+
+/// The month it is, as `YYYY-MM`, and the ledger's entries
+fn this_month(ledger: &Ledger) -> Result<(String, Vec<UsageEntry>), ReviewError> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    Ok((rfc3339_utc(seconds)[..7].to_string(), ledger.load()?))
+}
+
+/// With --monthly-budget, warn when the month's spend has reached it, or with --enforce-budget
+/// refuse to send
+fn check_monthly_budget(cli: &Cli) -> Result<(), ReviewError> {
+    let (Some(budget), Some(ledger)) = (cli.monthly_budget, Ledger::user()) else {
+        return Ok(());
+    };
+    let (month, entries) = this_month(&ledger)?;
+    let spent = month_spend(&entries, &month);
+    if spent < budget {
+        return Ok(());
+    }
+    if cli.enforce_budget {
+        return Err(ReviewError::BudgetExceeded { spent, budget, month });
+    }
+    warn!("\n{}", budget_banner(spent, budget, &month));
+    Ok(())
+}
+
+/// The estimated cost of sending `prompt_tokens` to `model` at `provider` and getting
+/// `response_tokens` back, if its price is known
+fn request_cost(provider: &ProviderConfig, model: &str, prompt_tokens: usize, response_tokens: usize) -> Option<f64> {
+    // Models run locally cost nothing
+    match provider.format {
+        ProviderFormat::Ollama => Some(0.0),
+        _ => estimate_cost(model, prompt_tokens, response_tokens),
+    }
+}
+
+/// With --max-cost, refuse to send `prompt_tokens` to `model` when that alone would take the run's
+/// spend over it. A model whose price isn't known is sent to with a warning.
+fn check_max_cost(cli: &Cli, provider: &ProviderConfig, model: &str, prompt_tokens: usize) -> Result<(), ReviewError> {
+    let Some(limit) = cli.max_cost else {
+        return Ok(());
+    };
+    let Some(cost) = request_cost(provider, model, prompt_tokens, 0) else {
+        warn!("--max-cost: the price of {} isn't known, so what it costs can't be checked", model);
+        return Ok(());
+    };
+    let spent = cli.spent.get();
+    if spent + cost > limit {
+        return Err(ReviewError::OverMaxCost { model: model.to_string(), cost, spent, limit });
+    }
+    Ok(())
+}
+
+/// Ask at the terminal before sending `prompt_tokens` to a paid `provider` takes the run over
+/// --confirm-threshold, once a run; declining aborts it. Not asked with --yes or --pre-commit, or
+/// without a terminal on standard output and input to ask on.
+fn confirm_send(cli: &Cli, provider: &ProviderConfig, model: &str, prompt_tokens: usize) -> Result<(), ReviewError> {
+    let total = cli.tokens_sent.get() + prompt_tokens;
+    cli.tokens_sent.set(total);
+    if total <= cli.confirm_threshold || cli.send_confirmed.get() || cli.yes || cli.pre_commit {
+        return Ok(());
+    }
+    let cost = request_cost(provider, model, prompt_tokens, 0);
+    if cost == Some(0.0) {
+        return Ok(());
+    }
+    if !io::stdout().is_terminal() || !io::stdin().is_terminal() {
+        debug!("--confirm-threshold: not running interactively, sending ~{} tokens without asking", prompt_tokens);
+        return Ok(());
+    }
+
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "{} Send? [y/N] ", confirm_message(model, prompt_tokens, total, cost));
+    let _ = stderr.flush();
+
+    let mut answer = String::new();
+    if !matches!(io::stdin().lock().read_line(&mut answer), Ok(n) if n > 0) {
+        return Err(ReviewError::Aborted);
+    }
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        return Err(ReviewError::Aborted);
+    }
+    cli.send_confirmed.set(true);
+    Ok(())
+}
+
+/// What --confirm-threshold asks about: the estimated tokens of a prompt to `model` and what it
+/// costs before the response, which is `None` when its price isn't known, and the run's `total`
+/// when earlier prompts count towards it too
+pub fn confirm_message(model: &str, prompt_tokens: usize, total: usize, cost: Option<f64>) -> String {
+    let cost = cost.map_or_else(|| "an unknown cost".to_string(), |cost| format!("about ${:.2}", cost));
+    let run = if total > prompt_tokens { format!(" ({} this run)", total) } else { String::new() };
+    format!("About {} prompt tokens{} to {}, for {} before the response.", prompt_tokens, run, model, cost)
+}
+
+/// Record the tokens a request to `model` sent and got back, and their estimated cost, in the
+/// run's spend and the usage ledger, warning if the run took the month over --monthly-budget
+fn record_usage(cli: &Cli, provider: &ProviderConfig, model: &str, prompt_tokens: usize, response_tokens: usize) {
+    let cost_usd = request_cost(provider, model, prompt_tokens, response_tokens);
+    cli.spent.set(cli.spent.get() + cost_usd.unwrap_or_default());
+    let Some(ledger) = Ledger::user() else {
+        return;
+    };
+    let model = model.to_string();
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let entry = UsageEntry {
+        timestamp: rfc3339_utc(seconds),
+        model,
+        provider: Some(provider.name.clone()),
+        repo: repo_root().and_then(|root| root.file_name().map(|name| name.to_string_lossy().to_string())),
+        preset: Some(preset_name(cli)),
+        prompt_tokens,
+        response_tokens,
+        cost_usd,
+    };
+    if let Err(e) = ledger.append(&entry) {
+        warn!("Could not record the run's usage in {}: {}", ledger.path.display(), e);
+        return;
+    }
+    if let Some(budget) = cli.monthly_budget
+        && let Ok((month, entries)) = this_month(&ledger)
+    {
+        let spent = month_spend(&entries, &month);
+        if spent >= budget && spent - cost_usd.unwrap_or_default() < budget {
+            warn!("\n{}", budget_banner(spent, budget, &month));
+        }
+    }
+}
+
+/// With --output-format json, ask `model` once more when its `review` isn't the JSON asked for,
+/// saying what was wrong with it, and keep the second answer if it is. Otherwise, and when asking
+/// again fails or would take the run over --max-cost, the review is kept as it came, for
+/// `json_review` to salvage what it can. The warnings are logged to standard error, so what CI
+/// reads from standard output stays JSON.
+fn retry_invalid_json(
+    cli: &Cli,
+    provider: &ProviderConfig,
+    model: &str,
+    prompt: &str,
+    parts: &PromptParts,
+    review: String,
+) -> Result<String, ReviewError> {
+    if cli.output_format() != Some(OutputFormat::Json) {
+        return Ok(review);
+    }
+    let Err(e) = parse_json_findings(&review) else {
+        return Ok(review);
+    };
+    let corrective = format!(
+        "{}\n\nYour previous answer wasn't the JSON asked for: {}. Answer again with only the JSON array of findings described above, and nothing before or after it.",
+        prompt, e
+    );
+    let tokens = cli.token_estimator().estimate_tokens(&corrective);
+    if let Err(over) = check_max_cost(cli, provider, model, tokens) {
+        warn!("The review isn't the JSON asked for ({}), but asking again would cost too much: {}", e, over);
+        return Ok(review);
+    }
+    warn!("The review isn't the JSON asked for ({}); asking {} again", e, model);
+    let retry = match send_prompt(cli, provider, model, &corrective, parts, None) {
+        Ok(Some(retry)) => retry,
+        Ok(None) => return Ok(review),
+        Err(e) => {
+            warn!("Asking {} again failed: {}; keeping its first answer", model, e);
+            return Ok(review);
+        }
+    };
+    record_usage(cli, provider, model, tokens, cli.token_estimator().estimate_tokens(&retry));
+    match parse_json_findings(&retry) {
+        Ok(_) => Ok(retry),
+        Err(e) => {
+            warn!("The second answer isn't the JSON asked for either ({}); keeping the first", e);
+            Ok(review)
+        }
+    }
+}
+
+/// Check `review` is in the --output-format asked for. A review without the structure every
+/// review in its format has, such as Markdown without a heading, is asked for again, once, saying
+/// what was missing. With --format-check, a review that looks like another format is warned of,
+/// and with --enforce-format asked for again too. The second answer is kept if it passes; otherwise,
+/// or if asking again fails or would go over --max-cost, the first is.
+fn check_format(
+    cli: &Cli,
+    provider: &ProviderConfig,
+    model: &str,
+    prompt: &str,
+    parts: &PromptParts,
+    review: String,
+) -> Result<String, ReviewError> {
+    let Some(format) = cli.output_format() else {
+        return Ok(review);
+    };
+    let check = cli.format_check || cli.enforce_format;
+    let mismatch = format_mismatch(&review, &format).filter(|_| check);
+    if let Some(mismatch) = &mismatch {
+        warn!("The review doesn't look like the {} asked for: it has {}", format, mismatch);
+    }
+    let problem = mismatch.filter(|_| cli.enforce_format).or_else(|| missing_structure(&review, &format));
+    let Some(problem) = problem else {
+        return Ok(review);
+    };
+    let corrective = format!(
+        "{}\n\nYour previous answer wasn't in the {} format asked for: it had {}. Answer again with the same review, in {} format only.",
+        prompt, format, problem, format
+    );
+    let tokens = cli.token_estimator().estimate_tokens(&corrective);
+    if let Err(over) = check_max_cost(cli, provider, model, tokens) {
+        warn!("The review has {}, but asking again for the {} asked for would cost too much: {}", problem, format, over);
+        return Ok(review);
+    }
+    warn!("The review has {}; asking {} again for it in {}", problem, model, format);
+    let retry = match send_prompt(cli, provider, model, &corrective, parts, None) {
+        Ok(Some(retry)) => retry,
+        Ok(None) => return Ok(review),
+        Err(e) => {
+            warn!("Asking {} again failed: {}; keeping its first answer", model, e);
+            return Ok(review);
+        }
+    };
+    record_usage(cli, provider, model, tokens, cli.token_estimator().estimate_tokens(&retry));
+    let problem = format_mismatch(&retry, &format)
+        .filter(|_| cli.enforce_format)
+        .or_else(|| missing_structure(&retry, &format));
+    match problem {
+        None => Ok(retry),
+        Some(problem) => {
+            warn!("The second answer isn't in {} either (it has {}); keeping the first", format, problem);
+            Ok(review)
+        }
+    }
+}
+
+/// With --fallback-model, re-run the prompt with the fallback model, once, when the `review` by
+/// `model` fails the quality checks, and keep the re-run's review instead. There's no re-run when
+/// `model` is the fallback, or when --max-cost can't be kept to; a re-run that fails leaves the
+/// review as it was. Returns the model whose review is kept, the review, and the escalation if
+/// there was one.
+fn review_with_fallback(
+    cli: &Cli,
+    provider: &ProviderConfig,
+    model: String,
+    prompt: &str,
+    parts: &PromptParts,
+    tokens: usize,
+    review: String,
+) -> Result<(String, String, Option<Escalation>), ReviewError> {
+    let Some(fallback) = cli.fallback_model.clone().filter(|fallback| *fallback != model) else {
+        return Ok((model, review, None));
+    };
+    let json = cli.output_format() == Some(OutputFormat::Json);
+    let problems: Vec<String> =
+        check_quality(&review, &parse_diff(&parts.diff), &cli.quality, json).iter().map(ToString::to_string).collect();
+    if problems.is_empty() {
+        return Ok((model, review, None));
+    }
+    if let Some(limit) = cli.max_cost {
+        // The re-run sends the same prompt, and is expected to write about as much again
+        let response_tokens = cli.token_estimator().estimate_tokens(&review);
+        let Some(cost) = request_cost(provider, &fallback, tokens, response_tokens) else {
+            warn!(
+                "The review by {} looks too shallow ({}), but the price of {} isn't known, so a re-run can't be kept to --max-cost; keeping it",
+                model,
+                problems.join("; "),
+                fallback
+            );
+            return Ok((model, review, None));
+        };
+        if cli.spent.get() + cost > limit {
+            warn!(
+                "The review by {} looks too shallow ({}), but re-running it with {} would cost about ${:.2}, taking the run over the --max-cost of ${:.2}; keeping it",
+                model,
+                problems.join("; "),
+                fallback,
+                cost,
+                limit
+            );
+            return Ok((model, review, None));
+        }
+    }
+    warn!("The review by {} looks too shallow: {}; re-running it with {}", model, problems.join("; "), fallback);
+    let rerun = match send_prompt(cli, provider, &fallback, prompt, parts, None) {
+        Ok(Some(rerun)) => rerun,
+        Ok(None) => return Ok((model, review, None)),
+        Err(e) => {
+            warn!("Re-running the review with {} failed: {}; keeping the review by {}", fallback, e, model);
+            return Ok((model, review, None));
+        }
+    };
+    record_usage(cli, provider, &fallback, tokens, cli.token_estimator().estimate_tokens(&rerun));
+    Ok((fallback, rerun, Some(Escalation { from_model: model, problems })))
+}
+
+/// A chunk of a run with --send that pressing Enter skips, and the files it covers
+struct SkippableChunk<'a> {
+    keys: &'a KeyWatcher,
+    part: usize,
+    parts: usize,
+    paths: &'a [String],
+}
+
+/// Send the prompt to `model` at `provider` and return the review, condensed if it ran away and
+/// without the canary trailer, which is checked first. `None` when the chunk was `skippable` and
+/// was skipped.
+fn send_prompt(
+    cli: &Cli,
+    provider: &ProviderConfig,
+    model: &str,
+    prompt: &str,
+    parts: &PromptParts,
+    skippable: Option<&SkippableChunk>,
+) -> Result<Option<String>, ReviewError> {
+    let timeout = match (cli.timeout_secs.map(|secs| Duration::from_secs(secs as u64)), cli.deadline.remaining()) {
+        (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+        (timeout, remaining) => timeout.or(remaining),
+    };
+    let settings = RequestSettings {
+        endpoint: provider.endpoint.clone(),
+        model: model.to_string(),
+        key: provider.api_key_var().and_then(api_key),
+        // A context window is far more than any model will write in one response
+        max_tokens: cli.max_tokens().min(DEFAULT_MAX_TOKENS),
+        limits: cli.generation_limits(),
+        timeout,
+        chars_per_token: provider.chars_per_token,
+        extra_params: cli.extra_param.clone(),
+    };
+    // The system prompt goes in its own message, unless --format-width or a plugin changed it
+    let (system, user) = match prompt.strip_prefix(&parts.system_prompt) {
+        Some(user) => (parts.system_prompt.as_str(), user.trim_start()),
+        None => ("", prompt),
+    };
+    let response = match skippable {
+        Some(chunk) => {
+            // The chunk reports its own progress, which the request would go on with if skipped
+            let heartbeat = settings.limits.heartbeat;
+            let settings = RequestSettings { limits: GenerationLimits { heartbeat: None, ..settings.limits }, ..settings };
+            let (provider, system, user) = (provider.clone(), system.to_string(), user.to_string());
+            let label = chunk_label(chunk.part, chunk.parts, chunk.paths);
+            let request = move || provider_for(&provider, settings)?.complete(&system, &user);
+            match run_skippable(chunk.keys, &label, heartbeat, request)? {
+                Some(response) => response,
+                None => return Ok(None),
+            }
+        }
+        None => provider_for(provider, settings)?.complete(system, user)?,
+    };
+    let mut review = cap_response(&response, &RunawayLimits::default()).comment().to_string();
+    if let Some(canary) = &parts.canary {
+        let check = check_canary(&review, canary);
+        if !check.is_found() {
+            warn!("{}", check);
+        }
+        review = strip_canary(&review);
+    }
+    // Models often put output meant for a program in a code block anyway, or talk around it
+    Ok(Some(normalize_review(&review, cli.output_format())))
+}
+
+/// Take the settings from the config files for the options not given on the command line. An
+/// option still at its default counts as not given. A system prompt isn't taken with --persona.
+fn apply_config(cli: &mut Cli, config: &Config) {
+    if !config.sources.is_empty() {
+        info!("Settings from {}", config.sources.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "));
+    }
+    if cli.context.is_none() {
+        cli.context = config.context.clone();
+    }
+    if cli.system_prompt.is_none() && cli.persona.is_none() {
+        cli.system_prompt = config.system_prompt.clone();
+    }
+    if cli.prompt_template.is_none() {
+        cli.prompt_template = config.prompt_template.clone();
+    }
+    if cli.output_format.is_none() {
+        cli.output_format = config.output_format.clone();
+    }
+    if cli.unified_context == DEFAULT_UNIFIED_CONTEXT
+        && let Some(unified_context) = config.unified_context
+    {
+        cli.unified_context = unified_context;
+    }
+    if cli.token_limit.is_none() {
+        cli.token_limit = config.max_tokens;
+    }
+    if cli.provider.is_none() && cli.llm_provider_config.is_none() {
+        cli.provider = config.provider.clone();
+    }
+    if cli.model.is_none() {
+        cli.model = config.model.clone();
+    }
+    if cli.api_key_env.is_none() {
+        cli.api_key_env = config.api_key_env.clone();
+    }
+    if cli.monthly_budget.is_none() {
+        cli.monthly_budget = config.monthly_budget_usd;
+    }
+    if cli.history_dir.is_none() {
+        cli.history_dir = config.history_dir.clone();
+    }
+    if cli.infra_files.is_empty() {
+        cli.infra_files = config.infra_files.clone().unwrap_or_default();
+    }
+    if cli.skip_infra_check.is_empty() {
+        cli.skip_infra_check = config.skip_infra_checks.clone().unwrap_or_default();
+    }
+    if cli.migration_files.is_empty() {
+        cli.migration_files = config.migration_files.clone().unwrap_or_default();
+    }
+    if cli.max_findings_per_file.is_none() {
+        cli.max_findings_per_file = config.max_findings_per_file;
+    }
+    if cli.max_findings.is_none() {
+        cli.max_findings = config.max_findings;
+    }
+    if cli.review_language.is_none() {
+        cli.review_language = config.review_language.clone();
+    }
+    if cli.fallback_model.is_none() {
+        cli.fallback_model = config.fallback_model.clone();
+    }
+    if cli.max_cost.is_none() {
+        cli.max_cost = config.max_cost_usd;
+    }
+    cli.quality = config.quality.clone().unwrap_or_default();
+}
+
+/// Whether to offer the setup wizard: no config file was found, someone is at the terminal to answer
+/// it, and nothing on the command line says which provider to use
+fn wants_wizard(cli: &Cli, config: &Config) -> bool {
+    config.sources.is_empty()
+        && !cli.no_wizard
+        && !cli.no_config
+        && cli.command.is_none()
+        && !cli.show_config
+        && !cli.capabilities
+        && !cli.smoke_test
+        && !cli.send
+        && !cli.pre_commit
+        && cli.provider.is_none()
+        && cli.llm_provider_config.is_none()
+        && cli.model.is_none()
+        && cli.api_key_env.is_none()
+        && io::stdin().is_terminal()
+        && io::stderr().is_terminal()
+}
+
+/// Run the setup wizard and save its settings as the user's config file, returning them. The
+/// wizard stopping early, or being told not to save, leaves everything as it was.
+fn setup_wizard() -> Result<Option<Config>, ReviewError> {
+    let Some(path) = user_config_path() else {
+        return Ok(None);
+    };
+    let config = match run_wizard(&mut Tty, &api_key, &round_trip_with_key) {
+        Ok(Some(config)) => config,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            warn!("Setup stopped: {}; nothing was saved", e);
+            return Ok(None);
+        }
+    };
+    save_config(&config, &path)?;
+    eprintln!("Saved {}", path.display());
+    let flags = equivalent_flags(&config);
+    if !flags.is_empty() {
+        eprintln!("The same settings on the command line: {}", flags);
+    }
+    Ok(Some(Config { sources: vec![path], ..config }))
+}
+
+/// The settings a config file can give, as the command line ended up with them, for --show-config
+fn effective_config(cli: &Cli, sources: Vec<PathBuf>) -> Config {
+    Config {
+        context: cli.context.clone(),
+        system_prompt: cli.system_prompt.clone(),
+        system_prompt_file: None,
+        prompt_template: cli.prompt_template.clone(),
+        output_format: cli.output_format.clone(),
+        unified_context: Some(cli.unified_context),
+        max_tokens: cli.token_limit,
+        provider: cli.provider.clone(),
+        model: cli.model.clone(),
+        api_key_env: cli.api_key_env.clone(),
+        history_dir: cli.history_dir.clone(),
+        monthly_budget_usd: cli.monthly_budget,
+        infra_files: (!cli.infra_files.is_empty()).then(|| cli.infra_files.clone()),
+        skip_infra_checks: (!cli.skip_infra_check.is_empty()).then(|| cli.skip_infra_check.clone()),
+        migration_files: (!cli.migration_files.is_empty()).then(|| cli.migration_files.clone()),
+        max_findings_per_file: cli.max_findings_per_file,
+        max_findings: cli.max_findings,
+        review_language: cli.review_language.clone(),
+        fallback_model: cli.fallback_model.clone(),
+        max_cost_usd: cli.max_cost,
+        quality: (cli.quality != QualityThresholds::default()).then(|| cli.quality.clone()),
+        sources,
+    }
+}
+
+/// Take the settings in the pull request description's ```llm-review block for the flags not
+/// given on the command line. A flag still at its default counts as not given. A block that can't
+/// be parsed is reported and left out, rather than failing the run.
+fn apply_pr_config(cli: &mut Cli, pr: &PullRequestContext) {
+    let config = match PrConfig::from_description(pr.body.as_deref().unwrap_or_default()) {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("{}; ignoring it", e);
+            return;
+        }
+    };
+    if !config.ignored.is_empty() {
+        warn!(
+            "The pull request description can't set {}; it may only set {}",
+            config.ignored.join(", "),
+            PR_CONFIG_KEYS.iter().map(|(key, _)| *key).collect::<Vec<_>>().join(", ")
+        );
+    }
+    info!("Review settings from the pull request description: {}", config.describe());
+    if cli.persona.is_none() && cli.system_prompt.is_none() {
+        cli.persona = config.preset;
+    }
+    if cli.review_length == "auto"
+        && let Some(depth) = config.depth
+    {
+        cli.review_length = depth;
+    }
+    cli.exclude.extend(config.exclude);
+    if cli.min_severity.is_none() {
+        cli.min_severity = config.min_severity.map(|severity| severity.to_string());
+    }
+}
+
+/// The built-in personas and any from --personas-file
+fn personas(cli: &Cli) -> Result<Personas, ReviewError> {
+    let mut personas = Personas::default();
+    if let Some(path) = &cli.personas_file {
+        personas.extend_from_file(path)?;
+    }
+    Ok(personas)
+}
+
+/// Do what the command line asks, returning the status to exit with. Errors are returned rather
+/// than exiting; the binary logs them and exits with status 1.
+pub fn run(mut cli: Cli) -> Result<i32, ReviewError> {
+    let started = Instant::now();
+    setup_logging(&cli);
+    if cli.notify {
+        notify::enable();
+    }
+    if cli.offline {
+        network::go_offline();
+    }
+    let mut config = match &cli.config {
+        _ if cli.no_config => Config::default(),
+        Some(path) => Config::load(path)?,
+        None => load_config()?,
+    };
+    if wants_wizard(&cli, &config)
+        && let Some(saved) = setup_wizard()?
+    {
+        config = saved;
+    }
+    apply_config(&mut cli, &config);
+    if cli.show_config {
+        println!("{}", effective_config(&cli, config.sources).describe().trim_end());
+        return Ok(0);
+    }
+    if cli.capabilities {
+        println!("{}", render_probes(&capabilities().report()));
+        return Ok(0);
+    }
+    match cli.command.clone() {
+        Some(CliCommand::Doctor { offline }) => return Ok(run_doctor(&mut cli, offline || network::is_offline())),
+        Some(CliCommand::Feedback { action }) => {
+            println!("{}", feedback(&cli, &action)?);
+            return Ok(0);
+        }
+        Some(CliCommand::Report { action }) => {
+            println!("{}", report(&cli, &action)?);
+            return Ok(0);
+        }
+        Some(CliCommand::Usage { action: UsageAction::Report { since } }) => {
+            let ledger = Ledger::user().ok_or_else(|| ReviewError::Config("no home directory for the usage ledger".to_string()))?;
+            println!("{}", usage_report(&ledger.load()?, since.as_deref()));
+            return Ok(0);
+        }
+        None => {}
+    }
+    if cli.smoke_test {
+        return Ok(run_smoke_test(&mut cli));
+    }
+    if cli.list_personas {
+        println!("{}", personas(&cli)?.list());
+        return Ok(0);
+    }
+    let pull_request = if cli.github_actions_pr_context {
+        github_actions_pr_context()?
+    } else {
+        None
+    };
+    if let Some(pr) = &pull_request {
+        apply_pr_config(&mut cli, pr);
+    }
+    if cli.post {
+        cli.post_to = Some(post_target(pull_request.as_ref())?);
+    }
+    if let Some(path) = &cli.replay {
+        replay(&cli, path)?;
+        return Ok(0);
+    }
+    prepare(&mut cli, started)?;
+    if let Some(path) = &cli.token_cache {
+        use_token_cache(path);
+    }
+
+    if cli.show_system_prompt {
+        // Indent the each line of the prompt by two spaces
+        println!(
+            "Default System Prompt:\n\n{}",
+            Regex::new(r"(?m)^")
+                .unwrap()
+                .replace_all(&try_system_prompt(&cli)?, "  ")
+        );
+        return Ok(0);
+    }
+    if cli.show_template {
+        match &cli.prompt_template {
+            Some(path) if cli.template.is_some() => info!("Prompt template from {}:", path.display()),
+            _ => info!("The built-in prompt template:"),
+        }
+        let template = cli.template.clone().unwrap_or_default();
+        println!("{}", template.source().strip_suffix('\n').unwrap_or(template.source()));
+        return Ok(0);
+    }
+
+    cli.repo_defaults = repo_defaults(&cli);
+    if let Some(root) = repo_root() {
+        let excludes = load_excludes(&root)?;
+        if !excludes.is_empty() {
+            debug!("Leaving out {} from {}", excludes.join(", "), EXCLUDE_FILE);
+            cli.exclude.extend(excludes);
+        }
+    }
+    cli.workspace = workspace(&cli)?;
+    if let Some(name) = &cli.package {
+        let workspace = cli.workspace.as_ref().ok_or_else(|| {
+            ReviewError::Config(format!(
+                "--package {}: no Cargo or npm workspace found; list the packages with --packages-file",
+                name
+            ))
+        })?;
+        let package = workspace.get(name)?;
+        info!("Reviewing only the files of package {} in {}", package.name, package.dir);
+    }
+
+    if let Some(path) = &cli.raw_code {
+        let budget = cli.effective_budget();
+        let code = read_raw_code(&cli, path)?;
+        check_diff_size(&code, cli.diff_budget(&[]).remaining(), &cli.token_estimator())?;
+        let parts = try_prompt_parts(&cli, &code, &[])?;
+        write_prompt(&cli, &parts, BudgetReport::new(budget), started)?;
+        return Ok(0);
+    }
+
+    let vcs = vcs(&cli);
+    if vcs == Vcs::Git && cli.diff_command.is_none() && cli.diff_file.is_none() {
+        check_checkout(&cli);
+    }
+    if let (Some(range), Some(output_dir)) = (&cli.each, &cli.output_dir) {
+        if vcs != Vcs::Git {
+            return Err(ReviewError::Usage("--each is only supported in git repositories".to_string()));
+        }
+        return review_each(&cli, range, output_dir, started);
+    }
+
+    cli.status.phase("diff");
+    // With merge commits in the range, what's said about them in the prompt
+    let mut merges_note = None;
+    let (original_diff, diff_output, source, mut size_tier) = if let Some(n) = cli.from_stash {
+        if vcs != Vcs::Git {
+            return Err(ReviewError::Usage("--from-stash is only supported in git repositories".to_string()));
+        }
+        let diff_output = preprocess_diff(&cli, get_stash_diff(n)?)?;
+        (diff_output.clone(), diff_output, None, None)
+    } else if let Some(path) = &cli.diff_file {
+        if !cli.remaining_args.is_empty() {
+            return Err(ReviewError::Config(format!(
+                "--diff-file doesn't run `git diff`, so it takes no diff arguments; got `{}`",
+                cli.remaining_args.join(" ")
+            )));
+        }
+        let diff_output = preprocess_diff(&cli, read_diff_file(path)?)?;
+        let filtered = filter_by_paths(&cli, &diff_output);
+        let source = if path == Path::new("-") {
+            "A diff given on standard input, not taken from the repository".to_string()
+        } else {
+            format!("The diff in `{}`, not taken from the repository", path.display())
+        };
+        let size_tier = size_tier(&cli, &filtered);
+        let reduced = match size_tier {
+            SizeTier::Medium if !filtered.is_empty() => {
+                trim_diff_file_context(&cli, filtered, &source)?
+            }
+            _ => filtered,
+        };
+        (diff_output, reduced, Some(source), Some(size_tier))
+    } else if let Some(url) = cli.remaining_args.iter().find_map(|arg| parse_github_url(arg)) {
+        if cli.remaining_args.len() > 1 {
+            warn!("Reviewing a GitHub URL; ignoring the other arguments");
+        }
+        if cli.watch {
+            warn!("--watch has no effect when reviewing a GitHub URL");
+        }
+        let diff_output = preprocess_diff(&cli, fetch_github_diff(&url, cli.deadline.remaining())?)?;
+        (diff_output.clone(), diff_output, Some(url.describe()), None)
+    } else {
+        check_repository(&cli)?;
+        if vcs == Vcs::Git && cli.diff_command.is_none() {
+            cli.remaining_args = resolve_git_revisions(&cli.remaining_args)?;
+        }
+        let selection = match select_commits(&cli, vcs, started) {
+            Err(ReviewError::NoChanges) => return Ok(0),
+            selection => selection?,
+        };
+        let merges = match (&cli.diff_command, &selection) {
+            (None, None) if vcs == Vcs::Git => adapt_merges(&cli),
+            _ => None,
+        };
+        let git_args_vec = match (&cli.diff_command, &selection, &merges) {
+            (Some(_), _, _) => cli.remaining_args.clone(),
+            (None, Some((combined, _)), _) => vcs.diff_args(cli.unified_context, &[combined.base.clone(), combined.tree.clone()]),
+            (None, None, Some(MergeAdaptation { combined: Some(combined), .. })) => {
+                let mut args = vec![combined.base.clone(), combined.tree.clone()];
+                args.extend(cli.remaining_args.iter().skip_while(|arg| *arg != "--").cloned());
+                vcs.diff_args(cli.unified_context, &args)
+            }
+            (None, None, _) => vcs.diff_args(cli.unified_context, &cli.remaining_args),
+        };
+        merges_note = merges.map(|merges| merges.section);
+
+        if cli.watch {
+            // A provider from the config file; one on the command line can't be given with --watch
+            if cli.send {
+                warn!("--watch prints each prompt rather than sending it");
+            }
+            watch(&cli, vcs, &git_args_vec)?;
+        }
+
+        let diff_output = run_diff(&cli, vcs, &git_args_vec)?;
+        let filtered = filter_by_paths(&cli, &diff_output);
+        let size_tier = size_tier(&cli, &filtered);
+        // Large diffs are split into parts once they've been filtered
+        let reduced = match size_tier {
+            SizeTier::Medium if !filtered.is_empty() => try_reduce_context(&cli, vcs, &git_args_vec, filtered)?,
+            _ => filtered,
+        };
+        (diff_output, reduced, selection.map(|(_, note)| note), Some(size_tier))
+    };
+
+    let context_reduced = diff_output != filter_by_paths(&cli, &original_diff);
+    let diff_output = filter_diff(&cli, &diff_output);
+    let (diff_output, redactions) = scrub_paths(&cli, diff_output);
+    let (diff_output, sampled) = sample_diff(&cli, diff_output);
+    let mut report = BudgetReport::from_diffs(
+        &original_diff,
+        &diff_output,
+        context_reduced,
+        cli.effective_budget(),
+        &cli.token_estimator(),
+    );
+    report.sampled = sampled.clone();
+    report.redactions = redactions;
+    report.size_tier = size_tier;
+    if cli.attention_report {
+        report.attention = Some(assess(&parse_diff(&diff_output), &cli.attention_rules, &[]));
+    }
+    if log_enabled!(Level::Info) {
+        info!("Token budget by file:\n{}", report);
+    }
+    if let Some(path) = &cli.file_issues {
+        return file_issues(&cli, path, &diff_output);
+    }
+    if let Some(path) = &cli.check_response {
+        let found = check_response(&cli, path, &diff_output)?;
+        if found || !cli.strict_canary {
+            return Ok(0);
+        }
+        warn!("--strict-canary: writing the prompt again, split into parts");
+        size_tier = Some(SizeTier::Large);
+    }
+    if diff_output.is_empty() && !parse_diff(&original_diff).is_empty() {
+        println!("All the changed files were left out by --include, --exclude or the other filters, so there is nothing to review.");
+        finish(&cli, 0, 0, started);
+        return Ok(0);
+    }
+    if diff_output.is_empty() {
+        println!("No changes found to review.");
+        finish(&cli, 0, 0, started);
+        return Ok(0);
+    }
+
+    let local = source.is_none();
+    let mut sections: Vec<(&str, String)> = Vec::new();
+    if !cli.no_provenance && cli.deadline.allows("the provenance section", GIT_STEP_ESTIMATE) {
+        let provenance = provenance(&cli, vcs, &diff_output, local);
+        let description = if cli.stable_prompt {
+            provenance.describe_inputs(cli.locale())
+        } else {
+            provenance.describe(cli.locale())
+        };
+        sections.push(("Provenance", description));
+        report.provenance = Some(provenance);
+    }
+    // Describe where the diff came from, when it isn't the local repository
+    cli.status.phase("context");
+    if let Some(source) = source {
+        sections.push(("Diff Source", source));
+    }
+    if let Some(note) = merges_note {
+        sections.push(("Merge Commits", note));
+    }
+    if let Some(omitted) = omitted_files_section(&cli, &original_diff) {
+        sections.push(("Omitted Files", omitted));
+    }
+    if let Some(pr) = &pull_request {
+        sections.extend(pr.sections());
+    }
+    if !sampled.is_empty() {
+        sections.push(("Representative Sample", sample_note(&sampled)));
+    }
+    if let Some(markers) = markers_section(&cli, &diff_output)? {
+        sections.push(("New TODO Markers", markers));
+    }
+    let answers = match (&cli.previous_review, &cli.answer_file) {
+        (Some(previous_review), Some(answer_file)) => {
+            Some(answers_section(previous_review, answer_file)?)
+        }
+        _ => None,
+    };
+
+    // The optional sections share what's left once the diff and the sections above are placed.
+    // A diff split into parts is sized around the sections instead, so only the deadline limits them.
+    let remaining = (size_tier != Some(SizeTier::Large)).then(|| {
+        let mut required = sections.clone();
+        required.extend(answers.iter().map(|answers| ("Answers to Previous Questions", answers.clone())));
+        let budget = cli.diff_budget(&required);
+        budget.remaining().saturating_sub(cli.token_estimator().estimate_tokens(&diff_output))
+    });
+    let estimator = cli.token_estimator();
+    let mut arbiter = ContextArbiter::new(remaining, &cli.deadline, &estimator);
+    let mut optional = Vec::new();
+    let mut failed: Option<ReviewError> = None;
+    for feature in cli.context_features()? {
+        let latency = match feature.name {
+            "change-summary" => GIT_STEP_ESTIMATE,
+            "previous-review" => RANGE_DIFF_ESTIMATE,
+            "linked-issues" => ISSUE_FETCH_TIMEOUT,
+            "lint" if !cli.lint_commands().is_empty() => LINT_ESTIMATE,
+            _ => Duration::ZERO,
+        };
+        let context = ContextInputs {
+            vcs,
+            diff_output: &diff_output,
+            pull_request: pull_request.as_ref(),
+            local,
+            remaining: arbiter.remaining(),
+        };
+        let section = arbiter.offer(feature, latency, || {
+            context_feature_section(&cli, feature, &context).unwrap_or_else(|e| {
+                failed = Some(e);
+                None
+            })
+        });
+        if let Some(e) = failed.take() {
+            return Err(e);
+        }
+        if let Some(section) = section {
+            optional.push((feature, section));
+        }
+    }
+    report.context_features = arbiter.into_decisions();
+    for feature in CONTEXT_FEATURES {
+        if let Some(index) = optional.iter().position(|(included, _)| included == feature) {
+            sections.push((feature.heading, optional.remove(index).1));
+        }
+    }
+    if let Some(answers) = answers {
+        sections.push(("Answers to Previous Questions", answers));
+    }
+    if let Some(partial) = partial_context_section(&cli) {
+        sections.push(("Partial Context", partial));
+    }
+
+    if size_tier == Some(SizeTier::Large) || cli.split_per_file {
+        write_diff_parts(&cli, &diff_output, &sections, report, started)?;
+        return Ok(cli.exit_status());
+    }
+    let parts = try_prompt_parts(&cli, &diff_output, &sections)?;
+    write_prompt(&cli, &parts, report, started)?;
+    Ok(cli.exit_status())
+}
+
+/// What the optional context sections are built from
+struct ContextInputs<'a> {
+    vcs: Vcs,
+    diff_output: &'a str,
+    pull_request: Option<&'a PullRequestContext>,
+    /// Whether the diff is from the local repository
+    local: bool,
+    /// The tokens left for the section, for one that fills what it's given; `None` when only the
+    /// deadline limits it
+    remaining: Option<usize>,
+}
+
+/// The section for an optional context `feature`, if it's turned on and has something to say
+fn context_feature_section(
+    cli: &Cli,
+    feature: ContextFeature,
+    context: &ContextInputs,
+) -> Result<Option<String>, ReviewError> {
+    Ok(match feature.name {
+        "change-summary" if cli.diff_stat_as_table => stat_section(cli, context.vcs, !context.local),
+        "packages" if context.local => cli.workspace.as_ref().and_then(|workspace| {
+            packages_section(
+                workspace,
+                &parse_diff(context.diff_output).into_iter().map(|file| file.path).collect::<Vec<_>>(),
+            )
+        }),
+        "previous-review" if !cli.full_rereview => context.pull_request.and_then(rebase_section),
+        "linked-issues" if !cli.no_issue_context && context.vcs == Vcs::Git && context.local => linked_issues(cli),
+        "ci-logs" if !cli.ci_log.is_empty() => ci_log_section(&cli.ci_log, CI_LOG_MAX_CHARS, cli.deadline.remaining()),
+        "lint" if context.local => lint_context_section(cli, context.diff_output),
+        "related-tests" => related_tests_section(cli, context.diff_output),
+        "contracts" => contracts_section(cli, context.diff_output),
+        "infrastructure" => infrastructure_context_section(cli, context.diff_output),
+        "migrations" => migrations_context_section(cli, context.diff_output),
+        "naming" if cli.check_naming => naming_candidates_section(context.diff_output, context.local),
+        "history" => return history_section(cli, context.diff_output),
+        "full-files" if cli.full_files && context.local => full_files_context_section(cli, context.diff_output, context.remaining),
+        _ => None,
+    })
+}
+
+/// What --lint-context and --auto-lint's linters report on the files in `diff_output`. A linter
+/// that can't be run, fails or runs out of time is left out with a warning.
+fn lint_context_section(cli: &Cli, diff_output: &str) -> Option<String> {
+    let commands = cli.lint_commands();
+    if commands.is_empty() {
+        if cli.auto_lint {
+            info!("--auto-lint: no linter known for the repository's languages");
+        }
+        return None;
+    }
+    let root = env::current_dir().unwrap_or_default();
+    let paths: Vec<String> = parse_diff(diff_output).into_iter().map(|file| file.path).collect();
+    let mut diagnostics = Vec::new();
+    for command in &commands {
+        let timeout = cli.deadline.remaining().map_or(LINT_TIMEOUT, |remaining| remaining.min(LINT_TIMEOUT));
+        match lint(command, timeout, &root) {
+            Ok(found) => {
+                let found = on_changed_files(found, &paths);
+                info!("`{}` reported {} problems in the changed files", command, found.len());
+                diagnostics.extend(found);
+            }
+            Err(e) => warn!("Leaving out the linter `{}`: {}", command, e),
+        }
+    }
+    lint_section(&on_changed_files(diagnostics, &paths), LINT_MAX_DIAGNOSTICS)
+}
+
+/// With --author or --since, the commits they pick out combined into one diff, and the note
+/// describing them for the prompt. Fails with `ReviewError::NoChanges`, after saying so, if they
+/// pick out none.
+fn select_commits(cli: &Cli, vcs: Vcs, started: Instant) -> Result<Option<(CombinedCommits, String)>, ReviewError> {
+    let filter = CommitFilter { author: cli.author.clone(), since: cli.since.clone() };
+    if filter.is_empty() {
+        return Ok(None);
+    }
+    if vcs != Vcs::Git {
+        return Err(ReviewError::Usage("--author and --since are only supported in git repositories".to_string()));
+    }
+    let commits = selected_commits(&filter, &cli.remaining_args)?;
+    if commits.is_empty() {
+        println!("No changes found to review: no commits {}.", filter);
+        finish(cli, 0, 0, started);
+        return Err(ReviewError::NoChanges);
+    }
+    info!("Reviewing {} commits {}", commits.len(), filter);
+    let combined = combine_commits(&commits)?;
+
+    // The lines are compared with the end of the revisions looked through
+    let tip = cli
+        .remaining_args
+        .last()
+        .and_then(|revision| revision.rsplit("..").next())
+        .filter(|tip| !tip.is_empty() && !tip.starts_with('-'))
+        .unwrap_or("HEAD");
+    let overlaps = later_overlaps(&commits, tip).unwrap_or_else(|e| {
+        warn!("Could not check for later changes to the selected lines: {}", e);
+        Vec::new()
+    });
+    Ok(Some((combined, selection_note(&filter, &commits, &overlaps))))
+}
+
+/// What to review in place of a range of commits with merge commits in it, as `adapt_to_merges`
+/// decides; `None` without merges, or when the diff isn't of a range of commits. A single
+/// revision is compared with the working tree, whose changes can't be combined with the commits',
+/// so it only counts as the range up to HEAD with no uncommitted changes.
+fn adapt_merges(cli: &Cli) -> Option<MergeAdaptation> {
+    let range = commit_range(cli)?;
+    let against_worktree = matches!(diff_revisions(cli).as_slice(), [revision] if !revision.contains(".."));
+    if against_worktree
+        && git_output(&["status", "--porcelain", "--untracked-files=no"]).is_ok_and(|status| !status.trim().is_empty())
+    {
+        if let Ok(merges) = merges_in(&range)
+            && !merges.is_empty()
+        {
+            warn!(
+                "The diff includes what {} merge {} brought in, as it's of the working tree; review a range of \
+                 commits, such as {}, to leave that out",
+                merges.len(),
+                if merges.len() == 1 { "commit" } else { "commits" },
+                range
+            );
+        }
+        return None;
+    }
+    adapt_to_merges(&range, cli.include_merges).unwrap_or_else(|e| {
+        warn!("Could not check the range for merge commits: {}", e);
+        None
+    })
+}
+
+/// How to handle the diff, going by its size unless the command line forced a tier
+fn size_tier(cli: &Cli, diff_output: &str) -> SizeTier {
+    if let Some(tier) = cli.forced_tier() {
+        return tier;
+    }
+    let budget = cli.diff_budget(&[]);
+    if cli.chunk_mode() == ChunkMode::Auto && !budget.fits(cli.token_estimator().estimate_tokens(diff_output)) {
+        info!("The diff doesn't fit in {} tokens, so it's split into chunks of whole files", budget.remaining());
+        return SizeTier::Large;
+    }
+    let tier = choose_tier(diff_output, &budget, &cli.tier_thresholds(), &cli.token_estimator());
+    match tier {
+        SizeTier::Large => info!(
+            "The diff doesn't fit in {} tokens even without context lines, so it's split into parts reviewed one by one",
+            budget.remaining()
+        ),
+        tier => debug!("The diff is {}", tier),
+    }
+    tier
+}
+
+/// Split the diff into parts that each fit in the budget, and write a prompt for each: on standard
+/// output one after another, or with --output-file to files numbered after it, e.g.
+/// `review.part-1.md`.
+fn write_diff_parts(
+    cli: &Cli,
+    diff_output: &str,
+    sections: &[(&str, String)],
+    report: BudgetReport,
+    started: Instant,
+) -> Result<(), ReviewError> {
+    // Every part repeats the sections, and says which files it covers
+    let part_section = |parts: usize, part: usize, files: &[FileDiff]| {
+        let files: Vec<String> = files.iter().map(describe_file).collect();
+        let text = if cli.split_per_file {
+            format!(
+                "This change is reviewed one file at a time, in {} parts. This is part {}, covering {}; \
+                 review only this file.",
+                parts,
+                part,
+                files.join(", ")
+            )
+        } else if cli.chunk_mode() != ChunkMode::None {
+            format!(
+                "This change is reviewed in {} chunks of whole files. This is chunk {}, covering {}; \
+                 review only these files.",
+                parts,
+                part,
+                files.join(", ")
+            )
+        } else {
+            format!(
+                "This change is too large to review at once, so it has been split into {} parts. \
+                 This is part {}, covering {}; review only these files.",
+                parts,
+                part,
+                files.join(", ")
+            )
+        };
+        ("Part of a Larger Change", text)
+    };
+    let all_files = parse_diff(diff_output);
+    let mut largest_sections = sections.to_vec();
+    largest_sections.push(part_section(all_files.len(), all_files.len(), &all_files));
+    let budget = cli.diff_budget(&largest_sections);
+    check_prompt_overhead(cli, &budget)?;
+    let diff_parts = if cli.split_per_file {
+        split_per_file(cli, diff_output, &budget)?
+    } else if cli.chunk_mode() != ChunkMode::None {
+        chunk_per_file(cli, diff_output, &budget)?
+    } else {
+        split_into_parts(diff_output, cli.unified_context, &budget, &cli.token_estimator())?
+    };
+    info!("Split the diff into {} parts", diff_parts.len());
+    if cli.copy && diff_parts.len() > 1 {
+        return Err(ReviewError::Usage(format!(
+            "the diff is split into {} parts, which the clipboard can't hold at once; use --output-file instead",
+            diff_parts.len()
+        )));
+    }
+    cli.status.update(|status| status.chunks_total = diff_parts.len());
+    // Someone at the terminal can skip a chunk that's taking too long, when there's no other use
+    // for what they type
+    let keys = (cli.send_to.is_some()
+        && diff_parts.len() > 1
+        && !cli.require_approval
+        && !cli.pre_commit
+        && io::stdin().is_terminal()
+        && io::stderr().is_terminal())
+    .then(KeyWatcher::stdin);
+
+    let (mut total_files, mut total_tokens) = (0, 0);
+    for (i, diff_part) in diff_parts.iter().enumerate() {
+        let files = parse_diff(diff_part);
+        let paths: Vec<String> = files.iter().map(|file| file.path.clone()).collect();
+        let mut part_sections = sections.to_vec();
+        part_sections.push(part_section(diff_parts.len(), i + 1, &files));
+        let mut part_report = report.clone();
+        part_report.files.retain(|file| paths.contains(&file.path));
+        if let Some(attention) = &mut part_report.attention {
+            attention.retain(|file| paths.contains(&file.path));
+        }
+        let output_file = cli.output_file.as_deref().map(|path| part_path(path, i + 1));
+
+        let parts = try_prompt_parts(cli, diff_part, &part_sections)?;
+        // Printed chunks are marked, so they can be told apart and fed to the LLM one by one
+        if output_file.is_none() && cli.chunk_mode() != ChunkMode::None {
+            println!("--- chunk {}/{} ---", i + 1, diff_parts.len());
+        }
+        let skippable = keys.as_ref().map(|keys| SkippableChunk { keys, part: i + 1, parts: diff_parts.len(), paths: &paths });
+        let (files, tokens) = emit_prompt(cli, &parts, part_report, output_file.as_deref(), skippable.as_ref())?;
+        cli.status.update(|status| {
+            status.chunks_done += 1;
+            status.prompt_tokens += tokens;
+        });
+        total_files += files;
+        total_tokens += tokens;
+    }
+    finish(cli, total_files, total_tokens, started);
+    Ok(())
+}
+
+/// With --split-per-file, a part for each file, or more than one for a file too large for the
+/// budget even with its context reduced. Binary files are left out: their diff has nothing to
+/// review.
+fn split_per_file(cli: &Cli, diff_output: &str, budget: &Budget) -> Result<Vec<String>, ReviewError> {
+    let mut parts = Vec::new();
+    for (path, file_diff) in split_diff_by_file(diff_output) {
+        if parse_diff(&file_diff).iter().any(FileDiff::is_binary) {
+            info!("Leaving out {}: it's a binary file", path);
+            continue;
+        }
+        parts.extend(split_into_parts(&file_diff, cli.unified_context, budget, &cli.token_estimator())?);
+    }
+    Ok(parts)
+}
+
+/// With --chunk-mode, the files packed into chunks that fit the budget, each with all its
+/// context. A file too large for a chunk of its own is split as --chunked would. Binary files are
+/// left out, as with --split-per-file.
+fn chunk_per_file(cli: &Cli, diff_output: &str, budget: &Budget) -> Result<Vec<String>, ReviewError> {
+    let estimator = cli.token_estimator();
+    let mut files = split_diff_by_file(diff_output);
+    files.retain(|(path, file_diff)| {
+        let binary = parse_diff(file_diff).iter().any(FileDiff::is_binary);
+        if binary {
+            info!("Leaving out {}: it's a binary file", path);
+        }
+        !binary
+    });
+    let mut chunks = Vec::new();
+    for chunk in group_into_chunks(&files, budget, &estimator) {
+        if budget.fits(estimator.estimate_tokens(&chunk)) {
+            chunks.push(chunk);
+        } else {
+            chunks.extend(split_into_parts(&chunk, cli.unified_context, budget, &estimator)?);
+        }
+    }
+    Ok(chunks)
+}
+
+/// A file's path, with the path a renamed or copied file came from, or the modes of a file whose
+/// mode changed
+fn describe_file(file: &FileDiff) -> String {
+    match (file.kind(), file.old_path(), file.mode_change()) {
+        (FileKind::Renamed, Some(old), _) => format!("{} (renamed from {})", file.path, old),
+        (FileKind::Copied, Some(old), _) => format!("{} (copied from {})", file.path, old),
+        (_, _, Some((old, new))) => format!("{} (mode changed from {} to {})", file.path, old, new),
+        _ => file.path.clone(),
+    }
+}
+
+/// `review.md` numbered as `review.part-N.md`
+fn part_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().map_or_else(|| "prompt".into(), |stem| stem.to_string_lossy().to_string());
+    let name = match path.extension() {
+        Some(extension) => format!("{}.part-{}.{}", stem, n, extension.to_string_lossy()),
+        None => format!("{}.part-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+/// The width text printed on standard output is wrapped to: --wrap or the terminal's, unless
+/// --no-wrap was given
+fn wrap_width(cli: &Cli) -> Option<usize> {
+    if cli.no_wrap {
+        None
+    } else if cli.stable_prompt {
+        // The terminal's width would make the same prompt differ from one window to the next
+        cli.wrap
+    } else {
+        cli.wrap.or_else(terminal_width)
+    }
+}
+
+/// Print `text` on standard output, wrapped to --wrap or the terminal's width. Files are never
+/// wrapped this way, so what's copied from them is exactly what was generated.
+fn print_wrapped(cli: &Cli, text: &str) {
+    match wrap_width(cli) {
+        Some(width) => println!("{}", wrap_prose_sections(text, width)),
+        None => println!("{}", text),
+    }
+}
+
+/// Print the prompt, or write it to --output-file along with any requested budget report.
+fn write_prompt(cli: &Cli, parts: &PromptParts, report: BudgetReport, started: Instant) -> Result<(), ReviewError> {
+    cli.status.update(|status| status.chunks_total = 1);
+    let (files, tokens) = emit_prompt(cli, parts, report, cli.output_file.as_deref(), None)?;
+    cli.status.update(|status| {
+        status.chunks_done = 1;
+        status.prompt_tokens = tokens;
+    });
+    finish(cli, files, tokens, started);
+    Ok(())
+}
+
+/// Print the prompt, or write it to `output_file` along with any requested budget report.
+/// Returns the number of files and the estimated tokens in the prompt.
+fn emit_prompt(
+    cli: &Cli,
+    parts: &PromptParts,
+    mut report: BudgetReport,
+    output_file: Option<&Path>,
+    skippable: Option<&SkippableChunk>,
+) -> Result<(usize, usize), ReviewError> {
+    cli.status.phase("prompt");
+    let prompt = render_prompt(cli, parts);
+    report.review_target = Some(parts.review_target);
+    report.deadline = cli.deadline.report();
+    let output = if cli.debug_prompt {
+        let sections: Vec<(&str, String)> = parts
+            .named()
+            .iter()
+            .map(|(name, text)| (*name, text.to_string()))
+            .collect();
+        annotate_sections(&sections, &cli.token_estimator())
+    } else {
+        prompt.clone()
+    };
+    let files = if cli.raw_code.is_some() {
+        1
+    } else {
+        parse_diff(&parts.diff).len()
+    };
+
+    check_hard_cap(&prompt, cli.hard_max_chars, &cli.token_estimator(), cli.effective_budget())?;
+    let tokens = check_prompt_tokens(&parts.system_prompt, &prompt, &cli.token_estimator(), cli.effective_budget())?;
+    if cli.require_approval {
+        wait_for_approval(&prompt, &cli.token_estimator())?;
+    }
+    if cli.send_to.is_none() {
+        record_history(cli, parts, None);
+    }
+
+    if let Some(provider) = &cli.send_to {
+        check_before_sending(cli, parts, &prompt)?;
+        check_monthly_budget(cli)?;
+        let model = sent_model(cli, provider);
+        check_max_cost(cli, provider, &model, tokens)?;
+        confirm_send(cli, provider, &model, tokens)?;
+        cli.status.phase("review");
+        let Some(review) = send_prompt(cli, provider, &model, &prompt, parts, skippable)? else {
+            // The prompt went out, and may be charged for, but nothing came back to record
+            record_usage(cli, provider, &model, tokens, 0);
+            if let Some(chunk) = skippable {
+                skip_chunk(cli, chunk, output_file)?;
+            }
+            return Ok((files, tokens));
+        };
+        record_usage(cli, provider, &model, tokens, cli.token_estimator().estimate_tokens(&review));
+        let review = retry_invalid_json(cli, provider, &model, &prompt, parts, review)?;
+        let review = check_format(cli, provider, &model, &prompt, parts, review)?;
+        let (model, review, escalation) = review_with_fallback(cli, provider, model, &prompt, parts, tokens, review)?;
+        // What the local checks of CI and build configuration and of migrations found goes with
+        // the model's findings
+        let mut local = local_infra_findings(cli, &parts.diff);
+        local.extend(local_migration_findings(cli, &parts.diff));
+        cli.status.update(|status| {
+            status.response_tokens += cli.token_estimator().estimate_tokens(&review);
+            status.findings.extend(Transcript::new(None, &review).findings.unwrap_or_default());
+            status.findings.extend(local.iter().cloned());
+        });
+        let attention = cli
+            .attention_report
+            .then(|| assess(&parse_diff(&parts.diff), &cli.attention_rules, &parse_confidence(&review)));
+        let mut transcript = Transcript {
+            attention: attention.clone(),
+            escalation: escalation.clone(),
+            ..Transcript::new(Some(model.clone()), &review)
+        };
+        if let Some(findings) = &mut transcript.findings {
+            findings.extend(local.iter().cloned());
+        }
+        record_history(cli, parts, Some(&transcript));
+        if let Some(path) = &cli.save_transcript {
+            transcript.save(path)?;
+            info!("Saved the transcript to {}", path.display());
+        }
+        let review = if cli.pre_commit {
+            let findings = review_findings(cli, &review, &local, &parse_diff(&parts.diff))?;
+            let fail_on: Severity = cli.fail_on.parse().unwrap_or(Severity::Error);
+            if findings.iter().any(|finding| finding.severity <= fail_on) {
+                cli.issues_found.set(true);
+            }
+            terse_findings(&findings)
+        } else if cli.output_format() == Some(OutputFormat::Json) {
+            // The JSON printed is only the findings; the attention is in the transcript
+            json_review(cli, &review, &local, &parse_diff(&parts.diff))?
+        } else {
+            let mut review = review.trim_end().to_string();
+            if !local.is_empty() && cli.output_format().is_none_or(|format| format == OutputFormat::Markdown) {
+                review = format!("{}\n\n{}", review, local_findings_section(&local));
+            }
+            if let Some(attention) = &attention {
+                review = format!("{}\n\n## Human Attention\n\n{}", review, attention_section(attention));
+            }
+            if let Some(escalation) = &escalation
+                && cli.output_format().is_none_or(|format| format == OutputFormat::Markdown)
+            {
+                review = format!("{}\n\n{}", review, escalation.note(&model, cli.locale()));
+            }
+            review
+        };
+        if cli.fail_on_issues && has_review_issues(&review, &cli.output_format().unwrap_or(OutputFormat::Markdown)) {
+            cli.issues_found.set(true);
+        }
+        print_review(cli, &review, output_file)?;
+        return Ok((files, tokens));
+    }
+    let summary = format!("{} bytes, about {} tokens, {} file{}", output.len(), tokens, files, if files == 1 { "" } else { "s" });
+    if cli.copy {
+        copy(&output)?;
+        println!("Copied the prompt to the clipboard: {}", summary);
+    }
+    let Some(output_file) = output_file else {
+        if !cli.copy {
+            print_wrapped(cli, &output);
+        }
+        return Ok((files, tokens));
+    };
+
+    write_atomic(output_file, format!("{}\n", output))?;
+    println!("Wrote the prompt to {}: {}", output_file.display(), summary);
+
+    if cli.token_budget_report {
+        let mut report_path = output_file.as_os_str().to_os_string();
+        report_path.push(".budget.json");
+        let report_path = PathBuf::from(report_path);
+
+        report.record_prompt(&prompt, &parts.named(), &cli.token_estimator());
+        write_budget_report(&report, &report_path)?;
+        info!("Wrote token budget report to {}", report_path.display());
+    }
+    Ok((files, tokens))
+}
+
+/// Mark a skipped chunk in the output where its review would have gone, and offer to add its files
+/// to the repository's exclude file so they're never sent again
+fn skip_chunk(cli: &Cli, chunk: &SkippableChunk, output_file: Option<&Path>) -> Result<(), ReviewError> {
+    cli.status.update(|status| status.chunks_skipped += 1);
+    print_review(cli, &skipped_note(chunk.part, chunk.parts, chunk.paths, cli.locale()), output_file)?;
+    let Some(root) = repo_root() else {
+        return Ok(());
+    };
+    let question = format!("Leave {} out of future reviews, in {}? [y/N]", chunk.paths.join(", "), EXCLUDE_FILE);
+    if chunk.keys.ask(&question).is_some_and(|answer| answer.trim().eq_ignore_ascii_case("y")) {
+        let added = add_excludes(&root, chunk.paths)?;
+        if !added.is_empty() {
+            info!("Added {} to {}", added.join(", "), root.join(EXCLUDE_FILE).display());
+        }
+    }
+    Ok(())
+}
+
+/// A review written for --output-format json, checked and printed in a fixed layout with the
+/// `local` checks' findings added, repeated findings merged, those under --min-confidence left
+/// out, and those over the limits on findings marked lower priority. If it isn't the JSON asked
+/// for, the review is printed on standard error as it came, and the findings that can be read out
+/// of its prose about `files` take its place, with a warning. With --strict-json, or when none
+/// can, that's an error.
+fn json_review(cli: &Cli, review: &str, local: &[Finding], files: &[FileDiff]) -> Result<String, ReviewError> {
+    let findings = review_findings(cli, review, local, files)?;
+    Ok(render_json(&findings.iter().map(JsonFinding::from).collect::<Vec<_>>()))
+}
+
+/// `json_review`'s findings, before they're rendered
+fn review_findings(cli: &Cli, review: &str, local: &[Finding], files: &[FileDiff]) -> Result<Vec<Finding>, ReviewError> {
+    let findings = match parse_json_findings(review) {
+        Ok(findings) => findings.into_iter().map(Finding::from).collect(),
+        Err(e) => {
+            eprintln!("{}", review.trim_end());
+            let paths: Vec<String> = files.iter().map(|file| file.path.clone()).collect();
+            let extracted = if cli.strict_json { Vec::new() } else { extract_findings(review, &paths) };
+            if extracted.is_empty() {
+                return Err(ReviewError::InvalidJsonReview(e));
+            }
+            // On standard error, like the review, so what CI reads from standard output stays JSON
+            eprintln!("{}", salvage_banner(&e, extracted.len()));
+            extracted
+        }
+    };
+    let findings = merge_findings(findings.into_iter().chain(local.iter().cloned()).collect());
+    Ok(cap_findings(confident_findings(cli, findings), &cli.finding_limits()))
+}
+
+/// The findings of a --pre-commit review, one per line as `path:line: severity: comment`
+fn terse_findings(findings: &[Finding]) -> String {
+    if findings.is_empty() {
+        return "No findings in the staged changes.".to_string();
+    }
+    findings
+        .iter()
+        .map(|finding| {
+            let location = match finding.line {
+                Some(line) => format!("{}:{}", finding.path, line),
+                None => finding.path.clone(),
+            };
+            format!("{}: {}: {}", location, finding.severity, finding.comment.split_whitespace().collect::<Vec<_>>().join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The findings, without those the model gave less than --min-confidence
+fn confident_findings(cli: &Cli, findings: Vec<Finding>) -> Vec<Finding> {
+    match cli.min_confidence {
+        Some(min) => filter_by_confidence(findings, min as f64),
+        None => findings,
+    }
+}
+
+/// Print the review, or write it to `output_file`, and post it with --post
+fn print_review(cli: &Cli, review: &str, output_file: Option<&Path>) -> Result<(), ReviewError> {
+    if cli.copy {
+        copy(review.trim_end())?;
+        println!("Copied the review to the clipboard: {} bytes", review.trim_end().len());
+    }
+    match output_file {
+        Some(output_file) => {
+            write_atomic(output_file, format!("{}\n", review.trim_end()))?;
+            println!("Wrote the review to {}: {} bytes", output_file.display(), review.trim_end().len() + 1);
+        }
+        None if cli.copy => {}
+        // Only Markdown is wrapped; the other formats are read by programs or laid out by the
+        // tool that renders them
+        None => match wrap_width(cli).filter(|_| cli.output_format().is_none_or(|format| format == OutputFormat::Markdown)) {
+            Some(width) => println!("{}", wrap_markdown(review.trim_end(), width)),
+            None => println!("{}", review.trim_end()),
+        },
+    }
+    if let Some((project, number, token)) = &cli.post_to {
+        post_review(project, *number, token, review.trim_end(), cli.locale())?;
+    }
+    Ok(())
+}
+
+/// With --replay, render the saved transcript in --output-format and print or post it, with the
+/// findings over the limits on findings set apart. A transcript without findings is shown as the
+/// model wrote it.
+fn replay(cli: &Cli, path: &Path) -> Result<(), ReviewError> {
+    let mut transcript = Transcript::load(path)?;
+    transcript.findings =
+        transcript.findings.map(|findings| cap_findings(confident_findings(cli, findings), &cli.finding_limits()));
+    let format = cli.output_format().unwrap_or(OutputFormat::Markdown);
+    let review = transcript.render(format, cli.locale()).unwrap_or_else(|| {
+        warn!(
+            "{} has no structured findings to render as {}; showing the review as it was written",
+            path.display(),
+            cli.output_format.as_deref().unwrap_or("markdown")
+        );
+        transcript.review.clone()
+    });
+    let review = match (&transcript.attention, format) {
+        (Some(attention), OutputFormat::Markdown) => {
+            format!("{}\n\n## Human Attention\n\n{}", review.trim_end(), attention_section(attention))
+        }
+        _ => review,
+    };
+    print_review(cli, &review, cli.output_file.as_deref())
+}
+
+/// The repository and number of the pull request --post comments on, and the token to comment with
+fn post_target(pull_request: Option<&PullRequestContext>) -> Result<(String, u64, String), ReviewError> {
+    let pr = pull_request.ok_or_else(|| {
+        ReviewError::Config("--post needs --github-actions-pr-context, in a pull_request workflow".to_string())
+    })?;
+    let (Some(project), Some(number)) = (&pr.repository, pr.number) else {
+        return Err(ReviewError::GitHub("--post: the event payload has no pull request number or repository".to_string()));
+    };
+    let token = forge_token(Forge::GitHub, "github.com")
+        .map_err(|e| ReviewError::GitHub(format!("--post needs GITHUB_TOKEN to comment on the pull request: {}", e)))?;
+    Ok((project.clone(), number, token.token))
+}
+
+/// Post `review` on pull request `number` in `project`, as one comment or a series of them,
+/// replacing the comments an earlier run posted
+fn post_review(project: &str, number: u64, token: &str, review: &str, locale: Locale) -> Result<(), ReviewError> {
+    let api = actions_api_url();
+    let comments = split_review(review, GITHUB_COMMENT_LIMIT, locale);
+    let existing = issue_comments(&api, project, number, token, ISSUE_REQUEST_TIMEOUT)?;
+    for action in plan_comments(&existing, &comments) {
+        match action {
+            CommentAction::Create { body } => create_issue_comment(&api, project, number, token, &body, ISSUE_REQUEST_TIMEOUT)?,
+            CommentAction::Update { id, body } => update_issue_comment(&api, project, id, token, &body, ISSUE_REQUEST_TIMEOUT)?,
+            CommentAction::Delete { id } => delete_issue_comment(&api, project, id, token, ISSUE_REQUEST_TIMEOUT)?,
+        }
+    }
+    match comments.len() {
+        1 => info!("Posted the review on {}#{}", project, number),
+        n => info!("Posted the review on {}#{} in {} comments", project, number, n),
+    }
+    Ok(())
+}
+
+/// The language presets in effect, joined with `+`, or `none`
+fn preset_name(cli: &Cli) -> String {
+    match cli.repo_defaults.presets.as_slice() {
+        [] => "none".to_string(),
+        presets => presets.iter().map(|preset| preset.language).collect::<Vec<_>>().join("+"),
+    }
+}
+
+/// What the review is of, for the history: the pull request's URL, or the branch and commit
+/// checked out
+fn reviewed_ref(cli: &Cli) -> Option<String> {
+    if let Some(url) = cli.remaining_args.iter().find(|arg| parse_github_url(arg).is_some()) {
+        return Some(url.clone());
+    }
+    let commit = git_output(&["rev-parse", "--short", "HEAD"]).ok()?;
+    let branch = current_branch(|var| env::var(var).ok()).unwrap_or_else(|| "HEAD".to_string());
+    Some(format!("{} @ {}", branch, commit.trim()))
+}
+
+/// Record the review in --history-dir, with the `transcript` of what --send got saved in its
+/// `reviews` directory
+fn record_history(cli: &Cli, parts: &PromptParts, transcript: Option<&Transcript>) {
+    let Some(dir) = &cli.history_dir else {
+        return;
+    };
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let mut entry = HistoryEntry {
+        timestamp: rfc3339_utc(seconds),
+        diff_sha256: Some(sha256_hex(&parts.diff)),
+        prompt_version: try_system_prompt(cli).ok().map(|prompt| sha256_hex(&prompt)[..12].to_string()),
+        model: Some(cli.model_name().unwrap_or_else(|| "none".to_string())),
+        preset: Some(preset_name(cli)),
+        depth: Some(cli.review_length.clone()),
+        git_ref: reviewed_ref(cli),
+        review_file: None,
+        feedback: None,
+    };
+    let recorded = lock(&dir.join(HISTORY_FILE)).and_then(|_lock| {
+        if let Some(transcript) = transcript {
+            let reviews = dir.join(REVIEWS_DIR);
+            fs::create_dir_all(&reviews).map_err(|e| ReviewError::Write(reviews.clone(), e))?;
+            let id = run_id(&entry);
+            let mut file = format!("{}.json", id);
+            for n in 2.. {
+                if !reviews.join(&file).exists() {
+                    break;
+                }
+                file = format!("{}-{}.json", id, n);
+            }
+            transcript.save(&reviews.join(&file))?;
+            entry.review_file = Some(file);
+        }
+        let mut history = History::load(dir)?;
+        let dropped = history.record(entry);
+        history.save(dir)?;
+        for file in dropped.iter().filter_map(|entry| entry.review_file.as_ref()) {
+            let _ = fs::remove_file(dir.join(REVIEWS_DIR).join(file));
+        }
+        Ok(())
+    });
+    if let Err(e) = recorded {
+        warn!("Could not record the review in {}: {}", dir.display(), e);
+    }
+}
+
+/// Rate the most recent review in --history-dir, or report the ratings
+fn feedback(cli: &Cli, action: &FeedbackAction) -> Result<String, ReviewError> {
+    let Some(dir) = &cli.history_dir else {
+        return Err(ReviewError::Usage(
+            "feedback needs --history-dir DIR, the directory the reviews were recorded in".to_string(),
+        ));
+    };
+    let (rating, note) = match action {
+        FeedbackAction::Good { note } => (Rating::Good, note),
+        FeedbackAction::Bad { note } => (Rating::Bad, note),
+        FeedbackAction::Report => return Ok(feedback_report(&History::load(dir)?.entries)),
+    };
+    // Another run may be recording a review meanwhile
+    let _lock = lock(&dir.join(HISTORY_FILE))?;
+    let mut history = History::load(dir)?;
+
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let feedback = Feedback { rating, note: note.clone(), timestamp: rfc3339_utc(seconds) };
+    let entry = history
+        .rate_latest(feedback)
+        .ok_or_else(|| ReviewError::NoHistoryEntries(dir.clone()))?;
+    let message = format!("Rated the review from {} as {}", entry.timestamp, rating);
+    history.save(dir)?;
+    Ok(message)
+}
+
+/// Write the dashboard of the reviews in --history-dir
+fn report(cli: &Cli, action: &ReportAction) -> Result<String, ReviewError> {
+    let Some(dir) = &cli.history_dir else {
+        return Err(ReviewError::Usage(
+            "report needs --history-dir DIR, the directory the reviews were recorded in".to_string(),
+        ));
+    };
+    let ReportAction::Generate { output_dir, page_size, title } = action;
+    if *page_size == 0 {
+        return Err(ReviewError::Usage("--page-size must be at least 1".to_string()));
+    }
+    let runs = load_runs(dir)?;
+    write_dashboard(output_dir, &render_dashboard(title, &runs, *page_size))?;
+    Ok(format!(
+        "Wrote the dashboard of {} {} to {}",
+        runs.len(),
+        if runs.len() == 1 { "review" } else { "reviews" },
+        output_dir.join("index.html").display()
+    ))
+}
+
+/// With --check-response, check the saved response for the canary of the diff as the prompt has it,
+/// warning if it's missing, and print the response without it unless --strict-canary will write
+/// the prompt again. Returns whether the canary was found.
+fn check_response(cli: &Cli, path: &Path, diff_output: &str) -> Result<bool, ReviewError> {
+    let response = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+    let check = check_canary(&response, &canary_for(&plugin_transform_diff(cli, diff_output.to_string())));
+    if check.is_found() {
+        info!("{}: {}", path.display(), check);
+    } else {
+        warn!("{}: {}", path.display(), check);
+    }
+    if check.is_found() || !cli.strict_canary {
+        print!("{}", strip_canary(&response));
+    }
+    Ok(check.is_found())
+}
+
+/// Raise the severity of findings below their paths' floors in the severity policy, logging each
+pub fn enforce_severity_policy(cli: &Cli, findings: &mut [Finding]) {
+    if cli.severity_policy.enforce(findings) == 0 {
+        return;
+    }
+    for finding in findings.iter() {
+        if let Some(note) = &finding.policy_note {
+            info!("{}{}: {}", finding.path, finding.line.map(|line| format!(":{}", line)).unwrap_or_default(), note);
+        }
+    }
+}
+
+/// With --file-issues, open an issue for each follow-up finding in `path` that the tool hasn't
+/// filed before, or with --file-issues-dry-run print them. Returns the exit status: 1 if any
+/// couldn't be opened, after listing them.
+fn file_issues(cli: &Cli, path: &Path, diff_output: &str) -> Result<i32, ReviewError> {
+    let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+    let findings: Vec<Finding> = serde_json::from_str(&contents)
+        .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+    let below = cli.file_issues_below.as_deref().map(Severity::from_str).transpose().map_err(ReviewError::Config)?;
+    let mut findings = findings;
+    enforce_severity_policy(cli, &mut findings);
+    let findings = follow_ups(findings, below);
+    if findings.is_empty() {
+        println!("No follow-up findings to file.");
+        return Ok(0);
+    }
+
+    let remote = git_output(&["remote", "get-url", "origin"])
+        .ok()
+        .and_then(|url| parse_remote_url(&url))
+        .filter(|remote| remote.forge == Forge::GitHub)
+        .ok_or_else(|| ReviewError::GitHub("--file-issues needs a GitHub repository as `origin`".to_string()))?;
+    let code = ReviewedCode {
+        repository_url: format!("https://{}/{}", remote.host, remote.project),
+        sha: git_output(&["rev-parse", reviewed_revision(cli)])?.trim().to_string(),
+    };
+    let files = parse_diff(diff_output);
+    let drafts: Vec<DraftIssue> = findings
+        .iter()
+        .map(|finding| draft_issue(finding, &files, &code, &cli.file_issues_label))
+        .collect();
+
+    let api = api_base(&remote.host);
+    let token = forge_token(Forge::GitHub, &remote.host);
+    let existing = match open_issue_bodies(
+        &api,
+        &remote.project,
+        &cli.file_issues_label,
+        token.as_ref().ok().map(|token| token.token.as_str()),
+        ISSUE_REQUEST_TIMEOUT,
+    ) {
+        Ok(bodies) => bodies,
+        // Without the list, a dry run can still show what would be filed
+        Err(e) if cli.file_issues_dry_run => {
+            warn!("Could not check for issues filed before: {}", e);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    let total = drafts.len();
+    let drafts = unfiled(drafts, &existing);
+    if drafts.len() < total {
+        info!("{} of the {} findings were filed before", total - drafts.len(), total);
+    }
+
+    if cli.file_issues_dry_run {
+        for draft in &drafts {
+            println!("{}\n\n---\n", draft);
+        }
+        println!("Would open {} {}", drafts.len(), if drafts.len() == 1 { "issue" } else { "issues" });
+        return Ok(0);
+    }
+
+    let token = token
+        .map_err(|e| ReviewError::GitHub(format!("--file-issues needs GITHUB_TOKEN to open issues: {}", e)))?
+        .token;
+    let mut failed = Vec::new();
+    for draft in &drafts {
+        match create_issue(&api, &remote.project, &token, &draft.to_json(), ISSUE_REQUEST_TIMEOUT) {
+            Ok(url) => println!("Opened {}: {}", url, draft.title),
+            Err(e) => failed.push(format!("{} ({})", draft.title, e)),
+        }
+    }
+    if failed.is_empty() {
+        return Ok(0);
+    }
+    println!("Not filed:\n{}", failed.iter().map(|failure| format!("- {}", failure)).collect::<Vec<_>>().join("\n"));
+    Ok(1)
+}
+
+/// The commit the diff under review ends at, which --file-issues links to
+fn reviewed_revision(cli: &Cli) -> &str {
+    let revisions: Vec<&str> = cli
+        .remaining_args
+        .iter()
+        .map(String::as_str)
+        .take_while(|arg| *arg != "--")
+        .filter(|arg| !arg.starts_with('-') && !Path::new(arg).exists())
+        .collect();
+    let to = match revisions.as_slice() {
+        [range] => range.split_once("...").or_else(|| range.split_once("..")).map_or("", |(_, to)| to),
+        [_, to, ..] => to,
+        _ => "",
+    };
+    if to.is_empty() { "HEAD" } else { to }
+}
+
+// How much of the prompt --require-approval shows
+const APPROVAL_PREVIEW_CHARS: usize = 200;
+
+/// The summary --require-approval shows before asking to continue: the prompt's size, estimated
+/// tokens and the start of it.
+pub fn approval_preview(prompt: &str, estimator: &dyn TokenEstimator) -> String {
+    format!(
+        "Prompt: {} chars, ~{} tokens\n---\n{}\n---",
+        prompt.len(),
+        estimator.estimate_tokens(prompt),
+        truncate_graphemes(prompt, APPROVAL_PREVIEW_CHARS)
+    )
+}
+
+/// Show the preview and wait for Enter; Ctrl-C (or end of input) aborts. Without a terminal to
+/// ask on, carry on with a warning.
+fn wait_for_approval(prompt: &str, estimator: &dyn TokenEstimator) -> Result<(), ReviewError> {
+    if !io::stdin().is_terminal() {
+        warn!("--require-approval: not running interactively, continuing without approval");
+        return Ok(());
+    }
+
+    let mut stderr = io::stderr();
+    let _ = write!(
+        stderr,
+        "{}\nPress Enter to continue or Ctrl-C to abort: ",
+        approval_preview(prompt, estimator)
+    );
+    let _ = stderr.flush();
+
+    let mut answer = String::new();
+    if !matches!(io::stdin().lock().read_line(&mut answer), Ok(n) if n > 0) {
+        return Err(ReviewError::Aborted);
+    }
+    Ok(())
+}
+
+/// Report the end of a successful run with the --summary-line and the --notify notification, and
+/// save the --token-cache
+fn finish(cli: &Cli, files: usize, tokens: usize, started: Instant) {
+    cli.status.phase("done");
+    if let Err(e) = save_token_cache() {
+        warn!("Could not save the token cache: {}", e);
+    }
+    print_summary_line(cli, files, tokens, started);
+    let ready = if cli.send_to.is_some() { "Review ready" } else { "Prompt ready" };
+    notify::finished(true, &format!("{}: {} files, ~{} tokens", ready, files, tokens));
+}
+
+/// With --summary-line, print `llm_code_review: files=<N> tokens=<N> elapsed=<S>s` to stderr.
+/// `tokens` is the estimated size of the prompt. CI scripts parse this line, so new fields may be
+/// appended but existing ones must not change.
+fn print_summary_line(cli: &Cli, files: usize, tokens: usize, started: Instant) {
+    if cli.summary_line {
+        eprintln!(
+            "llm_code_review: files={} tokens={} elapsed={:.1}s",
+            files,
+            tokens,
+            started.elapsed().as_secs_f64()
+        );
+    }
+}
+
+/// Read the code for --raw-code from `path`, or standard input if it is `-`, and put it in a code
+/// block tagged with its language.
+fn read_raw_code(cli: &Cli, path: &Path) -> Result<String, ReviewError> {
+    let code = if path == Path::new("-") {
+        io::read_to_string(io::stdin())
+            .map_err(|e| ReviewError::Io(PathBuf::from("<stdin>"), e))
+    } else {
+        fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))
+    }?;
+
+    let language = cli
+        .language
+        .as_deref()
+        .or_else(|| language_for_path(path))
+        .or(cli.repo_defaults.language.as_deref())
+        .unwrap_or_default();
+    Ok(format!("```{}\n{}\n```", language, code.trim_end()))
+}
+
+/// Read the diff for --diff-file from `path`, or standard input if it is `-`, decoding files that
+/// aren't UTF-8 as a git diff's are. Text with no file diffs in it is refused, unless it's empty.
+fn read_diff_file(path: &Path) -> Result<String, ReviewError> {
+    let bytes = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).map_err(|e| ReviewError::Io(PathBuf::from("<stdin>"), e))?;
+        bytes
+    } else {
+        fs::read(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?
+    };
+    // The diff may not be of this repository, so its git attributes don't apply
+    let diff = decode_diff_with(&bytes, |_| None);
+    if !diff.trim().is_empty() && parse_diff(&diff).is_empty() {
+        return Err(ReviewError::Config(format!(
+            "--diff-file {}: not a unified diff; expected `diff --git` or `---`/`+++` file headers",
+            path.display()
+        )));
+    }
+    Ok(diff)
 }
 
-pub fn run(cli: Cli) {
-    setup_logging(&cli);
+/// Fit a --diff-file diff, with its `source` section, into the budget. There is no `git diff` to
+/// re-run with less context, so the context lines already in it are trimmed instead.
+fn trim_diff_file_context(cli: &Cli, diff_output: String, source: &str) -> Result<String, ReviewError> {
+    let budget = cli.diff_budget(&[("Diff Source", source.to_string())]);
+    check_prompt_overhead(cli, &budget)?;
+    if cli.no_reduce_context {
+        check_diff_size(&diff_output, budget.remaining(), &cli.token_estimator())?;
+        return Ok(diff_output);
+    }
+    let context_lines = hunk_context_lines(&diff_output).max(cli.unified_context);
+    let fitted = fit_to_budget(&diff_output, context_lines, &budget, &cli.token_estimator())?;
+    if fitted.context_lines < context_lines {
+        warn!(
+            "Context reduction can't re-run the diff given by --diff-file; trimming its context to {} lines instead",
+            fitted.context_lines
+        );
+    }
+    Ok(fitted.diff)
+}
 
-    if cli.show_system_prompt {
-        // Indent the each line of the prompt by two spaces
-        println!(
-            "Default System Prompt:\n\n{}",
-            Regex::new(r"(?m)^")
-                .unwrap()
-                .replace_all(DEFAULT_SYSTEM_PROMPT, "  ")
+/// The context lines `diff` was generated with, going by the longest run of them opening a hunk
+fn hunk_context_lines(diff: &str) -> usize {
+    parse_diff(diff)
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .map(|hunk| hunk.lines.iter().take_while(|line| line.starts_with(' ')).count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Write a prompt for each commit in `range` to `output_dir`, along with an `index.md` listing
+/// them. A commit that can't be reviewed is noted in the index and skipped. Returns the status to
+/// exit with, `EXIT_PARTIAL` if some commits were skipped without --allow-partial.
+fn review_each(cli: &Cli, range: &str, output_dir: &Path, started: Instant) -> Result<i32, ReviewError> {
+    let commits = list_commits(range)?;
+    fs::create_dir_all(output_dir).map_err(|e| ReviewError::Io(output_dir.to_path_buf(), e))?;
+
+    let mut index = format!(
+        "# Review Prompts for {}\n\n| # | Commit | Subject | Files | Tokens | Prompt |\n|---|---|---|---|---|---|\n",
+        range
+    );
+    let (mut total_files, mut total_tokens) = (0, 0);
+    let mut failed: Vec<FailedCommit> = Vec::new();
+    let mut stopped = false;
+
+    for (i, commit) in commits.iter().enumerate() {
+        let short = &commit[..commit.len().min(7)];
+        let message = commit_message(commit).unwrap_or_default();
+        let subject = message.lines().next().unwrap_or_default();
+
+        // Once the deadline has passed, the remaining commits are reported as failed
+        let prompt = if cli.deadline.exceeded() {
+            if !stopped {
+                cli.deadline.record(format!("Stopped --each after {} of {} commits", i, commits.len()));
+                stopped = true;
+            }
+            Err(ReviewError::DeadlineExceeded)
+        } else {
+            commit_prompt(cli, commit, &message)
+        };
+        let result = prompt.and_then(|prompt| {
+            let Some((prompt, files)) = prompt else {
+                return Ok((0, 0, "No changes".to_string()));
+            };
+            check_hard_cap(&prompt, cli.hard_max_chars, &cli.token_estimator(), cli.effective_budget())?;
+            let name = format!("{:03}-{}.md", i + 1, short);
+            let path = output_dir.join(&name);
+            write_atomic(&path, format!("{}\n", prompt))?;
+
+            let tokens = cli.token_estimator().estimate_tokens(&prompt);
+            total_files += files;
+            total_tokens += tokens;
+            Ok((files, tokens, format!("[{}]({})", name, name)))
+        });
+        let (files, tokens, prompt) = result.unwrap_or_else(|e| {
+            warn!("Skipping commit {}: {}", short, e);
+            failed.push(FailedCommit {
+                commit: commit.clone(),
+                subject: subject.to_string(),
+                error: e.to_string(),
+            });
+            (0, 0, format!("Failed: {}", e))
+        });
+
+        index.push_str(&format!(
+            "| {} | `{}` | {} | {} | {} | {} |\n",
+            i + 1,
+            short,
+            table_cell(subject),
+            files,
+            tokens,
+            table_cell(&prompt)
+        ));
+    }
+
+    if !failed.is_empty() {
+        let short_shas: Vec<String> = failed
+            .iter()
+            .map(|failure| format!("`{}`", &failure.commit[..failure.commit.len().min(7)]))
+            .collect();
+        index.push_str(&format!(
+            "\nReview unavailable for these commits: {}\n",
+            short_shas.join(", ")
+        ));
+    }
+
+    let index_path = output_dir.join("index.md");
+    write_atomic(&index_path, index)?;
+    info!(
+        "Wrote prompts for {} of {} commits to {}",
+        commits.len() - failed.len(),
+        commits.len(),
+        output_dir.display()
+    );
+    print_summary_line(cli, total_files, total_tokens, started);
+    let summary = format!(
+        "Wrote prompts for {} of {} commits",
+        commits.len() - failed.len(),
+        commits.len()
+    );
+
+    if failed.is_empty() {
+        notify::finished(true, &summary);
+    } else {
+        write_failed_commits(&failed, &output_dir.join(FAILED_COMMITS_FILE))?;
+        notify::finished(cli.allow_partial, &summary);
+        if !cli.allow_partial {
+            error!(
+                "No prompt was written for {} of {} commits; see {}",
+                failed.len(),
+                commits.len(),
+                FAILED_COMMITS_FILE
+            );
+            return Ok(EXIT_PARTIAL);
+        }
+    }
+    Ok(0)
+}
+
+/// A commit --each couldn't write a prompt for.
+#[derive(Debug, Serialize)]
+struct FailedCommit {
+    commit: String,
+    subject: String,
+    error: String,
+}
+
+fn write_failed_commits(failed: &[FailedCommit], path: &Path) -> Result<(), ReviewError> {
+    let json = serde_json::to_string_pretty(failed)
+        .map_err(|e| ReviewError::Io(path.to_path_buf(), e.into()))?;
+    write_atomic(path, format!("{}\n", json))
+}
+
+/// The prompt for a single commit reviewed with --each, and the number of files in its diff.
+/// `None` if the commit has no changes left to review.
+fn commit_prompt(
+    cli: &Cli,
+    commit: &str,
+    message: &str,
+) -> Result<Option<(String, usize)>, ReviewError> {
+    // Compare with the first parent, or with an empty tree for a root commit
+    let parent = format!("{}^", commit);
+    let base = match git_output(&["rev-parse", "--verify", "--quiet", &parent]) {
+        Ok(_) => parent,
+        Err(_) => EMPTY_TREE.to_string(),
+    };
+    let mut git_args_vec = vec![format!("-U{}", cli.unified_context), base, commit.to_string()];
+    git_args_vec.extend(cli.remaining_args.iter().cloned());
+    let diff_output = preprocess_diff(cli, try_git_diff(&git_args_vec)?)?;
+    if diff_output.is_empty() {
+        return Ok(None);
+    }
+
+    let diff_output = try_reduce_context(cli, Vcs::Git, &git_args_vec, diff_output)?;
+    let (diff_output, _) = scrub_paths(cli, filter_diff(cli, &diff_output));
+    if diff_output.is_empty() {
+        return Ok(None);
+    }
+
+    let mut sections = vec![("Commit Message", message.trim().to_string())];
+    if let Some(history) = history_section(cli, &diff_output)? {
+        sections.push(("Previous Reviews of These Files", history));
+    }
+    let prompt = build_prompt(cli, &diff_output, &sections)?;
+    Ok(Some((prompt, parse_diff(&diff_output).len())))
+}
+
+/// `text` on one line, with pipes escaped, for a Markdown table cell
+fn table_cell(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// When a push rewrote the pull request's branch, the range-diff between the head the previous
+/// review saw and the new one. Anything that gets in the way, such as the old head not having
+/// been fetched, falls back to a full review with a warning.
+fn rebase_section(pr: &PullRequestContext) -> Option<String> {
+    let (Some(base), Some(old_head), Some(new_head)) = (&pr.base_sha, &pr.before_sha, &pr.head_sha)
+    else {
+        return None;
+    };
+
+    let fall_back = |e: ReviewError| {
+        warn!("Could not compare with the previously reviewed head, reviewing everything: {}", e)
+    };
+    if !was_rebased(old_head, new_head).map_err(fall_back).ok()? {
+        return None;
+    }
+    if !capabilities().range_diff() {
+        info!("The branch was rebased, but this git is too old for `git range-diff`; reviewing everything");
+        return None;
+    }
+    let range_diff = range_diff(base, old_head, new_head).map_err(fall_back).ok()?;
+    info!("The branch was rebased since {}; focusing on the commits that changed", old_head);
+    Some(summarize_range_diff(&range_diff))
+}
+
+/// Warn up front when the repository is a partial clone or sparse checkout, and unless
+/// --allow-fetch was given, keep the context around the diff from fetching missing objects.
+fn check_checkout(cli: &Cli) {
+    let checkout = Checkout::detect();
+    for warning in checkout.warnings(cli.allow_fetch) {
+        warn!("{}", warning);
+    }
+    if checkout.partial_clone_filter.is_some() && !cli.allow_fetch {
+        disable_lazy_fetch();
+    }
+}
+
+/// The defaults for --repo-language, or for the languages of the files in the repository. Outside
+/// a git repository there are none.
+fn repo_defaults(cli: &Cli) -> RepoDefaults {
+    if let Some(language) = &cli.repo_language {
+        return RepoDefaults::for_language(language);
+    }
+    match detect_repo_languages() {
+        Ok(profile) => {
+            let defaults = RepoDefaults::from_profile(&profile);
+            info!(
+                "Repository languages: {}; using presets for {}",
+                profile.describe(),
+                match defaults.presets.as_slice() {
+                    [] => "no language".to_string(),
+                    presets => presets.iter().map(|preset| preset.language).collect::<Vec<_>>().join(", "),
+                }
+            );
+            defaults
+        }
+        Err(e) => {
+            debug!("Not detecting the repository's languages: {}", e);
+            RepoDefaults::default()
+        }
+    }
+}
+
+/// With --full-files, the new versions of the files the diff changes, in full, as many as fit in
+/// the `remaining` tokens, smallest first. A diff split into parts has no tokens to spare for them.
+fn full_files_context_section(cli: &Cli, diff_output: &str, remaining: Option<usize>) -> Option<String> {
+    let Some(remaining) = remaining else {
+        info!("--full-files: the diff is split into parts, so the full files are left out");
+        return None;
+    };
+    let estimator = cli.token_estimator();
+    let files = full_files(
+        &parse_diff(diff_output),
+        cli.full_file_max_lines.unwrap_or(DEFAULT_FULL_FILE_MAX_LINES),
+        read_new_version,
+    );
+    let overhead = estimator.estimate_tokens(&format!("## Full File Contents\n{}\n\n\n", FULL_FILES_INTRO));
+    let cost = |file: &FullFile| estimator.estimate_tokens(&full_file_block(file)) + 1;
+    let (included, skipped) = pack_files(files, remaining.saturating_sub(overhead), cost);
+    for file in &included {
+        info!("Full files: included {} ({} tokens)", file.path, cost(file));
+    }
+    for file in &skipped {
+        info!("Full files: left out {}: its {} tokens don't fit in what's left", file.path, cost(file));
+    }
+    full_files_section(&included)
+}
+
+/// The existing test files usually covering the source files the diff changes, when the diff
+/// leaves them alone, so the reviewer can ask whether they need updating.
+fn related_tests_section(cli: &Cli, diff_output: &str) -> Option<String> {
+    if cli.repo_defaults.presets.is_empty() {
+        return None;
+    }
+    let changed: Vec<String> = parse_diff(diff_output).into_iter().map(|file| file.path).collect();
+    let lines: Vec<String> = changed
+        .iter()
+        .filter_map(|path| {
+            let tests: Vec<String> = cli
+                .repo_defaults
+                .related_tests(path)
+                .into_iter()
+                .filter(|test| !changed.contains(test) && Path::new(test).is_file())
+                .map(|test| format!("`{}`", test))
+                .collect();
+            (!tests.is_empty()).then(|| format!("- `{}` is usually tested by {}", path, tests.join(", ")))
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "These tests weren't changed. Say if they need updating for this change.\n\n{}",
+        lines.join("\n")
+    ))
+}
+
+/// The compatibility review section for the contract files the diff changes, logging the
+/// problems the protobuf check finds
+fn contracts_section(cli: &Cli, diff_output: &str) -> Option<String> {
+    if cli.no_compatibility_review {
+        return None;
+    }
+    let files = parse_diff(diff_output);
+    let configured: Vec<Regex> = cli.contract_files.iter().map(|pattern| glob_regex(pattern)).collect();
+    let contracts = find_contracts(&files, &repo_root().unwrap_or_default(), &configured);
+    if contracts.is_empty() {
+        return None;
+    }
+    info!("Asking for a compatibility review of {} contract files", contracts.len());
+    let problems: Vec<String> = files
+        .iter()
+        .filter(|file| contracts.contains(&(file.path.as_str(), ContractKind::Protobuf)))
+        .flat_map(proto_field_changes)
+        .collect();
+    for problem in &problems {
+        warn!("Incompatible protobuf change: {}", problem);
+    }
+    Some(compatibility_section(&contracts, &problems))
+}
+
+/// The changed CI and build configuration files in `files`, as --infra-files adds to them
+fn changed_infra<'a>(cli: &Cli, files: &'a [FileDiff]) -> Vec<(&'a str, InfraKind)> {
+    let configured: Vec<Regex> = cli.infra_files.iter().map(|pattern| glob_regex(pattern)).collect();
+    find_infra(files, &configured)
+}
+
+/// What the local checks find on the lines `diff_output` adds to CI and build configuration; none
+/// with --no-infra-review
+fn local_infra_findings(cli: &Cli, diff_output: &str) -> Vec<Finding> {
+    if cli.no_infra_review {
+        return Vec::new();
+    }
+    let files = parse_diff(diff_output);
+    check_infra(&files, &changed_infra(cli, &files), &cli.skip_infra_check)
+}
+
+/// The infrastructure review section for the CI and build configuration the diff changes, logging
+/// what the local checks find
+fn infrastructure_context_section(cli: &Cli, diff_output: &str) -> Option<String> {
+    if cli.no_infra_review {
+        return None;
+    }
+    let files = parse_diff(diff_output);
+    let infra = changed_infra(cli, &files);
+    if infra.is_empty() {
+        return None;
+    }
+    info!("Reviewing {} CI and build configuration files with the infrastructure rubric", infra.len());
+    let findings = check_infra(&files, &infra, &cli.skip_infra_check);
+    for finding in &findings {
+        warn!("{}:{}: {}", finding.path, finding.line.unwrap_or_default(), finding.comment);
+    }
+    Some(infrastructure_section(&infra, &findings))
+}
+
+/// The database migrations `files` add or change, as --migration-files adds to them, with the
+/// schema and model changes they come from
+fn changed_migrations<'a>(cli: &Cli, files: &'a [FileDiff]) -> Vec<Migration<'a>> {
+    let configured: Vec<Regex> = cli.migration_files.iter().map(|pattern| glob_regex(pattern)).collect();
+    find_migrations(files, &configured)
+}
+
+/// The tables and columns the lines `diff_output` adds to migrations drop; none with
+/// --no-migration-review
+fn local_migration_findings(cli: &Cli, diff_output: &str) -> Vec<Finding> {
+    if cli.no_migration_review {
+        return Vec::new();
+    }
+    let files = parse_diff(diff_output);
+    check_migrations(&files, &changed_migrations(cli, &files))
+}
+
+/// The migration review section for the database migrations the diff changes, logging the tables
+/// and columns they drop
+fn migrations_context_section(cli: &Cli, diff_output: &str) -> Option<String> {
+    if cli.no_migration_review {
+        return None;
+    }
+    let files = parse_diff(diff_output);
+    let migrations = changed_migrations(cli, &files);
+    if migrations.is_empty() {
+        return None;
+    }
+    info!("Reviewing {} database migrations against their schema changes", migrations.len());
+    let findings = check_migrations(&files, &migrations);
+    for finding in &findings {
+        warn!("{}:{}: {}", finding.path, finding.line.unwrap_or_default(), finding.comment);
+    }
+    Some(migrations_section(&migrations, &findings))
+}
+
+/// With --check-naming, the names and strings the diff adds that the local check flags, logged
+/// and listed for the model to confirm. The files are read for the names they already had when the
+/// diff is of the local repository.
+fn naming_candidates_section(diff_output: &str, local: bool) -> Option<String> {
+    let root = repo_root().unwrap_or_default();
+    let existing = |path: &str| local.then(|| fs::read_to_string(root.join(path)).ok()).flatten();
+    let candidates = check_naming(&parse_diff(diff_output), existing, &Dictionary::default());
+    if candidates.is_empty() {
+        info!("--check-naming found nothing to ask about");
+        return None;
+    }
+    for candidate in &candidates {
+        info!("{}:{}: {}: {}", candidate.path, candidate.line, candidate.text, candidate.problem);
+    }
+    Some(naming_section(&candidates))
+}
+
+/// With --new-todos or --require-todo-issue, list the markers the diff adds on standard error and
+/// for the prompt, exiting if --require-todo-issue finds one without an issue reference.
+fn markers_section(cli: &Cli, diff_output: &str) -> Result<Option<String>, ReviewError> {
+    if !cli.new_todos && cli.require_todo_issue.is_none() {
+        return Ok(None);
+    }
+
+    let markers = find_new_markers(&parse_diff(diff_output));
+    if markers.is_empty() {
+        return Ok(None);
+    }
+    eprintln!("New TODO markers:\n{}", describe_markers(&markers));
+
+    if let Some(pattern) = &cli.require_todo_issue {
+        let issue_pattern =
+            Regex::new(pattern).map_err(|e| ReviewError::Config(format!("--require-todo-issue: {}", e)))?;
+        let untracked = markers_without_issue(&markers, &issue_pattern);
+        if !untracked.is_empty() {
+            return Err(ReviewError::TodoWithoutIssue {
+                pattern: pattern.clone(),
+                count: untracked.len(),
+                markers: describe_markers(&untracked),
+            });
+        }
+    }
+
+    Ok(Some(format!(
+        "The diff adds these TODO-style markers. Say whether each is acceptable to merge as it is, or should be resolved or tracked first:\n{}",
+        describe_markers(&markers)
+    )))
+}
+
+/// Say what --deadline-secs left out of the prompt, so the review isn't mistaken for a full one
+fn partial_context_section(cli: &Cli) -> Option<String> {
+    let decisions = cli.deadline.decisions();
+    if decisions.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "This prompt was assembled under a time limit and is missing some context:\n{}",
+        decisions
+            .iter()
+            .map(|decision| format!("- {}", decision))
+            .collect::<Vec<_>>()
+            .join("\n")
+    ))
+}
+
+/// Excerpts from saved reviews in --history-dir that discuss the files in this diff
+fn history_section(cli: &Cli, diff_output: &str) -> Result<Option<String>, ReviewError> {
+    let Some(dir) = &cli.history_dir else {
+        return Ok(None);
+    };
+    if !cli.deadline.allows("excerpts of previous reviews", HISTORY_ESTIMATE) {
+        return Ok(None);
+    }
+    let reviews = load_reviews(dir)?;
+
+    let changed_paths: Vec<String> = parse_diff(diff_output)
+        .into_iter()
+        .map(|file| file.path)
+        .collect();
+    let budget = cli.effective_budget();
+    let max_chars = budget * HISTORY_BUDGET_SHARE / 100 * cli.chars_per_token();
+
+    let excerpts = related_excerpts(&reviews, &changed_paths, max_chars);
+    info!(
+        "Including {} characters of previous reviews from {}",
+        excerpts.len(),
+        dir.display()
+    );
+    Ok((!excerpts.is_empty()).then_some(excerpts))
+}
+
+/// Rewrite the absolute paths in the diff's lines unless --no-path-scrub was given: those under
+/// the repository become relative to it, and home directories become ~. Returns what was
+/// scrubbed, if anything was looked for.
+pub(crate) fn scrub_paths(cli: &Cli, diff_output: String) -> (String, Option<RedactionSummary>) {
+    if cli.no_path_scrub || diff_output.is_empty() {
+        return (diff_output, None);
+    }
+    let repo_root = repo_root();
+    let home = env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok();
+    let scrubber = PathScrubber::new(repo_root.as_deref().and_then(Path::to_str), home.as_deref());
+    let (scrubbed, summary) = scrubber.scrub_diff(&diff_output);
+    if summary.total() > 0 {
+        info!("{}", summary);
+    }
+    (scrubbed, Some(summary))
+}
+
+/// The root of the repository the current directory is in, of any version control system
+fn repo_root() -> Option<PathBuf> {
+    env::current_dir().ok().and_then(|dir| {
+        dir.ancestors()
+            .find(|dir| [".git", ".hg", ".jj"].iter().any(|marker| dir.join(marker).exists()))
+            .map(Path::to_path_buf)
+    })
+}
+
+/// The packages in --packages-file, or else the members of the repository's workspace, if any
+fn workspace(cli: &Cli) -> Result<Option<Workspace>, ReviewError> {
+    if let Some(path) = &cli.packages_file {
+        return Workspace::load(path).map(Some);
+    }
+    let Some(root) = repo_root() else {
+        return Ok(None);
+    };
+    Ok(match Workspace::detect(&root) {
+        Ok(workspace) => {
+            if let Some(workspace) = &workspace {
+                let names: Vec<&str> = workspace.packages.iter().map(|package| package.name.as_str()).collect();
+                info!("Workspace packages: {}", names.join(", "));
+            }
+            workspace
+        }
+        Err(e) => {
+            warn!("Not grouping the diff by package: {}", e);
+            None
+        }
+    })
+}
+
+/// With --package, the workspace member to review
+fn selected_package(cli: &Cli) -> Option<&Package> {
+    cli.workspace.as_ref()?.get(cli.package.as_deref()?).ok()
+}
+
+/// `diff_output` without the files --include and --exclude leave out. Run on the diff as it comes
+/// from the VCS, so the size checks only count the files that will be reviewed.
+fn filter_by_paths(cli: &Cli, diff_output: &str) -> String {
+    if cli.include.is_empty() && cli.exclude.is_empty() {
+        return diff_output.to_string();
+    }
+    let include: Vec<Regex> = cli.include.iter().map(|glob| glob_regex(glob)).collect();
+    let exclude: Vec<Regex> = cli.exclude.iter().map(|glob| glob_regex(glob)).collect();
+    filter_diff_by_paths(diff_output, &include, &exclude)
+}
+
+/// The note telling the model which files of `original_diff` --include and --exclude left out, so
+/// the review doesn't take them to be unchanged. Lists at most `OMITTED_FILES_LISTED` of them.
+fn omitted_files_section(cli: &Cli, original_diff: &str) -> Option<String> {
+    let filtered = filter_by_paths(cli, original_diff);
+    let kept: Vec<String> = parse_diff(&filtered).into_iter().map(|file| file.path).collect();
+    let omitted_files: Vec<FileDiff> = parse_diff(original_diff).into_iter().filter(|file| !kept.contains(&file.path)).collect();
+    if omitted_files.is_empty() {
+        return None;
+    }
+    let omitted: Vec<String> = omitted_files.iter().map(describe_file).collect();
+    let estimator = cli.token_estimator();
+    let changed_lines: usize = omitted_files.iter().flat_map(|file| &file.hunks).map(Hunk::changed_lines).sum();
+    info!(
+        "Left out by --include or --exclude: {} ({} file{}, {} changed line{}, about {} tokens)",
+        omitted.join(", "),
+        omitted.len(),
+        if omitted.len() == 1 { "" } else { "s" },
+        changed_lines,
+        if changed_lines == 1 { "" } else { "s" },
+        estimator.estimate_tokens(original_diff).saturating_sub(estimator.estimate_tokens(&filtered))
+    );
+    let locale = cli.locale();
+    let mut listed = omitted.iter().take(OMITTED_FILES_LISTED).cloned().collect::<Vec<_>>().join(", ");
+    if omitted.len() > OMITTED_FILES_LISTED {
+        listed.push_str(&locale.format(" and {} more", &[&(omitted.len() - OMITTED_FILES_LISTED).to_string()]));
+    }
+    Some(locale.format("The following files were changed but omitted from this review: {}.", &[&listed]))
+}
+
+/// Apply the language presets' excludes and the hunk and file filters requested on the command line, then elide long runs of
+/// unchanged lines unless --no-compress was given.
+pub(crate) fn filter_diff(cli: &Cli, diff_output: &str) -> String {
+    let excludes = !cli.repo_defaults.presets.is_empty();
+    let package = selected_package(cli);
+    if cli.min_change_size.is_none()
+        && !cli.api_only
+        && !excludes
+        && cli.include.is_empty()
+        && cli.exclude.is_empty()
+        && package.is_none()
+        && cli.no_compress
+    {
+        return diff_output.to_string();
+    }
+
+    let mut files = parse_diff(diff_output);
+    if let Some(package) = package {
+        let before = files.len();
+        files = restrict_to_package(files, package);
+        info!("Dropped {} of {} files outside package {}", before - files.len(), before, package.name);
+    }
+    if excludes {
+        let (excluded, kept): (Vec<FileDiff>, Vec<FileDiff>) =
+            files.into_iter().partition(|file| cli.repo_defaults.is_excluded(&file.path));
+        if !excluded.is_empty() {
+            let paths: Vec<&str> = excluded.iter().map(|file| file.path.as_str()).collect();
+            info!("Dropped build output and vendored files: {}", paths.join(", "));
+        }
+        files = kept;
+    }
+    if !cli.include.is_empty() || !cli.exclude.is_empty() {
+        files = parse_diff(&filter_by_paths(cli, &render_diff(&files)));
+    }
+    if let Some(min_changes) = cli.min_change_size {
+        let before = files.len();
+        files = filter_small_hunks(files, min_changes);
+        info!(
+            "Dropped {} of {} files with no hunks of at least {} changed lines",
+            before - files.len(),
+            before,
+            min_changes
+        );
+    }
+    if cli.api_only {
+        let unsupported: Vec<&str> = files
+            .iter()
+            .map(|file| file.path.as_str())
+            .filter(|path| !supports_public_api(path))
+            .collect();
+        if !unsupported.is_empty() {
+            warn!(
+                "--api-only only understands Rust; reviewing these files in full: {}",
+                unsupported.join(", ")
+            );
+        }
+        let before = files.len();
+        files = filter_public_api(files);
+        info!(
+            "Dropped {} of {} files with no changes to public items",
+            before - files.len(),
+            before
         );
-        process::exit(0);
     }
+    if cli.no_compress {
+        return render_diff(&files);
+    }
+
+    let uncompressed = render_diff(&files).len();
+    let compressed = render_diff(&compress_diff(files, MAX_UNCHANGED_RUN));
+    info!(
+        "Eliding unchanged lines saved about {} tokens",
+        uncompressed.saturating_sub(compressed.len()) / cli.chars_per_token()
+    );
+    compressed
+}
+
+/// Provenance for the diff under review, with repository details when it came from the local git
+/// repository
+fn provenance(cli: &Cli, vcs: Vcs, diff_output: &str, local: bool) -> Provenance {
+    let provider = cli.provider_config.as_ref().map(|provider| provider.name.clone());
+    let provenance = Provenance::new(diff_output, provider);
+    if vcs != Vcs::Git || !local {
+        return provenance;
+    }
+
+    // The revision the diff starts from: the first one given, or the left side of a range
+    let base = cli
+        .remaining_args
+        .iter()
+        .map(String::as_str)
+        .find(|arg| !arg.starts_with('-'))
+        .and_then(|arg| arg.split("..").next())
+        .filter(|base| !base.is_empty());
+
+    // Don't count the tool's own output as uncommitted changes
+    let mut own_files: Vec<PathBuf> = [&cli.output_file, &cli.output_dir, &cli.history_dir]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    if let Some(output_file) = &cli.output_file {
+        let mut report_path = output_file.clone().into_os_string();
+        report_path.push(".budget.json");
+        own_files.push(PathBuf::from(report_path));
+    }
+
+    provenance.with_git_details(base, &own_files)
+}
+
+/// The issues the branch name and the messages of the commits under review refer to, fetched
+/// from whichever issue trackers have tokens in the environment.
+fn linked_issues(cli: &Cli) -> Option<String> {
+    let branch = current_branch(|var| env::var(var).ok());
+    let mut refs = branch.map(|branch| parse_branch_refs(&branch)).unwrap_or_default();
+    if let Some(range) = commit_range(cli)
+        && let Ok(log) = git_output(&["log", "--format=%B", "-n", "50", &range])
+    {
+        refs.extend(parse_issue_refs(&log).into_iter().filter(|r| !refs.contains(r)).collect::<Vec<_>>());
+    }
+    if refs.is_empty() {
+        return None;
+    }
+
+    let remote = git_output(&["remote", "get-url", "origin"])
+        .ok()
+        .and_then(|url| parse_remote_url(&url));
+    let timeout = cli
+        .deadline
+        .remaining()
+        .map_or(ISSUE_FETCH_TIMEOUT, |remaining| remaining.min(ISSUE_FETCH_TIMEOUT));
+    let sources = IssueSources::from_env(remote, timeout);
+    let names: Vec<String> = refs.iter().map(|r| r.to_string()).collect();
+    if !sources.any() {
+        info!("Not fetching linked issues {}: no issue tracker token is set or logged in", names.join(", "));
+        return None;
+    }
+    info!("Fetching linked issues: {}", names.join(", "));
+    linked_issues_section(&sources.fetch_all(&refs, MAX_LINKED_ISSUES), ISSUE_DESCRIPTION_MAX_CHARS)
+}
+
+/// The commits under review as a `git log` range: a range given on the command line, or from a
+/// single revision to HEAD. `None` when the diff is only of uncommitted changes.
+fn commit_range(cli: &Cli) -> Option<String> {
+    let (from, to) = match diff_revisions(cli).as_slice() {
+        [] => return None,
+        [range] if range.contains("..") => {
+            let (from, to) = range.split_once("...").or_else(|| range.split_once(".."))?;
+            (from, to)
+        }
+        [from] => (*from, "HEAD"),
+        [from, to, ..] => (*from, *to),
+    };
+    let (from, to) = (if from.is_empty() { "HEAD" } else { from }, if to.is_empty() { "HEAD" } else { to });
+    // From the empty tree, every commit is under review
+    if from == EMPTY_TREE {
+        Some(to.to_string())
+    } else {
+        Some(format!("{}..{}", from, to))
+    }
+}
+
+/// The revisions among the diff arguments, leaving out options and paths
+fn diff_revisions(cli: &Cli) -> Vec<&str> {
+    cli.remaining_args
+        .iter()
+        .map(String::as_str)
+        .take_while(|arg| *arg != "--")
+        .filter(|arg| !arg.starts_with('-') && !Path::new(arg).exists())
+        .collect()
+}
+
+/// `git diff --stat` for the diff under review, as a Markdown table. Only diffs taken from the
+/// local git repository with git itself have a stat to show.
+fn stat_section(cli: &Cli, vcs: Vcs, remote: bool) -> Option<String> {
+    if vcs != Vcs::Git || remote || cli.from_stash.is_some() || cli.diff_command.is_some() {
+        warn!("--diff-stat-as-table only works with diffs from the local git repository");
+        return None;
+    }
+
+    // Wide enough that git doesn't shorten paths or shrink the graph of most files
+    let mut args = vec!["diff", "--stat=1000", "--stat-graph-width=1000"];
+    args.extend(cli.remaining_args.iter().map(String::as_str));
+    match git_output(&args) {
+        Ok(stat) => Some(format_stat_as_table(&stat)),
+        Err(e) => {
+            warn!("Could not get the diff stat: {}", e);
+            None
+        }
+    }
+}
+
+/// With --sample-similar, leave out files whose changes repeat ones already in the diff
+fn sample_diff(cli: &Cli, diff_output: String) -> (String, Vec<SampledCluster>) {
+    let Some(per_cluster) = cli.sample_similar else {
+        return (diff_output, Vec::new());
+    };
 
-    let git_args_vec: Vec<String> = vec![
-        format!("-U{}", cli.unified_context),
-        cli.remaining_args.join(" "),
-    ];
+    let (files, sampled) = sample_similar(parse_diff(&diff_output), per_cluster);
+    let omitted: usize = sampled.iter().map(|cluster| cluster.omitted.len()).sum();
+    info!(
+        "Left out {} files making the same changes as the {} sampled",
+        omitted,
+        sampled.iter().map(|cluster| cluster.sampled.len()).sum::<usize>()
+    );
+    (render_diff(&files), sampled)
+}
+
+/// Re-run the diff with reduced context if the diff is too large for the token budget.
+fn try_reduce_context(
+    cli: &Cli,
+    vcs: Vcs,
+    git_args_vec: &[String],
+    diff_output: String,
+) -> Result<String, ReviewError> {
+    let budget = cli.diff_budget(&[]);
+    check_prompt_overhead(cli, &budget)?;
+    let max_tokens = budget.remaining();
 
-    let mut diff_output = get_git_diff(&git_args_vec.join(" "));
+    if cli.no_reduce_context {
+        check_diff_size(&diff_output, max_tokens, &cli.token_estimator())?;
+        return Ok(diff_output);
+    }
 
-    if let Some(new_args) = reduce_context_if_needed(
-        &git_args_vec,
+    // Where the trimmed diff elided context, the VCS may split hunks instead, which can come out
+    // a little larger, so the new diff is measured rather than the trimmed one
+    reduce_context_with(
+        git_args_vec,
         cli.unified_context,
         cli.force_reduced,
-        &diff_output,
-        MAX_TOKENS,
-        CHARS_PER_TOKEN,
-    ) {
-        diff_output = get_git_diff(&new_args.join(" "));
+        diff_output,
+        max_tokens,
+        &cli.token_estimator(),
+        |args| Ok(filter_by_paths(cli, &run_diff(cli, vcs, args)?)),
+    )
+}
+
+/// Poll the diff and print a fresh prompt each time it changes. Only tracked files show up in
+/// the diff, and untracked ones with --include-untracked, so ignored files and anything excluded by
+/// the diff arguments never trigger a re-run.
+/// Runs until the process is interrupted, or the diff fails.
+fn watch(cli: &Cli, vcs: Vcs, git_args_vec: &[String]) -> Result<(), ReviewError> {
+    if cli.watch_incremental && vcs != Vcs::Git {
+        return Err(ReviewError::Usage("--watch-incremental is only supported in git repositories".to_string()));
+    }
+
+    let mut last_diff: Option<String> = None;
+    let mut last_snapshot: Option<String> = None;
+    // git can't snapshot the worktree until there is a commit to base the snapshot on
+    let incremental = cli.watch_incremental && has_commits();
+    if cli.watch_incremental && !incremental {
+        warn!("The repository has no commits yet, so --watch-incremental reviews the whole diff each time");
+    }
+
+    loop {
+        let diff_output = run_diff(cli, vcs, git_args_vec)?;
+
+        if last_diff.as_ref() != Some(&diff_output) {
+            // Wait for the change to settle, so a burst of saves only triggers one review
+            thread::sleep(WATCH_DEBOUNCE);
+            let settled = run_diff(cli, vcs, git_args_vec)?;
+            if settled != diff_output {
+                continue;
+            }
+            let started = Instant::now();
+
+            // With --watch-incremental, everything after the first run only covers the changes
+            // made since the previous run
+            let mut delta = None;
+            if incremental {
+                let snapshot = snapshot_worktree()?;
+                if let Some(previous) = &last_snapshot {
+                    let args = incremental_git_args(cli, previous, &snapshot);
+                    delta = Some(try_git_diff(&args)?);
+                }
+                last_snapshot = Some(snapshot);
+            }
+
+            // Clear the screen and move the cursor to the top left
+            print!("\x1B[2J\x1B[H");
+            let (filtered, mut sections) = match delta {
+                Some(delta) => (
+                    scrub_paths(cli, filter_diff(cli, &delta)).0,
+                    vec![(
+                        "Diff Source",
+                        "Only the changes made since the previous review in this session"
+                            .to_string(),
+                    )],
+                ),
+                None if diff_output.is_empty() => (String::new(), vec![]),
+                None => {
+                    let reduced = try_reduce_context(cli, vcs, git_args_vec, diff_output.clone())?;
+                    (scrub_paths(cli, filter_diff(cli, &reduced)).0, vec![])
+                }
+            };
+            if filtered.is_empty() {
+                println!("No changes found to review.");
+                finish(cli, 0, 0, started);
+            } else {
+                if let Some(history) = history_section(cli, &filtered)? {
+                    sections.push(("Previous Reviews of These Files", history));
+                }
+                let prompt = build_prompt(cli, &filtered, &sections)?;
+                print_wrapped(cli, &prompt);
+                finish(
+                    cli,
+                    parse_diff(&filtered).len(),
+                    cli.token_estimator().estimate_tokens(&prompt),
+                    started,
+                );
+            }
+            info!("Watching for changes, press Ctrl-C to stop.");
+            last_diff = Some(diff_output);
+        }
+
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Record the current state of the tracked files as a commit object, without touching the index,
+/// working tree or stash list. Returns `HEAD` when there is nothing to record.
+fn snapshot_worktree() -> Result<String, ReviewError> {
+    let output = git_command()
+        .args(["stash", "create"])
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(ReviewError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if sha.is_empty() {
+        Ok("HEAD".to_string())
+    } else {
+        Ok(sha)
     }
+}
 
-    println!("{}", build_prompt(&cli, &diff_output));
+/// Arguments to diff two worktree snapshots, keeping any pathspecs given after `--`
+fn incremental_git_args(cli: &Cli, from: &str, to: &str) -> Vec<String> {
+    let mut args = vec![format!("-U{}", cli.unified_context), from.to_string(), to.to_string()];
+    if let Some(separator) = cli.remaining_args.iter().position(|arg| arg == "--") {
+        args.extend(cli.remaining_args[separator..].iter().cloned());
+    }
+    args
 }
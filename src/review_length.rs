@@ -0,0 +1,95 @@
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::diff::parse_diff;
+
+/// Values accepted by `--review-length`
+pub const REVIEW_LENGTHS: &[&str] = &["auto", "short", "medium", "long", "unlimited"];
+
+/// How long the review asked for in the prompt should be.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReviewLength {
+    /// Scale the length with the number of changed lines
+    #[default]
+    Auto,
+    Short,
+    Medium,
+    Long,
+    /// Don't ask for any particular length
+    Unlimited,
+}
+
+impl FromStr for ReviewLength {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ReviewLength::Auto),
+            "short" => Ok(ReviewLength::Short),
+            "medium" => Ok(ReviewLength::Medium),
+            "long" => Ok(ReviewLength::Long),
+            "unlimited" => Ok(ReviewLength::Unlimited),
+            _ => Err(format!("unknown review length `{}`", s)),
+        }
+    }
+}
+
+/// The shortest and longest reviews `ReviewLength::Auto` asks for, in words
+const AUTO_MIN_WORDS: usize = 150;
+const AUTO_MAX_WORDS: usize = 1500;
+
+impl ReviewLength {
+    /// The most words the review should have for a change of `changed_lines` lines, or `None` for
+    /// no limit. `Auto` grows with the square root of the change's size, so a handful of lines gets
+    /// a couple of paragraphs and a large change gets a few pages, rounded to 50 words.
+    pub fn target_words(&self, changed_lines: usize) -> Option<usize> {
+        match self {
+            ReviewLength::Auto => {
+                let words = 100.0 + 50.0 * (changed_lines as f64).sqrt();
+                let words = (words / 50.0).round() as usize * 50;
+                Some(words.clamp(AUTO_MIN_WORDS, AUTO_MAX_WORDS))
+            }
+            ReviewLength::Short => Some(200),
+            ReviewLength::Medium => Some(600),
+            ReviewLength::Long => Some(1500),
+            ReviewLength::Unlimited => None,
+        }
+    }
+}
+
+/// The review length asked for in a prompt, and what it was based on. Recorded in the token budget
+/// report so the `auto` curve can be tuned against real reviews.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ReviewTarget {
+    pub changed_lines: usize,
+    /// `None` when the length is unlimited
+    pub words: Option<usize>,
+}
+
+impl ReviewTarget {
+    pub fn new(length: ReviewLength, changed_lines: usize) -> Self {
+        ReviewTarget {
+            changed_lines,
+            words: length.target_words(changed_lines),
+        }
+    }
+
+    /// The instruction to add to the system prompt, if any
+    pub fn instructions(&self) -> Option<String> {
+        self.words.map(|words| {
+            format!(
+                "\nKeep the review under {} words, spending them on the most important issues first.\n",
+                words
+            )
+        })
+    }
+}
+
+/// Added and removed lines across every file in `diff`
+pub fn diff_changed_lines(diff: &str) -> usize {
+    parse_diff(diff)
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .map(|hunk| hunk.changed_lines())
+        .sum()
+}
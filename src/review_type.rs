@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+use crate::system_prompt::PROMPT_REVIEW_RULES;
+
+/// Values accepted by `--review-type`
+pub const REVIEW_TYPES: &[&str] = &["general", "security", "performance", "tests", "style"];
+
+/// What the review should look at. Each type other than `General` replaces the focus areas of the
+/// system prompt with its own, asking for that dimension only.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReviewType {
+    /// Everything the default focus areas cover
+    #[default]
+    General,
+    Security,
+    Performance,
+    Tests,
+    Style,
+}
+
+impl FromStr for ReviewType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "general" => Ok(ReviewType::General),
+            "security" => Ok(ReviewType::Security),
+            "performance" => Ok(ReviewType::Performance),
+            "tests" => Ok(ReviewType::Tests),
+            "style" => Ok(ReviewType::Style),
+            _ => Err(format!("unknown review type `{}`", s)),
+        }
+    }
+}
+
+impl ReviewType {
+    /// The focus areas section of the system prompt for this type
+    pub fn rules(&self) -> &'static str {
+        match self {
+            ReviewType::General => PROMPT_REVIEW_RULES,
+            ReviewType::Security => include_str!("rsc/prompt/review_types/security.txt"),
+            ReviewType::Performance => include_str!("rsc/prompt/review_types/performance.txt"),
+            ReviewType::Tests => include_str!("rsc/prompt/review_types/tests.txt"),
+            ReviewType::Style => include_str!("rsc/prompt/review_types/style.txt"),
+        }
+    }
+}
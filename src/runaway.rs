@@ -0,0 +1,279 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Responses longer than this many characters (about 10,000 tokens) are cut short
+pub const DEFAULT_MAX_RESPONSE_CHARS: usize = 40_000;
+
+/// A line repeated this many times means the model is going round in circles
+pub const DEFAULT_MAX_REPEATS: usize = 6;
+
+/// How long the local summary of a runaway response may get
+pub const DEFAULT_SUMMARY_CHARS: usize = 4_000;
+
+// Lines with fewer words than this, such as `}` or `---`, repeat in any review
+const MIN_REPEATED_WORDS: usize = 3;
+
+/// When a response counts as a runaway.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunawayLimits {
+    pub max_chars: usize,
+    pub max_repeats: usize,
+    pub summary_chars: usize,
+}
+
+impl Default for RunawayLimits {
+    fn default() -> Self {
+        RunawayLimits {
+            max_chars: DEFAULT_MAX_RESPONSE_CHARS,
+            max_repeats: DEFAULT_MAX_REPEATS,
+            summary_chars: DEFAULT_SUMMARY_CHARS,
+        }
+    }
+}
+
+/// Why a response was cut short.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunawayReason {
+    TooLong { chars: usize },
+    Repetitive { line: String, count: usize },
+}
+
+impl fmt::Display for RunawayReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunawayReason::TooLong { chars } => write!(f, "the response ran to {} characters", chars),
+            RunawayReason::Repetitive { line, count } => {
+                write!(f, "the line \"{}\" was repeated {} times", line, count)
+            }
+        }
+    }
+}
+
+/// A runaway response: why it counts as one, and the offset it went wrong at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Runaway {
+    pub reason: RunawayReason,
+    pub at: usize,
+}
+
+/// Whether `response` is too long, or repeats a line `max_repeats` times; if so, where it went
+/// wrong: where it passed the limit, or where the repeated line came up for the second time.
+pub fn detect_runaway(response: &str, limits: &RunawayLimits) -> Option<Runaway> {
+    let repetitive = repetition(response, limits.max_repeats);
+    let too_long = (response.len() > limits.max_chars).then(|| Runaway {
+        reason: RunawayReason::TooLong { chars: response.chars().count() },
+        at: response.floor_char_boundary(limits.max_chars),
+    });
+    match (repetitive, too_long) {
+        (Some(repetitive), Some(too_long)) if too_long.at < repetitive.at => Some(too_long),
+        (repetitive, too_long) => repetitive.or(too_long),
+    }
+}
+
+fn repetition(response: &str, max_repeats: usize) -> Option<Runaway> {
+    // Where each line was seen, by its normalized text
+    let mut seen: HashMap<String, Vec<usize>> = HashMap::new();
+    for (offset, line) in line_offsets(response) {
+        let normalized = normalize(line);
+        if normalized.split_whitespace().count() < MIN_REPEATED_WORDS {
+            continue;
+        }
+        let offsets = seen.entry(normalized).or_default();
+        offsets.push(offset);
+        if offsets.len() >= max_repeats.max(2) {
+            return Some(Runaway {
+                reason: RunawayReason::Repetitive { line: line.trim().to_string(), count: offsets.len() },
+                at: offsets[1],
+            });
+        }
+    }
+    None
+}
+
+/// The line for comparing with others: without list markers, numbers or case
+fn normalize(line: &str) -> String {
+    let line = line.trim().trim_start_matches(['-', '*', '>', ' ']);
+    let line = line.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == ')' || c == ' ');
+    line.chars().map(|c| if c.is_ascii_digit() { '#' } else { c.to_ascii_lowercase() }).collect()
+}
+
+/// Each line of `text` with the offset it starts at
+fn line_offsets(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line.trim_end_matches(['\n', '\r'])))
+    })
+}
+
+fn is_heading(line: &str) -> bool {
+    line.starts_with('#') && line.trim_start_matches('#').starts_with(' ')
+}
+
+/// The length of the `- `, `* ` or number such as `1. ` or `2) ` that `line` starts with, if it
+/// is a list item
+fn list_marker_len(line: &str) -> Option<usize> {
+    let numbered = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    if line.starts_with("- ") || line.starts_with("* ") {
+        Some(2)
+    } else if numbered.len() < line.len() && (numbered.starts_with(". ") || numbered.starts_with(") ")) {
+        Some(line.len() - numbered.len() + 2)
+    } else {
+        None
+    }
+}
+
+/// The offset to cut `response` at, at or before `at`: the start of the last heading in the
+/// second half of what's kept, or else the last paragraph, or else the line `at` is in. Cuts are
+/// never made inside a fenced code block.
+pub fn section_boundary(response: &str, at: usize) -> usize {
+    let (mut heading, mut paragraph, mut line_start) = (None, None, 0);
+    let mut fence: Option<&str> = None;
+    let mut after_blank = false;
+    for (offset, line) in line_offsets(response) {
+        if offset > at {
+            break;
+        }
+        let trimmed = line.trim_start();
+        let fence_marker = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker));
+        if fence.is_none() {
+            line_start = offset;
+            if is_heading(line) {
+                heading = Some(offset);
+            } else if after_blank && !trimmed.is_empty() {
+                paragraph = Some(offset);
+            }
+        }
+        match (fence, fence_marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            _ => {}
+        }
+        after_blank = trimmed.is_empty();
+    }
+    heading
+        .filter(|&heading| heading > 0 && heading >= at / 2)
+        .or(paragraph.filter(|&paragraph| paragraph > 0))
+        .unwrap_or(line_start)
+}
+
+/// A short version of `response` made without a model: its headings and the first sentence of
+/// each distinct paragraph and list item, in order, up to `max_chars`. Code blocks are left out.
+pub fn local_summary(response: &str, max_chars: usize) -> String {
+    let mut seen = HashSet::new();
+    let mut lines: Vec<String> = Vec::new();
+    let mut length = 0;
+    let mut fence: Option<&str> = None;
+    let mut in_paragraph = false;
+
+    for (_, line) in line_offsets(response) {
+        let trimmed = line.trim();
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = match fence {
+                Some(open) if open == marker => None,
+                None => Some(marker),
+                open => open,
+            };
+            continue;
+        }
+        if fence.is_some() || trimmed.is_empty() {
+            in_paragraph = false;
+            continue;
+        }
+        let marker = list_marker_len(trimmed);
+        let item = marker.is_some();
+        let summary_line = if is_heading(trimmed) {
+            // A heading with nothing under it yet is replaced by this one
+            if lines.last().is_some_and(|last| is_heading(last)) {
+                length -= lines.pop().map_or(0, |last| last.len() + 1);
+            }
+            trimmed.to_string()
+        } else if item || !in_paragraph {
+            in_paragraph = !item;
+            if !seen.insert(normalize(trimmed)) {
+                continue;
+            }
+            first_sentence(trimmed, marker.unwrap_or(0)).to_string()
+        } else {
+            continue;
+        };
+        if length + summary_line.len() + 1 > max_chars {
+            break;
+        }
+        length += summary_line.len() + 1;
+        lines.push(summary_line);
+    }
+    if lines.last().is_some_and(|last| is_heading(last)) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// `text` up to the end of its first sentence, which starts `start` bytes in
+fn first_sentence(text: &str, start: usize) -> &str {
+    match text[start..].find(". ") {
+        Some(end) => &text[..start + end + 1],
+        None => text,
+    }
+}
+
+/// A response that may have been cut short: the response as the model gave it, and the version to
+/// show, which is the same unless it was a runaway.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CappedResponse {
+    pub raw: String,
+    /// What was kept of the response, a marker saying why the rest wasn't, and a summary
+    pub condensed: String,
+    pub runaway: Option<Runaway>,
+}
+
+impl CappedResponse {
+    /// What to post as a comment: only the condensed form
+    pub fn comment(&self) -> &str {
+        &self.condensed
+    }
+
+    /// What to keep in a transcript: the condensed form, then the raw response if it differs
+    pub fn transcript(&self) -> String {
+        match &self.runaway {
+            None => self.raw.clone(),
+            Some(_) => format!(
+                "{}\n\n---\n\n## Raw Response ({} characters)\n\n{}",
+                self.condensed,
+                self.raw.chars().count(),
+                self.raw
+            ),
+        }
+    }
+}
+
+/// Cut a runaway response short at a section boundary, and summarize the whole of it locally.
+pub fn cap_response(response: &str, limits: &RunawayLimits) -> CappedResponse {
+    cap_response_with(response, limits, |_| None)
+}
+
+/// As `cap_response`, but summarizing with `summarize`, such as a call to a cheap model. The local
+/// summary is used if it returns `None`.
+pub fn cap_response_with(
+    response: &str,
+    limits: &RunawayLimits,
+    summarize: impl FnOnce(&str) -> Option<String>,
+) -> CappedResponse {
+    let Some(runaway) = detect_runaway(response, limits) else {
+        return CappedResponse { raw: response.to_string(), condensed: response.to_string(), runaway: None };
+    };
+    warn!("Cutting the review short: {}", runaway.reason);
+    let kept = response[..section_boundary(response, runaway.at)].trim_end();
+    let summary = summarize(response).unwrap_or_else(|| local_summary(response, limits.summary_chars));
+    let condensed = format!(
+        "{}\n\n---\n\n**[Response truncated after {} of {} characters: {}.]**\n\n## Condensed Review\n\n{}\n",
+        kept,
+        kept.chars().count(),
+        response.chars().count(),
+        runaway.reason,
+        summary.trim()
+    );
+    CappedResponse { raw: response.to_string(), condensed, runaway: Some(runaway) }
+}
@@ -0,0 +1,36 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+// Cargo's default when a manifest doesn't set an edition
+const DEFAULT_EDITION: &str = "2015";
+
+/// The Rust edition set in `workspace_root/Cargo.toml`, from `package.edition` or, for a
+/// workspace, `workspace.package.edition`. `None` if there is no readable manifest.
+pub fn detect_rust_edition(workspace_root: &Path) -> Option<String> {
+    let path = workspace_root.join("Cargo.toml");
+    let contents = fs::read_to_string(&path).ok()?;
+    let manifest: Value = toml::from_str(&contents)
+        .map_err(|e| warn!("Could not parse {}: {}", path.display(), e))
+        .ok()?;
+
+    let edition = |table: Option<&Value>| {
+        table
+            .and_then(|table| table.get("edition"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    edition(manifest.get("package"))
+        .or_else(|| edition(manifest.get("workspace").and_then(|w| w.get("package"))))
+        .or_else(|| Some(DEFAULT_EDITION.to_string()))
+}
+
+/// The note added to the start of the system prompt for `edition`
+pub fn edition_note(edition: &str) -> String {
+    format!(
+        "This code targets Rust edition {}. Use edition-appropriate idioms.\n\n",
+        edition
+    )
+}
@@ -0,0 +1,212 @@
+use regex::Regex;
+
+use crate::citations::parse_citations;
+use crate::findings::{Finding, Severity};
+
+/// Sections of a review that the tool asked for and that hold no findings
+const SECTIONS_WITHOUT_FINDINGS: &[&str] = &["summary", "questions", "review confidence", "human attention"];
+
+/// The fewest words a comment may have; shorter ones are a file's confidence score or the like
+const MIN_COMMENT_WORDS: usize = 3;
+
+/// A banner warning that the findings were read out of prose, so they may be wrong or incomplete
+pub fn salvage_banner(error: &str, found: usize) -> String {
+    let message = format!(
+        "The review isn't the JSON --output-format json asks for ({}); {} {} read out of its prose \
+         instead, and may be incomplete or misplaced",
+        error,
+        found,
+        if found == 1 { "finding was" } else { "findings were" }
+    );
+    let rule = "=".repeat(message.chars().count().min(100));
+    format!("{}\n{}\n{}", rule, message, rule)
+}
+
+/// Best-effort findings from a review that should have been JSON but isn't, as when a model can't
+/// write JSON or its answer was cut off. Each list item, JSON object, or paragraph citing a line is
+/// a finding if it names a file: one of `paths`, the files in the diff, or with no `paths`, a
+/// `path:line` citation or a path in backticks. A file named in a heading counts for the items
+/// under it, and a diff of one file for every item. The severity is the first severity word in the item, or warning if it has none. The
+/// findings are marked `extracted`.
+pub fn extract_findings(review: &str, paths: &[String]) -> Vec<Finding> {
+    let heading = Regex::new(r"^\s*#+\s*(.*?)\s*#*\s*$").unwrap();
+    let mut findings = Vec::new();
+    let mut heading_path: Option<String> = None;
+    let mut skipping = false;
+    for (text, is_item) in items(review) {
+        if let Some(caps) = heading.captures(&text) {
+            let title = caps[1].trim_matches(|c| c == '`' || c == '*').to_string();
+            skipping = SECTIONS_WITHOUT_FINDINGS.contains(&title.to_lowercase().as_str());
+            heading_path = find_path(&title, paths);
+            continue;
+        }
+        if skipping {
+            continue;
+        }
+        let fields = JsonFields::parse(&text);
+        let line = fields.line.or_else(|| line_reference(&text));
+        if !is_item && fields.path.is_none() && line.is_none() {
+            continue;
+        }
+        let Some(path) = fields
+            .path
+            .as_deref()
+            .and_then(|path| resolve_path(path, paths))
+            .or_else(|| find_path(&text, paths))
+            .or_else(|| heading_path.clone())
+            .or_else(|| match paths {
+                [only] => Some(only.clone()),
+                _ => None,
+            })
+        else {
+            continue;
+        };
+        let comment = fields.comment.clone().unwrap_or_else(|| prose_comment(&text));
+        if comment.split_whitespace().count() < MIN_COMMENT_WORDS {
+            continue;
+        }
+        let severity = fields
+            .severity
+            .as_deref()
+            .and_then(severity_word)
+            .or_else(|| first_severity_word(&text))
+            .unwrap_or(Severity::Warning);
+        findings.push(Finding {
+            path,
+            line,
+            severity,
+            category: None,
+            comment,
+            follow_up: false,
+            policy_note: None,
+            confidence: None,
+            extracted: true,
+            lower_priority: false,
+        });
+    }
+    findings
+}
+
+/// The review split into headings, list items with the lines continuing them, JSON objects and
+/// paragraphs, each with whether it's an item or object rather than a paragraph or heading
+fn items(review: &str) -> Vec<(String, bool)> {
+    let bullet = Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+").unwrap();
+    let mut items: Vec<(String, bool)> = Vec::new();
+    let mut current: Option<(String, bool)> = None;
+    for line in review.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("<!--") || matches!(trimmed, "[" | "]" | "]," | "}" | "},") {
+            items.extend(current.take());
+            continue;
+        }
+        if trimmed.is_empty() {
+            items.extend(current.take());
+        } else if trimmed.starts_with('#') {
+            items.extend(current.take());
+            items.push((trimmed.to_string(), false));
+        } else if bullet.is_match(line) || trimmed.starts_with('{') {
+            items.extend(current.take());
+            current = Some((trimmed.to_string(), true));
+        } else if let Some((text, _)) = &mut current {
+            text.push(' ');
+            text.push_str(trimmed);
+        } else {
+            current = Some((trimmed.to_string(), false));
+        }
+    }
+    items.extend(current);
+    items
+}
+
+/// What an item written as a JSON object says, as far as it got
+#[derive(Debug, Default)]
+struct JsonFields {
+    path: Option<String>,
+    line: Option<usize>,
+    severity: Option<String>,
+    comment: Option<String>,
+}
+
+impl JsonFields {
+    fn parse(text: &str) -> Self {
+        let string = |key: &str| {
+            // The closing quote is missing from a string the response was cut off in
+            Regex::new(&format!(r#""{}"\s*:\s*"((?:[^"\\]|\\.)*)"#, key))
+                .unwrap()
+                .captures(text)
+                .map(|caps| caps[1].replace("\\\"", "\"").replace("\\n", " ").replace("\\\\", "\\"))
+        };
+        JsonFields {
+            path: string("file").or_else(|| string("path")),
+            line: Regex::new(r#""line"\s*:\s*(\d+)"#).unwrap().captures(text).and_then(|caps| caps[1].parse().ok()),
+            severity: string("severity"),
+            comment: string("comment").map(|comment| comment.trim().to_string()),
+        }
+    }
+}
+
+/// The line an item refers to: the first `path:line` citation, or `line 12`, `lines 12-14`, `L12`
+fn line_reference(text: &str) -> Option<usize> {
+    if let Some(citation) = parse_citations(text).first() {
+        return Some(citation.line);
+    }
+    Regex::new(r"(?i)\blines?\s+(\d+)|\bL(\d+)\b")
+        .unwrap()
+        .captures(text)
+        .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .and_then(|line| line.as_str().parse().ok())
+}
+
+/// The first file `text` names. With `paths`, that's one of them, found by any path ending in one
+/// or a path one ends in, so `cart.rs` names `src/cart.rs`. Without, only a citation or a path in
+/// backticks counts.
+fn find_path(text: &str, paths: &[String]) -> Option<String> {
+    let token = Regex::new(r"[\w./-]*\w\.\w+").unwrap();
+    if paths.is_empty() {
+        return parse_citations(text).first().map(|citation| citation.path.clone()).or_else(|| {
+            Regex::new(r"`([\w./-]+\.\w+)(?::\d+)?`").unwrap().captures(text).map(|caps| caps[1].to_string())
+        });
+    }
+    token.find_iter(text).find_map(|path| resolve_path(path.as_str(), paths))
+}
+
+/// The one of `paths` that `path` names, or `path` itself when there are no `paths` to check
+fn resolve_path(path: &str, paths: &[String]) -> Option<String> {
+    let path = path.trim_start_matches("./");
+    if paths.is_empty() {
+        return Some(path.to_string());
+    }
+    let path_ends = |long: &str, short: &str| long == short || long.ends_with(&format!("/{}", short));
+    paths.iter().find(|known| path_ends(known, path) || path_ends(path, known)).cloned()
+}
+
+/// The severity a word names, if it names one
+fn severity_word(word: &str) -> Option<Severity> {
+    match word.to_lowercase().as_str() {
+        "error" | "errors" | "critical" | "blocker" | "bug" | "high" | "major" | "severe" => Some(Severity::Error),
+        "warning" | "warnings" | "warn" | "medium" | "moderate" => Some(Severity::Warning),
+        "info" | "note" | "nit" | "nitpick" | "suggestion" | "minor" | "low" | "style" => Some(Severity::Info),
+        _ => None,
+    }
+}
+
+fn first_severity_word(text: &str) -> Option<Severity> {
+    Regex::new(r"[A-Za-z]+").unwrap().find_iter(text).find_map(|word| severity_word(word.as_str()))
+}
+
+/// An item's text without its bullet, its severity label or the place it's about
+fn prose_comment(text: &str) -> String {
+    // A label is set off by emphasis or brackets, or ends in a colon, so `Error handling` is left be
+    let label = Regex::new(
+        r"(?i)^\s*(?:[-*+]|\d+[.)])?\s*(?:(?:\*\*|__|\[)\s*(?:SEVERITIES)\s*:?\s*(?:\*\*|__|\])\s*[:\-–]?|(?:SEVERITIES)\s*:)\s*"
+            .replace("SEVERITIES", "error|warning|info|critical|major|minor|high|medium|low|nit|note|suggestion")
+            .as_str(),
+    )
+    .unwrap();
+    let place = Regex::new(r"^\(?`?[\w./-]+\.\w+(?::\d+(?:-\d+)?)?`?\)?\s*[:\-–]?\s*").unwrap();
+    let bullet = Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+").unwrap();
+    let text = label.replace(text, "");
+    let text = bullet.replace(&text, "");
+    let text = place.replace(&text, "");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
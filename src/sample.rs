@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::diff::FileDiff;
+
+/// Files whose diffs make the same change, with the ones kept in the prompt to represent them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SampledCluster {
+    /// Files included in the prompt
+    pub sampled: Vec<String>,
+    /// Files left out because they make the same change
+    pub omitted: Vec<String>,
+}
+
+/// Group files whose changes are the same once normalized: only added and removed lines count,
+/// whitespace is collapsed and digits are ignored, so a key renamed across many files or a version
+/// bumped in several places falls into one cluster. Files without hunks are never clustered.
+/// Returns the indexes of each cluster's files in diff order, clusters ordered by their first file.
+pub fn cluster_similar(files: &[FileDiff]) -> Vec<Vec<usize>> {
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut by_change: HashMap<String, usize> = HashMap::new();
+
+    for (i, file) in files.iter().enumerate() {
+        if file.hunks.is_empty() {
+            clusters.push(vec![i]);
+            continue;
+        }
+        let cluster = *by_change.entry(normalized_change(file)).or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[cluster].push(i);
+    }
+
+    clusters
+}
+
+/// Keep at most `per_cluster` files from each cluster of similar changes. Returns the files kept,
+/// in their original order, and the clusters that had files left out.
+pub fn sample_similar(files: Vec<FileDiff>, per_cluster: usize) -> (Vec<FileDiff>, Vec<SampledCluster>) {
+    let clusters = cluster_similar(&files);
+    let mut keep = vec![false; files.len()];
+    let mut sampled_clusters = Vec::new();
+
+    for cluster in clusters {
+        let (sampled, omitted) = cluster.split_at(per_cluster.min(cluster.len()));
+        sampled.iter().for_each(|&i| keep[i] = true);
+        if !omitted.is_empty() {
+            let paths = |indexes: &[usize]| indexes.iter().map(|&i| files[i].path.clone()).collect();
+            sampled_clusters.push(SampledCluster {
+                sampled: paths(sampled),
+                omitted: paths(omitted),
+            });
+        }
+    }
+
+    let files = files
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(file, keep)| keep.then_some(file))
+        .collect();
+    (files, sampled_clusters)
+}
+
+/// Describe the left-out files for the prompt, so the model knows it is looking at a sample
+pub fn sample_note(clusters: &[SampledCluster]) -> String {
+    let mut note = String::from(
+        "Some files make the same change as others, so only a representative sample of them is \
+         included in the diff. Judge each change once and assume it applies to every file listed \
+         with it.\n",
+    );
+    for cluster in clusters {
+        note.push_str(&format!(
+            "\n- {}: identical change applied to {} other files: {}",
+            cluster.sampled.join(", "),
+            cluster.omitted.len(),
+            cluster.omitted.join(", ")
+        ));
+    }
+    note
+}
+
+fn normalized_change(file: &FileDiff) -> String {
+    file.hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| line.starts_with('+') || line.starts_with('-'))
+        .map(|line| {
+            let collapsed = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+            collapsed.replace(|c: char| c.is_ascii_digit(), "")
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
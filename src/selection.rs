@@ -0,0 +1,296 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::process::Stdio;
+
+use crate::diff::parse_diff;
+use crate::error::ReviewError;
+use crate::vcs::{git_command, git_output, EMPTY_TREE};
+
+/// The commits --author and --since pick out of the history.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommitFilter {
+    /// A pattern matched against the author, as by `git log --author`
+    pub author: Option<String>,
+    /// A date, as by `git log --since`, e.g. `2025-08-01` or `1 week ago`
+    pub since: Option<String>,
+}
+
+impl CommitFilter {
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none() && self.since.is_none()
+    }
+
+    /// The `git log` options for the filter
+    pub fn log_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(author) = &self.author {
+            args.push(format!("--author={}", author));
+        }
+        if let Some(since) = &self.since {
+            args.push(format!("--since={}", since));
+        }
+        args
+    }
+}
+
+impl fmt::Display for CommitFilter {
+    /// e.g. `by authors matching "alice" since "1 week ago"`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut criteria = Vec::new();
+        if let Some(author) = &self.author {
+            criteria.push(format!("by authors matching {:?}", author));
+        }
+        if let Some(since) = &self.since {
+            criteria.push(format!("since {:?}", since));
+        }
+        f.write_str(&criteria.join(" "))
+    }
+}
+
+/// A commit the filter picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedCommit {
+    pub sha: String,
+    pub author: String,
+    pub subject: String,
+}
+
+impl SelectedCommit {
+    pub fn short(&self) -> &str {
+        &self.sha[..self.sha.len().min(7)]
+    }
+}
+
+// The format `parse_log` reads: sha, author and subject, separated by tabs
+pub(crate) const LOG_FORMAT: &str = "--format=%H%x09%an%x09%s";
+
+/// Parse `git log` output in `LOG_FORMAT`
+pub fn parse_log(output: &str) -> Vec<SelectedCommit> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            Some(SelectedCommit {
+                sha: fields.next().filter(|sha| !sha.is_empty())?.to_string(),
+                author: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The commits in `revisions` (HEAD if there are none) that `filter` picks, oldest first. Merges
+/// are left out, as the changes they bring in are in the commits they merge.
+pub fn selected_commits(filter: &CommitFilter, revisions: &[String]) -> Result<Vec<SelectedCommit>, ReviewError> {
+    let mut args = vec!["log".to_string(), "--reverse".to_string(), "--no-merges".to_string(), LOG_FORMAT.to_string()];
+    args.extend(filter.log_args());
+    if revisions.is_empty() {
+        args.push("HEAD".to_string());
+    } else {
+        args.extend(revisions.iter().cloned());
+    }
+    args.push("--".to_string());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    Ok(parse_log(&git_output(&args)?))
+}
+
+/// The selected commits squashed together: the tree they start from, and the tree with just their
+/// changes applied to it, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombinedCommits {
+    pub base: String,
+    pub tree: String,
+}
+
+/// Apply each of `commits`' changes in turn, starting from the parent of the oldest, leaving out
+/// the commits between them. Fails, naming the commit, when one only makes sense on top of
+/// changes that were left out.
+pub fn combine_commits(commits: &[SelectedCommit]) -> Result<CombinedCommits, ReviewError> {
+    let oldest = commits.first().ok_or_else(|| ReviewError::Config("no commits to combine".to_string()))?;
+    let base = parent(&oldest.sha)?;
+    // A throwaway index, so the repository's own isn't touched
+    let index = std::env::temp_dir().join(format!("llm_code_review_selection_{}.index", std::process::id()));
+    let result = (|| {
+        index_command(&index, &["read-tree", &base], None)?;
+        for commit in commits {
+            let patch = git_output(&["diff", "--binary", "--full-index", &parent(&commit.sha)?, &commit.sha])?;
+            if patch.is_empty() {
+                continue;
+            }
+            index_command(&index, &["apply", "--cached", "--3way", "--whitespace=nowarn"], Some(&patch)).map_err(|e| {
+                ReviewError::GitFailed(format!(
+                    "{} ({}) can't be applied without the commits left out between it and the other selected \
+                     commits; review a range of commits instead: {}",
+                    commit.short(),
+                    commit.subject,
+                    e.to_string().trim()
+                ))
+            })?;
+        }
+        index_command(&index, &["write-tree"], None)
+    })();
+    let _ = fs::remove_file(&index);
+    Ok(CombinedCommits { base, tree: result?.trim().to_string() })
+}
+
+/// `commit`'s parent, or the empty tree for a root commit
+fn parent(commit: &str) -> Result<String, ReviewError> {
+    match git_output(&["rev-parse", "--verify", "--quiet", &format!("{}^", commit)]) {
+        Ok(parent) => Ok(parent.trim().to_string()),
+        Err(_) => Ok(EMPTY_TREE.to_string()),
+    }
+}
+
+/// Run git on the index file at `index`, with `input` on standard input
+fn index_command(index: &std::path::Path, args: &[&str], input: Option<&str>) -> Result<String, ReviewError> {
+    let mut command = git_command();
+    command.env("GIT_INDEX_FILE", index).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    debug!("Running command: {:?}", command);
+    let mut child = command.spawn().map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(input.unwrap_or_default().as_bytes())
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+    drop(stdin);
+    let output = child.wait_with_output().map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(ReviewError::GitFailed(String::from_utf8_lossy(&output.stderr).to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Lines a selected commit added that a commit outside the selection changed or removed later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub path: String,
+    /// The first and last of the lines, numbered as in the selected commit
+    pub lines: (usize, usize),
+    /// The selected commit that added them
+    pub selected: String,
+    /// The commit that changed them, and its author
+    pub changed_by: String,
+    pub author: String,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = match self.lines {
+            (first, last) if first == last => format!("line {} from {} was", first, self.selected),
+            (first, last) => format!("lines {}-{} from {} were", first, last, self.selected),
+        };
+        write!(f, "`{}` {} changed later by {} ({})", self.path, lines, self.changed_by, self.author)
+    }
+}
+
+/// The line numbers in the starting revision of `git blame --reverse --porcelain`, each with the
+/// last commit the line was still there in
+pub fn parse_reverse_blame(porcelain: &str) -> Vec<(usize, String)> {
+    let header = Regex::new(r"^([0-9a-f]{40,64}) \d+ (\d+)").unwrap();
+    porcelain
+        .lines()
+        .filter_map(|line| {
+            let caps = header.captures(line)?;
+            Some((caps[2].parse().ok()?, caps[1].to_string()))
+        })
+        .collect()
+}
+
+/// The lines `commits` added that commits outside the selection changed before `tip`, so the
+/// combined diff shows them as they were, not as they are now.
+pub fn later_overlaps(commits: &[SelectedCommit], tip: &str) -> Result<Vec<Overlap>, ReviewError> {
+    let tip = git_output(&["rev-parse", "--verify", tip])?.trim().to_string();
+    let selected: Vec<&str> = commits.iter().map(|commit| commit.sha.as_str()).collect();
+    // The commit after each one that outlived a line, and its author
+    let mut changers: HashMap<String, Option<(String, String)>> = HashMap::new();
+    let mut overlaps = Vec::new();
+
+    for commit in commits {
+        let diff = git_output(&["diff", "-U0", "--no-renames", &parent(&commit.sha)?, &commit.sha])?;
+        for file in parse_diff(&diff) {
+            let Some(path) = file.new_path().map(str::to_string) else {
+                continue;
+            };
+            let added: Vec<usize> = file
+                .hunks
+                .iter()
+                .filter_map(|hunk| Some((hunk.range()?, hunk)))
+                .flat_map(|(range, hunk)| {
+                    hunk.lines.iter().filter(|line| !line.starts_with('-') && !line.starts_with('\\')).enumerate().filter_map(
+                        move |(offset, line)| line.starts_with('+').then_some(range.new_start + offset),
+                    )
+                })
+                .collect();
+            if added.is_empty() {
+                continue;
+            }
+            let range = format!("{}..{}", commit.sha, tip);
+            let Ok(blame) = git_output(&["blame", "--reverse", "--porcelain", &range, "--", &path]) else {
+                continue;
+            };
+
+            let mut current: Option<Overlap> = None;
+            for (line, last_seen) in parse_reverse_blame(&blame) {
+                let changer = if !added.contains(&line) || last_seen == tip {
+                    None
+                } else {
+                    changers.entry(last_seen.clone()).or_insert_with(|| next_commit(&last_seen, &tip)).clone()
+                };
+                let changer = changer.filter(|(sha, _)| !selected.contains(&sha.as_str()));
+                match (&mut current, changer) {
+                    (Some(overlap), Some((sha, _))) if overlap.changed_by == sha[..7] && overlap.lines.1 + 1 == line => {
+                        overlap.lines.1 = line;
+                    }
+                    (_, changer) => {
+                        overlaps.extend(current.take());
+                        current = changer.map(|(sha, author)| Overlap {
+                            path: path.clone(),
+                            lines: (line, line),
+                            selected: commit.short().to_string(),
+                            changed_by: sha[..7].to_string(),
+                            author,
+                        });
+                    }
+                }
+            }
+            overlaps.extend(current);
+        }
+    }
+    Ok(overlaps)
+}
+
+/// The commit after `commit` on the way to `tip`, and its author
+fn next_commit(commit: &str, tip: &str) -> Option<(String, String)> {
+    let range = format!("{}..{}", commit, tip);
+    let output = git_output(&["log", "--ancestry-path", "--reverse", LOG_FORMAT, &range]).ok()?;
+    let next = parse_log(&output).into_iter().next()?;
+    Some((next.sha, next.author))
+}
+
+/// The prompt section describing the selection: the criteria, the commits and any overlaps
+pub fn selection_note(filter: &CommitFilter, commits: &[SelectedCommit], overlaps: &[Overlap]) -> String {
+    let mut note = format!(
+        "Only the changes made in the {} {} {}, combined into one diff; changes made between them by \
+         others are left out.\n",
+        commits.len(),
+        if commits.len() == 1 { "commit" } else { "commits" },
+        filter
+    );
+    for commit in commits {
+        note.push_str(&format!("\n- {} {} ({})", commit.short(), commit.subject, commit.author));
+    }
+    if !overlaps.is_empty() {
+        note.push_str(
+            "\n\nLater commits outside the selection changed some of these lines, so the code may \
+             differ from what is shown:\n",
+        );
+        for overlap in overlaps {
+            note.push_str(&format!("\n- {}", overlap));
+        }
+    }
+    note
+}
@@ -0,0 +1,148 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::ReviewError;
+use crate::findings::{Finding, Severity};
+
+/// The least severity findings on the paths matching a glob are given, whatever the model said.
+#[derive(Debug, Clone)]
+pub struct SeverityFloor {
+    /// e.g. `billing/**` or `*.sql`
+    pub pattern: String,
+    pub severity: Severity,
+    regex: Regex,
+}
+
+impl SeverityFloor {
+    pub fn new(pattern: &str, severity: Severity) -> Self {
+        SeverityFloor { pattern: pattern.to_string(), severity, regex: glob_regex(pattern) }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+impl PartialEq for SeverityFloor {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.severity == other.severity
+    }
+}
+
+impl FromStr for SeverityFloor {
+    type Err = String;
+
+    /// `GLOB=SEVERITY`, as --severity-floor takes it
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, severity) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("`{}` is not GLOB=SEVERITY, e.g. billing/**=error", s))?;
+        if pattern.is_empty() {
+            return Err(format!("`{}` has no glob before the =", s));
+        }
+        Ok(SeverityFloor::new(pattern, severity.parse()?))
+    }
+}
+
+/// A regex matching the paths `pattern` does: `**` matches any number of directories, `*` and
+/// `?` anything within one. A pattern without a `/` matches the file name at any depth, as in
+/// `.gitignore`.
+pub fn glob_regex(pattern: &str) -> Regex {
+    let mut regex = String::from(if pattern.contains('/') { "^" } else { "(^|/)" });
+    let mut chars = pattern.trim_start_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` may match no directories at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+/// Appended to the system prompt with --min-severity, unless every severity is wanted
+pub fn min_severity_instructions(min: Severity) -> Option<String> {
+    let wanted: Vec<String> = Severity::ALL.iter().filter(|severity| **severity <= min).map(Severity::to_string).collect();
+    (wanted.len() < Severity::ALL.len()).then(|| {
+        format!("\nReport only findings of severity {}, and leave out anything less serious.\n", wanted.join(" or "))
+    })
+}
+
+#[derive(Deserialize)]
+struct PolicyFile {
+    rubric: Option<String>,
+    #[serde(default)]
+    floor: Vec<FloorEntry>,
+}
+
+#[derive(Deserialize)]
+struct FloorEntry {
+    path: String,
+    severity: String,
+}
+
+/// A team's definition of severity: the rubric the model is given, and the floors enforced on the
+/// findings afterwards.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SeverityPolicy {
+    /// Replaces the severity rubric section of the system prompt
+    pub rubric: Option<String>,
+    pub floors: Vec<SeverityFloor>,
+}
+
+impl SeverityPolicy {
+    /// Read a TOML file with an optional `rubric` and `[[floor]]` tables of `path` and `severity`.
+    pub fn load(path: &Path) -> Result<Self, ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        let config_error = |msg: String| ReviewError::Config(format!("{}: {}", path.display(), msg));
+
+        let file: PolicyFile = toml::from_str(&contents).map_err(|e| config_error(e.to_string()))?;
+        let floors = file
+            .floor
+            .iter()
+            .map(|floor| Ok(SeverityFloor::new(&floor.path, floor.severity.parse().map_err(config_error)?)))
+            .collect::<Result<Vec<_>, ReviewError>>()?;
+        Ok(SeverityPolicy { rubric: file.rubric, floors })
+    }
+
+    /// The most severe floor for `path`, if any match it
+    pub fn floor_for(&self, path: &str) -> Option<&SeverityFloor> {
+        self.floors.iter().filter(|floor| floor.matches(path)).min_by_key(|floor| floor.severity)
+    }
+
+    /// Raise each finding less severe than its path's floor to that floor, saying why in its
+    /// `policy_note`. Returns the number raised.
+    pub fn enforce(&self, findings: &mut [Finding]) -> usize {
+        let mut raised = 0;
+        for finding in findings.iter_mut() {
+            let Some(floor) = self.floor_for(&finding.path) else {
+                continue;
+            };
+            if finding.severity > floor.severity {
+                finding.severity = floor.severity;
+                finding.policy_note = Some(format!(
+                    "severity raised to {} by policy: path matches {}",
+                    floor.severity, floor.pattern
+                ));
+                raised += 1;
+            }
+        }
+        raised
+    }
+}
@@ -0,0 +1,238 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::budget::{fit_to_budget, Budget, TokenEstimator};
+use crate::compress::trim_context;
+use crate::diff::{parse_diff, render_diff, FileDiff, Hunk};
+use crate::error::ReviewError;
+
+/// The largest diff, in estimated tokens, that goes into the prompt as it is, unless the
+/// provider's `small_diff_tokens` says otherwise
+pub const DEFAULT_SMALL_DIFF_TOKENS: usize = 2_000;
+
+/// Values accepted by `--chunk-mode`
+pub const CHUNK_MODES: &[&str] = &["none", "per-file", "auto"];
+
+/// How a diff is handled, going by its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeTier {
+    /// Small enough to go into the prompt as it is
+    Small,
+    /// Has its context lines reduced until it fits in the budget
+    Medium,
+    /// Too large to fit even without context lines, so it's split into parts reviewed one by one
+    Large,
+}
+
+impl fmt::Display for SizeTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SizeTier::Small => "small",
+            SizeTier::Medium => "medium",
+            SizeTier::Large => "large",
+        })
+    }
+}
+
+/// Whether a diff is reviewed in chunks of whole files, each with all its context, rather than
+/// having its context reduced to fit.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ChunkMode {
+    /// Go by the size tiers
+    #[default]
+    None,
+    /// Always split the diff into chunks of whole files
+    PerFile,
+    /// Split the diff into chunks of whole files if it doesn't fit in the budget as it is
+    Auto,
+}
+
+impl FromStr for ChunkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(ChunkMode::None),
+            "per-file" => Ok(ChunkMode::PerFile),
+            "auto" => Ok(ChunkMode::Auto),
+            _ => Err(format!("unknown chunk mode `{}`", s)),
+        }
+    }
+}
+
+/// Where the tiers start, from the provider config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TierThresholds {
+    /// Diffs up to this many estimated tokens are small, if they fit in the budget
+    pub small_max_tokens: usize,
+}
+
+impl Default for TierThresholds {
+    fn default() -> Self {
+        TierThresholds { small_max_tokens: DEFAULT_SMALL_DIFF_TOKENS }
+    }
+}
+
+/// The tier for `diff`: small if it's within `thresholds` and the budget, medium if it fits in
+/// the budget once its context lines are gone, and large otherwise.
+pub fn choose_tier(
+    diff: &str,
+    budget: &Budget,
+    thresholds: &TierThresholds,
+    estimator: &dyn TokenEstimator,
+) -> SizeTier {
+    let tokens = estimator.estimate_tokens(diff);
+    if tokens <= thresholds.small_max_tokens && budget.fits(tokens) {
+        return SizeTier::Small;
+    }
+    let without_context = render_diff(&trim_context(parse_diff(diff), 0));
+    if budget.fits(estimator.estimate_tokens(&without_context)) {
+        SizeTier::Medium
+    } else {
+        SizeTier::Large
+    }
+}
+
+/// Split `diff` into parts that each fit in `budget`, keeping whole files together where they fit
+/// and splitting the rest between hunks. Each part has as much of the `context_lines` as fits. A
+/// hunk too large on its own is cut into consecutive hunks at line boundaries, with a warning
+/// naming its file; only a single line too large for the budget fails.
+pub fn split_into_parts(
+    diff: &str,
+    context_lines: usize,
+    budget: &Budget,
+    estimator: &dyn TokenEstimator,
+) -> Result<Vec<String>, ReviewError> {
+    // The pieces to pack: each file, fitted on its own, or else each of its hunks
+    let mut pieces: Vec<FileDiff> = Vec::new();
+    for file in parse_diff(diff) {
+        match fit_to_budget(&render_diff(std::slice::from_ref(&file)), context_lines, budget, estimator) {
+            Ok(fitted) => pieces.extend(parse_diff(&fitted.diff)),
+            Err(ReviewError::DiffTooLarge { .. }) if !file.hunks.is_empty() => {
+                for hunk in &file.hunks {
+                    let piece = FileDiff { hunks: vec![hunk.clone()], ..file.clone() };
+                    match fit_to_budget(&render_diff(std::slice::from_ref(&piece)), context_lines, budget, estimator) {
+                        Ok(fitted) => pieces.extend(parse_diff(&fitted.diff)),
+                        Err(ReviewError::DiffTooLarge { .. }) => {
+                            warn!("A hunk of {} is too large for one part, so it's split across parts", file.path);
+                            let hunks = split_hunk(&piece, budget, estimator)?;
+                            pieces.extend(hunks.into_iter().map(|hunk| FileDiff { hunks: vec![hunk], ..file.clone() }));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut parts: Vec<Vec<FileDiff>> = Vec::new();
+    let mut part: Vec<FileDiff> = Vec::new();
+    for piece in pieces {
+        let mut grown = part.clone();
+        match grown.last_mut() {
+            // Hunks of a file split between pieces stay under one header
+            Some(last) if last.path == piece.path && last.header == piece.header => {
+                last.hunks.extend(piece.hunks.clone())
+            }
+            _ => grown.push(piece.clone()),
+        }
+        if part.is_empty() || budget.fits(estimator.estimate_tokens(&render_diff(&grown))) {
+            part = grown;
+        } else {
+            parts.push(part);
+            part = vec![piece];
+        }
+    }
+    if !part.is_empty() {
+        parts.push(part);
+    }
+    Ok(parts.iter().map(|part| render_diff(part)).collect())
+}
+
+/// The only hunk of `file` cut into consecutive hunks that each fit in `budget` under the file's
+/// header, with their line ranges worked out from the original's. Fails if a single line doesn't
+/// fit, or the hunk's header can't be parsed.
+fn split_hunk(file: &FileDiff, budget: &Budget, estimator: &dyn TokenEstimator) -> Result<Vec<Hunk>, ReviewError> {
+    let hunk = &file.hunks[0];
+    let too_large = || ReviewError::DiffTooLarge {
+        estimated_tokens: estimator.estimate_tokens(&render_diff(std::slice::from_ref(file))),
+        max_tokens: budget.remaining(),
+    };
+    let range = hunk.range().ok_or_else(too_large)?;
+    // The function name git puts after the ranges, if any
+    let section = hunk.header.splitn(3, "@@").nth(2).unwrap_or_default().trim_end().to_string();
+    // A range of no lines starts at the line before it
+    let mut old_line = range.old_start + usize::from(range.old_len == 0);
+    let mut new_line = range.new_start + usize::from(range.new_len == 0);
+
+    let sub_hunk = |lines: &[String], old_line: usize, new_line: usize| {
+        let old_len = lines.iter().filter(|line| line.starts_with(' ') || line.starts_with('-')).count();
+        let new_len = lines.iter().filter(|line| line.starts_with(' ') || line.starts_with('+')).count();
+        let start = |line: usize, len: usize| if len == 0 { line.saturating_sub(1) } else { line };
+        Hunk {
+            header: format!(
+                "@@ -{},{} +{},{} @@{}",
+                start(old_line, old_len),
+                old_len,
+                start(new_line, new_len),
+                new_len,
+                section
+            ),
+            lines: lines.to_vec(),
+        }
+    };
+    let fits = |hunk: Hunk| {
+        let piece = FileDiff { hunks: vec![hunk], ..file.clone() };
+        budget.fits(estimator.estimate_tokens(&render_diff(&[piece])))
+    };
+
+    let mut hunks = Vec::new();
+    let mut rest = &hunk.lines[..];
+    while !rest.is_empty() {
+        // The most lines that fit, found by bisection: `fitting` lines fit and `too_many` don't
+        let (mut fitting, mut too_many) = (0, rest.len() + 1);
+        while too_many - fitting > 1 {
+            let middle = fitting + (too_many - fitting) / 2;
+            if fits(sub_hunk(&rest[..middle], old_line, new_line)) {
+                fitting = middle;
+            } else {
+                too_many = middle;
+            }
+        }
+        if fitting == 0 {
+            return Err(too_large());
+        }
+        let (lines, after) = rest.split_at(fitting);
+        hunks.push(sub_hunk(lines, old_line, new_line));
+        old_line += lines.iter().filter(|line| line.starts_with(' ') || line.starts_with('-')).count();
+        new_line += lines.iter().filter(|line| line.starts_with(' ') || line.starts_with('+')).count();
+        rest = after;
+    }
+    Ok(hunks)
+}
+
+/// Pack the per-file diffs `files`, as `(path, diff)` pairs from `split_diff_by_file`, into as few
+/// chunks as fit in `budget`, keeping each file whole and the files in order. A file too large for
+/// the budget on its own gets a chunk to itself, for the caller to split further.
+pub fn group_into_chunks(files: &[(String, String)], budget: &Budget, estimator: &dyn TokenEstimator) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    for (_, file_diff) in files {
+        let grown = format!("{}{}", chunk, file_diff);
+        if chunk.is_empty() || budget.fits(estimator.estimate_tokens(&grown)) {
+            chunk = grown;
+        } else {
+            chunks.push(chunk);
+            chunk = file_diff.clone();
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
@@ -0,0 +1,202 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::time::Instant;
+
+use crate::budget::BudgetReport;
+use crate::diff::Diff;
+use crate::doctor::{check_credentials, check_round_trip, render_checks, round_trip, Check, CheckStatus};
+use crate::findings::{render_findings, Finding};
+use crate::output::OutputFormat;
+use crate::review::{check_hard_cap, filter_diff, prepare, render_prompt, scrub_paths, try_prompt_parts, Cli, PromptParts};
+use crate::review_length::diff_changed_lines;
+
+/// The change --smoke-test reviews, so it needs nothing from the repository
+pub const SMOKE_TEST_DIFF: &str = include_str!("rsc/smoke_test.diff");
+
+/// What the mock provider answers the sample change with
+pub const MOCK_FINDINGS: &str = include_str!("rsc/smoke_test_findings.json");
+
+/// The stages of the pipeline, in the order they run
+pub const STAGES: [&str; 8] = ["configuration", "diff", "filter", "prompt", "budget", "provider", "findings", "output"];
+
+/// Review the sample change with the options given, checking each stage's output, and print a
+/// PASS or FAIL row per stage. Returns the exit status: 1 if any stage failed. With --live, the
+/// configured provider is sent a test request in place of asking the mock.
+pub fn run_smoke_test(cli: &mut Cli) -> i32 {
+    let started = Instant::now();
+    let stages = smoke_test(cli);
+    println!("{}", render_checks(&stages));
+    println!("Finished in {} ms", started.elapsed().as_millis());
+    if stages.iter().any(|stage| stage.status == CheckStatus::Fail) { 1 } else { 0 }
+}
+
+/// Run the stages, skipping the rest once one fails
+pub fn smoke_test(cli: &mut Cli) -> Vec<Check> {
+    let mut stages = Vec::new();
+    run_stages(cli, &mut stages);
+    for name in STAGES.iter().skip(stages.len()) {
+        stages.push(Check::new(name, CheckStatus::Skip, "an earlier stage failed"));
+    }
+    stages
+}
+
+fn run_stages(cli: &mut Cli, stages: &mut Vec<Check>) -> Option<()> {
+    let configured = prepare(cli, Instant::now()).map_err(|e| e.to_string()).map(|()| ((), configuration(cli)));
+    stage(stages, "configuration", configured)?;
+    stage(stages, "diff", check_diff(SMOKE_TEST_DIFF))?;
+    let filtered = stage(stages, "filter", check_filter(cli, SMOKE_TEST_DIFF))?;
+    let (parts, prompt) = stage(stages, "prompt", check_prompt(cli, &filtered))?;
+    stage(stages, "budget", check_budget(cli, &filtered, &parts, &prompt))?;
+    let reply = stage(stages, "provider", if cli.live { check_live(cli) } else { Ok(mock_reply()) })?;
+    let findings = stage(stages, "findings", check_findings(cli, &reply, &filtered))?;
+    stage(stages, "output", check_output(cli, &findings))
+}
+
+/// Record how the stage went, passing on its output if it succeeded
+fn stage<T>(stages: &mut Vec<Check>, name: &'static str, result: Result<(T, String), String>) -> Option<T> {
+    match result {
+        Ok((output, detail)) => {
+            stages.push(Check::new(name, CheckStatus::Pass, detail));
+            Some(output)
+        }
+        Err(e) => {
+            stages.push(Check::new(name, CheckStatus::Fail, e));
+            None
+        }
+    }
+}
+
+fn configuration(cli: &Cli) -> String {
+    match cli.provider() {
+        Some(provider) => format!("provider {}", provider.name),
+        None => "no provider configured".to_string(),
+    }
+}
+
+/// The diff parses into its files and renders back unchanged
+pub fn check_diff(text: &str) -> Result<((), String), String> {
+    let diff = Diff::parse(text);
+    if diff.render() != text {
+        return Err("the diff changed when parsed and rendered again".to_string());
+    }
+    if diff.files.is_empty() || diff.files.iter().any(|file| file.hunks.is_empty()) {
+        return Err("the diff has no files, or a file with no hunks".to_string());
+    }
+    Ok(((), format!("{} files, {} changed lines", diff.files.len(), diff_changed_lines(text))))
+}
+
+/// The filters and path scrubbing leave some of the diff, and only files that were in it
+fn check_filter(cli: &Cli, text: &str) -> Result<(String, String), String> {
+    let (filtered, _) = scrub_paths(cli, filter_diff(cli, text));
+    let paths: Vec<String> = Diff::parse(text).files.into_iter().map(|file| file.path).collect();
+    let kept = Diff::parse(&filtered).files;
+    if kept.is_empty() {
+        return Err("the filters left nothing of the diff; check --min-change-size and --api-only".to_string());
+    }
+    if let Some(file) = kept.iter().find(|file| !paths.contains(&file.path)) {
+        return Err(format!("the filtered diff has a file that wasn't in the diff: {}", file.path));
+    }
+    let detail = format!("{} of {} files kept, {} characters", kept.len(), paths.len(), filtered.len());
+    Ok((filtered, detail))
+}
+
+/// The prompt holds every line of the diff, and the instructions for the output format
+fn check_prompt(cli: &Cli, diff: &str) -> Result<((PromptParts, String), String), String> {
+    let parts = try_prompt_parts(cli, diff, &[]).map_err(|e| e.to_string())?;
+    let prompt = render_prompt(cli, &parts);
+    check_hard_cap(&prompt, cli.hard_max_chars, &cli.token_estimator(), cli.effective_budget())
+        .map_err(|e| e.to_string())?;
+    if let Some(line) = parts.diff.lines().find(|line| !prompt.contains(line)) {
+        return Err(format!("the prompt is missing the diff line `{}`", line));
+    }
+    if let Some(format) = cli.output_format.as_deref().and_then(|format| format.parse::<OutputFormat>().ok())
+        && !prompt.contains(format.instructions(cli.language()).trim())
+    {
+        return Err(format!("the prompt is missing the instructions for --output-format {:?}", format));
+    }
+    let detail = format!("{} characters, the diff under `# {}`", prompt.len(), parts.diff_heading);
+    Ok(((parts, prompt), detail))
+}
+
+/// The budget report's sums agree with the prompt, and the prompt fits the budget
+fn check_budget(cli: &Cli, diff: &str, parts: &PromptParts, prompt: &str) -> Result<((), String), String> {
+    let estimator = cli.token_estimator();
+    let mut report = BudgetReport::from_diffs(SMOKE_TEST_DIFF, diff, false, cli.effective_budget(), &estimator);
+    report.record_prompt(prompt, &parts.named(), &estimator);
+
+    let sections: usize = report.sections.values().sum();
+    if sections > report.total_chars {
+        return Err(format!("the parts add up to {} characters, more than the prompt's {}", sections, report.total_chars));
+    }
+    if report.used_tokens() > report.estimated_tokens {
+        return Err(format!(
+            "the files come to ~{} tokens, more than the whole prompt's ~{}",
+            report.used_tokens(),
+            report.estimated_tokens
+        ));
+    }
+    let percent = report.estimated_tokens as f64 * 100.0 / report.budget.max(1) as f64;
+    if (percent - report.utilization_pct).abs() > 0.01 {
+        return Err(format!("the report says {:.1}% of the budget, not {:.1}%", report.utilization_pct, percent));
+    }
+    if report.estimated_tokens > report.budget {
+        return Err(format!("the prompt is ~{} tokens, over the budget of {}", report.estimated_tokens, report.budget));
+    }
+    Ok(((), format!("~{} of {} tokens ({:.1}%)", report.estimated_tokens, report.budget, report.utilization_pct)))
+}
+
+fn mock_reply() -> (String, String) {
+    (MOCK_FINDINGS.to_string(), "the mock provider answered; use --live to try yours".to_string())
+}
+
+/// With --live, send the configured provider the `doctor` test request. Reviews aren't requested
+/// from providers yet, so the mock's findings are passed on.
+fn check_live(cli: &Cli) -> Result<(String, String), String> {
+    let provider = cli.provider().ok_or("--live needs a provider from --llm-provider-config")?;
+    let credentials = check_credentials(provider, |var| std::env::var(var).ok());
+    if credentials.status == CheckStatus::Fail {
+        return Err(credentials.detail);
+    }
+    let check = check_round_trip(&provider.endpoint, round_trip(provider));
+    if check.status == CheckStatus::Fail {
+        return Err(check.detail);
+    }
+    Ok((MOCK_FINDINGS.to_string(), format!("{}; using the mock's findings", check.detail)))
+}
+
+/// The reply parses as findings on files in the diff, and the severity policy applies to them
+fn check_findings(cli: &Cli, reply: &str, diff: &str) -> Result<(Vec<Finding>, String), String> {
+    let mut findings: Vec<Finding> =
+        serde_json::from_str(reply).map_err(|e| format!("the reply isn't a list of findings: {}", e))?;
+    let paths: Vec<String> = Diff::parse(diff).files.into_iter().map(|file| file.path).collect();
+    if let Some(finding) = findings.iter().find(|finding| !paths.contains(&finding.path)) {
+        return Err(format!("a finding is on a file that isn't in the diff: {}", finding.path));
+    }
+    let raised = cli.severity_policy.enforce(&mut findings);
+    let detail = format!("{} findings, {} raised by the severity policy", findings.len(), raised);
+    Ok((findings, detail))
+}
+
+/// The findings render in the output format, each one in its file's section
+fn check_output(cli: &Cli, findings: &[Finding]) -> Result<((), String), String> {
+    let name = cli.output_format.as_deref().unwrap_or("markdown");
+    let format: OutputFormat = name.parse()?;
+    let locale = cli.locale();
+    let rendered = render_findings(findings, format, locale);
+    let findings_heading = locale.text("Findings");
+    let heading = match format {
+        OutputFormat::AsciiDoc => format!("\n== {}\n", findings_heading),
+        OutputFormat::MediaWiki => format!("\n== {} ==\n", findings_heading),
+        OutputFormat::Html => format!("\">{}</h2>\n", findings_heading),
+        OutputFormat::Jira => format!("\nh2. {}\n", findings_heading),
+        OutputFormat::Json => "\"file\": ".to_string(),
+        _ => format!("\n## {}\n", findings_heading),
+    };
+    if !rendered.contains(&heading) {
+        return Err(format!("the {} output has no findings heading", name));
+    }
+    if let Some(finding) = findings.iter().find(|finding| !rendered.contains(&finding.comment)) {
+        return Err(format!("the {} output is missing the finding on {}", name, finding.path));
+    }
+    Ok(((), format!("{} findings as {}, {} characters", findings.len(), name, rendered.len())))
+}
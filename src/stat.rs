@@ -0,0 +1,56 @@
+use regex::Regex;
+
+/// Format `git diff --stat` output as a Markdown table with a row per file and a total row.
+/// Insertions and deletions are read from each line's `+`/`-` graph, scaled to its change count
+/// in case git shrank the graph to fit; binary files show `-` for both. Change% is each file's
+/// share of all changed lines. Lines that aren't per-file stat lines, such as git's own summary
+/// line, are skipped.
+pub fn format_stat_as_table(stat: &str) -> String {
+    let text_line = Regex::new(r"^\s*(.+?)\s+\|\s+(\d+)\s*(\+*)(-*)\s*$").unwrap();
+    let binary_line = Regex::new(r"^\s*(.+?)\s+\|\s+Bin\b").unwrap();
+
+    // (path, insertions and deletions, or None for a binary file)
+    let mut rows: Vec<(String, Option<(usize, usize)>)> = Vec::new();
+    for line in stat.lines() {
+        if let Some(caps) = text_line.captures(line) {
+            let changes: usize = caps[2].parse().unwrap_or_default();
+            let (plus, minus) = (caps[3].len(), caps[4].len());
+            let insertions = match plus + minus {
+                0 => 0,
+                graph => (changes * plus + graph / 2) / graph,
+            };
+            rows.push((caps[1].to_string(), Some((insertions, changes - insertions))));
+        } else if let Some(caps) = binary_line.captures(line) {
+            rows.push((caps[1].to_string(), None));
+        }
+    }
+
+    let (total_insertions, total_deletions) = rows
+        .iter()
+        .filter_map(|(_, counts)| *counts)
+        .fold((0, 0), |(i, d), (ins, del)| (i + ins, d + del));
+    let total_changes = total_insertions + total_deletions;
+    let percent = |changes: usize| format!("{:.1}%", changes as f64 * 100.0 / total_changes.max(1) as f64);
+
+    let mut table = String::from("| File | Insertions | Deletions | Change% |\n|---|---:|---:|---:|\n");
+    for (path, counts) in &rows {
+        match counts {
+            Some((insertions, deletions)) => table.push_str(&format!(
+                "| `{}` | {} | {} | {} |\n",
+                path,
+                insertions,
+                deletions,
+                percent(insertions + deletions)
+            )),
+            None => table.push_str(&format!("| `{}` (binary) | - | - | - |\n", path)),
+        }
+    }
+    table.push_str(&format!(
+        "| **Total: {} files** | {} | {} | {} |",
+        rows.len(),
+        total_insertions,
+        total_deletions,
+        percent(total_changes)
+    ));
+    table
+}
@@ -0,0 +1,178 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::ReviewError;
+use crate::findings::Finding;
+
+/// The host --status-server binds to when it's only given a port
+pub const DEFAULT_STATUS_HOST: &str = "127.0.0.1";
+
+/// How often the server checks whether the run has finished between requests
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How the run is going, as `GET /status` reports it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunStatus {
+    /// What the run is doing: `starting`, `diff`, `context`, `prompt`, `review` or `done`
+    pub phase: String,
+    /// The prompts the diff was split into, or 1 if it wasn't split
+    pub chunks_total: usize,
+    pub chunks_done: usize,
+    /// The chunks skipped by pressing Enter, which count as done but weren't reviewed
+    pub chunks_skipped: usize,
+    /// Estimated tokens in the prompts written or sent so far
+    pub prompt_tokens: usize,
+    /// Estimated tokens in the reviews received so far
+    pub response_tokens: usize,
+    /// The findings in the reviews received so far, when they came as JSON
+    pub findings: Vec<Finding>,
+    pub elapsed_secs: f64,
+}
+
+/// The run's status, and with --status-server the endpoint serving it. The default, without
+/// --status-server, ignores updates, and no server runs.
+#[derive(Debug, Default)]
+pub struct StatusBoard {
+    status: Option<Arc<Mutex<RunStatus>>>,
+    server: Option<StatusServer>,
+}
+
+impl StatusBoard {
+    /// Serve the status on `address` until the board is dropped, at the end of the run
+    pub fn serve(address: &str, started: Instant) -> Result<Self, ReviewError> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| ReviewError::Config(format!("--status-server could not listen on {}: {}", address, e)))?;
+        let address = listener.local_addr().map_err(|e| ReviewError::Config(format!("--status-server: {}", e)))?;
+        if !address.ip().is_loopback() {
+            warn!("--status-server is listening on {}, which other machines may be able to reach", address);
+        }
+        let status = Arc::new(Mutex::new(RunStatus { phase: "starting".to_string(), ..RunStatus::default() }));
+        let server = StatusServer::start(listener, address, status.clone(), started)?;
+        info!("Serving the run's status on http://{}/status", address);
+        Ok(StatusBoard { status: Some(status), server: Some(server) })
+    }
+
+    /// Where the status is served, if it is
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.server.as_ref().map(|server| server.address)
+    }
+
+    /// Change the status, if it's being served
+    pub fn update(&self, change: impl FnOnce(&mut RunStatus)) {
+        if let Some(status) = &self.status {
+            change(&mut status.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+    }
+
+    pub fn phase(&self, phase: &str) {
+        self.update(|status| status.phase = phase.to_string());
+    }
+
+    /// The status as it is now, if it's being served
+    pub fn snapshot(&self) -> Option<RunStatus> {
+        self.status.as_ref().map(|status| status.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+/// The thread answering requests, stopped and joined when dropped.
+#[derive(Debug)]
+struct StatusServer {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusServer {
+    fn start(
+        listener: TcpListener,
+        address: SocketAddr,
+        status: Arc<Mutex<RunStatus>>,
+        started: Instant,
+    ) -> Result<Self, ReviewError> {
+        // Polled, so the thread notices when it's told to stop
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| ReviewError::Config(format!("--status-server: {}", e)))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = stop.clone();
+        let thread = thread::spawn(move || {
+            while !stopping.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let snapshot = {
+                            let status = status.lock().unwrap_or_else(|e| e.into_inner());
+                            RunStatus { elapsed_secs: started.elapsed().as_secs_f64(), ..status.clone() }
+                        };
+                        if let Err(e) = answer(stream, &snapshot) {
+                            debug!("--status-server: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(e) => debug!("--status-server: {}", e),
+                }
+            }
+        });
+        Ok(StatusServer { address, stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer one request: the status for `GET /status`, and an error for anything else
+fn answer(stream: TcpStream, status: &RunStatus) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers aren't needed, but are read so the client isn't cut off mid-request
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+    let mut words = request_line.split_whitespace();
+    let (code, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/status")) => ("200 OK", serde_json::to_string(status).unwrap_or_default()),
+        (Some("GET"), _) => ("404 Not Found", r#"{"error": "only /status is served"}"#.to_string()),
+        _ => ("405 Method Not Allowed", r#"{"error": "only GET is allowed"}"#.to_string()),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// The address for --status-server: `HOST:PORT`, or a port alone (`8080` or `:8080`) for
+/// `DEFAULT_STATUS_HOST`
+pub fn parse_status_address(s: &str) -> Result<String, String> {
+    let port = s.strip_prefix(':').unwrap_or(s);
+    if let Ok(port) = port.parse::<u16>() {
+        return Ok(format!("{}:{}", DEFAULT_STATUS_HOST, port));
+    }
+    match s.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(s.to_string()),
+        _ => Err(format!("`{}` is not a port or HOST:PORT", s)),
+    }
+}
@@ -0,0 +1,73 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+
+/// Read the phrases to strip from a review, one per line. A line written as `/pattern/` is a
+/// regular expression; any other line is matched literally. Blank lines and lines starting with
+/// `#` are ignored.
+pub fn load_strip_phrases(path: &Path) -> Result<Vec<Regex>, ReviewError> {
+    let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let pattern = match line.strip_prefix('/').and_then(|l| l.strip_suffix('/')) {
+                Some(pattern) => pattern.to_string(),
+                None => regex::escape(line),
+            };
+            Regex::new(&pattern).map_err(|e| {
+                ReviewError::Config(format!("{} line {}: {}", path.display(), i + 1, e))
+            })
+        })
+        .collect()
+}
+
+/// Remove every match of `phrases` from `text`, returning the result and the number of matches
+/// removed. Lines left blank by a removal are dropped, along with a blank line that would
+/// otherwise end up doubled, so the paragraphs around them stay intact.
+pub fn strip_phrases(text: &str, phrases: &[Regex]) -> (String, usize) {
+    let mut removals = 0;
+    let mut lines: Vec<String> = Vec::new();
+    let mut dropped_line = false;
+
+    for line in text.lines() {
+        let mut stripped = line.to_string();
+        for phrase in phrases {
+            let count = phrase.find_iter(&stripped).count();
+            if count > 0 {
+                removals += count;
+                stripped = phrase.replace_all(&stripped, "").into_owned();
+            }
+        }
+
+        if stripped != line && stripped.trim().is_empty() {
+            dropped_line = true;
+            continue;
+        }
+        let is_blank = stripped.trim().is_empty();
+        let after_blank = lines.last().is_none_or(|last| last.trim().is_empty());
+        if !(dropped_line && is_blank && after_blank) {
+            lines.push(if stripped == line {
+                stripped
+            } else {
+                // Keep the line's indentation, but not spaces the removal left at either end
+                let indent = &line[..line.len() - line.trim_start().len()];
+                format!("{}{}", indent, stripped.trim())
+            });
+        }
+        dropped_line = false;
+    }
+
+    debug!("Removed {} banned phrases from the review", removals);
+    let mut stripped = lines.join("\n");
+    if text.ends_with('\n') {
+        stripped.push('\n');
+    }
+    (stripped, removals)
+}
@@ -0,0 +1,114 @@
+// The default system prompt is built from these sections, separated by blank lines. Each section
+// except the role can be replaced from a file on the command line.
+pub const PROMPT_ROLE: &str = include_str!("rsc/prompt/role.txt");
+pub const PROMPT_REVIEW_RULES: &str = include_str!("rsc/prompt/review_rules.txt");
+pub const PROMPT_OUTPUT_RULES: &str = include_str!("rsc/prompt/output_rules.txt");
+pub const PROMPT_TONE: &str = include_str!("rsc/prompt/tone.txt");
+
+pub const DEFAULT_SYSTEM_PROMPT: &str = concat!(
+    include_str!("rsc/prompt/role.txt"),
+    "\n",
+    include_str!("rsc/prompt/review_rules.txt"),
+    "\n",
+    include_str!("rsc/prompt/output_rules.txt"),
+    "\n",
+    include_str!("rsc/prompt/tone.txt"),
+);
+
+/// The named sections of the system prompt, in the order they are assembled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptSection {
+    /// Who the LLM should act as
+    Role,
+    /// What to look for (the "Focus Areas")
+    ReviewRules,
+    /// How to lay out the review
+    OutputRules,
+    /// How to rate the severity of each finding; empty by default
+    SeverityRubric,
+    /// How to phrase the feedback
+    Tone,
+}
+
+impl PromptSection {
+    pub const ALL: [PromptSection; 5] = [
+        PromptSection::Role,
+        PromptSection::ReviewRules,
+        PromptSection::OutputRules,
+        PromptSection::SeverityRubric,
+        PromptSection::Tone,
+    ];
+
+    /// The section's text in the default system prompt
+    pub fn default_text(&self) -> &'static str {
+        match self {
+            PromptSection::Role => PROMPT_ROLE,
+            PromptSection::ReviewRules => PROMPT_REVIEW_RULES,
+            PromptSection::OutputRules => PROMPT_OUTPUT_RULES,
+            PromptSection::SeverityRubric => "",
+            PromptSection::Tone => PROMPT_TONE,
+        }
+    }
+}
+
+/// Builds a system prompt from its sections, starting from the default text of each, so presets
+/// and variants only need to substitute the sections they change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemPrompt {
+    sections: Vec<(PromptSection, String)>,
+}
+
+impl Default for SystemPrompt {
+    fn default() -> Self {
+        SystemPrompt {
+            sections: PromptSection::ALL
+                .iter()
+                .map(|section| (*section, section.default_text().to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SystemPrompt {
+    /// Replace the text of `section`. Empty text leaves the section out.
+    pub fn with_section(mut self, section: PromptSection, text: impl Into<String>) -> Self {
+        if let Some((_, current)) = self.sections.iter_mut().find(|(s, _)| *s == section) {
+            *current = text.into();
+        }
+        self
+    }
+
+    /// The text of `section`
+    pub fn section(&self, section: PromptSection) -> &str {
+        self.sections
+            .iter()
+            .find(|(s, _)| *s == section)
+            .map_or("", |(_, text)| text)
+    }
+
+    /// Join the non-empty sections with a blank line between each
+    pub fn build(&self) -> String {
+        let sections: Vec<&str> = self
+            .sections
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect();
+        assemble_system_prompt(&sections)
+    }
+}
+
+/// Join system prompt sections with a blank line between each.
+pub fn assemble_system_prompt(sections: &[&str]) -> String {
+    sections
+        .iter()
+        .map(|section| {
+            let mut section = section.to_string();
+            if !section.ends_with('\n') {
+                section.push('\n');
+            }
+            section
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ReviewError;
+
+/// The layout the prompt has without --prompt-template: the system prompt, the context sections,
+/// then the diff under its heading. It gives the same prompt `PromptSchema::V1` does.
+pub const DEFAULT_TEMPLATE: &str = "{{system_prompt}}{{context}}\n\n# {{diff_heading}}\n\n{{diff}}";
+
+/// A value a prompt template can place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// The system prompt, with the output format instructions unless the template places them
+    SystemPrompt,
+    /// The instructions for --output-format, if any
+    OutputFormat,
+    /// The additional context and the other sections that go before the diff, each under its own
+    /// `##` heading
+    Context,
+    /// The heading of the diff, or of the code given with --raw-code
+    DiffHeading,
+    /// The diff, or the code given with --raw-code
+    Diff,
+    /// The branch checked out
+    Branch,
+    /// The arguments the diff was made from
+    DiffArgs,
+}
+
+impl Placeholder {
+    pub const ALL: [Placeholder; 7] = [
+        Placeholder::SystemPrompt,
+        Placeholder::OutputFormat,
+        Placeholder::Context,
+        Placeholder::DiffHeading,
+        Placeholder::Diff,
+        Placeholder::Branch,
+        Placeholder::DiffArgs,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placeholder::SystemPrompt => "system_prompt",
+            Placeholder::OutputFormat => "output_format",
+            Placeholder::Context => "context",
+            Placeholder::DiffHeading => "diff_heading",
+            Placeholder::Diff => "diff",
+            Placeholder::Branch => "branch",
+            Placeholder::DiffArgs => "diff_args",
+        }
+    }
+}
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{{{}}}}}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    /// A placeholder, and whether it's alone on its line, which it takes with it when its value is
+    /// empty
+    Value(Placeholder, bool),
+}
+
+/// A prompt template: text with `{{name}}` placeholders for the parts of the prompt, one of
+/// `Placeholder::ALL`. `\{{` is a literal `{{`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        PromptTemplate::parse(DEFAULT_TEMPLATE).expect("the default template is valid")
+    }
+}
+
+impl PromptTemplate {
+    /// Parse `source`, which must place `{{diff}}`; an unknown or unclosed placeholder is an error
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if rest[..start].ends_with('\\') {
+                text.push_str(&rest[..start - 1]);
+                text.push_str("{{");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            text.push_str(&rest[..start]);
+            let end = rest[start..].find("}}").ok_or_else(|| {
+                let line = source[..source.len() - rest.len() + start].matches('\n').count() + 1;
+                format!("the placeholder on line {} has no closing `}}}}`", line)
+            })?;
+            let name = rest[start + 2..start + end].trim();
+            let placeholder = Placeholder::ALL.into_iter().find(|placeholder| placeholder.name() == name).ok_or_else(|| {
+                let names: Vec<String> = Placeholder::ALL.iter().map(ToString::to_string).collect();
+                format!("unknown placeholder `{{{{{}}}}}`; the placeholders are {}", name, names.join(", "))
+            })?;
+            rest = &rest[start + end + 2..];
+            let alone = (text.is_empty() && pieces.is_empty() || text.ends_with('\n')) && (rest.is_empty() || rest.starts_with('\n'));
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+            }
+            pieces.push(Piece::Value(placeholder, alone));
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        let template = PromptTemplate { source: source.to_string(), pieces };
+        if !template.places(Placeholder::Diff) {
+            return Err(format!("there's no {} placeholder, so the prompt would leave out the code to review", Placeholder::Diff));
+        }
+        Ok(template)
+    }
+
+    /// Read and parse the template in `path`
+    pub fn load(path: &Path) -> Result<Self, ReviewError> {
+        let source = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        PromptTemplate::parse(&source).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The template as it was written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the template places `placeholder`
+    pub fn places(&self, placeholder: Placeholder) -> bool {
+        self.pieces.iter().any(|piece| matches!(piece, Piece::Value(value, _) if *value == placeholder))
+    }
+
+    /// The template with each placeholder replaced by `value` of it. A placeholder alone on its line
+    /// whose value is empty takes the line with it, so a section left out leaves no gap.
+    pub fn render(&self, value: impl Fn(Placeholder) -> String) -> String {
+        let mut rendered = String::new();
+        let mut drop_newline = false;
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => {
+                    rendered.push_str(if drop_newline { &text[1..] } else { text });
+                    drop_newline = false;
+                }
+                Piece::Value(placeholder, alone) => {
+                    let text = value(*placeholder);
+                    drop_newline = *alone && text.is_empty();
+                    rendered.push_str(&text);
+                }
+            }
+        }
+        rendered
+    }
+}
@@ -0,0 +1,90 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::error::ReviewError;
+use crate::provider::ProviderConfig;
+use crate::write_atomic::write_atomic;
+
+/// Where server-side conversation ids are kept in the history directory. `load_reviews` skips it.
+pub const THREADS_FILE: &str = ".threads.json";
+
+/// A conversation a provider holds server-side, started for one diff.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreadRecord {
+    pub provider: String,
+    pub thread_id: String,
+    /// Seconds since the Unix epoch
+    pub created: u64,
+}
+
+/// Conversation ids by the SHA-256 of the diff they were started for, so follow-up requests about
+/// the same diff can refer to the conversation instead of sending the diff again. Anything missing,
+/// expired or for another provider means the request is sent in full, as it is by default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThreadStore {
+    threads: BTreeMap<String, ThreadRecord>,
+}
+
+impl ThreadStore {
+    /// Read the store from `dir`, or start an empty one if there isn't one yet
+    pub fn load(dir: &Path) -> Result<Self, ReviewError> {
+        let path = dir.join(THREADS_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ThreadStore::default()),
+            Err(e) => return Err(ReviewError::Io(path, e)),
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), ReviewError> {
+        let path = dir.join(THREADS_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+        write_atomic(&path, json)
+    }
+
+    /// The conversation to continue for `diff_sha256` with `provider`, if the provider keeps
+    /// conversations and one was started within its `thread_ttl_secs` of `now`
+    pub fn reusable(&self, provider: &ProviderConfig, diff_sha256: &str, now: SystemTime) -> Option<&str> {
+        let ttl = Duration::from_secs(provider.thread_ttl_secs?);
+        let record = self.threads.get(diff_sha256)?;
+        if record.provider != provider.name {
+            return None;
+        }
+        if seconds(now).saturating_sub(record.created) >= ttl.as_secs() {
+            debug!("Conversation {} has expired", record.thread_id);
+            return None;
+        }
+        Some(&record.thread_id)
+    }
+
+    /// Remember the conversation `provider` started for `diff_sha256` at `now`
+    pub fn record(&mut self, provider: &ProviderConfig, diff_sha256: &str, thread_id: &str, now: SystemTime) {
+        self.threads.insert(
+            diff_sha256.to_string(),
+            ThreadRecord {
+                provider: provider.name.clone(),
+                thread_id: thread_id.to_string(),
+                created: seconds(now),
+            },
+        );
+    }
+
+    /// Forget the conversation for `diff_sha256`, e.g. when the provider no longer has it
+    pub fn forget(&mut self, diff_sha256: &str) {
+        self.threads.remove(diff_sha256);
+    }
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
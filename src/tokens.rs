@@ -0,0 +1,241 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer as ModelTokenizer};
+
+use crate::budget::{CharsPerToken, TokenEstimator};
+use crate::error::ReviewError;
+use crate::file_lock::lock;
+use crate::provenance::sha256_hex;
+use crate::write_atomic::write_atomic;
+
+/// Texts shorter than this are counted every time: hashing them saves little, and they'd fill the
+/// --token-cache with the prompt's small sections
+pub const TOKEN_CACHE_MIN_BYTES: usize = 4096;
+
+/// The most counts a --token-cache keeps; past that, only those the run used are saved
+pub const TOKEN_CACHE_MAX_ENTRIES: usize = 10_000;
+
+// The --token-cache `count_tokens` reads and adds to, once `use_token_cache` has loaded it
+static TOKEN_CACHE: Mutex<Option<TokenCache>> = Mutex::new(None);
+
+/// The values --tokenizer and a provider's `tokenizer` key take
+pub const TOKENIZERS: &[&str] = &["cl100k", "o200k", "chars"];
+
+/// A byte-pair encoding to count tokens with. Only OpenAI publishes its encodings; Claude's and
+/// Llama's vocabularies are close enough in size to cl100k's that it counts well for them too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// GPT-4 and GPT-3.5's encoding
+    Cl100k,
+    /// The encoding of GPT-4o, GPT-4.1, GPT-5 and the o-series
+    O200k,
+}
+
+impl Encoding {
+    /// The encoding `model` uses, or cl100k for models that aren't OpenAI's
+    pub fn for_model(model: &str) -> Self {
+        match get_tokenizer(model) {
+            Some(ModelTokenizer::O200kBase | ModelTokenizer::O200kHarmony) => Encoding::O200k,
+            _ => Encoding::Cl100k,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Cl100k => "cl100k",
+            Encoding::O200k => "o200k",
+        })
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cl100k" | "cl100k_base" => Ok(Encoding::Cl100k),
+            "o200k" | "o200k_base" => Ok(Encoding::O200k),
+            _ => Err(format!("unknown tokenizer `{}`; expected one of {}", s, TOKENIZERS.join(", "))),
+        }
+    }
+}
+
+/// The number of tokens in `text` under `encoding`. Special tokens such as `<|endoftext|>` are
+/// counted as the plain text they are in a diff.
+pub fn count_tokens(text: &str, encoding: Encoding) -> usize {
+    if text.len() >= TOKEN_CACHE_MIN_BYTES
+        && let Some(cache) = TOKEN_CACHE.lock().unwrap_or_else(|e| e.into_inner()).as_mut()
+    {
+        return cache.count(text, encoding);
+    }
+    encode(text, encoding)
+}
+
+fn encode(text: &str, encoding: Encoding) -> usize {
+    let bpe = match encoding {
+        Encoding::Cl100k => tiktoken_rs::cl100k_base_singleton(),
+        Encoding::O200k => tiktoken_rs::o200k_base_singleton(),
+    };
+    bpe.encode_ordinary(text).len()
+}
+
+/// Token counts of texts, such as the diff of each file, kept on disk between runs so a file
+/// that hasn't changed isn't tokenized again. Each is keyed by the encoding and the SHA-256 of the
+/// text it counts.
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    path: PathBuf,
+    counts: BTreeMap<String, usize>,
+    used: HashSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenCacheFile {
+    version: u32,
+    counts: BTreeMap<String, usize>,
+}
+
+impl TokenCache {
+    /// The cache saved at `path`, or an empty one if there's none yet. One that can't be read is
+    /// started afresh, with a warning.
+    pub fn load(path: &Path) -> Self {
+        let counts = match fs::read_to_string(path) {
+            Ok(text) => match serde_json::from_str::<TokenCacheFile>(&text) {
+                Ok(file) if file.version == 1 => file.counts,
+                Ok(file) => {
+                    warn!("{} is a version {} token cache, which this version can't read; starting afresh", path.display(), file.version);
+                    BTreeMap::new()
+                }
+                Err(e) => {
+                    warn!("Could not read the token cache {}: {}; starting afresh", path.display(), e);
+                    BTreeMap::new()
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                warn!("Could not read the token cache {}: {}; starting afresh", path.display(), e);
+                BTreeMap::new()
+            }
+        };
+        TokenCache { path: path.to_path_buf(), counts, used: HashSet::new() }
+    }
+
+    /// The number of tokens in `text` under `encoding`, from the cache if it has it
+    pub fn count(&mut self, text: &str, encoding: Encoding) -> usize {
+        let key = format!("{}:{}", encoding, sha256_hex(text));
+        let count = match self.counts.get(&key) {
+            Some(&count) => count,
+            None => {
+                let count = encode(text, encoding);
+                self.counts.insert(key.clone(), count);
+                count
+            }
+        };
+        self.used.insert(key);
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Write the cache back to its file, with the counts other runs have saved there since it was
+    /// loaded. Once it holds more than `TOKEN_CACHE_MAX_ENTRIES`, only the counts used since it
+    /// was loaded are kept.
+    pub fn save(&mut self) -> Result<(), ReviewError> {
+        let _lock = lock(&self.path)?;
+        for (key, count) in TokenCache::load(&self.path).counts {
+            self.counts.entry(key).or_insert(count);
+        }
+        if self.counts.len() > TOKEN_CACHE_MAX_ENTRIES {
+            self.counts.retain(|key, _| self.used.contains(key));
+        }
+        let file = TokenCacheFile { version: 1, counts: self.counts.clone() };
+        let json = serde_json::to_string(&file).map_err(|e| ReviewError::Config(format!("{}: {}", self.path.display(), e)))?;
+        write_atomic(&self.path, json)
+    }
+}
+
+/// With --token-cache, count the tokens of long texts through the cache at `path` from now on
+pub fn use_token_cache(path: &Path) {
+    let cache = TokenCache::load(path);
+    debug!("Loaded {} token counts from {}", cache.len(), path.display());
+    *TOKEN_CACHE.lock().unwrap_or_else(|e| e.into_inner()) = Some(cache);
+}
+
+/// Save the cache `use_token_cache` loaded, if there is one
+pub fn save_token_cache() -> Result<(), ReviewError> {
+    match TOKEN_CACHE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        Some(cache) => cache.save(),
+        None => Ok(()),
+    }
+}
+
+/// The context window of `model`, in tokens, if it's a model the tool knows
+///
+/// ```
+/// use llm_code_review::tokens::context_window;
+///
+/// assert_eq!(context_window("claude-sonnet-4-5"), Some(200_000));
+/// assert_eq!(context_window("gpt-4o-mini"), Some(128_000));
+/// assert_eq!(context_window("my-fine-tune"), None);
+/// ```
+pub fn context_window(model: &str) -> Option<usize> {
+    if model.starts_with("claude-") {
+        Some(200_000)
+    } else if model.starts_with("llama3") {
+        Some(128_000)
+    } else {
+        tiktoken_rs::model::get_context_size(model)
+    }
+}
+
+/// How the size of the prompt is measured: by an encoding, or by the older estimate of a fixed
+/// number of bytes per token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tokenizer {
+    Bpe(Encoding),
+    Chars(CharsPerToken),
+}
+
+impl Tokenizer {
+    /// Parse a --tokenizer value, with `chars_per_token` for `chars`
+    pub fn parse(name: &str, chars_per_token: usize) -> Result<Self, String> {
+        match name {
+            "chars" => Ok(Tokenizer::Chars(CharsPerToken(chars_per_token))),
+            name => name.parse().map(Tokenizer::Bpe),
+        }
+    }
+}
+
+impl fmt::Display for Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tokenizer::Bpe(encoding) => write!(f, "{}", encoding),
+            Tokenizer::Chars(CharsPerToken(chars)) => write!(f, "{} characters per token", chars),
+        }
+    }
+}
+
+impl TokenEstimator for Tokenizer {
+    fn estimate_tokens(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Bpe(encoding) => count_tokens(text, *encoding),
+            Tokenizer::Chars(chars) => chars.estimate_tokens(text),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::attention::FileAttention;
+use crate::error::ReviewError;
+use crate::findings::{confidence_note, render_findings, Finding, Severity};
+use crate::locale::Locale;
+use crate::output::{unfence, AnnotationLevel, OutputFormat};
+use crate::quality::Escalation;
+use crate::write_atomic::write_atomic;
+
+/// The version of the transcript format --save-transcript writes. Version 0 is a review saved
+/// as plain text, before transcripts were JSON.
+pub const TRANSCRIPT_VERSION: u32 = 1;
+
+/// A review as --send got it, saved with --save-transcript so --replay can render it again in
+/// another format without asking the model. Every field defaults, so older transcripts still load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    #[serde(default)]
+    pub version: u32,
+    /// The model that wrote the review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The review as the model wrote it
+    #[serde(default)]
+    pub review: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// The findings, when the review gave them as JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings: Option<Vec<Finding>>,
+    /// With --attention-report, which files need a human reviewer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attention: Option<Vec<FileAttention>>,
+    /// With --fallback-model, the model whose review failed the quality checks, so `model` wrote
+    /// this one instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation: Option<Escalation>,
+}
+
+/// The JSON form of a review with structured findings: a summary and its findings
+#[derive(Deserialize)]
+struct StructuredReview {
+    #[serde(default)]
+    summary: Option<String>,
+    findings: Vec<Finding>,
+}
+
+impl Transcript {
+    /// A transcript of `review`, keeping its summary and findings when it gives them as JSON:
+    /// either a list of findings, or an object with `summary` and `findings`
+    pub fn new(model: Option<String>, review: &str) -> Self {
+        let json = unfence(review.trim());
+        let (summary, findings) = match serde_json::from_str::<Vec<Finding>>(json) {
+            Ok(findings) => (None, Some(findings)),
+            Err(_) => match serde_json::from_str::<StructuredReview>(json) {
+                Ok(structured) => (structured.summary, Some(structured.findings)),
+                Err(_) => (None, None),
+            },
+        };
+        Transcript {
+            version: TRANSCRIPT_VERSION,
+            model,
+            review: review.to_string(),
+            summary,
+            findings,
+            attention: None,
+            escalation: None,
+        }
+    }
+
+    /// Read a transcript: JSON from --save-transcript, a findings file like --file-issues reads,
+    /// or a review saved as plain text, which has nothing structured to render
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        match serde_json::from_str::<Value>(contents) {
+            Ok(Value::Array(_)) => {
+                let findings: Vec<Finding> = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+                Ok(Transcript {
+                    version: TRANSCRIPT_VERSION,
+                    review: contents.to_string(),
+                    findings: Some(findings),
+                    ..Transcript::default()
+                })
+            }
+            Ok(value @ Value::Object(_)) => {
+                let transcript: Transcript = serde_json::from_value(value).map_err(|e| e.to_string())?;
+                if transcript.version > TRANSCRIPT_VERSION {
+                    return Err(format!(
+                        "transcript version {} is newer than this version of llm_code_review reads ({})",
+                        transcript.version, TRANSCRIPT_VERSION
+                    ));
+                }
+                Ok(transcript)
+            }
+            _ => Ok(Transcript { review: contents.to_string(), ..Transcript::default() }),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        Transcript::parse(&contents).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReviewError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+        write_atomic(path, format!("{}\n", json))
+    }
+
+    /// The summary and findings rendered in `format` with `locale`'s headings and labels, or
+    /// `None` if the transcript has no findings
+    pub fn render(&self, format: OutputFormat, locale: Locale) -> Option<String> {
+        let findings = self.findings.as_ref()?;
+        if let OutputFormat::GitHubActions(_) = format {
+            return Some(findings.iter().map(|finding| annotation(finding, locale)).collect::<Vec<_>>().join("\n"));
+        }
+        let rendered = render_findings(findings, format, locale);
+        Some(match self.summary.as_deref().map(str::trim).filter(|summary| !summary.is_empty()) {
+            Some(summary) => format!("{}\n\n{}", summary, rendered),
+            None => rendered,
+        })
+    }
+}
+
+/// A finding as a GitHub Actions workflow command, at the level matching its severity. Findings
+/// over the limits on findings can't be collapsed, so they're notices.
+fn annotation(finding: &Finding, locale: Locale) -> String {
+    let level = match finding.severity {
+        _ if finding.lower_priority => AnnotationLevel::Notice,
+        Severity::Error => AnnotationLevel::Error,
+        Severity::Warning => AnnotationLevel::Warning,
+        Severity::Info => AnnotationLevel::Notice,
+    };
+    let line = finding.line.map(|line| format!(",line={}", line)).unwrap_or_default();
+    // Workflow commands end at a newline, so it has to be escaped
+    let comment = if finding.lower_priority {
+        locale.format("Lower priority: {}", &[&finding.comment])
+    } else if finding.is_unsure() {
+        format!(
+            "{} ({}): {}",
+            locale.text("Possibly wrong, please verify"),
+            confidence_note(finding, locale),
+            finding.comment
+        )
+    } else {
+        finding.comment.clone()
+    };
+    let message = comment.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+    format!("::{} file={}{}::{}", level, finding.path, line, message)
+}
@@ -0,0 +1,95 @@
+/// What marks text as cut short
+pub const ELLIPSIS: char = '…';
+
+/// `text` cut to at most `max_chars` characters, the last of them `…` when anything was cut, with
+/// any whitespace the cut leaves at the end dropped. Never cuts inside a character.
+pub fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        None => text.to_string(),
+        Some(_) => {
+            let kept = text.char_indices().nth(max_chars.saturating_sub(1)).map_or(text, |(end, _)| &text[..end]);
+            with_ellipsis(kept, max_chars)
+        }
+    }
+}
+
+/// `text` cut to at most `max_graphemes` user-perceived characters, the last of them `…` when
+/// anything was cut, as `truncate_chars` does, but never separating an emoji from its skin tone
+/// or the rest of its sequence, a flag's two letters or a letter from its accents.
+pub fn truncate_graphemes(text: &str, max_graphemes: usize) -> String {
+    let clusters = graphemes(text);
+    if clusters.len() <= max_graphemes {
+        return text.to_string();
+    }
+    let end: usize = clusters.iter().take(max_graphemes.saturating_sub(1)).map(|cluster| cluster.len()).sum();
+    with_ellipsis(&text[..end], max_graphemes)
+}
+
+fn with_ellipsis(kept: &str, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    format!("{}{}", kept.trim_end(), ELLIPSIS)
+}
+
+/// `text` split into grapheme clusters, the characters a reader sees as one. This follows the
+/// Unicode rules closely enough for cutting text short: combining marks, variation selectors, emoji
+/// modifiers and tags stay with the character before them, zero-width joiners join the characters
+/// on either side, regional indicators pair up into flags, and CR LF is one cluster. Scripts whose
+/// clusters need the full tables, such as some Indic vowel signs, may be split.
+pub fn graphemes(text: &str) -> Vec<&str> {
+    let mut clusters = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    // Regional indicators in the current cluster, which pair up into flags
+    let mut regional = 0;
+    for (i, c) in text.char_indices() {
+        let joins = previous.is_some_and(|previous| {
+            previous == '\u{200D}'
+                || extends(c)
+                || (previous == '\r' && c == '\n')
+                || (is_regional_indicator(c) && regional % 2 == 1)
+        });
+        if !joins && i > 0 {
+            clusters.push(&text[start..i]);
+            start = i;
+            regional = 0;
+        }
+        if is_regional_indicator(c) {
+            regional += 1;
+        }
+        previous = Some(c);
+    }
+    if start < text.len() {
+        clusters.push(&text[start..]);
+    }
+    clusters
+}
+
+/// Whether `c` belongs with the character before it
+fn extends(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'       // combining diacritical marks
+        | '\u{0483}'..='\u{0489}'
+        | '\u{0591}'..='\u{05BD}'     // Hebrew points
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'     // Arabic vowel marks
+        | '\u{0E31}' | '\u{0E34}'..='\u{0E3A}' | '\u{0E47}'..='\u{0E4E}' // Thai
+        | '\u{1160}'..='\u{11FF}'     // Hangul medial vowels and final consonants
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200C}' | '\u{200D}'     // zero-width non-joiner and joiner
+        | '\u{20D0}'..='\u{20FF}'     // combining marks for symbols, such as the keycap
+        | '\u{302A}'..='\u{302F}'
+        | '\u{3099}' | '\u{309A}'     // kana voicing marks
+        | '\u{FE00}'..='\u{FE0F}'     // variation selectors
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'   // emoji skin tones
+        | '\u{E0020}'..='\u{E007F}'   // tags, as in subdivision flags
+        | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
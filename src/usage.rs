@@ -0,0 +1,230 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::user_config_dir;
+use crate::error::ReviewError;
+use crate::feedback::UNKNOWN;
+use crate::file_lock::lock;
+use crate::write_atomic::write_atomic;
+
+/// The ledger's file, in the user's config directory
+pub const USAGE_FILE: &str = "usage.jsonl";
+
+/// List prices in US dollars per million input and output tokens, by model name prefix. The
+/// longest prefix matching a model is its price. Models not listed are counted in tokens only.
+pub const MODEL_PRICES: &[(&str, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("gpt-5-nano", 0.05, 0.4),
+    ("gpt-5-mini", 0.25, 2.0),
+    ("gpt-5", 1.25, 10.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+];
+
+/// One run that sent a prompt, as the ledger records it. Token counts are estimates, as the
+/// prompt's size is checked before sending.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    /// UTC, in RFC 3339 format
+    pub timestamp: String,
+    pub model: String,
+    /// The provider's name, from --provider or --llm-provider-config
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// The name of the repository's directory
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// The language presets in effect, joined with `+`, or `none`
+    #[serde(default)]
+    pub preset: Option<String>,
+    pub prompt_tokens: usize,
+    pub response_tokens: usize,
+    /// The estimated cost in US dollars, if the model's price is known
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+}
+
+/// The price of `model` per million input and output tokens, if it's listed
+pub fn model_price(model: &str) -> Option<(f64, f64)> {
+    MODEL_PRICES
+        .iter()
+        .filter(|(prefix, _, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|&(_, input, output)| (input, output))
+}
+
+/// What sending `prompt_tokens` and getting `response_tokens` back from `model` costs, if its
+/// price is known
+pub fn estimate_cost(model: &str, prompt_tokens: usize, response_tokens: usize) -> Option<f64> {
+    let (input, output) = model_price(model)?;
+    Some((prompt_tokens as f64 * input + response_tokens as f64 * output) / 1_000_000.0)
+}
+
+/// The token usage of every run that sent a prompt, one JSON object per line. Runs may append
+/// to it at the same time, so appending holds the ledger's lock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ledger {
+    pub path: PathBuf,
+}
+
+impl Ledger {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Ledger { path: path.into() }
+    }
+
+    /// The ledger in the user's config directory
+    pub fn user() -> Option<Self> {
+        user_config_dir().map(|dir| Ledger::new(dir.join(USAGE_FILE)))
+    }
+
+    /// Add `entry` to the end of the ledger, creating it if need be. The ledger is rewritten with
+    /// `write_atomic` rather than appended to, so a run stopped part way can't leave half a line.
+    pub fn append(&self, entry: &UsageEntry) -> Result<(), ReviewError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| ReviewError::Write(dir.to_path_buf(), e))?;
+        }
+        let line = serde_json::to_string(entry).map_err(|e| ReviewError::Config(e.to_string()))?;
+        let _lock = lock(&self.path)?;
+        let mut contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(ReviewError::Io(self.path.clone(), e)),
+        };
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(&line);
+        contents.push('\n');
+        write_atomic(&self.path, contents)
+    }
+
+    /// Every entry in the ledger, oldest first. A line that can't be read, such as one cut short
+    /// by a full disk, is skipped with a warning.
+    pub fn load(&self) -> Result<Vec<UsageEntry>, ReviewError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ReviewError::Io(self.path.clone(), e)),
+        };
+        Ok(parse_ledger(&contents, &self.path))
+    }
+}
+
+fn parse_ledger(contents: &str, path: &Path) -> Vec<UsageEntry> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping line {} of {}: {}", i + 1, path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The estimated spend of the entries in `month`, given as `YYYY-MM`
+pub fn month_spend(entries: &[UsageEntry], month: &str) -> f64 {
+    entries.iter().filter(|entry| entry.timestamp.starts_with(month)).filter_map(|entry| entry.cost_usd).sum()
+}
+
+/// The warning shown when `spent` in `month` has reached the monthly `budget`
+pub fn budget_banner(spent: f64, budget: f64, month: &str) -> String {
+    let message = format!("Over the monthly budget: ${:.2} of ${:.2} spent in {}", spent, budget, month);
+    let rule = "=".repeat(message.len());
+    format!("{}\n{}\n{}", rule, message, rule)
+}
+
+type EntrySetting = fn(&UsageEntry) -> Option<&String>;
+
+/// The entries from `since` (a `YYYY-MM-DD` date) on, totalled by model, repository and preset:
+/// one table each.
+pub fn usage_report(entries: &[UsageEntry], since: Option<&str>) -> String {
+    let entries: Vec<&UsageEntry> =
+        entries.iter().filter(|entry| since.is_none_or(|since| entry.timestamp.as_str() >= since)).collect();
+    let dimensions: [(&str, EntrySetting); 3] = [
+        ("Model", |entry| Some(&entry.model)),
+        ("Repository", |entry| entry.repo.as_ref()),
+        ("Preset", |entry| entry.preset.as_ref()),
+    ];
+    let total: Totals = entries.iter().copied().collect();
+    let mut report = vec![format!(
+        "{} runs{}, {} prompt and {} response tokens, {}",
+        total.runs,
+        since.map(|since| format!(" since {}", since)).unwrap_or_default(),
+        total.prompt_tokens,
+        total.response_tokens,
+        total.cost()
+    )];
+
+    for (title, value) in dimensions {
+        let mut totals: BTreeMap<&str, Vec<&UsageEntry>> = BTreeMap::new();
+        for entry in &entries {
+            totals.entry(value(entry).map_or(UNKNOWN, String::as_str)).or_default().push(entry);
+        }
+        let width = totals.keys().map(|value| value.chars().count()).chain([title.len()]).max().unwrap_or(0);
+        report.push(String::new());
+        report.push(format!("{:width$}  {:>4}  {:>13}  {:>15}  {:>9}", title, "runs", "prompt tokens", "response tokens", "cost"));
+        for (value, entries) in totals {
+            let totals: Totals = entries.into_iter().collect();
+            report.push(format!(
+                "{:width$}  {:>4}  {:>13}  {:>15}  {:>9}",
+                value,
+                totals.runs,
+                totals.prompt_tokens,
+                totals.response_tokens,
+                totals.cost()
+            ));
+        }
+    }
+    report.join("\n")
+}
+
+#[derive(Default)]
+struct Totals {
+    runs: usize,
+    prompt_tokens: usize,
+    response_tokens: usize,
+    cost_usd: f64,
+    /// Runs with a model whose price isn't known
+    unpriced: usize,
+}
+
+impl<'a> FromIterator<&'a UsageEntry> for Totals {
+    fn from_iter<I: IntoIterator<Item = &'a UsageEntry>>(entries: I) -> Self {
+        let mut totals = Totals::default();
+        for entry in entries {
+            totals.runs += 1;
+            totals.prompt_tokens += entry.prompt_tokens;
+            totals.response_tokens += entry.response_tokens;
+            match entry.cost_usd {
+                Some(cost) => totals.cost_usd += cost,
+                None => totals.unpriced += 1,
+            }
+        }
+        totals
+    }
+}
+
+impl Totals {
+    /// The cost in dollars, marked with `+` when some runs' cost isn't known, or `-` if none is
+    fn cost(&self) -> String {
+        match (self.runs - self.unpriced, self.unpriced) {
+            (0, _) => "-".to_string(),
+            (_, 0) => format!("${:.2}", self.cost_usd),
+            _ => format!("${:.2}+", self.cost_usd),
+        }
+    }
+}
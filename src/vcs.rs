@@ -0,0 +1,646 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use regex::Regex;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::diff::parse_diff;
+use crate::encoding::{decode_diff, decode_diff_with};
+use crate::error::ReviewError;
+use crate::network::is_offline;
+use crate::partial_clone::{diff_available_files, is_missing_object_error, limit_lazy_fetch, NO_LAZY_FETCH_ENV};
+
+pub const VCS_NAMES: [&str; 3] = ["git", "hg", "jj"];
+
+/// The id git gives a tree with no files in it, for diffing from before the first commit
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+// Environment variables that change what git prints or make it run other programs. They are
+// cleared for every git command this tool runs.
+const UNSAFE_GIT_ENV: &[&str] = &[
+    "GIT_EXTERNAL_DIFF",
+    "GIT_DIFF_OPTS",
+    "GIT_PAGER",
+    "PAGER",
+    "GIT_EDITOR",
+    "GIT_SEQUENCE_EDITOR",
+    "GIT_CONFIG_COUNT",
+];
+
+// Settings the tool relies on whatever the user's config says: unquoted paths, so they match the
+// files' names, and no color codes in the output
+const GIT_CONFIG_OVERRIDES: &str = "'core.quotePath'='false' 'color.ui'='false' 'color.diff'='false'";
+
+/// The version control system the diff is taken from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vcs {
+    Git,
+    Hg,
+    Jj,
+}
+
+impl FromStr for Vcs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "git" => Ok(Vcs::Git),
+            "hg" => Ok(Vcs::Hg),
+            "jj" => Ok(Vcs::Jj),
+            _ => Err(format!("unknown version control system `{}`", s)),
+        }
+    }
+}
+
+impl Vcs {
+    /// Find the repository `dir` belongs to by looking for `.jj`, `.git` or `.hg` in it and each
+    /// of its parents. The nearest one wins, and jj wins over git in a colocated repository.
+    pub fn detect(dir: &Path) -> Option<Vcs> {
+        dir.ancestors().find_map(|dir| {
+            if dir.join(".jj").is_dir() {
+                Some(Vcs::Jj)
+            } else if dir.join(".git").exists() {
+                Some(Vcs::Git)
+            } else if dir.join(".hg").is_dir() {
+                Some(Vcs::Hg)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Arguments for this VCS's diff command: the context size followed by the user's arguments,
+    /// which are written in `git diff` syntax. Each argument stays whole, spaces and all. A context
+    /// size among the user's options takes the place of `unified_context`, rather than both being
+    /// given, and is spelled as this VCS spells it, whichever of `context_arg`'s spellings it was
+    /// given in.
+    pub fn diff_args(&self, unified_context: usize, args: &[String]) -> Vec<String> {
+        let options = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+        let given = args[..options].iter().any(|arg| context_arg(arg).is_some());
+        let context = (!given).then(|| self.context_option(unified_context));
+        let args: Vec<String> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match context_arg(arg) {
+                Some(lines) if i < options => self.context_option(lines),
+                _ => arg.clone(),
+            })
+            .collect();
+        let args = match self {
+            Vcs::Git => args,
+            Vcs::Hg => hg_revision_args(&args),
+            Vcs::Jj => jj_revision_args(&args),
+        };
+        context.into_iter().chain(args).collect()
+    }
+
+    /// The option asking this VCS's diff command for `lines` lines of context
+    fn context_option(&self, lines: usize) -> String {
+        match self {
+            Vcs::Git => format!("-U{}", lines),
+            Vcs::Hg => format!("--unified={}", lines),
+            Vcs::Jj => format!("--context={}", lines),
+        }
+    }
+
+    /// The command that takes this VCS's diff with `args`, as `diff_args` gives them. hg and jj are
+    /// asked for git's format, which is what the rest of the tool reads.
+    pub fn diff_command(&self, args: &[String]) -> Command {
+        let mut command = match self {
+            Vcs::Git => {
+                let mut command = git_command();
+                // diff.external in the user's config would replace the unified diff too
+                command.args(["diff", "--no-ext-diff"]);
+                command
+            }
+            Vcs::Hg => {
+                let mut command = Command::new("hg");
+                command.args(["diff", "--git"]);
+                command
+            }
+            Vcs::Jj => {
+                let mut command = Command::new("jj");
+                command.args(["diff", "--git"]);
+                command
+            }
+        };
+        command.args(args);
+        command
+    }
+}
+
+/// Check that `dir` is in a repository, found as `Vcs::detect` finds one or, for a bare git
+/// repository or one `GIT_DIR` points to, by asking git
+pub fn require_repository(dir: &Path) -> Result<(), ReviewError> {
+    if Vcs::detect(dir).is_some() || git_output(&["-C", &dir.to_string_lossy(), "rev-parse", "--git-dir"]).is_ok() {
+        return Ok(());
+    }
+    Err(ReviewError::NoRepository(dir.to_path_buf()))
+}
+
+/// The context lines a `-U5`, `--unified=5` or `--context=5` diff argument asks for
+pub fn context_arg(arg: &str) -> Option<usize> {
+    ["-U", "--unified=", "--context="].iter().find_map(|prefix| arg.strip_prefix(prefix)?.parse().ok())
+}
+
+/// A `git --no-pager` command run with a curated environment: variables that could change its
+/// output are cleared and `GIT_CONFIG_PARAMETERS` holds the settings the tool needs, replacing any
+/// `-c` options git was given by a parent process.
+pub(crate) fn git_command() -> Command {
+    let mut command = Command::new("git");
+    command.arg("--no-pager");
+    for name in UNSAFE_GIT_ENV {
+        command.env_remove(name);
+    }
+    command.env("GIT_CONFIG_PARAMETERS", GIT_CONFIG_OVERRIDES);
+    // With --offline, a partial clone's missing objects stay missing rather than being fetched
+    if is_offline() {
+        command.env(NO_LAZY_FETCH_ENV, "1");
+    }
+    trace!(
+        "git environment: {:?}",
+        command.get_envs().collect::<Vec<_>>()
+    );
+    command
+}
+
+/// Run `git diff` with the given arguments, returning its output (which may be empty). Each
+/// argument reaches git as it is, so paths may contain spaces. Exit status 1 with a diff on
+/// standard output is success, as `--exit-code` and some diff filters give; `--quiet` is refused,
+/// since it prints nothing to review.
+pub fn try_git_diff(git_args: &[String]) -> Result<String, ReviewError> {
+    if git_args.iter().take_while(|arg| *arg != "--").any(|arg| arg == "--quiet") {
+        return Err(ReviewError::Usage(
+            "--quiet makes git diff print nothing, so there would be nothing to review; leave it out, or use --exit-code"
+                .to_string(),
+        ));
+    }
+    let mut command = Vcs::Git.diff_command(git_args);
+
+    debug!("Running command: {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+
+    let diff = decode_diff(&output.stdout);
+    if output.status.code() == Some(1) && !parse_diff(&diff).is_empty() {
+        debug!("git diff exited with status 1 and printed a diff; taking it as success");
+        return Ok(diff);
+    }
+    if !output.status.success() {
+        let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if stderr.trim().is_empty() {
+            stderr = match output.status.code() {
+                Some(code) => format!("git diff exited with status {} without printing a diff", code),
+                None => "git diff was killed by a signal".to_string(),
+            };
+        }
+        // A partial clone that couldn't fetch some files still has the others to review
+        if is_missing_object_error(&stderr)
+            && let Some((diff, missing)) = diff_available_files(git_args)?
+        {
+            warn!(
+                "Left out {} {} whose contents couldn't be fetched: {}",
+                missing.len(),
+                if missing.len() == 1 { "file" } else { "files" },
+                missing.join(", ")
+            );
+            return Ok(diff);
+        }
+        return Err(ReviewError::GitFailed(stderr));
+    }
+
+    Ok(diff)
+}
+
+/// The pathspecs of `git diff` arguments that compare the working tree with the index or a
+/// commit, which untracked files belong in, or `None` for those comparing the index or two
+/// commits. Anything before `--` that isn't an option or an existing path is taken as a revision.
+pub fn untracked_pathspecs(git_args: &[String]) -> Option<Vec<String>> {
+    let mut pathspecs = Vec::new();
+    let mut revisions = 0;
+    let mut paths_only = false;
+    for arg in git_args {
+        if paths_only || (!arg.starts_with('-') && Path::new(arg).exists()) {
+            pathspecs.push(arg.clone());
+        } else if arg == "--" {
+            paths_only = true;
+        } else if arg == "--cached" || arg == "--staged" || arg.contains("..") {
+            return None;
+        } else if !arg.starts_with('-') {
+            revisions += 1;
+        }
+    }
+    (revisions < 2).then_some(pathspecs)
+}
+
+/// The files git doesn't track that `pathspecs` cover, or the whole repository's without any,
+/// relative to the repository's root. Ignored files are left out.
+pub fn untracked_files(pathspecs: &[String]) -> Result<Vec<PathBuf>, ReviewError> {
+    let mut args = vec!["ls-files", "--others", "--exclude-standard", "--full-name", "-z", "--"];
+    match pathspecs {
+        [] => args.push(":/"),
+        pathspecs => args.extend(pathspecs.iter().map(String::as_str)),
+    }
+    let files = git_output(&args)?;
+    Ok(files.split('\0').filter(|file| !file.is_empty()).map(PathBuf::from).collect())
+}
+
+/// A unified diff adding each of `paths`, relative to the repository's root, as a new file, as
+/// `git diff` would show it once staged. Binary files are left out, and a file of more than
+/// `max_lines` lines is cut short with a note saying how many were left out.
+pub fn get_untracked_diff(paths: &[PathBuf], max_lines: usize) -> Result<String, ReviewError> {
+    let root = PathBuf::from(git_output(&["rev-parse", "--show-toplevel"])?.trim_end_matches('\n'));
+    let mut diff = Vec::new();
+    for path in paths {
+        let full_path = root.join(path);
+        let metadata = full_path.symlink_metadata().map_err(|e| ReviewError::Io(full_path.clone(), e))?;
+        let (mode, contents) = if metadata.file_type().is_symlink() {
+            let target = full_path.read_link().map_err(|e| ReviewError::Io(full_path.clone(), e))?;
+            ("120000", target.to_string_lossy().into_owned().into_bytes())
+        } else {
+            let contents = fs::read(&full_path).map_err(|e| ReviewError::Io(full_path.clone(), e))?;
+            (if is_executable(&metadata) { "100755" } else { "100644" }, contents)
+        };
+        // As git decides, a NUL byte in the first 8000 makes a file binary
+        if contents.iter().take(8000).any(|&byte| byte == 0) {
+            info!("Leaving out the untracked binary file {}", path.display());
+            continue;
+        }
+
+        let name = path.to_string_lossy();
+        diff.extend(format!("diff --git a/{0} b/{0}\nnew file mode {1}\n", name, mode).into_bytes());
+        if contents.is_empty() {
+            continue;
+        }
+        let lines: Vec<&[u8]> = contents.split_inclusive(|&byte| byte == b'\n').collect();
+        let shown = lines.len().min(max_lines);
+        diff.extend(format!("--- /dev/null\n+++ b/{}\n@@ -0,0 +1,{} @@\n", name, shown).into_bytes());
+        for line in &lines[..shown] {
+            diff.push(b'+');
+            diff.extend_from_slice(line);
+        }
+        if shown < lines.len() {
+            let left_out = lines.len() - shown;
+            info!("Cut the untracked file {} short at {} of its {} lines", name, shown, lines.len());
+            diff.extend(
+                format!("\\ {} more line{} of the untracked file left out\n", left_out, if left_out == 1 { "" } else { "s" })
+                    .into_bytes(),
+            );
+        } else if !contents.ends_with(b"\n") {
+            diff.extend_from_slice(b"\n\\ No newline at end of file\n");
+        }
+    }
+    Ok(decode_diff(&diff))
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Run git with `args`, returning its standard output. In a partial clone reviewed without
+/// --allow-fetch, missing objects aren't fetched and the command fails instead.
+pub(crate) fn git_output(args: &[&str]) -> Result<String, ReviewError> {
+    let mut command_binding = git_command();
+    limit_lazy_fetch(&mut command_binding);
+    let command = command_binding.args(args);
+    debug!("Running command: {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| ReviewError::GitFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(ReviewError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Rewrite `git diff` arguments so they work in a repository with no commits yet and for the first
+/// commit, where git itself would fail with an unknown revision.
+///
+/// With no commits, `HEAD` becomes the empty tree, and with no revision at all the empty tree is
+/// added so the staged and unstaged files are reviewed together. A revision exactly one step before
+/// the first commit, such as `HEAD~1` in a repository with one commit, also becomes the empty tree,
+/// as `git show --root` would use. Ranges with such a side become two separate revisions. Revisions
+/// that go back further fail with an error saying why. Options, anything after `--`, existing
+/// paths and revisions that resolve are passed through unchanged.
+pub fn resolve_git_revisions(args: &[String]) -> Result<Vec<String>, ReviewError> {
+    let unborn = !has_commits();
+    let mut resolved = Vec::new();
+    let mut paths_only = false;
+    let mut has_revision = false;
+    for arg in args.iter().map(String::as_str) {
+        paths_only |= arg == "--";
+        if paths_only || arg.starts_with('-') || Path::new(arg).exists() {
+            resolved.push(arg.to_string());
+            continue;
+        }
+        has_revision = true;
+        if resolves(arg) {
+            resolved.push(arg.to_string());
+            continue;
+        }
+
+        let range = arg.split_once("...").or_else(|| arg.split_once(".."));
+        match range {
+            Some((from, to)) => {
+                let from = if from.is_empty() { "HEAD" } else { from };
+                let to = if to.is_empty() { "HEAD" } else { to };
+                let (from_resolved, to_resolved) = (resolve_revision(from, unborn)?, resolve_revision(to, unborn)?);
+                if from_resolved == from && to_resolved == to {
+                    // Nothing before the first commit; git will explain what's wrong
+                    resolved.push(arg.to_string());
+                } else {
+                    resolved.push(from_resolved);
+                    resolved.push(to_resolved);
+                }
+            }
+            None => resolved.push(resolve_revision(arg, unborn)?),
+        }
+    }
+
+    if unborn && !has_revision {
+        info!("The repository has no commits yet; reviewing the staged and unstaged files");
+        let at = resolved.iter().position(|arg| arg == "--").unwrap_or(resolved.len());
+        resolved.insert(at, EMPTY_TREE.to_string());
+    }
+    Ok(resolved)
+}
+
+/// `revision`, or the empty tree if it is the parent of a root commit or HEAD with no commits
+fn resolve_revision(revision: &str, unborn: bool) -> Result<String, ReviewError> {
+    if resolves(revision) {
+        return Ok(revision.to_string());
+    }
+    if unborn {
+        return match revision {
+            "HEAD" | "@" => Ok(EMPTY_TREE.to_string()),
+            _ if revision.starts_with("HEAD") || revision.starts_with('@') => {
+                Err(ReviewError::NoCommitsYet(revision.to_string()))
+            }
+            _ => Ok(revision.to_string()),
+        };
+    }
+
+    let Some((base, generations)) = split_ancestry(revision) else {
+        return Ok(revision.to_string());
+    };
+    if !resolves(base) {
+        return Ok(revision.to_string());
+    }
+    let commits: usize = git_output(&["rev-list", "--count", "--first-parent", base])?
+        .trim()
+        .parse()
+        .map_err(|_| ReviewError::GitFailed(format!("Could not count the commits behind {}", base)))?;
+    if generations == commits {
+        info!("{} is before the first commit; comparing with an empty tree", revision);
+        Ok(EMPTY_TREE.to_string())
+    } else {
+        Err(ReviewError::BeforeFirstCommit {
+            revision: revision.to_string(),
+            base: base.to_string(),
+            commits,
+        })
+    }
+}
+
+/// Split `HEAD~2^` into `HEAD` and the number of first parents it goes back, 3. Other parents
+/// (`^2`) and anything else aren't ancestry this can follow.
+fn split_ancestry(revision: &str) -> Option<(&str, usize)> {
+    let suffix = Regex::new(r"((~\d*)|\^1?)+$").unwrap();
+    let start = suffix.find(revision)?.start();
+    let base = &revision[..start];
+    if base.is_empty() {
+        return None;
+    }
+    let steps = Regex::new(r"~(\d*)|\^1?").unwrap();
+    let generations = steps
+        .captures_iter(&revision[start..])
+        .map(|step| step.get(1).map_or(1, |n| n.as_str().parse().unwrap_or(1)))
+        .sum();
+    Some((base, generations))
+}
+
+/// Environment variables CI services name the branch being built in, checked in order when HEAD
+/// is detached. `GITHUB_HEAD_REF` is only set for pull requests, so it comes before
+/// `GITHUB_REF_NAME`, which is then the merge ref.
+const CI_BRANCH_VARS: &[&str] = &[
+    "GITHUB_HEAD_REF",
+    "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME",
+    "CI_COMMIT_BRANCH",
+    "BUILDKITE_BRANCH",
+    "CIRCLE_BRANCH",
+    "BITBUCKET_BRANCH",
+    "GIT_BRANCH",
+    "GITHUB_REF_NAME",
+];
+
+/// The branch checked out, or with HEAD detached the branch the CI environment names, looked up
+/// with `var`. `None` if there's neither.
+pub fn current_branch(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    if let Ok(branch) = git_output(&["symbolic-ref", "--quiet", "--short", "HEAD"]) {
+        return Some(branch.trim().to_string());
+    }
+    ci_branch(var)
+}
+
+/// The branch the first of `CI_BRANCH_VARS` that's set names. Jenkins' `GIT_BRANCH` is prefixed
+/// with the remote, which is dropped.
+pub fn ci_branch(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let (name, branch) = CI_BRANCH_VARS.iter().find_map(|name| {
+        let value = var(name)?.trim().to_string();
+        (!value.is_empty()).then_some((*name, value))
+    })?;
+    Some(match (name, branch.strip_prefix("origin/")) {
+        ("GIT_BRANCH", Some(branch)) => branch.to_string(),
+        _ => branch,
+    })
+}
+
+/// The branch `origin/HEAD` points at, e.g. `origin/main`, if the clone recorded one
+pub fn default_branch() -> Option<String> {
+    git_output(&["symbolic-ref", "--quiet", "--short", "refs/remotes/origin/HEAD"])
+        .ok()
+        .map(|branch| branch.trim().to_string())
+}
+
+/// The git directory shared by all the repository's worktrees, where state that isn't particular
+/// to one checkout is kept. git gives it relative to the current directory, or absolute.
+pub fn git_common_dir() -> Result<PathBuf, ReviewError> {
+    let dir = PathBuf::from(git_output(&["rev-parse", "--git-common-dir"])?.trim());
+    Ok(env::current_dir().map(|cwd| cwd.join(&dir)).unwrap_or(dir))
+}
+
+/// Whether the repository has any commits, i.e. HEAD isn't unborn
+pub fn has_commits() -> bool {
+    resolves("HEAD")
+}
+
+/// Whether `revision` names a commit
+fn resolves(revision: &str) -> bool {
+    git_output(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", revision)]).is_ok()
+}
+
+/// Run the diff command for `vcs` with the given arguments, returning its output (which may be empty).
+pub fn try_diff(vcs: Vcs, args: &[String]) -> Result<String, ReviewError> {
+    match vcs {
+        Vcs::Git => try_git_diff(args),
+        Vcs::Hg => try_hg_diff(args),
+        Vcs::Jj => try_jj_diff(args),
+    }
+}
+
+/// Translate `git diff` style arguments to `hg diff` ones. Revisions become `-r` options, with
+/// `a..b` split into two and `a...b` diffing from the common ancestor as git does. Options,
+/// anything after `--` and anything naming an existing path are passed through unchanged.
+pub fn hg_revision_args(args: &[String]) -> Vec<String> {
+    let mut hg_args = Vec::new();
+    let mut paths_only = false;
+
+    for arg in args {
+        if paths_only || arg.starts_with('-') || Path::new(arg).exists() {
+            paths_only |= arg == "--";
+            hg_args.push(arg.clone());
+        } else if let Some((base, head)) = arg.split_once("...") {
+            hg_args.extend([
+                "-r".to_string(),
+                format!("ancestor({},{})", base, head),
+                "-r".to_string(),
+                head.to_string(),
+            ]);
+        } else if let Some((base, head)) = arg.split_once("..") {
+            hg_args.extend([
+                "-r".to_string(),
+                base.to_string(),
+                "-r".to_string(),
+                head.to_string(),
+            ]);
+        } else {
+            hg_args.extend(["-r".to_string(), arg.clone()]);
+        }
+    }
+
+    hg_args
+}
+
+/// Translate `git diff` style arguments to `jj diff` ones. A range `a..b` becomes `--from a --to b`,
+/// `a...b` starts from the common ancestor as git does, and a single revision is compared with the
+/// working copy. Options, anything after `--` and anything naming an existing path are passed
+/// through unchanged.
+pub fn jj_revision_args(args: &[String]) -> Vec<String> {
+    let mut jj_args = Vec::new();
+    let mut revisions = Vec::new();
+    let mut paths_only = false;
+
+    for arg in args {
+        if paths_only || arg.starts_with('-') || Path::new(arg).exists() {
+            paths_only |= arg == "--";
+            jj_args.push(arg.clone());
+        } else if let Some((base, head)) = arg.split_once("...") {
+            revisions.push(format!("heads(::{}&::{})", base, head));
+            revisions.push(head.to_string());
+        } else if let Some((base, head)) = arg.split_once("..") {
+            revisions.push(base.to_string());
+            revisions.push(head.to_string());
+        } else {
+            revisions.push(arg.clone());
+        }
+    }
+
+    let mut revision_args = Vec::new();
+    if let Some(from) = revisions.first() {
+        revision_args.extend(["--from".to_string(), from.clone()]);
+    }
+    if let Some(to) = revisions.get(1) {
+        revision_args.extend(["--to".to_string(), to.clone()]);
+    }
+    revision_args.extend(jj_args);
+    revision_args
+}
+
+/// Run `hg diff --git` with the given arguments, returning its output (which may be empty).
+pub fn try_hg_diff(hg_args: &[String]) -> Result<String, ReviewError> {
+    let mut command = Vcs::Hg.diff_command(hg_args);
+
+    debug!("Running command: {:?}", command);
+    let output = command.output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ReviewError::CommandNotFound("hg".to_string()),
+        _ => ReviewError::HgFailed(e.to_string()),
+    })?;
+
+    if !output.status.success() {
+        return Err(ReviewError::HgFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    // There are no git attributes to say how files are encoded, so rely on detection alone
+    Ok(decode_diff_with(&output.stdout, |_| None))
+}
+
+/// Run a user-supplied diff command with `args` appended, returning its output (which may be
+/// empty). Warns if the output doesn't look like a unified diff.
+pub fn try_command_diff(diff_command: &str, args: &[String]) -> Result<String, ReviewError> {
+    let mut words = diff_command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| ReviewError::DiffCommandFailed("the diff command is empty".to_string()))?;
+    let mut command_binding = Command::new(program);
+    let command = command_binding.args(words).args(args);
+
+    debug!("Running command: {:?}", command);
+    let output = command.output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ReviewError::CommandNotFound(program.to_string()),
+        _ => ReviewError::DiffCommandFailed(e.to_string()),
+    })?;
+
+    // Like `diff` itself, a diff command may exit with 1 to say it found differences
+    let found_differences = output.status.code() == Some(1) && !output.stdout.is_empty();
+    if !output.status.success() && !found_differences {
+        return Err(ReviewError::DiffCommandFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    let diff_output = decode_diff(&output.stdout);
+    if !diff_output.trim().is_empty() && parse_diff(&diff_output).is_empty() {
+        warn!(
+            "The output of `{}` doesn't look like a unified diff; the review may not make sense",
+            diff_command
+        );
+    }
+    Ok(diff_output)
+}
+
+/// Run `jj diff --git` with the given arguments, returning its output (which may be empty).
+pub fn try_jj_diff(jj_args: &[String]) -> Result<String, ReviewError> {
+    let mut command = Vcs::Jj.diff_command(jj_args);
+
+    debug!("Running command: {:?}", command);
+    let output = command.output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ReviewError::CommandNotFound("jj".to_string()),
+        _ => ReviewError::JjFailed(e.to_string()),
+    })?;
+
+    if !output.status.success() {
+        return Err(ReviewError::JjFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    Ok(decode_diff_with(&output.stdout, |_| None))
+}
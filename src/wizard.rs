@@ -0,0 +1,219 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::client::{builtin_provider, default_model, BUILTIN_PROVIDERS};
+use crate::config::Config;
+use crate::doctor::{check_round_trip, CheckStatus};
+use crate::error::ReviewError;
+use crate::output::OUTPUT_FORMATS;
+use crate::provider::ProviderConfig;
+use crate::write_atomic::write_atomic;
+
+/// Where the history is kept when the wizard is asked to keep one, relative to where the tool runs
+pub const WIZARD_HISTORY_DIR: &str = ".llm_code_review/history";
+
+/// The first line of a config file the wizard wrote
+const WIZARD_HEADER: &str = "# Written by llm_code_review's setup wizard; --show-config shows the settings in use";
+
+/// Sends the test request to a provider with the API key given, returning the HTTP status
+pub type RoundTrip<'a> = &'a dyn Fn(&ProviderConfig, Option<&str>) -> Result<u16, String>;
+
+/// Where the wizard's questions are asked and answered
+pub trait Terminal {
+    /// Show `message` on a line of its own
+    fn say(&mut self, message: &str) -> io::Result<()>;
+    /// Show `question` and read the answer, trimmed. The end of input is an error.
+    fn ask(&mut self, question: &str) -> io::Result<String>;
+    /// As `ask`, but what's typed isn't shown
+    fn ask_secret(&mut self, question: &str) -> io::Result<String>;
+}
+
+/// The terminal the tool runs in: questions on stderr, so they never end up in the prompt, and
+/// answers from stdin
+pub struct Tty;
+
+impl Terminal for Tty {
+    fn say(&mut self, message: &str) -> io::Result<()> {
+        writeln!(io::stderr(), "{}", message)
+    }
+
+    fn ask(&mut self, question: &str) -> io::Result<String> {
+        write!(io::stderr(), "{}", question)?;
+        io::stderr().flush()?;
+        read_answer()
+    }
+
+    fn ask_secret(&mut self, question: &str) -> io::Result<String> {
+        write!(io::stderr(), "{}", question)?;
+        io::stderr().flush()?;
+        // Without a way to turn echo off the key isn't read at all, rather than shown
+        set_echo(false)?;
+        let answer = read_answer();
+        let restored = set_echo(true);
+        writeln!(io::stderr())?;
+        restored.and(answer)
+    }
+}
+
+fn read_answer() -> io::Result<String> {
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no answer given"));
+    }
+    Ok(answer.trim().to_string())
+}
+
+fn set_echo(on: bool) -> io::Result<()> {
+    let status = Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("stty could not change the terminal's echo"))
+    }
+}
+
+/// Ask `question` until the answer is one of `choices`, or empty for `default`
+fn choose(terminal: &mut dyn Terminal, question: &str, choices: &[&str], default: &str) -> io::Result<String> {
+    loop {
+        let answer = terminal.ask(&format!("{} ({}) [{}]: ", question, choices.join(", "), default))?;
+        if answer.is_empty() {
+            return Ok(default.to_string());
+        }
+        if choices.contains(&answer.as_str()) {
+            return Ok(answer);
+        }
+        terminal.say(&format!("`{}` is not one of {}", answer, choices.join(", ")))?;
+    }
+}
+
+/// Ask a yes or no question, `default` being the answer to an empty line
+fn confirm(terminal: &mut dyn Terminal, question: &str, default: bool) -> io::Result<bool> {
+    let answer = terminal.ask(&format!("{} [{}]: ", question, if default { "Y/n" } else { "y/N" }))?;
+    Ok(match answer.to_lowercase().as_str() {
+        "" => default,
+        answer => answer.starts_with('y'),
+    })
+}
+
+/// Ask for the settings a new user most needs, checking the API key with a request to the
+/// provider. `env` reads an environment variable and `round_trip` sends the test request with the
+/// key given. Returns the settings to save, an empty config if the user never wants to be asked
+/// again, or `None` if nothing should be saved. The key itself is never part of the settings.
+pub fn run_wizard(
+    terminal: &mut dyn Terminal,
+    env: &dyn Fn(&str) -> Option<String>,
+    round_trip: RoundTrip,
+) -> io::Result<Option<Config>> {
+    terminal.say("No config file found.")?;
+    let answer = terminal.ask("Set up your defaults now? [Y/n/never]: ")?.to_lowercase();
+    if answer == "never" {
+        return Ok(Some(Config::default()));
+    }
+    if answer.starts_with('n') {
+        terminal.say("Skipped; pass --no-wizard to not be asked, or answer `never` to save an empty config.")?;
+        return Ok(None);
+    }
+
+    let name = choose(terminal, "Provider", BUILTIN_PROVIDERS, BUILTIN_PROVIDERS[0])?;
+    let mut provider = builtin_provider(&name).expect("a built-in provider");
+    let default = default_model(provider.format).unwrap_or_default();
+    let model = match terminal.ask(&format!("Model [{}]: ", default))? {
+        model if model.is_empty() => default.to_string(),
+        model => model,
+    };
+
+    let usual_var = provider.api_key_var().map(str::to_string);
+    if let Some(usual_var) = &usual_var {
+        let var = match terminal.ask(&format!("Environment variable holding the API key [{}]: ", usual_var))? {
+            var if var.is_empty() => usual_var.clone(),
+            var => var,
+        };
+        provider.api_key_env = Some(var);
+    }
+    loop {
+        let key = match provider.api_key_var() {
+            None => None,
+            Some(var) => match env(var).filter(|key| !key.is_empty()) {
+                Some(key) => Some(key),
+                None => Some(terminal.ask_secret(&format!(
+                    "{} isn't set. Paste the key to check it; it won't be shown or saved: ",
+                    var
+                ))?),
+            },
+        };
+        terminal.say(&format!("Checking {} ...", provider.endpoint))?;
+        let check = check_round_trip(&provider.endpoint, round_trip(&provider, key.as_deref()));
+        terminal.say(&format!("{}: {}", check.status, check.detail))?;
+        if let Some(hint) = &check.hint {
+            terminal.say(&format!("  {}", hint))?;
+        }
+        if check.status != CheckStatus::Fail {
+            break;
+        }
+        if !confirm(terminal, "Try again?", true)? {
+            terminal.say("Nothing saved.")?;
+            return Ok(None);
+        }
+    }
+
+    let format = choose(terminal, "Default output format", OUTPUT_FORMATS, OUTPUT_FORMATS[0])?;
+    let history = confirm(terminal, &format!("Keep a history of reviews in {}?", WIZARD_HISTORY_DIR), false)?;
+    Ok(Some(Config {
+        provider: Some(name),
+        model: Some(model),
+        api_key_env: provider.api_key_env.filter(|var| Some(var) != usual_var.as_ref()),
+        output_format: (format != OUTPUT_FORMATS[0]).then_some(format),
+        history_dir: history.then(|| PathBuf::from(WIZARD_HISTORY_DIR)),
+        ..Config::default()
+    }))
+}
+
+/// The command line options that give the same settings as `config`
+pub fn equivalent_flags(config: &Config) -> String {
+    let mut flags = Vec::new();
+    let mut flag = |name: &str, value: &str| {
+        flags.push(format!("--{} {}", name, quote(value)));
+    };
+    if let Some(provider) = &config.provider {
+        flag("provider", provider);
+    }
+    if let Some(model) = &config.model {
+        flag("model", model);
+    }
+    if let Some(var) = &config.api_key_env {
+        flag("api-key-env", var);
+    }
+    if let Some(format) = &config.output_format {
+        flag("output-format", format);
+    }
+    if let Some(dir) = &config.history_dir {
+        flag("history-dir", &dir.display().to_string());
+    }
+    flags.join(" ")
+}
+
+/// `value` as a shell word, in single quotes unless it needs none
+fn quote(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "._/:-=+@".contains(c)) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Write the wizard's settings to `path`, creating its directory
+pub fn save_config(config: &Config, path: &Path) -> Result<(), ReviewError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| ReviewError::Write(dir.to_path_buf(), e))?;
+    }
+    let toml = toml::to_string(config).map_err(|e| ReviewError::Config(e.to_string()))?;
+    write_atomic(path, format!("{}\n{}", WIZARD_HEADER, toml))
+}
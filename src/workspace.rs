@@ -0,0 +1,360 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::diff::FileDiff;
+use crate::error::ReviewError;
+use crate::severity_policy::glob_regex;
+
+/// How many of a package's dependencies the prompt names
+pub const MAX_LISTED_DEPENDENCIES: usize = 12;
+
+// Directories never searched for workspace members
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// A member of a monorepo workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    pub name: String,
+    /// The package's directory relative to the repository root, empty for the root itself
+    pub dir: String,
+    /// Its manifest relative to the repository root, when it has one
+    pub manifest: Option<String>,
+    pub version: Option<String>,
+    /// The names of the packages it depends on, as its manifest lists them
+    pub dependencies: Vec<String>,
+}
+
+impl Package {
+    /// Whether `path`, relative to the repository root, is in the package
+    pub fn contains(&self, path: &str) -> bool {
+        self.dir.is_empty() || path.strip_prefix(&self.dir).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// The manifest's file name, version and dependencies, marking those in `workspace`
+    pub fn summary(&self, workspace: &Workspace) -> Option<String> {
+        let manifest = self.manifest.as_deref()?;
+        let mut summary = Path::new(manifest).file_name()?.to_string_lossy().to_string();
+        if let Some(version) = &self.version {
+            summary.push_str(&format!(": version {}", version));
+        }
+        if !self.dependencies.is_empty() {
+            let mut names: Vec<String> = self
+                .dependencies
+                .iter()
+                .take(MAX_LISTED_DEPENDENCIES)
+                .map(|name| match workspace.packages.iter().any(|package| &package.name == name) {
+                    true => format!("`{}` (in the workspace)", name),
+                    false => name.clone(),
+                })
+                .collect();
+            if self.dependencies.len() > MAX_LISTED_DEPENDENCIES {
+                names.push(format!("{} more", self.dependencies.len() - MAX_LISTED_DEPENDENCIES));
+            }
+            summary.push_str(if self.version.is_some() { "; " } else { ": " });
+            summary.push_str(&format!("depends on {}", names.join(", ")));
+        }
+        Some(summary)
+    }
+}
+
+/// The packages of a Cargo or npm workspace, or those listed in --packages-file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workspace {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+struct PackagesFile {
+    #[serde(default)]
+    package: Vec<PackageEntry>,
+}
+
+#[derive(Deserialize)]
+struct PackageEntry {
+    name: String,
+    path: String,
+}
+
+impl Workspace {
+    /// The workspace whose root manifest is in `root`: a Cargo.toml with a `[workspace]` table, or
+    /// a package.json with `workspaces`. `None` if there's neither.
+    pub fn detect(root: &Path) -> Result<Option<Self>, ReviewError> {
+        if let Some(workspace) = cargo_workspace(root)? {
+            return Ok(Some(workspace));
+        }
+        npm_workspace(root)
+    }
+
+    /// Read a TOML file of `[[package]]` tables with a `name` and a `path` relative to the
+    /// repository root.
+    pub fn load(path: &Path) -> Result<Self, ReviewError> {
+        let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+        let file: PackagesFile =
+            toml::from_str(&contents).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))?;
+        let packages = file
+            .package
+            .into_iter()
+            .map(|entry| Package {
+                name: entry.name,
+                dir: entry.path.trim_start_matches("./").trim_end_matches('/').to_string(),
+                manifest: None,
+                version: None,
+                dependencies: Vec::new(),
+            })
+            .collect();
+        Ok(Workspace { packages })
+    }
+
+    /// The package named `name`
+    pub fn get(&self, name: &str) -> Result<&Package, ReviewError> {
+        self.packages.iter().find(|package| package.name == name).ok_or_else(|| {
+            let names: Vec<&str> = self.packages.iter().map(|package| package.name.as_str()).collect();
+            ReviewError::Config(format!("no package named `{}` in the workspace; there are {}", name, names.join(", ")))
+        })
+    }
+
+    /// The package `path` is in: the one with the longest directory containing it
+    pub fn owner(&self, path: &str) -> Option<&Package> {
+        self.packages.iter().filter(|package| package.contains(path)).max_by_key(|package| package.dir.len())
+    }
+
+    /// `paths` grouped by the package they're in, in the order the packages are listed, with the
+    /// paths in no package last
+    pub fn group<'a>(&self, paths: &'a [String]) -> Vec<(Option<&Package>, Vec<&'a str>)> {
+        let mut groups: Vec<(Option<&Package>, Vec<&'a str>)> = self
+            .packages
+            .iter()
+            .map(|package| (Some(package), Vec::new()))
+            .chain([(None, Vec::new())])
+            .collect();
+        for path in paths {
+            let owner = self.owner(path);
+            if let Some((_, group)) = groups.iter_mut().find(|(package, _)| *package == owner) {
+                group.push(path);
+            }
+        }
+        groups.retain(|(_, paths)| !paths.is_empty());
+        groups
+    }
+}
+
+fn cargo_workspace(root: &Path) -> Result<Option<Workspace>, ReviewError> {
+    let Some(manifest) = read_toml(&root.join("Cargo.toml"))? else {
+        return Ok(None);
+    };
+    let Some(workspace) = manifest.get("workspace") else {
+        return Ok(None);
+    };
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|value| value.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect()
+    };
+    let mut dirs: Vec<String> = Vec::new();
+    if manifest.get("package").is_some() {
+        dirs.push(String::new());
+    }
+    dirs.extend(member_dirs(root, &strings("members"), &strings("exclude"), "Cargo.toml"));
+
+    let mut packages = Vec::new();
+    for dir in dirs {
+        let path = join(&dir, "Cargo.toml");
+        let Some(manifest) = read_toml(&root.join(&path))? else {
+            continue;
+        };
+        let package = manifest.get("package");
+        let field = |key: &str| package.and_then(|package| package.get(key));
+        let version = field("version").map(|version| match version.as_str() {
+            Some(version) => version.to_string(),
+            None => "from the workspace".to_string(),
+        });
+        let dependencies = manifest
+            .get("dependencies")
+            .and_then(|dependencies| dependencies.as_table())
+            .map(|dependencies| dependencies.keys().cloned().collect())
+            .unwrap_or_default();
+        packages.push(Package {
+            name: field("name").and_then(|name| name.as_str()).map_or_else(|| dir_name(&dir, root), str::to_string),
+            dir,
+            manifest: Some(path),
+            version,
+            dependencies,
+        });
+    }
+    Ok(Some(Workspace { packages }))
+}
+
+fn npm_workspace(root: &Path) -> Result<Option<Workspace>, ReviewError> {
+    let Some(manifest) = read_json(&root.join("package.json"))? else {
+        return Ok(None);
+    };
+    // Either a list of globs, or an object with them in `packages`
+    let Some(globs) = manifest
+        .get("workspaces")
+        .and_then(|workspaces| workspaces.as_array().or_else(|| workspaces.get("packages")?.as_array()))
+    else {
+        return Ok(None);
+    };
+    let globs: Vec<&str> = globs.iter().filter_map(|glob| glob.as_str()).collect();
+    let (excludes, members): (Vec<&str>, Vec<&str>) = globs.into_iter().partition(|glob| glob.starts_with('!'));
+    let members: Vec<String> = members.into_iter().map(str::to_string).collect();
+    let excludes: Vec<String> = excludes.iter().map(|glob| glob[1..].to_string()).collect();
+
+    let mut packages = Vec::new();
+    for dir in member_dirs(root, &members, &excludes, "package.json") {
+        let path = join(&dir, "package.json");
+        let Some(manifest) = read_json(&root.join(&path))? else {
+            continue;
+        };
+        let dependencies = manifest
+            .get("dependencies")
+            .and_then(|dependencies| dependencies.as_object())
+            .map(|dependencies| dependencies.keys().cloned().collect())
+            .unwrap_or_default();
+        packages.push(Package {
+            name: manifest.get("name").and_then(|name| name.as_str()).map_or_else(|| dir_name(&dir, root), str::to_string),
+            version: manifest.get("version").and_then(|version| version.as_str()).map(str::to_string),
+            dir,
+            manifest: Some(path),
+            dependencies,
+        });
+    }
+    Ok(Some(Workspace { packages }))
+}
+
+fn read_toml(path: &Path) -> Result<Option<toml::Value>, ReviewError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+    toml::from_str(&contents).map(Some).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))
+}
+
+fn read_json(path: &Path) -> Result<Option<serde_json::Value>, ReviewError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| ReviewError::Io(path.to_path_buf(), e))?;
+    serde_json::from_str(&contents).map(Some).map_err(|e| ReviewError::Config(format!("{}: {}", path.display(), e)))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() { name.to_string() } else { format!("{}/{}", dir, name) }
+}
+
+fn dir_name(dir: &str, root: &Path) -> String {
+    match dir.rsplit('/').next().filter(|name| !name.is_empty()) {
+        Some(name) => name.to_string(),
+        None => root.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+    }
+}
+
+/// The directories under `root` matching one of the `members` globs and none of the `excludes`
+/// that have a `manifest`, sorted
+pub fn member_dirs(root: &Path, members: &[String], excludes: &[String], manifest: &str) -> Vec<String> {
+    let excludes: Vec<_> = excludes.iter().map(|glob| glob_regex(glob.trim_start_matches("./"))).collect();
+    let mut dirs = Vec::new();
+    for member in members {
+        let member = member.trim_start_matches("./").trim_end_matches('/');
+        let components: Vec<&str> = member.split('/').filter(|component| !component.is_empty()).collect();
+        expand(root, "", &components, &mut dirs);
+    }
+    dirs.retain(|dir| {
+        !dir.is_empty() && root.join(dir).join(manifest).is_file() && !excludes.iter().any(|glob| glob.is_match(dir))
+    });
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Add the directories under `dir` that match `components`, one glob per directory level
+fn expand(root: &Path, dir: &str, components: &[&str], out: &mut Vec<String>) {
+    let Some((first, rest)) = components.split_first() else {
+        out.push(dir.to_string());
+        return;
+    };
+    if *first == "**" {
+        expand(root, dir, rest, out);
+        for subdir in subdirs(root, dir) {
+            expand(root, &join(dir, &subdir), components, out);
+        }
+    } else if first.contains(['*', '?']) {
+        let glob = glob_regex(first);
+        for subdir in subdirs(root, dir).into_iter().filter(|subdir| glob.is_match(subdir)) {
+            expand(root, &join(dir, &subdir), rest, out);
+        }
+    } else if root.join(dir).join(first).is_dir() {
+        expand(root, &join(dir, first), rest, out);
+    }
+}
+
+fn subdirs(root: &Path, dir: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(root.join(dir)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()))
+        .collect()
+}
+
+/// The files of `files` in `package`, by their old or new path
+pub fn restrict_to_package(files: Vec<FileDiff>, package: &Package) -> Vec<FileDiff> {
+    files
+        .into_iter()
+        .filter(|file| {
+            [Some(file.path.as_str()), file.old_path(), file.new_path()]
+                .into_iter()
+                .flatten()
+                .any(|path| package.contains(path))
+        })
+        .collect()
+}
+
+/// The prompt section listing the changed files by package, with each package's manifest
+/// summary, asking for coupling between packages to be flagged if more than one is changed.
+/// `None` if no package is changed.
+pub fn packages_section(workspace: &Workspace, paths: &[String]) -> Option<String> {
+    if workspace.packages.is_empty() || paths.is_empty() {
+        return None;
+    }
+    let groups = workspace.group(paths);
+    let changed = groups.iter().filter(|(package, _)| package.is_some()).count();
+    let mut section = match changed {
+        0 => return None,
+        1 => "The diff changes one package of this monorepo.\n".to_string(),
+        n => format!(
+            "The diff changes {} packages of this monorepo. Flag any coupling between packages that the change \
+             introduces, such as a new dependency between them or one package relying on another's internals.\n",
+            n
+        ),
+    };
+    for (package, paths) in groups {
+        match package {
+            Some(package) => {
+                let dir = match package.dir.as_str() {
+                    "" => "the repository root".to_string(),
+                    dir => format!("`{}`", dir),
+                };
+                section.push_str(&format!("\n### `{}` in {}\n", package.name, dir));
+                if let Some(summary) = package.summary(workspace) {
+                    section.push_str(&format!("{}\n", summary));
+                }
+            }
+            None => section.push_str("\n### Outside any package\n"),
+        }
+        for path in paths {
+            section.push_str(&format!("- `{}`\n", path));
+        }
+    }
+    Some(section.trim_end().to_string())
+}
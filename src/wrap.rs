@@ -0,0 +1,78 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use textwrap::{Options, WordSeparator};
+
+use crate::review::{DIFF_HEADING, RAW_CODE_HEADING};
+
+/// Wrap lines of the prompt longer than `width` at word boundaries. Everything from the diff
+/// heading on is left alone, since wrapping would change the code, as are fenced code blocks and
+/// tables. Wrapped lines keep their indentation, and those of list items line up with the item's
+/// text.
+pub fn wrap_prose_sections(prompt: &str, width: usize) -> String {
+    let diff_headings = [format!("# {}", DIFF_HEADING), format!("# {}", RAW_CODE_HEADING)];
+    wrap_lines(prompt, width, |line| diff_headings.iter().any(|heading| heading == line))
+}
+
+/// Wrap lines of Markdown longer than `width` at word boundaries, as `wrap_prose_sections` does
+/// but for the whole text. Words longer than `width`, such as URLs, are left whole.
+pub fn wrap_markdown(text: &str, width: usize) -> String {
+    wrap_lines(text, width, |_| false)
+}
+
+/// Wrap the lines of `text` up to the first one `stop` matches; that line and the rest are kept
+/// as they are.
+fn wrap_lines(text: &str, width: usize, stop: impl Fn(&str) -> bool) -> String {
+    let mut wrapped: Vec<String> = Vec::new();
+    let mut lines = text.split('\n');
+    // The ``` or ~~~ that opened the fenced code block we're in, if any
+    let mut fence: Option<&str> = None;
+
+    for line in lines.by_ref() {
+        if stop(line) {
+            wrapped.push(line.to_string());
+            break;
+        }
+        let trimmed = line.trim_start();
+        let fence_marker = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker));
+        match (fence, fence_marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            _ => {}
+        }
+        if fence.is_some() || fence_marker.is_some() || trimmed.starts_with('|') || line.len() <= width {
+            wrapped.push(line.to_string());
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        let hanging_indent = if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            format!("{}  ", indent)
+        } else {
+            indent.to_string()
+        };
+        // Only spaces separate words, so URLs and paths aren't split at their slashes
+        let options = Options::new(width)
+            .subsequent_indent(&hanging_indent)
+            .word_separator(WordSeparator::AsciiSpace)
+            .break_words(false);
+        wrapped.extend(textwrap::wrap(line, options).into_iter().map(|l| l.into_owned()));
+    }
+
+    wrapped.extend(lines.map(str::to_string));
+    wrapped.join("\n")
+}
+
+/// The width to wrap what's printed on standard output at, or `None` when it isn't a terminal
+pub fn terminal_width() -> Option<usize> {
+    output_width(io::stdout().is_terminal(), env::var("COLUMNS").ok().as_deref(), textwrap::termwidth)
+}
+
+/// The width to wrap output at: `None` unless it goes to a terminal, and otherwise `COLUMNS` if it
+/// holds a positive number, or else what `detect` finds the terminal's width to be.
+pub fn output_width(is_terminal: bool, columns: Option<&str>, detect: impl FnOnce() -> usize) -> Option<usize> {
+    if !is_terminal {
+        return None;
+    }
+    let columns = columns.and_then(|columns| columns.trim().parse().ok()).filter(|&columns| columns > 0);
+    Some(columns.unwrap_or_else(detect))
+}
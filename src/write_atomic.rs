@@ -0,0 +1,78 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::ReviewError;
+
+/// New contents for a file, written and synced to a temporary file beside it but not yet moved
+/// into place. Dropping it without calling `commit`, as happens when a run fails part way, removes
+/// the temporary file and leaves the destination as it was.
+#[derive(Debug)]
+pub struct StagedFile {
+    temp: PathBuf,
+    target: PathBuf,
+    committed: bool,
+}
+
+/// Write `contents` to a temporary file in the same directory as `path`, so the rename in
+/// `commit` can't cross filesystems
+pub fn stage(path: &Path, contents: &[u8]) -> Result<StagedFile, ReviewError> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| ReviewError::Config(format!("{} is not a file name", path.display())))?;
+    let mut temp_name = format!(".{}.{}.tmp", file_name.to_string_lossy(), process::id()).into();
+    if let Some(parent) = path.parent() {
+        temp_name = parent.join(temp_name);
+    }
+
+    let staged = StagedFile {
+        temp: temp_name,
+        target: path.to_path_buf(),
+        committed: false,
+    };
+    let write_error = |e| ReviewError::Write(staged.temp.clone(), e);
+    let mut file = File::create(&staged.temp).map_err(write_error)?;
+    file.write_all(contents).map_err(write_error)?;
+    file.sync_all().map_err(write_error)?;
+    Ok(staged)
+}
+
+impl StagedFile {
+    /// Where the contents are until `commit`
+    pub fn temp_path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Move the new contents into place, replacing the destination in one step
+    pub fn commit(mut self) -> Result<(), ReviewError> {
+        fs::rename(&self.temp, &self.target).map_err(|e| ReviewError::Write(self.target.clone(), e))?;
+        self.committed = true;
+
+        // Make the rename itself durable; not every platform can open a directory for this
+        let dir = match self.target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if let Err(e) = File::open(dir).and_then(|dir| dir.sync_all()) {
+            debug!("Could not sync {}: {}", dir.display(), e);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StagedFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Replace the contents of `path` so that a reader, or a later run after a crash, sees either the
+/// old contents or the new ones and never a partly written file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), ReviewError> {
+    stage(path, contents.as_ref())?.commit()
+}
@@ -1,4 +1,6 @@
 // use llm_code_review::review::{run, Cli, get_git_diff, DEFAULT_SYSTEM_PROMPT};
+use llm_code_review::budget::{CharsPerToken, TokenEstimator};
+use llm_code_review::error::ReviewError;
 use llm_code_review::review::*;
 use clap::Parser;
 
@@ -11,7 +13,7 @@ mod tests {
         // Use ::parse_from because the default value set
         // via #[arg(default_value_t = 3)] in the Cli struct isn't applied
         // during Cli::default().
-        let cli = Cli::parse_from(&["llm_code_review"]);
+        let cli = Cli::parse_from(["llm_code_review"]);
 
         assert_eq!(cli.unified_context, 3);
         assert!(!cli.verbose);
@@ -21,10 +23,264 @@ mod tests {
 
     #[test]
     fn test_prompt_assembly_with_context() {
-        let mut cli = Cli::default();
+        let diff = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        let mut cli = Cli::parse_from(["llm_code_review"]);
         cli.context = Some("Extra context".to_string());
-        let prompt = DEFAULT_SYSTEM_PROMPT.to_string()
-            + &format!("\n## Additional Context\n{}\n", cli.context.as_ref().unwrap());
-        assert!(prompt.contains("Extra context"));
+        let prompt = try_build_prompt(&cli, diff).unwrap();
+        assert!(prompt.contains("Additional Context"), "{}", prompt);
+        assert!(prompt.contains("Extra context"), "{}", prompt);
+        assert!(prompt.find("Extra context") < prompt.find("+b"), "{}", prompt);
+    }
+
+    #[test]
+    fn test_compute_effective_budget() {
+        assert_eq!(compute_effective_budget(50_000, 0.0), 50_000);
+        assert_eq!(compute_effective_budget(50_000, 0.5), 25_000);
+        assert_eq!(compute_effective_budget(50_000, 0.25), 37_500);
+        // Never leaves the prompt with less than 1000 tokens
+        assert_eq!(compute_effective_budget(50_000, 1.0), 1_000);
+        assert_eq!(compute_effective_budget(500, 0.0), 1_000);
+    }
+
+    #[test]
+    fn test_no_reduce_context_returns_error() {
+        let cli = Cli::parse_from(["llm_code_review", "--no-reduce-context"]);
+        assert!(cli.no_reduce_context);
+
+        let diff = "+".repeat(4_001 * 4);
+        match check_diff_size(&diff, 4_000, &CharsPerToken(4)) {
+            Err(ReviewError::DiffTooLarge {
+                estimated_tokens,
+                max_tokens,
+            }) => {
+                assert_eq!(estimated_tokens, 4_001);
+                assert_eq!(max_tokens, 4_000);
+            }
+            other => panic!("expected DiffTooLarge, got {:?}", other),
+        }
+        assert!(check_diff_size(&diff, 5_000, &CharsPerToken(4)).is_ok());
+    }
+
+    fn context_diff(context: usize) -> String {
+        let lines: String = (0..context).map(|i| format!(" unchanged line {:03}\n", i)).collect();
+        format!("diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,{0} +1,{0} @@\n{1}-old\n+new\n{1}", context * 2 + 1, lines)
+    }
+
+    #[test]
+    fn test_reduce_context_if_needed() {
+        let args = |context: &str| vec!["diff".to_string(), context.to_string(), "main".to_string()];
+        let estimator = CharsPerToken(4);
+        // 200 context lines of 20 bytes are about 1000 tokens
+        let diff = context_diff(100);
+
+        let (reduced, context) = reduce_context_if_needed(&args("-U100"), 3, false, &diff, 1_000, &estimator)
+            .unwrap()
+            .expect("the diff should need reducing");
+        assert!((90..100).contains(&context), "{}", context);
+        assert_eq!(reduced, args(&format!("-U{}", context)));
+        // The reduced diff, as git would produce it, fits
+        assert!(check_diff_size(&context_diff(context), 1_000, &estimator).is_ok());
+        let (reduced, _) = reduce_context_if_needed(&args("--unified=100"), 3, false, &diff, 1_000, &estimator)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reduced, args(&format!("--unified={}", context)));
+        // Pathspecs after `--` are left alone, spaces and all, even if they look like -U
+        let pathspecs = |context: &str| {
+            vec![context.to_string(), "--".to_string(), "my folder/a b.rs".to_string(), "-U7".to_string()]
+        };
+        let (reduced, _) = reduce_context_if_needed(&pathspecs("-U100"), 3, false, &diff, 1_000, &estimator)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reduced, pathspecs(&format!("-U{}", context)));
+
+        assert_eq!(reduce_context_if_needed(&args("-U100"), 3, false, &diff, 2_000, &estimator).unwrap(), None);
+        assert_eq!(
+            reduce_context_if_needed(&args("-U100"), 3, true, &diff, 2_000, &estimator).unwrap(),
+            Some((args("-U100"), 100))
+        );
+
+        // Even without context the changes don't fit
+        let diff = format!("diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -0,0 +1,500 @@\n{}", "+added line\n".repeat(500));
+        match reduce_context_if_needed(&args("-U3"), 3, false, &diff, 1_000, &estimator) {
+            Err(ReviewError::DiffTooLarge { max_tokens, .. }) => assert_eq!(max_tokens, 1_000),
+            other => panic!("expected DiffTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reduce_context_with() {
+        let args = |context: &str| vec!["diff".to_string(), context.to_string(), "main".to_string()];
+        // Seven tokens a byte, so a few lines of context fill the smallest budget
+        struct Dense;
+        impl TokenEstimator for Dense {
+            fn estimate_tokens(&self, text: &str) -> usize {
+                text.len() * 7
+            }
+        }
+        let estimator = Dense;
+        // The diff is regenerated at the reduced context and measured, as git produced it
+        let mut asked = Vec::new();
+        let reduced = reduce_context_with(&args("-U3"), 3, false, context_diff(3), 1_000, &estimator, |args| {
+            asked = args.to_vec();
+            Ok(context_diff(args[1].trim_start_matches("-U").parse().unwrap()))
+        })
+        .unwrap();
+        assert_eq!(asked, args("-U1"));
+        assert_eq!(reduced, context_diff(1));
+        // Nothing is regenerated when the diff fits
+        let diff = reduce_context_with(&args("-U3"), 3, false, context_diff(3), 2_000, &estimator, |_| unreachable!());
+        assert_eq!(diff.unwrap(), context_diff(3));
+
+        // A regenerated diff larger than predicted is trimmed further, and fails only if it
+        // doesn't fit without context
+        let larger = |_: &[String]| Ok(context_diff(2));
+        let reduced = reduce_context_with(&args("-U3"), 3, false, context_diff(3), 1_000, &estimator, larger).unwrap();
+        assert!(estimator.estimate_tokens(&reduced) <= 1_000, "{}", reduced);
+        let added = format!("{}{}", context_diff(0), "+added line\n".repeat(20));
+        match reduce_context_with(&args("-U3"), 3, false, context_diff(3), 1_000, &estimator, |_| Ok(added)) {
+            Err(ReviewError::DiffTooLarge { max_tokens, .. }) => assert_eq!(max_tokens, 1_000),
+            other => panic!("expected DiffTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_system_prompt_sections() {
+        let default = assemble_system_prompt(&[
+            PROMPT_ROLE,
+            PROMPT_REVIEW_RULES,
+            PROMPT_OUTPUT_RULES,
+            PROMPT_TONE,
+        ]);
+        assert_eq!(default, DEFAULT_SYSTEM_PROMPT);
+        assert!(default.starts_with("Please review this PR as if you were a senior engineer.\n\n## Focus Areas\n"));
+
+        // A replacement section without a trailing newline is still separated by a blank line
+        let custom = assemble_system_prompt(&[
+            PROMPT_ROLE,
+            "## Focus Areas\n- Security only",
+            PROMPT_OUTPUT_RULES,
+            PROMPT_TONE,
+        ]);
+        assert!(custom.contains("- Security only\n\n## Review Format\n"));
+        assert!(custom.ends_with(PROMPT_TONE));
+    }
+
+    #[test]
+    fn test_review_type_presets() {
+        let diff = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        let prompt = |args: &[&str]| {
+            let cli = Cli::parse_from([&["llm_code_review"][..], args].concat());
+            try_build_prompt(&cli, diff).unwrap()
+        };
+        assert!(prompt(&[]).contains(PROMPT_REVIEW_RULES));
+        assert_eq!(prompt(&["--review-type", "general"]), prompt(&[]));
+        for (review_type, keywords) in [
+            ("security", ["for security only", "Authentication and authorization", "Injection"]),
+            ("performance", ["for performance only", "Algorithmic complexity", "N+1 queries"]),
+            ("tests", ["for its tests only", "no test covering it", "flaky"]),
+            ("style", ["for style and readability only", "Names that are unclear", "conventions"]),
+        ] {
+            let prompt = prompt(&["--review-type", review_type]);
+            assert!(!prompt.contains(PROMPT_REVIEW_RULES), "{}", prompt);
+            assert!(prompt.starts_with(PROMPT_ROLE), "{}", prompt);
+            for keyword in keywords {
+                assert!(prompt.contains(keyword), "{}: {}", review_type, prompt);
+            }
+        }
+
+        // --system-prompt replaces the preset along with everything else
+        let custom = prompt(&["--review-type", "security", "--system-prompt", "Talk like a pirate."]);
+        assert!(custom.starts_with("Talk like a pirate.") && !custom.contains("security only"), "{}", custom);
+        assert!(Cli::try_parse_from(["llm_code_review", "--review-type", "security", "--rules-file", "rules.md"]).is_err());
+        assert!(Cli::try_parse_from(["llm_code_review", "--review-type", "docs"]).is_err());
+    }
+
+    #[test]
+    fn test_help_examples_need_verbose() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let short = command(&args(&["llm_code_review", "--help"])).render_help().to_string();
+        assert!(!short.contains("Review Examples"));
+        assert!(short.contains("--help --verbose"));
+
+        let full = command(&args(&["llm_code_review", "--help", "--verbose"]))
+            .render_help()
+            .to_string();
+        assert!(full.contains("Review Examples"));
+    }
+
+    #[test]
+    fn test_annotate_sections() {
+        use llm_code_review::budget::CharsPerToken;
+
+        let sections = [
+            ("system_prompt", "Review this.".to_string()),
+            ("diff", "+added\n-removed".to_string()),
+        ];
+        let annotated = annotate_sections(&sections, &CharsPerToken(4));
+        assert_eq!(
+            annotated,
+            "|====BEGIN system_prompt (12 chars, ~3 tokens)====\n\
+             Review this.\n\
+             |====END system_prompt====\n\
+             |====BEGIN diff (15 chars, ~3 tokens)====\n\
+             +added\n-removed\n\
+             |====END diff===="
+        );
+    }
+
+    #[test]
+    fn test_approval_preview() {
+        use llm_code_review::budget::CharsPerToken;
+
+        let preview = approval_preview("Review this.", &CharsPerToken(4));
+        assert_eq!(preview, "Prompt: 12 chars, ~3 tokens\n---\nReview this.\n---");
+
+        let long = "é".repeat(300);
+        let preview = approval_preview(&long, &CharsPerToken(4));
+        assert!(preview.starts_with("Prompt: 600 chars, ~150 tokens\n"));
+        // At most 200 characters, the ellipsis included
+        assert!(preview.ends_with(&format!("\n{}…\n---", "é".repeat(199))));
+    }
+
+    #[test]
+    fn test_confirm_message() {
+        assert_eq!(
+            confirm_message("claude-opus-4-1", 60_000, 60_000, Some(0.9)),
+            "About 60000 prompt tokens to claude-opus-4-1, for about $0.90 before the response."
+        );
+        // A later part of a split diff counts the run's earlier ones
+        assert_eq!(
+            confirm_message("my-model", 20_000, 55_000, None),
+            "About 20000 prompt tokens (55000 this run) to my-model, for an unknown cost before the response."
+        );
+        let cli = Cli::parse_from(["llm_code_review"]);
+        assert_eq!((cli.confirm_threshold, cli.yes), (50_000, false));
+        assert!(Cli::parse_from(["llm_code_review", "-y"]).yes);
+    }
+
+    #[test]
+    fn test_hard_cap_ignores_broken_estimator() {
+        use llm_code_review::budget::TokenEstimator;
+
+        // Claims every prompt is free, as a wrong model table might
+        struct BrokenEstimator;
+        impl TokenEstimator for BrokenEstimator {
+            fn estimate_tokens(&self, _text: &str) -> usize {
+                0
+            }
+        }
+
+        let prompt = "é".repeat(100);
+        assert!(check_hard_cap(&prompt, 100, &BrokenEstimator, 1_000).is_ok());
+        match check_hard_cap(&prompt, 99, &BrokenEstimator, 1_000) {
+            Err(e @ ReviewError::PromptOverHardCap { .. }) => {
+                let message = e.to_string();
+                assert!(message.contains("100 characters (200 bytes)"), "{}", message);
+                assert!(message.contains("limit of 99"), "{}", message);
+                assert!(message.contains("estimated at 0 tokens against a budget of 1000"), "{}", message);
+            }
+            other => panic!("expected PromptOverHardCap, got {:?}", other),
+        }
     }
 }
@@ -0,0 +1,81 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::attention::{assess, attention_section, parse_confidence, AttentionRules, FileAttention};
+use llm_code_review::diff::parse_diff;
+use std::fs;
+
+fn diff(path: &str, removed: &str, added: &str) -> String {
+    format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1 +1 @@\n-{1}\n+{2}\n", path, removed, added)
+}
+
+fn reasons(attention: &[FileAttention], path: &str) -> (bool, Vec<String>) {
+    let file = attention.iter().find(|file| file.path == path).unwrap();
+    (file.needs_human, file.reasons.clone())
+}
+
+#[test]
+fn test_assess_heuristics() {
+    let diffs = [
+        diff("src/buffer.rs", "let x = 1;", "let x = unsafe { read(p) };"),
+        diff("src/pool.rs", "let jobs = Vec::new();", "let jobs = Mutex::new(Vec::new());"),
+        diff("src/auth/login.rs", "let a = 1;", "let a = 2;"),
+        diff("src/format.rs", "fn f(a:i32){}", "fn f(a: i32) {}"),
+        diff("Cargo.lock", "version = 1", "version = 2"),
+        diff("tests/pool_test.rs", "assert!(a);", "assert!(b);"),
+        diff("src/lib.rs", "let a = 1;", "let a = 2;"),
+    ]
+    .concat();
+    let attention = assess(&parse_diff(&diffs), &AttentionRules::default(), &[]);
+    assert_eq!(reasons(&attention, "src/buffer.rs"), (true, vec!["unsafe code".to_string()]));
+    assert_eq!(reasons(&attention, "src/pool.rs"), (true, vec!["concurrency".to_string()]));
+    assert_eq!(reasons(&attention, "src/auth/login.rs"), (true, vec!["authentication or authorization path".to_string()]));
+    assert_eq!(reasons(&attention, "src/format.rs"), (false, vec!["mechanical change".to_string()]));
+    assert_eq!(reasons(&attention, "Cargo.lock"), (false, vec!["mechanical change".to_string()]));
+    assert_eq!(reasons(&attention, "tests/pool_test.rs"), (false, vec!["test-only change".to_string()]));
+    assert_eq!(reasons(&attention, "src/lib.rs"), (false, vec![]));
+
+    // A low confidence from the model outweighs a test-only change; a high one changes nothing
+    let confidence = [("tests/pool_test.rs".to_string(), 55), ("src/lib.rs".to_string(), 95)];
+    let attention = assess(&parse_diff(&diffs), &AttentionRules::default(), &confidence);
+    assert_eq!(
+        reasons(&attention, "tests/pool_test.rs"),
+        (true, vec!["the model's confidence in its review is 55%".to_string()])
+    );
+    assert_eq!(reasons(&attention, "src/lib.rs"), (false, vec![]));
+    assert_eq!(attention.iter().find(|file| file.path == "src/lib.rs").unwrap().confidence, Some(95));
+
+    let section = attention_section(&attention);
+    assert!(section.starts_with("A human should review 4 of 7 files.\n\n- `src/buffer.rs`: needs a human (unsafe code)"), "{}", section);
+    assert!(section.ends_with("- `src/lib.rs`: LLM review is likely enough"), "{}", section);
+}
+
+#[test]
+fn test_attention_rules() {
+    let dir = temp_dir("attention");
+    let path = dir.join("attention.toml");
+    fs::write(
+        &path,
+        "[[human]]\npath = \"billing/**\"\nreason = \"money\"\n\n[[llm]]\npath = \"docs/**\"\nreason = \"documentation\"\n",
+    )
+    .unwrap();
+    let rules = AttentionRules::load(&path).unwrap();
+    let diffs = [diff("billing/invoice.rs", "a", "b"), diff("docs/guide.md", "a", "b")].concat();
+    let attention = assess(&parse_diff(&diffs), &rules, &[]);
+    assert_eq!(reasons(&attention, "billing/invoice.rs"), (true, vec!["money".to_string()]));
+    assert_eq!(reasons(&attention, "docs/guide.md"), (false, vec!["documentation".to_string()]));
+
+    fs::write(&path, "[[human]]\nreason = \"no path\"\n").unwrap();
+    assert!(AttentionRules::load(&path).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_confidence() {
+    let review = "Looks fine.\n\n## Review Confidence\n- `src/a.rs`: 85\n* src/b.rs: 40%\n- src/c.rs: 250\nnot a line\n\n## Other\n- src/d.rs: 10\n";
+    assert_eq!(
+        parse_confidence(review),
+        vec![("src/a.rs".to_string(), 85), ("src/b.rs".to_string(), 40)]
+    );
+    assert!(parse_confidence("- src/a.rs: 85").is_empty());
+}
@@ -0,0 +1,183 @@
+use llm_code_review::budget::{
+    estimate_tokens, fit_to_budget, write_budget_report, Budget, BudgetAction, BudgetReport, CharsPerToken,
+    TokenEstimator, MIN_PROMPT_TOKENS,
+};
+use llm_code_review::compress::trim_context;
+use llm_code_review::diff::{parse_diff, render_diff, HunkRange};
+use llm_code_review::diff_filter::filter_small_hunks;
+use llm_code_review::error::ReviewError;
+use llm_code_review::provider::load_provider_config;
+use proptest::prelude::*;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+const MIN_CHANGE_SIZE_DIFF: &str = include_str!("fixtures/min_change_size.diff");
+
+#[test]
+fn test_budget_report_records_dropped_files() {
+    let filtered = render_diff(&filter_small_hunks(parse_diff(MIN_CHANGE_SIZE_DIFF), 5));
+    let report = BudgetReport::from_diffs(MIN_CHANGE_SIZE_DIFF, &filtered, false, 1_000, &CharsPerToken(4));
+
+    let actions: Vec<(&str, BudgetAction)> = report
+        .files
+        .iter()
+        .map(|file| (file.path.as_str(), file.action))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            ("Cargo.toml", BudgetAction::Dropped),
+            ("src/lib.rs", BudgetAction::Kept),
+            ("logo.png", BudgetAction::Kept),
+        ]
+    );
+    assert_eq!(
+        report.used_tokens(),
+        (report.files[1].bytes + report.files[2].bytes) / 4
+    );
+
+    let table = report.to_string();
+    assert!(table.starts_with("File"));
+    assert!(table.contains("dropped"));
+    assert!(table.ends_with(&format!("of 1000 tokens ({:.1}%)", report.used_tokens() as f64 / 10.0)));
+}
+
+#[test]
+fn test_write_budget_report_json() {
+    let mut report = BudgetReport::from_diffs(MIN_CHANGE_SIZE_DIFF, MIN_CHANGE_SIZE_DIFF, false, 1_000, &CharsPerToken(4));
+    let prompt = format!("system{}\n\n# PR Code\n\n{}", "context", MIN_CHANGE_SIZE_DIFF);
+    report.record_prompt(
+        &prompt,
+        &[("system_prompt", "system"), ("context", "context"), ("diff", MIN_CHANGE_SIZE_DIFF)],
+        &CharsPerToken(4),
+    );
+
+    let path = std::env::temp_dir().join(format!("llm_code_review_budget_{}.json", std::process::id()));
+    write_budget_report(&report, &path).unwrap();
+    let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(json["total_chars"], prompt.len());
+    assert_eq!(json["estimated_tokens"], prompt.len() / 4);
+    assert_eq!(json["budget"], 1_000);
+    assert_eq!(json["utilization_pct"], (prompt.len() / 4) as f64 / 10.0);
+    assert_eq!(json["sections"]["system_prompt"], 6);
+    assert_eq!(json["sections"]["context"], 7);
+    assert_eq!(json["sections"]["diff"], MIN_CHANGE_SIZE_DIFF.len());
+    assert_eq!(json["files"][0]["path"], "Cargo.toml");
+    assert_eq!(json["files"][0]["action"], "kept");
+}
+
+/// A diff of a file of `lines` lines with a change every `gap` lines, all in one hunk
+fn spread_out_diff(lines: usize, gap: usize) -> String {
+    let mut body = String::new();
+    for n in 1..=lines {
+        if n % gap == 0 {
+            body.push_str(&format!("-line {}\n+line {} changed\n", n, n));
+        } else {
+            body.push_str(&format!(" line {}\n", n));
+        }
+    }
+    format!("--- a/f.txt\n+++ b/f.txt\n@@ -1,{} +1,{} @@ fn f()\n{}", lines, lines, body)
+}
+
+#[test]
+fn test_estimate_tokens_per_model() {
+    let providers = load_provider_config(Path::new("tests/fixtures/providers.toml")).unwrap();
+    let text = "x".repeat(120);
+    assert_eq!(estimate_tokens(&text, None), 30);
+    assert_eq!(estimate_tokens(&text, Some(&providers[1])), 40);
+    assert_eq!(Budget::for_model(Some(&providers[1])).limit, 8_000);
+}
+
+#[test]
+fn test_budget() {
+    let mut budget = Budget::new(50_000).reserving_output(0.25);
+    assert_eq!(budget.reserved_output, 12_500);
+    assert_eq!(budget.prompt_limit(), 37_500);
+    budget.spend(37_000);
+    assert_eq!(budget.remaining(), 500);
+    budget.spend(1_000);
+    assert_eq!(budget.remaining(), 0);
+    assert!(!budget.fits(1));
+    // The prompt always gets something, however much is reserved
+    assert_eq!(Budget::new(50_000).reserving_output(1.0).prompt_limit(), MIN_PROMPT_TOKENS);
+}
+
+#[test]
+fn test_trim_context() {
+    let files = trim_context(parse_diff(&spread_out_diff(30, 10)), 2);
+    let hunk = &files[0].hunks[0];
+    // Lines 1-7 are dropped, and the 9 lines between changes keep 2 at each end
+    assert_eq!(HunkRange::parse(&hunk.header), Some(HunkRange { old_start: 8, old_len: 23, new_start: 8, new_len: 23 }));
+    assert!(hunk.header.ends_with("@@ fn f()"), "{}", hunk.header);
+    assert_eq!(hunk.lines[..3], [" line 8", " line 9", "-line 10"]);
+    assert_eq!(hunk.lines[5..8], [" line 12", " … 5 unchanged lines …", " line 18"]);
+    assert_eq!(hunk.lines.last().map(String::as_str), Some("+line 30 changed"));
+
+    // The trimmed diff still parses the same way
+    let rendered = render_diff(&files);
+    assert_eq!(parse_diff(&rendered), files);
+}
+
+#[test]
+fn test_fit_to_budget() {
+    let diff = spread_out_diff(3_000, 100);
+    let estimator = CharsPerToken(4);
+    let budget = Budget::new(2_000);
+    let fitted = fit_to_budget(&diff, 100, &budget, &estimator).unwrap();
+    assert!(fitted.context_lines < 100);
+    assert!(fitted.estimated_tokens <= 2_000);
+    assert_eq!(fitted.estimated_tokens, estimator.estimate_tokens(&fitted.diff));
+    // One more line of context wouldn't have fitted
+    let more = render_diff(&trim_context(parse_diff(&diff), fitted.context_lines + 1));
+    assert!(!budget.fits(estimator.estimate_tokens(&more)));
+
+    // A diff that fits is left alone
+    let fitted = fit_to_budget(&diff, 100, &Budget::new(1_000_000), &estimator).unwrap();
+    assert_eq!((fitted.diff, fitted.context_lines), (diff.clone(), 100));
+
+    let mut spent = Budget::new(2_000);
+    spent.spend(1_990);
+    match fit_to_budget(&diff, 100, &spent, &estimator) {
+        Err(ReviewError::DiffTooLarge { max_tokens, .. }) => assert_eq!(max_tokens, 10),
+        other => panic!("expected DiffTooLarge, got {:?}", other),
+    }
+}
+
+proptest! {
+    #[test]
+    fn estimates_grow_with_the_text(text in ".*", more in ".*", chars_per_token in 1usize..8) {
+        let estimator = CharsPerToken(chars_per_token);
+        let longer = format!("{}{}", text, more);
+        prop_assert!(estimator.estimate_tokens(&text) <= estimator.estimate_tokens(&longer));
+    }
+
+    #[test]
+    fn fitting_never_exceeds_the_limit(
+        lines in 1usize..400,
+        gap in 1usize..60,
+        context in 0usize..50,
+        limit in 1usize..4_000,
+        used in 0usize..2_000,
+    ) {
+        let diff = spread_out_diff(lines, gap);
+        let mut budget = Budget::new(limit);
+        budget.spend(used);
+        let estimator = CharsPerToken(4);
+        match fit_to_budget(&diff, context, &budget, &estimator) {
+            Ok(fitted) => {
+                prop_assert!(budget.fits(fitted.estimated_tokens));
+                prop_assert_eq!(fitted.estimated_tokens, estimator.estimate_tokens(&fitted.diff));
+                // Every change is still there
+                let changes = |diff: &str| diff.lines().filter(|l| l.starts_with("-line") || l.starts_with("+line")).count();
+                prop_assert_eq!(changes(&fitted.diff), changes(&diff));
+            }
+            Err(ReviewError::DiffTooLarge { estimated_tokens, max_tokens }) => {
+                prop_assert!(estimated_tokens > max_tokens);
+            }
+            Err(e) => prop_assert!(false, "unexpected error {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::canary::{canary_for, check_canary, strip_canary, CanaryCheck};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_canary() {
+    let canary = canary_for("diff --git a/x b/x\n");
+    assert_eq!(canary, canary_for("diff --git a/x b/x\n"));
+    assert_ne!(canary, canary_for("diff --git a/y b/y\n"));
+    assert!(canary.starts_with("LLMCR-") && canary.len() == 14, "{}", canary);
+
+    let review = format!("The change looks fine.\n\n<!-- end-of-diff: {} -->\n", canary);
+    assert_eq!(check_canary(&review, &canary), CanaryCheck::Found);
+    assert_eq!(strip_canary(&review), "The change looks fine.\n");
+    assert_eq!(check_canary("The change looks fine.\n", &canary), CanaryCheck::Missing);
+    let made_up = "Fine.\n<!--end-of-diff: LLMCR-00000000-->";
+    assert_eq!(check_canary(made_up, &canary), CanaryCheck::Wrong("LLMCR-00000000".to_string()));
+    assert_eq!(strip_canary(made_up), "Fine.");
+    assert!(CanaryCheck::Missing.to_string().starts_with("the model may not have processed the full diff"));
+}
+
+#[test]
+fn test_check_response() {
+    let dir = temp_dir("canary");
+    init_repo(&dir);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+
+    let prompt = review(&["--canary"]);
+    assert!(prompt.contains("End your response with the line `<!-- end-of-diff: CODE -->`"), "{}", prompt);
+    let code_line = prompt.lines().find(|line| line.starts_with("Verification code: ")).unwrap();
+    assert!(prompt.contains(&format!("+two\n\n{}", code_line)), "{}", prompt);
+    let canary = code_line.trim_start_matches("Verification code: ");
+    assert!(!review(&[]).contains(canary));
+
+    fs::write(dir.join("good.md"), format!("Rename `two`.\n<!-- end-of-diff: {} -->\n", canary)).unwrap();
+    let output = review(&["--check-response", "good.md", "--strict-canary"]);
+    assert_eq!(output, "Rename `two`.\n");
+
+    fs::write(dir.join("cut_off.md"), "Rename `two`.\n").unwrap();
+    let output = review(&["--check-response", "cut_off.md"]);
+    assert!(output.contains("the model may not have processed the full diff"), "{}", output);
+    assert!(output.ends_with("Rename `two`.\n"), "{}", output);
+
+    let output = review(&["--check-response", "cut_off.md", "--strict-canary"]);
+    assert!(output.contains("writing the prompt again, split into parts"), "{}", output);
+    assert!(output.contains("This is part 1, covering a.txt"), "{}", output);
+    assert!(output.contains(code_line), "{}", output);
+    assert!(!output.contains("Rename `two`."), "{}", output);
+}
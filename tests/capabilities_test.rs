@@ -0,0 +1,127 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::capabilities::{render_probes, Capabilities, Probe};
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+fn git(version: &'static str) -> impl Fn() -> Result<String, String> + Send + Sync + 'static {
+    move || Ok(version.to_string())
+}
+
+/// A directory of executable stand-ins for `names`, as a PATH
+#[cfg(unix)]
+fn path_with(test: &str, names: &[&str]) -> OsString {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir(&format!("capabilities_{}", test));
+    for name in names {
+        fs::write(dir.join(name), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(dir.join(name), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    std::env::join_paths([Path::new("/nonexistent"), &dir]).unwrap()
+}
+
+#[test]
+fn test_git_probes() {
+    let current = Capabilities::new(OsString::new(), None, git("git version 2.43.0"));
+    assert_eq!(current.git_version(), Some((2, 43)));
+    assert!(current.range_diff() && current.color_moved());
+
+    // Too old for range-diff, but not for --color-moved
+    let older = Capabilities::new(OsString::new(), None, git("git version 2.17.1"));
+    assert!(!older.range_diff() && older.color_moved());
+    let ancient = Capabilities::new(OsString::new(), None, git("git version 1.8.3.1"));
+    assert!(!ancient.range_diff() && !ancient.color_moved());
+
+    // Without git, or with a version that can't be read, neither is used
+    let missing = Capabilities::new(OsString::new(), None, || Err("No such file or directory".to_string()));
+    assert_eq!(missing.git_version(), None);
+    assert!(!missing.range_diff() && !missing.color_moved());
+    assert_eq!(missing.report()[0], Probe { name: "git", available: false, detail: "could not run git: No such file or directory".to_string() });
+    let unknown = Capabilities::new(OsString::new(), None, git("hub version 2.14"));
+    assert!(!unknown.range_diff());
+}
+
+#[test]
+fn test_probes_run_once() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counted = runs.clone();
+    let capabilities = Capabilities::new(OsString::new(), None, move || {
+        counted.fetch_add(1, Ordering::SeqCst);
+        Ok("git version 2.43.0".to_string())
+    });
+    assert_eq!(runs.load(Ordering::SeqCst), 0);
+    assert!(capabilities.range_diff() && capabilities.color_moved());
+    capabilities.report();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_clipboard_probe() {
+    let none = Capabilities::new(OsString::new(), None, git("git version 2.43.0"));
+    assert_eq!(none.clipboard(), None);
+    #[cfg(unix)]
+    {
+        let found = Capabilities::new(path_with("clipboard", &["xsel"]), None, git("git version 2.43.0"));
+        assert_eq!(found.clipboard(), Some("xsel"));
+    }
+}
+
+#[test]
+fn test_pager_probe() {
+    let none = Capabilities::new(OsString::new(), None, git("git version 2.43.0"));
+    assert_eq!(none.pager(), None);
+    let unset = none.report().into_iter().find(|probe| probe.name == "pager").unwrap();
+    assert_eq!(unset.detail, "none of less, more found");
+    let missing = Capabilities::new(OsString::new(), Some("most -s".to_string()), git("git version 2.43.0"));
+    assert_eq!(missing.pager(), None);
+    let report = missing.report().into_iter().find(|probe| probe.name == "pager").unwrap();
+    assert_eq!((report.available, report.detail.as_str()), (false, "PAGER is most -s, which wasn't found"));
+    #[cfg(unix)]
+    {
+        let path = path_with("pager", &["more", "most"]);
+        assert_eq!(Capabilities::new(path.clone(), None, git("git version 2.43.0")).pager(), Some("more"));
+        assert_eq!(Capabilities::new(path, Some("most -s".to_string()), git("git version 2.43.0")).pager(), Some("most"));
+    }
+}
+
+#[test]
+fn test_keyring_probe() {
+    let none = Capabilities::new(OsString::new(), None, git("git version 2.43.0"));
+    assert_eq!(none.keyring(), None);
+    assert!(!none.report().iter().find(|probe| probe.name == "keyring").unwrap().available);
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let found = Capabilities::new(path_with("keyring", &["secret-tool"]), None, git("git version 2.43.0"));
+        assert_eq!(found.keyring(), Some("secret-tool"));
+    }
+}
+
+#[test]
+fn test_render_probes() {
+    let capabilities = Capabilities::new(OsString::new(), None, git("git version 2.17.1"));
+    let table = render_probes(&capabilities.report());
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines[0], "Capability   Available  Details");
+    assert_eq!(lines[1], "git          yes        git version 2.17.1");
+    assert_eq!(lines[2], "range-diff   no         needs git 2.19");
+    assert_eq!(lines[3], "color-moved  yes        needs git 2.15");
+    assert_eq!(lines[7], "network      yes        allowed");
+    assert_eq!(lines.len(), 8);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .arg("--capabilities")
+        .env("PATH", "")
+        .output()
+        .expect("failed to run llm_code_review");
+    // Nothing is found on an empty PATH, which is reported rather than an error
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("git          no         could not run git"), "{}", stdout);
+    assert!(stdout.contains("range-diff   no"), "{}", stdout);
+}
@@ -0,0 +1,47 @@
+use llm_code_review::category::{category_instructions, Taxonomy, BUILTIN_CATEGORIES};
+use std::path::Path;
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_select_builtin_categories() {
+    let taxonomy = Taxonomy::default();
+    assert_eq!(taxonomy.names().len(), BUILTIN_CATEGORIES.len());
+
+    let selected = taxonomy.select(&names(&["concurrency", "error-handling"])).unwrap();
+    assert_eq!(selected[0].name, "concurrency");
+    assert_eq!(selected[1].name, "error-handling");
+
+    let instructions = category_instructions(&selected);
+    assert!(instructions.contains("\n- concurrency: data races"), "{}", instructions);
+    assert!(instructions.contains("\n- error-handling: ignored errors"), "{}", instructions);
+}
+
+#[test]
+fn test_unknown_category_lists_taxonomy() {
+    let error = Taxonomy::default()
+        .select(&names(&["concurrency", "vibes"]))
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("unknown category \"vibes\""), "{}", error);
+    assert!(error.contains("correctness, security, performance, concurrency"), "{}", error);
+}
+
+#[test]
+fn test_extend_taxonomy_from_file() {
+    let mut taxonomy = Taxonomy::default();
+    taxonomy
+        .extend_from_file(Path::new("tests/fixtures/categories.toml"))
+        .unwrap();
+
+    assert_eq!(taxonomy.names().len(), BUILTIN_CATEGORIES.len() + 1);
+    assert_eq!(taxonomy.names().last(), Some(&"feature-flags"));
+    // Team-specific descriptions replace the built-in ones
+    let testing = taxonomy.select(&names(&["testing"])).unwrap();
+    assert_eq!(
+        testing[0].description,
+        "missing tests, and tests that don't use the shared fixtures"
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::chunk_skip::{
+    add_excludes, chunk_label, load_excludes, parse_excludes, run_skippable, skipped_note, KeyWatcher, EXCLUDE_FILE,
+};
+use llm_code_review::error::ReviewError;
+use llm_code_review::locale::Locale;
+use std::fs;
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_finished_chunk() {
+    let (_typed, lines) = mpsc::channel();
+    let keys = KeyWatcher::new(lines);
+    let review = run_skippable(&keys, "chunk 1/2 (a.rs)", None, || Ok("LGTM".to_string())).unwrap();
+    assert_eq!(review.as_deref(), Some("LGTM"));
+
+    let failed: Result<Option<String>, ReviewError> =
+        run_skippable(&keys, "chunk 2/2 (b.rs)", None, || Err(ReviewError::Provider("HTTP 500".to_string())));
+    assert!(matches!(failed, Err(ReviewError::Provider(message)) if message == "HTTP 500"));
+}
+
+#[test]
+fn test_skipped_chunk() {
+    let (typed, lines) = mpsc::channel();
+    let keys = KeyWatcher::new(lines);
+    // A key pressed before the chunk started doesn't skip it
+    typed.send(String::new()).unwrap();
+    let review = run_skippable(&keys, "chunk 1/2 (a.rs)", None, || Ok("LGTM".to_string())).unwrap();
+    assert_eq!(review.as_deref(), Some("LGTM"));
+
+    let started = Instant::now();
+    let pressing = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        typed.send(String::new()).unwrap();
+        typed
+    });
+    let review = run_skippable(&keys, "chunk 2/2 (dump.sql)", None, || {
+        thread::sleep(Duration::from_secs(30));
+        Ok("too late".to_string())
+    })
+    .unwrap();
+    assert_eq!(review, None);
+    assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+
+    // What's typed after the skip is the answer to the next question
+    let typed = pressing.join().unwrap();
+    typed.send("y".to_string()).unwrap();
+    assert_eq!(keys.ask("Leave dump.sql out?").as_deref(), Some("y"));
+    drop(typed);
+    assert_eq!(keys.ask("Leave dump.sql out?"), None);
+}
+
+#[test]
+fn test_chunk_without_input() {
+    // With input closed, the chunk can only finish
+    let (typed, lines) = mpsc::channel::<String>();
+    drop(typed);
+    let keys = KeyWatcher::new(lines);
+    let review = run_skippable(&keys, "chunk 1/1 (a.rs)", None, || {
+        thread::sleep(Duration::from_millis(300));
+        Ok(1)
+    })
+    .unwrap();
+    assert_eq!(review, Some(1));
+}
+
+#[test]
+fn test_skipped_note() {
+    let paths = vec!["db/dump.sql".to_string()];
+    assert_eq!(chunk_label(2, 5, &paths), "chunk 2/5 (db/dump.sql)");
+    assert_eq!(skipped_note(2, 5, &paths, Locale::En), "_Chunk 2 of 5 was skipped, so this file was not reviewed: db/dump.sql._");
+    let paths = vec!["a.sql".to_string(), "b.sql".to_string()];
+    assert_eq!(skipped_note(1, 3, &paths, Locale::En), "_Chunk 1 of 3 was skipped, so these files were not reviewed: a.sql, b.sql._");
+}
+
+#[test]
+fn test_exclude_file() {
+    assert_eq!(parse_excludes("# generated\n/db/dump.sql\n\n  *.min.js  \n"), vec!["/db/dump.sql", "*.min.js"]);
+
+    let dir = temp_dir("file");
+    assert!(load_excludes(&dir).unwrap().is_empty());
+
+    let added = add_excludes(&dir, &["db/dump.sql".to_string()]).unwrap();
+    assert_eq!(added, vec!["/db/dump.sql"]);
+    assert_eq!(fs::read_to_string(dir.join(EXCLUDE_FILE)).unwrap(), "/db/dump.sql\n");
+
+    // Paths already in the file aren't added again, and what's there is kept
+    fs::write(dir.join(EXCLUDE_FILE), "# Never send these\n/db/dump.sql").unwrap();
+    let added = add_excludes(&dir, &["db/dump.sql".to_string(), "seed.sql".to_string(), "seed.sql".to_string()]).unwrap();
+    assert_eq!(added, vec!["/seed.sql"]);
+    assert_eq!(fs::read_to_string(dir.join(EXCLUDE_FILE)).unwrap(), "# Never send these\n/db/dump.sql\n/seed.sql\n");
+    assert_eq!(load_excludes(&dir).unwrap(), vec!["/db/dump.sql", "/seed.sql"]);
+    assert!(add_excludes(&dir, &["seed.sql".to_string()]).unwrap().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_excluded_files_left_out_of_prompt() {
+    let dir = temp_dir("prompt");
+    init_repo(&dir);
+    fs::create_dir_all(dir.join("db")).unwrap();
+    fs::write(dir.join("db/dump.sql"), "INSERT INTO t VALUES (1);\n").unwrap();
+    fs::write(dir.join("lib.rs"), "fn a() {}\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Start"]);
+    fs::write(dir.join("db/dump.sql"), "INSERT INTO t VALUES (2);\n").unwrap();
+    fs::write(dir.join("lib.rs"), "fn b() {}\n").unwrap();
+    add_excludes(&dir, &["db/dump.sql".to_string()]).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("+fn b() {}"), "{}", stdout);
+    assert!(!stdout.contains("VALUES (2)"), "{}", stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
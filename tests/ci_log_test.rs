@@ -0,0 +1,60 @@
+use llm_code_review::ci_log::{ci_log_section, denoise_log, log_tail};
+use llm_code_review::github::parse_actions_job_url;
+
+const ACTIONS_LOG: &str = include_str!("fixtures/actions_job.log");
+
+#[test]
+fn test_denoise_actions_log() {
+    assert_eq!(
+        denoise_log(ACTIONS_LOG),
+        "Run actions/checkout@v4\n\
+         with:\n  \
+           repository: xunker/llm_code_review\n\
+         Syncing repository: xunker/llm_code_review\n\
+         \n\
+         Run cargo test --workspace\n\
+         cargo test --workspace\n\
+         shell: /usr/bin/bash -e {0}\n   \
+            Compiling llm_code_review v1.0.0 (/home/runner/work/llm_code_review)\n\
+         test test_hard_cap ... ok\n\
+         test test_split_at_newline_boundary ... FAILED\n\
+         \n\
+         ---- test_split_at_newline_boundary stdout ----\n\
+         thread 'test_split_at_newline_boundary' panicked at tests/diff_filter_test.rs:88:5:\n\
+         assertion `left == right` failed\n\
+         error: Process completed with exit code 101."
+    );
+}
+
+#[test]
+fn test_log_tail() {
+    let log = "one\ntwo\nthree\nfour";
+    assert_eq!(log_tail(log, 100), log);
+    assert_eq!(log_tail(log, 11), "[… 2 earlier lines left out …]\nthree\nfour");
+}
+
+#[test]
+fn test_ci_log_section() {
+    let section = ci_log_section(
+        &["tests/fixtures/actions_job.log".to_string(), "tests/fixtures/missing.log".to_string()],
+        120,
+        None,
+    )
+    .unwrap();
+    assert!(section.starts_with("CI is failing for this change."), "{}", section);
+    assert!(section.contains("### tests/fixtures/actions_job.log\n```\n[… "), "{}", section);
+    assert!(section.ends_with("error: Process completed with exit code 101.\n```"), "{}", section);
+    // The missing log is left out rather than failing the run
+    assert!(!section.contains("missing.log"), "{}", section);
+
+    assert_eq!(ci_log_section(&["tests/fixtures/missing.log".to_string()], 120, None), None);
+}
+
+#[test]
+fn test_parse_actions_job_url() {
+    assert_eq!(
+        parse_actions_job_url("https://github.com/xunker/llm_code_review/actions/runs/123456/job/789?pr=4"),
+        Some(("xunker".to_string(), "llm_code_review".to_string(), "789".to_string()))
+    );
+    assert_eq!(parse_actions_job_url("https://github.com/xunker/llm_code_review/actions/runs/123456"), None);
+}
@@ -0,0 +1,35 @@
+use llm_code_review::citations::{has_valid_citations, is_valid_citation, parse_citations, Citation};
+use llm_code_review::diff::parse_diff;
+
+const API_ONLY_DIFF: &str = include_str!("fixtures/api_only.diff");
+
+fn citation(path: &str, line: usize) -> Citation {
+    Citation {
+        path: path.to_string(),
+        line,
+    }
+}
+
+#[test]
+fn test_parse_citations() {
+    assert_eq!(
+        parse_citations("See src/lib.rs:2 and ::warning file=build.py,line=1::Capitalised"),
+        [citation("src/lib.rs", 2), citation("build.py", 1)]
+    );
+    assert!(parse_citations("The error handling could be better.").is_empty());
+}
+
+#[test]
+fn test_citations_checked_against_diff() {
+    let files = parse_diff(API_ONLY_DIFF);
+
+    // src/lib.rs has hunks covering new lines 1-5 and 20-25
+    assert!(is_valid_citation(&citation("src/lib.rs", 2), &files));
+    assert!(is_valid_citation(&citation("lib.rs", 25), &files));
+    assert!(!is_valid_citation(&citation("src/lib.rs", 12), &files));
+    assert!(!is_valid_citation(&citation("src/main.rs", 2), &files));
+
+    assert!(has_valid_citations("src/lib.rs:2 returns a Result now", &files));
+    assert!(!has_valid_citations("src/lib.rs:2 and src/lib.rs:99", &files));
+    assert!(!has_valid_citations("Consider adding tests.", &files));
+}
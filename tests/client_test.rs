@@ -0,0 +1,464 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::client::{
+    builtin_provider, parse_extra_param, provider_from_env, with_extra_params, Anthropic, Ollama, OpenAi, Provider,
+    RequestSettings,
+};
+use llm_code_review::progress::GenerationLimits;
+use llm_code_review::provider::ProviderFormat;
+use serde_json::json;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+
+fn settings(model: &str) -> RequestSettings {
+    RequestSettings {
+        endpoint: "http://127.0.0.1:9/".to_string(),
+        model: model.to_string(),
+        key: None,
+        max_tokens: 50_000,
+        limits: GenerationLimits::default(),
+        timeout: None,
+        chars_per_token: 4,
+        extra_params: Vec::new(),
+    }
+}
+
+#[test]
+fn test_request_body() {
+    assert_eq!(
+        Anthropic(settings("claude-test")).request_body("Be brief.", "Review this"),
+        json!({
+            "model": "claude-test",
+            "max_tokens": 50_000,
+            "system": "Be brief.",
+            "messages": [{"role": "user", "content": "Review this"}]
+        })
+    );
+    let body = OpenAi(settings("gpt-test")).request_body("Be brief.", "Review this");
+    assert_eq!(body["max_completion_tokens"], 50_000);
+    assert_eq!(body["messages"][0], json!({"role": "system", "content": "Be brief."}));
+    let body = Ollama(settings("llama-test")).request_body("", "Review this");
+    assert_eq!(body["messages"], json!([{"role": "user", "content": "Review this"}]));
+    assert_eq!((body["stream"].clone(), body["options"]["num_predict"].clone()), (json!(false), json!(50_000)));
+}
+
+#[test]
+fn test_extra_params() {
+    assert_eq!(parse_extra_param("top_p=0.9"), Ok(("top_p".to_string(), json!(0.9))));
+    assert_eq!(
+        parse_extra_param(r#"response_format={"type":"json_object"}"#),
+        Ok(("response_format".to_string(), json!({"type": "json_object"})))
+    );
+    assert_eq!(parse_extra_param(r#"reasoning_effort="high""#), Ok(("reasoning_effort".to_string(), json!("high"))));
+    let error = parse_extra_param("reasoning_effort=high").unwrap_err();
+    assert!(error.contains("the value of reasoning_effort isn't JSON") && error.contains(r#"reasoning_effort='"high"'"#), "{}", error);
+    assert!(parse_extra_param("top_p").unwrap_err().contains("expected KEY=VALUE"));
+    assert!(parse_extra_param("=1").is_err());
+
+    // Set at the top of the body, replacing what's there
+    let body = OpenAi(settings("gpt-test")).request_body("", "Review this");
+    let body = with_extra_params(body, &[("top_p".to_string(), json!(0.9)), ("max_completion_tokens".to_string(), json!(100))]);
+    assert_eq!((body["top_p"].clone(), body["max_completion_tokens"].clone()), (json!(0.9), json!(100)));
+    assert_eq!(body["model"], "gpt-test");
+}
+
+#[test]
+fn test_parse_response() {
+    let anthropic = json!({"content": [{"type": "text", "text": "Looks "}, {"type": "text", "text": "fine."}], "usage": {"output_tokens": 3}});
+    assert_eq!(Anthropic(settings("m")).parse_response(&anthropic), Some(("Looks fine.".to_string(), Some(3))));
+    let openai = json!({"choices": [{"message": {"role": "assistant", "content": "Looks fine."}}]});
+    assert_eq!(OpenAi(settings("m")).parse_response(&openai), Some(("Looks fine.".to_string(), None)));
+    let ollama = json!({"message": {"role": "assistant", "content": "Looks fine."}, "eval_count": 4});
+    assert_eq!(Ollama(settings("m")).parse_response(&ollama), Some(("Looks fine.".to_string(), Some(4))));
+    assert_eq!(OpenAi(settings("m")).parse_response(&ollama), None);
+}
+
+#[test]
+fn test_provider_from_env() {
+    let provider = provider_from_env(|var| (var == "OPENAI_API_KEY").then(|| "sk-test".to_string())).unwrap();
+    assert_eq!((provider.name.as_str(), provider.format), ("openai", ProviderFormat::OpenAi));
+    assert_eq!(provider.api_key_var(), Some("OPENAI_API_KEY"));
+    assert_eq!(provider.max_tokens, 50_000);
+    let provider = provider_from_env(|_| Some("key".to_string())).unwrap();
+    assert_eq!(provider.format, ProviderFormat::Anthropic);
+    assert_eq!(provider_from_env(|_| None), None);
+
+    let ollama = builtin_provider("ollama").unwrap();
+    assert_eq!((ollama.endpoint.as_str(), ollama.api_key_var()), ("http://localhost:11434/api/chat", None));
+    assert_eq!(builtin_provider("mystery"), None);
+}
+
+/// Read a request on `listener` and answer it with `status` and `response`, returning the
+/// request's headers and body
+fn answer(listener: &TcpListener, status: &str, response: &str) -> String {
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+        request.push_str(&line);
+        if line == "\r\n" {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    request.push_str(&String::from_utf8(body).unwrap());
+    let reply = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    );
+    reader.get_mut().write_all(reply.as_bytes()).unwrap();
+    request
+}
+
+/// Answer one request with `status` and `response`, returning the request's headers and body
+fn serve_once(listener: TcpListener, status: &'static str, response: &'static str) -> thread::JoinHandle<String> {
+    thread::spawn(move || answer(&listener, status, response))
+}
+
+/// Answer a request with each of `responses` in turn, returning the requests
+fn serve(listener: TcpListener, responses: Vec<&'static str>) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || responses.into_iter().map(|response| answer(&listener, "200 OK", response)).collect())
+}
+
+#[test]
+fn test_send() {
+    let dir = temp_dir("client");
+    init_repo(&dir);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/v1/messages", listener.local_addr().unwrap());
+    fs::write(
+        dir.join("providers.toml"),
+        format!(
+            "[[provider]]\nname = \"mock\"\nendpoint = \"{}\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n\
+             api_key_env = \"LLMCR_TEST_KEY\"\nmodel = \"claude-mock\"\n\n\
+             [[provider]]\nname = \"local\"\nendpoint = \"{}\"\nmax_tokens = 8000\nchars_per_token = 4\nformat = \"ollama\"\n",
+            endpoint, endpoint
+        ),
+    )
+    .unwrap();
+
+    let review = |key: Option<&str>, args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_llm_code_review"));
+        command
+            .current_dir(&dir)
+            .env_remove("ANTHROPIC_API_KEY")
+            .env_remove("OPENAI_API_KEY")
+            .args(["--llm-provider-config", "providers.toml", "--send", "--heartbeat-secs", "0"])
+            .args(args);
+        match key {
+            Some(key) => command.env("LLMCR_TEST_KEY", key),
+            None => command.env_remove("LLMCR_TEST_KEY"),
+        };
+        let output = command.output().expect("failed to run llm_code_review");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "200 OK",
+        r#"{"content": [{"type": "text", "text": "Rename `two`."}], "usage": {"output_tokens": 4}}"#,
+    );
+    let (success, output, _) = review(Some("secret"), &[]);
+    let request = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "Rename `two`.\n");
+    assert!(request.starts_with("POST /v1/messages"), "{}", request);
+    assert!(request.contains("x-api-key: secret"), "{}", request);
+    assert!(request.contains(r#""max_tokens":20000"#) && request.contains(r#""model":"claude-mock""#), "{}", request);
+    assert!(request.contains(r#""system":"Please review this PR"#) && request.contains("+two"), "{}", request);
+
+    let server = serve_once(listener.try_clone().unwrap(), "200 OK", r#"{"content": [{"type": "text", "text": "Fine."}]}"#);
+    let (success, output, _) = review(Some("secret"), &["--extra-param", "top_k=5", "--extra-param", r#"metadata={"user_id":"ci"}"#]);
+    let request = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert!(request.contains(r#""top_k":5"#) && request.contains(r#""metadata":{"user_id":"ci"}"#), "{}", request);
+    let (success, _, log) = review(Some("secret"), &["--extra-param", "top_k=five"]);
+    assert!(!success && log.contains("the value of top_k isn't JSON"), "{}", log);
+
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "429 Too Many Requests\r\nRetry-After: 30",
+        r#"{"type": "error", "error": {"type": "rate_limit_error", "message": "Slow down"}}"#,
+    );
+    let (success, _, log) = review(Some("secret"), &["--model", "claude-other", "--verbose"]);
+    assert!(server.join().unwrap().contains(r#""model":"claude-other""#));
+    assert!(!success);
+    assert!(
+        log.contains("LLM request failed: ")
+            && log.contains("answered HTTP 429: Slow down; the provider is limiting requests, try again in 30 seconds"),
+        "{}",
+        log
+    );
+    // --verbose logs the size of the request and how long the answer took
+    assert!(log.contains("(claude-other): ") && log.contains(" answered HTTP 429 in "), "{}", log);
+
+    let server = serve_once(listener.try_clone().unwrap(), "200 OK", r#"{"message": {"role": "assistant", "content": "Fine."}}"#);
+    let (success, output, _) = review(None, &["--provider", "local", "--model", "llama-mock"]);
+    let request = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "Fine.\n");
+    assert!(!request.contains("x-api-key") && request.contains(r#""num_predict":8000"#), "{}", request);
+
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "200 OK",
+        r#"{"content": [{"type": "text", "text": "```\n::warning file=a.txt,line=1::Rename `two`, which says nothing about what it holds\n```"}]}"#,
+    );
+    let (success, output, _) = review(Some("secret"), &["--output-format", "github-actions", "--wrap", "40"]);
+    server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "::warning file=a.txt,line=1::Rename `two`, which says nothing about what it holds\n");
+
+    // --output-format json prints the findings in a fixed layout, and fails on anything else
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "200 OK",
+        r#"{"content": [{"type": "text", "text": "```json\n[{\"path\": \"a.txt\", \"line\": 1, \"severity\": \"warning\", \"comment\": \"Rename `two`.\"}]\n```"}]}"#,
+    );
+    let (success, output, _) = review(Some("secret"), &["--output-format", "json"]);
+    let request = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert!(request.contains("Output the review as a JSON array"), "{}", request);
+    let findings: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(findings, json!([{"file": "a.txt", "line": 1, "severity": "warning", "comment": "Rename `two`."}]));
+
+    // A review that isn't the JSON is asked for once more, saying what was wrong with it
+    let fine = r#"{"content": [{"type": "text", "text": "Looks fine to me."}]}"#;
+    let server = serve(listener.try_clone().unwrap(), vec![fine, r#"{"content": [{"type": "text", "text": "[]"}]}"#]);
+    let (success, output, log) = review(Some("secret"), &["--output-format", "json"]);
+    let requests = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "[]\n");
+    assert!(log.contains("WARN  [llm_code_review::review] The review isn't the JSON asked for ("), "{}", log);
+    assert!(!requests[0].contains("previous answer"), "{}", requests[0]);
+    assert!(requests[1].contains("Your previous answer wasn't the JSON asked for: "), "{}", requests[1]);
+    let server = serve(listener.try_clone().unwrap(), vec![fine, fine]);
+    let (success, _, log) = review(Some("secret"), &["--output-format", "json"]);
+    server.join().unwrap();
+    assert!(!success);
+    assert!(log.contains("isn't the JSON array of findings --output-format json asks for"), "{}", log);
+    assert!(log.contains("The second answer isn't the JSON asked for either"), "{}", log);
+
+    // Findings in prose stand in for the JSON, marked as extracted, unless --strict-json
+    let prose = r#"{"content": [{"type": "text", "text": "I found one problem:\n\n1. **Warning** (a.txt:1): `two` says nothing about what it holds."}]}"#;
+    let server = serve(listener.try_clone().unwrap(), vec![prose, prose]);
+    let (success, output, _) = review(Some("secret"), &["--output-format", "json"]);
+    server.join().unwrap();
+    assert!(success, "{}", output);
+    let findings: serde_json::Value = serde_json::from_str(&output).expect(&output);
+    assert_eq!(
+        findings,
+        json!([{"file": "a.txt", "line": 1, "severity": "warning", "comment": "`two` says nothing about what it holds.", "extracted": true}])
+    );
+    let server = serve(listener.try_clone().unwrap(), vec![prose, prose]);
+    let (success, _, _) = review(Some("secret"), &["--output-format", "json", "--strict-json"]);
+    server.join().unwrap();
+    assert!(!success);
+
+    // --format-check warns of a review in another format than asked for, and --enforce-format
+    // asks again
+    let markdown = r###"{"content": [{"type": "text", "text": "## Summary\n\nRename `two`."}]}"###;
+    let asciidoc = r#"{"content": [{"type": "text", "text": "== Summary\n\nRename `two`."}]}"#;
+    let server = serve(listener.try_clone().unwrap(), vec![markdown, markdown]);
+    let (success, output, log) = review(Some("secret"), &["--output-format", "asciidoc", "--format-check"]);
+    server.join().unwrap();
+    assert!(success, "{}", log);
+    assert_eq!(output, "## Summary\n\nRename `two`.\n");
+    assert!(
+        log.contains("WARN  [llm_code_review::review] The review doesn't look like the asciidoc asked for: it has 1 Markdown heading (`# Title`), but 0 AsciiDoc ones (`= Title`)"),
+        "{}",
+        log
+    );
+    // A review without a single AsciiDoc heading is asked for again whether or not it's checked
+    assert!(log.contains("The review has no AsciiDoc headings (`= Title`); asking claude-mock again for it in asciidoc"), "{}", log);
+    assert!(log.contains("The second answer isn't in asciidoc either"), "{}", log);
+    let server = serve(listener.try_clone().unwrap(), vec![markdown, asciidoc]);
+    let (success, output, _) = review(Some("secret"), &["--output-format", "asciidoc", "--enforce-format"]);
+    let requests = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "== Summary\n\nRename `two`.\n");
+    assert!(requests[1].contains("Your previous answer wasn't in the asciidoc format asked for: it had 1 Markdown heading"), "{}", requests[1]);
+
+    // The chatter around a review, and a code block round all of it, are left out
+    let chatty = r#"{"content": [{"type": "text", "text": "Sure! Here is my review:\n\n```markdown\nRename `two`.\n```\n\nLet me know if you have any questions."}]}"#;
+    let server = serve(listener.try_clone().unwrap(), vec![chatty]);
+    let (success, output, _) = review(Some("secret"), &[]);
+    server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "Rename `two`.\n");
+
+    // --fail-on-issues exits with status 1 when the review flags an issue
+    let flagged = r#"{"content": [{"type": "text", "text": "[ISSUE] a.txt:1: `two` says nothing about what it holds."}]}"#;
+    let server = serve(listener.try_clone().unwrap(), vec![flagged, fine]);
+    let (success, output, _) = review(Some("secret"), &["--fail-on-issues"]);
+    assert!(!success);
+    assert_eq!(output, "[ISSUE] a.txt:1: `two` says nothing about what it holds.\n");
+    let (success, output, _) = review(Some("secret"), &["--fail-on-issues"]);
+    server.join().unwrap();
+    assert!(success, "{}", output);
+    let (success, _, log) = review(None, &["--fail-on-issues"]);
+    assert!(!success && log.contains("--fail-on-issues needs a review to check"), "{}", log);
+
+    // A review with structured findings keeps them in its transcript, for --replay
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "200 OK",
+        r#"{"content": [{"type": "text", "text": "```json\n{\"summary\": \"One nit.\", \"findings\": [{\"path\": \"a.txt\", \"line\": 1, \"severity\": \"info\", \"comment\": \"Rename `two`.\"}]}\n```"}]}"#,
+    );
+    let (success, output, _) = review(Some("secret"), &["--save-transcript", "review.json"]);
+    server.join().unwrap();
+    assert!(success, "{}", output);
+    let transcript: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("review.json")).unwrap()).unwrap();
+    assert_eq!((transcript["version"].clone(), transcript["model"].clone()), (json!(1), json!("claude-mock")));
+    assert_eq!(transcript["summary"], "One nit.");
+    assert_eq!(transcript["findings"][0]["comment"], "Rename `two`.");
+
+    // --attention-report asks for a confidence per file, and routes a file the model is unsure of
+    // to a human
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "200 OK",
+        r#"{"content": [{"type": "text", "text": "Rename `two`.\n\n## Review Confidence\n- a.txt: 40"}]}"#,
+    );
+    let (success, output, _) = review(Some("secret"), &["--attention-report", "--save-transcript", "attention.json"]);
+    let request = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert!(request.contains("## Review Confidence"), "{}", request);
+    assert!(output.contains("## Human Attention\n\nA human should review 1 of 1 files."), "{}", output);
+    assert!(output.contains("- `a.txt`: needs a human (the model's confidence in its review is 40%)"), "{}", output);
+    let transcript: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("attention.json")).unwrap()).unwrap();
+    assert_eq!(transcript["attention"][0], json!({"path": "a.txt", "needs_human": true, "reasons": ["the model's confidence in its review is 40%"], "confidence": 40}));
+
+    let server = serve_once(
+        listener.try_clone().unwrap(),
+        "404 Not Found",
+        r#"{"type": "error", "error": {"type": "not_found_error", "message": "model: claude-missing"}}"#,
+    );
+    let (success, _, log) = review(Some("secret"), &["--model", "claude-missing"]);
+    server.join().unwrap();
+    assert!(!success);
+    assert!(log.contains("check that the model `claude-missing` exists"), "{}", log);
+
+    // Without provenance, whose timestamp can tick over between the two runs
+    let (success, output, _) = review(Some("secret"), &["--dry-run", "--no-provenance"]);
+    assert!(success, "{}", output);
+    assert!(output.starts_with("Please review this PR") && output.contains("+two"), "{}", output);
+    let (no_send, no_send_output, _) = review(Some("secret"), &["--no-send", "--no-provenance"]);
+    assert_eq!((no_send, no_send_output), (success, output));
+
+    // Without a key the prompt is printed, as without --send
+    let (success, output, log) = review(None, &[]);
+    assert!(success, "{}", log);
+    assert!(log.contains("--send: LLMCR_TEST_KEY is not set; printing the prompt instead"), "{}", log);
+    assert!(output.contains("+two"), "{}", output);
+
+    // --watch only prints prompts, so what would send or save a review is refused with it
+    for args in [&["--watch"][..], &["--watch", "--provider", "local"], &["--watch", "--output", "review.md"]] {
+        let (success, _, log) = review(Some("secret"), args);
+        assert!(!success && log.contains("cannot be used with"), "{:?}: {}", args, log);
+    }
+}
+
+#[test]
+fn test_builtin_provider() {
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .env_remove("ANTHROPIC_API_KEY")
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), String::from_utf8_lossy(&output.stderr).to_string())
+    };
+    let (success, output) = review(&["--provider", "anthropic"]);
+    assert!(!success);
+    assert!(output.contains("--provider anthropic needs an API key in ANTHROPIC_API_KEY"), "{}", output);
+    let (success, output) = review(&["--provider", "mystery"]);
+    assert!(!success);
+    assert!(output.contains("--provider takes anthropic, openai, ollama"), "{}", output);
+}
+
+#[test]
+fn test_pre_commit() {
+    let dir = temp_dir("pre_commit");
+    init_repo(&dir);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    fs::write(dir.join("b.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    fs::write(dir.join("b.txt"), "two\n").unwrap();
+    git(&dir, &["add", "a.txt"]);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/v1/messages", listener.local_addr().unwrap());
+    fs::write(
+        dir.join("providers.toml"),
+        format!(
+            "[[provider]]\nname = \"mock\"\nendpoint = \"{}\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n\
+             api_key_env = \"LLMCR_TEST_KEY\"\nmodel = \"claude-mock\"\n",
+            endpoint
+        ),
+    )
+    .unwrap();
+    let review = |key: Option<&str>, args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_llm_code_review"));
+        command.current_dir(&dir).args(["--llm-provider-config", "providers.toml", "--pre-commit"]).args(args);
+        match key {
+            Some(key) => command.env("LLMCR_TEST_KEY", key),
+            None => command.env_remove("LLMCR_TEST_KEY"),
+        };
+        let output = command.output().expect("failed to run llm_code_review");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    // Only what's staged of the files pre-commit passes is reviewed, and a warning doesn't fail it
+    let warning = r#"{"content": [{"type": "text", "text": "[{\"path\": \"a.txt\", \"line\": 1, \"severity\": \"warning\", \"comment\": \"Rename\\n`two`.\"}]"}]}"#;
+    let server = serve(listener.try_clone().unwrap(), vec![warning, warning]);
+    let (success, output, log) = review(Some("secret"), &["a.txt", "b.txt"]);
+    assert!(success, "{}", log);
+    assert_eq!(output, "a.txt:1: warning: Rename `two`.\n");
+    // Findings at --fail-on or above do
+    let (success, output, _) = review(Some("secret"), &["--fail-on", "warning", "a.txt", "b.txt"]);
+    assert!(!success);
+    assert_eq!(output, "a.txt:1: warning: Rename `two`.\n");
+    let requests = server.join().unwrap();
+    assert!(requests[0].contains("+two") && requests[0].contains("a.txt") && !requests[0].contains("b.txt"), "{}", requests[0]);
+    assert!(requests[0].contains("Output the review as a JSON array"), "{}", requests[0]);
+
+    let server = serve(listener.try_clone().unwrap(), vec![r#"{"content": [{"type": "text", "text": "[]"}]}"#]);
+    let (success, output, _) = review(Some("secret"), &["a.txt"]);
+    server.join().unwrap();
+    assert!(success);
+    assert_eq!(output, "No findings in the staged changes.\n");
+
+    let (success, _, log) = review(None, &["a.txt"]);
+    assert!(!success && log.contains("--pre-commit needs a provider"), "{}", log);
+    let (success, _, log) = review(Some("secret"), &["--output-format", "markdown", "a.txt"]);
+    assert!(!success && log.contains("cannot be used with"), "{}", log);
+}
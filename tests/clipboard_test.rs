@@ -0,0 +1,111 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::clipboard::copy_with;
+use llm_code_review::error::ReviewError;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const DIFF: &str = "diff --git a/cart.rs b/cart.rs\n--- a/cart.rs\n+++ b/cart.rs\n@@ -1,3 +1,3 @@\n fn total() {\n-    1\n+    2\n }\n";
+
+#[test]
+fn test_copy_with() {
+    let dir = temp_dir("copy_with");
+    let copied = dir.join("copied.txt");
+    copy_with(&format!("tee {}", copied.display()), "Review this.\n").unwrap();
+    assert_eq!(fs::read_to_string(&copied).unwrap(), "Review this.\n");
+
+    match copy_with("false", "Review this.") {
+        Err(ReviewError::Clipboard(message)) => assert!(message.starts_with("false exited with"), "{}", message),
+        other => panic!("expected a Clipboard error, got {:?}", other),
+    }
+    match copy_with("llm_code_review_no_such_clipboard", "Review this.") {
+        Err(ReviewError::Clipboard(message)) => assert!(message.starts_with("could not run llm_code_review_no_such_clipboard"), "{}", message),
+        other => panic!("expected a Clipboard error, got {:?}", other),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Run the tool in `dir` on cart.diff with only `path` to find commands on, and `display` as
+/// DISPLAY, returning whether it succeeded and its output
+fn review(dir: &Path, path: &Path, display: Option<&str>, args: &[&str]) -> (bool, String) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_llm_code_review"));
+    command
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("PATH", path)
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .args(["--diff-file", "cart.diff"])
+        .args(args);
+    if let Some(display) = display {
+        command.env("DISPLAY", display);
+    }
+    let output = command.output().expect("failed to run llm_code_review");
+    (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+}
+
+#[test]
+fn test_output_file() {
+    let dir = temp_dir("output_file");
+    fs::write(dir.join("cart.diff"), DIFF).unwrap();
+    let path = std::env::var_os("PATH").unwrap();
+    let path = Path::new(&path);
+
+    let (success, output) = review(&dir, path, None, &["--output", "prompt.md"]);
+    assert!(success, "{}", output);
+    assert!(!output.contains("+    2"), "{}", output);
+    assert!(output.contains("Wrote the prompt to prompt.md: "), "{}", output);
+    assert!(output.contains(" tokens, 1 file\n"), "{}", output);
+    assert!(fs::read_to_string(dir.join("prompt.md")).unwrap().contains("+    2"));
+
+    // An existing file is only replaced with --force
+    fs::write(dir.join("prompt.md"), "notes").unwrap();
+    let (success, output) = review(&dir, path, None, &["--output-file", "prompt.md"]);
+    assert!(!success);
+    assert!(output.contains("prompt.md already exists; give --force to replace it"), "{}", output);
+    assert_eq!(fs::read_to_string(dir.join("prompt.md")).unwrap(), "notes");
+    let (success, output) = review(&dir, path, None, &["--output-file", "prompt.md", "--force"]);
+    assert!(success, "{}", output);
+    assert!(fs::read_to_string(dir.join("prompt.md")).unwrap().contains("+    2"));
+
+    // - is standard output
+    let (success, output) = review(&dir, path, None, &["--output-file", "-"]);
+    assert!(success, "{}", output);
+    assert!(output.contains("+    2") && !output.contains("Wrote the prompt"), "{}", output);
+    assert!(!dir.join("-").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_copy() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("copy");
+    fs::write(dir.join("cart.diff"), DIFF).unwrap();
+    // A PATH with nothing on it but an xclip that keeps what it's given
+    let bin = dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let (success, output) = review(&dir, &bin, Some(":0"), &["--copy"]);
+    assert!(!success);
+    assert!(output.contains("Could not copy to the clipboard: none of pbcopy, wl-copy"), "{}", output);
+    assert!(output.contains("use --output-file instead"), "{}", output);
+
+    fs::write(bin.join("xclip"), format!("#!/bin/sh\n[ \"$*\" = \"-selection clipboard\" ] || exit 2\n/bin/cat > {}\n", dir.join("copied.md").display())).unwrap();
+    fs::set_permissions(bin.join("xclip"), fs::Permissions::from_mode(0o755)).unwrap();
+    let (success, output) = review(&dir, &bin, Some(":0"), &["--copy"]);
+    assert!(success, "{}", output);
+    assert!(!output.contains("+    2"), "{}", output);
+    assert!(output.contains("Copied the prompt to the clipboard: "), "{}", output);
+    assert!(fs::read_to_string(dir.join("copied.md")).unwrap().contains("+    2"));
+
+    // Without a display, xclip has nowhere to put it
+    fs::remove_file(dir.join("copied.md")).unwrap();
+    let (success, output) = review(&dir, &bin, None, &["--copy"]);
+    assert!(!success);
+    assert!(output.contains("xclip needs a display, and neither DISPLAY nor WAYLAND_DISPLAY is set"), "{}", output);
+    assert!(!dir.join("copied.md").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::comment_series::{join_series, plan_comments, series_part, split_review, CommentAction};
+use llm_code_review::locale::Locale;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+
+fn long_review() -> String {
+    let mut review = "## Summary\nThe change adds discounts. Two problems need fixing first.\n\n".to_string();
+    for n in 1..=6 {
+        review.push_str(&format!("## Finding {}\n\n{}\n\n```rust\n", n, "The total is computed twice. ".repeat(8)));
+        review.push_str(&"let total = cart.items().map(|item| item.price).sum::<u64>();\n".repeat(6));
+        review.push_str("```\n\n");
+    }
+    review.push_str("## Verdict\nRequest changes.\n");
+    review
+}
+
+fn fences_balanced(text: &str) -> bool {
+    text.lines().filter(|line| line.trim_start().starts_with("```")).count() % 2 == 0
+}
+
+#[test]
+fn test_split_review() {
+    let review = long_review();
+    assert_eq!(split_review(&review, 100_000, Locale::En), [format!("<!-- llm-code-review series 1/1 -->\n{}", review)]);
+
+    let comments = split_review(&review, 1_000, Locale::En);
+    assert!(comments.len() > 2, "{:#?}", comments);
+    assert_eq!(join_series(&comments), review);
+    for (i, comment) in comments.iter().enumerate() {
+        assert!(comment.len() <= 1_000, "{}", comment);
+        assert!(fences_balanced(comment), "{}", comment);
+        assert_eq!(series_part(comment), Some((i + 1, comments.len())));
+        assert!(comment.contains(&format!("**Review {}/{}**\n\n", i + 1, comments.len())), "{}", comment);
+    }
+    assert!(comments[0].contains("## Summary\nThe change adds discounts."), "{}", comments[0]);
+    assert!(comments[1..].iter().all(|comment| comment.contains("\n## Finding")), "{:#?}", comments);
+
+    // A code block longer than a comment is closed at each cut and reopened with its info
+    // string, and still comes back together
+    let block = format!("Rename `x`:\n\n```python\n{}```\n\nThen rerun the tests.\n", "x = 1\n".repeat(400));
+    let comments = split_review(&block, 500, Locale::En);
+    assert!(comments.len() > 2, "{:#?}", comments);
+    for comment in &comments {
+        assert!(comment.len() <= 500, "{}", comment);
+        assert!(fences_balanced(comment), "{}", comment);
+    }
+    assert!(comments[2..].iter().all(|comment| comment.contains("**\n\n<!-- llm-code-review code block continued -->\n```python\nx = 1\n")), "{:#?}", comments);
+    assert!(comments.last().unwrap().ends_with("```\n\nThen rerun the tests.\n"), "{:#?}", comments);
+    assert_eq!(join_series(&comments), block);
+    // A line too long for a comment is cut mid-line
+    let block = format!("~~~~\n{}\n~~~~\n", "y".repeat(1_200));
+    let comments = split_review(&block, 500, Locale::En);
+    assert!(comments.iter().all(|comment| comment.len() <= 500 && comment.matches("~~~~\n").count() == 2), "{:#?}", comments);
+    assert_eq!(join_series(&comments), block);
+    let text = "é".repeat(300);
+    assert_eq!(join_series(&split_review(&text, 200, Locale::En)), text);
+}
+
+#[test]
+fn test_plan_comments() {
+    let old = split_review(&long_review(), 1_000, Locale::En);
+    let existing: Vec<(u64, String)> = vec![(1, "Thanks for the fix!".to_string())]
+        .into_iter()
+        .chain(old.iter().enumerate().map(|(i, body)| (10 + i as u64, body.clone())))
+        .collect();
+
+    assert_eq!(plan_comments(&existing, &old), []);
+    let new = split_review("## Summary\nLooks good now.\n", 1_000, Locale::En);
+    let mut expected = vec![CommentAction::Update { id: 10, body: new[0].clone() }];
+    expected.extend((1..old.len()).map(|i| CommentAction::Delete { id: 10 + i as u64 }));
+    assert_eq!(plan_comments(&existing, &new), expected);
+    assert_eq!(plan_comments(&existing[..1], &new), [CommentAction::Create { body: new[0].clone() }]);
+}
+
+/// Answer a request with each of `responses` in turn, returning the requests' first lines and bodies
+fn serve(listener: TcpListener, responses: Vec<String>) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            requests.push(format!("{}{}", request_line, String::from_utf8(body).unwrap()));
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            );
+            reader.get_mut().write_all(reply.as_bytes()).unwrap();
+        }
+        requests
+    })
+}
+
+#[test]
+fn test_post() {
+    let dir = temp_dir("post");
+    init_repo(&dir);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    fs::write(
+        dir.join("providers.toml"),
+        format!(
+            "[[provider]]\nname = \"mock\"\nendpoint = \"{}/v1/messages\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n",
+            address
+        ),
+    )
+    .unwrap();
+    let event = serde_json::json!({
+        "pull_request": {"number": 7, "title": "Rename", "base": {"ref": "main"}, "head": {"ref": "rename"}},
+        "repository": {"full_name": "octo/cart"}
+    });
+    fs::write(dir.join("event.json"), event.to_string()).unwrap();
+    // A gh that isn't logged in, for when GITHUB_TOKEN is empty
+    let bin = dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let script = "#!/bin/sh\necho 'You are not logged into any GitHub hosts. Run gh auth login to authenticate.' >&2\nexit 4\n";
+        fs::write(bin.join("gh"), script).unwrap();
+        fs::set_permissions(bin.join("gh"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = std::env::join_paths(std::iter::once(bin).chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())))
+        .unwrap();
+
+    let review = |token: &str, args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("GITHUB_EVENT_NAME", "pull_request")
+            .env("GITHUB_EVENT_PATH", dir.join("event.json"))
+            .env("GITHUB_API_URL", &address)
+            .env("GITHUB_TOKEN", token)
+            .env("PATH", &path)
+            .args(["--llm-provider-config", "providers.toml", "--send", "--heartbeat-secs", "0"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+
+    let answer = serde_json::json!({"content": [{"type": "text", "text": "Rename `two`."}]}).to_string();
+    let earlier = serde_json::json!([
+        {"id": 1, "body": "Thanks!"},
+        {"id": 2, "body": "<!-- llm-code-review series 1/2 -->\n**Review 1/2**\n\nOld."},
+        {"id": 3, "body": "<!-- llm-code-review series 2/2 -->\n**Review 2/2**\n\nOlder."}
+    ]);
+    let server = serve(listener, vec![answer, earlier.to_string(), "{}".to_string(), String::new()]);
+    let (success, output) = review("secret", &["--github-actions-pr-context", "--post"]);
+    let requests = server.join().unwrap();
+    assert!(success, "{}", output);
+    assert_eq!(output, "Rename `two`.\n");
+    assert!(requests[1].starts_with("GET /repos/octo/cart/issues/7/comments?per_page=100&page=1 "), "{:#?}", requests);
+    assert!(requests[2].starts_with("PATCH /repos/octo/cart/issues/comments/2 "), "{:#?}", requests);
+    assert!(requests[2].ends_with(r#"{"body":"<!-- llm-code-review series 1/1 -->\nRename `two`."}"#), "{:#?}", requests);
+    assert!(requests[3].starts_with("DELETE /repos/octo/cart/issues/comments/3 "), "{:#?}", requests);
+
+    let (success, output) = review("", &["--github-actions-pr-context", "--post"]);
+    assert!(!success);
+    assert!(output.contains("--post needs GITHUB_TOKEN"), "{}", output);
+    #[cfg(unix)]
+    assert!(output.contains("gh isn't logged in to github.com; run `gh auth login`"), "{}", output);
+    let (success, output) = review("secret", &["--post"]);
+    assert!(!success);
+    assert!(output.contains("--post needs --github-actions-pr-context"), "{}", output);
+}
@@ -0,0 +1,96 @@
+use llm_code_review::compress::{compress_diff, elided_line_count, elision_marker};
+use llm_code_review::diff::{parse_diff, render_diff, HunkRange};
+
+#[test]
+fn test_hunk_range_parse() {
+    assert_eq!(
+        HunkRange::parse("@@ -10,6 +10,12 @@ fn main() {"),
+        Some(HunkRange {
+            old_start: 10,
+            old_len: 6,
+            new_start: 10,
+            new_len: 12,
+        })
+    );
+    assert_eq!(
+        HunkRange::parse("@@ -5 +5,0 @@"),
+        Some(HunkRange {
+            old_start: 5,
+            old_len: 1,
+            new_start: 5,
+            new_len: 0,
+        })
+    );
+    assert_eq!(HunkRange::parse("not a hunk"), None);
+}
+
+#[test]
+fn test_elide_unchanged_runs() {
+    let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,10 +1,10 @@ mod a;
+-old first
++new first
+ one
+ two
+ three
+ four
+ five
+ six
+ seven
+-old last
++new last
+";
+    let compressed = render_diff(&compress_diff(parse_diff(diff), 5));
+    assert_eq!(
+        compressed,
+        "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,10 +1,10 @@ mod a;
+-old first
++new first
+ one
+ … 5 unchanged lines …
+ seven
+-old last
++new last
+"
+    );
+
+    // Short runs are left alone
+    assert_eq!(render_diff(&compress_diff(parse_diff(diff), 7)), diff);
+}
+
+#[test]
+fn test_merge_adjacent_hunks() {
+    let diff = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+@@ -3,3 +3,3 @@ section
+ three
+-four
++FOUR
+";
+    let files = compress_diff(parse_diff(diff), 5);
+    assert_eq!(files[0].hunks.len(), 1);
+    assert_eq!(files[0].hunks[0].header, "@@ -1,5 +1,5 @@");
+    assert_eq!(
+        files[0].hunks[0].lines,
+        [" one", "-two", "+TWO", "-four", "+FOUR"]
+    );
+}
+
+#[test]
+fn test_elided_line_count() {
+    assert_eq!(elided_line_count(&elision_marker(14)), Some(14));
+    assert_eq!(elided_line_count(" let x = 1;"), None);
+}
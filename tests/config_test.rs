@@ -0,0 +1,131 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::config::{config_paths, Config, REPO_CONFIG_FILE};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_parse_config() {
+    let config = Config::parse("context = \"A Rust project\"\nunified_context = 5\nmax_tokens = 9000\n").unwrap();
+    assert_eq!(config.context.as_deref(), Some("A Rust project"));
+    assert_eq!((config.unified_context, config.max_tokens), (Some(5), Some(9000)));
+
+    assert!(Config::parse("contxt = \"typo\"\n").unwrap_err().contains("contxt"));
+    assert!(Config::parse("output_format = \"pdf\"\n").unwrap_err().contains("`pdf` is not one of"));
+    assert!(Config::parse("max_tokens = 0\n").is_err());
+    assert!(Config::parse("system_prompt = \"a\"\nsystem_prompt_file = \"b.md\"\n").unwrap_err().contains("not both"));
+    assert!(Config::parse("provider = \"mistral\"\n").unwrap_err().contains("`mistral` is not one of"));
+    assert_eq!(Config::parse("monthly_budget_usd = 25.0\n").unwrap().monthly_budget_usd, Some(25.0));
+    assert!(Config::parse("monthly_budget_usd = 0.0\n").unwrap_err().contains("more than 0"));
+    let config = Config::parse("max_findings_per_file = 3\nmax_findings = 15\n").unwrap();
+    assert_eq!((config.max_findings_per_file, config.max_findings), (Some(3), Some(15)));
+    assert!(Config::parse("max_findings = 0\n").unwrap_err().contains("at least 1"));
+    assert_eq!(Config::parse("review_language = \"ja\"\n").unwrap().review_language.as_deref(), Some("ja"));
+    assert!(Config::parse("review_language = \"fr\"\n").unwrap_err().contains("`fr` is not one of"));
+    let config = Config::parse("provider = \"ollama\"\nmodel = \"qwen3\"\nhistory_dir = \"reviews\"\n").unwrap();
+    assert_eq!((config.provider.as_deref(), config.model.as_deref()), (Some("ollama"), Some("qwen3")));
+    assert_eq!(config.history_dir, Some(std::path::PathBuf::from("reviews")));
+
+    // The first config's settings take the place of the second's
+    let merged = Config::parse("context = \"repo\"\n").unwrap().or(Config::parse("context = \"user\"\nunified_context = 1\n").unwrap());
+    assert_eq!((merged.context.as_deref(), merged.unified_context), (Some("repo"), Some(1)));
+}
+
+#[test]
+fn test_config_files() {
+    let dir = temp_dir("config");
+    let repo = dir.join("repo");
+    let config_home = dir.join("config");
+    fs::create_dir_all(repo.join("src")).unwrap();
+    fs::create_dir_all(config_home.join("llm_code_review")).unwrap();
+    init_repo(&repo);
+    fs::write(repo.join("src/lib.rs"), "one\ntwo\nthree\nfour\nfive\nsix\nseven\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "first"]);
+    fs::write(repo.join("src/lib.rs"), "one\ntwo\nthree\n4\nfive\nsix\nseven\n").unwrap();
+
+    fs::write(repo.join(REPO_CONFIG_FILE), "context = \"A Rust project\"\nunified_context = 0\n").unwrap();
+    fs::write(
+        config_home.join("llm_code_review/config.toml"),
+        "context = \"The user's context\"\noutput_format = \"asciidoc\"\n",
+    )
+    .unwrap();
+    assert_eq!(config_paths(&repo.join("src")), vec![repo.join(REPO_CONFIG_FILE)]);
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(repo.join("src"))
+            .env("XDG_CONFIG_HOME", &config_home)
+            .arg("--no-provenance")
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+
+    // The repository's config comes first, then the user's, then the defaults
+    let (ok, stdout) = review(&[]);
+    assert!(ok, "{}", stdout);
+    assert!(stdout.contains("A Rust project") && !stdout.contains("The user's context"), "{}", stdout);
+    assert!(stdout.contains("AsciiDoc"), "{}", stdout);
+    assert!(stdout.contains("@@ -4 +4 @@") && stdout.contains("\n-four\n+4\n"), "{}", stdout);
+
+    // The command line comes before both
+    let (ok, stdout) = review(&["-c", "From the command line", "-U", "1"]);
+    assert!(ok, "{}", stdout);
+    assert!(stdout.contains("From the command line") && !stdout.contains("A Rust project"), "{}", stdout);
+    assert!(stdout.contains("@@ -3,3 +3,3 @@") && stdout.contains("\n three\n-four\n+4\n five\n"), "{}", stdout);
+
+    let (ok, stdout) = review(&["--show-config", "--max-tokens", "9000"]);
+    assert!(ok, "{}", stdout);
+    assert_eq!(
+        stdout,
+        format!(
+            "# Read from {}, {}\ncontext = \"A Rust project\"\noutput_format = \"asciidoc\"\nunified_context = 0\nmax_tokens = 9000\n",
+            repo.join(REPO_CONFIG_FILE).display(),
+            config_home.join("llm_code_review/config.toml").display()
+        )
+    );
+
+    // --no-config leaves only the command line and the defaults, and --config reads one file alone
+    let (ok, stdout) = review(&["--no-config"]);
+    assert!(ok, "{}", stdout);
+    assert!(!stdout.contains("A Rust project") && !stdout.contains("AsciiDoc"), "{}", stdout);
+    assert!(stdout.contains("@@ -1,7 +1,7 @@"), "{}", stdout);
+    fs::write(dir.join("prompt.md"), "Review this as the billing team would.\n").unwrap();
+    fs::write(dir.join("team.toml"), "system_prompt_file = \"prompt.md\"\n").unwrap();
+    let (ok, stdout) = review(&["--config", dir.join("team.toml").to_str().unwrap()]);
+    assert!(ok, "{}", stdout);
+    assert!(stdout.starts_with("Review this as the billing team would.\n"), "{}", stdout);
+    assert!(!stdout.contains("A Rust project") && !stdout.contains("AsciiDoc"), "{}", stdout);
+    let (ok, stdout) = review(&["--config", "team.toml", "--no-config"]);
+    assert!(!ok && stdout.starts_with("error: the argument '--config <PATH>' cannot be used with '--no-config'"), "{}", stdout);
+
+    // A system prompt file in the user's config gives way to the repository's system prompt
+    fs::write(config_home.join("llm_code_review/prompt.md"), "The user's prompt.\n").unwrap();
+    fs::write(config_home.join("llm_code_review/config.toml"), "system_prompt_file = \"prompt.md\"\n").unwrap();
+    let (ok, stdout) = review(&[]);
+    assert!(ok && stdout.starts_with("The user's prompt.\n"), "{}", stdout);
+    fs::write(repo.join(REPO_CONFIG_FILE), "system_prompt = \"The repository's prompt.\"\n").unwrap();
+    let (ok, stdout) = review(&[]);
+    assert!(ok && stdout.starts_with("The repository's prompt."), "{}", stdout);
+    let (ok, stdout) = review(&["--system-prompt", "The command line's prompt."]);
+    assert!(ok && stdout.starts_with("The command line's prompt."), "{}", stdout);
+    fs::remove_file(config_home.join("llm_code_review/prompt.md")).unwrap();
+    let (ok, stdout) = review(&[]);
+    assert!(!ok && stdout.contains("prompt.md"), "{}", stdout);
+
+    // A mistake in a config file is an error, naming the file and line
+    fs::write(repo.join(REPO_CONFIG_FILE), "context = \"A Rust project\"\nunified_context = \n").unwrap();
+    let (ok, stdout) = review(&[]);
+    assert!(!ok);
+    assert!(stdout.contains(&format!("{}: TOML parse error at line 2", repo.join(REPO_CONFIG_FILE).display())), "{}", stdout);
+    fs::write(repo.join(REPO_CONFIG_FILE), "unified_contxt = 0\n").unwrap();
+    let (ok, stdout) = review(&[]);
+    assert!(!ok);
+    assert!(stdout.contains(&format!("{}: TOML parse error", repo.join(REPO_CONFIG_FILE).display())), "{}", stdout);
+    assert!(stdout.contains("unknown field `unified_contxt`"), "{}", stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
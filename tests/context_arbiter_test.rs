@@ -0,0 +1,118 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::budget::CharsPerToken;
+use llm_code_review::context_arbiter::{context_priority, ContextArbiter, ContextFeature, Decision, CONTEXT_FEATURES};
+use llm_code_review::deadline::Deadline;
+use std::cell::Cell;
+use std::fs;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+fn feature(name: &str) -> ContextFeature {
+    *CONTEXT_FEATURES.iter().find(|feature| feature.name == name).unwrap()
+}
+
+#[test]
+fn test_context_priority() {
+    let names = |priority: &[&str]| {
+        let priority: Vec<String> = priority.iter().map(|name| name.to_string()).collect();
+        context_priority(&priority).map(|features| features.iter().map(|feature| feature.name).collect::<Vec<_>>())
+    };
+    assert_eq!(names(&[]).unwrap()[..3], ["ci-logs", "previous-review", "linked-issues"]);
+    let custom = names(&["history", "packages"]).unwrap();
+    assert_eq!(custom[..3], ["history", "packages", "ci-logs"]);
+    assert_eq!(custom.len(), CONTEXT_FEATURES.len());
+    assert!(names(&["blame"]).unwrap_err().starts_with("unknown context feature `blame`; expected one of change-summary"));
+}
+
+#[test]
+fn test_arbiter_spends_budget_in_order() {
+    let deadline = Deadline::default();
+    let estimator = CharsPerToken(1);
+    let mut arbiter = ContextArbiter::new(Some(100), &deadline, &estimator);
+
+    // Each section's heading and the blank line after it count too
+    assert_eq!(arbiter.offer(feature("ci-logs"), Duration::ZERO, || Some("x".repeat(50))), Some("x".repeat(50)));
+    assert_eq!(arbiter.offer(feature("related-tests"), Duration::ZERO, || Some("y".repeat(50))), None);
+    assert_eq!(arbiter.offer(feature("contracts"), Duration::ZERO, || None), None);
+    assert!(arbiter.offer(feature("packages"), Duration::ZERO, || Some("z".to_string())).is_some());
+
+    let decisions = arbiter.into_decisions();
+    let summary: Vec<(&str, Decision, Option<usize>)> =
+        decisions.iter().map(|decision| (decision.feature.as_str(), decision.decision, decision.tokens)).collect();
+    assert_eq!(
+        summary,
+        [
+            ("ci-logs", Decision::Included, Some(67)),
+            ("related-tests", Decision::OverBudget, Some(69)),
+            ("contracts", Decision::Empty, None),
+            ("packages", Decision::Included, Some(15)),
+        ]
+    );
+    assert_eq!(decisions[1].to_string(), "skipped related-tests: its 69 tokens don't fit in what's left");
+}
+
+#[test]
+fn test_arbiter_with_no_budget_left() {
+    // The diff alone took everything: nothing is built, so nothing costs git or network calls
+    let deadline = Deadline::default();
+    let estimator = CharsPerToken(4);
+    let mut arbiter = ContextArbiter::new(Some(0), &deadline, &estimator);
+    let built = Cell::new(0);
+    for feature in CONTEXT_FEATURES {
+        let section = arbiter.offer(*feature, Duration::ZERO, || {
+            built.set(built.get() + 1);
+            Some("context".to_string())
+        });
+        assert_eq!(section, None);
+    }
+    assert_eq!(built.get(), 0);
+    let decisions = arbiter.into_decisions();
+    assert!(decisions.iter().all(|decision| decision.decision == Decision::OverBudget && decision.tokens.is_none()));
+    assert_eq!(decisions[0].to_string(), "skipped change-summary: no tokens left");
+}
+
+#[test]
+fn test_arbiter_deadline() {
+    let deadline = Deadline::new(Instant::now(), Duration::from_secs(1));
+    let estimator = CharsPerToken(4);
+    // Without a token limit only the deadline decides
+    let mut arbiter = ContextArbiter::new(None, &deadline, &estimator);
+    assert!(arbiter.offer(feature("linked-issues"), Duration::from_secs(30), || Some("issues".to_string())).is_none());
+    assert!(arbiter.offer(feature("history"), Duration::ZERO, || Some("x".repeat(10_000))).is_some());
+    let decisions = arbiter.into_decisions();
+    assert_eq!(decisions[0].decision, Decision::OutOfTime);
+    assert_eq!(decisions[1].decision, Decision::Included);
+    assert_eq!(deadline.decisions(), ["Skipped the linked-issues section: 1.0s left, needs up to 30.0s"]);
+}
+
+#[test]
+fn test_context_features_in_budget_report() {
+    let dir = temp_dir("context_arbiter");
+    // Removing fields without reserving their numbers makes a compatibility section several times
+    // the size of the diff
+    let fields: String = (1..=100).map(|n| format!("-  string f{0} = {0};\n", n)).collect();
+    let diff = format!("diff --git a/cart.proto b/cart.proto\n--- a/cart.proto\n+++ b/cart.proto\n@@ -1,100 +1,0 @@\n{}", fields);
+    fs::write(dir.join("cart.diff"), &diff).unwrap();
+
+    let report = |max_tokens: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(["--diff-file", "cart.diff", "--tokenizer", "chars", "--max-tokens", max_tokens])
+            .args(["--output-file", "prompt.md", "--force", "--token-budget-report", "--context-priority", "contracts"])
+            .output()
+            .expect("failed to run llm_code_review");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+        let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("prompt.md.budget.json")).unwrap()).unwrap();
+        (report["context_features"][0].clone(), fs::read_to_string(dir.join("prompt.md")).unwrap())
+    };
+
+    let (contracts, prompt) = report("100000");
+    assert_eq!(contracts["feature"], "contracts");
+    assert_eq!(contracts["decision"], "included");
+    assert!(prompt.contains("## Public Contracts"), "{}", prompt);
+    let (contracts, prompt) = report("1500");
+    assert_eq!(contracts["decision"], "over-budget");
+    assert!(!prompt.contains("## Public Contracts"), "{}", prompt);
+}
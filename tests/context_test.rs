@@ -0,0 +1,33 @@
+use llm_code_review::context::{dedup_context, dedup_sections};
+
+fn entries(entries: &[&str]) -> Vec<String> {
+    entries.iter().map(|entry| entry.to_string()).collect()
+}
+
+#[test]
+fn test_dedup_context_exact() {
+    assert_eq!(
+        dedup_context(entries(&["Fixes #12", "Uses the new API", "  Fixes #12\n", "Fixes  #12"]), false),
+        entries(&["Fixes #12", "Uses the new API", "Fixes  #12"])
+    );
+}
+
+#[test]
+fn test_dedup_context_fuzzy() {
+    assert_eq!(
+        dedup_context(entries(&["Fixes #12", "Fixes  #12", "Fixes\n#12\n", "fixes #12"]), true),
+        entries(&["Fixes #12", "fixes #12"])
+    );
+}
+
+#[test]
+fn test_dedup_sections() {
+    let sections = vec![
+        ("Additional Context", "Retry on 503".to_string()),
+        ("Pull Request Description", "Retry on 503\n".to_string()),
+        ("Pull Request", "Title: Retry".to_string()),
+    ];
+    let deduped = dedup_sections(sections, false);
+    let headings: Vec<&str> = deduped.iter().map(|(heading, _)| *heading).collect();
+    assert_eq!(headings, ["Additional Context", "Pull Request"]);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::contracts::{compatibility_section, find_contracts, proto_field_changes, ContractKind};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::severity_policy::glob_regex;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn file_diff(path: &str, lines: &str) -> String {
+    format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1,3 +1,3 @@\n{1}", path, lines)
+}
+
+#[test]
+fn test_find_contracts() {
+    let diff = [
+        file_diff("api/openapi.yaml", " paths:\n-  /carts:\n+  /baskets:\n"),
+        file_diff("proto/cart.proto", "-  int32 total = 1;\n+  int64 total = 1;\n"),
+        file_diff("schema/cart.graphqls", "-  total: Int\n+  total: Float\n"),
+        file_diff("src/cart.rs", "-pub fn total() -> u32 {\n+pub fn total() -> u64 {\n"),
+        file_diff("src/main.rs", "-pub fn run() {\n+pub fn run(args: Args) {\n"),
+        file_diff("src/price.rs", "-    let x = 1;\n+    let x = 2;\n"),
+        file_diff("docs/cart.md", "-Old\n+New\n"),
+        file_diff("avro/cart.avsc", "-{}\n+{\"type\": \"record\"}\n"),
+    ]
+    .concat();
+    let files = parse_diff(&diff);
+    // This crate has a src/lib.rs, so its src/ files are a library's
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    assert_eq!(
+        find_contracts(&files, root, &[glob_regex("*.avsc")]),
+        [
+            ("api/openapi.yaml", ContractKind::OpenApi),
+            ("proto/cart.proto", ContractKind::Protobuf),
+            ("schema/cart.graphqls", ContractKind::GraphQl),
+            ("src/cart.rs", ContractKind::RustPublicApi),
+            ("avro/cart.avsc", ContractKind::Configured),
+        ]
+    );
+    assert_eq!(find_contracts(&files, &root.join("tests"), &[]).len(), 3);
+
+    let section = compatibility_section(&[("proto/cart.proto", ContractKind::Protobuf)], &["a problem".to_string()]);
+    assert!(section.contains("- proto/cart.proto (protobuf schema)"), "{}", section);
+    assert!(section.contains("Label these findings with the category `compatibility`"), "{}", section);
+    assert!(section.ends_with("wire-incompatible changes:\n- a problem"), "{}", section);
+}
+
+#[test]
+fn test_proto_field_changes() {
+    let diff = file_diff(
+        "cart.proto",
+        " message Cart {\n\
+         -  string id = 1;\n\
+         -  int32 total = 2;\n\
+         +  int64 total = 2;\n\
+         -  repeated Item items = 3;\n\
+         +  repeated Item items = 4;\n\
+         -  string coupon = 5;\n\
+         +  string discount_code = 5;\n\
+         -  string note = 6;\n\
+         +  reserved 6;\n\
+         -  map<string, string> labels = 7;\n\
+         -  bool gift = 8 [deprecated = true];\n\
+         +  reserved 7 to 9;\n\
+         +  reserved \"labels\";\n\
+         +  optional string currency = 10;\n\
+         -  string channel = 11;\n\
+          }\n",
+    );
+    let files = parse_diff(&diff);
+    assert_eq!(
+        proto_field_changes(&files[0]),
+        [
+            "cart.proto: field `channel` (11) removed without reserving its number or name",
+            "cart.proto: field number 5 reused: `coupon` is now `discount_code`",
+            "cart.proto: field `id` (1) removed without reserving its number or name",
+            "cart.proto: field `items` renumbered from 3 to 4",
+            "cart.proto: field `total` (2) changed type from int32 to int64",
+        ]
+    );
+}
+
+#[test]
+fn test_review_contracts() {
+    let dir = temp_dir("contracts");
+    init_repo(&dir);
+    fs::write(dir.join("cart.proto"), "message Cart {\n  string id = 1;\n  int32 total = 2;\n}\n").unwrap();
+    fs::write(dir.join("notes.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("cart.proto"), "message Cart {\n  int32 total = 2;\n}\n").unwrap();
+    fs::write(dir.join("notes.txt"), "two\n").unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+
+    let prompt = review(&[]);
+    assert!(prompt.contains("Incompatible protobuf change: cart.proto: field `id` (1) removed"), "{}", prompt);
+    assert!(prompt.contains("- cart.proto (protobuf schema)\n"), "{}", prompt);
+    assert!(!prompt.contains("notes.txt (contract)"), "{}", prompt);
+    assert!(review(&["--contract-files", "notes.*"]).contains("- notes.txt (contract)\n"));
+    assert!(!review(&["--no-compatibility-review"]).contains("protobuf schema"));
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::dashboard::{
+    escape_html, load_runs, markdown_to_html, render_dashboard, run_id, write_dashboard, Run, Verdict, REVIEWS_DIR,
+};
+use llm_code_review::feedback::{Feedback, History, HistoryEntry, Rating};
+use llm_code_review::findings::Severity;
+use llm_code_review::transcript::Transcript;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn entry(minute: usize) -> HistoryEntry {
+    HistoryEntry {
+        timestamp: format!("2025-09-01T12:{:02}:00Z", minute),
+        diff_sha256: Some(format!("{:08x}{}", minute, "f".repeat(56))),
+        model: Some("claude".to_string()),
+        git_ref: Some(format!("feature-{} @ abc1234", minute)),
+        ..Default::default()
+    }
+}
+
+const FINDINGS: &str = r#"{"summary": "Mostly **fine**.", "findings": [
+    {"path": "src/cart.rs", "line": 12, "severity": "error", "comment": "Overflows when `quantity` is <large>"},
+    {"path": "src/cart.rs", "line": 20, "severity": "info", "comment": "Could be a constant"}
+]}"#;
+
+#[test]
+fn test_escape_html() {
+    assert_eq!(escape_html(r#"<a href="x">Tom & 'Jerry'</a>"#), "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;");
+}
+
+#[test]
+fn test_markdown_to_html() {
+    let markdown = "# Review\n\nThe **cart** total uses `a < b`.\nStill the same paragraph.\n\n\
+                    - first\n- second <script>\n\n1. one\n2. two\n\n```rust\nif a < b {}\n```\n\nAn unmatched ` and **.";
+    assert_eq!(
+        markdown_to_html(markdown),
+        "<h2>Review</h2>\n\
+         <p>The <strong>cart</strong> total uses <code>a &lt; b</code>.\nStill the same paragraph.</p>\n\
+         <ul>\n<li>first</li>\n<li>second &lt;script&gt;</li>\n</ul>\n\
+         <ol>\n<li>one</li>\n<li>two</li>\n</ol>\n\
+         <pre><code>if a &lt; b {}</code></pre>\n\
+         <p>An unmatched ` and **.</p>\n"
+    );
+}
+
+#[test]
+fn test_verdicts() {
+    let run = |transcript: Option<Transcript>| Run { id: "run".to_string(), entry: entry(0), transcript };
+    assert_eq!(run(None).verdict(), Verdict::NotSent);
+    assert_eq!(run(Some(Transcript::new(None, "Looks good to me."))).verdict(), Verdict::Unstructured);
+    assert_eq!(run(Some(Transcript::new(None, "[]"))).verdict(), Verdict::Clean);
+    let reviewed = run(Some(Transcript::new(None, FINDINGS)));
+    assert_eq!(reviewed.verdict(), Verdict::ChangesNeeded);
+    assert_eq!((reviewed.count(Severity::Error), reviewed.count(Severity::Warning), reviewed.count(Severity::Info)), (1, 0, 1));
+    assert_eq!(Verdict::ChangesNeeded.to_string(), "changes needed");
+}
+
+#[test]
+fn test_run_id() {
+    assert_eq!(run_id(&entry(5)), "20250901T120500Z-00000005");
+    assert_eq!(run_id(&HistoryEntry { timestamp: "2025-09-01T12:05:00Z".to_string(), ..Default::default() }), "20250901T120500Z");
+}
+
+/// A history of `count` runs, one a minute, the last of them with a saved review
+fn history(dir: &Path, count: usize) {
+    let mut history = History::default();
+    for minute in 0..count {
+        history.record(entry(minute));
+    }
+    let last = history.entries.last_mut().unwrap();
+    last.review_file = Some(format!("{}.json", run_id(last)));
+    last.feedback = Some(Feedback { rating: Rating::Good, note: Some("caught the overflow".to_string()), timestamp: String::new() });
+    fs::create_dir_all(dir.join(REVIEWS_DIR)).unwrap();
+    Transcript::new(Some("claude-sonnet-4".to_string()), FINDINGS)
+        .save(&dir.join(REVIEWS_DIR).join(last.review_file.as_ref().unwrap()))
+        .unwrap();
+    history.save(dir).unwrap();
+}
+
+#[test]
+fn test_render_dashboard() {
+    let dir = temp_dir("dashboard_render");
+    history(&dir, 5);
+    let runs = load_runs(&dir).unwrap();
+    assert_eq!(runs.len(), 5);
+    // Newest first
+    assert_eq!(runs[0].id, "20250901T120400Z-00000004");
+    assert!(runs[0].transcript.is_some() && runs[1].transcript.is_none());
+
+    let files = render_dashboard("Shop reviews", &runs, 2);
+    let paths: Vec<&str> = files.keys().map(|path| path.to_str().unwrap()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "index.html",
+            "page-2.html",
+            "page-3.html",
+            "runs/20250901T120000Z-00000000.html",
+            "runs/20250901T120100Z-00000001.html",
+            "runs/20250901T120200Z-00000002.html",
+            "runs/20250901T120300Z-00000003.html",
+            "runs/20250901T120400Z-00000004.html",
+        ]
+    );
+
+    let index = &files[Path::new("index.html")];
+    assert!(index.contains("<title>Shop reviews</title>"), "{}", index);
+    assert!(index.contains("<p>5 reviews recorded.</p>"), "{}", index);
+    assert!(index.contains("<nav>Page 1 of 3 | <a href=\"page-2.html\">Older</a></nav>"), "{}", index);
+    assert!(
+        index.contains(
+            "<tr><td><a href=\"runs/20250901T120400Z-00000004.html\">2025-09-01 12:04:00 UTC</a></td>\
+             <td>feature-4 @ abc1234</td><td>claude-sonnet-4</td><td class=\"verdict-changes-needed\">changes needed</td>\
+             <td class=\"count\">1</td><td class=\"count\">0</td><td class=\"count\">1</td><td>good</td></tr>"
+        ),
+        "{}",
+        index
+    );
+    assert!(index.contains("<td>claude</td><td class=\"verdict-not-sent\">not sent</td>"), "{}", index);
+    assert!(!index.contains("feature-2"), "{}", index);
+    // No external assets
+    assert!(!index.contains("http") && !index.contains("<script") && !index.contains("<link"), "{}", index);
+    let middle = &files[Path::new("page-2.html")];
+    assert!(middle.contains("<a href=\"index.html\">Newer</a> | Page 2 of 3 | <a href=\"page-3.html\">Older</a>"), "{}", middle);
+
+    let page = &files[Path::new("runs/20250901T120400Z-00000004.html")];
+    assert!(page.contains("<nav><a href=\"../index.html\">Shop reviews</a></nav>"), "{}", page);
+    assert!(page.contains("<dt>Rating</dt><dd>good: caught the overflow</dd>"), "{}", page);
+    assert!(page.contains("<h2>Summary</h2>\n<p>Mostly <strong>fine</strong>.</p>"), "{}", page);
+    assert!(
+        page.contains(
+            "<tr><td class=\"severity-error\">error</td><td><code>src/cart.rs:12</code></td><td>-</td>\
+             <td>Overflows when <code>quantity</code> is &lt;large&gt;</td></tr>"
+        ),
+        "{}",
+        page
+    );
+    // The oldest run's page links back to the page it's listed on
+    let oldest = &files[Path::new("runs/20250901T120000Z-00000000.html")];
+    assert!(oldest.contains("<a href=\"../page-3.html\">"), "{}", oldest);
+    assert!(oldest.contains("there's no review to show"), "{}", oldest);
+
+    // The same history gives the same pages
+    assert_eq!(render_dashboard("Shop reviews", &load_runs(&dir).unwrap(), 2), files);
+}
+
+#[test]
+fn test_write_dashboard_removes_stale_pages() {
+    let dir = temp_dir("dashboard_write");
+    let history_dir = dir.join("history");
+    let output = dir.join("site");
+    history(&history_dir, 5);
+    write_dashboard(&output, &render_dashboard("Reviews", &load_runs(&history_dir).unwrap(), 2)).unwrap();
+    assert!(output.join("page-3.html").exists());
+    fs::write(output.join("CNAME"), "reviews.example.com\n").unwrap();
+
+    let _ = fs::remove_dir_all(&history_dir);
+    history(&history_dir, 2);
+    write_dashboard(&output, &render_dashboard("Reviews", &load_runs(&history_dir).unwrap(), 2)).unwrap();
+    assert!(output.join("index.html").exists());
+    assert!(!output.join("page-2.html").exists() && !output.join("page-3.html").exists());
+    assert_eq!(fs::read_dir(output.join("runs")).unwrap().count(), 2);
+    assert!(output.join("CNAME").exists());
+}
+
+#[test]
+fn test_report_subcommand() {
+    let dir = temp_dir("dashboard_cli");
+    let history_dir = dir.join("history");
+    let output = dir.join("site");
+    history(&history_dir, 3);
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), String::from_utf8_lossy(&output.stdout).to_string(), String::from_utf8_lossy(&output.stderr).to_string())
+    };
+    let generate = ["--history-dir", "history", "report", "generate", "--output-dir", "site"];
+
+    let (success, stdout, stderr) = run(&generate);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Wrote the dashboard of 3 reviews to site/index.html"), "{}", stdout);
+    let first = fs::read_to_string(output.join("index.html")).unwrap();
+    assert!(first.contains("<title>Code reviews</title>"), "{}", first);
+    let (success, _, stderr) = run(&generate);
+    assert!(success, "{}", stderr);
+    assert_eq!(fs::read_to_string(output.join("index.html")).unwrap(), first);
+
+    let (success, stdout, stderr) = run(&["report", "generate", "--output-dir", "site"]);
+    assert!(!success);
+    assert!(format!("{}{}", stdout, stderr).contains("report needs --history-dir"), "{}{}", stdout, stderr);
+}
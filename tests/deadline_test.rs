@@ -0,0 +1,34 @@
+use llm_code_review::deadline::Deadline;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_no_deadline_allows_everything() {
+    let deadline = Deadline::default();
+    assert_eq!(deadline.remaining(), None);
+    assert!(!deadline.exceeded());
+    assert!(deadline.allows("a slow step", Duration::from_secs(3600)));
+    assert_eq!(deadline.report(), None);
+}
+
+#[test]
+fn test_deadline_skips_steps_without_time() {
+    let deadline = Deadline::new(Instant::now(), Duration::from_secs(60));
+    assert!(!deadline.exceeded());
+    assert!(deadline.allows("a quick step", Duration::from_secs(1)));
+    assert!(!deadline.allows("a slow step", Duration::from_secs(120)));
+
+    let report = deadline.report().unwrap();
+    assert_eq!(report.deadline_secs, 60.0);
+    assert!(report.partial);
+    assert_eq!(report.decisions.len(), 1);
+    assert!(report.decisions[0].starts_with("Skipped a slow step: "), "{:?}", report.decisions);
+}
+
+#[test]
+fn test_exceeded_deadline() {
+    let deadline = Deadline::new(Instant::now() - Duration::from_secs(5), Duration::from_secs(2));
+    assert_eq!(deadline.remaining(), Some(Duration::ZERO));
+    assert!(deadline.exceeded());
+    assert!(deadline.allows("a free step", Duration::ZERO));
+    assert!(!deadline.report().unwrap().partial);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::temp_dir;
+use regex::Regex;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const DIFF: &str = "diff --git a/cart.rs b/cart.rs\n--- a/cart.rs\n+++ b/cart.rs\n@@ -1,3 +1,3 @@\n fn total() {\n-    1\n+    2\n }\n";
+
+/// Run the tool in `dir` with `stdin`, returning whether it succeeded and its output
+fn review(dir: &Path, args: &[&str], stdin: impl AsRef<[u8]>) -> (bool, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run llm_code_review");
+    child.stdin.take().unwrap().write_all(stdin.as_ref()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+}
+
+#[test]
+fn test_diff_file() {
+    // Not a repository: the diff is all there is
+    let dir = temp_dir("diff_file");
+    fs::write(dir.join("change.diff"), DIFF).unwrap();
+
+    let (success, prompt) = review(&dir, &["--diff-file", "-"], DIFF);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains(DIFF.trim_end()), "{}", prompt);
+    assert!(prompt.contains("## Diff Source\nA diff given on standard input"), "{}", prompt);
+    let (success, prompt) = review(&dir, &["--diff-file", "change.diff"], "");
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("The diff in `change.diff`"), "{}", prompt);
+    let (success, output) = review(&dir, &["--diff-file", "-"], "");
+    assert!(success, "{}", output);
+    assert!(output.contains("No changes found to review."), "{}", output);
+
+    // --stdin, or a lone `-`, reads standard input too
+    for args in [&["--stdin"][..], &["-"]] {
+        let (success, prompt) = review(&dir, args, DIFF);
+        assert!(success, "{}", prompt);
+        assert!(prompt.contains("## Diff Source\nA diff given on standard input"), "{}", prompt);
+    }
+    let (success, output) = review(&dir, &["--stdin"], "");
+    assert!(success && output.contains("No changes found to review."), "{}", output);
+    // A diff that isn't UTF-8 is decoded, not refused
+    let latin1 = [&DIFF.as_bytes()[..DIFF.len() - 3], b"// caf\xe9\n }\n"].concat();
+    let (success, prompt) = review(&dir, &["--stdin"], &latin1);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("+    2\n// café"), "{}", prompt);
+    let (success, output) = review(&dir, &["--stdin", "--diff-file", "change.diff"], "");
+    assert!(!success && output.starts_with("error: the argument '--stdin' cannot be used with '--diff-file <PATH>'"), "{}", output);
+
+    // `git format-patch` output, with its mail headers and signature
+    let patch = format!(
+        "From 1a2b3c Mon Sep 17 00:00:00 2001\nFrom: Dev <dev@example.com>\nSubject: [PATCH] Fix the total\n\n---\n cart.rs | 2 +-\n\n{}-- \n2.43.0\n",
+        DIFF
+    );
+    let (success, prompt) = review(&dir, &["--diff-file", "-", "--no-provenance"], &patch);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains(DIFF.trim_end()), "{}", prompt);
+
+    let (success, output) = review(&dir, &["--diff-file", "change.diff", "HEAD~1"], "");
+    assert!(!success);
+    assert!(output.contains("--diff-file doesn't run `git diff`, so it takes no diff arguments; got `HEAD~1`"), "{}", output);
+    let (success, output) = review(&dir, &["--diff-file", "-"], "Fixed the total.\n");
+    assert!(!success);
+    assert!(output.contains("--diff-file -: not a unified diff"), "{}", output);
+    let (success, output) = review(&dir, &["--diff-file", "missing.diff"], "");
+    assert!(!success);
+    assert!(output.contains("missing.diff"), "{}", output);
+}
+
+#[test]
+fn test_diff_file_context() {
+    let dir = temp_dir("diff_file_context");
+    let context: String = (0..150).map(|i| format!(" line {} of the unchanged text around the change\n", i)).collect();
+    let diff = format!("diff --git a/b.txt b/b.txt\n--- a/b.txt\n+++ b/b.txt\n@@ -1,301 +1,301 @@\n{0}-old\n+new\n{0}", context);
+
+    let args = ["--diff-file", "-", "--tokenizer", "chars", "--no-compress", "--no-provenance", "--max-tokens"];
+    let (success, prompt) = review(&dir, &[&args[..], &["5000"]].concat(), &diff);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains(&context), "{}", prompt);
+
+    let (success, prompt) = review(&dir, &[&args[..], &["3000"]].concat(), &diff);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("trimming its context to"), "{}", prompt);
+    assert!(prompt.contains("-old\n+new\n"), "{}", prompt);
+    assert!(prompt.matches("of the unchanged text").count() < 300, "{}", prompt);
+}
+
+#[test]
+fn test_diff_file_paths() {
+    let dir = temp_dir("diff_file_paths");
+    // A lock file far over the limit alongside a small change
+    let lock = format!(
+        "diff --git a/Cargo.lock b/Cargo.lock\n--- a/Cargo.lock\n+++ b/Cargo.lock\n@@ -0,0 +1,2000 @@\n{}",
+        "+checksum = \"0123456789abcdef0123456789abcdef\"\n".repeat(2000)
+    );
+    let diff = format!("{}{}", lock, DIFF);
+
+    let args = ["--diff-file", "-", "--no-reduce-context", "--max-tokens", "3000"];
+    let (success, output) = review(&dir, &args, &diff);
+    assert!(!success, "{}", output);
+    let (success, prompt) = review(&dir, &[&args[..], &["--exclude", "**/*.lock", "-v"]].concat(), &diff);
+    assert!(success, "{}", prompt);
+    // -v says what the filter saved
+    let saved = Regex::new(r"Left out by --include or --exclude: Cargo\.lock \(1 file, 2000 changed lines, about (\d+) tokens\)").unwrap();
+    let tokens: usize = saved.captures(&prompt).unwrap_or_else(|| panic!("{}", prompt))[1].parse().unwrap();
+    assert!(tokens > 3000, "{}", tokens);
+    assert!(prompt.contains(DIFF.trim_end()), "{}", prompt);
+    assert!(!prompt.contains("+++ b/Cargo.lock"), "{}", prompt);
+    // The model is told the lock file changed, so it doesn't take it to be untouched
+    assert!(prompt.contains("## Omitted Files\nThe following files were changed but omitted from this review: Cargo.lock."), "{}", prompt);
+    let (success, prompt) = review(&dir, &[&args[..], &["--include", "*.rs"]].concat(), &diff);
+    assert!(success, "{}", prompt);
+    assert!(!prompt.contains("+++ b/Cargo.lock"), "{}", prompt);
+
+    let renamed = format!(
+        "diff --git a/gen/api.rs b/gen/client.rs\nsimilarity index 100%\nrename from gen/api.rs\nrename to gen/client.rs\n{}",
+        DIFF
+    );
+    let (success, prompt) = review(&dir, &["--diff-file", "-", "--exclude", "gen/**"], &renamed);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("omitted from this review: gen/client.rs (renamed from gen/api.rs)."), "{}", prompt);
+
+    let (success, output) = review(&dir, &["--diff-file", "-", "--include", "docs/**"], &diff);
+    assert!(success, "{}", output);
+    assert!(output.contains("All the changed files were left out by --include, --exclude"), "{}", output);
+}
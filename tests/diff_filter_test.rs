@@ -0,0 +1,132 @@
+use llm_code_review::diff::{parse_diff, render_diff};
+use llm_code_review::diff_filter::{
+    filter_diff_by_paths, filter_public_api, filter_small_hunks, split_at_newline_boundary, supports_public_api,
+};
+use llm_code_review::error::ReviewError;
+use llm_code_review::severity_policy::glob_regex;
+use llm_code_review::vcs::try_command_diff;
+
+const MIN_CHANGE_SIZE_DIFF: &str = include_str!("fixtures/min_change_size.diff");
+const PLAIN_DIFF: &str = include_str!("fixtures/plain.diff");
+const API_ONLY_DIFF: &str = include_str!("fixtures/api_only.diff");
+
+#[test]
+fn test_parse_and_render_round_trip() {
+    let files = parse_diff(MIN_CHANGE_SIZE_DIFF);
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["Cargo.toml", "src/lib.rs", "logo.png"]);
+    assert_eq!(files[1].hunks.len(), 2);
+    assert_eq!(render_diff(&files), MIN_CHANGE_SIZE_DIFF);
+}
+
+#[test]
+fn test_filter_small_hunks() {
+    let files = filter_small_hunks(parse_diff(MIN_CHANGE_SIZE_DIFF), 5);
+
+    // Cargo.toml only had a 2 line hunk, so the whole file goes; the binary file had no hunks
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["src/lib.rs", "logo.png"]);
+
+    // The typo fix in src/lib.rs is dropped, the larger hunk is kept
+    assert_eq!(files[0].hunks.len(), 1);
+    assert!(files[0].hunks[0].header.starts_with("@@ -10,6 +10,12 @@"));
+    assert_eq!(files[0].hunks[0].changed_lines(), 8);
+}
+
+#[test]
+fn test_filter_diff_by_paths() {
+    let paths = |include: &[&str], exclude: &[&str]| {
+        let include: Vec<_> = include.iter().map(|glob| glob_regex(glob)).collect();
+        let exclude: Vec<_> = exclude.iter().map(|glob| glob_regex(glob)).collect();
+        let filtered = filter_diff_by_paths(MIN_CHANGE_SIZE_DIFF, &include, &exclude);
+        parse_diff(&filtered).into_iter().map(|file| file.path).collect::<Vec<_>>()
+    };
+    assert_eq!(filter_diff_by_paths(MIN_CHANGE_SIZE_DIFF, &[], &[]), MIN_CHANGE_SIZE_DIFF);
+    assert_eq!(paths(&["src/**/*.rs"], &[]), ["src/lib.rs"]);
+    assert_eq!(paths(&["*.toml", "*.png"], &[]), ["Cargo.toml", "logo.png"]);
+    assert_eq!(paths(&[], &["*.png", "Cargo.*"]), ["src/lib.rs"]);
+    assert_eq!(paths(&["src/**"], &["*.rs"]), Vec::<String>::new());
+}
+
+#[test]
+fn test_filter_diff_by_paths_renames_and_deletes() {
+    // A file moved out of gen/ and changed, one renamed within it, and one deleted from it
+    let diff = "diff --git a/gen/old.rs b/src/new.rs\nsimilarity index 90%\nrename from gen/old.rs\nrename to src/new.rs\n\
+                --- a/gen/old.rs\n+++ b/src/new.rs\n@@ -1 +1 @@\n-a\n+b\n\
+                diff --git a/gen/a.pb.rs b/gen/b.pb.rs\nsimilarity index 100%\nrename from gen/a.pb.rs\nrename to gen/b.pb.rs\n\
+                diff --git a/gen/gone.rs b/gen/gone.rs\ndeleted file mode 100644\n--- a/gen/gone.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-c\n";
+    let paths = |include: &[&str], exclude: &[&str]| {
+        let include: Vec<_> = include.iter().map(|glob| glob_regex(glob)).collect();
+        let exclude: Vec<_> = exclude.iter().map(|glob| glob_regex(glob)).collect();
+        parse_diff(&filter_diff_by_paths(diff, &include, &exclude)).into_iter().map(|file| file.path).collect::<Vec<_>>()
+    };
+    // Renamed files go by their new path, deleted ones by their old one
+    assert_eq!(paths(&[], &["gen/**"]), ["src/new.rs"]);
+    assert_eq!(paths(&["gen/**"], &[]), ["gen/b.pb.rs", "gen/gone.rs"]);
+    assert_eq!(paths(&[], &["*.pb.rs", "src/**"]), ["gen/gone.rs"]);
+    assert_eq!(paths(&[], &["gen/old.rs", "gen/a.pb.rs"]), ["src/new.rs", "gen/b.pb.rs", "gen/gone.rs"]);
+}
+
+#[test]
+fn test_filter_public_api() {
+    let files = filter_public_api(parse_diff(API_ONLY_DIFF));
+
+    // pub(crate) isn't public API; the Python file can't be filtered, so it's kept whole
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["src/lib.rs", "build.py"]);
+
+    // The signature change is kept, the change inside a private function is not
+    assert_eq!(files[0].hunks.len(), 1);
+    assert!(files[0].hunks[0].lines[2].starts_with("+pub fn parse"));
+
+    assert!(supports_public_api("src/lib.rs"));
+    assert!(!supports_public_api("build.py"));
+}
+
+#[test]
+fn test_parse_plain_unified_diff() {
+    let files = parse_diff(PLAIN_DIFF);
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["new/f.txt", "new/g.txt"]);
+
+    // A removed line starting with "--" is not a new file
+    assert_eq!(files[0].hunks[0].lines, [" a", "-b", "--- x", "+B", " c"]);
+    assert_eq!(files[1].hunks[0].lines.len(), 3);
+}
+
+#[test]
+fn test_try_command_diff() {
+    let diff = try_command_diff("cat", &["tests/fixtures/min_change_size.diff".to_string()]).unwrap();
+    assert_eq!(diff, MIN_CHANGE_SIZE_DIFF);
+    // The arguments reach the command whole
+    let path = std::env::temp_dir().join(format!("llm_code_review diff ü {}.diff", std::process::id()));
+    std::fs::write(&path, MIN_CHANGE_SIZE_DIFF).unwrap();
+    assert_eq!(try_command_diff("cat", &[path.to_str().unwrap().to_string()]).unwrap(), MIN_CHANGE_SIZE_DIFF);
+    std::fs::remove_file(&path).unwrap();
+
+    match try_command_diff("no-such-diff-command", &[]) {
+        Err(ReviewError::CommandNotFound(program)) => assert_eq!(program, "no-such-diff-command"),
+        other => panic!("expected CommandNotFound, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_split_at_newline_boundary() {
+    let text = "first line\nsecond line\nthird line\n";
+
+    // Snaps back to the end of the last whole line
+    assert_eq!(split_at_newline_boundary(text, 15), ("first line\n", "second line\nthird line\n"));
+    // A newline right at the target ends the first part
+    assert_eq!(split_at_newline_boundary(text, 10), ("first line\n", "second line\nthird line\n"));
+    assert_eq!(split_at_newline_boundary(text, 22), ("first line\nsecond line\n", "third line\n"));
+    // Everything fits
+    assert_eq!(split_at_newline_boundary(text, 100), (text, ""));
+}
+
+#[test]
+fn test_split_at_newline_boundary_without_newlines() {
+    assert_eq!(split_at_newline_boundary("abcdefgh", 3), ("abc", "defgh"));
+    // Never splits inside a character
+    assert_eq!(split_at_newline_boundary("añb", 2), ("a", "ñb"));
+    assert_eq!(split_at_newline_boundary("", 0), ("", ""));
+}
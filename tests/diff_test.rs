@@ -0,0 +1,133 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::{parse_diff, split_diff_by_file, Diff, FileKind};
+use std::fs;
+
+fn assert_round_trip(name: &str, text: &str) -> Diff {
+    let diff = Diff::parse(text);
+    assert_eq!(diff.render(), text, "{} didn't round-trip", name);
+    diff
+}
+
+#[test]
+fn test_fixture_round_trip() {
+    let mut count = 0;
+    for entry in fs::read_dir("tests/fixtures").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "diff") {
+            let bytes = fs::read(&path).unwrap();
+            assert_round_trip(&path.display().to_string(), &String::from_utf8_lossy(&bytes));
+            count += 1;
+        }
+    }
+    assert!(count >= 5);
+
+    // What parse_diff leaves out is kept
+    let plain = assert_round_trip("plain.diff", include_str!("fixtures/plain.diff"));
+    assert_eq!(plain.preamble, ["diff -ur old/f.txt new/f.txt"]);
+    assert_eq!(plain.files[1].header[0], "diff -ur old/g.txt new/g.txt");
+    assert_eq!(plain.files[0].old_path(), Some("old/f.txt"));
+    assert_eq!(plain.files[0].new_path(), Some("new/f.txt"));
+    assert_eq!(plain.files[1].hunks[0].range().unwrap().new_start, 1);
+}
+
+#[test]
+fn test_edge_round_trip() {
+    for (name, text) in [
+        ("empty", ""),
+        ("no final newline", "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b"),
+        ("crlf", "diff --git a/x b/x\r\n--- a/x\r\n+++ b/x\r\n@@ -1 +1 @@\r\n-a\r\n+b\r\n"),
+        ("blank lines", "\n\ndiff --git a/x b/x\n--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n-a\n+b\n \n\n"),
+        (
+            "plain with trailing lines",
+            "--- old/x\n+++ new/x\n@@ -1 +1 @@\n-a\n+b\nOnly in new: y\nOnly in old: z\n",
+        ),
+    ] {
+        assert_round_trip(name, text);
+    }
+    let crlf = Diff::parse("diff --git a/x b/x\r\n--- a/x\r\n+++ b/x\r\n@@ -1 +1 @@\r\n-a\r\n+b\r\n");
+    assert_eq!(crlf.files[0].path, "x");
+    assert_eq!(crlf.files[0].new_path(), Some("x"));
+    let plain = Diff::parse("--- old/x\n+++ new/x\n@@ -1 +1 @@\n-a\n+b\nOnly in new: y\n");
+    assert_eq!(plain.epilogue, ["Only in new: y"]);
+}
+
+#[test]
+fn test_git_round_trip() {
+    let dir = temp_dir("diff");
+    init_repo(&dir);
+    let text: String = (0..40).map(|n| format!("line {}\n", n)).collect();
+    fs::write(dir.join("moved.txt"), &text).unwrap();
+    fs::write(dir.join("script.sh"), "echo hi\n").unwrap();
+    fs::write(dir.join("gone.txt"), "bye\n").unwrap();
+    fs::write(dir.join("logo.png"), [0u8, 1, 2, 3, 255]).unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+
+    git(&dir, &["mv", "moved.txt", "renamed.txt"]);
+    fs::write(dir.join("renamed.txt"), text.replace("line 20\n", "line twenty\n")).unwrap();
+    git(&dir, &["rm", "-q", "gone.txt"]);
+    fs::write(dir.join("logo.png"), [0u8, 1, 2, 3, 254, 0]).unwrap();
+    fs::write(dir.join("added.txt"), "new\n").unwrap();
+    fs::write(dir.join("copy.txt"), &text).unwrap();
+    git(&dir, &["add", "-A"]);
+    git(&dir, &["update-index", "--chmod=+x", "script.sh"]);
+    git(&dir, &["commit", "-q", "-m", "two\n\nWith a body."]);
+
+    let diff = git(&dir, &["diff", "-M", "-C", "--find-copies-harder", "HEAD~1", "HEAD"]);
+    let parsed = assert_round_trip("git diff", &diff);
+    let file = |path: &str| parsed.files.iter().find(|file| file.path == path).unwrap();
+    assert_eq!(file("renamed.txt").kind(), FileKind::Renamed);
+    assert_eq!(file("renamed.txt").old_path(), Some("moved.txt"));
+    assert_eq!(file("copy.txt").kind(), FileKind::Copied);
+    assert_eq!(file("added.txt").kind(), FileKind::Added);
+    assert_eq!(file("added.txt").old_path(), None);
+    assert_eq!(file("gone.txt").kind(), FileKind::Deleted);
+    assert_eq!(file("gone.txt").new_path(), None);
+    assert_eq!(file("script.sh").mode_change(), Some(("100644", "100755")));
+    assert_eq!(file("script.sh").kind(), FileKind::Modified);
+    assert_eq!(file("script.sh").old_path(), Some("script.sh"));
+    assert!(file("logo.png").is_binary());
+    assert!(file("logo.png").hunks.is_empty());
+
+    assert_round_trip("binary patch", &git(&dir, &["diff", "--binary", "HEAD~1", "HEAD"]));
+    assert_round_trip("no context", &git(&dir, &["diff", "-U0", "HEAD~1", "HEAD"]));
+    let show = assert_round_trip("git show", &git(&dir, &["show", "HEAD"]));
+    assert!(show.preamble[0].starts_with("commit "), "{:?}", show.preamble);
+    assert!(show.preamble.iter().any(|line| line.trim() == "With a body."));
+    assert_round_trip("format-patch", &git(&dir, &["format-patch", "--stdout", "-1", "HEAD"]));
+}
+
+#[test]
+fn test_split_diff_by_file() {
+    let diff = "diff --git a/old.rs b/new.rs\nsimilarity index 90%\nrename from old.rs\nrename to new.rs\n--- a/old.rs\n+++ b/new.rs\n\
+                @@ -1 +1 @@\n-a\n+b\n\
+                diff --git a/logo.png b/logo.png\nindex 1234567..89abcde 100644\nBinary files a/logo.png and b/logo.png differ\n\
+                diff --git a/gone.rs b/gone.rs\ndeleted file mode 100644\n--- a/gone.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-c\n";
+    let files = split_diff_by_file(diff);
+    let paths: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(paths, ["new.rs", "logo.png", "gone.rs"]);
+    assert!(files[0].1.starts_with("diff --git a/old.rs b/new.rs\nsimilarity index 90%\nrename from old.rs"), "{}", files[0].1);
+    assert!(files[1].1.ends_with("Binary files a/logo.png and b/logo.png differ\n"), "{}", files[1].1);
+    assert_eq!(files.iter().map(|(_, chunk)| chunk.as_str()).collect::<String>(), diff);
+}
+
+#[test]
+fn test_split_diff_by_file_fixture() {
+    // git's output for a mode change, a binary file, a rename into a directory with a space in its
+    // name, and a file with a space in its name, whose ---/+++ lines git ends with a tab
+    let diff = fs::read_to_string("tests/fixtures/per_file.diff").unwrap();
+    let files = split_diff_by_file(&diff);
+    let paths: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(paths, ["deploy.sh", "logo.png", "src/shop/old api.rs", "src/shop/order total.rs"]);
+    assert_eq!(files.iter().map(|(_, chunk)| chunk.as_str()).collect::<String>(), diff);
+
+    let parsed = parse_diff(&diff);
+    assert_eq!(parsed[0].mode_change(), Some(("100644", "100755")));
+    assert!(parsed[0].hunks.is_empty() && parsed[0].kind() == FileKind::Modified);
+    assert!(parsed[1].is_binary());
+    assert_eq!((parsed[2].kind(), parsed[2].old_path()), (FileKind::Renamed, Some("src/legacy.rs")));
+    assert_eq!(parsed[2].new_path(), Some("src/shop/old api.rs"));
+    assert_eq!((parsed[3].old_path(), parsed[3].new_path()), (Some("src/shop/order total.rs"), Some("src/shop/order total.rs")));
+}
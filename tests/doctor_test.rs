@@ -0,0 +1,128 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::doctor::{
+    check_credentials, check_git, check_pager, check_round_trip, find_on_path, parse_git_version, render_checks,
+    Check, CheckStatus,
+};
+use llm_code_review::provider::load_provider_config;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_git_version() {
+    assert_eq!(parse_git_version("git version 2.43.0\n"), Some((2, 43)));
+    assert_eq!(parse_git_version("git version 2.39.3 (Apple Git-146)"), Some((2, 39)));
+    assert_eq!(parse_git_version("git version 2.45.1.windows.1"), Some((2, 45)));
+    assert_eq!(parse_git_version("hub version 2.14"), None);
+
+    assert_eq!(check_git(&Ok("git version 2.43.0".to_string())).status, CheckStatus::Pass);
+    assert_eq!(check_git(&Ok("git version 2.17.1".to_string())).status, CheckStatus::Warn);
+    let old = check_git(&Ok("git version 1.8.3.1".to_string()));
+    assert_eq!(old.status, CheckStatus::Fail);
+    assert_eq!(old.hint.as_deref(), Some("Upgrade git to 2.5 or newer"));
+    assert_eq!(check_git(&Err("No such file or directory".to_string())).status, CheckStatus::Fail);
+}
+
+#[test]
+fn test_credentials() {
+    let providers = load_provider_config(Path::new("tests/fixtures/providers.toml")).unwrap();
+    let set = check_credentials(&providers[0], |var| (var == "ANTHROPIC_API_KEY").then(|| "sk-test".to_string()));
+    assert_eq!(set.status, CheckStatus::Pass);
+    assert_eq!(set.detail, "ANTHROPIC_API_KEY is set");
+
+    let blank = check_credentials(&providers[1], |_| Some(" ".to_string()));
+    assert_eq!(blank.status, CheckStatus::Fail);
+    assert_eq!(blank.detail, "LOCAL_LLM_KEY is not set");
+    assert!(blank.hint.unwrap().contains("api_key_env for provider local"));
+}
+
+#[test]
+fn test_round_trip() {
+    let endpoint = "https://api.example.com/v1/chat";
+    // The test request is empty, so a working setup is told it's invalid
+    assert_eq!(check_round_trip(endpoint, Ok(400)).status, CheckStatus::Pass);
+    assert_eq!(check_round_trip(endpoint, Ok(401)).status, CheckStatus::Fail);
+    assert_eq!(check_round_trip(endpoint, Ok(404)).status, CheckStatus::Fail);
+    assert_eq!(check_round_trip(endpoint, Ok(429)).status, CheckStatus::Warn);
+    assert_eq!(check_round_trip(endpoint, Ok(503)).status, CheckStatus::Warn);
+    let proxy = check_round_trip(endpoint, Ok(407));
+    assert!(proxy.hint.unwrap().contains("HTTPS_PROXY"));
+
+    let unreachable = check_round_trip(endpoint, Err("Connection refused".to_string()));
+    assert_eq!(unreachable.status, CheckStatus::Fail);
+    assert!(unreachable.detail.contains("Connection refused"), "{}", unreachable.detail);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_commands_on_path() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("doctor");
+    for (name, mode) in [("xclip", 0o755), ("less", 0o644)] {
+        fs::write(dir.join(name), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(dir.join(name), fs::Permissions::from_mode(mode)).unwrap();
+    }
+    let path = std::env::join_paths([Path::new("/nonexistent"), &dir]).unwrap();
+
+    assert_eq!(find_on_path(&["pbcopy", "xclip"], &path), Some("xclip"));
+    // Not executable
+    assert_eq!(find_on_path(&["less"], &path), None);
+    assert_eq!(find_on_path(&["xclip"], OsStr::new("")), None);
+
+    assert_eq!(check_pager(Some("xclip -o"), &path).status, CheckStatus::Pass);
+    let missing = check_pager(Some("most"), &path);
+    assert_eq!(missing.status, CheckStatus::Warn);
+    assert_eq!(missing.detail, "PAGER is most, which wasn't found");
+    assert_eq!(check_pager(None, &path).status, CheckStatus::Warn);
+}
+
+#[test]
+fn test_table() {
+    let checks = vec![
+        Check { name: "git", status: CheckStatus::Pass, detail: "git version 2.43.0".to_string(), hint: None },
+        Check {
+            name: "credentials",
+            status: CheckStatus::Fail,
+            detail: "OPENAI_API_KEY is not set".to_string(),
+            hint: Some("Export OPENAI_API_KEY".to_string()),
+        },
+    ];
+    assert_eq!(
+        render_checks(&checks),
+        "Check        Status  Details\n\
+         git          PASS    git version 2.43.0\n\
+         credentials  FAIL    OPENAI_API_KEY is not set\n\
+         \x20                    hint: Export OPENAI_API_KEY\n\
+         \n\
+         1 failure, 0 warnings"
+    );
+}
+
+#[test]
+fn test_doctor_fails_on_invalid_configuration() {
+    let dir = temp_dir("doctor_config");
+    fs::write(dir.join("providers.toml"), "[[provider]]\nname = \"x\"\n").unwrap();
+
+    let doctor = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review")
+    };
+
+    let output = doctor(&["doctor", "--offline"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("provider API   SKIP    no provider configured"), "{}", stdout);
+
+    let output = doctor(&["--llm-provider-config", "providers.toml", "doctor", "--offline"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("configuration  FAIL"), "{}", stdout);
+    assert!(stdout.contains("the configuration is invalid"), "{}", stdout);
+}
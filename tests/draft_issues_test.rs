@@ -0,0 +1,167 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::draft_issues::{
+    draft_issue, finding_marker, follow_ups, hunk_excerpt, unfiled, ReviewedCode,
+};
+use llm_code_review::findings::{Finding, Severity};
+use std::fs;
+use std::process::Command;
+
+fn finding(path: &str, line: Option<usize>, severity: Severity, comment: &str, follow_up: bool) -> Finding {
+    Finding {
+        path: path.to_string(),
+        line,
+        severity,
+        category: None,
+        comment: comment.to_string(),
+        follow_up,
+        policy_note: None,
+        confidence: None,
+        extracted: false,
+        lower_priority: false,
+    }
+}
+
+const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                    --- a/src/lib.rs\n\
+                    +++ b/src/lib.rs\n\
+                    @@ -10,7 +10,8 @@ fn load()\n \
+                    let a = 1;\n \
+                    let b = 2;\n\
+                    -    let c = read();\n\
+                    +    let c = read().unwrap();\n\
+                    +    let d = c.len();\n \
+                    … 20 unchanged lines …\n \
+                    let e = 5;\n \
+                    let f = 6;\n";
+
+fn code() -> ReviewedCode {
+    ReviewedCode {
+        repository_url: "https://github.com/acme/app".to_string(),
+        sha: "0123456789abcdef0123456789abcdef01234567".to_string(),
+    }
+}
+
+#[test]
+fn test_follow_ups() {
+    let findings = vec![
+        finding("a.rs", Some(1), Severity::Error, "Panics on empty input.", false),
+        finding("a.rs", Some(2), Severity::Warning, "Slow for large files.", false),
+        finding("a.rs", Some(3), Severity::Error, "Split this module later.", true),
+        finding("a.rs", Some(4), Severity::Info, "Name this constant.", false),
+    ];
+    let comments = |findings: Vec<Finding>| findings.into_iter().map(|f| f.comment).collect::<Vec<_>>();
+    assert_eq!(comments(follow_ups(findings.clone(), None)), vec!["Split this module later."]);
+    assert_eq!(
+        comments(follow_ups(findings.clone(), Some(Severity::Error))),
+        vec!["Slow for large files.", "Split this module later.", "Name this constant."]
+    );
+    assert_eq!(comments(follow_ups(findings, Some(Severity::Warning))).len(), 2);
+    assert_eq!("warning".parse::<Severity>(), Ok(Severity::Warning));
+    assert!("fatal".parse::<Severity>().is_err());
+}
+
+#[test]
+fn test_hunk_excerpt() {
+    let files = parse_diff(DIFF);
+    let unwrap = finding("src/lib.rs", Some(12), Severity::Warning, "unwrap", true);
+    assert_eq!(
+        hunk_excerpt(&files, &unwrap).unwrap(),
+        "@@ -10,7 +10,8 @@ fn load()\n let a = 1;\n let b = 2;\n-    let c = read();\n+    let c = read().unwrap();\n\
+         +    let d = c.len();\n … 20 unchanged lines …\n let e = 5;\n let f = 6;"
+    );
+    // Past the elided lines, with at most four lines before
+    let after = finding("src/lib.rs", Some(34), Severity::Info, "e", true);
+    assert!(hunk_excerpt(&files, &after).unwrap().starts_with("@@ -10,7 +10,8 @@ fn load()\n-    let c = read();"));
+    // Inside them, or in another file, there's nothing to show
+    assert_eq!(hunk_excerpt(&files, &finding("src/lib.rs", Some(20), Severity::Info, "x", true)), None);
+    assert_eq!(hunk_excerpt(&files, &finding("src/main.rs", Some(12), Severity::Info, "x", true)), None);
+    // Without a line, the start of the file's first hunk
+    let whole_file = hunk_excerpt(&files, &finding("src/lib.rs", None, Severity::Info, "x", true)).unwrap();
+    assert_eq!(whole_file.lines().count(), 6);
+}
+
+#[test]
+fn test_draft_issue() {
+    let files = parse_diff(DIFF);
+    let mut unwrap = finding(
+        "src/lib.rs",
+        Some(12),
+        Severity::Warning,
+        "This unwrap panics when the file is missing. Return the error instead.",
+        true,
+    );
+    unwrap.category = Some("error-handling".to_string());
+    let draft = draft_issue(&unwrap, &files, &code(), "tech-debt");
+    assert_eq!(draft.title, "src/lib.rs: This unwrap panics when the file is missing");
+    assert_eq!(draft.labels, vec!["tech-debt"]);
+    assert!(draft.body.starts_with(
+        "This unwrap panics when the file is missing. Return the error instead.\n\n\
+         **Where:** [`src/lib.rs` line 12](https://github.com/acme/app/blob/0123456789abcdef0123456789abcdef01234567/src/lib.rs#L12)\n\
+         **Severity:** warning | **Category:** error-handling\n\n```diff\n@@ -10,7 +10,8 @@"
+    ), "{}", draft.body);
+    assert!(draft.body.contains("review of 0123456789ab"), "{}", draft.body);
+    assert!(draft.body.ends_with(&draft.marker));
+    assert_eq!(draft.to_json()["labels"][0], "tech-debt");
+
+    let long = finding("src/lib.rs", None, Severity::Info, &"word ".repeat(40), true);
+    let title = draft_issue(&long, &files, &code(), "tech-debt").title;
+    assert_eq!(title.chars().count(), 100);
+    assert!(title.starts_with("src/lib.rs: word word") && title.ends_with('…'), "{}", title);
+}
+
+#[test]
+fn test_unfiled() {
+    let files = parse_diff(DIFF);
+    let at_12 = finding("src/lib.rs", Some(12), Severity::Warning, "Handle the error.", true);
+    // The same finding after the code around it moved is still the same
+    let at_40 = finding("src/lib.rs", Some(40), Severity::Warning, "Handle the error. ", true);
+    assert_eq!(finding_marker(&at_12), finding_marker(&at_40));
+    let other = finding("src/lib.rs", Some(12), Severity::Warning, "Add a test.", true);
+
+    let drafts: Vec<_> = [&at_12, &at_40, &other].iter().map(|f| draft_issue(f, &files, &code(), "x")).collect();
+    assert_eq!(unfiled(drafts.clone(), &[]).len(), 2);
+    let existing = vec![format!("Edited by hand.\n\n{}", drafts[2].marker)];
+    let left = unfiled(drafts, &existing);
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].marker, finding_marker(&at_12));
+}
+
+#[test]
+fn test_dry_run() {
+    let dir = temp_dir("draft_issues");
+    init_repo(&dir);
+    // Nothing listens there, so checking for earlier issues fails without leaving the machine
+    git(&dir, &["remote", "add", "origin", "https://127.0.0.1/acme/app.git"]);
+    fs::write(dir.join("app.py"), "def load():\n    return 1\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("app.py"), "def load():\n    return open('x').read()\n").unwrap();
+    git(&dir, &["commit", "-q", "-a", "-m", "two"]);
+
+    let findings = vec![
+        finding("app.py", Some(2), Severity::Warning, "The file is never closed.", true),
+        finding("app.py", Some(2), Severity::Error, "x may not exist.", false),
+    ];
+    fs::write(dir.join("findings.json"), serde_json::to_string(&findings).unwrap()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .args(["--file-issues", "findings.json", "--file-issues-dry-run", "HEAD~1", "HEAD"])
+        .env_remove("GITHUB_TOKEN")
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Could not check for issues filed before"), "{}", stderr);
+    assert!(stdout.contains("Title: app.py: The file is never closed\nLabels: llm-review-follow-up"), "{}", stdout);
+    assert!(stdout.contains("(https://127.0.0.1/acme/app/blob/"), "{}", stdout);
+    assert!(stdout.contains("+    return open('x').read()"), "{}", stdout);
+    assert!(!stdout.contains("x may not exist"), "{}", stdout);
+    assert!(stdout.contains("Would open 1 issue"), "{}", stdout);
+    // No prompt is written
+    assert!(!stdout.contains("Please review"), "{}", stdout);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::review::{commit_message, list_commits};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_list_commits() {
+    let dir = temp_dir("each");
+
+    init_repo(&dir);
+    for (i, subject) in ["first", "second", "third"].iter().enumerate() {
+        fs::write(dir.join("file.txt"), format!("{}\n", i)).unwrap();
+        git(&dir, &["add", "file.txt"]);
+        git(&dir, &["commit", "-q", "-m", subject, "-m", "Details"]);
+    }
+
+    // This is the only test in this binary, so changing directory is safe
+    std::env::set_current_dir(&dir).unwrap();
+
+    // Oldest first
+    let commits = list_commits("HEAD~2..HEAD").unwrap();
+    assert_eq!(commits.len(), 2);
+    assert_eq!(commit_message(&commits[0]).unwrap(), "second\n\nDetails\n\n");
+    assert!(commit_message(&commits[1]).unwrap().starts_with("third\n"));
+
+    assert!(list_commits("no-such-branch..HEAD").is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_each_partial_failure() {
+    let dir = temp_dir("partial");
+
+    init_repo(&dir);
+    fs::write(dir.join("small.txt"), "small\n").unwrap();
+    git(&dir, &["add", "small.txt"]);
+    git(&dir, &["commit", "-q", "-m", "small"]);
+    // Over the smallest budget --context-window-padding can leave, with no context to reduce
+    fs::write(dir.join("large.txt"), "large line\n".repeat(1_000)).unwrap();
+    git(&dir, &["add", "large.txt"]);
+    git(&dir, &["commit", "-q", "-m", "large"]);
+
+    let review_each = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(["--each", "HEAD", "--output-dir", "prompts"])
+            .args(["--context-window-padding", "1.0", "--no-reduce-context"])
+            .args(extra)
+            .output()
+            .expect("failed to run llm_code_review")
+    };
+
+    let output = review_each(&[]);
+    assert_eq!(output.status.code(), Some(llm_code_review::review::EXIT_PARTIAL));
+    let index = fs::read_to_string(dir.join("prompts/index.md")).unwrap();
+    assert!(index.contains("](001-"), "{}", index);
+    assert!(index.contains("Review unavailable for these commits: `"), "{}", index);
+    let failed = fs::read_to_string(dir.join("prompts/failed.json")).unwrap();
+    assert!(failed.contains("\"subject\": \"large\""), "{}", failed);
+    assert!(failed.contains("too large"), "{}", failed);
+
+    assert!(review_each(&["--allow-partial"]).status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
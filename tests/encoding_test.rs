@@ -0,0 +1,34 @@
+use llm_code_review::encoding::decode_diff_with;
+
+const SHIFT_JIS_DIFF: &[u8] = include_bytes!("fixtures/shift_jis.diff");
+const LATIN1_DIFF: &[u8] = include_bytes!("fixtures/latin1.diff");
+
+#[test]
+fn test_decode_shift_jis_by_detection() {
+    let decoded = decode_diff_with(SHIFT_JIS_DIFF, |_| None);
+    assert!(decoded.contains("+  MESSAGE = \"こんにちは、皆さん。今日もよろしくお願いします。\""));
+    assert!(decoded.contains("# Note: file content transcoded from Shift_JIS to UTF-8"));
+    assert!(!decoded.contains('\u{FFFD}'));
+}
+
+#[test]
+fn test_decode_latin1_from_git_attribute() {
+    let decoded = decode_diff_with(LATIN1_DIFF, |path| {
+        assert_eq!(path, "README.txt");
+        Some("ISO-8859-1".to_string())
+    });
+    assert!(decoded.contains("+Crème brûlée: 6 francs, garçon"));
+    // encoding_rs treats ISO-8859-1 as windows-1252, per the WHATWG encoding standard
+    assert!(decoded.contains("transcoded from windows-1252 to UTF-8"));
+}
+
+#[test]
+fn test_decode_only_touches_non_utf8_files() {
+    let mut raw = b"diff --git a/ok.txt b/ok.txt\n--- a/ok.txt\n+++ b/ok.txt\n@@ -1 +1 @@\n-caf\xc3\xa9\n+cafe\n".to_vec();
+    raw.extend_from_slice(LATIN1_DIFF);
+
+    let decoded = decode_diff_with(&raw, |_| None);
+    assert!(decoded.starts_with("diff --git a/ok.txt b/ok.txt\n--- a/ok.txt\n"));
+    assert!(decoded.contains("-café\n"));
+    assert_eq!(decoded.matches("# Note: file content transcoded").count(), 1);
+}
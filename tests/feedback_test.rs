@@ -0,0 +1,193 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::feedback::{
+    feedback_report, Feedback, History, HistoryEntry, Rating, HISTORY_FILE, MAX_HISTORY_ENTRIES,
+};
+use llm_code_review::history::load_reviews;
+use std::fs;
+use std::process::Command;
+use std::thread;
+
+fn entry(prompt_version: &str, model: &str, depth: &str, rating: Option<Rating>) -> HistoryEntry {
+    HistoryEntry {
+        timestamp: "2025-09-01T12:00:00Z".to_string(),
+        prompt_version: Some(prompt_version.to_string()),
+        model: Some(model.to_string()),
+        preset: Some("rust".to_string()),
+        depth: Some(depth.to_string()),
+        feedback: rating.map(|rating| Feedback { rating, note: None, timestamp: "2025-09-01T12:05:00Z".to_string() }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_rate_latest() {
+    let dir = temp_dir("feedback_rate");
+    let mut history = History::load(&dir).unwrap();
+    let feedback = Feedback { rating: Rating::Good, note: None, timestamp: "2025-09-01T12:05:00Z".to_string() };
+    assert!(history.rate_latest(feedback.clone()).is_none());
+
+    history.record(entry("aaa", "claude", "auto", None));
+    history.record(entry("bbb", "claude", "short", None));
+    let rated = history.rate_latest(Feedback { note: Some("caught the race".to_string()), ..feedback }).unwrap();
+    assert_eq!(rated.prompt_version.as_deref(), Some("bbb"));
+    history.save(&dir).unwrap();
+
+    let history = History::load(&dir).unwrap();
+    assert_eq!(history.entries[0].feedback, None);
+    let feedback = history.entries[1].feedback.as_ref().unwrap();
+    assert_eq!(feedback.rating, Rating::Good);
+    assert_eq!(feedback.note.as_deref(), Some("caught the race"));
+
+    // The history isn't read as a past review
+    fs::write(dir.join("review.md"), "Looks good").unwrap();
+    let reviews = load_reviews(&dir).unwrap();
+    assert_eq!(reviews.len(), 1);
+    assert!(reviews[0].path.ends_with("review.md"));
+}
+
+#[test]
+fn test_oldest_entries_are_dropped() {
+    let mut history = History::default();
+    for i in 0..MAX_HISTORY_ENTRIES + 2 {
+        history.record(entry(&i.to_string(), "claude", "auto", None));
+    }
+    assert_eq!(history.entries.len(), MAX_HISTORY_ENTRIES);
+    assert_eq!(history.entries[0].prompt_version.as_deref(), Some("2"));
+}
+
+#[test]
+fn test_entries_from_before_feedback() {
+    let dir = temp_dir("feedback_old");
+    fs::write(
+        dir.join(HISTORY_FILE),
+        r#"{"entries": [{"timestamp": "2025-08-01T09:00:00Z", "diff_sha256": "abc"}, {}]}"#,
+    )
+    .unwrap();
+    let history = History::load(&dir).unwrap();
+    assert_eq!(history.entries.len(), 2);
+    assert_eq!(history.entries[0].feedback, None);
+    assert_eq!(history.entries[0].model, None);
+
+    let report = feedback_report(&history.entries);
+    assert!(report.starts_with("2 reviews, 0 rated"), "{}", report);
+    // Every table has them under `unknown`, with no ratings
+    let unknown: Vec<Vec<&str>> = report
+        .lines()
+        .filter(|line| line.starts_with("unknown"))
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(unknown, vec![vec!["unknown", "0", "0", "2", "-"]; 4], "{}", report);
+}
+
+#[test]
+fn test_report() {
+    let entries = vec![
+        entry("aaa", "claude", "auto", Some(Rating::Good)),
+        entry("aaa", "claude", "auto", Some(Rating::Bad)),
+        entry("bbb", "claude", "short", Some(Rating::Good)),
+        entry("bbb", "local", "short", Some(Rating::Good)),
+        entry("bbb", "local", "short", None),
+    ];
+    assert_eq!(
+        feedback_report(&entries),
+        "5 reviews, 4 rated\n\
+         \n\
+         Prompt version  good   bad  unrated  liked\n\
+         aaa                1     1        0    50%\n\
+         bbb                2     0        1   100%\n\
+         \n\
+         Model   good   bad  unrated  liked\n\
+         claude     2     1        0    67%\n\
+         local      1     0        1   100%\n\
+         \n\
+         Preset  good   bad  unrated  liked\n\
+         rust       3     1        1    75%\n\
+         \n\
+         Depth  good   bad  unrated  liked\n\
+         auto      1     1        0    50%\n\
+         short     2     0        1   100%"
+    );
+}
+
+#[test]
+fn test_feedback_subcommand() {
+    let dir = temp_dir("feedback_cli");
+    fs::write(dir.join("code.rs"), "fn main() {}\n").unwrap();
+    let history = dir.join("history");
+    fs::create_dir_all(&history).unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .arg("--history-dir")
+            .arg(&history)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+
+    let (success, stdout) = run(&["feedback", "good"]);
+    assert!(!success);
+    assert!(stdout.contains("No reviews are recorded"), "{}", stdout);
+
+    let (success, stdout) = run(&["--review-length", "short", "--raw-code", "code.rs"]);
+    assert!(success, "{}", stdout);
+    let (success, stdout) = run(&["feedback", "bad", "--note", "too vague"]);
+    assert!(success, "{}", stdout);
+    assert!(stdout.contains("as bad"), "{}", stdout);
+
+    let history = History::load(&history).unwrap();
+    assert_eq!(history.entries.len(), 1);
+    let entry = &history.entries[0];
+    assert_eq!(entry.depth.as_deref(), Some("short"));
+    assert_eq!(entry.model.as_deref(), Some("none"));
+    assert_eq!(entry.prompt_version.as_ref().map(String::len), Some(12));
+    assert_eq!(entry.feedback.as_ref().and_then(|feedback| feedback.note.as_deref()), Some("too vague"));
+
+    // The model, not the provider it went through, so two models' reviews are counted apart
+    let (success, stdout) = run(&["--model", "gpt-4o-mini", "--raw-code", "code.rs"]);
+    assert!(success, "{}", stdout);
+    let history = History::load(&dir.join("history")).unwrap();
+    assert_eq!(history.entries[1].model.as_deref(), Some("gpt-4o-mini"));
+}
+
+#[test]
+fn test_concurrent_runs() {
+    let dir = temp_dir("feedback_concurrent");
+    fs::write(dir.join("code.rs"), "fn main() {}\n").unwrap();
+    let history = dir.join("history");
+    fs::create_dir_all(&history).unwrap();
+
+    // Runs recording at the same time each add their entry, and none is left half written
+    let runs: Vec<_> = (0..4)
+        .map(|_| {
+            let (dir, history) = (dir.clone(), history.clone());
+            thread::spawn(move || {
+                for _ in 0..3 {
+                    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+                        .current_dir(&dir)
+                        .arg("--history-dir")
+                        .arg(&history)
+                        .args(["--raw-code", "code.rs"])
+                        .output()
+                        .expect("failed to run llm_code_review");
+                    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+                }
+            })
+        })
+        .collect();
+    for run in runs {
+        run.join().unwrap();
+    }
+    assert_eq!(History::load(&history).unwrap().entries.len(), 12);
+    let leftovers: Vec<_> = fs::read_dir(&history)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "ffi")]
+
+use llm_code_review::ffi::*;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fs;
+use std::ptr;
+
+fn diff() -> CString {
+    CString::new(fs::read_to_string("tests/fixtures/plain.diff").unwrap()).unwrap()
+}
+
+/// What the provider saw, and what it should answer, shared through `user_data`
+struct Conversation {
+    prompt: Option<String>,
+    review: Option<CString>,
+}
+
+extern "C" fn recording_provider(prompt: *const c_char, user_data: *mut c_void) -> *const c_char {
+    let conversation = unsafe { &mut *(user_data as *mut Conversation) };
+    conversation.prompt = Some(unsafe { CStr::from_ptr(prompt) }.to_str().unwrap().to_string());
+    match &conversation.review {
+        Some(review) => review.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[test]
+fn test_build_prompt_round_trip() {
+    let argv = [c"--review-length".as_ptr(), c"short".as_ptr(), c"--context".as_ptr(), c"Totals are in cents".as_ptr()];
+    let mut prompt = ptr::null_mut();
+    let status = unsafe { llm_review_build_prompt(diff().as_ptr(), argv.as_ptr(), argv.len(), &mut prompt) };
+    assert_eq!(status, LlmReviewStatus::Ok);
+    assert!(!prompt.is_null());
+
+    let text = unsafe { CStr::from_ptr(prompt) }.to_str().unwrap().to_string();
+    unsafe { llm_review_free_string(prompt) };
+    assert!(text.contains("+B"), "{}", text);
+    assert!(text.contains("200 words"), "{}", text);
+    assert!(text.contains("## Additional Context\nTotals are in cents\n"), "{}", text);
+}
+
+#[test]
+fn test_run_passes_prompt_and_user_data_to_provider() {
+    let mut conversation = Conversation {
+        prompt: None,
+        review: Some(CString::new("Rename `x` to something descriptive.").unwrap()),
+    };
+    let mut review = ptr::null_mut();
+    let status = unsafe {
+        llm_review_run(
+            diff().as_ptr(),
+            ptr::null(),
+            0,
+            Some(recording_provider),
+            &mut conversation as *mut Conversation as *mut c_void,
+            &mut review,
+        )
+    };
+    assert_eq!(status, LlmReviewStatus::Ok);
+
+    // The review is a copy, so it outlives the provider's string
+    drop(conversation.review.take());
+    assert_eq!(
+        unsafe { CStr::from_ptr(review) }.to_str(),
+        Ok("Rename `x` to something descriptive.")
+    );
+    unsafe { llm_review_free_string(review) };
+
+    let mut expected = ptr::null_mut();
+    unsafe { llm_review_build_prompt(diff().as_ptr(), ptr::null(), 0, &mut expected) };
+    assert_eq!(conversation.prompt.as_deref(), unsafe { CStr::from_ptr(expected) }.to_str().ok());
+    unsafe { llm_review_free_string(expected) };
+}
+
+#[test]
+fn test_errors_leave_output_null() {
+    let diff = diff();
+    let mut out = c"stale".as_ptr() as *mut c_char;
+
+    let status = unsafe { llm_review_build_prompt(ptr::null(), ptr::null(), 0, &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrNullArgument);
+    assert!(out.is_null());
+
+    let status = unsafe { llm_review_build_prompt(diff.as_ptr(), ptr::null(), 0, ptr::null_mut()) };
+    assert_eq!(status, LlmReviewStatus::ErrNullArgument);
+
+    // Options counted but missing, or with a NULL among them
+    let status = unsafe { llm_review_build_prompt(diff.as_ptr(), ptr::null(), 1, &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrNullArgument);
+    let argv = [c"--review-length".as_ptr(), ptr::null()];
+    let status = unsafe { llm_review_build_prompt(diff.as_ptr(), argv.as_ptr(), argv.len(), &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrNullArgument);
+    assert!(out.is_null());
+
+    let argv = [c"--hard-max-chars".as_ptr(), c"0".as_ptr()];
+    let status = unsafe { llm_review_build_prompt(diff.as_ptr(), argv.as_ptr(), argv.len(), &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrInvalidOptions);
+    assert!(out.is_null());
+
+    let argv = [c"--rules-file".as_ptr(), c"/nonexistent/rules.txt".as_ptr()];
+    let status = unsafe { llm_review_build_prompt(diff.as_ptr(), argv.as_ptr(), argv.len(), &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrPrompt);
+    assert!(out.is_null());
+
+    let invalid_utf8 = CString::new(vec![0x66, 0xff, 0x6f]).unwrap();
+    let status = unsafe { llm_review_build_prompt(invalid_utf8.as_ptr(), ptr::null(), 0, &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrInvalidUtf8);
+    let argv = [invalid_utf8.as_ptr()];
+    let status = unsafe { llm_review_build_prompt(diff.as_ptr(), argv.as_ptr(), argv.len(), &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrInvalidUtf8);
+
+    let status = unsafe { llm_review_run(diff.as_ptr(), ptr::null(), 0, None, ptr::null_mut(), &mut out) };
+    assert_eq!(status, LlmReviewStatus::ErrNullArgument);
+    assert!(out.is_null());
+}
+
+#[test]
+fn test_provider_failure() {
+    let mut conversation = Conversation { prompt: None, review: None };
+    let mut out = ptr::null_mut();
+    let status = unsafe {
+        llm_review_run(
+            diff().as_ptr(),
+            ptr::null(),
+            0,
+            Some(recording_provider),
+            &mut conversation as *mut Conversation as *mut c_void,
+            &mut out,
+        )
+    };
+    assert_eq!(status, LlmReviewStatus::ErrProvider);
+    assert!(out.is_null());
+    assert!(conversation.prompt.is_some());
+}
+
+#[test]
+fn test_free_null_is_a_no_op() {
+    unsafe { llm_review_free_string(ptr::null_mut()) };
+}
+
+#[test]
+fn test_header_is_current() {
+    // Regenerate with: cbindgen --config cbindgen.toml --output include/llm_code_review.h
+    let header = fs::read_to_string("include/llm_code_review.h").unwrap();
+    for name in ["llm_review_build_prompt", "llm_review_run", "llm_review_free_string", "LlmReviewProvider"] {
+        assert!(header.contains(name), "{} is missing from the header", name);
+    }
+    assert!(header.contains("LLM_REVIEW_STATUS_ERR_INTERNAL = 7"));
+    assert!(header.contains("const char *const *argv,\n") && header.contains("size_t argc,\n"));
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::files::{full_file_candidates, full_files, full_files_section, pack_files, FullFile, FULL_FILES_INTRO};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const DIFF: &str = "diff --git a/cart.rs b/cart.rs\nindex 1111111..2222222 100644\n--- a/cart.rs\n+++ b/cart.rs\n\
+@@ -1 +1 @@\n-    1\n+    2\n\
+diff --git a/old.rs b/old.rs\ndeleted file mode 100644\nindex 3333333..0000000\n--- a/old.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-fn old() {}\n\
+diff --git a/logo.png b/logo.png\nindex 4444444..5555555 100644\nBinary files a/logo.png and b/logo.png differ\n\
+diff --git a/tax.rs b/tax.rs\n--- a/tax.rs\n+++ b/tax.rs\n@@ -1 +1 @@\n-    0\n+    1\n";
+
+fn file(path: &str, contents: &str) -> FullFile {
+    FullFile { path: path.to_string(), contents: contents.to_string() }
+}
+
+#[test]
+fn test_full_files() {
+    let files = parse_diff(DIFF);
+    // Deleted and binary files have no new version to show
+    assert_eq!(full_file_candidates(&files), [("cart.rs", Some("2222222")), ("tax.rs", None)]);
+
+    let read = |id: Option<&str>, path: &str| match (id, path) {
+        (Some("2222222"), "cart.rs") => Some("fn total() -> u32 {\n    2\n}\n".to_string()),
+        (None, "tax.rs") => Some("fn tax() -> u32 {\n    1\n}\n".to_string()),
+        _ => None,
+    };
+    assert_eq!(
+        full_files(&files, 10, read),
+        [file("cart.rs", "fn total() -> u32 {\n    2\n}\n"), file("tax.rs", "fn tax() -> u32 {\n    1\n}\n")]
+    );
+    // Files longer than the cap, binary after all, or unreadable are left out
+    assert!(full_files(&files, 2, read).is_empty());
+    let binary = |_: Option<&str>, path: &str| (path == "cart.rs").then(|| "\u{89}PNG\0".to_string());
+    assert!(full_files(&files, 10, binary).is_empty());
+}
+
+#[test]
+fn test_pack_files() {
+    let files = vec![file("big.rs", &"x".repeat(50)), file("small.rs", &"x".repeat(10)), file("medium.rs", &"x".repeat(30))];
+    let cost = |file: &FullFile| file.contents.len();
+    // The smallest go first, and each group keeps the diff's order
+    let (included, skipped) = pack_files(files.clone(), 45, cost);
+    assert_eq!(included.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), ["small.rs", "medium.rs"]);
+    assert_eq!(skipped.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), ["big.rs"]);
+    let (included, skipped) = pack_files(files.clone(), 90, cost);
+    assert_eq!(included, files);
+    assert!(skipped.is_empty());
+    let (included, skipped) = pack_files(files, 5, cost);
+    assert!(included.is_empty() && skipped.len() == 3);
+
+    assert_eq!(full_files_section(&[]), None);
+    assert_eq!(
+        full_files_section(&[file("cart.rs", "fn total() {}\n"), file("notes", "Check the tax.")]).unwrap(),
+        format!("{}\n\n### cart.rs\n```rust\nfn total() {{}}\n```\n\n### notes\n```\nCheck the tax.\n```\n", FULL_FILES_INTRO)
+    );
+}
+
+/// A repository with a commit changing a short file and a long one, and a change to the short one
+/// not yet committed
+fn repository() -> PathBuf {
+    let dir = temp_dir("files");
+    init_repo(&dir);
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    1\n}\n").unwrap();
+    let rates: String = (1..=300).map(|n| format!("const RATE_{0}: u32 = {0};\n", n)).collect();
+    fs::write(dir.join("rates.rs"), &rates).unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Start"]);
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    2\n}\n").unwrap();
+    fs::write(dir.join("rates.rs"), rates.replace("RATE_1: u32 = 1;", "RATE_1: u32 = 5;")).unwrap();
+    git(&dir, &["commit", "-q", "-am", "Raise the total"]);
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    3\n}\n").unwrap();
+    dir
+}
+
+#[test]
+fn test_full_files_option() {
+    let dir = repository();
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .args(["--no-provenance", "-v"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+    };
+
+    let (ok, prompt) = review(&["HEAD~1..HEAD"]);
+    assert!(ok, "{}", prompt);
+    assert!(!prompt.contains("## Full File Contents"), "{}", prompt);
+
+    // The version the diff ends at, not the working tree's
+    let (ok, prompt) = review(&["--full-files", "HEAD~1..HEAD"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("## Full File Contents\n"), "{}", prompt);
+    assert!(prompt.contains("### cart.rs\n```rust\nfn total() -> u32 {\n    2\n}\n```\n"), "{}", prompt);
+    assert!(prompt.contains("### rates.rs\n```rust\nconst RATE_1: u32 = 5;\n"), "{}", prompt);
+    assert!(prompt.contains("Full files: included cart.rs ("), "{}", prompt);
+
+    // Without a commit to read, the working tree has the new version
+    let (ok, prompt) = review(&["--full-files"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("### cart.rs\n```rust\nfn total() -> u32 {\n    3\n}\n```\n"), "{}", prompt);
+
+    let (ok, prompt) = review(&["--full-files", "--full-file-max-lines", "100", "HEAD~1..HEAD"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("### cart.rs\n") && !prompt.contains("### rates.rs"), "{}", prompt);
+    assert!(prompt.contains("Leaving out rates.rs from the full files: its 300 lines are more than 100"), "{}", prompt);
+
+    // A small budget takes the smaller file and leaves out the one that doesn't fit
+    let (ok, prompt) = review(&["--full-files", "--max-tokens", "2500", "HEAD~1..HEAD"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("### cart.rs\n") && !prompt.contains("### rates.rs"), "{}", prompt);
+    assert!(prompt.contains("Full files: left out rates.rs: its "), "{}", prompt);
+
+    let (ok, output) = review(&["--full-file-max-lines", "100"]);
+    assert!(!ok && output.contains("--full-files"), "{}", output);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
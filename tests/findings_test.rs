@@ -0,0 +1,243 @@
+use llm_code_review::findings::{
+    cap_findings, filter_by_category, filter_by_confidence, merge_findings, parse_json_findings, rank_findings,
+    render_findings, Finding, FindingLimits, JsonFinding, Severity,
+};
+use llm_code_review::locale::Locale;
+use llm_code_review::output::OutputFormat;
+
+fn finding(path: &str, line: Option<usize>, severity: Severity, comment: &str) -> Finding {
+    Finding {
+        path: path.to_string(),
+        line,
+        severity,
+        category: None,
+        comment: comment.to_string(),
+        follow_up: false,
+        policy_note: None,
+        confidence: None,
+        extracted: false,
+        lower_priority: false,
+    }
+}
+
+// Deliberately out of order, to show rendering sorts them
+fn findings() -> Vec<Finding> {
+    vec![
+        finding("src/main.rs", Some(40), Severity::Info, "Consider naming this constant."),
+        finding("src/lib.rs", Some(12), Severity::Warning, "This error is silently ignored."),
+        finding("src/main.rs", Some(7), Severity::Error, "This unwrap panics on empty input."),
+        finding("src/lib.rs", None, Severity::Info, "The module has no doc comment."),
+        finding("src/main.rs", Some(7), Severity::Warning, "The loop re-reads the file each time."),
+    ]
+}
+
+#[test]
+fn test_render_findings_markdown() {
+    assert_eq!(
+        render_findings(&findings(), OutputFormat::Markdown, Locale::En),
+        include_str!("fixtures/findings.md")
+    );
+}
+
+#[test]
+fn test_render_findings_asciidoc() {
+    assert_eq!(
+        render_findings(&findings(), OutputFormat::AsciiDoc, Locale::En),
+        include_str!("fixtures/findings.adoc")
+    );
+}
+
+#[test]
+fn test_render_findings_mediawiki() {
+    assert_eq!(
+        render_findings(&findings(), OutputFormat::MediaWiki, Locale::En),
+        include_str!("fixtures/findings.wiki")
+    );
+}
+
+#[test]
+fn test_render_findings_html() {
+    assert_eq!(render_findings(&findings(), OutputFormat::Html, Locale::En), include_str!("fixtures/findings.html"));
+    let findings = [finding("src/<gen>.rs", Some(1), Severity::Warning, "Use `a && b`.")];
+    assert!(render_findings(&findings, OutputFormat::Html, Locale::En)
+        .contains("<code>src/&lt;gen&gt;.rs</code></h3>\n\n<ul>\n<li><strong>warning</strong> (line 1): Use `a &amp;&amp; b`.</li>"));
+}
+
+#[test]
+fn test_render_findings_jira() {
+    assert_eq!(render_findings(&findings(), OutputFormat::Jira, Locale::En), include_str!("fixtures/findings.jira"));
+}
+
+#[test]
+fn test_filter_by_category() {
+    let mut findings = findings();
+    findings[0].category = Some("readability".to_string());
+    findings[2].category = Some("error-handling".to_string());
+
+    assert_eq!(filter_by_category(findings.clone(), &[]).len(), 5);
+    let filtered = filter_by_category(findings, &["error-handling".to_string()]);
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].comment, "This unwrap panics on empty input.");
+}
+
+#[test]
+fn test_parse_json_findings() {
+    let expected = vec![JsonFinding {
+        file: "src/main.rs".to_string(),
+        line: Some(7),
+        severity: Severity::Error,
+        comment: "This unwrap panics on empty input.".to_string(),
+        confidence: None,
+        extracted: false,
+        lower_priority: false,
+    }];
+    let review = r#"[{"file": "src/main.rs", "line": 7, "severity": "error", "comment": "This unwrap panics on empty input."}]"#;
+    assert_eq!(parse_json_findings(review), Ok(expected.clone()));
+    let fenced = "```json\n[{\"path\": \"src/main.rs\", \"line\": 7, \"severity\": \"error\", \"comment\": \"This unwrap panics on empty input.\"}]\n```";
+    assert_eq!(parse_json_findings(fenced), Ok(expected));
+    assert_eq!(parse_json_findings("[]"), Ok(Vec::new()));
+    assert!(parse_json_findings("No issues found.").is_err());
+    assert!(parse_json_findings(r#"[{"file": "a.rs", "severity": "fatal", "comment": "?"}]"#).is_err());
+
+    let sure = r#"[{"file": "a.rs", "line": 1, "severity": "info", "comment": "Fine.", "confidence": 0.8}]"#;
+    assert_eq!(parse_json_findings(sure).unwrap()[0].confidence, Some(0.8));
+    let over = r#"[{"file": "a.rs", "line": 1, "severity": "info", "comment": "Fine.", "confidence": 80}]"#;
+    assert!(parse_json_findings(over).unwrap_err().contains("not between 0 and 1"));
+}
+
+#[test]
+fn test_confidence() {
+    let mut findings = findings();
+    findings[1].confidence = Some(0.3);
+    findings[2].confidence = Some(0.95);
+
+    // Unsure findings are listed apart, in every format, and left out of the files' counts
+    let markdown = render_findings(&findings, OutputFormat::Markdown, Locale::En);
+    let (sure, unsure) = markdown.split_once("### Possibly wrong, please verify\n\n").unwrap();
+    assert!(!sure.contains("silently ignored") && sure.contains("| [`src/lib.rs`](#findings-src-lib-rs) | 0 | 0 | 1 |"), "{}", sure);
+    assert_eq!(unsure, "- **warning** `src/lib.rs` (line 12): This error is silently ignored. _(confidence 0.30)_\n");
+    assert!(render_findings(&findings, OutputFormat::AsciiDoc, Locale::En).contains("=== Possibly wrong, please verify\n\n* *warning* `src/lib.rs`"));
+    assert!(render_findings(&findings, OutputFormat::MediaWiki, Locale::En).contains("=== Possibly wrong, please verify ===\n\n* '''warning''' <code>src/lib.rs</code>"));
+    let json: serde_json::Value = serde_json::from_str(&render_findings(&findings[1..3], OutputFormat::Json, Locale::En)).unwrap();
+    assert_eq!((json[0]["confidence"].as_f64(), json[1]["confidence"].as_f64()), (Some(0.3), Some(0.95)));
+
+    // Findings without a confidence are kept
+    let kept = filter_by_confidence(findings, 0.5);
+    assert_eq!(kept.len(), 4);
+    assert!(kept.iter().all(|finding| finding.comment != "This error is silently ignored."));
+}
+
+#[test]
+fn test_merge_findings() {
+    let mut repeated = finding("src/main.rs", Some(7), Severity::Error, "This unwrap panics  on empty input.");
+    repeated.confidence = Some(0.9);
+    let mut findings = findings();
+    findings[2].confidence = Some(0.4);
+    findings[2].severity = Severity::Warning;
+    findings.push(repeated);
+
+    // The repeat is merged into the first, taking the higher severity and confidence
+    let merged = merge_findings(findings);
+    assert_eq!(merged.len(), 5);
+    assert_eq!((merged[2].severity, merged[2].confidence), (Severity::Error, Some(0.9)));
+    assert_eq!(merged[2].comment, "This unwrap panics on empty input.");
+}
+
+#[test]
+fn test_render_findings_json() {
+    let rendered: serde_json::Value = serde_json::from_str(&render_findings(&findings()[..2], OutputFormat::Json, Locale::En)).unwrap();
+    assert_eq!(
+        rendered,
+        serde_json::json!([
+            {"file": "src/main.rs", "line": 40, "severity": "info", "comment": "Consider naming this constant."},
+            {"file": "src/lib.rs", "line": 12, "severity": "warning", "comment": "This error is silently ignored."},
+        ])
+    );
+}
+
+#[test]
+fn test_rank_findings() {
+    let mut findings = findings();
+    findings[1].confidence = Some(0.4);
+    findings.push(Finding { confidence: Some(0.9), ..finding("src/lib.rs", Some(3), Severity::Warning, "Racy.") });
+    findings.push(Finding { follow_up: true, ..finding("src/a.rs", Some(1), Severity::Warning, "Later.") });
+    let ranked: Vec<String> = rank_findings(findings.clone()).into_iter().map(|finding| finding.comment).collect();
+    assert_eq!(
+        ranked,
+        vec![
+            "This unwrap panics on empty input.",
+            // No confidence counts as certain, then ties go by path and line
+            "The loop re-reads the file each time.",
+            "Racy.",
+            "This error is silently ignored.",
+            "Later.",
+            "The module has no doc comment.",
+            "Consider naming this constant.",
+        ]
+    );
+    // The order the findings came in makes no difference
+    findings.reverse();
+    let again: Vec<String> = rank_findings(findings).into_iter().map(|finding| finding.comment).collect();
+    assert_eq!(again, ranked);
+}
+
+#[test]
+fn test_cap_findings() {
+    let unlimited = cap_findings(findings(), &FindingLimits::default());
+    assert_eq!(unlimited, findings());
+
+    let capped = cap_findings(findings(), &FindingLimits { per_file: Some(1), total: None });
+    let summary: Vec<(&str, Option<usize>, bool)> =
+        capped.iter().map(|finding| (finding.path.as_str(), finding.line, finding.lower_priority)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("src/main.rs", Some(7), false),
+            ("src/lib.rs", Some(12), false),
+            ("src/main.rs", Some(7), true),
+            ("src/lib.rs", None, true),
+            ("src/main.rs", Some(40), true),
+        ]
+    );
+    let capped = cap_findings(findings(), &FindingLimits { per_file: Some(2), total: Some(3) });
+    assert_eq!(capped.iter().filter(|finding| !finding.lower_priority).count(), 3);
+    assert_eq!(capped[2].comment, "The loop re-reads the file each time.");
+    assert!(capped[3..].iter().all(|finding| finding.lower_priority));
+}
+
+#[test]
+fn test_render_lower_priority() {
+    let capped = cap_findings(findings(), &FindingLimits { per_file: None, total: Some(2) });
+    let markdown = render_findings(&capped, OutputFormat::Markdown, Locale::En);
+    let (shown, appendix) = markdown.split_once("<details>").unwrap();
+    assert!(shown.contains("This unwrap panics") && !shown.contains("Consider naming"), "{}", markdown);
+    assert_eq!(
+        appendix,
+        "\n<summary>Additional lower-priority notes (3)</summary>\n\n\
+         - **warning** `src/main.rs` (line 7): The loop re-reads the file each time.\n\
+         - **info** `src/lib.rs`: The module has no doc comment.\n\
+         - **info** `src/main.rs` (line 40): Consider naming this constant.\n\n</details>\n"
+    );
+    let asciidoc = render_findings(&capped, OutputFormat::AsciiDoc, Locale::En);
+    assert!(
+        asciidoc.contains("\n.Additional lower-priority notes (3)\n[%collapsible]\n====\n* *warning* `src/main.rs`"),
+        "{}",
+        asciidoc
+    );
+    let mediawiki = render_findings(&capped, OutputFormat::MediaWiki, Locale::En);
+    assert!(
+        mediawiki.contains("<div class=\"mw-collapsible mw-collapsed\">\n'''Additional lower-priority notes (3)'''"),
+        "{}",
+        mediawiki
+    );
+    let json: serde_json::Value = serde_json::from_str(&render_findings(&capped, OutputFormat::Json, Locale::En)).unwrap();
+    assert_eq!(json[1].get("lower_priority"), None);
+    assert_eq!(json[2]["lower_priority"], true);
+
+    assert_eq!(FindingLimits::default().instructions(), None);
+    assert_eq!(
+        FindingLimits { per_file: Some(3), total: Some(15) }.instructions().unwrap(),
+        "\nReport at most 3 findings for any one file, and at most 15 in all, so the review stays actionable: \
+         choose the most serious, and leave out the rest.\n"
+    );
+}
@@ -0,0 +1,73 @@
+use llm_code_review::forge_auth::{lookup_token, parse_cli_token, AuthError, CliOutput, ForgeToken};
+use llm_code_review::issues::Forge;
+use std::cell::RefCell;
+use std::io;
+
+fn output(success: bool, stdout: &str, stderr: &str) -> CliOutput {
+    CliOutput { success, stdout: stdout.to_string(), stderr: stderr.to_string() }
+}
+
+#[test]
+fn test_parse_cli_token() {
+    assert_eq!(parse_cli_token("gh", "github.com", &output(true, "gho_abc123\n", "")), Ok("gho_abc123".to_string()));
+
+    let not_logged_in = AuthError::NotLoggedIn { cli: "gh", host: "github.com".to_string() };
+    let logged_out = output(false, "", "no oauth token found for github.com\n");
+    assert_eq!(parse_cli_token("gh", "github.com", &logged_out), Err(not_logged_in.clone()));
+    let never_logged_in = output(false, "", "You are not logged into any GitHub hosts. Run gh auth login to authenticate.\n");
+    assert_eq!(parse_cli_token("gh", "github.com", &never_logged_in), Err(not_logged_in));
+    // glab prints nothing for a host it has no token for
+    assert_eq!(
+        parse_cli_token("glab", "gitlab.com", &output(true, "\n", "")),
+        Err(AuthError::NotLoggedIn { cli: "glab", host: "gitlab.com".to_string() })
+    );
+
+    // gh before 2.17 has no `auth token`
+    let old = output(false, "", "unknown command \"token\" for \"gh auth\"\n\nUsage:  gh auth <command> [flags]\n");
+    let error = parse_cli_token("gh", "github.com", &old).unwrap_err();
+    assert_eq!(error, AuthError::Unsupported { cli: "gh", detail: "unknown command \"token\" for \"gh auth\"".to_string() });
+    assert_eq!(
+        error.to_string(),
+        "gh is too old to print its token (unknown command \"token\" for \"gh auth\"); upgrade it or set GITHUB_TOKEN"
+    );
+    assert!(matches!(
+        parse_cli_token("gh", "github.com", &output(true, "Usage: gh auth <command>\n", "")),
+        Err(AuthError::Unsupported { .. })
+    ));
+
+    assert_eq!(
+        parse_cli_token("gh", "github.com", &output(false, "", "HTTP 502\n")),
+        Err(AuthError::Failed { cli: "gh", detail: "HTTP 502".to_string() })
+    );
+}
+
+#[test]
+fn test_lookup_token() {
+    let runs = RefCell::new(Vec::new());
+    let run = |cli: &str, args: &[&str]| {
+        runs.borrow_mut().push(format!("{} {}", cli, args.join(" ")));
+        Some(Ok(output(true, "glpat-xyz\n", "")))
+    };
+
+    // The environment comes first, and the tool isn't run
+    let from_env = lookup_token(Forge::GitHub, "github.com", |_| Some("ghp_env".to_string()), run).unwrap();
+    assert_eq!(from_env, ForgeToken { token: "ghp_env".to_string(), source: "GITHUB_TOKEN".to_string() });
+    assert!(runs.borrow().is_empty());
+
+    // An empty variable is as good as unset
+    let from_cli = lookup_token(Forge::GitLab, "gitlab.example.com", |_| Some(String::new()), run).unwrap();
+    assert_eq!(from_cli, ForgeToken { token: "glpat-xyz".to_string(), source: "glab config get token".to_string() });
+    assert_eq!(*runs.borrow(), vec!["glab config get token --host gitlab.example.com"]);
+
+    let from_gh = lookup_token(Forge::GitHub, "ghe.example.com", |_| None, run).unwrap();
+    assert_eq!(from_gh.source, "gh auth token");
+    assert_eq!(runs.borrow()[1], "gh auth token --hostname ghe.example.com");
+
+    let missing = lookup_token(Forge::GitHub, "github.com", |_| None, |_, _| None).unwrap_err();
+    assert_eq!(missing, AuthError::Missing { var: "GITHUB_TOKEN", cli: "gh" });
+    assert_eq!(missing.to_string(), "set GITHUB_TOKEN or log in with `gh auth login`");
+    let broken = lookup_token(Forge::GitHub, "github.com", |_| None, |_, _| {
+        Some(Err(io::Error::new(io::ErrorKind::PermissionDenied, "permission denied")))
+    });
+    assert_eq!(broken, Err(AuthError::Failed { cli: "gh", detail: "permission denied".to_string() }));
+}
@@ -0,0 +1,82 @@
+use llm_code_review::github::{load_pr_event, parse_github_url, GitHubTarget, GitHubUrl};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn test_parse_compare_url() {
+    let url = parse_github_url("https://github.com/org/repo/compare/v1.2.0...main").unwrap();
+    assert_eq!(
+        url,
+        GitHubUrl {
+            owner: "org".to_string(),
+            repo: "repo".to_string(),
+            target: GitHubTarget::Compare {
+                base: "v1.2.0".to_string(),
+                head: "main".to_string(),
+            },
+        }
+    );
+    assert_eq!(
+        url.describe(),
+        "GitHub repository org/repo, comparing `v1.2.0` to `main`"
+    );
+}
+
+#[test]
+fn test_parse_commit_url() {
+    let url = parse_github_url("https://github.com/org/repo/commit/abc123.diff").unwrap();
+    assert_eq!(
+        url.target,
+        GitHubTarget::Commit {
+            sha: "abc123".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_parse_non_github_args() {
+    assert!(parse_github_url("main..feature-branch").is_none());
+    assert!(parse_github_url("https://github.com/org/repo/pull/12").is_none());
+    assert!(parse_github_url("https://github.com/org/repo/compare/main").is_none());
+}
+
+#[test]
+fn test_load_pr_event() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/github_pull_request_event.json");
+    let pr = load_pr_event(&path).unwrap();
+
+    assert_eq!(pr.title, "Retry token refresh on 503");
+    assert_eq!(pr.base_ref, "main");
+    assert_eq!(pr.head_ref, "retry-refresh");
+    assert_eq!(pr.head_sha.as_deref(), Some("e5bd3914e2e596debea16f433f57875b5b90bcd6"));
+    // Only synchronize events say what the head was before the push
+    assert_eq!(pr.before_sha, None);
+
+    let sections = pr.sections();
+    assert_eq!(
+        sections[0],
+        (
+            "Pull Request",
+            "Title: Retry token refresh on 503\nBase branch: main\nHead branch: retry-refresh"
+                .to_string()
+        )
+    );
+    assert_eq!(sections[1].0, "Pull Request Description");
+    assert!(sections[1].1.ends_with("up to three times."));
+}
+
+#[test]
+fn test_load_pr_event_without_body() {
+    let path = std::env::temp_dir().join(format!("llm_code_review_event_{}.json", std::process::id()));
+    fs::write(
+        &path,
+        r#"{"pull_request": {"title": "T", "body": null, "base": {"ref": "main"}, "head": {"ref": "dev"}}}"#,
+    )
+    .unwrap();
+
+    let pr = load_pr_event(&path).unwrap();
+    assert_eq!(pr.body, None);
+    assert_eq!(pr.sections().len(), 1);
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::history::{load_reviews, related_excerpts};
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_related_excerpts_by_overlap_and_recency() {
+    let dir = temp_dir("history");
+
+    let write = |name: &str, text: &str, age_secs: u64| {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .unwrap();
+    };
+    write("old.md", "# Review\n\nIn src/auth.rs the token check is racy.\n\nUnrelated note.", 300);
+    write("new.md", "# Review\n\nsrc/auth.rs: prefer constant time comparison.", 100);
+    write("both.md", "Both src/auth.rs and src/db.rs need error handling.", 200);
+    write("other.md", "Only talks about src/ui.rs.", 0);
+
+    let reviews = load_reviews(&dir).unwrap();
+    let names: Vec<String> = reviews
+        .iter()
+        .map(|r| r.path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, ["other.md", "new.md", "both.md", "old.md"]);
+
+    let changed = vec!["src/auth.rs".to_string(), "src/db.rs".to_string()];
+    let excerpts = related_excerpts(&reviews, &changed, 10_000);
+    assert_eq!(
+        excerpts,
+        "### both.md\nBoth src/auth.rs and src/db.rs need error handling.\n\n\
+         ### new.md\nsrc/auth.rs: prefer constant time comparison.\n\n\
+         ### old.md\nIn src/auth.rs the token check is racy."
+    );
+
+    // Only whole excerpts are included when the budget runs out
+    let excerpts = related_excerpts(&reviews, &changed, 70);
+    assert_eq!(
+        excerpts,
+        "### both.md\nBoth src/auth.rs and src/db.rs need error handling."
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::findings::Severity;
+use llm_code_review::infra::{check_infra, find_infra, infra_kind, infrastructure_section, InfraKind};
+use llm_code_review::severity_policy::glob_regex;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+#[test]
+fn test_infra_kind() {
+    let kind = |path: &str| infra_kind(path, &[]);
+    assert_eq!(kind(".github/workflows/ci.yml"), Some(InfraKind::Ci));
+    assert_eq!(kind(".github/actions/setup/action.yaml"), Some(InfraKind::Ci));
+    assert_eq!(kind(".gitlab-ci.yml"), Some(InfraKind::Ci));
+    assert_eq!(kind(".circleci/config.yml"), Some(InfraKind::Ci));
+    assert_eq!(kind("Jenkinsfile"), Some(InfraKind::Ci));
+    assert_eq!(kind("Dockerfile"), Some(InfraKind::Container));
+    assert_eq!(kind("services/api/Dockerfile.prod"), Some(InfraKind::Container));
+    assert_eq!(kind("docker/worker.dockerfile"), Some(InfraKind::Container));
+    assert_eq!(kind("docker-compose.override.yml"), Some(InfraKind::Container));
+    assert_eq!(kind("Makefile"), Some(InfraKind::Make));
+    assert_eq!(kind("tools/rules.mk"), Some(InfraKind::Make));
+    assert_eq!(kind("build.rs"), Some(InfraKind::BuildScript));
+    assert_eq!(kind("scripts/release.sh"), Some(InfraKind::BuildScript));
+    assert_eq!(kind("app/build.gradle.kts"), Some(InfraKind::BuildScript));
+
+    assert_eq!(kind("src/main.rs"), None);
+    assert_eq!(kind("docs/workflows/ci.yml"), None);
+    assert_eq!(kind("src/scripts/helper.py"), None);
+    assert_eq!(kind("config/settings.yml"), None);
+
+    let configured = [glob_regex("deploy/*.tf")];
+    assert_eq!(infra_kind("deploy/main.tf", &configured), Some(InfraKind::Configured));
+    assert_eq!(infra_kind("Dockerfile", &configured), Some(InfraKind::Container));
+}
+
+const WORKFLOW_DIFF: &str = "\
+diff --git a/.github/workflows/ci.yml b/.github/workflows/ci.yml
+--- a/.github/workflows/ci.yml
++++ b/.github/workflows/ci.yml
+@@ -5,4 +5,14 @@ jobs:
+     steps:
+-      - uses: actions/checkout@v3
++      - uses: actions/checkout@v4
++      - uses: actions/setup-node@1d0ff469b7ec7b3cb9d8673fde0c81c44821de2a # v4.2.0
++      - uses: ./.github/actions/setup
++      # - uses: some/action@main
++      - run: curl -fsSL https://example.com/install.sh | sudo bash
++      - run: echo \"Deploying with ${{ secrets.DEPLOY_TOKEN }}\"
++      - run: echo \"::add-mask::${{ secrets.DEPLOY_TOKEN }}\"
++      - run: echo \"$NPM_TOKEN\" | npm login
++      - run: echo \"Token is $NPM_TOKEN\"
+       - run: make test
+       - run: make lint
+diff --git a/src/install.sh b/src/install.sh
+--- a/src/install.sh
++++ b/src/install.sh
+@@ -1 +1,2 @@
+ #!/bin/sh
++curl https://example.com/x.sh | sh
+";
+
+#[test]
+fn test_check_infra() {
+    let files = parse_diff(WORKFLOW_DIFF);
+    let infra = find_infra(&files, &[]);
+    assert_eq!(infra, vec![(".github/workflows/ci.yml", InfraKind::Ci)]);
+
+    let findings = check_infra(&files, &infra, &[]);
+    let found: Vec<(usize, Severity, &str)> = findings
+        .iter()
+        .map(|finding| (finding.line.unwrap(), finding.severity, finding.comment.rsplit('`').nth(1).unwrap()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (6, Severity::Warning, "unpinned-action"),
+            (10, Severity::Warning, "pipe-to-shell"),
+            (11, Severity::Error, "secret-echo"),
+            (14, Severity::Error, "secret-echo"),
+        ]
+    );
+    assert!(findings[0].comment.starts_with("`actions/checkout@v4` isn't pinned to a full commit SHA"), "{}", findings[0].comment);
+    assert!(findings.iter().all(|finding| finding.path == ".github/workflows/ci.yml"));
+    assert!(findings.iter().all(|finding| finding.category.as_deref() == Some("infrastructure")));
+
+    let skipped = check_infra(&files, &infra, &["secret-echo".to_string(), "unpinned-action".to_string()]);
+    assert_eq!(skipped.len(), 1);
+    assert!(skipped[0].comment.ends_with("(found by the local `pipe-to-shell` check)"), "{}", skipped[0].comment);
+
+    let section = infrastructure_section(&infra, &findings);
+    assert!(section.contains("\n- .github/workflows/ci.yml (CI pipeline)\n"), "{}", section);
+    assert!(section.contains("Label these findings with the category `infrastructure`."), "{}", section);
+    assert!(section.contains("already report these, so don't repeat them:\n- .github/workflows/ci.yml:6: `actions/checkout@v4`"), "{}", section);
+}
+
+/// A repository whose uncommitted change adds an unpinned action to a workflow
+fn repository(test: &str) -> PathBuf {
+    let dir = temp_dir(test);
+    init_repo(&dir);
+    fs::create_dir_all(dir.join(".github/workflows")).unwrap();
+    fs::write(dir.join(".github/workflows/ci.yml"), "on: push\njobs:\n  test:\n    steps:\n      - run: make test\n").unwrap();
+    fs::write(dir.join("deploy.tf"), "resource \"x\" \"y\" {}\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Start"]);
+    fs::write(
+        dir.join(".github/workflows/ci.yml"),
+        "on: push\njobs:\n  test:\n    steps:\n      - uses: actions/checkout@main\n      - run: make test\n",
+    )
+    .unwrap();
+    fs::write(dir.join("deploy.tf"), "resource \"x\" \"z\" {}\n").unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .args(args)
+        .output()
+        .expect("failed to run llm_code_review");
+    (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+}
+
+#[test]
+fn test_infrastructure_section_in_prompt() {
+    let dir = repository("prompt");
+    let (success, output) = run(&dir, &[]);
+    assert!(success, "{}", output);
+    assert!(output.contains("## Infrastructure Changes\n"), "{}", output);
+    assert!(output.contains("- .github/workflows/ci.yml (CI pipeline)"), "{}", output);
+    assert!(output.contains(".github/workflows/ci.yml:5: `actions/checkout@main` isn't pinned"), "{}", output);
+    assert!(!output.contains("deploy.tf (infrastructure file)"), "{}", output);
+
+    let (success, output) = run(&dir, &["--infra-files", "*.tf", "--skip-infra-check", "unpinned-action"]);
+    assert!(success, "{}", output);
+    assert!(output.contains("- deploy.tf (infrastructure file)"), "{}", output);
+    assert!(!output.contains("isn't pinned"), "{}", output);
+
+    // The repository's config file can add globs and turn checks off too
+    fs::write(dir.join(".llm_code_review.toml"), "infra_files = [\"*.tf\"]\nskip_infra_checks = [\"unpinned-action\"]\n").unwrap();
+    let (success, output) = run(&dir, &[]);
+    assert!(success, "{}", output);
+    assert!(output.contains("- deploy.tf (infrastructure file)") && !output.contains("isn't pinned"), "{}", output);
+    fs::write(dir.join(".llm_code_review.toml"), "skip_infra_checks = [\"lint\"]\n").unwrap();
+    let (success, output) = run(&dir, &[]);
+    assert!(!success);
+    assert!(output.contains("skip_infra_checks: `lint` is not one of unpinned-action"), "{}", output);
+    fs::remove_file(dir.join(".llm_code_review.toml")).unwrap();
+
+    let (success, output) = run(&dir, &["--no-infra-review"]);
+    assert!(success, "{}", output);
+    assert!(!output.contains("## Infrastructure Changes"), "{}", output);
+
+    let (success, output) = run(&dir, &["--skip-infra-check", "yaml-lint"]);
+    assert!(!success);
+    assert!(output.contains("unknown check `yaml-lint`; the checks are unpinned-action, pipe-to-shell, secret-echo"), "{}", output);
+}
+
+/// Answer one request with a message whose text is `text`
+fn serve(listener: TcpListener, text: &str) -> thread::JoinHandle<()> {
+    let response = serde_json::json!({"content": [{"type": "text", "text": text}]}).to_string();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        let reply = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.len(),
+            response
+        );
+        reader.get_mut().write_all(reply.as_bytes()).unwrap();
+    })
+}
+
+#[test]
+fn test_local_findings_merged_with_the_models() {
+    let dir = repository("send");
+    let send = |args: &[&str], text: &str| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        fs::write(
+            dir.join("providers.toml"),
+            format!(
+                "[[provider]]\nname = \"mock\"\nendpoint = \"http://{}/v1/messages\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n",
+                listener.local_addr().unwrap()
+            ),
+        )
+        .unwrap();
+        let server = serve(listener, text);
+        let send = ["--llm-provider-config", "providers.toml", "--model", "claude-opus-4-1", "--send", "--heartbeat-secs", "0"];
+        let (success, output) = run(&dir, &[&send[..], args].concat());
+        // Nothing connects to the server when the run fails before sending
+        assert!(success, "{}", output);
+        server.join().unwrap();
+        output
+    };
+
+    let model = r#"[{"file": ".github/workflows/ci.yml", "line": 6, "severity": "info", "comment": "Consider caching."}]"#;
+    let output = send(&["--output-format", "json"], model);
+    let start = output.find("[\n").unwrap();
+    let findings: serde_json::Value = serde_json::from_str(&output[start..output.rfind(']').unwrap() + 1]).unwrap();
+    assert_eq!(findings.as_array().unwrap().len(), 2, "{}", output);
+    assert_eq!(findings[1]["line"], 5);
+    assert_eq!(findings[1]["severity"], "warning");
+    assert!(findings[1]["comment"].as_str().unwrap().ends_with("(found by the local `unpinned-action` check)"), "{}", output);
+
+    let output = send(&[], "The workflow change looks fine.");
+    assert!(
+        output.contains("The workflow change looks fine.\n\n## Local Checks\n\n- **warning** `.github/workflows/ci.yml:5`: `actions/checkout@main`"),
+        "{}",
+        output
+    );
+}
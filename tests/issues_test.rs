@@ -0,0 +1,104 @@
+use llm_code_review::issues::{
+    linked_issues_section, parse_branch_refs, parse_github_issue, parse_gitlab_issue, parse_issue_refs,
+    parse_jira_issue, parse_remote_url, Forge, IssueRef, LinkedIssue, Remote,
+};
+use serde_json::json;
+
+#[test]
+fn test_commit_message_refs() {
+    let message = "Fix timeouts in the uploader (#482)\n\n\
+        Fixes GH-17 and acme/infra#9, see also PAY-1203.\n\
+        Follow-up to #482; don't confuse with &#39; or https://example.com/page#12 or issue#3.";
+    assert_eq!(
+        parse_issue_refs(message),
+        vec![
+            IssueRef::Local(482),
+            IssueRef::Local(17),
+            IssueRef::Project { project: "acme/infra".to_string(), number: 9 },
+            IssueRef::Jira("PAY-1203".to_string()),
+        ]
+    );
+    assert!(parse_issue_refs("Bump version to 1.2.3\n\nNo issue.").is_empty());
+    assert_eq!(IssueRef::Project { project: "acme/infra".to_string(), number: 9 }.to_string(), "acme/infra#9");
+}
+
+#[test]
+fn test_branch_refs() {
+    assert_eq!(parse_branch_refs("482-fix-timeouts"), vec![IssueRef::Local(482)]);
+    assert_eq!(parse_branch_refs("fix/issue-482"), vec![IssueRef::Local(482)]);
+    assert_eq!(parse_branch_refs("feature/gh-17"), vec![IssueRef::Local(17)]);
+    assert_eq!(parse_branch_refs("feature/PAY-1203-retry"), vec![IssueRef::Jira("PAY-1203".to_string())]);
+    assert!(parse_branch_refs("feature/pay-1203-retry").is_empty());
+    assert!(parse_branch_refs("main").is_empty());
+    assert!(parse_branch_refs("release-2024").is_empty());
+    assert!(parse_branch_refs("fix-482").is_empty());
+}
+
+#[test]
+fn test_remote_urls() {
+    let github = Remote { forge: Forge::GitHub, host: "github.com".to_string(), project: "acme/app".to_string() };
+    assert_eq!(parse_remote_url("git@github.com:acme/app.git"), Some(github.clone()));
+    assert_eq!(parse_remote_url("https://github.com/acme/app\n"), Some(github.clone()));
+    assert_eq!(parse_remote_url("ssh://git@github.com/acme/app.git"), Some(github));
+
+    let gitlab = parse_remote_url("https://gitlab.example.com:8443/group/sub/app.git").unwrap();
+    assert_eq!(gitlab.forge, Forge::GitLab);
+    assert_eq!(gitlab.host, "gitlab.example.com");
+    assert_eq!(gitlab.project, "group/sub/app");
+
+    assert_eq!(parse_remote_url("/srv/git/app.git"), None);
+}
+
+#[test]
+fn test_tracker_responses() {
+    let github = json!({"number": 482, "title": "Uploads time out", "state": "open", "body": "Large files fail."});
+    assert_eq!(
+        parse_github_issue(&IssueRef::Local(482), &github),
+        Some(LinkedIssue {
+            reference: "#482".to_string(),
+            title: "Uploads time out".to_string(),
+            state: Some("open".to_string()),
+            description: Some("Large files fail.".to_string()),
+        })
+    );
+
+    let gitlab = json!({"iid": 9, "title": "Flaky deploy", "state": "closed", "description": null});
+    let issue = parse_gitlab_issue(&IssueRef::Local(9), &gitlab).unwrap();
+    assert_eq!(issue.state.as_deref(), Some("closed"));
+    assert_eq!(issue.description, None);
+
+    let jira = json!({"key": "PAY-1203", "fields": {
+        "summary": "Retry failed payments",
+        "status": {"name": "In Progress"},
+        "description": "Retry up to three times."
+    }});
+    let issue = parse_jira_issue(&IssueRef::Jira("PAY-1203".to_string()), &jira).unwrap();
+    assert_eq!(issue.reference, "PAY-1203");
+    assert_eq!(issue.state.as_deref(), Some("In Progress"));
+
+    // An error response has no title
+    assert_eq!(parse_github_issue(&IssueRef::Local(1), &json!({"message": "Not Found"})), None);
+}
+
+#[test]
+fn test_section_is_condensed() {
+    let issues = vec![
+        LinkedIssue {
+            reference: "#482".to_string(),
+            title: "Uploads time out ".to_string(),
+            state: Some("open".to_string()),
+            description: Some("Large files\n\nfail after   30 seconds, every time.".to_string()),
+        },
+        LinkedIssue {
+            reference: "PAY-1203".to_string(),
+            title: "Retry failed payments".to_string(),
+            state: None,
+            description: Some(" \n".to_string()),
+        },
+    ];
+    assert_eq!(
+        linked_issues_section(&issues, 30).unwrap(),
+        "- #482 (open): Uploads time out\n  Large files fail after 30 sec…\n- PAY-1203: Retry failed payments"
+    );
+    assert_eq!(linked_issues_section(&[], 30), None);
+}
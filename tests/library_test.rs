@@ -0,0 +1,93 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use clap::Parser;
+use llm_code_review::error::ReviewError;
+use llm_code_review::review::{build_prompt, build_review_prompt, get_git_diff, prepare, run, run_review, Cli};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Instant;
+
+/// The options for `args`, leaving out the user's config, which could also start the setup wizard
+fn cli(args: &[&str]) -> Cli {
+    let mut cli = Cli::parse_from([&["llm_code_review", "--no-provenance", "--no-config"][..], args].concat());
+    prepare(&mut cli, Instant::now()).unwrap();
+    cli
+}
+
+// One test, since it changes the process's working directory
+#[test]
+fn test_build_review_prompt() {
+    let dir = temp_dir("library");
+    init_repo(&dir);
+    fs::write(dir.join("lib.rs"), "fn old() {}\n").unwrap();
+    git(&dir, &["add", "lib.rs"]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+    env::set_current_dir(&dir).unwrap();
+
+    // Errors are returned rather than exiting the process
+    assert!(matches!(build_review_prompt(&cli(&[])), Err(ReviewError::NoChanges)));
+    assert!(matches!(get_git_diff(&[]), Err(ReviewError::NoChanges)));
+    assert!(matches!(build_review_prompt(&cli(&["no-such-revision"])), Err(ReviewError::GitFailed(_))));
+
+    fs::write(dir.join("lib.rs"), "fn new() {}\n").unwrap();
+    assert!(get_git_diff(&[]).unwrap().contains("+fn new() {}"));
+    let prompt = build_review_prompt(&cli(&[])).unwrap();
+    assert!(prompt.contains("-fn old() {}\n+fn new() {}"), "{}", prompt);
+    assert!(matches!(build_review_prompt(&cli(&["--exclude", "*.rs"])), Err(ReviewError::NoChanges)));
+    // A pipeline of its own can add sections
+    let prompt = build_prompt(&cli(&[]), "+fn new() {}\n", &[("Ticket", "SHOP-12".to_string())]).unwrap();
+    assert!(prompt.contains("## Ticket\nSHOP-12"), "{}", prompt);
+
+    // run_review prepares the options itself and returns the prompt, or the review with --send
+    let args = |args: &[&str]| Cli::parse_from([&["llm_code_review", "--no-provenance", "--no-config"][..], args].concat());
+    assert!(run_review(args(&[])).unwrap().contains("-fn old() {}\n+fn new() {}"));
+    assert!(matches!(run_review(args(&["--exclude", "*.rs"])), Err(ReviewError::NoChanges)));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    fs::write(
+        dir.join("providers.toml"),
+        format!(
+            "[[provider]]\nname = \"mock\"\nendpoint = \"http://{}/v1/messages\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n",
+            listener.local_addr().unwrap()
+        ),
+    )
+    .unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        let body = serde_json::json!({"content": [{"type": "text", "text": "Rename `new`: it isn't a constructor."}]}).to_string();
+        let reply = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        reader.get_mut().write_all(reply.as_bytes()).unwrap();
+    });
+    let sent = ["--send", "--llm-provider-config", "providers.toml", "--model", "claude-haiku-4-5", "--heartbeat-secs", "0"];
+    assert_eq!(run_review(args(&sent)).unwrap(), "Rename `new`: it isn't a constructor.");
+    server.join().unwrap();
+    fs::remove_file(dir.join("providers.toml")).unwrap();
+
+    fs::write(dir.join("lib.rs"), "fn new() {}\n".repeat(5_000)).unwrap();
+    let too_large = build_review_prompt(&cli(&["--no-reduce-context", "--max-tokens", "5000"]));
+    assert!(matches!(too_large, Err(ReviewError::DiffTooLarge { .. })), "{:?}", too_large.map(|_| ()));
+
+    // run() returns its errors too, and can be called again once it has set up the logger
+    let output_file = dir.join("prompt.md");
+    assert!(matches!(run(args(&["no-such-revision"])), Err(ReviewError::GitFailed(_))));
+    assert!(matches!(run(args(&["--from-stash", "0"])), Err(ReviewError::NoSuchStash(0))));
+    assert_eq!(run(args(&["--output-file", output_file.to_str().unwrap()])).unwrap(), 0);
+    assert!(fs::read_to_string(&output_file).unwrap().contains("+fn new() {}"));
+
+    env::set_current_dir(env::temp_dir()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
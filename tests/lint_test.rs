@@ -0,0 +1,110 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::lint::{auto_lint_command, lint, lint_section, on_changed_files, parse_diagnostics, run_lint, Diagnostic};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+fn diagnostic(path: &str, line: usize, message: &str) -> Diagnostic {
+    Diagnostic { path: path.to_string(), line, message: message.to_string() }
+}
+
+#[test]
+fn test_parse_diagnostics() {
+    let output = "    Checking cart v0.1.0\n\
+                  src/cart.rs:12:9: warning: unused variable: `total`\n\
+                  /work/repo/web/app.js:3:7: 'x' is assigned a value but never used. [Error/no-unused-vars]\n\
+                  # cart\n\
+                  ./pkg/cart.go:8:2: fmt.Printf format %d has arg s of wrong type string\n\
+                  lib/cart.rb:4: C: Style/StringLiterals: Prefer single-quoted strings\n\
+                  warning: 1 warning emitted\n";
+    assert_eq!(
+        parse_diagnostics(output, Path::new("/work/repo/")),
+        [
+            diagnostic("src/cart.rs", 12, "warning: unused variable: `total`"),
+            diagnostic("web/app.js", 3, "'x' is assigned a value but never used. [Error/no-unused-vars]"),
+            diagnostic("pkg/cart.go", 8, "fmt.Printf format %d has arg s of wrong type string"),
+            diagnostic("lib/cart.rb", 4, "C: Style/StringLiterals: Prefer single-quoted strings"),
+        ]
+    );
+}
+
+#[test]
+fn test_on_changed_files() {
+    let diagnostics = vec![
+        diagnostic("src/cart.rs", 12, "unused"),
+        diagnostic("src/other.rs", 1, "unused"),
+        diagnostic("src/cart.rs", 2, "shadowed"),
+        diagnostic("src/cart.rs", 12, "unused"),
+        // Reported by a linter run in web/
+        diagnostic("app.js", 3, "no-unused-vars"),
+    ];
+    let paths = ["src/cart.rs".to_string(), "web/app.js".to_string()];
+    assert_eq!(
+        on_changed_files(diagnostics, &paths),
+        [diagnostic("app.js", 3, "no-unused-vars"), diagnostic("src/cart.rs", 2, "shadowed"), diagnostic("src/cart.rs", 12, "unused")]
+    );
+}
+
+#[test]
+fn test_lint_section() {
+    assert_eq!(lint_section(&[], 10), None);
+    let diagnostics: Vec<Diagnostic> = (1..=5).map(|line| diagnostic("a.rs", line, "unused")).collect();
+    let section = lint_section(&diagnostics, 3).unwrap();
+    assert!(section.starts_with("The project's linters already report these problems"), "{}", section);
+    assert!(section.contains("Don't repeat them in the review"), "{}", section);
+    assert!(section.ends_with("```\na.rs:1: unused\na.rs:2: unused\na.rs:3: unused\n[… 2 more …]\n```"), "{}", section);
+    assert_eq!(auto_lint_command("Rust"), Some("cargo clippy --quiet --message-format=short"));
+    assert_eq!(auto_lint_command("java"), None);
+}
+
+#[test]
+fn test_run_lint() {
+    let root = Path::new("/");
+    assert_eq!(run_lint("echo a.rs:1:1: unused", Duration::from_secs(10)), Ok((true, "a.rs:1:1: unused\n".to_string())));
+    let started = Instant::now();
+    assert_eq!(run_lint("sleep 10", Duration::from_millis(200)), Err("it took longer than 0.2s".to_string()));
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(lint("no-such-linter --check", Duration::from_secs(10), root), Err("no-such-linter is not installed".to_string()));
+    assert_eq!(lint("false", Duration::from_secs(10), root), Err("it failed without reporting any problems: ".to_string()));
+    assert_eq!(lint("", Duration::from_secs(10), root), Err("the command is empty".to_string()));
+}
+
+#[test]
+fn test_lint_context() {
+    let dir = temp_dir("lint");
+    init_repo(&dir);
+    fs::write(dir.join("cart.rs"), "fn total() {}\n").unwrap();
+    // Reports a problem in a changed file and one elsewhere, and fails as linters do
+    fs::write(
+        dir.join("lint.sh"),
+        "echo 'cart.rs:1:4: warning: function `total` is never used'\necho 'untouched.rs:9:1: warning: unused import'\nexit 1\n",
+    )
+    .unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 { 1 }\n").unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+    let (success, prompt) = review(&["--lint-context", "sh lint.sh"]);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("## Existing Linter Findings\nThe project's linters already report"), "{}", prompt);
+    assert!(prompt.contains("cart.rs:1: warning: function `total` is never used"), "{}", prompt);
+    assert!(!prompt.contains("untouched.rs"), "{}", prompt);
+
+    // A linter that can't be run leaves the prompt as it was, with a warning
+    let (success, prompt) = review(&["--lint-context", "no-such-linter", "--lint-context", "false"]);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("Leaving out the linter `no-such-linter`: no-such-linter is not installed"), "{}", prompt);
+    assert!(prompt.contains("Leaving out the linter `false`: it failed without reporting any problems"), "{}", prompt);
+    assert!(!prompt.contains("## Existing Linter Findings"), "{}", prompt);
+}
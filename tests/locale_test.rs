@@ -0,0 +1,182 @@
+mod common;
+
+use common::{init_repo, temp_dir};
+use llm_code_review::comment_series::{join_series, series_part, split_review};
+use llm_code_review::findings::{render_findings, Finding, Severity};
+use llm_code_review::locale::Locale;
+use llm_code_review::output::OutputFormat;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The prompt printed for the repository below, and the findings below rendered, in each language
+const PROMPTS: [(Locale, &str); 3] = [
+    (Locale::En, include_str!("fixtures/locale/prompt.en.txt")),
+    (Locale::Ja, include_str!("fixtures/locale/prompt.ja.txt")),
+    (Locale::De, include_str!("fixtures/locale/prompt.de.txt")),
+];
+const FINDINGS: [(Locale, &str); 3] = [
+    (Locale::En, include_str!("fixtures/locale/findings.en.md")),
+    (Locale::Ja, include_str!("fixtures/locale/findings.ja.md")),
+    (Locale::De, include_str!("fixtures/locale/findings.de.md")),
+];
+
+#[test]
+fn test_parse_locale() {
+    assert_eq!("ja".parse::<Locale>(), Ok(Locale::Ja));
+    assert_eq!(Locale::De.to_string(), "de");
+    assert_eq!(
+        "fr".parse::<Locale>(),
+        Err("unknown review language `fr`; it may be auto, en, ja, de".to_string())
+    );
+    assert_eq!(Locale::select("de"), Ok(Locale::De));
+}
+
+#[test]
+fn test_locale_from_environment() {
+    let from = |vars: &[(&str, &str)]| {
+        let vars: HashMap<String, String> = vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        Locale::from_vars(|name| vars.get(name).cloned())
+    };
+    assert_eq!(from(&[("LANG", "ja_JP.UTF-8")]), Locale::Ja);
+    assert_eq!(from(&[("LANG", "de")]), Locale::De);
+    assert_eq!(from(&[("LANG", "de_AT@euro")]), Locale::De);
+    // LC_ALL comes first, then LC_MESSAGES; an empty one doesn't count
+    assert_eq!(from(&[("LANG", "ja_JP.UTF-8"), ("LC_MESSAGES", "de_DE.UTF-8")]), Locale::De);
+    assert_eq!(from(&[("LC_ALL", "ja_JP.UTF-8"), ("LC_MESSAGES", "de_DE.UTF-8")]), Locale::Ja);
+    assert_eq!(from(&[("LC_ALL", ""), ("LANG", "de_CH.UTF-8")]), Locale::De);
+    // Languages without a translation, and none at all, are English
+    assert_eq!(from(&[("LANG", "fr_FR.UTF-8")]), Locale::En);
+    assert_eq!(from(&[("LANG", "C.UTF-8")]), Locale::En);
+    assert_eq!(from(&[]), Locale::En);
+}
+
+#[test]
+fn test_messages() {
+    assert_eq!(Locale::Ja.text("Findings"), "指摘事項");
+    assert_eq!(Locale::De.text("Findings"), "Befunde");
+    assert_eq!(Locale::En.text("Findings"), "Findings");
+    // Text that isn't in the table, such as a name, is left as it is
+    assert_eq!(Locale::Ja.text("HEAD"), "HEAD");
+    assert_eq!(Locale::De.format(" and {} more", &["3"]), " und 3 weitere");
+    assert_eq!(Locale::Ja.format("Review {}/{}", &["1", "2"]), "レビュー 1/2");
+
+    assert_eq!(Locale::En.instructions(), None);
+    let instructions = Locale::Ja.instructions().unwrap();
+    assert!(instructions.starts_with("\nWrite the review in Japanese."), "{}", instructions);
+}
+
+fn findings() -> Vec<Finding> {
+    let finding = |path: &str, line: Option<usize>, severity: Severity, comment: &str| Finding {
+        path: path.to_string(),
+        line,
+        severity,
+        category: None,
+        comment: comment.to_string(),
+        follow_up: false,
+        policy_note: None,
+        confidence: None,
+        extracted: false,
+        lower_priority: false,
+    };
+    vec![
+        finding("src/cart.rs", Some(12), Severity::Error, "The total overflows for large carts."),
+        finding("src/cart.rs", None, Severity::Info, "The module has no doc comment."),
+        Finding { confidence: Some(0.3), ..finding("src/tax.rs", Some(4), Severity::Warning, "The rate may be stale.") },
+        Finding { lower_priority: true, ..finding("src/tax.rs", Some(9), Severity::Info, "Name this constant.") },
+    ]
+}
+
+#[test]
+fn test_findings_snapshots() {
+    for (locale, expected) in FINDINGS {
+        assert_eq!(render_findings(&findings(), OutputFormat::Markdown, locale), expected, "{}", locale);
+        // JSON is read by programs, so it's the same in every language
+        assert_eq!(
+            render_findings(&findings(), OutputFormat::Json, locale),
+            render_findings(&findings(), OutputFormat::Json, Locale::En)
+        );
+    }
+    let mediawiki = render_findings(&findings(), OutputFormat::MediaWiki, Locale::De);
+    assert!(mediawiki.contains("== Befunde ==\n\n{| class=\"wikitable\"\n! Datei !! Fehler !! Warnung !! Info"), "{}", mediawiki);
+    let asciidoc = render_findings(&findings(), OutputFormat::AsciiDoc, Locale::Ja);
+    assert!(asciidoc.contains("\n=== 誤りの可能性あり、要確認\n\n* *警告* `src/tax.rs` (4行目)"), "{}", asciidoc);
+}
+
+#[test]
+fn test_comment_series_in_any_language() {
+    let review = format!("## Summary\n{}\n## Details\n{}\n", "Fine. ".repeat(60), "More. ".repeat(60));
+    let comments = split_review(&review, 500, Locale::Ja);
+    assert!(comments.len() > 1);
+    // The hidden marker is the same in every language, and the series joins up again
+    assert_eq!(series_part(&comments[0]), Some((1, comments.len())));
+    assert!(comments[0].contains(&format!("-->\n**レビュー 1/{}**\n\n", comments.len())), "{}", comments[0]);
+    assert_eq!(join_series(&comments), review);
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    // Fixed dates, so the commit's hash in the provenance section is the same every run
+    let status = Command::new("git")
+        .current_dir(dir)
+        .env("GIT_AUTHOR_DATE", "2025-08-01T10:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2025-08-01T10:00:00Z")
+        .args(args)
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// A repository named `repo` with uncommitted changes to a source file and to notes
+fn repository() -> PathBuf {
+    let dir = temp_dir("locale").join("repo");
+    fs::create_dir_all(&dir).unwrap();
+    init_repo(&dir);
+    git(&dir, &["checkout", "-q", "-b", "main"]);
+    fs::write(dir.join("cart.py"), "def total(items):\n    return sum(item.price for item in items)\n").unwrap();
+    fs::write(dir.join("notes.txt"), "Remember the tax.\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Add the cart"]);
+    fs::write(dir.join("cart.py"), "def total(items):\n    return sum(item.price * item.quantity for item in items)\n").unwrap();
+    fs::write(dir.join("notes.txt"), "Remember the tax and the quantity.\n").unwrap();
+    dir
+}
+
+#[test]
+fn test_prompt_snapshots() {
+    let dir = repository();
+    let run = |args: &[&str], lang: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env("LANG", lang)
+            .args(["--stable-prompt", "--canary", "--exclude", "notes.txt", "--context", "Quantities were ignored."])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    for (locale, expected) in PROMPTS {
+        let prompt = run(&["--review-language", &locale.to_string()], "C");
+        assert_eq!(prompt, expected, "{}", locale);
+    }
+    // Without --review-language the prompt is in English, whatever the locale, and with auto it
+    // follows the locale
+    assert_eq!(run(&[], "ja_JP.UTF-8"), PROMPTS[0].1);
+    assert_eq!(run(&["--review-language", "auto"], "ja_JP.UTF-8"), PROMPTS[1].1);
+    assert_eq!(run(&["--review-language", "auto"], "fr_FR.UTF-8"), PROMPTS[0].1);
+
+    // What the tool reads back is the same in every language
+    let canary = |prompt: &str| prompt.lines().find(|line| line.starts_with("Verification code: ")).map(str::to_string);
+    assert!(canary(PROMPTS[0].1).is_some());
+    for (_, prompt) in &PROMPTS[1..] {
+        assert_eq!(canary(prompt), canary(PROMPTS[0].1));
+        assert!(prompt.contains("- Diff SHA-256: ") && prompt.contains("`<!-- end-of-diff: CODE -->`"), "{}", prompt);
+    }
+
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::temp_dir;
+use std::fs;
+use std::process::Command;
+
+const DIFF: &str = "diff --git a/cart.rs b/cart.rs\n--- a/cart.rs\n+++ b/cart.rs\n@@ -1,3 +1,3 @@\n fn total() {\n-    1\n+    2\n }\n";
+
+#[test]
+fn test_logs_go_to_standard_error() {
+    let dir = temp_dir("logging");
+    fs::write(dir.join("change.diff"), DIFF).unwrap();
+
+    // Piped into another tool, standard output has the prompt and nothing else
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .args(["-v", "--inject-rust-edition", "--diff-file", "change.diff"])
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stdout.starts_with("Please review this PR"), "{}", stdout);
+    assert!(!stdout.contains("[llm_code_review"), "{}", stdout);
+    assert!(stderr.contains("INFO  [llm_code_review::review] Verbose mode enabled."), "{}", stderr);
+    // Measuring the prompt around the diff logs nothing: the notes are the prompt's alone
+    assert_eq!(stderr.matches("--inject-rust-edition: no Cargo.toml found").count(), 1, "{}", stderr);
+    assert!(stderr.contains("Review length for 2 changed lines: 150 words"), "{}", stderr);
+    assert!(!stderr.contains("Review length for 0 changed lines"), "{}", stderr);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
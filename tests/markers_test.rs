@@ -0,0 +1,42 @@
+use llm_code_review::diff::parse_diff;
+use llm_code_review::markers::{
+    describe_markers, find_new_markers, markers_without_issue, Marker, DEFAULT_TODO_ISSUE_PATTERN,
+};
+use regex::Regex;
+
+const DIFF: &str = include_str!("fixtures/todo_markers.diff");
+
+fn marker(path: &str, line: usize, text: &str) -> Marker {
+    Marker {
+        path: path.to_string(),
+        line,
+        text: text.to_string(),
+    }
+}
+
+#[test]
+fn test_find_new_markers() {
+    // Markers on context and removed lines, and inside longer words, are ignored, and elided
+    // lines still count towards line numbers
+    assert_eq!(
+        find_new_markers(&parse_diff(DIFF)),
+        vec![
+            marker("src/lib.rs", 12, "// TODO(#42): handle nested tables"),
+            marker("src/lib.rs", 14, "// FIXME handle empty input"),
+            marker("src/lib.rs", 50, "out.push_str(TODOS_HEADER); // HACK: see below"),
+            marker("src/lib.rs", 51, "// XXX remove once the renderer is rewritten"),
+        ]
+    );
+}
+
+#[test]
+fn test_markers_without_issue() {
+    let markers = find_new_markers(&parse_diff(DIFF));
+    let untracked = markers_without_issue(&markers, &Regex::new(DEFAULT_TODO_ISSUE_PATTERN).unwrap());
+    assert_eq!(
+        describe_markers(&untracked),
+        "- src/lib.rs:14: // FIXME handle empty input\n\
+         - src/lib.rs:50: out.push_str(TODOS_HEADER); // HACK: see below\n\
+         - src/lib.rs:51: // XXX remove once the renderer is rewritten"
+    );
+}
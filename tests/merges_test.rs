@@ -0,0 +1,149 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::{line_counts, Diff, HunkRange};
+use llm_code_review::merges::{merges_section, Merge};
+use llm_code_review::preflight::check_prompt;
+use llm_code_review::selection::SelectedCommit;
+use std::fs;
+use std::process::Command;
+
+// `git show --cc` of a merge that resolved a conflict in shop.py and added a line to notes.txt
+// that neither parent had
+const COMBINED: &str = include_str!("fixtures/combined.diff");
+
+#[test]
+fn test_parse_combined_diff() {
+    let diff = Diff::parse(COMBINED);
+    assert_eq!(diff.render(), COMBINED);
+    let paths: Vec<&str> = diff.files.iter().map(|file| file.path.as_str()).collect();
+    assert_eq!(paths, vec!["notes.txt", "shop.py"]);
+    assert!(diff.files.iter().all(|file| file.is_combined()));
+
+    let hunk = &diff.files[1].hunks[0];
+    assert_eq!(hunk.parents(), 2);
+    assert_eq!(hunk.range(), Some(HunkRange { old_start: 1, old_len: 6, new_start: 1, new_len: 6 }));
+    assert_eq!(hunk.lines.len(), 9);
+    assert_eq!(hunk.changed_lines(), 5);
+    assert_eq!(diff.files[0].hunks[0].changed_lines(), 1);
+
+    // The hunks' lines add up to their headers, so the prompt isn't held back as broken
+    let prompt = format!("Review this.\n\n# PR Code\n\n{}", COMBINED);
+    assert_eq!(check_prompt(&prompt, "PR Code", COMBINED, true), Vec::new());
+}
+
+#[test]
+fn test_combined_hunk_range() {
+    let range = HunkRange::parse("@@@@ -3,4 -3,5 -2,4 +3,7 @@@@ fn main() {");
+    assert_eq!(range, Some(HunkRange { old_start: 3, old_len: 4, new_start: 3, new_len: 7 }));
+    // Fewer ranges than the `@`s call for
+    assert_eq!(HunkRange::parse("@@@ -1,2 +1,2 @@@"), None);
+    assert_eq!(HunkRange::parse("@@ -1,2 +1,3 @@"), Some(HunkRange { old_start: 1, old_len: 2, new_start: 1, new_len: 3 }));
+}
+
+#[test]
+fn test_line_counts() {
+    // (first parent, result)
+    assert_eq!(line_counts("  context", 2), (1, 1));
+    assert_eq!(line_counts("++written by the merge", 2), (0, 1));
+    assert_eq!(line_counts("+ from the second parent", 2), (0, 1));
+    assert_eq!(line_counts(" +from the first parent", 2), (1, 1));
+    assert_eq!(line_counts("- only in the first parent", 2), (1, 0));
+    assert_eq!(line_counts(" -only in the second parent", 2), (0, 0));
+    assert_eq!(line_counts("--in both parents", 2), (1, 0));
+    assert_eq!(line_counts("", 2), (1, 1));
+    assert_eq!(line_counts("-removed", 1), (1, 0));
+    assert_eq!(line_counts("\\ No newline at end of file", 1), (0, 0));
+}
+
+fn merge(short: &str, combined_diff: &str) -> Merge {
+    Merge {
+        commit: SelectedCommit {
+            sha: format!("{}000000", short),
+            author: "Alice".to_string(),
+            subject: format!("Merge {}", short),
+        },
+        combined_diff: combined_diff.to_string(),
+    }
+}
+
+#[test]
+fn test_merges_section() {
+    let merges = [merge("abc1234", ""), merge("def5678", COMBINED)];
+    let section = merges_section(&merges, true, false);
+    assert!(section.starts_with("The 2 merge commits in the range are left out: the diff has only the changes"), "{}", section);
+    assert!(section.contains("\n- abc1234 Merge abc1234 (Alice)\n"), "{}", section);
+    assert!(section.contains("- def5678 Merge def5678 (Alice): changes code itself"), "{}", section);
+    assert!(section.ends_with("What the merges changed themselves isn't shown."), "{}", section);
+
+    let section = merges_section(&merges, true, true);
+    assert!(section.contains("`++` is a line the merge wrote itself"), "{}", section);
+    assert!(section.contains("### Merge def5678: Merge def5678\n\n```diff\ndiff --cc notes.txt\n"), "{}", section);
+    // A merge that changed nothing itself has no diff to show
+    assert!(!section.contains("### Merge abc1234"), "{}", section);
+
+    let section = merges_section(&merges[..1], false, false);
+    assert!(section.starts_with("The range has 1 merge commit in it, and the diff includes"), "{}", section);
+}
+
+#[test]
+fn test_range_with_merge() {
+    let dir = temp_dir("merges");
+    init_repo(&dir);
+    git(&dir, &["checkout", "-q", "-b", "main"]);
+    fs::write(dir.join("app.py"), "def run():\n    pass\n").unwrap();
+    fs::write(dir.join("lib.py"), "def helper():\n    return 1\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Start"]);
+    let base = git(&dir, &["rev-parse", "HEAD"]);
+
+    git(&dir, &["checkout", "-q", "-b", "work"]);
+    fs::write(dir.join("app.py"), "def run():\n    start_engine()\n").unwrap();
+    git(&dir, &["commit", "-q", "-am", "Start the engine"]);
+    git(&dir, &["checkout", "-q", "main"]);
+    fs::write(dir.join("lib.py"), "def helper():\n    return 2  # from main\n").unwrap();
+    git(&dir, &["commit", "-q", "-am", "Change the helper on main"]);
+    git(&dir, &["checkout", "-q", "work"]);
+    git(&dir, &["merge", "-q", "--no-commit", "main"]);
+    // An evil merge: a change neither branch made
+    fs::write(dir.join("lib.py"), "def helper():\n    return 2  # from main\n\nSECRET_BACKDOOR = True\n").unwrap();
+    git(&dir, &["commit", "-q", "-am", "Merge main into work"]);
+    fs::write(dir.join("app.py"), "def run():\n    start_engine()\n    stop_engine()\n").unwrap();
+    git(&dir, &["commit", "-q", "-am", "Stop the engine"]);
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(["--no-provenance"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout))
+    };
+
+    let output = review(&[&base, "HEAD"]);
+    let diff = output.split("# PR Code").nth(1).unwrap();
+    assert!(diff.contains("+    start_engine()") && diff.contains("+    stop_engine()"), "{}", output);
+    // What came from main, and what the merge added, aren't in the diff
+    assert!(!diff.contains("from main") && !diff.contains("SECRET_BACKDOOR"), "{}", output);
+    assert!(output.contains("## Merge Commits\nThe 1 merge commit in the range is left out"), "{}", output);
+    assert!(output.contains("Merge main into work (Test): changes code itself"), "{}", output);
+    assert!(output.contains("--include-merges reviews those changes"), "{}", output);
+
+    // A single revision is the same range, with nothing uncommitted
+    let output = review(&[&base]);
+    assert!(!output.split("# PR Code").nth(1).unwrap().contains("from main"), "{}", output);
+
+    let output = review(&["--include-merges", &format!("{}..HEAD", base)]);
+    assert!(output.contains("### Merge "), "{}", output);
+    assert!(output.contains("\n++SECRET_BACKDOOR = True\n"), "{}", output);
+    assert!(!output.split("# PR Code").nth(1).unwrap().contains("SECRET_BACKDOOR"), "{}", output);
+
+    // With uncommitted changes, the single revision's diff goes to the working tree as it is
+    fs::write(dir.join("app.py"), "def run():\n    start_engine()\n    stop_engine()\n    log()\n").unwrap();
+    let output = review(&[&base]);
+    assert!(!output.contains("## Merge Commits"), "{}", output);
+    assert!(output.contains("+    log()"), "{}", output);
+    assert!(output.contains("The diff includes what 1 merge commit brought in"), "{}", output);
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::findings::Severity;
+use llm_code_review::migrations::{check_migrations, find_migrations, is_schema_source, migration_kind, migrations_section, MigrationKind};
+use llm_code_review::severity_policy::glob_regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[test]
+fn test_migration_kind() {
+    let kind = |path: &str| migration_kind(path, &[]);
+    assert_eq!(kind("shop/migrations/0002_remove_order_note.py"), Some(MigrationKind::Django));
+    assert_eq!(kind("migrations/0001_initial.py"), Some(MigrationKind::Django));
+    assert_eq!(kind("db/migrate/20250801100000_drop_legacy_orders.rb"), Some(MigrationKind::Rails));
+    assert_eq!(kind("alembic/versions/3f2a_add_orders.py"), Some(MigrationKind::Alembic));
+    assert_eq!(kind("app/migrations/versions/3f2a_add_orders.py"), Some(MigrationKind::Alembic));
+    assert_eq!(kind("migrations/20250801100000_orders.up.sql"), Some(MigrationKind::Sql));
+    assert_eq!(kind("migrations/2025-08-01-100000_orders/down.sql"), Some(MigrationKind::Sql));
+    assert_eq!(kind("src/main/resources/db/migration/V2__orders.sql"), Some(MigrationKind::Sql));
+
+    assert_eq!(kind("shop/migrations/__init__.py"), None);
+    assert_eq!(kind("shop/models.py"), None);
+    assert_eq!(kind("db/schema.rb"), None);
+    assert_eq!(kind("sql/reports.sql"), None);
+    assert_eq!(kind("docs/migrations/guide.md"), None);
+
+    let configured = [glob_regex("db/changes/*.sql")];
+    assert_eq!(migration_kind("db/changes/0042.sql", &configured), Some(MigrationKind::Configured));
+    assert_eq!(migration_kind("db/migrate/1_x.rb", &configured), Some(MigrationKind::Rails));
+
+    assert!(is_schema_source("shop/models.py") && is_schema_source("shop/models/order.py"));
+    assert!(is_schema_source("app/models/order.rb") && is_schema_source("prisma/schema.prisma"));
+    assert!(!is_schema_source("shop/views.py"));
+}
+
+const DIFF: &str = "\
+diff --git a/shop/models.py b/shop/models.py
+--- a/shop/models.py
++++ b/shop/models.py
+@@ -8,3 +8,2 @@ class Order(models.Model):
+     total = models.DecimalField()
+-    note = models.TextField()
+     placed = models.DateTimeField()
+diff --git a/billing/models.py b/billing/models.py
+--- a/billing/models.py
++++ b/billing/models.py
+@@ -3 +3 @@ class Invoice(models.Model):
+-    due = models.DateField()
++    due = models.DateField(null=True)
+diff --git a/shop/migrations/0002_remove_order_note.py b/shop/migrations/0002_remove_order_note.py
+new file mode 100644
+--- /dev/null
++++ b/shop/migrations/0002_remove_order_note.py
+@@ -0,0 +1,8 @@
++from django.db import migrations
++
++
++class Migration(migrations.Migration):
++    dependencies = [(\"shop\", \"0001_initial\")]
++    operations = [
++        migrations.RemoveField(model_name=\"order\", name=\"note\"),
++    ]
+diff --git a/db/migrate/20250801100000_drop_legacy.rb b/db/migrate/20250801100000_drop_legacy.rb
+--- a/db/migrate/20250801100000_drop_legacy.rb
++++ b/db/migrate/20250801100000_drop_legacy.rb
+@@ -2,6 +2,12 @@ class DropLegacy < ActiveRecord::Migration[7.1]
+   def up
++    # allow-destructive: archived to S3 in #412
++    drop_table :legacy_orders
++    remove_column :orders, :coupon
++    # remove_column :orders, :referrer
+   end
+ 
+   def down
++    create_table :legacy_orders
++    drop_table :coupons
++  end
++
++  def cleanup
++    execute \"DROP TABLE tmp_orders\"
+   end
+diff --git a/migrations/20250801100000_orders.down.sql b/migrations/20250801100000_orders.down.sql
+new file mode 100644
+--- /dev/null
++++ b/migrations/20250801100000_orders.down.sql
+@@ -0,0 +1 @@
++DROP TABLE orders;
+diff --git a/alembic/versions/3f2a_orders.py b/alembic/versions/3f2a_orders.py
+--- a/alembic/versions/3f2a_orders.py
++++ b/alembic/versions/3f2a_orders.py
+@@ -20,2 +20,3 @@ def downgrade():
+     op.drop_column(\"orders\", \"placed\")
++    op.drop_table(\"order_events\")
+     pass
+";
+
+#[test]
+fn test_find_migrations() {
+    let files = parse_diff(DIFF);
+    let migrations = find_migrations(&files, &[]);
+    let found: Vec<(&str, MigrationKind, bool)> = migrations.iter().map(|migration| (migration.path, migration.kind, migration.added)).collect();
+    assert_eq!(
+        found,
+        [
+            ("shop/migrations/0002_remove_order_note.py", MigrationKind::Django, true),
+            ("db/migrate/20250801100000_drop_legacy.rb", MigrationKind::Rails, false),
+            ("migrations/20250801100000_orders.down.sql", MigrationKind::Sql, true),
+            ("alembic/versions/3f2a_orders.py", MigrationKind::Alembic, false),
+        ]
+    );
+    // A Django app's migration goes with its own models; the others, with every schema change
+    let sources = |i: usize| migrations[i].sources.iter().map(|file| file.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(sources(0), ["shop/models.py"]);
+    assert_eq!(sources(1), ["shop/models.py", "billing/models.py"]);
+
+    let section = migrations_section(&migrations, &[]);
+    assert!(
+        section.contains(
+            "\n- shop/migrations/0002_remove_order_note.py (new Django migration)\n  - shop/models.py: @@ -8,3 +8,2 @@ class Order(models.Model):\n"
+        ),
+        "{}",
+        section
+    );
+    assert!(
+        section.contains("- db/migrate/20250801100000_drop_legacy.rb (changed Rails migration; one that already ran won't run again"),
+        "{}",
+        section
+    );
+    assert!(section.contains("Label these findings with the category `migrations`."), "{}", section);
+    assert!(!section.contains("already report these"), "{}", section);
+
+    // Without a schema change in the diff, the migration is said to have none
+    let files = parse_diff(&DIFF[DIFF.find("diff --git a/shop/migrations").unwrap()..]);
+    let section = migrations_section(&find_migrations(&files, &[]), &[]);
+    assert!(section.contains("(new Django migration)\n  - no schema or model change in the diff;"), "{}", section);
+}
+
+#[test]
+fn test_check_migrations() {
+    let files = parse_diff(DIFF);
+    let findings = check_migrations(&files, &find_migrations(&files, &[]));
+    let found: Vec<(&str, usize, &str)> = findings
+        .iter()
+        .map(|finding| (finding.path.as_str(), finding.line.unwrap(), finding.comment.split('`').nth(1).unwrap()))
+        .collect();
+    // Not the guarded drop, the comment, the `down` method, the down file or `downgrade()`
+    assert_eq!(
+        found,
+        [
+            ("shop/migrations/0002_remove_order_note.py", 7, "migrations.RemoveField"),
+            ("db/migrate/20250801100000_drop_legacy.rb", 5, "remove_column"),
+            ("db/migrate/20250801100000_drop_legacy.rb", 15, "DROP TABLE"),
+        ]
+    );
+    assert!(findings.iter().all(|finding| finding.severity == Severity::Error && finding.category.as_deref() == Some("migrations")));
+    assert!(findings[0].comment.ends_with("with an `allow-destructive` comment (found by the local `destructive-ddl` check)"), "{}", findings[0].comment);
+
+    let section = migrations_section(&find_migrations(&files, &[]), &findings);
+    assert!(
+        section.contains("already report these, so don't repeat them:\n- shop/migrations/0002_remove_order_note.py:7: `migrations.RemoveField`"),
+        "{}",
+        section
+    );
+}
+
+/// A repository whose uncommitted change drops a model's field and adds a SQL migration dropping
+/// a table from a directory only --migration-files knows
+fn repository() -> PathBuf {
+    let dir = temp_dir("migrations");
+    fs::create_dir_all(dir.join("shop/migrations")).unwrap();
+    fs::create_dir_all(dir.join("db/changes")).unwrap();
+    init_repo(&dir);
+    fs::write(dir.join("shop/models.py"), "class Order(models.Model):\n    total = models.DecimalField()\n    note = models.TextField()\n").unwrap();
+    fs::write(dir.join("shop/migrations/__init__.py"), "").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Start"]);
+    fs::write(dir.join("shop/models.py"), "class Order(models.Model):\n    total = models.DecimalField()\n").unwrap();
+    fs::write(
+        dir.join("shop/migrations/0002_remove_order_note.py"),
+        "from django.db import migrations\n\n\nclass Migration(migrations.Migration):\n    operations = [migrations.RemoveField(\"order\", \"note\")]\n",
+    )
+    .unwrap();
+    fs::write(dir.join("db/changes/0042.sql"), "DROP TABLE coupons;\n").unwrap();
+    git(&dir, &["add", "-N", "."]);
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .args(args)
+        .output()
+        .expect("failed to run llm_code_review");
+    (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+}
+
+#[test]
+fn test_migrations_section_in_prompt() {
+    let dir = repository();
+    let (success, output) = run(&dir, &[]);
+    assert!(success, "{}", output);
+    assert!(output.contains("## Database Migrations\n"), "{}", output);
+    assert!(output.contains("- shop/migrations/0002_remove_order_note.py (new Django migration)\n  - shop/models.py: @@"), "{}", output);
+    assert!(output.contains("shop/migrations/0002_remove_order_note.py:5: `migrations.RemoveField` deletes the data"), "{}", output);
+    assert!(!output.contains("db/changes/0042.sql (new migration)"), "{}", output);
+
+    let (success, output) = run(&dir, &["--migration-files", "db/changes/*.sql"]);
+    assert!(success, "{}", output);
+    assert!(output.contains("- db/changes/0042.sql (new migration)"), "{}", output);
+    assert!(output.contains("db/changes/0042.sql:1: `DROP TABLE` deletes the data"), "{}", output);
+
+    // The repository's config file can add globs too
+    fs::write(dir.join(".llm_code_review.toml"), "migration_files = [\"db/changes/*.sql\"]\n").unwrap();
+    let (success, output) = run(&dir, &[]);
+    assert!(success, "{}", output);
+    assert!(output.contains("- db/changes/0042.sql (new migration)"), "{}", output);
+    fs::remove_file(dir.join(".llm_code_review.toml")).unwrap();
+
+    let (success, output) = run(&dir, &["--no-migration-review"]);
+    assert!(success, "{}", output);
+    assert!(!output.contains("## Database Migrations") && !output.contains("deletes the data"), "{}", output);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,96 @@
+use llm_code_review::diff::parse_diff;
+use llm_code_review::naming::{
+    check_naming, declared_identifiers, identifier_words, naming_section, string_literals, Dictionary, NamingCandidate,
+};
+
+#[test]
+fn test_identifier_words() {
+    assert_eq!(identifier_words("parseHTTPResponse_v2"), vec!["parse", "HTTP", "Response", "v"]);
+    assert_eq!(identifier_words("recieved_count"), vec!["recieved", "count"]);
+    assert_eq!(identifier_words("MAX_RETRY_COUNT"), vec!["MAX", "RETRY", "COUNT"]);
+    assert_eq!(identifier_words("HttpClient"), vec!["Http", "Client"]);
+    assert_eq!(identifier_words("x"), vec!["x"]);
+}
+
+#[test]
+fn test_declared_identifiers() {
+    assert_eq!(declared_identifiers(Some("rust"), "    let mut recieved = 0;"), vec!["recieved"]);
+    assert_eq!(declared_identifiers(Some("rust"), "pub fn retreive_all(&self) -> Vec<Item> {"), vec!["retreive_all"]);
+    assert_eq!(declared_identifiers(Some("rust"), "    pub lenght: usize,"), vec!["lenght"]);
+    assert_eq!(declared_identifiers(Some("rust"), "    total += item.price;"), Vec::<String>::new());
+    assert_eq!(declared_identifiers(Some("python"), "def calcualte_total(items):"), vec!["calcualte_total"]);
+    assert_eq!(declared_identifiers(Some("python"), "    threshhold = 3"), vec!["threshhold"]);
+    assert_eq!(declared_identifiers(Some("python"), "    if a == b:"), Vec::<String>::new());
+    assert_eq!(declared_identifiers(Some("go"), "func (s *Server) HanlderFor(path string) {"), vec!["HanlderFor"]);
+    assert_eq!(declared_identifiers(Some("go"), "\tresponse, err := fetch()"), vec!["err"]);
+    assert_eq!(declared_identifiers(Some("typescript"), "export interface UserAdress {"), vec!["UserAdress"]);
+    assert_eq!(declared_identifiers(Some("java"), "    private final int maxLenght = 3;"), vec!["maxLenght"]);
+    // Languages without their own patterns get the common keywords
+    assert_eq!(declared_identifiers(None, "function seperateWords() {"), vec!["seperateWords"]);
+}
+
+#[test]
+fn test_string_literals() {
+    let line = r#"    println!("Could not \"retreive\" {}", 'c');"#;
+    assert_eq!(string_literals(Some("rust"), line), vec![r#""Could not \"retreive\" {}""#]);
+    assert_eq!(string_literals(Some("python"), "print('Recieved', \"twice\")"), vec!["'Recieved'", "\"twice\""]);
+    assert_eq!(string_literals(Some("rust"), "fn lifetime<'a>(s: &'a str) {}"), Vec::<String>::new());
+}
+
+#[test]
+fn test_dictionary() {
+    let dictionary = Dictionary::default();
+    assert_eq!(dictionary.correction("recieve"), Some("receive"));
+    assert_eq!(dictionary.correction("Recieve"), Some("receive"));
+    assert_eq!(dictionary.correction("receive"), None);
+
+    let custom = Dictionary::parse("# comment\n\nfoo->bar\n  Baz -> qux  \nnot a pair\n");
+    assert_eq!((custom.correction("foo"), custom.correction("baz")), (Some("bar"), Some("qux")));
+    assert_eq!(custom.correction("recieve"), None);
+}
+
+const DIFF: &str = "\
+diff --git a/src/client.rs b/src/client.rs
+--- a/src/client.rs
++++ b/src/client.rs
+@@ -10,3 +10,6 @@ impl Client {
+     let retreive_old = 1;
+-    let count = 0;
++    let recieved_count = 0;
++    let retreive_old = 2;
++    let parseUrl = 3;
++    eprintln!(\"Failed to conect: the adress is invalid\");
+     }
+";
+
+#[test]
+fn test_check_naming() {
+    let files = parse_diff(DIFF);
+    let dictionary = Dictionary::default();
+    let existing = |_: &str| Some("fn parseURL() {}\n".repeat(20));
+    let candidates = check_naming(&files, existing, &dictionary);
+    let candidate = |line, text: &str, problem: &str| NamingCandidate {
+        path: "src/client.rs".to_string(),
+        line,
+        text: text.to_string(),
+        problem: problem.to_string(),
+    };
+    assert_eq!(
+        candidates,
+        vec![
+            candidate(11, "recieved_count", "`recieved` may be a misspelling of `received`"),
+            // retreive_old was already there, so it isn't new
+            candidate(13, "parseUrl", "cased differently from `parseURL`, already in the file"),
+            candidate(14, "\"Failed to conect: the adress is invalid\"", "`adress` may be a misspelling of `address`"),
+        ]
+    );
+
+    // Without the file's contents, only the diff's own lines are compared with
+    let candidates = check_naming(&files, |_| None, &dictionary);
+    assert_eq!(candidates.len(), 2);
+    assert!(candidates.iter().all(|candidate| candidate.text != "parseUrl"));
+
+    let section = naming_section(&candidates);
+    assert!(section.contains("- src/client.rs:11: recieved_count: `recieved` may be a misspelling of `received`"), "{}", section);
+    assert!(section.contains("the category `naming`"), "{}", section);
+}
@@ -0,0 +1,79 @@
+use llm_code_review::normalize::{close_fences, normalize_review, strip_chatter, unwrap_json_string};
+use llm_code_review::output::{missing_structure, OutputFormat};
+
+#[test]
+fn test_strip_chatter() {
+    let review = "Sure! Here's my review of the changes:\n\n## Summary\n\nThe retry loop never gives up.\n\nLet me know if you'd like me to look at anything else!\n";
+    assert_eq!(strip_chatter(review), "## Summary\n\nThe retry loop never gives up.");
+    let review = "Certainly.\nI've reviewed the diff below:\n\n- `parse` ignores its error.\n\nI hope this helps.\nFeel free to ask follow-up questions.";
+    assert_eq!(strip_chatter(review), "- `parse` ignores its error.");
+    // Lines that are part of the review stay, even ones starting like chatter
+    let review = "Here is a race: `count` is read and written without the lock.\n\nIf you have any tests for it, run them under loom.";
+    assert_eq!(strip_chatter(review), review);
+    assert_eq!(strip_chatter("## Summary\n\nFine."), "## Summary\n\nFine.");
+}
+
+#[test]
+fn test_close_fences() {
+    // Cut short inside a suggestion
+    let review = "## Issues\n\n- Check the length first:\n\n```rust\nif buf.len() < 4 {\n    return None;";
+    assert_eq!(close_fences(review), format!("{}\n```", review));
+    let review = "````markdown\n## Summary\n\n```rust\nlet x = 1;\n```\n";
+    assert_eq!(close_fences(review), "````markdown\n## Summary\n\n```rust\nlet x = 1;\n```\n````");
+    let closed = "```rust\nlet x = 1;\n```\n\nFine.";
+    assert_eq!(close_fences(closed), closed);
+}
+
+#[test]
+fn test_unwrap_fence() {
+    let markdown = Some(OutputFormat::Markdown);
+    let review = "```markdown\n## Summary\n\nThe cache key ignores the encoding.\n```";
+    assert_eq!(normalize_review(review, markdown), "## Summary\n\nThe cache key ignores the encoding.");
+    // Left open as well as wrapped round the whole review
+    let review = "```\n## Summary\n\nThe cache key ignores the encoding.";
+    assert_eq!(normalize_review(review, markdown), "## Summary\n\nThe cache key ignores the encoding.");
+    // A Markdown review that's only a suggested change keeps its code block
+    let review = "```rust\nlet key = format!(\"{}:{}\", encoding, hash);\n```";
+    assert_eq!(normalize_review(review, markdown), review);
+    let review = "```asciidoc\n= Review\n\n== Summary\n\nFine.\n```";
+    assert_eq!(normalize_review(review, Some(OutputFormat::AsciiDoc)), "= Review\n\n== Summary\n\nFine.");
+}
+
+#[test]
+fn test_unwrap_json_string() {
+    assert_eq!(unwrap_json_string(r###""## Summary\n\nThe lock is never released.""###), "## Summary\n\nThe lock is never released.");
+    assert_eq!(
+        unwrap_json_string(r#"{"review": "== Summary ==\nThe lock is never released.", "format": "mediawiki"}"#),
+        "== Summary ==\nThe lock is never released."
+    );
+    // Findings aren't a review in a string, and are left as they are
+    let findings = r#"{"summary": "One nit.", "findings": []}"#;
+    assert_eq!(unwrap_json_string(findings), findings);
+    assert_eq!(
+        normalize_review("```json\n{\"review\": \"## Summary\\n\\nFine.\"}\n```", Some(OutputFormat::Markdown)),
+        "```json\n{\"review\": \"## Summary\\n\\nFine.\"}\n```"
+    );
+    assert_eq!(normalize_review("```\n{\"review\": \"## Summary\\n\\nFine.\"}\n```", Some(OutputFormat::Markdown)), "## Summary\n\nFine.");
+    // JSON asked for is never taken apart
+    assert_eq!(normalize_review(r#""[]""#, Some(OutputFormat::Json)), r#""[]""#);
+}
+
+#[test]
+fn test_missing_structure() {
+    assert_eq!(missing_structure("## Summary\n\nFine.", &OutputFormat::Markdown), None);
+    assert_eq!(
+        missing_structure("Overall this looks fine, but `parse` ignores its error.", &OutputFormat::Markdown).as_deref(),
+        Some("no Markdown headings (`# Title`)")
+    );
+    assert_eq!(missing_structure("= Review\n\nFine.", &OutputFormat::AsciiDoc), None);
+    assert_eq!(
+        missing_structure("**Summary**\n\nFine.\n\n```\n= not a heading\n```", &OutputFormat::AsciiDoc).as_deref(),
+        Some("no AsciiDoc headings (`= Title`)")
+    );
+    assert_eq!(missing_structure("== Summary ==\nFine.", &OutputFormat::MediaWiki), None);
+    assert_eq!(
+        missing_structure("## Summary\n\nFine.", &OutputFormat::MediaWiki).as_deref(),
+        Some("no MediaWiki headings (`== Title ==`)")
+    );
+    assert_eq!(missing_structure("Fine.", &OutputFormat::Html), None);
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::notebook::{convert_notebooks, is_notebook, notebook_hunks, notebook_text};
+use serde_json::json;
+use std::fs;
+use std::process::Command;
+
+/// A notebook with a markdown cell, a code cell with magics that printed and plotted, and a cell
+/// that failed
+fn notebook(threshold: u32) -> String {
+    let png = "iVBORw0KGgo".repeat(400);
+    json!({
+        "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Sales\n", "\n", "Weekly totals by region."]},
+            {
+                "cell_type": "code",
+                "execution_count": 3,
+                "metadata": {},
+                "source": ["%matplotlib inline\n", "!pip install pandas\n", format!("totals = sales[sales.amount > {}].sum()\n", threshold), "totals.plot()"],
+                "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["Collecting pandas\n", "Installed\n"]},
+                    {"output_type": "display_data", "metadata": {}, "data": {"image/png": png, "text/plain": ["<Figure size 640x480>"]}},
+                    {"output_type": "execute_result", "execution_count": 3, "metadata": {}, "data": {"text/plain": ["42"]}}
+                ]
+            },
+            {
+                "cell_type": "code",
+                "execution_count": 4,
+                "metadata": {},
+                "source": "print(totals['north'])",
+                "outputs": [{"output_type": "error", "ename": "KeyError", "evalue": "'north'", "traceback": ["..."]}]
+            },
+            {"cell_type": "raw", "metadata": {}, "source": "```\nraw text\n```"}
+        ],
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}, "language_info": {"name": "python"}},
+        "nbformat": 4,
+        "nbformat_minor": 5
+    })
+    .to_string()
+}
+
+#[test]
+fn test_notebook_text() {
+    let text = notebook_text(&notebook(100)).unwrap();
+    assert_eq!(
+        text,
+        "## Markdown cell\n\n# Sales\n\nWeekly totals by region.\n\n\
+         ## Code cell\n\n```python\n%matplotlib inline\n!pip install pandas\ntotals = sales[sales.amount > 100].sum()\ntotals.plot()\n```\n\n\
+         Output: 2 lines of stdout, image/png (4 KB), 1 line of text\n\n\
+         ## Code cell\n\n```python\nprint(totals['north'])\n```\n\nOutput: error KeyError: 'north'\n\n\
+         ## Raw cell\n\n````\n```\nraw text\n```\n````\n"
+    );
+    // The image's data isn't in the text
+    assert!(!text.contains("iVBOR"));
+
+    assert!(notebook_text("{\"worksheets\": []}").unwrap_err().contains("nbformat 4"));
+    assert!(notebook_text("not json").is_err());
+    assert!(is_notebook("analysis/Sales.IPYNB") && !is_notebook("notebook.py"));
+}
+
+#[test]
+fn test_notebook_hunks() {
+    let (old, new) = (notebook(100), notebook(250));
+    let hunks = notebook_hunks(Some(&old), Some(&new), 1).unwrap();
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].header, "@@ -11,3 +11,3 @@ Weekly totals by region.");
+    assert_eq!(
+        hunks[0].lines,
+        vec![
+            " !pip install pandas",
+            "-totals = sales[sales.amount > 100].sum()",
+            "+totals = sales[sales.amount > 250].sum()",
+            " totals.plot()"
+        ]
+    );
+
+    // A new notebook is all added lines
+    let added = notebook_hunks(None, Some(&new), 3).unwrap();
+    assert!(added[0].header.starts_with("@@ -0,0 +1,"), "{}", added[0].header);
+    assert!(added[0].lines.iter().all(|line| line.starts_with('+')));
+    assert!(notebook_hunks(Some("{}"), Some(&new), 3).is_err());
+}
+
+#[test]
+fn test_convert_notebooks() {
+    let diff = "\
+diff --git a/notes.md b/notes.md
+index 1111111..2222222 100644
+--- a/notes.md
++++ b/notes.md
+@@ -1 +1 @@
+-a
++b
+diff --git a/sales.ipynb b/sales.ipynb
+index aaaaaaa..bbbbbbb 100644
+--- a/sales.ipynb
++++ b/sales.ipynb
+@@ -1,3 +1,3 @@
+-    \"totals = sales[sales.amount > 100].sum()\\n\",
++    \"totals = sales[sales.amount > 250].sum()\\n\",
+";
+    let read = |id: &str, path: &str| {
+        assert_eq!(path, "sales.ipynb");
+        match id {
+            "aaaaaaa" => Some(notebook(100)),
+            "bbbbbbb" => Some(notebook(250)),
+            _ => None,
+        }
+    };
+    let converted = convert_notebooks(diff, 1, read);
+    assert!(converted.starts_with("diff --git a/notes.md b/notes.md\nindex 1111111..2222222 100644\n"), "{}", converted);
+    assert!(converted.contains("+++ b/sales.ipynb\n@@ -11,3 +11,3 @@ Weekly totals by region.\n !pip install pandas\n"), "{}", converted);
+    assert!(converted.contains("+totals = sales[sales.amount > 250].sum()\n totals.plot()\n"), "{}", converted);
+    assert!(!converted.contains("\\n\","), "{}", converted);
+
+    // A notebook whose versions can't be read keeps its JSON diff
+    let unreadable = diff.replace("bbbbbbb", "ccccccc");
+    assert_eq!(convert_notebooks(&unreadable, 1, read), unreadable);
+}
+
+#[test]
+fn test_review_notebook() {
+    let dir = temp_dir("notebook_test");
+    fs::create_dir_all(dir.join("analysis")).unwrap();
+    init_repo(&dir);
+    let pretty = |threshold| serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(&notebook(threshold)).unwrap()).unwrap();
+    fs::write(dir.join("analysis/sales.ipynb"), pretty(100)).unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("analysis/sales.ipynb"), pretty(250)).unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(dir.join("analysis"))
+            .env_remove("ANTHROPIC_API_KEY")
+            .env_remove("OPENAI_API_KEY")
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+    // Unstaged changes: the new version is read from the working tree
+    let prompt = review(&[]);
+    assert!(prompt.contains("-totals = sales[sales.amount > 100].sum()\n+totals = sales[sales.amount > 250].sum()\n"), "{}", prompt);
+    assert!(!prompt.contains("iVBOR"), "{}", prompt);
+
+    git(&dir, &["commit", "-q", "-am", "two"]);
+    let prompt = review(&["HEAD~1", "HEAD"]);
+    assert!(prompt.contains("+totals = sales[sales.amount > 250].sum()\n"), "{}", prompt);
+
+    let raw = review(&["--raw-notebooks", "HEAD~1", "HEAD"]);
+    assert!(raw.contains("+        \"totals = sales[sales.amount > 250].sum()\\n\","), "{}", raw);
+}
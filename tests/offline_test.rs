@@ -0,0 +1,126 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::capabilities::capabilities;
+use llm_code_review::ci_log::{ci_log_section, load_ci_log};
+use llm_code_review::client::{Anthropic, Provider, RequestSettings};
+use llm_code_review::error::ReviewError;
+use llm_code_review::github::{create_issue_comment, fetch_actions_job_log, fetch_github_diff, parse_github_url};
+use llm_code_review::network;
+use llm_code_review::progress::GenerationLimits;
+use std::fs;
+use std::net::TcpListener;
+use std::panic;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use ureq::unversioned::transport::{ConnectionDetails, Connector, Transport};
+
+/// A transport that fails the test the moment anything tries to connect
+#[derive(Debug)]
+struct NoConnections;
+
+impl Connector for NoConnections {
+    type Out = Box<dyn Transport>;
+
+    fn connect(&self, details: &ConnectionDetails, _: Option<()>) -> Result<Option<Self::Out>, ureq::Error> {
+        panic!("connected to {}", details.uri);
+    }
+}
+
+fn refused<T: std::fmt::Debug>(result: Result<T, ReviewError>) -> String {
+    match result {
+        Err(ReviewError::OfflineModeViolation(url)) => url,
+        other => panic!("expected an OfflineModeViolation, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_offline_makes_no_connections() {
+    assert!(network::set_transport(NoConnections));
+    // Before --offline, requests reach the transport
+    let connected = panic::catch_unwind(|| network::get("http://127.0.0.1:9/").unwrap().call());
+    assert!(connected.is_err());
+    assert!(capabilities().network());
+
+    network::go_offline();
+    assert!(!capabilities().network());
+    let diff = parse_github_url("https://github.com/octo/shop/commit/0123abc").unwrap();
+    assert_eq!(refused(fetch_github_diff(&diff, None)), "https://api.github.com/repos/octo/shop/commits/0123abc");
+    assert_eq!(refused(fetch_actions_job_log("octo", "shop", "42", None)), "https://api.github.com/repos/octo/shop/actions/jobs/42/logs");
+    assert_eq!(refused(load_ci_log("https://ci.example.com/logs/7.txt", None)), "https://ci.example.com/logs/7.txt");
+    let comment = create_issue_comment("https://api.github.com", "octo/shop", 3, "token", "Looks good.", Duration::from_secs(5));
+    assert_eq!(refused(comment), "https://api.github.com/repos/octo/shop/issues/3/comments");
+    let provider = Anthropic(RequestSettings {
+        endpoint: "https://api.anthropic.com/v1/messages".to_string(),
+        model: "claude-opus-4-1".to_string(),
+        key: Some("sk-test".to_string()),
+        max_tokens: 1000,
+        limits: GenerationLimits::default(),
+        timeout: None,
+        chars_per_token: 4,
+        extra_params: Vec::new(),
+    });
+    assert_eq!(refused(provider.complete("Review this.", "diff")), "https://api.anthropic.com/v1/messages");
+    // Optional context that needs the network is left out rather than failing the run
+    assert_eq!(ci_log_section(&["https://ci.example.com/logs/7.txt".to_string()], 1000, None), None);
+}
+
+/// A repository with an uncommitted change, and a providers.toml whose endpoint is `listener`
+fn repository(listener: &TcpListener) -> PathBuf {
+    let dir = temp_dir("offline");
+    init_repo(&dir);
+    fs::write(dir.join("cart.py"), "def total(items):\n    return sum(items)\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Add the cart"]);
+    fs::write(dir.join("cart.py"), "def total(items):\n    return sum(item.price for item in items)\n").unwrap();
+    fs::write(
+        dir.join("providers.toml"),
+        format!(
+            "[[provider]]\nname = \"mock\"\nendpoint = \"http://{}/v1/messages\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n",
+            listener.local_addr().unwrap()
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn test_offline_run() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let dir = repository(&listener);
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .args(["--offline", "--llm-provider-config", "providers.toml"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+    };
+
+    // The prompt is built as usual, leaving out the CI log it would have to download
+    let (success, output) = run(&["--ci-log", &format!("{}/logs/7.txt", endpoint)]);
+    assert!(success, "{}", output);
+    assert!(output.contains("+    return sum(item.price for item in items)"), "{}", output);
+    assert!(output.contains(&format!("Not connecting to {}/logs/7.txt: --offline allows no network access", endpoint)), "{}", output);
+
+    let (success, output) = run(&["--model", "claude-opus-4-1", "--send"]);
+    assert!(!success);
+    assert!(output.contains(&format!("Not connecting to {}/v1/messages: --offline allows no network access", endpoint)), "{}", output);
+
+    let (success, output) = run(&["doctor"]);
+    assert!(output.contains("provider API") && output.contains("--offline"), "{}", output);
+    assert!(success, "{}", output);
+    let (_, output) = run(&["--capabilities"]);
+    assert!(output.contains("network") && output.contains("forbidden by --offline"), "{}", output);
+    let (success, output) = run(&["--allow-fetch"]);
+    assert!(!success && output.contains("cannot be used with"), "{}", output);
+
+    // Nothing reached the listener
+    assert_eq!(listener.accept().unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,149 @@
+use llm_code_review::output::{
+    docstring_comment_format, format_mismatch, has_review_issues, language_for_path, unfence, AnnotationLevel, OutputFormat, JSON_OUTPUT_SCHEMA,
+    OUTPUT_FORMATS,
+};
+use std::path::Path;
+
+#[test]
+fn test_docstring_comment_formats() {
+    assert_eq!(docstring_comment_format("rust"), Some("// REVIEW(line N): <comment>"));
+    assert_eq!(docstring_comment_format("Python"), Some("# REVIEW(line N): <comment>"));
+    assert_eq!(docstring_comment_format("cobol"), None);
+}
+
+#[test]
+fn test_output_format_instructions() {
+    let format: OutputFormat = "docstring-comments".parse().unwrap();
+    assert_eq!(format, OutputFormat::DocstringComments);
+    assert!(format
+        .instructions(Some("python"))
+        .contains("`# REVIEW(line N): <comment>`"));
+    assert!(format
+        .instructions(None)
+        .contains("using the comment syntax of the file's language"));
+
+    assert_eq!(
+        OutputFormat::Markdown.instructions(Some("python")),
+        "\nOutput the review in Markdown format.\n"
+    );
+}
+
+#[test]
+fn test_output_format_names() {
+    // Each name parses to the format that displays as it, so clap and the parser agree
+    assert_eq!(OUTPUT_FORMATS.len(), OutputFormat::ALL.len());
+    for name in OUTPUT_FORMATS {
+        assert_eq!(name.parse::<OutputFormat>().unwrap().to_string(), *name);
+    }
+    assert_eq!("jira".parse(), Ok(OutputFormat::Jira));
+    assert_eq!(OutputFormat::GitHubActions(AnnotationLevel::Error).to_string(), "github-actions");
+    assert_eq!("pdf".parse::<OutputFormat>(), Err("unknown output format `pdf`".to_string()));
+
+    assert!(OutputFormat::Html.instructions(None).contains("HTML format"));
+    assert!(OutputFormat::Jira.instructions(None).contains("Jira wiki markup"));
+    assert_eq!(OutputFormat::Json.instructions(None), format!("\n{}", JSON_OUTPUT_SCHEMA));
+    assert!(JSON_OUTPUT_SCHEMA.contains("\"severity\": \"error\" | \"warning\" | \"info\""));
+}
+
+#[test]
+fn test_language_for_path() {
+    assert_eq!(language_for_path(Path::new("src/main.rs")), Some("rust"));
+    assert_eq!(language_for_path(Path::new("script.PY")), Some("python"));
+    assert_eq!(language_for_path(Path::new("Makefile")), None);
+}
+
+#[test]
+fn test_github_actions_annotation_level() {
+    let format: OutputFormat = "github-actions".parse().unwrap();
+    assert_eq!(format, OutputFormat::GitHubActions(AnnotationLevel::Warning));
+    assert!(format.instructions(None).contains("`::warning file=PATH,line=N::MESSAGE`"));
+
+    let notice = format.with_annotation_level("notice".parse().unwrap());
+    assert!(notice
+        .instructions(None)
+        .contains("`::notice file=PATH,line=N::MESSAGE`"));
+    // Only GitHub Actions output has an annotation level
+    assert_eq!(
+        OutputFormat::Markdown.with_annotation_level(AnnotationLevel::Error),
+        OutputFormat::Markdown
+    );
+}
+
+#[test]
+fn test_unfence() {
+    assert_eq!(unfence("```\n::error file=a.rs,line=1::Bad\n```\n"), "::error file=a.rs,line=1::Bad");
+    // A longer fence may hold shorter ones
+    assert_eq!(unfence("````text\nA\n```\nB\n```\n````"), "A\n```\nB\n```");
+    assert_eq!(unfence("```\nA\n```\nThen B\n```\nC\n```"), "```\nA\n```\nThen B\n```\nC\n```");
+    assert_eq!(unfence("No fence"), "No fence");
+}
+
+#[test]
+fn test_format_mismatch() {
+    let markdown = "## Summary\n\nThe change renames `total` to `sum`.\n\n### Issues\n\n- **src/lib.rs:12**: the old name is still used in the doc comment.\n";
+    let asciidoc = "= Review\n\n== Summary\n\nThe change renames `total` to `sum`.\n\n== Issues\n\n* src/lib.rs:12: the old name is still used in the doc comment.\n";
+    let mediawiki = "== Summary ==\nThe change renames <code>total</code>.\n\n== Issues ==\n* src/lib.rs:12: the old name is still used.\n";
+    assert_eq!(format_mismatch(markdown, &OutputFormat::Markdown), None);
+    assert_eq!(format_mismatch(asciidoc, &OutputFormat::AsciiDoc), None);
+    assert_eq!(format_mismatch(mediawiki, &OutputFormat::MediaWiki), None);
+
+    // Markdown when AsciiDoc was asked for, and the other way round
+    assert_eq!(
+        format_mismatch(markdown, &OutputFormat::AsciiDoc).as_deref(),
+        Some("2 Markdown headings (`# Title`), but 0 AsciiDoc ones (`= Title`)")
+    );
+    assert_eq!(
+        format_mismatch(asciidoc, &OutputFormat::Markdown).as_deref(),
+        Some("3 AsciiDoc headings (`= Title`), but 0 Markdown ones (`# Title`)")
+    );
+    // MediaWiki's closed headings aren't taken for AsciiDoc's
+    assert_eq!(
+        format_mismatch(mediawiki, &OutputFormat::AsciiDoc).as_deref(),
+        Some("2 MediaWiki headings (`== Title ==`), but 0 AsciiDoc ones (`= Title`)")
+    );
+
+    // A `#` comment in a code block isn't a heading
+    let quoted = "== Issues\n\n```python\n# the old name\ntotal = 1\n```\n";
+    assert_eq!(format_mismatch(quoted, &OutputFormat::AsciiDoc), None);
+
+    assert_eq!(format_mismatch(markdown, &OutputFormat::Html).as_deref(), Some("no HTML tags, and 2 Markdown headings (`# Title`)"));
+    assert_eq!(format_mismatch("<h2>Summary</h2>\n<p>Fine.</p>", &OutputFormat::Html), None);
+    assert_eq!(
+        format_mismatch("h2. Summary\n\n```\nlet x = 1;\n```", &OutputFormat::Jira).as_deref(),
+        Some("Markdown code fences, where Jira's are {code}")
+    );
+    assert_eq!(
+        format_mismatch(
+            "Here are the annotations:\n::warning file=a.rs,line=1::Unused import",
+            &OutputFormat::GitHubActions(AnnotationLevel::Warning)
+        )
+        .as_deref(),
+        Some("1 line that isn't one of the workflow commands (`::warning file=PATH,line=N::MESSAGE`)")
+    );
+    assert_eq!(format_mismatch("Looks fine.", &OutputFormat::Json), None);
+}
+
+#[test]
+fn test_has_review_issues() {
+    let markdown = OutputFormat::Markdown;
+    assert!(has_review_issues("## Issues\n\n[ISSUE] src/lib.rs:12: `total` can overflow.", &markdown));
+    assert!(has_review_issues("1. **Severity:** High. The lock is never released.", &markdown));
+    assert!(has_review_issues("- file: a.rs\n  severity: critical\n", &OutputFormat::AsciiDoc));
+    assert!(!has_review_issues("## Summary\n\nNo issues found. The change looks good.", &markdown));
+    assert!(!has_review_issues("A nit, low severity: high-level naming could be clearer.", &markdown));
+
+    let json = OutputFormat::Json;
+    assert!(has_review_issues(r#"[{"file": "a.rs", "line": 1, "severity": "info", "comment": "Rename it."}]"#, &json));
+    assert!(has_review_issues("```json\n{\"summary\": \"One nit.\", \"findings\": [{\"comment\": \"Rename it.\"}]}\n```", &json));
+    assert!(!has_review_issues("[]", &json));
+
+    // A SARIF log counts its results, whatever format was asked for
+    let sarif = r#"{"version": "2.1.0", "runs": [{"tool": {"driver": {"name": "review"}}, "results": [{"ruleId": "naming", "message": {"text": "Rename it."}}]}]}"#;
+    assert!(has_review_issues(sarif, &markdown));
+    assert!(!has_review_issues(r#"{"version": "2.1.0", "runs": [{"results": []}]}"#, &json));
+
+    let actions = OutputFormat::GitHubActions(AnnotationLevel::Warning);
+    assert!(has_review_issues("::warning file=a.rs,line=1::Unused import", &actions));
+    assert!(!has_review_issues("::notice file=a.rs,line=1::Consider a shorter name", &actions));
+    assert!(has_review_issues("// REVIEW(line 4): this can panic", &OutputFormat::DocstringComments));
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::partial_clone::{
+    is_missing_object_error, parse_partial_clone_config, parse_raw_diff, Checkout, RawChange,
+};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_partial_clone_config() {
+    let config = "remote.origin.promisor true\nremote.origin.partialclonefilter blob:none\n";
+    assert_eq!(parse_partial_clone_config(config), Some("blob:none".to_string()));
+    // Older clones only name the promisor remote
+    assert_eq!(parse_partial_clone_config("extensions.partialclone origin\n"), Some("unknown".to_string()));
+    assert_eq!(parse_partial_clone_config("remote.origin.promisor false\n"), None);
+    assert_eq!(parse_partial_clone_config(""), None);
+}
+
+#[test]
+fn test_missing_object_errors() {
+    assert!(is_missing_object_error(
+        "fatal: could not fetch 5626abf0f72e58d7a153368ba57db4c673c0e171 from promisor remote\n"
+    ));
+    assert!(is_missing_object_error("warning: lazy fetching disabled; some objects may not be available\n"));
+    assert!(!is_missing_object_error("fatal: bad revision 'nope'\n"));
+}
+
+#[test]
+fn test_raw_diff() {
+    let raw = ":100644 100644 5626abf0f72e58d7a153368ba57db4c673c0e171 f719efd430d52bcfc8566a43b2eb655688d38871 M\tsrc/a b.txt\n\
+               :000000 100644 0000000000000000000000000000000000000000 3e757656cf36eca53338e520d134963a44f793f8 A\tb.txt\n\
+               not a raw line\n";
+    assert_eq!(
+        parse_raw_diff(raw),
+        vec![
+            RawChange {
+                path: "src/a b.txt".to_string(),
+                old: "5626abf0f72e58d7a153368ba57db4c673c0e171".to_string(),
+                new: "f719efd430d52bcfc8566a43b2eb655688d38871".to_string(),
+            },
+            RawChange {
+                path: "b.txt".to_string(),
+                old: "0000000000000000000000000000000000000000".to_string(),
+                new: "3e757656cf36eca53338e520d134963a44f793f8".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_warnings() {
+    assert!(Checkout::default().warnings(false).is_empty());
+
+    let checkout = Checkout {
+        partial_clone_filter: Some("blob:none".to_string()),
+        sparse_paths: Some(vec!["src".to_string()]),
+    };
+    let warnings = checkout.warnings(false);
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings[0].contains("partial clone (blob:none)"), "{}", warnings[0]);
+    assert!(warnings[1].contains("--allow-fetch"), "{}", warnings[1]);
+    assert!(warnings[2].contains("sparse checkout of 1 path:"), "{}", warnings[2]);
+    assert_eq!(checkout.warnings(true).len(), 2);
+}
+
+#[test]
+fn test_offline_partial_clone_reviews_what_it_has() {
+    let root = temp_dir("partial_clone");
+    let origin = root.join("origin");
+    fs::create_dir_all(&origin).unwrap();
+
+    init_repo(&origin);
+    git(&origin, &["config", "uploadpack.allowFilter", "true"]);
+    fs::write(origin.join("old.txt"), "one\n").unwrap();
+    git(&origin, &["add", "."]);
+    git(&origin, &["commit", "-q", "-m", "one"]);
+    fs::write(origin.join("old.txt"), "two\n").unwrap();
+    fs::write(origin.join("new.txt"), "added\n").unwrap();
+    git(&origin, &["add", "."]);
+    git(&origin, &["commit", "-q", "-m", "two"]);
+
+    // Only the checked out files are fetched; the first version of old.txt stays on the server,
+    // which then goes away
+    let url = format!("file://{}", origin.display());
+    git(&root, &["clone", "-q", "--filter=blob:none", &url, "clone"]);
+    fs::remove_dir_all(&origin).unwrap();
+
+    let clone = root.join("clone");
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&clone)
+        .args(["HEAD~1", "HEAD"])
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("diff --git a/new.txt b/new.txt"), "{}", stdout);
+    assert!(!stdout.contains("a/old.txt"), "{}", stdout);
+    assert!(stdout.contains("partial clone (blob:none)"), "{}", stdout);
+    assert!(stdout.contains("couldn't be fetched: old.txt"), "{}", stdout);
+}
@@ -0,0 +1,52 @@
+use llm_code_review::error::ReviewError;
+use llm_code_review::persona::Personas;
+use llm_code_review::system_prompt::{PromptSection, SystemPrompt};
+use std::path::Path;
+
+#[test]
+fn test_persona_overlays_role_and_tone() {
+    let persona = Personas::default().get("strict-gatekeeper").unwrap();
+    let prompt = persona.apply(SystemPrompt::default());
+    let default = SystemPrompt::default();
+
+    assert!(prompt.section(PromptSection::Role).contains("gatekeeper"));
+    assert!(prompt.section(PromptSection::Tone).contains("uncompromising"));
+    // The rest of the prompt is left for the other options to change
+    for section in [PromptSection::ReviewRules, PromptSection::OutputRules, PromptSection::SeverityRubric] {
+        assert_eq!(prompt.section(section), default.section(section));
+    }
+}
+
+#[test]
+fn test_unknown_persona() {
+    match Personas::default().get("pirate") {
+        Err(ReviewError::Config(msg)) => {
+            assert!(msg.contains("mentor, strict-gatekeeper, security-auditor, terse-senior"), "{}", msg)
+        }
+        other => panic!("expected Config error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_personas_from_file() {
+    let mut personas = Personas::default();
+    personas
+        .extend_from_file(Path::new("tests/fixtures/personas.toml"))
+        .unwrap();
+
+    // A persona may replace only the tone
+    let buddy = personas.get("pairing-buddy").unwrap();
+    let prompt = buddy.apply(SystemPrompt::default());
+    assert_eq!(
+        prompt.section(PromptSection::Role),
+        SystemPrompt::default().section(PromptSection::Role)
+    );
+    assert_eq!(prompt.section(PromptSection::Tone), "Think out loud and phrase suggestions as questions.\n");
+
+    // Built-in personas can be redefined
+    assert_eq!(personas.get("terse-senior").unwrap().tone.as_deref(), Some("Five lines at most.\n"));
+
+    let list = personas.list();
+    assert_eq!(list.lines().count(), 5);
+    assert!(list.ends_with("pairing-buddy      Friendly, thinks out loud"), "{}", list);
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "plugins")]
+
+mod common;
+
+use common::temp_dir;
+use llm_code_review::error::ReviewError;
+use llm_code_review::plugin::Plugins;
+use std::env::consts::DLL_EXTENSION;
+use std::fs;
+
+#[test]
+fn test_load_plugins() {
+    let dir = temp_dir("plugins");
+
+    // Files that aren't libraries are ignored, and no plugins leaves everything unchanged
+    fs::write(dir.join("README.md"), "not a plugin").unwrap();
+    let plugins = Plugins::load(&dir).unwrap();
+    assert!(plugins.is_empty());
+    assert_eq!(plugins.transform_diff("+a".to_string()), "+a");
+    assert_eq!(plugins.transform_prompt("prompt".to_string()), "prompt");
+
+    let bad = dir.join(format!("bad.{}", DLL_EXTENSION));
+    fs::write(&bad, "not a library either").unwrap();
+    match Plugins::load(&dir) {
+        Err(ReviewError::Plugin(path, _)) => assert_eq!(path, bad),
+        other => panic!("expected a plugin error, got {:?}", other),
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
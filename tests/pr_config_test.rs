@@ -0,0 +1,99 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::findings::Severity;
+use llm_code_review::pr_config::{config_block, strip_config_block, PrConfig};
+use std::fs;
+use std::process::Command;
+
+const DESCRIPTION: &str = "Adds a discount.\n\n```llm-review\npreset: security-auditor\ndepth: short  # keep it brief\nexclude: [docs/**, \"*.snap\"]\nmin-severity: warning\n```\n\nCloses #12.\n";
+
+#[test]
+fn test_pr_config() {
+    let config = PrConfig::from_description(DESCRIPTION).unwrap().unwrap();
+    assert_eq!(
+        config,
+        PrConfig {
+            preset: Some("security-auditor".to_string()),
+            depth: Some("short".to_string()),
+            exclude: vec!["docs/**".to_string(), "*.snap".to_string()],
+            min_severity: Some(Severity::Warning),
+            ignored: Vec::new(),
+        }
+    );
+    assert_eq!(strip_config_block(DESCRIPTION), "Adds a discount.\n\n\nCloses #12.");
+    assert_eq!(PrConfig::from_description("No settings here.").unwrap(), None);
+
+    // TOML, and YAML lists written as items
+    let toml = PrConfig::parse("depth = \"long\"\nexclude = \"vendor/**\"\nllm_provider_config = \"/etc/passwd\"\n").unwrap();
+    assert_eq!((toml.depth.as_deref(), toml.exclude.as_slice()), (Some("long"), ["vendor/**".to_string()].as_slice()));
+    assert_eq!(toml.ignored, ["llm_provider_config"]);
+    let yaml = PrConfig::parse("exclude:\n  - docs/**\n  - '*.lock'\n").unwrap();
+    assert_eq!(yaml.exclude, ["docs/**", "*.lock"]);
+
+    assert_eq!(config_block("~~~llm-review\ndepth: short\n"), Some("depth: short\n".to_string()));
+}
+
+#[test]
+fn test_malformed_pr_config() {
+    let error = |block: &str| PrConfig::parse(block).unwrap_err();
+    assert_eq!(error("depth: short\nthis is not a setting\n"), "line 2: expected `key: value`, found `this is not a setting`");
+    assert_eq!(error("depth: deep\n"), "`depth` must be one of auto, short, medium, long, unlimited, not `deep`");
+    assert_eq!(error("min-severity: critical\n"), "`min-severity`: unknown severity `critical`");
+    assert!(error("depth = \"short\"\nexclude = [\n").starts_with("line 2: "), "{}", error("depth = \"short\"\nexclude = [\n"));
+    let description = "```llm-review\ndepth = 3\n```";
+    assert_eq!(
+        PrConfig::from_description(description).unwrap_err().to_string(),
+        "Invalid configuration: the ```llm-review block in the pull request description: `depth` must be a string, not 3"
+    );
+}
+
+#[test]
+fn test_review_with_pr_config() {
+    let dir = temp_dir("pr_config");
+    fs::create_dir_all(dir.join("docs")).unwrap();
+    init_repo(&dir);
+    fs::write(dir.join("cart.py"), "total = 1\n").unwrap();
+    fs::write(dir.join("docs/guide.md"), "Old guide\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("cart.py"), "total = 2\n").unwrap();
+    fs::write(dir.join("docs/guide.md"), "New guide\n").unwrap();
+
+    let event = dir.join("event.json");
+    let review = |description: &str, args: &[&str]| {
+        let pull_request = serde_json::json!({
+            "pull_request": {"title": "Discounts", "body": description, "base": {"ref": "main"}, "head": {"ref": "discounts"}}
+        });
+        fs::write(&event, pull_request.to_string()).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("GITHUB_EVENT_NAME", "pull_request")
+            .env("GITHUB_EVENT_PATH", &event)
+            .arg("--github-actions-pr-context")
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+
+    let prompt = review(DESCRIPTION, &[]);
+    assert!(prompt.contains("+total = 2") && !prompt.contains("+New guide"), "{}", prompt);
+    assert!(prompt.contains("Report only findings of severity error or warning"), "{}", prompt);
+    assert!(prompt.contains("## Pull Request Description\nAdds a discount.\n\n\nCloses #12."), "{}", prompt);
+    assert!(!prompt.contains("min-severity: warning\n```"), "{}", prompt);
+
+    // Flags on the command line win
+    let prompt = review(DESCRIPTION, &["--min-severity", "error", "--review-length", "long"]);
+    assert!(prompt.contains("Report only findings of severity error, and"), "{}", prompt);
+    assert!(!prompt.contains("Report only findings of severity error or warning"), "{}", prompt);
+
+    let prompt = review("```llm-review\nprovider: local\ndepth: deep\n```\n", &[]);
+    assert!(prompt.contains("`depth` must be one of auto"), "{}", prompt);
+    assert!(prompt.contains("ignoring it") && prompt.contains("+New guide"), "{}", prompt);
+
+    let prompt = review("```llm-review\nprovider: local\n```\n", &[]);
+    assert!(prompt.contains("The pull request description can't set provider"), "{}", prompt);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::preflight::{check_prompt, PromptProblem};
+use std::fs;
+use std::process::Command;
+
+const DIFF: &str = "\
+diff --git a/src/cart.rs b/src/cart.rs
+index 1234567..89abcde 100644
+--- a/src/cart.rs
++++ b/src/cart.rs
+@@ -10,6 +10,6 @@ impl Cart {
+     fn total(&self) -> u64 {
+-        self.items.iter().map(|item| item.price).sum()
++        self.items.iter().map(|item| item.price * item.quantity).sum()
+     }
+ … 3 unchanged lines …
+";
+
+fn prompt(diff: &str) -> String {
+    format!("You are a code reviewer.\n\n# PR Code\n\n{}", diff)
+}
+
+#[test]
+fn test_sound_prompt() {
+    assert_eq!(check_prompt(&prompt(DIFF), "PR Code", DIFF, true), Vec::new());
+    // A blank line at the end isn't a context line too many
+    let trailing = format!("{}\n", DIFF);
+    assert_eq!(check_prompt(&prompt(&trailing), "PR Code", &trailing, true), Vec::new());
+    // Code given with --raw-code isn't a diff
+    let code = "fn main() {}\n";
+    assert_eq!(check_prompt(&prompt(code), "Code to Review", code, false), Vec::new());
+}
+
+#[test]
+fn test_empty_code_section() {
+    assert_eq!(
+        check_prompt(&prompt("\n"), "PR Code", "\n", true),
+        vec![PromptProblem::EmptyCode { heading: "PR Code".to_string() }]
+    );
+    assert_eq!(
+        check_prompt(&prompt(""), "Code to Review", "", false)[0].to_string(),
+        "the \"# Code to Review\" section is empty"
+    );
+}
+
+#[test]
+fn test_no_changed_lines() {
+    let context_only = DIFF.replace("-        self", "         self").replace("+        self.items.iter().map(|item| item.price * item.quantity).sum()\n", "");
+    assert_eq!(check_prompt(&prompt(&context_only), "PR Code", &context_only, true), vec![PromptProblem::NoChangedLines]);
+    // A diff with no hunks at all, such as a mode change, isn't flagged
+    let mode_change = "diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n";
+    assert_eq!(check_prompt(&prompt(mode_change), "PR Code", mode_change, true), Vec::new());
+}
+
+#[test]
+fn test_unbalanced_hunk() {
+    // Cut off after the removed line, as a careless truncation would
+    let cut: String = DIFF.lines().take(7).map(|line| format!("{}\n", line)).collect();
+    let problems = check_prompt(&prompt(&cut), "PR Code", &cut, true);
+    assert_eq!(
+        problems,
+        vec![PromptProblem::UnbalancedHunk {
+            path: "src/cart.rs".to_string(),
+            header: "@@ -10,6 +10,6 @@ impl Cart {".to_string(),
+            expected: (6, 6),
+            found: (2, 1),
+        }]
+    );
+    assert_eq!(
+        problems[0].to_string(),
+        "src/cart.rs: hunk `@@ -10,6 +10,6 @@ impl Cart {` should have 6 old and 6 new lines, but has 2 and 1"
+    );
+}
+
+#[test]
+fn test_ansi_escapes() {
+    let colored = DIFF.replace("-        self", "\x1b[31m-        self").replace("+        self", "\x1b[32m+        self");
+    let problems = check_prompt(&prompt(&colored), "PR Code", &colored, true);
+    assert!(problems.contains(&PromptProblem::AnsiEscapes { lines: 2, first_line: 11 }), "{:?}", problems);
+    assert!(problems.last().unwrap().to_string().contains("was the diff made with --color?"));
+}
+
+#[test]
+fn test_broken_prompt_not_sent() {
+    let dir = temp_dir("preflight");
+    init_repo(&dir);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    // Nothing listens here, so a prompt that got as far as being sent would fail differently
+    fs::write(
+        dir.join("providers.toml"),
+        "[[provider]]\nname = \"mock\"\nendpoint = \"http://127.0.0.1:9/v1/messages\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n",
+    )
+    .unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(["--llm-provider-config", "providers.toml", "--send", "--heartbeat-secs", "0"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+    // A diff with nothing changed in it
+    fs::write(dir.join("unchanged.diff"), "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n one\n").unwrap();
+    let (success, output) = review(&["--diff-command", "cat unchanged.diff"]);
+    assert!(!success);
+    assert!(output.contains("The prompt looks broken, so it wasn't sent:\n- the diff has only context lines"), "{}", output);
+    assert!(output.contains("Send it anyway with --force-send."), "{}", output);
+
+    let (success, output) = review(&["--context", "\x1b[1mImportant\x1b[0m: check the rounding"]);
+    assert!(!success);
+    assert!(output.contains("terminal escape codes"), "{}", output);
+
+    let (success, output) = review(&["--force-send", "--diff-command", "cat unchanged.diff"]);
+    assert!(!success);
+    assert!(output.contains("Sending anyway: the diff has only context lines"), "{}", output);
+    assert!(!output.contains("The prompt looks broken"), "{}", output);
+}
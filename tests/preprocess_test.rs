@@ -0,0 +1,112 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::error::ReviewError;
+use llm_code_review::preprocess::{preprocess, run_preprocessor, validate_diff};
+use std::fs;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+ fn a() {}
+-// PROPRIETARY: internal only
++fn b() {}
+";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn commands(commands: &[&str]) -> Vec<String> {
+    commands.iter().map(|command| command.to_string()).collect()
+}
+
+#[test]
+fn test_preprocess_in_order() {
+    assert_eq!(preprocess(&[], DIFF.to_string(), TIMEOUT).unwrap(), DIFF);
+    assert_eq!(preprocess(&commands(&["cat"]), DIFF.to_string(), TIMEOUT).unwrap(), DIFF);
+
+    // The second command sees what the first wrote
+    let hooks = commands(&["sed s/PROPRIETARY/REDACTED/", "sed s/REDACTED:/[redacted]/"]);
+    let output = preprocess(&hooks, DIFF.to_string(), TIMEOUT).unwrap();
+    assert!(output.contains("-// [redacted] internal only\n"), "{}", output);
+
+    // A command emptying the diff leaves nothing to review, and none is run on an empty diff
+    assert_eq!(preprocess(&commands(&["true"]), DIFF.to_string(), TIMEOUT).unwrap(), "");
+    assert_eq!(preprocess(&commands(&["false"]), String::new(), TIMEOUT).unwrap(), "");
+}
+
+#[test]
+fn test_failing_preprocessor_named() {
+    let hooks = commands(&["cat", "sed -n 1,4p", "cat"]);
+    match preprocess(&hooks, DIFF.to_string(), TIMEOUT) {
+        Err(ReviewError::Preprocess(command, message)) => {
+            assert_eq!(command, "sed -n 1,4p");
+            assert!(message.contains("src/lib.rs with 0 old and 0 new lines"), "{}", message);
+        }
+        other => panic!("expected a preprocess error, got {:?}", other),
+    }
+    let error = preprocess(&commands(&["no-such-preprocessor --strip"]), DIFF.to_string(), TIMEOUT).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The --preprocess command `no-such-preprocessor --strip` failed: no-such-preprocessor is not installed"
+    );
+    let error = preprocess(&commands(&["echo not a diff"]), DIFF.to_string(), TIMEOUT).unwrap_err();
+    assert!(error.to_string().ends_with("`echo not a diff` failed: its output isn't a unified diff"), "{}", error);
+}
+
+#[test]
+fn test_run_preprocessor() {
+    let failed = run_preprocessor("ls /no/such/directory", DIFF, TIMEOUT).unwrap_err();
+    assert!(failed.starts_with("it exited with exit status: 2: "), "{}", failed);
+    assert_eq!(run_preprocessor("  ", DIFF, TIMEOUT).unwrap_err(), "the command is empty");
+
+    let started = Instant::now();
+    let slow = run_preprocessor("sleep 30", DIFF, Duration::from_millis(200)).unwrap_err();
+    assert_eq!(slow, "it took longer than 0.2s");
+    assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+}
+
+#[test]
+fn test_validate_diff() {
+    assert_eq!(validate_diff(DIFF), Ok(()));
+    assert_eq!(validate_diff("\n"), Ok(()));
+    assert_eq!(validate_diff(&format!("{}\\ No newline at end of file\n", DIFF)), Ok(()));
+    assert!(validate_diff("Nothing changed.\n").is_err());
+    assert_eq!(
+        validate_diff(&DIFF.replace("@@ -1,2 +1,2 @@", "@@ one @@")).unwrap_err(),
+        "its output has a hunk header that doesn't parse in src/lib.rs: @@ one @@"
+    );
+    assert_eq!(
+        validate_diff(&format!("{}+fn c() {{}}\n", DIFF)).unwrap_err(),
+        "its output has a hunk in src/lib.rs with 2 old and 3 new lines where the header `@@ -1,2 +1,2 @@` says 2 and 2"
+    );
+}
+
+#[test]
+fn test_preprocess_option() {
+    let dir = temp_dir("preprocess");
+    fs::write(dir.join("change.diff"), DIFF).unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .args(["--diff-file", "change.diff"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review")
+    };
+    let output = run(&["--preprocess", "sed s/PROPRIETARY/REDACTED/"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("-// REDACTED: internal only") && !stdout.contains("PROPRIETARY"), "{}", stdout);
+
+    let output = run(&["--preprocess", "cat", "--preprocess", "head -c 20"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("The --preprocess command `head -c 20` failed"), "{}", stderr);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
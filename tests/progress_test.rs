@@ -0,0 +1,93 @@
+use clap::Parser;
+use llm_code_review::error::ReviewError;
+use llm_code_review::progress::{format_elapsed, heartbeat_message, watch_generation, GenerationLimits, Progress};
+use llm_code_review::review::Cli;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_heartbeat_message() {
+    assert_eq!(format_elapsed(Duration::from_millis(45_900)), "45s");
+    assert_eq!(format_elapsed(Duration::from_secs(187)), "3m 07s");
+    assert_eq!(heartbeat_message(Duration::from_secs(65), 0, None), "Waiting for the first token: 1m 05s");
+    assert_eq!(
+        heartbeat_message(Duration::from_secs(150), 412, Some(Duration::from_secs(110))),
+        "Generating: 2m 30s, 412 tokens so far (first after 1m 50s)"
+    );
+    assert_eq!(
+        heartbeat_message(Duration::from_secs(3), 1, Some(Duration::ZERO)),
+        "Generating: 3s, 1 token so far (first after 0s)"
+    );
+}
+
+#[test]
+fn test_first_token() {
+    let limits = GenerationLimits { heartbeat: None, max_wait_first_token: Some(Duration::from_secs(60)) };
+    let progress = Progress::new(Instant::now() - Duration::from_secs(20));
+    let timeout = progress.first_token_timeout(&limits).unwrap();
+    assert!(timeout <= Duration::from_secs(40) && timeout > Duration::from_secs(39), "{:?}", timeout);
+    assert!(progress.check_first_token(&limits).is_ok());
+
+    // Chunks without tokens don't count
+    progress.received(0);
+    assert_eq!(progress.first_token_after(), None);
+    progress.received(3);
+    progress.received(2);
+    assert_eq!(progress.tokens(), 5);
+    assert!(progress.first_token_after().unwrap() >= Duration::from_secs(20));
+    assert_eq!(progress.first_token_timeout(&limits), None);
+
+    let overdue = Progress::new(Instant::now() - Duration::from_secs(61));
+    assert!(matches!(overdue.check_first_token(&limits), Err(ReviewError::FirstTokenTimeout(_))));
+    assert!(overdue.check_first_token(&GenerationLimits::default()).is_ok());
+}
+
+#[test]
+fn test_watch_generation() {
+    let limits = GenerationLimits {
+        heartbeat: Some(Duration::from_millis(10)),
+        max_wait_first_token: Some(Duration::from_millis(50)),
+    };
+
+    // Streaming: tokens arrive in chunks
+    let review = watch_generation(limits, |progress| {
+        let mut review = String::new();
+        for chunk in ["Looks ", "good"] {
+            progress.check_first_token(&limits)?;
+            thread::sleep(Duration::from_millis(15));
+            progress.received(1);
+            review.push_str(chunk);
+        }
+        Ok(review)
+    });
+    assert_eq!(review.unwrap(), "Looks good");
+
+    // Non-streaming: the request times out waiting for the whole response
+    let timed_out = watch_generation(limits, |progress| -> Result<String, ReviewError> {
+        thread::sleep(progress.first_token_timeout(&limits).unwrap());
+        Err(ReviewError::Config("timeout: receiving response".to_string()))
+    });
+    let message = timed_out.unwrap_err().to_string();
+    assert!(message.contains("may still be loading"), "{}", message);
+    assert!(message.contains("--max-wait-first-token"), "{}", message);
+
+    // Other failures are passed on as they are
+    let refused = watch_generation(limits, |_| -> Result<String, ReviewError> {
+        Err(ReviewError::Config("connection refused".to_string()))
+    });
+    assert!(refused.unwrap_err().to_string().contains("connection refused"));
+}
+
+#[test]
+fn test_generation_limits() {
+    let cli = Cli::parse_from(["llm_code_review", "--max-wait-first-token", "300"]);
+    assert_eq!(
+        cli.generation_limits(),
+        GenerationLimits {
+            heartbeat: Some(Duration::from_secs(10)),
+            max_wait_first_token: Some(Duration::from_secs(300)),
+        }
+    );
+    let cli = Cli::parse_from(["llm_code_review", "--heartbeat-secs", "0"]);
+    assert_eq!(cli.generation_limits(), GenerationLimits::default());
+}
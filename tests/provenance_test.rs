@@ -0,0 +1,59 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::provenance::{rfc3339_utc, Provenance};
+use llm_code_review::locale::Locale;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn test_rfc3339_utc() {
+    assert_eq!(rfc3339_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(rfc3339_utc(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(rfc3339_utc(1_767_225_599), "2025-12-31T23:59:59Z");
+}
+
+#[test]
+fn test_describe_without_repository() {
+    let provenance = Provenance::new("+added\n", Some("local".to_string()));
+    assert_eq!(provenance.repository, None);
+    assert_eq!(provenance.tool_version, env!("CARGO_PKG_VERSION"));
+
+    let described = provenance.describe(Locale::En);
+    assert!(described.starts_with("- Diff SHA-256: "));
+    assert!(described.ends_with("- Provider: local"));
+    assert!(!described.contains("Worktree"));
+    // The hash only depends on the diff
+    assert_eq!(Provenance::new("+added\n", None).diff_sha256, provenance.diff_sha256);
+}
+
+#[test]
+fn test_git_details() {
+    let dir = temp_dir("provenance");
+
+    init_repo(&dir);
+    git(&dir, &["checkout", "-q", "-b", "main"]);
+    fs::write(dir.join("code.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("review.md"), "old review\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+
+    // This is the only test in this binary that uses the current directory
+    std::env::set_current_dir(&dir).unwrap();
+
+    // Rewriting the tool's own (tracked) output file doesn't make the worktree dirty
+    fs::write(dir.join("review.md"), "new review\n").unwrap();
+    let own_files = [PathBuf::from("review.md")];
+    let provenance = Provenance::new("", None).with_git_details(Some("main"), &own_files);
+    assert_eq!(provenance.branch.as_deref(), Some("main"));
+    assert_eq!(provenance.head.as_ref().map(String::len), Some(40));
+    assert_eq!(provenance.merge_base, provenance.head);
+    assert_eq!(provenance.dirty, Some(false));
+
+    fs::write(dir.join("code.rs"), "fn main() { todo!() }\n").unwrap();
+    let provenance = Provenance::new("", None).with_git_details(None, &own_files);
+    assert_eq!(provenance.dirty, Some(true));
+    assert_eq!(provenance.merge_base, None);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,35 @@
+use llm_code_review::error::ReviewError;
+use llm_code_review::provider::{load_provider_config, select_provider, ProviderFormat};
+use std::path::Path;
+
+const PROVIDERS: &str = "tests/fixtures/providers.toml";
+
+#[test]
+fn test_load_provider_config() {
+    let providers = load_provider_config(Path::new(PROVIDERS)).unwrap();
+    assert_eq!(providers.len(), 2);
+    assert_eq!(providers[0].name, "claude");
+    assert_eq!(providers[0].format, ProviderFormat::Anthropic);
+    assert_eq!(providers[1].max_tokens, 8000);
+    assert_eq!(providers[1].chars_per_token, 3);
+    assert_eq!(providers[1].format, ProviderFormat::OpenAi);
+    assert_eq!(providers[0].thread_ttl_secs, Some(3600));
+    assert_eq!(providers[1].thread_ttl_secs, None);
+    assert_eq!(providers[0].api_key_var(), Some("ANTHROPIC_API_KEY"));
+    assert_eq!(providers[1].api_key_var(), Some("LOCAL_LLM_KEY"));
+}
+
+#[test]
+fn test_select_provider() {
+    let providers = load_provider_config(Path::new(PROVIDERS)).unwrap();
+
+    assert_eq!(select_provider(providers.clone(), None).unwrap().name, "claude");
+    assert_eq!(
+        select_provider(providers.clone(), Some("local")).unwrap().endpoint,
+        "http://localhost:8080/v1/chat/completions"
+    );
+    match select_provider(providers, Some("missing")) {
+        Err(ReviewError::Config(msg)) => assert!(msg.contains("claude, local")),
+        other => panic!("expected Config error, got {:?}", other),
+    }
+}
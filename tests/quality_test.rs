@@ -0,0 +1,206 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::config::Config;
+use llm_code_review::diff::parse_diff;
+use llm_code_review::quality::{check_quality, QualityProblem, QualityThresholds};
+use llm_code_review::transcript::Transcript;
+use llm_code_review::usage::{Ledger, USAGE_FILE};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+
+/// A diff changing each of `lines` lines of src/cart.rs
+fn diff(lines: usize) -> String {
+    let mut diff = format!("diff --git a/src/cart.rs b/src/cart.rs\n--- a/src/cart.rs\n+++ b/src/cart.rs\n@@ -1,{0} +1,{0} @@\n", lines);
+    for i in 0..lines {
+        diff.push_str(&format!("-let a{} = 1;\n", i));
+    }
+    for i in 0..lines {
+        diff.push_str(&format!("+let b{} = 1;\n", i));
+    }
+    diff
+}
+
+const SHALLOW: &str = "The change looks reasonable. Consider adding tests. Naming could be clearer.";
+
+fn thorough() -> String {
+    "## Summary\nThe variables in src/cart.rs are renamed from `a0`..`a29` to `b0`..`b29`.\n\n## Issues\n\
+     - Line 1: `b0` says no more about what it holds than `a0` did; name it for the quantity.\n\
+     - The other twenty-nine are all set to 1, so a single constant would do.\n"
+        .to_string()
+}
+
+#[test]
+fn test_check_quality() {
+    let files = parse_diff(&diff(30));
+    let defaults = QualityThresholds::default();
+    assert_eq!(
+        check_quality(SHALLOW, &files, &defaults, false),
+        [
+            QualityProblem::TooShort { chars: SHALLOW.len(), required: 120, changed_lines: 60 },
+            QualityProblem::NoReference
+        ]
+    );
+    assert_eq!(check_quality(&thorough(), &files, &defaults, false), []);
+    // A line cited, or the file named, is a reference
+    assert_eq!(check_quality(&format!("{} See cart.rs.", SHALLOW.repeat(2)), &files, &defaults, false), []);
+    assert_eq!(check_quality(&format!("{} See L12.", SHALLOW.repeat(2)), &files, &defaults, false), []);
+    assert_eq!(
+        QualityProblem::TooShort { chars: 80, required: 120, changed_lines: 60 }.to_string(),
+        "it has 80 characters where 60 changed lines call for 120"
+    );
+
+    // Small changes aren't checked, and a large one doesn't need a review longer than the cap
+    assert_eq!(check_quality(SHALLOW, &parse_diff(&diff(20)), &defaults, false), []);
+    let huge = check_quality("", &parse_diff(&diff(5000)), &defaults, false);
+    assert!(matches!(huge[0], QualityProblem::TooShort { required: 4000, .. }), "{:?}", huge);
+}
+
+#[test]
+fn test_quality_thresholds() {
+    let files = parse_diff(&diff(30));
+    let sections = QualityThresholds {
+        required_sections: Some(vec!["Summary".to_string(), "Risks".to_string()]),
+        ..QualityThresholds::default()
+    };
+    assert_eq!(check_quality(&thorough(), &files, &sections, false), [QualityProblem::MissingSection("Risks".to_string())]);
+    // A bold line, with a colon or not, counts as a heading; JSON has none to look for
+    let review = format!("{}\n**Risks:**\nNone.\n", thorough());
+    assert_eq!(check_quality(&review, &files, &sections, false), []);
+    let json = format!("[{}]", r#"{"path": "src/cart.rs", "line": 1, "severity": "info", "comment": "Rename b0."},"#.repeat(5).trim_end_matches(','));
+    assert_eq!(check_quality(&json, &files, &sections, true), []);
+
+    let lenient = QualityThresholds {
+        min_changed_lines: Some(10),
+        min_chars_per_line: Some(0.5),
+        require_reference: Some(false),
+        required_sections: None,
+    };
+    assert_eq!(check_quality(SHALLOW, &parse_diff(&diff(20)), &lenient, false), []);
+
+    let config = Config::parse("fallback_model = \"claude-opus-4-1\"\nmax_cost_usd = 0.5\n[quality]\nmin_changed_lines = 10\n").unwrap();
+    assert_eq!((config.fallback_model.as_deref(), config.max_cost_usd), (Some("claude-opus-4-1"), Some(0.5)));
+    let user = Config::parse("[quality]\nmin_changed_lines = 99\nrequire_reference = false\n").unwrap();
+    let quality = config.or(user).quality.unwrap();
+    assert_eq!((quality.min_changed_lines, quality.require_reference), (Some(10), Some(false)));
+    assert!(Config::parse("[quality]\nmin_lines = 10\n").unwrap_err().contains("min_lines"));
+    assert!(Config::parse("[quality]\nmin_chars_per_line = -1.0\n").unwrap_err().contains("0 or more"));
+    assert!(Config::parse("max_cost_usd = 0.0\n").unwrap_err().contains("more than 0"));
+}
+
+/// Answer a request with each of `responses` in turn, returning the requests' bodies
+fn serve(listener: TcpListener, responses: Vec<String>) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            requests.push(String::from_utf8(body).unwrap());
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            );
+            reader.get_mut().write_all(reply.as_bytes()).unwrap();
+        }
+        requests
+    })
+}
+
+fn answer(text: &str) -> String {
+    serde_json::json!({"content": [{"type": "text", "text": text}]}).to_string()
+}
+
+#[test]
+fn test_fallback_model() {
+    let dir = temp_dir("quality");
+    let repo = dir.join("repo");
+    fs::create_dir_all(repo.join("src")).unwrap();
+    init_repo(&repo);
+    let source = |name: &str| (0..30).map(|i| format!("let {}{} = 1;\n", name, i)).collect::<String>();
+    fs::write(repo.join("src/cart.rs"), source("a")).unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "one"]);
+    fs::write(repo.join("src/cart.rs"), source("b")).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/v1/messages", listener.local_addr().unwrap());
+    fs::write(
+        repo.join("providers.toml"),
+        format!("[[provider]]\nname = \"mock\"\nendpoint = \"{}\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n", endpoint),
+    )
+    .unwrap();
+    let config_home = dir.join("config");
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&repo)
+            .env("XDG_CONFIG_HOME", &config_home)
+            .args(["--llm-provider-config", "providers.toml", "--model", "claude-haiku-4-5", "--send", "--heartbeat-secs", "0"])
+            .args(["--fallback-model", "claude-opus-4-1"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        (output.status.success(), stdout, String::from_utf8_lossy(&output.stderr).to_string())
+    };
+
+    // The shallow review is re-run once with the fallback model, whose review is kept
+    let server = serve(listener, vec![answer(SHALLOW), answer(&thorough())]);
+    let (success, stdout, stderr) = run(&["--save-transcript", "review.json"]);
+    let requests = server.join().unwrap();
+    assert!(success, "{}{}", stdout, stderr);
+    assert!(requests[0].contains("\"model\":\"claude-haiku-4-5\"") && requests[1].contains("\"model\":\"claude-opus-4-1\""));
+    assert!(stderr.contains("looks too shallow: it has 76 characters"), "{}", stderr);
+    assert!(stdout.starts_with("## Summary\n") && !stdout.contains(SHALLOW), "{}", stdout);
+    assert!(
+        stdout.contains("_Reviewed again by claude-opus-4-1, as the review by claude-haiku-4-5 failed the quality checks: it has 76 characters where 60 changed lines call for 120; it names no changed file or line._"),
+        "{}",
+        stdout
+    );
+    let transcript = Transcript::load(&repo.join("review.json")).unwrap();
+    assert_eq!(transcript.model.as_deref(), Some("claude-opus-4-1"));
+    assert_eq!(transcript.escalation.unwrap().from_model, "claude-haiku-4-5");
+    let entries = Ledger::new(config_home.join("llm_code_review").join(USAGE_FILE)).load().unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.model.as_str()).collect::<Vec<_>>(), ["claude-haiku-4-5", "claude-opus-4-1"]);
+
+    // With room under --max-cost for the first request but not the re-run, the review is kept
+    let first_cost = entries[0].cost_usd.unwrap();
+    let listener = TcpListener::bind(endpoint.trim_start_matches("http://").trim_end_matches("/v1/messages")).unwrap();
+    let server = serve(listener, vec![answer(SHALLOW)]);
+    let (success, stdout, stderr) = run(&["--max-cost", &format!("{:.6}", first_cost * 5.0)]);
+    server.join().unwrap();
+    assert!(success, "{}{}", stdout, stderr);
+    assert!(stdout.starts_with(SHALLOW), "{}", stdout);
+    assert!(stderr.contains("taking the run over the --max-cost of $"), "{}", stderr);
+
+    // Over --confirm-threshold without a terminal to ask on, the prompt is sent without asking
+    let listener = TcpListener::bind(endpoint.trim_start_matches("http://").trim_end_matches("/v1/messages")).unwrap();
+    let server = serve(listener, vec![answer(&thorough())]);
+    let (success, stdout, stderr) = run(&["--confirm-threshold", "1"]);
+    server.join().unwrap();
+    assert!(success, "{}{}", stdout, stderr);
+    assert!(stdout.starts_with("## Summary\n") && !stderr.contains("Send? [y/N]"), "{}", stderr);
+
+    // Nothing is sent when the first request alone would cost more; nothing is listening
+    let (success, _, stderr) = run(&["--max-cost", "0.000001"]);
+    assert!(!success);
+    assert!(stderr.contains("Not sending the prompt to claude-haiku-4-5: it would cost about $0.0"), "{}", stderr);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
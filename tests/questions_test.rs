@@ -0,0 +1,64 @@
+use llm_code_review::error::ReviewError;
+use llm_code_review::questions::{match_answers, parse_answers, parse_questions, Question};
+
+const REVIEW: &str = "\
+## Summary
+Adds retries to the HTTP client.
+
+## Questions for the Author
+- **Q1.** Why is the retry limit 3?
+- Q2: Should timeouts be retried too?
+Q3) Is this used from async code?
+";
+
+fn question(id: &str, text: &str) -> Question {
+    Question {
+        id: id.to_string(),
+        text: text.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_questions() {
+    assert_eq!(
+        parse_questions(REVIEW),
+        [
+            question("Q1", "Why is the retry limit 3?"),
+            question("Q2", "Should timeouts be retried too?"),
+            question("Q3", "Is this used from async code?"),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_answers() {
+    let answers = parse_answers("Q1: It matches the gateway.\n\nQ2: No.\nThey fail fast on purpose.\n");
+    assert_eq!(
+        answers,
+        [
+            ("Q1".to_string(), "It matches the gateway.".to_string()),
+            ("Q2".to_string(), "No.\nThey fail fast on purpose.".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_match_answers() {
+    let questions = parse_questions(REVIEW);
+
+    let all = parse_answers("Q1: a\nQ2: b\nQ3: c\n");
+    let matched = match_answers(&questions, &all).unwrap();
+    assert_eq!(matched.len(), 3);
+    assert_eq!(matched[2].1, "c");
+
+    match match_answers(&questions, &parse_answers("Q1: a\nQ3:\n")) {
+        Err(ReviewError::Answers(msg)) => assert_eq!(msg, "no answer given for Q2, Q3"),
+        other => panic!("expected Answers error, got {:?}", other),
+    }
+    match match_answers(&questions, &parse_answers("Q1: a\nQ2: b\nQ3: c\nQ9: d\n")) {
+        Err(ReviewError::Answers(msg)) => {
+            assert_eq!(msg, "no question with the ID Q9 in the previous review")
+        }
+        other => panic!("expected Answers error, got {:?}", other),
+    }
+}
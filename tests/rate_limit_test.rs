@@ -0,0 +1,70 @@
+use llm_code_review::rate_limit::{parse_quota, parse_reset_duration, parse_rfc3339, Quota, RateLimiter, MAX_PACE};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A response's headers, looked up as `RateLimiter::observe` does
+fn headers<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    move |name| pairs.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+}
+
+fn at(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+#[test]
+fn test_parse_quota() {
+    let now = at(1_700_000_000);
+    let github = [("x-ratelimit-remaining", "12"), ("x-ratelimit-reset", "1700000600")];
+    assert_eq!(parse_quota(headers(&github), now), Some(Quota { remaining: 12, reset: at(1_700_000_600) }));
+    let openai = [("x-ratelimit-remaining-requests", "0"), ("x-ratelimit-reset-requests", "6m0s")];
+    assert_eq!(parse_quota(headers(&openai), now), Some(Quota { remaining: 0, reset: at(1_700_000_360) }));
+    let anthropic =
+        [("anthropic-ratelimit-requests-remaining", "3"), ("anthropic-ratelimit-requests-reset", "2023-11-14T22:14:20Z")];
+    assert_eq!(parse_quota(headers(&anthropic), now), Some(Quota { remaining: 3, reset: at(1_700_000_060) }));
+    // Retry-After wins, as the host has already refused
+    let refused = [("retry-after", "34"), ("x-ratelimit-remaining", "5"), ("x-ratelimit-reset", "1700000600")];
+    assert_eq!(parse_quota(headers(&refused), now), Some(Quota { remaining: 0, reset: at(1_700_000_034) }));
+    assert_eq!(parse_quota(headers(&[("x-ratelimit-remaining", "5")]), now), None);
+    assert_eq!(parse_quota(headers(&[("content-type", "application/json")]), now), None);
+
+    assert_eq!(parse_reset_duration("20ms"), Some(Duration::from_millis(20)));
+    assert_eq!(parse_reset_duration("1.5s"), Some(Duration::from_millis(1_500)));
+    assert_eq!(parse_reset_duration("1h2m3s"), Some(Duration::from_secs(3_723)));
+    assert_eq!(parse_reset_duration(""), None);
+    assert_eq!(parse_reset_duration("soon"), None);
+    assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(at(0)));
+    assert_eq!(parse_rfc3339("2024-02-29T12:00:00.250Z"), Some(at(1_709_208_000)));
+    assert_eq!(parse_rfc3339("2024-02-29 12:00:00"), None);
+}
+
+#[test]
+fn test_pacing() {
+    let mut limiter = RateLimiter::default();
+    let now = at(1_000);
+    assert_eq!(limiter.delay("api.github.com", now), None);
+
+    // Plenty left: no waiting, until only a few are left and are spread out until the reset
+    let reset = "1060";
+    limiter.observe("api.github.com", headers(&[("x-ratelimit-remaining", "100"), ("x-ratelimit-reset", reset)]), now);
+    assert_eq!(limiter.delay("api.github.com", now), None);
+    limiter.observe("api.github.com", headers(&[("x-ratelimit-remaining", "2"), ("x-ratelimit-reset", reset)]), now);
+    assert_eq!(limiter.delay("api.github.com", now), Some(MAX_PACE));
+    assert_eq!(limiter.delay("api.github.com", at(1_030)), Some(Duration::from_secs(10)));
+    assert_eq!(limiter.delay("api.github.com", at(1_051)), Some(Duration::from_secs(3)));
+    // A request sent before the next response counts against what's left
+    limiter.reserve("api.github.com");
+    limiter.reserve("api.github.com");
+    assert_eq!(limiter.delay("api.github.com", at(1_026)), Some(Duration::from_secs(34)));
+    // Once the limit has reset, there's no waiting until a response says otherwise
+    assert_eq!(limiter.delay("api.github.com", at(1_060)), None);
+    assert_eq!(limiter.delay("api.github.com", at(2_000)), None);
+
+    // Each host has its own limit, and a response without the headers leaves it as it was
+    limiter.observe("api.anthropic.com", headers(&[("retry-after", "20")]), now);
+    limiter.observe("api.anthropic.com", headers(&[]), now);
+    assert_eq!(limiter.delay("api.anthropic.com", now), Some(Duration::from_secs(20)));
+    assert_eq!(limiter.delay("api.github.com", now), Some(Duration::from_secs(60)));
+
+    // A reset too far away isn't waited for
+    limiter.observe("api.openai.com", headers(&[("x-ratelimit-remaining-requests", "0"), ("x-ratelimit-reset-requests", "1h")]), now);
+    assert_eq!(limiter.delay("api.openai.com", now), None);
+}
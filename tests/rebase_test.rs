@@ -0,0 +1,65 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::rebase::{range_diff, summarize_range_diff, was_rebased};
+use std::fs;
+use std::path::Path;
+
+fn commit(dir: &Path, file: &str, contents: &str, subject: &str) -> String {
+    fs::write(dir.join(file), contents).unwrap();
+    git(dir, &["add", file]);
+    git(dir, &["commit", "-q", "-m", subject]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+#[test]
+fn test_rebased_branch() {
+    let dir = temp_dir("rebase");
+
+    init_repo(&dir);
+    git(&dir, &["checkout", "-q", "-b", "main"]);
+    commit(&dir, "base.txt", "base\n", "base");
+    git(&dir, &["checkout", "-q", "-b", "feature"]);
+    commit(&dir, "a.txt", "a\n", "add a");
+    let lines: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+    let old_head = commit(&dir, "b.txt", &lines, "add b");
+
+    // Move main on, then rebase the feature branch onto it and amend the second commit
+    git(&dir, &["checkout", "-q", "main"]);
+    let base = commit(&dir, "base.txt", "base 2\n", "update base");
+    git(&dir, &["checkout", "-q", "feature"]);
+    git(&dir, &["rebase", "-q", "main"]);
+    fs::write(dir.join("b.txt"), lines.replace("line 20", "line 20, fixed")).unwrap();
+    git(&dir, &["commit", "-q", "-a", "--amend", "--no-edit"]);
+    let new_head = git(&dir, &["rev-parse", "HEAD"]);
+
+    // This is the only test in this binary that uses the current directory
+    std::env::set_current_dir(&dir).unwrap();
+
+    assert!(was_rebased(&old_head, &new_head).unwrap());
+    assert!(!was_rebased(&base, &new_head).unwrap());
+    assert!(was_rebased("no-such-commit", &new_head).is_err());
+
+    let summary = summarize_range_diff(&range_diff(&base, &old_head, &new_head).unwrap());
+    assert!(summary.contains("Unchanged commits: add a\n"), "{}", summary);
+    assert!(summary.contains(" ! 2:  "), "{}", summary);
+    assert!(summary.contains("line 20, fixed"), "{}", summary);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_summarize_range_diff() {
+    let range_diff = "1:  a1b2c3d = 1:  e4f5a6b First\n\
+                      2:  b2c3d4e < -:  ------- Dropped\n\
+                      -:  ------- > 2:  c7d8e9f Added\n    \
+                      @@ new file\n";
+    let summary = summarize_range_diff(range_diff);
+    assert!(summary.contains("Unchanged commits: First\n"));
+    assert!(summary.contains("< -:  ------- Dropped"));
+    assert!(summary.contains("> 2:  c7d8e9f Added\n    @@ new file\n```"));
+    assert!(!summary.contains("= 1:"));
+
+    let only_base = summarize_range_diff("1:  a1b2c3d = 1:  e4f5a6b First\n");
+    assert!(only_base.ends_with("Only the base changed; every commit is the same as before."));
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::redact::{PathScrubber, RedactionSummary};
+use std::fs;
+use std::process::Command;
+
+fn scrub(scrubber: &PathScrubber, line: &str) -> (String, RedactionSummary) {
+    let mut summary = RedactionSummary::default();
+    (scrubber.scrub_line(line, &mut summary), summary)
+}
+
+#[test]
+fn test_posix_paths() {
+    let scrubber = PathScrubber::new(Some("/home/alice/src/app"), Some("/home/alice"));
+    assert_eq!(
+        scrub(&scrubber, "+    let config = \"/home/alice/src/app/config/dev.toml\";").0,
+        "+    let config = \"config/dev.toml\";"
+    );
+    assert_eq!(scrub(&scrubber, "+cd /home/alice/src/app && make").0, "+cd . && make");
+    assert_eq!(scrub(&scrubber, "+source /home/alice/.cargo/env").0, "+source ~/.cargo/env");
+    // Anyone's home, not just this user's
+    assert_eq!(scrub(&scrubber, "+# from /Users/bob/notes.txt").0, "+# from ~/notes.txt");
+    // A directory whose name starts with the repository's isn't under it
+    let (line, summary) = scrub(&scrubber, "+ls /home/alice/src/application");
+    assert_eq!(line, "+ls ~/src/application");
+    assert_eq!(summary, RedactionSummary { repo_paths: 0, home_paths: 1 });
+    // Paths inside URLs and relative paths are left alone
+    let untouched = "+fetch(\"https://example.com/home/alice/x\", \"vendor/home/alice\")";
+    assert_eq!(scrub(&scrubber, untouched).0, untouched);
+
+    let root = PathScrubber::new(Some("/root/app"), Some("/root"));
+    assert_eq!(scrub(&root, "+open('/root/app/a.txt'); open('/root/.netrc')").0, "+open('a.txt'); open('~/.netrc')");
+    // A repository at / would match every absolute path
+    let (line, summary) = scrub(&PathScrubber::new(Some("/"), None), "+cat /etc/hosts");
+    assert_eq!((line.as_str(), summary.total()), ("+cat /etc/hosts", 0));
+}
+
+#[test]
+fn test_windows_paths() {
+    let scrubber = PathScrubber::new(Some("C:\\Users\\bob\\app"), Some("C:\\Users\\bob"));
+    assert_eq!(scrub(&scrubber, "+path = C:\\Users\\bob\\app\\src\\main.rs").0, "+path = src\\main.rs");
+    // Escaped in a string literal, with either separator, and in another case
+    assert_eq!(scrub(&scrubber, "+let p = \"c:\\\\users\\\\BOB\\\\App\\\\data\";").0, "+let p = \"data\";");
+    assert_eq!(scrub(&scrubber, "+load(\"C:/Users/bob/app/x.json\")").0, "+load(\"x.json\")");
+    let (line, summary) = scrub(&scrubber, "+copy D:\\Users\\carol\\Desktop\\log.txt C:\\Users\\bob\\tmp");
+    assert_eq!(line, "+copy ~\\Desktop\\log.txt ~\\tmp");
+    assert_eq!(summary, RedactionSummary { repo_paths: 0, home_paths: 2 });
+}
+
+#[test]
+fn test_scrub_diff() {
+    let scrubber = PathScrubber::new(Some("/home/alice/app"), Some("/home/alice"));
+    let diff = "diff --git a/home/alice/app/x b/home/alice/app/x\n\
+                --- a/home/alice/app/x\n\
+                +++ b/home/alice/app/x\n\
+                @@ -1,2 +1,2 @@\n \
+                root = /home/alice/app\n\
+                -log = /home/alice/app/old.log\n\
+                +log = /home/alice/logs/new.log\n";
+    let (scrubbed, summary) = scrubber.scrub_diff(diff);
+    // The headers are left as git wrote them
+    assert!(scrubbed.starts_with("diff --git a/home/alice/app/x b/home/alice/app/x\n--- a/home/alice/app/x\n"));
+    assert!(scrubbed.ends_with("@@ -1,2 +1,2 @@\n root = .\n-log = old.log\n+log = ~/logs/new.log\n"), "{}", scrubbed);
+    assert_eq!(summary, RedactionSummary { repo_paths: 2, home_paths: 1 });
+    assert_eq!(
+        summary.to_string(),
+        "Scrubbed 3 absolute paths: 2 made relative to the repository, 1 home directory replaced with ~"
+    );
+}
+
+#[test]
+fn test_no_path_scrub() {
+    let dir = temp_dir("redact");
+    init_repo(&dir);
+    fs::write(dir.join("paths.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("paths.txt"), "one\ncache = /home/dave/.cache/app\n").unwrap();
+
+    let review = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(extra)
+            .env("HOME", "/nonexistent")
+            .output()
+            .expect("failed to run llm_code_review");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+    let scrubbed = review(&[]);
+    assert!(scrubbed.contains("+cache = ~/.cache/app"), "{}", scrubbed);
+    assert!(!scrubbed.contains("/home/dave"), "{}", scrubbed);
+    let kept = review(&["--no-path-scrub"]);
+    assert!(kept.contains("+cache = /home/dave/.cache/app"), "{}", kept);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::repo_language::{preset, LanguageProfile, RepoDefaults, LANGUAGE_CACHE_FILE};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_dominant_language() {
+    let profile = LanguageProfile::from_paths([
+        "src/main.rs",
+        "src/lib.rs",
+        "src/review.rs",
+        "build.sh",
+        "readme.md",
+        "Cargo.toml",
+    ]);
+    // Files in unknown languages don't count
+    assert_eq!(profile.counts, vec![("rust".to_string(), 3), ("shell".to_string(), 1)]);
+    assert_eq!(profile.dominant(), Some("rust"));
+    assert_eq!(profile.describe(), "rust 75%, shell 25%");
+
+    let defaults = RepoDefaults::from_profile(&profile);
+    assert_eq!(defaults.language.as_deref(), Some("rust"));
+    assert_eq!(defaults.presets, vec![preset("rust").unwrap()]);
+}
+
+#[test]
+fn test_mixed_languages_without_a_dominant_one() {
+    let profile = LanguageProfile::from_paths([
+        "server/main.go",
+        "server/api.go",
+        "web/app.ts",
+        "web/api.ts",
+        "scripts/report.py",
+        "scripts/load.py",
+        "deploy.sh",
+    ]);
+    assert_eq!(profile.dominant(), None);
+    assert_eq!(profile.significant(), vec!["go", "python", "typescript"]);
+
+    // No language hint, but every significant language's excludes and test rules
+    let defaults = RepoDefaults::from_profile(&profile);
+    assert_eq!(defaults.language, None);
+    assert!(defaults.is_excluded("vendor/github.com/x/y.go"));
+    assert!(defaults.is_excluded("web/dist/app.js"));
+    assert!(defaults.is_excluded("scripts/__pycache__/load.pyc"));
+    assert!(!defaults.is_excluded("web/distribution.ts"));
+    assert!(!defaults.is_excluded("target/debug/build.rs"));
+    assert_eq!(defaults.related_tests("server/api.go"), vec!["server/api_test.go"]);
+    assert_eq!(
+        defaults.related_tests("web/api.ts"),
+        vec!["web/api.test.ts", "web/api.spec.ts", "web/__tests__/api.ts"]
+    );
+    assert_eq!(defaults.related_tests("deploy.sh"), Vec::<String>::new());
+
+    // An even split has no dominant language either
+    let profile = LanguageProfile::from_paths(["a.rs", "b.py"]);
+    assert_eq!(profile.dominant(), None);
+    assert_eq!(LanguageProfile::from_paths(["readme.md"]).describe(), "no recognised source files");
+}
+
+#[test]
+fn test_related_tests() {
+    let rust = RepoDefaults::for_language("Rust");
+    assert_eq!(rust.language.as_deref(), Some("rust"));
+    assert_eq!(rust.related_tests("src/review.rs"), vec!["tests/review_test.rs", "tests/review.rs"]);
+    assert_eq!(
+        rust.related_tests("crates/core/src/diff.rs"),
+        vec!["crates/core/tests/diff_test.rs", "crates/core/tests/diff.rs"]
+    );
+
+    let python = RepoDefaults::for_language("python");
+    assert_eq!(python.related_tests("pkg/parse.py"), vec!["tests/test_parse.py", "pkg/test_parse.py"]);
+    // Tests don't have related tests of their own
+    assert!(python.related_tests("tests/test_parse.py").is_empty());
+    assert!(python.related_tests("pkg/test_parse.py").is_empty());
+    assert!(RepoDefaults::for_language("go").related_tests("a_test.go").is_empty());
+    assert!(RepoDefaults::for_language("typescript").related_tests("web/api.spec.ts").is_empty());
+    assert!(rust.related_tests("tests/review_test.rs").is_empty());
+
+    let java = RepoDefaults::for_language("java");
+    assert_eq!(java.related_tests("src/main/java/com/x/Parser.java"), vec!["src/test/java/com/x/ParserTest.java"]);
+    assert!(java.related_tests("src/test/java/com/x/ParserTest.java").is_empty());
+}
+
+#[test]
+fn test_language_override() {
+    assert_eq!(RepoDefaults::for_language("none"), RepoDefaults::default());
+    // A language without a preset still gives a language hint
+    let haskell = RepoDefaults::for_language("haskell");
+    assert_eq!(haskell.language.as_deref(), Some("haskell"));
+    assert!(haskell.presets.is_empty());
+}
+
+#[test]
+fn test_detection_in_a_repository() {
+    let dir = temp_dir("repo_language");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join("tests")).unwrap();
+    fs::create_dir_all(dir.join("target")).unwrap();
+
+    init_repo(&dir);
+    fs::write(dir.join("src/lib.rs"), "pub fn a() {}\n").unwrap();
+    fs::write(dir.join("src/parse.rs"), "pub fn parse() {}\n").unwrap();
+    fs::write(dir.join("tests/parse_test.rs"), "#[test]\nfn parses() {}\n").unwrap();
+    fs::write(dir.join("target/out.rs"), "generated\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+    fs::write(dir.join("src/parse.rs"), "pub fn parse() -> bool { true }\n").unwrap();
+    fs::write(dir.join("target/out.rs"), "regenerated\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let stdout = run(&[]);
+    assert!(stdout.contains("diff --git a/src/parse.rs"), "{}", stdout);
+    assert!(!stdout.contains("target/out.rs"), "{}", stdout);
+    assert!(stdout.contains("## Related Tests"), "{}", stdout);
+    assert!(stdout.contains("- `src/parse.rs` is usually tested by `tests/parse_test.rs`"), "{}", stdout);
+
+    let cache = fs::read_to_string(dir.join(".git").join(LANGUAGE_CACHE_FILE)).unwrap();
+    assert!(cache.contains("\"rust\""), "{}", cache);
+
+    // The override turns the presets off
+    let stdout = run(&["--repo-language", "none"]);
+    assert!(stdout.contains("target/out.rs"), "{}", stdout);
+    assert!(!stdout.contains("## Related Tests"), "{}", stdout);
+}
@@ -0,0 +1,139 @@
+//! Contract tests for what the tool reads back out of model responses, against the corpus in
+//! tests/fixtures/responses: one response per output mode, recorded for `change.diff`, and
+//! responses that go wrong in the ways models do. Refresh the corpus after a prompt change with
+//! `cargo run --example regenerate-fixtures`.
+
+use llm_code_review::attention::parse_confidence;
+use llm_code_review::canary::{canary_for, check_canary, strip_canary, CanaryCheck};
+use llm_code_review::citations::{is_valid_citation, parse_citations, Citation};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::findings::{parse_json_findings, Severity};
+use llm_code_review::output::unfence;
+use llm_code_review::questions::parse_questions;
+use llm_code_review::runaway::{cap_response, RunawayLimits};
+use llm_code_review::transcript::Transcript;
+
+const CHANGE: &str = include_str!("fixtures/responses/change.diff");
+
+/// Every line the response cites is in the change
+fn assert_citations_valid(response: &str) {
+    let files = parse_diff(CHANGE);
+    let citations = parse_citations(response);
+    assert!(!citations.is_empty(), "{}", response);
+    for citation in citations {
+        assert!(is_valid_citation(&citation, &files), "{:?} isn't in the change", citation);
+    }
+}
+
+#[test]
+fn test_markdown_response() {
+    let response = include_str!("fixtures/responses/markdown.md");
+    assert!(check_canary(response, &canary_for(CHANGE)).is_found());
+    let shown = strip_canary(response);
+    assert!(!shown.contains("end-of-diff") && shown.ends_with("- src/cart.rs: 85\n"), "{}", shown);
+    for heading in ["## Summary", "## Findings", "## Questions", "## Review Confidence"] {
+        assert!(shown.contains(&format!("\n{}\n", heading)) || shown.starts_with(heading), "{}", heading);
+    }
+
+    assert_eq!(parse_confidence(response), vec![("src/cart.rs".to_string(), 85)]);
+    let questions: Vec<String> = parse_questions(response).into_iter().map(|question| question.id).collect();
+    assert_eq!(questions, vec!["Q1", "Q2"]);
+    assert_eq!(parse_citations(response)[0], Citation { path: "src/cart.rs".to_string(), line: 18 });
+    assert_citations_valid(response);
+
+    // Not JSON, so there are no structured findings, and nothing is cut short
+    assert_eq!(Transcript::new(None, response).findings, None);
+    assert_eq!(cap_response(response, &RunawayLimits::default()).runaway, None);
+}
+
+#[test]
+fn test_structured_response() {
+    let transcript = Transcript::new(None, include_str!("fixtures/responses/structured.md"));
+    assert_eq!(
+        transcript.summary.as_deref(),
+        Some("The discount code parsing can panic, and the new total can overflow.")
+    );
+    let findings = transcript.findings.expect("the findings should parse");
+    assert_eq!(findings.len(), 2);
+    assert_eq!((findings[0].line, findings[0].severity), (Some(18), Severity::Error));
+    assert_eq!(findings[0].category.as_deref(), Some("error-handling"));
+    assert!(findings[1].follow_up && !findings[0].follow_up);
+}
+
+#[test]
+fn test_json_response() {
+    let response = include_str!("fixtures/responses/json.txt");
+    let findings = parse_json_findings(response).expect("the findings should parse");
+    let summary: Vec<(&str, Option<usize>, Severity)> =
+        findings.iter().map(|finding| (finding.file.as_str(), finding.line, finding.severity)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("src/cart.rs", Some(18), Severity::Error),
+            ("src/cart.rs", Some(12), Severity::Warning),
+            ("src/cart.rs", None, Severity::Info)
+        ]
+    );
+    // Transcripts read the same findings, with `file` for `path`
+    let transcript = Transcript::new(None, response).findings.expect("the transcript should have findings");
+    assert_eq!(transcript.len(), 3);
+    assert_eq!(transcript[0].path, "src/cart.rs");
+}
+
+#[test]
+fn test_github_actions_response() {
+    let response = unfence(include_str!("fixtures/responses/github_actions.txt"));
+    let lines: Vec<&str> = response.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|line| line.starts_with("::") && line.contains(" file=src/cart.rs,line=")), "{}", response);
+    assert_citations_valid(response);
+}
+
+#[test]
+fn test_docstring_comments_response() {
+    let response = include_str!("fixtures/responses/docstring_comments.txt");
+    assert_eq!(unfence(response), response);
+    assert_eq!(response.matches("// REVIEW(line ").count(), 2);
+}
+
+#[test]
+fn test_asciidoc_and_mediawiki_responses() {
+    for (response, heading) in [
+        (include_str!("fixtures/responses/asciidoc.adoc"), "== Findings\n"),
+        (include_str!("fixtures/responses/mediawiki.wiki"), "== Findings ==\n"),
+    ] {
+        assert!(response.contains(heading), "{}", response);
+        assert_citations_valid(response);
+        assert_eq!(Transcript::new(None, response).findings, None);
+    }
+}
+
+#[test]
+fn test_truncated_json_response() {
+    let response = include_str!("fixtures/responses/truncated_json.txt");
+    let error = parse_json_findings(response).expect_err("a truncated array shouldn't parse");
+    assert!(error.contains("EOF") || error.contains("expected"), "{}", error);
+    assert_eq!(Transcript::new(None, response).findings, None);
+}
+
+#[test]
+fn test_nested_fences_response() {
+    // A fence inside the block means it isn't one block, so it's left as it is, and isn't JSON
+    let response = include_str!("fixtures/responses/nested_fences.md");
+    assert_eq!(unfence(response), response);
+    assert_eq!(Transcript::new(None, response).findings, None);
+    assert!(parse_json_findings(response).is_err());
+}
+
+#[test]
+fn test_responses_without_a_valid_trailer() {
+    let canary = canary_for(CHANGE);
+    let missing = include_str!("fixtures/responses/missing_trailer.md");
+    assert_eq!(check_canary(missing, &canary), CanaryCheck::Missing);
+    assert!(parse_confidence(missing).is_empty());
+    assert!(parse_questions(missing).is_empty());
+
+    let wrong = include_str!("fixtures/responses/wrong_trailer.md");
+    assert_eq!(check_canary(wrong, &canary), CanaryCheck::Wrong("LLMCR-00000000".to_string()));
+    assert!(!strip_canary(wrong).contains("LLMCR-"));
+}
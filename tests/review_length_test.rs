@@ -0,0 +1,36 @@
+use llm_code_review::review_length::{diff_changed_lines, ReviewLength, ReviewTarget};
+
+#[test]
+fn test_auto_review_length_scales_with_changes() {
+    let auto = ReviewLength::Auto;
+    assert_eq!(auto.target_words(0), Some(150));
+    assert_eq!(auto.target_words(5), Some(200));
+    assert_eq!(auto.target_words(100), Some(600));
+    assert_eq!(auto.target_words(2000), Some(1500));
+    assert!(auto.target_words(50) < auto.target_words(500));
+}
+
+#[test]
+fn test_review_length_overrides() {
+    assert_eq!("short".parse::<ReviewLength>().unwrap().target_words(2000), Some(200));
+    assert_eq!("long".parse::<ReviewLength>().unwrap().target_words(5), Some(1500));
+    assert_eq!(ReviewLength::Unlimited.target_words(5), None);
+    assert_eq!(ReviewTarget::new(ReviewLength::Unlimited, 5).instructions(), None);
+    assert!("brief".parse::<ReviewLength>().is_err());
+
+    let target = ReviewTarget::new(ReviewLength::Medium, 40);
+    assert!(target.instructions().unwrap().contains("under 600 words"));
+}
+
+#[test]
+fn test_diff_changed_lines() {
+    let diff = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                --- a/src/lib.rs\n\
+                +++ b/src/lib.rs\n\
+                @@ -1,3 +1,3 @@\n \
+                fn main() {\n\
+                -    old();\n\
+                +    new();\n \
+                }\n";
+    assert_eq!(diff_changed_lines(diff), 2);
+}
@@ -0,0 +1,96 @@
+use llm_code_review::runaway::{
+    cap_response, cap_response_with, detect_runaway, local_summary, section_boundary, RunawayLimits, RunawayReason,
+};
+
+const REVIEW: &str = "## Summary\n\n\
+                      The change adds a discount. It looks fine otherwise.\n\n\
+                      ## Issues\n\n\
+                      - `apply_discount` accepts negative percentages. Clamp them.\n\
+                      - The README example rounds differently.\n\n\
+                      ```python\nreturn total * (1 - percent / 100)\n```\n";
+
+fn looping(times: usize) -> String {
+    let mut review = REVIEW.to_string();
+    review.push_str("\n## More Issues\n\n");
+    for n in 0..times {
+        review.push_str(&format!("{}. Consider adding a test for the discount.\n", n + 1));
+    }
+    review
+}
+
+#[test]
+fn test_detect_runaway() {
+    let limits = RunawayLimits::default();
+    assert_eq!(detect_runaway(REVIEW, &limits), None);
+    // A few repeats are fine, and short lines such as fences never count
+    assert_eq!(detect_runaway(&looping(5), &limits), None);
+    assert_eq!(detect_runaway(&"```\n}\n".repeat(50), &limits), None);
+
+    let review = looping(40);
+    let runaway = detect_runaway(&review, &limits).unwrap();
+    assert_eq!(
+        runaway.reason,
+        RunawayReason::Repetitive { line: "6. Consider adding a test for the discount.".to_string(), count: 6 }
+    );
+    assert!(review[runaway.at..].starts_with("2. Consider"), "{}", &review[runaway.at..]);
+
+    let long = format!("{}{}", REVIEW, "Every line of this says something new, honestly.\n".repeat(1_000).replace("new", "novel"));
+    let limits = RunawayLimits { max_repeats: 10_000, ..limits };
+    let runaway = detect_runaway(&long, &limits).unwrap();
+    assert_eq!(runaway.reason, RunawayReason::TooLong { chars: long.len() });
+    assert_eq!(runaway.at, limits.max_chars);
+    assert_eq!(runaway.reason.to_string(), format!("the response ran to {} characters", long.len()));
+}
+
+#[test]
+fn test_section_boundary() {
+    // The last heading, as long as it leaves at least half
+    let review = looping(2);
+    assert_eq!(section_boundary(&review, review.find("2. Consider").unwrap()), REVIEW.len() + 1);
+    assert_eq!(section_boundary(REVIEW, REVIEW.find("- The README").unwrap()), REVIEW.find("- `apply").unwrap());
+    // Otherwise the last paragraph, never in a code block
+    assert_eq!(section_boundary(REVIEW, REVIEW.find("return total").unwrap()), REVIEW.find("```python").unwrap());
+    let padded = format!("## Summary\n\n{}\n\nLast paragraph.\n```\ncode\n", "x".repeat(200));
+    let code = padded.find("code").unwrap();
+    assert_eq!(section_boundary(&padded, code), padded.find("Last paragraph").unwrap());
+    assert_eq!(section_boundary("one line only", 5), 0);
+}
+
+#[test]
+fn test_local_summary() {
+    let summary = local_summary(&format!("{}\n## Empty\n\n## Issues\n\n- Clamp them.\n", REVIEW), 4_000);
+    assert_eq!(
+        summary,
+        "## Summary\nThe change adds a discount.\n## Issues\n- `apply_discount` accepts negative percentages.\n\
+         - The README example rounds differently.\n## Issues\n- Clamp them."
+    );
+    assert!(local_summary(&looping(40), 4_000).matches("Consider adding a test").count() == 1);
+    assert!(local_summary(&looping(40), 60).len() <= 60);
+}
+
+#[test]
+fn test_cap_response() {
+    let limits = RunawayLimits::default();
+    let fine = cap_response(REVIEW, &limits);
+    assert_eq!((fine.comment(), fine.transcript().as_str()), (REVIEW, REVIEW));
+
+    let review = looping(40);
+    let capped = cap_response(&review, &limits);
+    assert!(capped.comment().starts_with(REVIEW.trim_end()), "{}", capped.comment());
+    assert!(!capped.comment().contains("2. Consider"), "{}", capped.comment());
+    assert!(
+        capped.comment().contains(&format!(
+            "**[Response truncated after {} of {} characters: the line \"6. Consider adding a test for the discount.\" \
+             was repeated 6 times.]**\n\n## Condensed Review\n\n## Summary\n",
+            REVIEW.trim_end().len(),
+            review.len()
+        )),
+        "{}",
+        capped.comment()
+    );
+    assert!(capped.transcript().starts_with(capped.comment()));
+    assert!(capped.transcript().ends_with(&review));
+
+    let cheap = cap_response_with(&review, &limits, |_| Some("Add a discount test.".to_string()));
+    assert!(cheap.comment().ends_with("## Condensed Review\n\nAdd a discount test.\n"), "{}", cheap.comment());
+}
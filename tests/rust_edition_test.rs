@@ -0,0 +1,24 @@
+use llm_code_review::rust_edition::{detect_rust_edition, edition_note};
+use std::path::Path;
+
+const FIXTURES: &str = "tests/fixtures/rust_edition";
+
+#[test]
+fn test_detect_rust_edition() {
+    let fixtures = Path::new(FIXTURES);
+    for edition in ["2018", "2021", "2024"] {
+        assert_eq!(detect_rust_edition(&fixtures.join(edition)).as_deref(), Some(edition));
+    }
+    // Cargo defaults to 2015 when no edition is given
+    assert_eq!(detect_rust_edition(&fixtures.join("2015")).as_deref(), Some("2015"));
+    assert_eq!(detect_rust_edition(&fixtures.join("workspace")).as_deref(), Some("2021"));
+    assert_eq!(detect_rust_edition(fixtures), None);
+}
+
+#[test]
+fn test_edition_note() {
+    assert_eq!(
+        edition_note("2021"),
+        "This code targets Rust edition 2021. Use edition-appropriate idioms.\n\n"
+    );
+}
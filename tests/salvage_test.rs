@@ -0,0 +1,96 @@
+use llm_code_review::findings::{parse_json_findings, Finding, Severity};
+use llm_code_review::salvage::{extract_findings, salvage_banner};
+
+fn summary(findings: &[Finding]) -> Vec<(&str, Option<usize>, Severity)> {
+    findings.iter().map(|finding| (finding.path.as_str(), finding.line, finding.severity)).collect()
+}
+
+fn paths() -> Vec<String> {
+    vec!["src/cart.rs".to_string()]
+}
+
+#[test]
+fn test_prose_instead_of_json() {
+    let response = include_str!("fixtures/responses/prose_instead_of_json.txt");
+    assert!(parse_json_findings(response).is_err());
+    let findings = extract_findings(response, &paths());
+    assert_eq!(
+        summary(&findings),
+        vec![
+            ("src/cart.rs", Some(18), Severity::Error),
+            ("src/cart.rs", Some(12), Severity::Warning),
+            ("src/cart.rs", None, Severity::Info)
+        ]
+    );
+    assert!(findings.iter().all(|finding| finding.extracted && finding.confidence.is_none()));
+    assert!(findings[0].comment.starts_with("line 18: calling `unwrap()`"), "{}", findings[0].comment);
+    assert_eq!(findings[2].comment, "the loop in total() could be written as an iterator with `sum()`.");
+}
+
+#[test]
+fn test_truncated_json() {
+    // What came before the response was cut off is kept, up to where it stops
+    let response = include_str!("fixtures/responses/truncated_json.txt");
+    let findings = extract_findings(response, &paths());
+    assert_eq!(
+        summary(&findings),
+        vec![("src/cart.rs", Some(18), Severity::Error), ("src/cart.rs", Some(12), Severity::Warning)]
+    );
+    assert_eq!(findings[0].comment, "`unwrap()` panics for any code that isn't `SAVE` followed by digits.");
+    assert_eq!(findings[1].comment, "`item.price * item.quan");
+}
+
+#[test]
+fn test_markdown_review() {
+    // The findings, and not the summary, questions or confidence scores
+    let response = include_str!("fixtures/responses/markdown.md");
+    let findings = extract_findings(response, &paths());
+    assert_eq!(
+        summary(&findings),
+        vec![
+            ("src/cart.rs", Some(18), Severity::Error),
+            ("src/cart.rs", Some(12), Severity::Warning),
+            ("src/cart.rs", Some(10), Severity::Info)
+        ]
+    );
+    assert!(findings[0].comment.starts_with("`unwrap()` on the parsed code panics"), "{}", findings[0].comment);
+}
+
+#[test]
+fn test_nested_fences() {
+    // The fence cuts the only finding's comment down to a word, which says nothing
+    let response = include_str!("fixtures/responses/nested_fences.md");
+    assert!(extract_findings(response, &paths()).is_empty());
+}
+
+#[test]
+fn test_files_outside_the_diff() {
+    let review = "- **Error** (src/other.rs:3): this file isn't in the diff at all.\n\
+                  - lib.rs:7 leaks the handle when the write fails.\n\
+                  - Error handling in `Store::save` swallows the write's failure.";
+    let paths = vec!["src/lib.rs".to_string(), "src/store.rs".to_string()];
+    let findings = extract_findings(review, &paths);
+    assert_eq!(summary(&findings), vec![("src/lib.rs", Some(7), Severity::Warning)]);
+
+    // Without the diff's files, anything cited counts
+    let findings = extract_findings(review, &[]);
+    assert_eq!(
+        summary(&findings),
+        vec![("src/other.rs", Some(3), Severity::Error), ("lib.rs", Some(7), Severity::Warning)]
+    );
+    assert_eq!(findings[0].comment, "this file isn't in the diff at all.");
+
+    // A heading naming a file covers the items under it
+    let review = "### `src/store.rs`\n\n- Critical: the lock is never released on the error path.\n";
+    assert_eq!(summary(&extract_findings(review, &paths)), vec![("src/store.rs", None, Severity::Error)]);
+    assert!(extract_findings("Looks fine to me.", &paths).is_empty());
+}
+
+#[test]
+fn test_salvage_banner() {
+    let banner = salvage_banner("expected value at line 1 column 1", 2);
+    let lines: Vec<&str> = banner.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains("(expected value at line 1 column 1); 2 findings were read out of its prose"), "{}", banner);
+    assert!(lines[0].chars().all(|c| c == '=') && lines[0] == lines[2]);
+}
@@ -0,0 +1,32 @@
+use llm_code_review::diff::parse_diff;
+use llm_code_review::sample::{cluster_similar, sample_note, sample_similar};
+
+const SIMILAR_DIFF: &str = include_str!("fixtures/similar.diff");
+
+#[test]
+fn test_cluster_similar() {
+    // The config files rename the same key, with different values, so they form one cluster
+    assert_eq!(cluster_similar(&parse_diff(SIMILAR_DIFF)), [vec![0, 1, 2, 3], vec![4]]);
+}
+
+#[test]
+fn test_sample_similar() {
+    let (files, clusters) = sample_similar(parse_diff(SIMILAR_DIFF), 1);
+
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["config/a.toml", "src/main.rs"]);
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].sampled, ["config/a.toml"]);
+    assert_eq!(clusters[0].omitted, ["config/b.toml", "config/c.toml", "config/d.toml"]);
+
+    let note = sample_note(&clusters);
+    assert!(note.contains("representative sample"));
+    assert!(note.contains(
+        "- config/a.toml: identical change applied to 3 other files: config/b.toml, config/c.toml, config/d.toml"
+    ));
+
+    // Nothing is left out when every cluster fits
+    let (files, clusters) = sample_similar(parse_diff(SIMILAR_DIFF), 4);
+    assert_eq!(files.len(), 5);
+    assert!(clusters.is_empty());
+}
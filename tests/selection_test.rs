@@ -0,0 +1,121 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::selection::{parse_log, parse_reverse_blame, selection_note, CommitFilter, Overlap};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_commit_filter() {
+    let filter = CommitFilter { author: Some("intern".to_string()), since: Some("1 week ago".to_string()) };
+    assert_eq!(filter.log_args(), ["--author=intern", "--since=1 week ago"]);
+    assert_eq!(filter.to_string(), "by authors matching \"intern\" since \"1 week ago\"");
+    assert!(CommitFilter::default().is_empty());
+}
+
+#[test]
+fn test_parse() {
+    let sha = "d1c85513769c37373138e3bedf6ff8dcd42102b3";
+    let commits = parse_log(&format!("{}\tAlice Smith\tFix the parser\tfor tabs\n", sha));
+    assert_eq!(commits.len(), 1);
+    assert_eq!((commits[0].short(), commits[0].author.as_str()), ("d1c8551", "Alice Smith"));
+    assert_eq!(commits[0].subject, "Fix the parser\tfor tabs");
+
+    let blame = format!(
+        "{0} 1 1 2\nauthor Bob\nfilename f\n\ta\n{0} 2 2\n\tb\ne1eadb64bf69890b5a22a21a1527dbb345a7cf53 3 4 1\n\tc\n",
+        sha
+    );
+    assert_eq!(
+        parse_reverse_blame(&blame),
+        [(1, sha.to_string()), (2, sha.to_string()), (4, "e1eadb64bf69890b5a22a21a1527dbb345a7cf53".to_string())]
+    );
+
+    let overlap = Overlap {
+        path: "src/a.rs".to_string(),
+        lines: (12, 14),
+        selected: "abc1234".to_string(),
+        changed_by: "def5678".to_string(),
+        author: "Bob".to_string(),
+    };
+    assert_eq!(overlap.to_string(), "`src/a.rs` lines 12-14 from abc1234 were changed later by def5678 (Bob)");
+    let note = selection_note(&CommitFilter { author: Some("alice".to_string()), since: None }, &commits, &[overlap]);
+    assert!(note.starts_with("Only the changes made in the 1 commit by authors matching \"alice\""), "{}", note);
+    assert!(note.contains("\n- d1c8551 Fix the parser\tfor tabs (Alice Smith)"), "{}", note);
+    assert!(note.ends_with("changed later by def5678 (Bob)"), "{}", note);
+}
+
+fn commit(dir: &Path, author: &str, date: &str, message: &str) {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["commit", "-q", "-a", "-m", message])
+        .env("GIT_AUTHOR_NAME", author)
+        .env("GIT_AUTHOR_EMAIL", format!("{}@example.com", author.to_lowercase()))
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date)
+        .output()
+        .expect("failed to run git");
+    assert!(output.status.success(), "git commit failed: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+fn lines(changed: &[(usize, &str)]) -> String {
+    (1..=20)
+        .map(|n| match changed.iter().find(|(line, _)| *line == n) {
+            Some((_, text)) => format!("{}\n", text),
+            None => format!("line {}\n", n),
+        })
+        .collect()
+}
+
+#[test]
+fn test_author_and_since() {
+    let dir = temp_dir("selection");
+    init_repo(&dir);
+    fs::write(dir.join("f.txt"), lines(&[])).unwrap();
+    fs::write(dir.join("g.txt"), "g\n").unwrap();
+    git(&dir, &["add", "."]);
+    commit(&dir, "Bob", "2020-01-01T00:00:00Z", "Start");
+    fs::write(dir.join("f.txt"), lines(&[(3, "alice three")])).unwrap();
+    commit(&dir, "Alice", "2020-01-02T00:00:00Z", "Old change by Alice");
+    fs::write(dir.join("f.txt"), lines(&[(3, "alice three"), (15, "bob fifteen")])).unwrap();
+    fs::write(dir.join("g.txt"), "bob's g\n").unwrap();
+    commit(&dir, "Bob", "2025-06-01T00:00:00Z", "Bob's change");
+    fs::write(dir.join("f.txt"), lines(&[(3, "alice three"), (8, "alice eight"), (15, "bob fifteen")])).unwrap();
+    fs::write(dir.join("h.txt"), "alice's h\n").unwrap();
+    git(&dir, &["add", "h.txt"]);
+    commit(&dir, "Alice", "2025-06-02T00:00:00Z", "New change by Alice");
+    fs::write(dir.join("f.txt"), lines(&[(3, "alice three"), (8, "bob eight"), (15, "bob fifteen")])).unwrap();
+    commit(&dir, "Bob", "2025-06-03T00:00:00Z", "Bob rewrites line 8");
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+
+    let prompt = review(&["--author", "Alice"]);
+    assert!(prompt.contains("Only the changes made in the 2 commits by authors matching \"Alice\""), "{}", prompt);
+    assert!(prompt.contains("Old change by Alice (Alice)") && prompt.contains("New change by Alice (Alice)"));
+    assert!(prompt.contains("+alice three") && prompt.contains("+alice eight") && prompt.contains("+alice's h"));
+    // Bob's changes are left out, though the lines Alice's changes were applied around are his
+    assert!(!prompt.contains("bob's g") && !prompt.contains("+bob fifteen"), "{}", prompt);
+    assert!(prompt.contains("`f.txt` line 8 from "), "{}", prompt);
+    assert!(prompt.contains("(Bob)"), "{}", prompt);
+    assert!(!prompt.contains("`f.txt` line 3"), "{}", prompt);
+
+    let recent = review(&["--author", "Alice", "--since", "2025-01-01"]);
+    assert!(recent.contains("the 1 commit by authors matching \"Alice\" since \"2025-01-01\""), "{}", recent);
+    assert!(recent.contains("+alice eight") && !recent.contains("+alice three"), "{}", recent);
+
+    let none = review(&["--author", "Carol", "--since", "2025-01-01"]);
+    assert!(
+        none.contains("No changes found to review: no commits by authors matching \"Carol\" since \"2025-01-01\"."),
+        "{}",
+        none
+    );
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::findings::{render_findings, Finding, Severity};
+use llm_code_review::locale::Locale;
+use llm_code_review::output::OutputFormat;
+use llm_code_review::severity_policy::{glob_regex, SeverityFloor, SeverityPolicy};
+use std::fs;
+use std::process::Command;
+
+fn finding(path: &str, severity: Severity) -> Finding {
+    Finding {
+        path: path.to_string(),
+        line: Some(3),
+        severity,
+        category: None,
+        comment: "Rounds the amount.".to_string(),
+        follow_up: true,
+        policy_note: None,
+        confidence: None,
+        extracted: false,
+        lower_priority: false,
+    }
+}
+
+#[test]
+fn test_glob() {
+    let billing = glob_regex("billing/**");
+    assert!(billing.is_match("billing/invoice.rs") && billing.is_match("billing/tax/vat.rs"));
+    assert!(!billing.is_match("src/billing/invoice.rs") && !billing.is_match("billing.rs"));
+    let sql = glob_regex("*.sql");
+    assert!(sql.is_match("schema.sql") && sql.is_match("db/migrations/001.sql"));
+    assert!(!sql.is_match("schema.sql.bak"));
+    let anywhere = glob_regex("**/auth/*.rs");
+    assert!(anywhere.is_match("auth/login.rs") && anywhere.is_match("src/auth/login.rs"));
+    assert!(!anywhere.is_match("src/auth/session/token.rs"));
+    assert!(glob_regex("v?.txt").is_match("docs/v2.txt"));
+
+    let floor: SeverityFloor = "billing/**=error".parse().unwrap();
+    assert_eq!(floor, SeverityFloor::new("billing/**", Severity::Error));
+    assert!("billing/**".parse::<SeverityFloor>().unwrap_err().contains("is not GLOB=SEVERITY"));
+    assert!("=error".parse::<SeverityFloor>().is_err());
+    assert!("billing/**=high".parse::<SeverityFloor>().is_err());
+}
+
+#[test]
+fn test_enforce() {
+    let policy = SeverityPolicy {
+        rubric: None,
+        floors: vec![SeverityFloor::new("billing/**", Severity::Warning), SeverityFloor::new("*.rs", Severity::Error)],
+    };
+    assert_eq!(policy.floor_for("billing/invoice.rs").unwrap().pattern, "*.rs");
+    let mut findings = vec![
+        finding("billing/rates.toml", Severity::Info),
+        finding("billing/invoice.rs", Severity::Info),
+        finding("billing/rates.toml", Severity::Error),
+        finding("README.md", Severity::Info),
+    ];
+    assert_eq!(policy.enforce(&mut findings), 2);
+    let severities: Vec<Severity> = findings.iter().map(|finding| finding.severity).collect();
+    assert_eq!(severities, [Severity::Warning, Severity::Error, Severity::Error, Severity::Info]);
+    assert_eq!(
+        findings[0].policy_note.as_deref(),
+        Some("severity raised to warning by policy: path matches billing/**")
+    );
+    // Findings are never lowered, and only raised ones are noted
+    assert_eq!(findings[2].policy_note, None);
+    assert_eq!(findings[3].policy_note, None);
+
+    let rendered = render_findings(&findings[..1], OutputFormat::Markdown, Locale::En);
+    assert!(
+        rendered.contains("Rounds the amount. _(severity raised to warning by policy: path matches billing/**)_"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn test_load() {
+    let path = std::env::temp_dir().join(format!("llm_code_review_severity_policy_{}.toml", std::process::id()));
+    fs::write(
+        &path,
+        "rubric = \"error: loses money\"\n\n[[floor]]\npath = \"billing/**\"\nseverity = \"error\"\n\n\
+         [[floor]]\npath = \"*.sql\"\nseverity = \"warning\"\n",
+    )
+    .unwrap();
+    let policy = SeverityPolicy::load(&path).unwrap();
+    assert_eq!(policy.rubric.as_deref(), Some("error: loses money"));
+    assert_eq!(
+        policy.floors,
+        [SeverityFloor::new("billing/**", Severity::Error), SeverityFloor::new("*.sql", Severity::Warning)]
+    );
+
+    fs::write(&path, "[[floor]]\npath = \"billing/**\"\nseverity = \"critical\"\n").unwrap();
+    let error = SeverityPolicy::load(&path).unwrap_err().to_string();
+    assert!(error.contains("critical"), "{}", error);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_rubric_and_floors() {
+    let dir = temp_dir("severity_policy");
+    fs::create_dir_all(dir.join("billing")).unwrap();
+    init_repo(&dir);
+    git(&dir, &["remote", "add", "origin", "https://127.0.0.1/acme/app.git"]);
+    fs::write(dir.join("billing/invoice.py"), "def total(amount):\n    return amount\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("billing/invoice.py"), "def total(amount):\n    return round(amount)\n").unwrap();
+    git(&dir, &["commit", "-q", "-a", "-m", "two"]);
+    fs::write(dir.join("policy.toml"), "rubric = \"error: anything that can lose a customer money\"\n").unwrap();
+    fs::write(dir.join("rubric.md"), "error: anything that can wake someone up at night").unwrap();
+    fs::write(dir.join("findings.json"), serde_json::to_string(&[finding("billing/invoice.py", Severity::Info)]).unwrap())
+        .unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .env_remove("GITHUB_TOKEN")
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+
+    let prompt = review(&["--severity-policy", "policy.toml", "HEAD~1", "HEAD"]);
+    assert!(prompt.contains("error: anything that can lose a customer money"), "{}", prompt);
+    let prompt = review(&["--severity-policy", "policy.toml", "--severity-rubric", "rubric.md", "HEAD~1", "HEAD"]);
+    assert!(prompt.contains("error: anything that can wake someone up at night"), "{}", prompt);
+    assert!(!prompt.contains("lose a customer money"), "{}", prompt);
+
+    let drafts = review(&[
+        "--file-issues",
+        "findings.json",
+        "--file-issues-dry-run",
+        "--severity-floor",
+        "billing/**=error",
+        "HEAD~1",
+        "HEAD",
+    ]);
+    assert!(
+        drafts.contains("**Severity:** error (severity raised to error by policy: path matches billing/**)"),
+        "{}",
+        drafts
+    );
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::budget::{Budget, CharsPerToken};
+use llm_code_review::diff::{parse_diff, split_diff_by_file};
+use llm_code_review::size_tier::{choose_tier, group_into_chunks, split_into_parts, SizeTier, TierThresholds};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// A diff of `files` files, each adding `lines` lines of 20 characters between 3 lines of context
+fn diff_of(files: usize, lines: usize) -> String {
+    let mut diff = String::new();
+    for file in 0..files {
+        diff.push_str(&format!(
+            "diff --git a/f{0}.txt b/f{0}.txt\n--- a/f{0}.txt\n+++ b/f{0}.txt\n@@ -1,6 +1,{1} @@\n",
+            file,
+            lines + 6
+        ));
+        diff.push_str(" context line one..\n context line two..\n context line 3....\n");
+        for line in 0..lines {
+            diff.push_str(&format!("+added line {:06}..\n", line));
+        }
+        diff.push_str(" context line 4....\n context line 5....\n context line 6....\n");
+    }
+    diff
+}
+
+#[test]
+fn test_choose_tier() {
+    let estimator = CharsPerToken(4);
+    let thresholds = TierThresholds { small_max_tokens: 100 };
+    let budget = Budget::new(1_000);
+    assert_eq!(choose_tier(&diff_of(1, 2), &budget, &thresholds, &estimator), SizeTier::Small);
+    assert_eq!(choose_tier(&diff_of(2, 20), &budget, &thresholds, &estimator), SizeTier::Medium);
+    // Over the budget with context, but not without it
+    let context_heavy = diff_of(12, 9);
+    assert!(context_heavy.len() / 4 > 1_000);
+    assert_eq!(choose_tier(&context_heavy, &budget, &thresholds, &estimator), SizeTier::Medium);
+    assert_eq!(choose_tier(&diff_of(4, 100), &budget, &thresholds, &estimator), SizeTier::Large);
+    // A small diff that doesn't fit what's left of the budget isn't small
+    let mut spent = Budget::new(1_000);
+    spent.spend(960);
+    assert_eq!(choose_tier(&diff_of(1, 2), &spent, &thresholds, &estimator), SizeTier::Medium);
+}
+
+#[test]
+fn test_split_into_parts() {
+    let estimator = CharsPerToken(4);
+    let budget = Budget::new(1_000);
+    let parts = split_into_parts(&diff_of(6, 40), 3, &budget, &estimator).unwrap();
+    assert_eq!(parts.len(), 2);
+    let paths: Vec<String> =
+        parts.iter().flat_map(|part| parse_diff(part)).map(|file| file.path).collect();
+    assert_eq!(paths, vec!["f0.txt", "f1.txt", "f2.txt", "f3.txt", "f4.txt", "f5.txt"]);
+    assert!(parts.iter().all(|part| part.len() / 4 <= 1_000));
+
+    // A file too large on its own is split between its hunks
+    let mut big = String::from("diff --git a/big.txt b/big.txt\n--- a/big.txt\n+++ b/big.txt\n");
+    for hunk in 0..3 {
+        big.push_str(&format!("@@ -{0},0 +{0},150 @@\n", hunk * 1_000 + 1));
+        big.push_str(&"+added line 000000..\n".repeat(150));
+    }
+    let parts = split_into_parts(&big, 3, &budget, &estimator).unwrap();
+    assert_eq!(parts.len(), 3);
+    assert!(parts.iter().all(|part| part.starts_with("diff --git a/big.txt b/big.txt\n")));
+
+    // A hunk too large on its own is split at line boundaries
+    let parts = split_into_parts(&diff_of(1, 300), 3, &budget, &estimator).unwrap();
+    assert!(parts.len() > 1);
+    assert!(parts.iter().all(|part| part.len() / 4 <= 1_000));
+    // Only a line too large on its own can't be split
+    let line = format!("diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -0,0 +1 @@\n+{}\n", "x".repeat(5_000));
+    assert!(split_into_parts(&line, 3, &budget, &estimator).is_err());
+}
+
+#[test]
+fn test_group_into_chunks() {
+    let estimator = CharsPerToken(4);
+    let budget = Budget::new(1_000);
+    let files = split_diff_by_file(&diff_of(6, 40));
+    let chunks = group_into_chunks(&files, &budget, &estimator);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks.concat(), diff_of(6, 40));
+    assert!(chunks.iter().all(|chunk| chunk.len() / 4 <= 1_000));
+
+    // A file too large on its own gets a chunk to itself, whole
+    let mut files = split_diff_by_file(&diff_of(1, 300));
+    files.extend(split_diff_by_file(&diff_of(1, 2)));
+    let chunks = group_into_chunks(&files, &budget, &estimator);
+    assert_eq!(chunks, vec![files[0].1.clone(), files[1].1.clone()]);
+    assert!(group_into_chunks(&[], &budget, &estimator).is_empty());
+}
+
+#[test]
+fn test_split_hunk_with_mixed_headers() {
+    const MIXED: &str = include_str!("fixtures/mixed_headers.diff");
+    let estimator = CharsPerToken(4);
+    let budget = Budget::new(1_000);
+    let lines: Vec<String> = (0..600).map(|line| format!("+    pub const C{:04}: u32 = 0;", line)).collect();
+    let generated = format!(
+        "diff --git a/generated.rs b/generated.rs\n--- a/generated.rs\n+++ b/generated.rs\n@@ -20,4 +20,604 @@ mod generated\n {}\n {}\n{}\n {}\n {}\n",
+        "// one", "// two", lines.join("\n"), "// three", "// four"
+    );
+    let parts = split_into_parts(&format!("{}{}", MIXED, generated), 3, &budget, &estimator).unwrap();
+    assert!(parts.iter().all(|part| part.len() / 4 <= 1_000), "{:#?}", parts);
+    // Renames, mode changes and binary files come through whole
+    assert!(parts[0].starts_with(MIXED), "{}", parts[0]);
+
+    let hunks: Vec<_> = parts
+        .iter()
+        .flat_map(|part| parse_diff(part))
+        .filter(|file| file.path == "generated.rs")
+        .flat_map(|file| file.hunks)
+        .collect();
+    assert!(hunks.len() >= 3, "{:#?}", hunks);
+    let ranges: Vec<_> = hunks.iter().map(|hunk| hunk.range().unwrap()).collect();
+    assert_eq!((ranges[0].old_start, ranges[0].new_start), (20, 20));
+    for pair in ranges.windows(2) {
+        assert_eq!(pair[1].new_start, pair[0].new_start + pair[0].new_len, "{:?}", ranges);
+    }
+    assert_eq!(ranges.iter().map(|range| range.new_len).sum::<usize>(), 604);
+    assert_eq!(ranges.iter().map(|range| range.old_len).sum::<usize>(), 4);
+    assert!(hunks.iter().all(|hunk| hunk.header.ends_with("@@ mod generated")), "{:#?}", hunks);
+    let added: Vec<&String> = hunks.iter().flat_map(|hunk| &hunk.lines).filter(|line| line.starts_with('+')).collect();
+    assert_eq!(added, lines.iter().collect::<Vec<_>>());
+}
+
+fn budget_report(path: &Path) -> serde_json::Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn test_size_tiers() {
+    let dir = temp_dir("size_tier");
+    init_repo(&dir);
+    fs::write(
+        dir.join("providers.toml"),
+        "[[provider]]\nname = \"tiny\"\nendpoint = \"http://localhost:8080\"\nmax_tokens = 1000\n\
+         chars_per_token = 4\nformat = \"openai\"\nsmall_diff_tokens = 100\n",
+    )
+    .unwrap();
+    for file in 0..6 {
+        fs::write(dir.join(format!("f{}.txt", file)), "start\n").unwrap();
+    }
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    let numbered = |count: usize| (0..count).map(|n| format!("line number {:06}\n", n)).collect::<String>();
+    fs::write(dir.join("f0.txt"), format!("start\n{}", numbered(2))).unwrap();
+    git(&dir, &["commit", "-q", "-a", "-m", "small"]);
+    fs::write(dir.join("f1.txt"), format!("start\n{}", numbered(40))).unwrap();
+    git(&dir, &["commit", "-q", "-a", "-m", "medium"]);
+    for file in 0..6 {
+        fs::write(dir.join(format!("f{}.txt", file)), format!("start\n{}", numbered(60))).unwrap();
+    }
+    git(&dir, &["commit", "-q", "-a", "-m", "large"]);
+
+    let review = |range: &str, output: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(["--llm-provider-config", "providers.toml", "--output-file", output, "--token-budget-report", "-v"])
+            .arg(range)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+
+    review("HEAD~3..HEAD~2", "small.md");
+    assert_eq!(budget_report(&dir.join("small.md.budget.json"))["size_tier"], "small");
+    review("HEAD~2..HEAD~1", "medium.md");
+    assert_eq!(budget_report(&dir.join("medium.md.budget.json"))["size_tier"], "medium");
+
+    let stdout = review("HEAD~1..HEAD", "large.md");
+    assert!(stdout.contains("so it's split into parts"), "{}", stdout);
+    assert!(!dir.join("large.md").exists());
+    let first = fs::read_to_string(dir.join("large.part-1.md")).unwrap();
+    assert!(first.contains("This is part 1, covering f0.txt"), "{}", first);
+    let report = budget_report(&dir.join("large.part-1.md.budget.json"));
+    assert_eq!(report["size_tier"], "large");
+    assert!(dir.join("large.part-2.md").exists());
+    // Each part's whole prompt, not just its diff, fits the 1000 tokens
+    let parts = (1..).map(|n| dir.join(format!("large.part-{}.md", n))).take_while(|path| path.exists());
+    for part in parts.map(|path| fs::read_to_string(path).unwrap()) {
+        assert!(part.trim_end().len() / 4 <= 1000, "{}", part.len());
+    }
+
+    // --chunk-mode auto keeps the whole files' context, and marks each chunk printed
+    let chunked = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(["--llm-provider-config", "providers.toml", "--no-wrap", "-v"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+        assert!(output.status.success(), "{}", stdout);
+        stdout
+    };
+    let stdout = chunked(&["--chunk-mode", "auto", "HEAD~1..HEAD"]);
+    assert!(stdout.contains("so it's split into chunks of whole files"), "{}", stdout);
+    let chunks = stdout.matches("\n--- chunk ").count() + usize::from(stdout.starts_with("--- chunk "));
+    assert!(chunks > 1, "{}", stdout);
+    assert!(stdout.contains(&format!("--- chunk 1/{} ---", chunks)), "{}", stdout);
+    assert!(stdout.contains("This is chunk 1, covering f0.txt"), "{}", stdout);
+    // A diff that fits isn't chunked
+    let stdout = chunked(&["--chunk-mode", "auto", "HEAD~2..HEAD~1"]);
+    assert!(!stdout.contains("--- chunk"), "{}", stdout);
+    let stdout = chunked(&["--chunk-mode", "per-file", "HEAD~2..HEAD~1"]);
+    assert!(stdout.contains("--- chunk 1/1 ---"), "{}", stdout);
+}
@@ -0,0 +1,62 @@
+use llm_code_review::smoke::{check_diff, MOCK_FINDINGS, SMOKE_TEST_DIFF, STAGES};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_sample() {
+    let (_, detail) = check_diff(SMOKE_TEST_DIFF).unwrap();
+    assert_eq!(detail, "2 files, 11 changed lines");
+    assert!(check_diff("").is_err());
+    assert!(check_diff("diff --git a/x b/x\n--- a/x\n+++ b/x\n").is_err());
+    assert!(MOCK_FINDINGS.contains("\"src/cart.py\""));
+}
+
+// Run --smoke-test with `args` outside any repository, returning the exit status and output
+fn smoke_test(args: &[&str]) -> (i32, String) {
+    let dir = std::env::temp_dir().join(format!("llm_code_review_smoke_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .arg("--smoke-test")
+        .args(args)
+        .output()
+        .expect("failed to run llm_code_review");
+    (output.status.code().unwrap(), String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn status(output: &str, stage: &str) -> String {
+    let row = output.lines().find(|line| line.starts_with(&format!("{} ", stage))).unwrap();
+    row.split_whitespace().nth(1).unwrap().to_string()
+}
+
+#[test]
+fn test_smoke_test() {
+    let (code, output) = smoke_test(&[]);
+    assert_eq!(code, 0, "{}", output);
+    for stage in STAGES {
+        assert_eq!(status(&output, stage), "PASS", "{}", output);
+    }
+    assert!(output.contains("the mock provider answered"), "{}", output);
+    assert!(output.contains("2 findings as markdown"), "{}", output);
+    assert!(output.contains("0 failures, 0 warnings\nFinished in "), "{}", output);
+
+    // The options given go through the pipeline
+    let (code, output) = smoke_test(&["--output-format", "asciidoc", "--severity-floor", "src/**=error"]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("2 findings, 1 raised by the severity policy"), "{}", output);
+    assert!(output.contains("2 findings as asciidoc"), "{}", output);
+    let (code, output) = smoke_test(&["--output-format", "json"]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("2 findings as json"), "{}", output);
+
+    // A failed stage fails the run, and the stages after it are skipped
+    let (code, output) = smoke_test(&["--min-change-size", "20"]);
+    assert_eq!(code, 1, "{}", output);
+    assert_eq!(status(&output, "filter"), "FAIL", "{}", output);
+    assert!(output.contains("the filters left nothing of the diff"), "{}", output);
+    assert_eq!(status(&output, "output"), "SKIP", "{}", output);
+
+    let (code, output) = smoke_test(&["--live"]);
+    assert_eq!(code, 1, "{}", output);
+    assert!(output.contains("--live needs a provider from --llm-provider-config"), "{}", output);
+}
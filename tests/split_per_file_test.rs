@@ -0,0 +1,68 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_split_per_file() {
+    let dir = temp_dir("split_per_file");
+    init_repo(&dir);
+    let lines: String = (0..300).map(|n| format!("line {:03} of the unchanged text around it\n", n)).collect();
+    fs::write(dir.join("big.txt"), &lines).unwrap();
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    1\n}\n").unwrap();
+    fs::write(dir.join("old_name.rs"), "fn name() {}\nfn other() {}\nfn third() {}\n").unwrap();
+    fs::write(dir.join("logo.png"), [0u8, 1, 2, 3]).unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("big.txt"), lines.replace("line 150 of", "LINE 150 of")).unwrap();
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    2\n}\n").unwrap();
+    git(&dir, &["mv", "old_name.rs", "new_name.rs"]);
+    fs::write(dir.join("new_name.rs"), "fn renamed() {}\nfn other() {}\nfn third() {}\n").unwrap();
+    fs::write(dir.join("logo.png"), [0u8, 1, 2, 4]).unwrap();
+
+    // All of big.txt as context is far over the limit on its own
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .args(["--split-per-file", "--tokenizer", "chars", "--max-tokens", "2500", "--no-provenance"])
+        .args(["--output-file", "review.md", "-v", "-U150", "HEAD"])
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("Leaving out logo.png: it's a binary file"), "{}", stdout);
+
+    let parts: Vec<String> = (1..)
+        .map(|n| dir.join(format!("review.part-{}.md", n)))
+        .take_while(|path| path.exists())
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect();
+    assert_eq!(parts.len(), 3, "{:?}", parts);
+    assert!(parts[0].contains("one file at a time, in 3 parts. This is part 1, covering big.txt;"), "{}", parts[0]);
+    assert!(parts[0].contains("+LINE 150 of") && parts[0].matches("of the unchanged text").count() < 299, "{}", parts[0]);
+    assert!(parts[1].contains("covering cart.rs;") && !parts[1].contains("big.txt"), "{}", parts[1]);
+    assert!(parts[2].contains("covering new_name.rs (renamed from old_name.rs);"), "{}", parts[2]);
+    assert!(parts[2].contains("rename from old_name.rs\nrename to new_name.rs"), "{}", parts[2]);
+    assert!(parts.iter().all(|part| !part.contains("logo.png")));
+}
+
+#[test]
+fn test_split_per_file_fixture() {
+    let dir = temp_dir("split_fixture");
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/per_file.diff");
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .args(["--split-per-file", "--no-provenance", "-v", "--diff-file"])
+        .arg(&fixture)
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("Leaving out logo.png: it's a binary file"), "{}", stdout);
+    assert!(stdout.contains("in 3 parts. This is part 1, covering deploy.sh (mode changed from 100644 to 100755);"), "{}", stdout);
+    assert!(stdout.contains("This is part 2, covering src/shop/old api.rs (renamed from src/legacy.rs);"), "{}", stdout);
+    assert!(stdout.contains("This is part 3, covering src/shop/order total.rs;"), "{}", stdout);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::{init_repo, temp_dir};
+use llm_code_review::prompt_schema::PromptSchema;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// What --stable-prompt printed for the repository below when each schema version was made. A
+// release must go on printing exactly this; a change to the default prompt needs a new version.
+const GOLDEN_V1: &str = include_str!("fixtures/stable_prompt/v1.txt");
+const GOLDEN_V1_CANARY: &str = include_str!("fixtures/stable_prompt/v1_canary.txt");
+
+#[test]
+fn test_parse_prompt_schema() {
+    assert_eq!("v1".parse::<PromptSchema>(), Ok(PromptSchema::V1));
+    assert_eq!(PromptSchema::CURRENT.to_string(), "v1");
+    assert_eq!("v9".parse::<PromptSchema>(), Err("unknown prompt schema `v9`; the versions are v1".to_string()));
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    // Fixed dates, so the commits' hashes in the provenance section are the same every run
+    let status = Command::new("git")
+        .current_dir(dir)
+        .env("GIT_AUTHOR_DATE", "2025-08-01T10:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2025-08-01T10:00:00Z")
+        .args(args)
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// A repository named `repo` with an uncommitted change, in a directory of its own
+fn repository() -> PathBuf {
+    let dir = temp_dir("stable_prompt").join("repo");
+    fs::create_dir_all(&dir).unwrap();
+    init_repo(&dir);
+    git(&dir, &["checkout", "-q", "-b", "main"]);
+    fs::write(dir.join("cart.py"), "def total(items):\n    return sum(item.price for item in items)\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Add the cart"]);
+    fs::write(dir.join("cart.py"), "def total(items):\n    return sum(item.price * item.quantity for item in items)\n").unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .args(args)
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    (output.status.success(), stdout, String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+#[test]
+fn test_stable_prompt_golden_files() {
+    let dir = repository();
+
+    let (success, prompt, stderr) = run(&dir, &["--stable-prompt"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(prompt, GOLDEN_V1);
+    assert!(prompt.contains("- Diff SHA-256: "), "{}", prompt);
+    assert!(!prompt.contains("- Generated: ") && !prompt.contains("- llm_code_review: "), "{}", prompt);
+    // The same again, and with the version given; the terminal's width doesn't wrap it either
+    assert_eq!(run(&dir, &["--stable-prompt"]).1, GOLDEN_V1);
+    assert_eq!(run(&dir, &["--stable-prompt", "--prompt-schema", "v1"]).1, GOLDEN_V1);
+
+    // The canary comes from the diff, so it's stable too
+    let (success, prompt, stderr) = run(&dir, &["--stable-prompt", "--canary"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(prompt, GOLDEN_V1_CANARY);
+
+    // Without it, the provenance section has the time in it
+    let (_, prompt, _) = run(&dir, &[]);
+    assert!(prompt.contains("- Generated: "), "{}", prompt);
+}
+
+#[test]
+fn test_stable_prompt_options() {
+    let dir = std::env::temp_dir();
+    let (success, _, stderr) = run(&dir, &["--prompt-schema", "v1"]);
+    assert!(!success);
+    assert!(stderr.contains("--stable-prompt"), "{}", stderr);
+
+    let (success, _, stderr) = run(&dir, &["--stable-prompt", "--prompt-schema", "v2"]);
+    assert!(!success);
+    assert!(stderr.contains("unknown prompt schema `v2`"), "{}", stderr);
+
+    // What the deadline leaves out depends on how long the run takes
+    let (success, _, stderr) = run(&dir, &["--stable-prompt", "--deadline-secs", "5"]);
+    assert!(!success);
+    assert!(stderr.contains("cannot be used with"), "{}", stderr);
+}
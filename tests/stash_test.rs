@@ -0,0 +1,32 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::error::ReviewError;
+use llm_code_review::review::get_stash_diff;
+use std::fs;
+
+#[test]
+fn test_get_stash_diff() {
+    let dir = temp_dir("stash");
+
+    init_repo(&dir);
+    fs::write(dir.join("file.txt"), "original\n").unwrap();
+    git(&dir, &["add", "file.txt"]);
+    git(&dir, &["commit", "-q", "-m", "initial"]);
+    fs::write(dir.join("file.txt"), "stashed change\n").unwrap();
+    git(&dir, &["stash", "-q"]);
+
+    // This is the only test in this binary, so changing directory is safe
+    std::env::set_current_dir(&dir).unwrap();
+
+    let diff = get_stash_diff(0).expect("stash@{0} should exist");
+    assert!(diff.contains("+stashed change"));
+    assert!(diff.contains("-original"));
+
+    match get_stash_diff(1) {
+        Err(ReviewError::NoSuchStash(1)) => {}
+        other => panic!("expected NoSuchStash(1), got {:?}", other),
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,24 @@
+use llm_code_review::stat::format_stat_as_table;
+
+const DIFF_STAT: &str = include_str!("fixtures/diff_stat.txt");
+
+#[test]
+fn test_format_stat_as_table() {
+    assert_eq!(
+        format_stat_as_table(DIFF_STAT),
+        "| File | Insertions | Deletions | Change% |\n\
+         |---|---:|---:|---:|\n\
+         | `assets/logo.png` (binary) | - | - | - |\n\
+         | `src/lib.rs` | 8 | 4 | 60.0% |\n\
+         | `src/main.rs` | 3 | 0 | 15.0% |\n\
+         | `tests/old_test.rs` | 0 | 5 | 25.0% |\n\
+         | **Total: 4 files** | 11 | 9 | 100.0% |"
+    );
+}
+
+#[test]
+fn test_format_scaled_stat_graph() {
+    // git shrinks the graph when a file has more changes than fit; the counts are scaled back up
+    let table = format_stat_as_table(&format!(" big.rs | 300 {}{}\n", "+".repeat(30), "-".repeat(10)));
+    assert!(table.contains("| `big.rs` | 225 | 75 | 100.0% |"), "{}", table);
+}
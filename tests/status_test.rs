@@ -0,0 +1,90 @@
+use llm_code_review::findings::{Finding, Severity};
+use llm_code_review::status::{parse_status_address, StatusBoard};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::process::Command;
+use std::time::Instant;
+
+fn get(address: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_parse_status_address() {
+    assert_eq!(parse_status_address("8080"), Ok("127.0.0.1:8080".to_string()));
+    assert_eq!(parse_status_address(":8080"), Ok("127.0.0.1:8080".to_string()));
+    assert_eq!(parse_status_address("0.0.0.0:9000"), Ok("0.0.0.0:9000".to_string()));
+    assert_eq!(parse_status_address("[::1]:9000"), Ok("[::1]:9000".to_string()));
+    assert!(parse_status_address("localhost").is_err());
+    assert!(parse_status_address("99999").is_err());
+}
+
+#[test]
+fn test_status_board() {
+    // Without --status-server, updates go nowhere
+    let board = StatusBoard::default();
+    board.phase("diff");
+    assert_eq!(board.snapshot(), None);
+    assert_eq!(board.address(), None);
+
+    let board = StatusBoard::serve("127.0.0.1:0", Instant::now()).unwrap();
+    let address = board.address().unwrap();
+    board.phase("review");
+    board.update(|status| {
+        status.chunks_total = 3;
+        status.chunks_done = 1;
+        status.prompt_tokens = 1200;
+        status.findings.push(Finding {
+            path: "src/lib.rs".to_string(),
+            line: Some(4),
+            severity: Severity::Warning,
+            category: None,
+            comment: "This error is ignored.".to_string(),
+            follow_up: false,
+            policy_note: None,
+            confidence: None,
+            extracted: false,
+            lower_priority: false,
+        });
+    });
+
+    let response = get(address, "GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Content-Type: application/json\r\n"), "{}", response);
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    let status: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(status["phase"], "review");
+    assert_eq!((status["chunks_total"].clone(), status["chunks_done"].clone()), (3.into(), 1.into()));
+    assert_eq!(status["prompt_tokens"], 1200);
+    assert_eq!(status["findings"][0]["path"], "src/lib.rs");
+    assert!(status["elapsed_secs"].is_f64());
+
+    assert!(get(address, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(get(address, "POST /status HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+
+    // The server stops with the run
+    drop(board);
+    assert!(TcpStream::connect(address).is_err());
+}
+
+#[test]
+fn test_status_server_flag() {
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .args(["--status-server", "127.0.0.1:0", "-v", "--raw-code", "-"])
+        .output()
+        .expect("failed to run llm_code_review");
+    let stdout = format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("Serving the run's status on http://127.0.0.1:"), "{}", stdout);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .args(["--status-server", "nowhere"])
+        .output()
+        .expect("failed to run llm_code_review");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not a port or HOST:PORT"));
+}
@@ -0,0 +1,39 @@
+use llm_code_review::error::ReviewError;
+use llm_code_review::strip::{load_strip_phrases, strip_phrases};
+use std::path::Path;
+
+#[test]
+fn test_strip_phrases() {
+    let phrases = load_strip_phrases(Path::new("tests/fixtures/strip_phrases.txt")).unwrap();
+    assert_eq!(phrases.len(), 3);
+
+    let review = "As an AI language model, I found two issues.\n\
+                  \n\
+                  1. `parse` can panic.\n\
+                  \n\
+                  Overall, this PR looks good.\n\
+                  \n\
+                  In conclusion, fix the panic. (a.b)\n";
+    let (stripped, removals) = strip_phrases(review, &phrases);
+    assert_eq!(removals, 3);
+    // The removed line doesn't leave two blank lines behind; "." is matched literally
+    assert_eq!(
+        stripped,
+        "I found two issues.\n\n1. `parse` can panic.\n\nfix the panic. (a.b)\n"
+    );
+
+    let (unchanged, removals) = strip_phrases("Nothing to strip here.\n\n", &phrases);
+    assert_eq!((unchanged.as_str(), removals), ("Nothing to strip here.\n\n", 0));
+}
+
+#[test]
+fn test_load_strip_phrases_invalid_regex() {
+    let path = std::env::temp_dir().join(format!("llm_code_review_strip_{}.txt", std::process::id()));
+    std::fs::write(&path, "fine\n/(unclosed/\n").unwrap();
+    let result = load_strip_phrases(&path);
+    std::fs::remove_file(&path).unwrap();
+    match result {
+        Err(ReviewError::Config(msg)) => assert!(msg.contains("line 2"), "{}", msg),
+        other => panic!("expected a config error, got {:?}", other),
+    }
+}
@@ -0,0 +1,24 @@
+use llm_code_review::system_prompt::{PromptSection, SystemPrompt, DEFAULT_SYSTEM_PROMPT, PROMPT_ROLE};
+
+// A copy of the default system prompt as it was when it was one file. Users' prompts must not
+// change unless this snapshot is deliberately updated.
+const SNAPSHOT: &str = include_str!("fixtures/default_system_prompt.txt");
+
+#[test]
+fn test_default_system_prompt_snapshot() {
+    assert_eq!(SystemPrompt::default().build(), SNAPSHOT);
+    assert_eq!(DEFAULT_SYSTEM_PROMPT, SNAPSHOT);
+}
+
+#[test]
+fn test_system_prompt_substitution() {
+    let prompt = SystemPrompt::default()
+        .with_section(PromptSection::SeverityRubric, "## Severity\n- blocker: must fix")
+        .with_section(PromptSection::Tone, "");
+
+    assert_eq!(prompt.section(PromptSection::Role), PROMPT_ROLE);
+    let built = prompt.build();
+    assert!(built.contains("(or \"None\")\n\n## Severity\n- blocker: must fix\n"));
+    // Empty sections are left out rather than leaving a gap
+    assert!(built.ends_with("must fix\n"));
+}
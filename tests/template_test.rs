@@ -0,0 +1,110 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::prompt_schema::PromptSchema;
+use llm_code_review::template::{Placeholder, PromptTemplate, DEFAULT_TEMPLATE};
+use std::fs;
+use std::process::Command;
+
+fn values(placeholder: Placeholder) -> String {
+    match placeholder {
+        Placeholder::SystemPrompt => "Review this.".to_string(),
+        Placeholder::OutputFormat => String::new(),
+        Placeholder::Context => "\n## Additional Context\nA Rust CLI\n".to_string(),
+        Placeholder::DiffHeading => "PR Code".to_string(),
+        Placeholder::Diff => "+fn total() {}".to_string(),
+        Placeholder::Branch => "main".to_string(),
+        Placeholder::DiffArgs => "main...HEAD".to_string(),
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(PromptTemplate::default().source(), DEFAULT_TEMPLATE);
+    assert_eq!(PromptSchema::V1.template(), PromptTemplate::default());
+    assert_eq!(
+        PromptTemplate::default().render(values),
+        "Review this.\n## Additional Context\nA Rust CLI\n\n\n# PR Code\n\n+fn total() {}"
+    );
+
+    // Placeholders a template leaves out are left out of the prompt
+    let template = PromptTemplate::parse("# {{ diff_heading }} on {{branch}} ({{diff_args}})\n{{diff}}\n\n{{system_prompt}}").unwrap();
+    assert!(!template.places(Placeholder::Context) && template.places(Placeholder::Branch));
+    assert_eq!(template.render(values), "# PR Code on main (main...HEAD)\n+fn total() {}\n\nReview this.");
+
+    let error = PromptTemplate::parse("{{diff}}\n{{instructions}}").unwrap_err();
+    assert!(error.starts_with("unknown placeholder `{{instructions}}`; the placeholders are {{system_prompt}}, "), "{}", error);
+    let error = PromptTemplate::parse("{{system_prompt}}\n\n{{diff").unwrap_err();
+    assert_eq!(error, "the placeholder on line 3 has no closing `}}`");
+    let error = PromptTemplate::parse("{{system_prompt}}\n{{context}}").unwrap_err();
+    assert!(error.starts_with("there's no {{diff}} placeholder"), "{}", error);
+
+    // \{{ is a literal {{, which is not a placeholder
+    let template = PromptTemplate::parse("Use \\{{name}} in Handlebars.\n{{diff}}").unwrap();
+    assert_eq!(template.render(values), "Use {{name}} in Handlebars.\n+fn total() {}");
+    assert!(PromptTemplate::parse("\\{{diff}}").is_err());
+}
+
+#[test]
+fn test_empty_sections() {
+    // A placeholder alone on its line that comes out empty takes the line with it
+    let template = PromptTemplate::parse("{{diff}}\n{{context}}\n{{output_format}}\n{{system_prompt}}\n").unwrap();
+    let empty = |placeholder| match placeholder {
+        Placeholder::Context => String::new(),
+        placeholder => values(placeholder),
+    };
+    assert_eq!(template.render(empty), "+fn total() {}\nReview this.\n");
+    // but not one sharing its line
+    let template = PromptTemplate::parse("Context: {{context}}\n{{diff}}").unwrap();
+    assert_eq!(template.render(empty), "Context: \n+fn total() {}");
+}
+
+#[test]
+fn test_prompt_template_option() {
+    let dir = temp_dir("template");
+    let diff = "diff --git a/cart.rs b/cart.rs\n--- a/cart.rs\n+++ b/cart.rs\n@@ -1 +1 @@\n-    1\n+    2\n";
+    fs::write(dir.join("cart.diff"), diff).unwrap();
+    fs::write(dir.join("diff_first.md"), "# {{diff_heading}}\n\n{{diff}}{{context}}\n\n{{output_format}}\n{{system_prompt}}\n").unwrap();
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .args(["--diff-file", "cart.diff", "--system-prompt", "Review this."])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+
+    let (ok, prompt) = review(&["--prompt-template", "diff_first.md", "--output-format", "json"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.starts_with("# PR Code\n\ndiff --git a/cart.rs b/cart.rs\n"), "{}", prompt);
+    // The output format instructions come where the template puts them, and only there
+    let format_at = prompt.find("JSON").unwrap();
+    assert!(format_at < prompt.find("Review this.").unwrap(), "{}", prompt);
+    assert_eq!(prompt.matches("Review this.").count(), 1, "{}", prompt);
+    let (ok, prompt) = review(&["--prompt-template", "diff_first.md", "--context", "A Rust CLI"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("+    2\n\n## Additional Context\nA Rust CLI\n"), "{}", prompt);
+
+    // A config file can name one, relative to itself
+    fs::create_dir_all(dir.join("config/llm_code_review")).unwrap();
+    fs::write(dir.join("config/llm_code_review/config.toml"), "prompt_template = \"../../diff_first.md\"\n").unwrap();
+    let (ok, prompt) = review(&[]);
+    assert!(ok && prompt.starts_with("# PR Code\n"), "{}", prompt);
+    let (ok, template) = review(&["--show-template"]);
+    assert!(ok, "{}", template);
+    assert!(template.ends_with(&fs::read_to_string(dir.join("diff_first.md")).unwrap()), "{}", template);
+    let (ok, template) = review(&["--show-template", "--no-config"]);
+    assert!(ok && template.ends_with(&format!("{}\n", DEFAULT_TEMPLATE)), "{}", template);
+
+    fs::write(dir.join("no_diff.md"), "{{system_prompt}}\n{{context}}\n").unwrap();
+    let (ok, output) = review(&["--prompt-template", "no_diff.md"]);
+    assert!(!ok);
+    assert!(output.contains("no_diff.md: there's no {{diff}} placeholder"), "{}", output);
+    fs::write(dir.join("unknown.md"), "{{diff}}\n{{ticket}}\n").unwrap();
+    let (ok, output) = review(&["--prompt-template", "unknown.md"]);
+    assert!(!ok && output.contains("unknown placeholder `{{ticket}}`"), "{}", output);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::history::load_reviews;
+use llm_code_review::provider::load_provider_config;
+use llm_code_review::threads::ThreadStore;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_reuse_thread_within_ttl() {
+    let providers = load_provider_config(Path::new("tests/fixtures/providers.toml")).unwrap();
+    let (claude, local) = (&providers[0], &providers[1]);
+    let started = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+    let mut store = ThreadStore::default();
+    assert_eq!(store.reusable(claude, "abc", started), None);
+    store.record(claude, "abc", "thread_1", started);
+
+    assert_eq!(store.reusable(claude, "abc", started + Duration::from_secs(60)), Some("thread_1"));
+    // Expired, for another diff, or for a provider without server-side conversations
+    assert_eq!(store.reusable(claude, "abc", started + Duration::from_secs(3600)), None);
+    assert_eq!(store.reusable(claude, "def", started), None);
+    assert_eq!(store.reusable(local, "abc", started), None);
+
+    store.forget("abc");
+    assert_eq!(store.reusable(claude, "abc", started), None);
+}
+
+#[test]
+fn test_thread_store_round_trip() {
+    let dir = temp_dir("threads");
+    let providers = load_provider_config(Path::new("tests/fixtures/providers.toml")).unwrap();
+    let now = SystemTime::now();
+
+    assert_eq!(ThreadStore::load(&dir).unwrap(), ThreadStore::default());
+    let mut store = ThreadStore::default();
+    store.record(&providers[0], "abc", "thread_1", now);
+    store.save(&dir).unwrap();
+    assert_eq!(ThreadStore::load(&dir).unwrap(), store);
+
+    // The store lives in the history directory but isn't a past review
+    fs::write(dir.join("review.md"), "src/lib.rs looks fine").unwrap();
+    let reviews = load_reviews(&dir).unwrap();
+    assert_eq!(reviews.len(), 1);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
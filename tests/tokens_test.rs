@@ -0,0 +1,134 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::budget::{CharsPerToken, TokenEstimator};
+use llm_code_review::error::ReviewError;
+use llm_code_review::review::check_prompt_tokens;
+use llm_code_review::tokens::{count_tokens, Encoding, TokenCache, Tokenizer, TOKEN_CACHE_MIN_BYTES};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_count_tokens() {
+    assert_eq!(count_tokens("hello world", Encoding::Cl100k), 2);
+    assert_eq!(count_tokens("", Encoding::O200k), 0);
+    // Code takes more tokens than the old four-characters-per-token guess allowed
+    let code = "fn total(&self) -> u64 { self.items.iter().map(|i| i.price).sum() }\n";
+    assert!(count_tokens(code, Encoding::Cl100k) > code.len() / 4, "{}", count_tokens(code, Encoding::Cl100k));
+    // Special tokens in a diff are text like any other
+    assert!(count_tokens("<|endoftext|>", Encoding::Cl100k) > 1);
+
+    assert_eq!(Encoding::for_model("gpt-4o"), Encoding::O200k);
+    assert_eq!(Encoding::for_model("gpt-4"), Encoding::Cl100k);
+    assert_eq!(Encoding::for_model("claude-sonnet-4-5"), Encoding::Cl100k);
+}
+
+#[test]
+fn test_parse_tokenizer() {
+    assert_eq!(Tokenizer::parse("o200k", 4), Ok(Tokenizer::Bpe(Encoding::O200k)));
+    assert_eq!(Tokenizer::parse("cl100k_base", 4), Ok(Tokenizer::Bpe(Encoding::Cl100k)));
+    assert_eq!(Tokenizer::parse("chars", 3), Ok(Tokenizer::Chars(CharsPerToken(3))));
+    assert_eq!(Tokenizer::parse("chars", 3).unwrap().estimate_tokens("abcdef"), 2);
+    assert_eq!(
+        Tokenizer::parse("gpt2", 4),
+        Err("unknown tokenizer `gpt2`; expected one of cl100k, o200k, chars".to_string())
+    );
+}
+
+#[test]
+fn test_token_cache() {
+    let dir = temp_dir("token_cache");
+    let path = dir.join("tokens.json");
+    let text = "fn total() -> u32 { 1 }\n".repeat(TOKEN_CACHE_MIN_BYTES / 10);
+    let mut cache = TokenCache::load(&path);
+    assert!(cache.is_empty());
+    assert_eq!(cache.count(&text, Encoding::Cl100k), count_tokens(&text, Encoding::Cl100k));
+    assert_eq!(cache.count(&text, Encoding::O200k), count_tokens(&text, Encoding::O200k));
+    cache.save().unwrap();
+
+    // Counts are read back rather than worked out again, so a changed one shows
+    let saved = fs::read_to_string(&path).unwrap();
+    assert!(saved.starts_with(r#"{"version":1,"counts":{"cl100k:"#), "{}", saved);
+    let count = count_tokens(&text, Encoding::Cl100k).to_string();
+    fs::write(&path, saved.replacen(&format!(":{}", count), ":7", 1)).unwrap();
+    let mut cache = TokenCache::load(&path);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.count(&text, Encoding::Cl100k), 7);
+
+    // Two runs saving one after the other keep each other's counts
+    let other = "let x = 2;\n".repeat(TOKEN_CACHE_MIN_BYTES / 10);
+    let mut first = TokenCache::load(&path);
+    let mut second = TokenCache::load(&path);
+    second.count(&other, Encoding::Cl100k);
+    second.save().unwrap();
+    first.save().unwrap();
+    assert_eq!(TokenCache::load(&path).len(), 3);
+
+    fs::write(&path, "not json").unwrap();
+    assert!(TokenCache::load(&path).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check_prompt_tokens() {
+    let chars = CharsPerToken(1);
+    assert_eq!(check_prompt_tokens("sys", "sys diff", &chars, 8).unwrap(), 8);
+    let error = check_prompt_tokens("sys", "sys diff!", &chars, 8).unwrap_err();
+    assert!(matches!(error, ReviewError::PromptTooLarge { tokens: 9, system_prompt_tokens: 3, max_tokens: 8 }));
+    assert!(error.to_string().starts_with("Prompt is too large to review: 9 tokens"), "{}", error);
+    let error = check_prompt_tokens("a long system prompt", "a long system prompt diff", &chars, 8).unwrap_err();
+    assert!(error.to_string().starts_with("The system prompt alone is 20 tokens"), "{}", error);
+}
+
+#[test]
+fn test_max_tokens() {
+    let dir = temp_dir("tokens");
+    init_repo(&dir);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+
+    let (success, output) = review(&["--max-tokens", "2000", "--tokenizer", "o200k"]);
+    assert!(success, "{}", output);
+    let long_prompt = "Review carefully. ".repeat(400);
+    let (success, output) = review(&["--max-tokens", "1000", "--system-prompt", &long_prompt]);
+    assert!(!success);
+    assert!(output.contains("The system prompt alone is"), "{}", output);
+    let (success, output) = review(&["--model-context", "1000", "--tokenizer", "chars", "--system-prompt", &long_prompt]);
+    assert!(!success);
+    // A quarter of its characters, with the output rules added to it
+    assert!(output.contains("The system prompt alone is 18"), "{}", output);
+    // One token a character, overriding the default of four
+    let (success, output) = review(&["--max-tokens", "1000", "--chars-per-token", "1", "--system-prompt", &long_prompt]);
+    assert!(!success);
+    assert!(output.contains("The system prompt alone is 7"), "{}", output);
+
+    // Too large for the default limit, but --model knows its window even without a provider
+    let huge_prompt = "x".repeat(60_000);
+    let (success, output) = review(&["--chars-per-token", "1", "--system-prompt", &huge_prompt]);
+    assert!(!success, "{}", output);
+    let (success, output) = review(&["--chars-per-token", "1", "--model", "claude-sonnet-4-5", "--system-prompt", &huge_prompt]);
+    assert!(success, "{}", output);
+
+    // --token-cache saves the counts of long files, and the next run takes them from it
+    let long_file: String = (0..400).map(|i| format!("line {} of the file\n", i)).collect();
+    fs::write(dir.join("a.txt"), long_file).unwrap();
+    let (success, output) = review(&["--token-cache", "tokens.json", "--verbose"]);
+    assert!(success, "{}", output);
+    assert!(!output.contains("900000"), "{}", output);
+    let cache = fs::read_to_string(dir.join("tokens.json")).unwrap();
+    let inflated = regex::Regex::new(r#"("cl100k:[0-9a-f]+":)\d+"#).unwrap().replace_all(&cache, "${1}900000").to_string();
+    fs::write(dir.join("tokens.json"), inflated).unwrap();
+    let (_, output) = review(&["--token-cache", "tokens.json", "--verbose"]);
+    assert!(regex::Regex::new(r"a\.txt +\d+ +900000 ").unwrap().is_match(&output), "{}", output);
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::findings::{Finding, Severity};
+use llm_code_review::locale::Locale;
+use llm_code_review::output::{AnnotationLevel, OutputFormat};
+use llm_code_review::transcript::{Transcript, TRANSCRIPT_VERSION};
+use std::fs;
+use std::process::Command;
+
+const REVIEW: &str = r#"```json
+{"summary": "Mostly fine.", "findings": [
+  {"path": "src/cart.rs", "line": 12, "severity": "error", "comment": "The total overflows for large carts."},
+  {"path": "src/cart.rs", "line": 3, "severity": "info", "comment": "Unused import."}
+]}
+```"#;
+
+#[test]
+fn test_transcript_new() {
+    let transcript = Transcript::new(Some("claude-test".to_string()), REVIEW);
+    assert_eq!(transcript.version, TRANSCRIPT_VERSION);
+    assert_eq!(transcript.summary.as_deref(), Some("Mostly fine."));
+    assert_eq!(transcript.findings.as_ref().map(Vec::len), Some(2));
+    assert_eq!(transcript.review, REVIEW);
+
+    let findings = Transcript::new(None, r#"[{"path": "a.rs", "severity": "warning", "comment": "Check this."}]"#);
+    assert_eq!(findings.findings.unwrap()[0].severity, Severity::Warning);
+    assert_eq!(Transcript::new(None, "Looks good to me.").findings, None);
+}
+
+#[test]
+fn test_transcript_parse() {
+    let saved = serde_json::to_string(&Transcript::new(None, REVIEW)).unwrap();
+    assert_eq!(Transcript::parse(&saved).unwrap(), Transcript::new(None, REVIEW));
+
+    // A review saved as plain text, as reviews were before transcripts
+    let plain = Transcript::parse("## Review\n\nLooks good.\n").unwrap();
+    assert_eq!((plain.version, plain.findings, plain.review.as_str()), (0, None, "## Review\n\nLooks good.\n"));
+    // An object without a version is the first version's layout, read as it is
+    let unversioned = Transcript::parse(r#"{"review": "Fine.", "findings": []}"#).unwrap();
+    assert_eq!((unversioned.version, unversioned.findings), (0, Some(Vec::new())));
+    // A findings file, like --file-issues reads
+    let findings = Transcript::parse(r#"[{"path": "a.rs", "line": 2, "severity": "info", "comment": "Nit."}]"#).unwrap();
+    assert_eq!(findings.findings.unwrap().len(), 1);
+
+    let newer = Transcript::parse(r#"{"version": 99, "review": "Fine."}"#).unwrap_err();
+    assert!(newer.starts_with("transcript version 99 is newer"), "{}", newer);
+    assert!(Transcript::parse(r#"{"findings": [{"path": "a.rs"}]}"#).is_err());
+}
+
+#[test]
+fn test_transcript_render() {
+    let transcript = Transcript::new(None, REVIEW);
+    let markdown = transcript.render(OutputFormat::Markdown, Locale::En).unwrap();
+    assert!(markdown.starts_with("Mostly fine.\n\n<a id=\"findings\"></a>\n## Findings"), "{}", markdown);
+    let asciidoc = transcript.render(OutputFormat::AsciiDoc, Locale::En).unwrap();
+    assert!(asciidoc.contains("== Findings") && asciidoc.contains("The total overflows"), "{}", asciidoc);
+    assert_eq!(
+        transcript.render(OutputFormat::GitHubActions(AnnotationLevel::Warning), Locale::En).unwrap(),
+        "::error file=src/cart.rs,line=12::The total overflows for large carts.\n::notice file=src/cart.rs,line=3::Unused import."
+    );
+    let multiline = Transcript {
+        findings: Some(vec![Finding {
+            path: "a.rs".to_string(),
+            line: None,
+            severity: Severity::Warning,
+            category: None,
+            comment: "Two\nlines at 100%".to_string(),
+            follow_up: false,
+            policy_note: None,
+            confidence: None,
+            extracted: false,
+            lower_priority: false,
+        }]),
+        ..Transcript::default()
+    };
+    assert_eq!(multiline.render(OutputFormat::GitHubActions(AnnotationLevel::Warning), Locale::En).unwrap(), "::warning file=a.rs::Two%0Alines at 100%25");
+    let mut unsure = multiline.clone();
+    unsure.findings.as_mut().unwrap()[0].confidence = Some(0.25);
+    assert_eq!(
+        unsure.render(OutputFormat::GitHubActions(AnnotationLevel::Warning), Locale::En).unwrap(),
+        "::warning file=a.rs::Possibly wrong, please verify (confidence 0.25): Two%0Alines at 100%25"
+    );
+    assert_eq!(Transcript::new(None, "Looks good.").render(OutputFormat::MediaWiki, Locale::En), None);
+}
+
+#[test]
+fn test_replay() {
+    let dir = temp_dir("transcript");
+    Transcript::new(Some("claude-test".to_string()), REVIEW).save(&dir.join("review.json")).unwrap();
+    fs::write(dir.join("review.md"), "Looks good.\n").unwrap();
+
+    // Not a repository, and no provider: replaying needs neither
+    let replay = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env_remove("ANTHROPIC_API_KEY")
+            .env_remove("OPENAI_API_KEY")
+            .args(["--no-wrap", "--replay"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+    let (success, output) = replay(&["review.json", "-F", "mediawiki"]);
+    assert!(success, "{}", output);
+    assert!(output.starts_with("Mostly fine.\n\n<span id=\"findings\"></span>\n== Findings =="), "{}", output);
+    let (success, output) = replay(&["review.json", "-F", "github-actions", "--output-file", "annotations.txt"]);
+    assert!(success, "{}", output);
+    assert!(fs::read_to_string(dir.join("annotations.txt")).unwrap().starts_with("::error file=src/cart.rs,line=12::"));
+
+    let unsure = r#"[{"path": "a.rs", "line": 1, "severity": "warning", "comment": "Maybe racy.", "confidence": 0.2},
+        {"path": "a.rs", "line": 9, "severity": "error", "comment": "Off by one.", "confidence": 0.9}]"#;
+    fs::write(dir.join("unsure.json"), unsure).unwrap();
+    let (success, output) = replay(&["unsure.json"]);
+    assert!(success && output.contains("### Possibly wrong, please verify\n\n- **warning** `a.rs` (line 1): Maybe racy."), "{}", output);
+    let (success, output) = replay(&["unsure.json", "--min-confidence", "0.5"]);
+    assert!(success && !output.contains("Maybe racy") && output.contains("Off by one."), "{}", output);
+    let (success, output) = replay(&["unsure.json", "--max-findings", "1"]);
+    assert!(success, "{}", output);
+    let (shown, appendix) = output.split_once("<details>").expect(&output);
+    assert!(shown.contains("Off by one.") && appendix.contains("- **warning** `a.rs` (line 1): Maybe racy."), "{}", output);
+
+    let (success, output) = replay(&["review.md", "-F", "asciidoc"]);
+    assert!(success, "{}", output);
+    assert!(output.contains("review.md has no structured findings to render as asciidoc"), "{}", output);
+    assert!(output.ends_with("Looks good.\n"), "{}", output);
+    let (success, output) = replay(&["missing.json"]);
+    assert!(!success);
+    assert!(output.contains("missing.json"), "{}", output);
+}
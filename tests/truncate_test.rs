@@ -0,0 +1,89 @@
+use llm_code_review::truncate::{graphemes, truncate_chars, truncate_graphemes};
+use proptest::prelude::*;
+
+#[test]
+fn test_truncate_chars() {
+    assert_eq!(truncate_chars("short", 10), "short");
+    assert_eq!(truncate_chars("exactly", 7), "exactly");
+    assert_eq!(truncate_chars("one two three", 8), "one two…");
+    // The space the cut leaves at the end goes
+    assert_eq!(truncate_chars("one two three", 9), "one two…");
+    assert_eq!(truncate_chars("漢字のテキストです", 4), "漢字の…");
+    assert_eq!(truncate_chars("anything", 1), "…");
+    assert_eq!(truncate_chars("anything", 0), "");
+    assert_eq!(truncate_chars("", 0), "");
+}
+
+#[test]
+fn test_graphemes() {
+    assert_eq!(graphemes("ae\u{301}b"), vec!["a", "e\u{301}", "b"]);
+    assert_eq!(graphemes("👍🏽!"), vec!["👍🏽", "!"]);
+    // A family, joined with zero-width joiners
+    assert_eq!(graphemes("👨\u{200D}👩\u{200D}👧x"), vec!["👨\u{200D}👩\u{200D}👧", "x"]);
+    // Three flags' regional indicators, in pairs
+    assert_eq!(graphemes("🇯🇵🇫🇷🇩🇪"), vec!["🇯🇵", "🇫🇷", "🇩🇪"]);
+    assert_eq!(graphemes("1\u{FE0F}\u{20E3}\r\n"), vec!["1\u{FE0F}\u{20E3}", "\r\n"]);
+    assert_eq!(graphemes("한국어"), vec!["한", "국", "어"]);
+    assert_eq!(graphemes(""), Vec::<&str>::new());
+}
+
+#[test]
+fn test_truncate_graphemes() {
+    assert_eq!(truncate_graphemes("👍🏽👍🏽👍🏽👍🏽", 3), "👍🏽👍🏽…");
+    assert_eq!(truncate_graphemes("🇯🇵🇫🇷🇩🇪", 3), "🇯🇵🇫🇷🇩🇪");
+    assert_eq!(truncate_graphemes("🇯🇵🇫🇷🇩🇪", 2), "🇯🇵…");
+    assert_eq!(truncate_graphemes("café au lait", 5), "café…");
+    // Characters would have split the skin tone from its hand
+    assert_eq!(truncate_chars("👍🏽👍🏽", 3), "👍🏽…");
+    assert_eq!(truncate_chars("👍🏽👍🏽", 2), "👍…");
+}
+
+// Pieces that are easy to cut in the wrong place: multi-byte letters, CJK, combining accents,
+// emoji with skin tones and joiners, flags and keycaps
+const PIECES: &[&str] = &[
+    "a", " ", "\n", "é", "e\u{301}", "漢", "字", "テ", "한", "👍", "👍🏽", "👨\u{200D}👩\u{200D}👧\u{200D}👦",
+    "🏳\u{FE0F}\u{200D}🌈", "🇯🇵", "🇺", "1\u{FE0F}\u{20E3}", "\r\n", "…",
+];
+
+fn text() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(PIECES), 0..40).prop_map(|pieces| pieces.concat())
+}
+
+proptest! {
+    #[test]
+    fn truncating_chars_never_goes_over(text in text(), max in 0usize..30) {
+        let cut = truncate_chars(&text, max);
+        prop_assert!(cut.chars().count() <= max);
+        if text.chars().count() <= max {
+            prop_assert_eq!(&cut, &text);
+        } else if max > 0 {
+            let kept = cut.strip_suffix('…').unwrap();
+            prop_assert!(text.starts_with(kept));
+        }
+    }
+
+    #[test]
+    fn truncating_graphemes_keeps_whole_clusters(text in text(), max in 0usize..30) {
+        let cut = truncate_graphemes(&text, max);
+        prop_assert!(graphemes(&cut).len() <= max);
+        let clusters = graphemes(&text);
+        if clusters.len() <= max {
+            prop_assert_eq!(&cut, &text);
+        } else if max > 0 {
+            let kept = cut.strip_suffix('…').unwrap();
+            let kept_clusters = graphemes(kept);
+            prop_assert_eq!(&kept_clusters[..], &clusters[..kept_clusters.len()]);
+        }
+    }
+
+    #[test]
+    fn graphemes_cover_the_text(text in text()) {
+        prop_assert_eq!(graphemes(&text).concat(), text);
+    }
+
+    #[test]
+    fn truncating_arbitrary_text_never_panics(text in ".*", max in 0usize..50) {
+        prop_assert!(truncate_chars(&text, max).chars().count() <= max);
+        prop_assert!(graphemes(&truncate_graphemes(&text, max)).len() <= max);
+    }
+}
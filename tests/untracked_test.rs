@@ -0,0 +1,124 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::vcs::{get_untracked_diff, untracked_files, untracked_pathspecs};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// A repository with a commit, a change to a tracked file, and untracked files: a short one
+/// without a final newline, a long one, a binary one, an ignored one and one in a directory
+/// with a space in its name
+fn repository(name: &str) -> PathBuf {
+    let dir = temp_dir(&format!("untracked_{}", name));
+    fs::create_dir_all(dir.join("new module")).unwrap();
+    init_repo(&dir);
+    fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    1\n}\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Start"]);
+    fs::write(dir.join("cart.rs"), "fn total() -> u32 {\n    2\n}\n").unwrap();
+    fs::write(dir.join("new module/tax.rs"), "fn tax() -> u32 {\n    0\n}").unwrap();
+    let long: String = (1..=30).map(|n| format!("const RATE_{0}: u32 = {0};\n", n)).collect();
+    fs::write(dir.join("rates.rs"), long).unwrap();
+    fs::write(dir.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    fs::write(dir.join("debug.log"), "ignored\n").unwrap();
+    dir
+}
+
+#[test]
+fn test_untracked_pathspecs() {
+    assert_eq!(untracked_pathspecs(&args(&["-U3"])), Some(vec![]));
+    assert_eq!(untracked_pathspecs(&args(&["-U3", "HEAD", "--", "src", "my file.rs"])), Some(args(&["src", "my file.rs"])));
+    assert_eq!(untracked_pathspecs(&args(&["main"])), Some(vec![]));
+    // The index, and two commits, don't have untracked files in them
+    assert_eq!(untracked_pathspecs(&args(&["--cached"])), None);
+    assert_eq!(untracked_pathspecs(&args(&["--staged", "HEAD"])), None);
+    assert_eq!(untracked_pathspecs(&args(&["main...HEAD"])), None);
+    assert_eq!(untracked_pathspecs(&args(&["main", "HEAD", "--", "src"])), None);
+}
+
+#[test]
+fn test_get_untracked_diff() {
+    let dir = repository("diff");
+    // This is the only test in this binary that uses the current directory
+    std::env::set_current_dir(dir.join("new module")).unwrap();
+
+    // Paths are the repository's, wherever it's run from, and ignored files are left out
+    let files = untracked_files(&[]).unwrap();
+    assert_eq!(files, [PathBuf::from("logo.png"), PathBuf::from("new module/tax.rs"), PathBuf::from("rates.rs")]);
+    assert_eq!(untracked_files(&args(&["tax.rs"])).unwrap(), [PathBuf::from("new module/tax.rs")]);
+
+    let diff = get_untracked_diff(&files, 20).unwrap();
+    assert!(!diff.contains("logo.png"), "{}", diff);
+    assert!(
+        diff.starts_with(
+            "diff --git a/new module/tax.rs b/new module/tax.rs\nnew file mode 100644\n--- /dev/null\n+++ b/new module/tax.rs\n\
+             @@ -0,0 +1,3 @@\n+fn tax() -> u32 {\n+    0\n+}\n\\ No newline at end of file\n"
+        ),
+        "{}",
+        diff
+    );
+    // A long file is cut short, with a note the diff's readers skip
+    assert!(diff.contains("+++ b/rates.rs\n@@ -0,0 +1,20 @@\n+const RATE_1: u32 = 1;\n"), "{}", diff);
+    assert!(diff.ends_with("+const RATE_20: u32 = 20;\n\\ 10 more lines of the untracked file left out\n"), "{}", diff);
+
+    let parsed = parse_diff(&diff);
+    let paths: Vec<&str> = parsed.iter().map(|file| file.path.as_str()).collect();
+    assert_eq!(paths, ["new module/tax.rs", "rates.rs"]);
+    assert_eq!(parsed[1].hunks[0].lines.iter().filter(|line| line.starts_with('+')).count(), 20);
+
+    std::env::set_current_dir(std::env::temp_dir()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_include_untracked() {
+    let dir = repository("review");
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .args(["--no-provenance"])
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+    };
+
+    let (ok, prompt) = review(&[]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("-    1\n+    2"), "{}", prompt);
+    assert!(!prompt.contains("tax.rs"), "{}", prompt);
+
+    let (ok, prompt) = review(&["--include-untracked", "--untracked-max-lines", "5", "-v"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("-    1\n+    2"), "{}", prompt);
+    assert!(prompt.contains("diff --git a/new module/tax.rs b/new module/tax.rs\nnew file mode 100644\n"), "{}", prompt);
+    assert!(prompt.contains("+const RATE_5: u32 = 5;\n\\ 25 more lines of the untracked file left out"), "{}", prompt);
+    assert!(prompt.contains("Leaving out the untracked binary file logo.png"), "{}", prompt);
+    assert!(!prompt.contains("debug.log"), "{}", prompt);
+
+    // Pathspecs limit the untracked files too, and a diff between commits has none
+    let (ok, prompt) = review(&["--include-untracked", "--", "rates.rs"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("+++ b/rates.rs\n@@ -0,0 +1,30 @@"), "{}", prompt);
+    assert!(!prompt.contains("tax.rs") && !prompt.contains("cart.rs"), "{}", prompt);
+    git(&dir, &["commit", "-q", "-am", "Raise the total"]);
+    let (ok, prompt) = review(&["--include-untracked", "HEAD~1..HEAD"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("the diff doesn't include the working tree, so untracked files are left out"), "{}", prompt);
+    assert!(!prompt.contains("tax.rs"), "{}", prompt);
+
+    // Untracked files are all there is to review
+    let (ok, prompt) = review(&["--include-untracked"]);
+    assert!(ok, "{}", prompt);
+    assert!(prompt.contains("+++ b/new module/tax.rs") && !prompt.contains("cart.rs"), "{}", prompt);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::file_lock::{lock, lock_path};
+use llm_code_review::usage::{
+    budget_banner, estimate_cost, model_price, month_spend, usage_report, Ledger, UsageEntry, USAGE_FILE,
+};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+
+fn entry(timestamp: &str, model: &str, repo: &str, prompt_tokens: usize, cost_usd: Option<f64>) -> UsageEntry {
+    UsageEntry {
+        timestamp: timestamp.to_string(),
+        model: model.to_string(),
+        provider: Some("anthropic".to_string()),
+        repo: Some(repo.to_string()),
+        preset: Some("none".to_string()),
+        prompt_tokens,
+        response_tokens: 1_000,
+        cost_usd,
+    }
+}
+
+#[test]
+fn test_model_price() {
+    assert_eq!(model_price("claude-sonnet-4-5"), Some((3.0, 15.0)));
+    // The longest prefix wins
+    assert_eq!(model_price("gpt-5-mini-2025-08-07"), Some((0.25, 2.0)));
+    assert_eq!(model_price("gpt-5"), Some((1.25, 10.0)));
+    assert_eq!(model_price("claude-opus-4-5-20251101"), Some((5.0, 25.0)));
+    assert_eq!(model_price("claude-opus-4-1"), Some((15.0, 75.0)));
+    assert_eq!(model_price("llama3.1"), None);
+
+    assert_eq!(estimate_cost("claude-sonnet-4-5", 100_000, 2_000), Some(0.33));
+    assert_eq!(estimate_cost("mistral", 100_000, 2_000), None);
+}
+
+#[test]
+fn test_ledger() {
+    let dir = temp_dir("ledger");
+    let ledger = Ledger::new(dir.join("nested").join(USAGE_FILE));
+    assert_eq!(ledger.load().unwrap(), Vec::new());
+
+    // Runs appending at the same time each get their own whole line
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let ledger = ledger.clone();
+            thread::spawn(move || {
+                for j in 0..25 {
+                    let repo = format!("repo-{}-{}", i, j);
+                    ledger.append(&entry("2026-10-01T00:00:00Z", "gpt-5", &repo, 5_000, Some(0.01))).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(ledger.load().unwrap().len(), 200);
+    assert!(lock_path(&ledger.path).ends_with("usage.jsonl.lock"));
+
+    // A line cut short is skipped
+    fs::OpenOptions::new().append(true).open(&ledger.path).unwrap().write_all(b"{\"timestamp\": \"2026-10").unwrap();
+    assert_eq!(ledger.load().unwrap().len(), 200);
+}
+
+#[test]
+fn test_lock_is_exclusive() {
+    let dir = temp_dir("lock");
+    let path = dir.join(".history.json");
+    let held = lock(&path).unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let waiter = {
+        let path = path.clone();
+        thread::spawn(move || {
+            let _lock = lock(&path).unwrap();
+            sender.send(()).unwrap();
+        })
+    };
+    assert!(receiver.recv_timeout(std::time::Duration::from_millis(200)).is_err());
+    drop(held);
+    receiver.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+    waiter.join().unwrap();
+}
+
+#[test]
+fn test_usage_report() {
+    let entries = vec![
+        entry("2026-09-30T23:00:00Z", "claude-sonnet-4-5", "shop", 10_000, Some(0.045)),
+        entry("2026-10-02T10:00:00Z", "claude-sonnet-4-5", "shop", 20_000, Some(0.075)),
+        entry("2026-10-03T10:00:00Z", "mistral", "blog", 1_000, None),
+    ];
+    assert!((month_spend(&entries, "2026-10") - 0.075).abs() < 1e-9);
+
+    let report = usage_report(&entries, None);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "3 runs, 31000 prompt and 3000 response tokens, $0.12+");
+    assert_eq!(lines[2], "Model              runs  prompt tokens  response tokens       cost");
+    assert_eq!(lines[3], "claude-sonnet-4-5     2          30000             2000      $0.12");
+    assert_eq!(lines[4], "mistral               1           1000             1000          -");
+    assert!(report.contains("\nRepository  runs"), "{}", report);
+    assert!(report.contains("\nblog           1"), "{}", report);
+
+    let since = usage_report(&entries, Some("2026-10-01"));
+    assert!(since.starts_with("2 runs since 2026-10-01, 21000 prompt and 2000 response tokens, $0.07+"), "{}", since);
+    assert_eq!(usage_report(&[], None).lines().next(), Some("0 runs, 0 prompt and 0 response tokens, -"));
+
+    let banner = budget_banner(12.5, 10.0, "2026-10");
+    assert_eq!(banner.lines().nth(1), Some("Over the monthly budget: $12.50 of $10.00 spent in 2026-10"));
+    assert_eq!(banner.lines().next().unwrap().len(), banner.lines().nth(1).unwrap().len());
+}
+
+/// Answer one request with `response`
+fn serve(listener: TcpListener, response: String) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        let reply = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.len(),
+            response
+        );
+        reader.get_mut().write_all(reply.as_bytes()).unwrap();
+    })
+}
+
+#[test]
+fn test_usage_recorded() {
+    let dir = temp_dir("recorded");
+    let repo = dir.join("shop");
+    fs::create_dir_all(&repo).unwrap();
+    init_repo(&repo);
+    fs::write(repo.join("a.txt"), "one\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "one"]);
+    fs::write(repo.join("a.txt"), "two\n").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    fs::write(
+        repo.join("providers.toml"),
+        format!(
+            "[[provider]]\nname = \"mock\"\nendpoint = \"http://{}/v1/messages\"\nmax_tokens = 20000\nchars_per_token = 4\nformat = \"anthropic\"\n",
+            listener.local_addr().unwrap()
+        ),
+    )
+    .unwrap();
+    let config_home = dir.join("config");
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&repo)
+            .env("XDG_CONFIG_HOME", &config_home)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+    let send = ["--llm-provider-config", "providers.toml", "--model", "claude-opus-4-1", "--send", "--heartbeat-secs", "0"];
+
+    let answer = serde_json::json!({"content": [{"type": "text", "text": "Looks fine."}]}).to_string();
+    let server = serve(listener, answer);
+    let (success, output) = run(&send);
+    server.join().unwrap();
+    assert!(success, "{}", output);
+
+    let entries = Ledger::new(config_home.join("llm_code_review").join(USAGE_FILE)).load().unwrap();
+    assert_eq!(entries.len(), 1);
+    let recorded = &entries[0];
+    assert_eq!((recorded.model.as_str(), recorded.provider.as_deref(), recorded.repo.as_deref()), ("claude-opus-4-1", Some("mock"), Some("shop")));
+    assert!(recorded.prompt_tokens > 100 && recorded.response_tokens > 0, "{:?}", recorded);
+    assert!(recorded.cost_usd.is_some_and(|cost| cost > 0.0), "{:?}", recorded);
+
+    let (success, report) = run(&["usage", "report", "--since", "2000-01-01"]);
+    assert!(success, "{}", report);
+    assert!(report.starts_with("1 runs since 2000-01-01, "), "{}", report);
+    assert!(report.contains("\nclaude-opus-4-1     1"), "{}", report);
+    let (success, _) = run(&["usage", "report", "--since", "October"]);
+    assert!(!success);
+
+    // The month's budget is spent, so with --enforce-budget nothing is sent; nothing is listening
+    let (success, output) = run(&[&send[..], &["--monthly-budget", "0.000001", "--enforce-budget"]].concat());
+    assert!(!success);
+    assert!(output.contains("Not sending the prompt: $0.0"), "{}", output);
+    assert!(output.contains("monthly budget is already spent in 20"), "{}", output);
+    // Without it, the banner is shown and the prompt sent, which fails here
+    let (success, output) = run(&[&send[..], &["--monthly-budget", "0.000001"]].concat());
+    assert!(!success);
+    assert!(output.contains("Over the monthly budget: $0.0"), "{}", output);
+    assert!(output.contains("LLM request failed"), "{}", output);
+}
@@ -0,0 +1,329 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::error::ReviewError;
+use llm_code_review::vcs::{ci_branch, hg_revision_args, jj_revision_args, require_repository, Vcs};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_detect_vcs() {
+    let dir = temp_dir("vcs");
+    fs::create_dir_all(dir.join("hg/src")).unwrap();
+    fs::create_dir_all(dir.join("hg/.hg")).unwrap();
+    fs::create_dir_all(dir.join("hg/nested-git/.git")).unwrap();
+    fs::create_dir_all(dir.join("colocated/.git")).unwrap();
+    fs::create_dir_all(dir.join("colocated/.jj")).unwrap();
+
+    assert_eq!(Vcs::detect(&dir.join("hg")), Some(Vcs::Hg));
+    assert_eq!(Vcs::detect(&dir.join("hg/src")), Some(Vcs::Hg));
+    assert_eq!(Vcs::detect(&dir.join("hg/nested-git")), Some(Vcs::Git));
+    assert_eq!(Vcs::detect(&dir.join("colocated")), Some(Vcs::Jj));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The program and arguments of `command`, as one line
+fn command_line(command: &Command) -> String {
+    let args: Vec<String> = command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+    format!("{} {}", command.get_program().to_string_lossy(), args.join(" "))
+}
+
+#[test]
+fn test_diff_command() {
+    let given = args(&["main..feature", "--", "src"]);
+    let command_for = |vcs: Vcs| command_line(&vcs.diff_command(&vcs.diff_args(3, &given)));
+    assert_eq!(command_for(Vcs::Git), "git --no-pager diff --no-ext-diff -U3 main..feature -- src");
+    assert_eq!(command_for(Vcs::Hg), "hg diff --git --unified=3 -r main -r feature -- src");
+    assert_eq!(command_for(Vcs::Jj), "jj diff --git --context=3 --from main --to feature -- src");
+    // A context size the user gave goes through in that VCS's own spelling
+    assert_eq!(
+        command_line(&Vcs::Jj.diff_command(&Vcs::Jj.diff_args(3, &args(&["--context=1", "@-"])))),
+        "jj diff --git --from @- --context=1"
+    );
+}
+
+#[test]
+fn test_require_repository() {
+    let dir = temp_dir("no_repo");
+    fs::create_dir_all(dir.join("src")).unwrap();
+
+    match require_repository(&dir.join("src")) {
+        Err(ReviewError::NoRepository(missing)) => assert_eq!(missing, dir.join("src")),
+        other => panic!("expected a NoRepository error, got {:?}", other),
+    }
+    let (ok, _, stderr) = review(&dir, &["main"]);
+    assert!(!ok);
+    assert!(stderr.contains(&format!("No repository found in {} or its parents: looked for .jj, .git and .hg.", dir.display())), "{}", stderr);
+    // --diff-command needs no repository
+    let (ok, stdout, _) = review(&dir, &["--diff-command", "echo"]);
+    assert!(ok, "{}", stdout);
+    assert!(!stdout.contains("No repository found"), "{}", stdout);
+
+    fs::create_dir_all(dir.join(".hg")).unwrap();
+    assert!(require_repository(&dir.join("src")).is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_hg_revision_args() {
+    assert_eq!(
+        hg_revision_args(&args(&["--stat", "abc123", "def456"])),
+        args(&["--stat", "-r", "abc123", "-r", "def456"])
+    );
+    assert_eq!(
+        hg_revision_args(&args(&["default..feature"])),
+        args(&["-r", "default", "-r", "feature"])
+    );
+    assert_eq!(
+        hg_revision_args(&args(&["default...feature"])),
+        args(&["-r", "ancestor(default,feature)", "-r", "feature"])
+    );
+    assert_eq!(
+        hg_revision_args(&args(&["tip", "--", "tip", "Cargo.toml"])),
+        args(&["-r", "tip", "--", "tip", "Cargo.toml"])
+    );
+    // Existing paths are left for hg to treat as file patterns
+    assert_eq!(hg_revision_args(&args(&["Cargo.toml"])), args(&["Cargo.toml"]));
+}
+
+#[test]
+fn test_diff_args() {
+    assert_eq!(
+        Vcs::Git.diff_args(3, &args(&["HEAD~1", "HEAD"])),
+        args(&["-U3", "HEAD~1", "HEAD"])
+    );
+    assert_eq!(
+        Vcs::Hg.diff_args(5, &args(&["tip"])),
+        args(&["--unified=5", "-r", "tip"])
+    );
+    // Each argument stays whole, whatever is in it
+    let given = args(&["-G", "fn main", "HEAD", "--", "my file.txt", "naïve notes.txt", "-U7"]);
+    assert_eq!(Vcs::Git.diff_args(3, &given), [&args(&["-U3"])[..], &given].concat());
+    // The user's context size is given alone, in the VCS's own spelling, but not a path after `--`
+    // that looks like one
+    for context in ["-U1", "--unified=1", "--context=1"] {
+        assert_eq!(Vcs::Git.diff_args(3, &args(&[context, "HEAD"])), args(&["-U1", "HEAD"]));
+        assert_eq!(Vcs::Hg.diff_args(5, &args(&[context, "tip"])), args(&["--unified=1", "-r", "tip"]));
+    }
+    assert_eq!(Vcs::Hg.diff_args(3, &args(&["-U5", "tip"])), args(&["--unified=5", "-r", "tip"]));
+    assert_eq!(Vcs::Jj.diff_args(3, &args(&["-U5", "@-"])), args(&["--from", "@-", "--context=5"]));
+    assert_eq!(Vcs::Jj.diff_args(3, &args(&["--unified=5", "@-"])), args(&["--from", "@-", "--context=5"]));
+}
+
+#[test]
+fn test_jj_revision_args() {
+    assert_eq!(jj_revision_args(&args(&[])), args(&[]));
+    assert_eq!(
+        jj_revision_args(&args(&["main..feature"])),
+        args(&["--from", "main", "--to", "feature"])
+    );
+    assert_eq!(
+        jj_revision_args(&args(&["main...feature"])),
+        args(&["--from", "heads(::main&::feature)", "--to", "feature"])
+    );
+    assert_eq!(
+        jj_revision_args(&args(&["main", "--stat", "--", "src"])),
+        args(&["--from", "main", "--stat", "--", "src"])
+    );
+    assert_eq!(
+        Vcs::Jj.diff_args(2, &args(&["@-"])),
+        args(&["--context=2", "--from", "@-"])
+    );
+}
+
+#[test]
+fn test_git_diff_ignores_hostile_environment() {
+    let dir = temp_dir("git_env");
+
+    init_repo(&dir);
+    git(&dir, &["config", "color.ui", "always"]);
+    fs::write(dir.join("café.txt"), "old line\n").unwrap();
+    git(&dir, &["add", "café.txt"]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+    fs::write(dir.join("café.txt"), "new line\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(&dir)
+        .env("GIT_EXTERNAL_DIFF", "echo HOSTILE")
+        .env("GIT_PAGER", "echo HOSTILE")
+        .env("GIT_CONFIG_PARAMETERS", "'diff.noprefix'='true'")
+        .output()
+        .expect("failed to run llm_code_review");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("HOSTILE"), "{}", stdout);
+    assert!(!stdout.contains('\x1b'), "{}", stdout);
+    assert!(stdout.contains("diff --git a/café.txt b/café.txt"), "{}", stdout);
+    assert!(stdout.contains("-old line\n+new line"), "{}", stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Run the tool in `dir`, returning whether it succeeded, its stdout and its stderr
+fn review(dir: &Path, args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+        .current_dir(dir)
+        .arg("--no-provenance")
+        .args(args)
+        .output()
+        .expect("failed to run llm_code_review");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn new_repo(name: &str) -> std::path::PathBuf {
+    let dir = temp_dir(name);
+    init_repo(&dir);
+    dir
+}
+
+#[test]
+fn test_repository_without_commits() {
+    let dir = new_repo("unborn");
+    fs::write(dir.join("staged.txt"), "staged line\n").unwrap();
+    git(&dir, &["add", "staged.txt"]);
+    fs::write(dir.join("staged.txt"), "staged line\nunstaged line\n").unwrap();
+
+    // With no revision, the staged and unstaged changes are reviewed together
+    let (ok, stdout, stderr) = review(&dir, &[]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("+staged line\n+unstaged line"), "{}", stdout);
+
+    let (ok, stdout, stderr) = review(&dir, &["HEAD"]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("+staged line\n+unstaged line"), "{}", stdout);
+
+    let (ok, stdout, stderr) = review(&dir, &["--cached"]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("+staged line\n"), "{}", stdout);
+    assert!(!stdout.contains("+unstaged line"), "{}", stdout);
+
+    // Errors are logged, which may go to either stream
+    let (ok, stdout, stderr) = review(&dir, &["HEAD~1"]);
+    let output = stdout + &stderr;
+    assert!(!ok);
+    assert!(output.contains("The repository has no commits yet, so there is no HEAD~1 to compare with"), "{}", output);
+    assert!(!output.contains("fatal:"), "{}", output);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_first_commit() {
+    let dir = new_repo("root_commit");
+    fs::write(dir.join("first.txt"), "first line\n").unwrap();
+    git(&dir, &["add", "first.txt"]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+
+    // The parent of the root commit is the empty tree, as with `git show --root`
+    for args in [&["HEAD~1"][..], &["HEAD^", "HEAD"], &["HEAD~1..HEAD"], &["HEAD^..."]] {
+        let (ok, stdout, stderr) = review(&dir, args);
+        assert!(ok, "{:?}: {}", args, stderr);
+        assert!(stdout.contains("new file mode 100644"), "{:?}: {}", args, stdout);
+        assert!(stdout.contains("+first line"), "{:?}: {}", args, stdout);
+    }
+
+    let (ok, stdout, stderr) = review(&dir, &["HEAD~3"]);
+    let output = stdout + &stderr;
+    assert!(!ok);
+    assert!(
+        output.contains("HEAD~3 is before the first commit: HEAD has only 1 commit of history. Use HEAD~1"),
+        "{}",
+        output
+    );
+
+    // Revisions that don't exist for other reasons are still git's to explain
+    let (ok, stdout, stderr) = review(&dir, &["no-such-branch"]);
+    assert!(!ok);
+    assert!((stdout + &stderr).contains("unknown revision"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_pathspec_with_space() {
+    let dir = new_repo("pathspec_space");
+    fs::create_dir_all(dir.join("my folder")).unwrap();
+    fs::write(dir.join("my folder/file.rs"), "old line\n").unwrap();
+    fs::write(dir.join("other.rs"), "old other\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+    fs::write(dir.join("my folder/file.rs"), "new line\n").unwrap();
+    fs::write(dir.join("other.rs"), "new other\n").unwrap();
+
+    let (ok, stdout, stderr) = review(&dir, &["--", "my folder/file.rs"]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("diff --git a/my folder/file.rs b/my folder/file.rs"), "{}", stdout);
+    assert!(stdout.contains("-old line\n+new line"), "{}", stdout);
+    assert!(!stdout.contains("other.rs"), "{}", stdout);
+
+    // Reducing the context rewrites -U without splitting the pathspec
+    let (ok, stdout, stderr) = review(&dir, &["--force-reduced", "-U5", "--", "my folder/file.rs"]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("-old line\n+new line"), "{}", stdout);
+    assert!(!stdout.contains("other.rs"), "{}", stdout);
+    // The user's -U is the only one git gets
+    let (ok, _, stderr) = review(&dir, &["--debug", "-U0", "--", "my folder/file.rs"]);
+    assert!(ok, "{}", stderr);
+    assert!(stderr.contains(r#""diff" "--no-ext-diff" "-U0" "--" "my folder/file.rs""#), "{}", stderr);
+    assert!(!stderr.contains(r#""-U3""#), "{}", stderr);
+
+    // An option's value with a space in it reaches git as one argument
+    let (ok, stdout, stderr) = review(&dir, &["-G", "new line", "HEAD"]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("-old line\n+new line"), "{}", stdout);
+    assert!(!stdout.contains("other.rs"), "{}", stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_ci_branch() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+    };
+    assert_eq!(ci_branch(env(&[])), None);
+    assert_eq!(ci_branch(env(&[("GITHUB_REF_NAME", "main")])), Some("main".to_string()));
+    // A pull request's head branch, not its merge ref
+    assert_eq!(
+        ci_branch(env(&[("GITHUB_REF_NAME", "12/merge"), ("GITHUB_HEAD_REF", "feature/x")])),
+        Some("feature/x".to_string())
+    );
+    // GITHUB_HEAD_REF is set but empty outside pull requests
+    assert_eq!(ci_branch(env(&[("GITHUB_HEAD_REF", ""), ("CIRCLE_BRANCH", "dev")])), Some("dev".to_string()));
+    assert_eq!(ci_branch(env(&[("GIT_BRANCH", "origin/release")])), Some("release".to_string()));
+}
+
+#[test]
+fn test_git_diff_exit_status() {
+    let dir = new_repo("exit_status");
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "first"]);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+
+    // --exit-code makes git exit with 1 when there are changes, which is what's wanted here
+    let (ok, stdout, stderr) = review(&dir, &["--exit-code"]);
+    assert!(ok, "{}{}", stdout, stderr);
+    assert!(stdout.contains("-one\n+two"), "{}", stdout);
+
+    let (ok, _, stderr) = review(&dir, &["--quiet"]);
+    assert!(!ok);
+    assert!(stderr.contains("--quiet makes git diff print nothing"), "{}", stderr);
+
+    let (ok, _, stderr) = review(&dir, &["no-such-revision"]);
+    assert!(!ok);
+    assert!(stderr.contains("Git command failed") && stderr.contains("no-such-revision"), "{}", stderr);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
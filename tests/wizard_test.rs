@@ -0,0 +1,149 @@
+use llm_code_review::config::Config;
+use llm_code_review::provider::ProviderConfig;
+use llm_code_review::wizard::{equivalent_flags, run_wizard, save_config, Terminal, WIZARD_HISTORY_DIR};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Answers the wizard's questions from a script, keeping a transcript of what was shown
+#[derive(Default)]
+struct Script {
+    answers: VecDeque<&'static str>,
+    shown: String,
+    secrets_asked: usize,
+}
+
+impl Script {
+    fn new(answers: &[&'static str]) -> Self {
+        Script { answers: answers.iter().copied().collect(), ..Script::default() }
+    }
+}
+
+impl Terminal for Script {
+    fn say(&mut self, message: &str) -> io::Result<()> {
+        self.shown.push_str(message);
+        self.shown.push('\n');
+        Ok(())
+    }
+
+    fn ask(&mut self, question: &str) -> io::Result<String> {
+        self.shown.push_str(question);
+        let answer = self.answers.pop_front().ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        self.shown.push_str(answer);
+        self.shown.push('\n');
+        Ok(answer.to_string())
+    }
+
+    fn ask_secret(&mut self, question: &str) -> io::Result<String> {
+        self.secrets_asked += 1;
+        self.shown.push_str(question);
+        self.shown.push('\n');
+        self.answers.pop_front().map(str::to_string).ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+}
+
+#[test]
+fn test_wizard_checks_the_key_before_saving() {
+    // The first key is rejected, the second accepted
+    let mut script = Script::new(&["", "openai", "", "LLMCR_TEST_KEY", "sk-rejected", "", "sk-accepted", "json", "y"]);
+    let sent = RefCell::new(Vec::new());
+    let round_trip = |provider: &ProviderConfig, key: Option<&str>| {
+        sent.borrow_mut().push((provider.name.clone(), key.map(str::to_string)));
+        Ok(if key == Some("sk-accepted") { 400 } else { 401 })
+    };
+    let config = run_wizard(&mut script, &|_| None, &round_trip).unwrap().unwrap();
+    assert_eq!(
+        *sent.borrow(),
+        vec![
+            ("openai".to_string(), Some("sk-rejected".to_string())),
+            ("openai".to_string(), Some("sk-accepted".to_string()))
+        ]
+    );
+    assert_eq!(script.secrets_asked, 2);
+    assert!(script.shown.contains("The API key was rejected"), "{}", script.shown);
+    // The key is never shown, nor saved
+    assert!(!script.shown.contains("sk-"), "{}", script.shown);
+    assert!(!toml::to_string(&config).unwrap().contains("sk-"));
+
+    assert_eq!(config.provider.as_deref(), Some("openai"));
+    assert_eq!(config.model.as_deref(), Some("gpt-5"));
+    assert_eq!(config.api_key_env.as_deref(), Some("LLMCR_TEST_KEY"));
+    assert_eq!(config.output_format.as_deref(), Some("json"));
+    assert_eq!(config.history_dir, Some(PathBuf::from(WIZARD_HISTORY_DIR)));
+    assert_eq!(
+        equivalent_flags(&config),
+        "--provider openai --model gpt-5 --api-key-env LLMCR_TEST_KEY --output-format json --history-dir .llm_code_review/history"
+    );
+}
+
+#[test]
+fn test_wizard_uses_the_key_in_the_environment() {
+    // The usual variable and the defaults are taken, so only the provider and model are saved
+    let mut script = Script::new(&["y", "", "claude-opus-4-1", "", "", ""]);
+    let env = |var: &str| (var == "ANTHROPIC_API_KEY").then(|| "sk-from-env".to_string());
+    let round_trip = |_: &ProviderConfig, key: Option<&str>| Ok(if key == Some("sk-from-env") { 400 } else { 401 });
+    let config = run_wizard(&mut script, &env, &round_trip).unwrap().unwrap();
+    assert_eq!(script.secrets_asked, 0);
+    assert_eq!(
+        config,
+        Config {
+            provider: Some("anthropic".to_string()),
+            model: Some("claude-opus-4-1".to_string()),
+            ..Config::default()
+        }
+    );
+    assert_eq!(equivalent_flags(&config), "--provider anthropic --model claude-opus-4-1");
+
+    // Ollama needs no key, but is still checked
+    let mut script = Script::new(&["", "ollama", "", "", "n"]);
+    let config = run_wizard(&mut script, &|_| None, &|_, key| {
+        assert_eq!(key, None);
+        Ok(400)
+    });
+    assert_eq!(config.unwrap().unwrap().provider.as_deref(), Some("ollama"));
+}
+
+#[test]
+fn test_wizard_saves_nothing_unless_asked() {
+    let unreachable = |_: &ProviderConfig, _: Option<&str>| Err("connection refused".to_string());
+
+    let mut script = Script::new(&["n"]);
+    assert_eq!(run_wizard(&mut script, &|_| None, &unreachable).unwrap(), None);
+    assert!(script.shown.contains("--no-wizard"), "{}", script.shown);
+
+    // Answering `never` saves an empty config, so the wizard isn't offered again
+    let mut script = Script::new(&["never"]);
+    assert_eq!(run_wizard(&mut script, &|_| None, &unreachable).unwrap(), Some(Config::default()));
+
+    // A provider that can't be reached isn't saved
+    let mut script = Script::new(&["", "ollama", "", "n"]);
+    assert_eq!(run_wizard(&mut script, &|_| None, &unreachable).unwrap(), None);
+    assert!(script.shown.contains("no response from") && script.shown.contains("Nothing saved"), "{}", script.shown);
+
+    // Unknown providers are asked for again, and running out of answers stops the wizard
+    let mut script = Script::new(&["", "mistral"]);
+    assert!(run_wizard(&mut script, &|_| None, &unreachable).is_err());
+    assert!(script.shown.contains("`mistral` is not one of anthropic, openai, ollama"), "{}", script.shown);
+}
+
+#[test]
+fn test_save_wizard_config() {
+    let dir = std::env::temp_dir().join(format!("llm_code_review_wizard_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("llm_code_review/config.toml");
+    let config = Config {
+        provider: Some("openai".to_string()),
+        model: Some("gpt-5".to_string()),
+        history_dir: Some(PathBuf::from(WIZARD_HISTORY_DIR)),
+        ..Config::default()
+    };
+    save_config(&config, &path).unwrap();
+    assert_eq!(Config::load(&path).unwrap(), Config { sources: vec![path.clone()], ..config });
+
+    // An empty config is still a config file
+    save_config(&Config::default(), &path).unwrap();
+    assert_eq!(Config::load(&path).unwrap().sources, vec![path]);
+    let _ = fs::remove_dir_all(&dir);
+}
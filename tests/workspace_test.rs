@@ -0,0 +1,134 @@
+mod common;
+
+use common::{git, init_repo, temp_dir};
+use llm_code_review::diff::parse_diff;
+use llm_code_review::workspace::{packages_section, restrict_to_package, Workspace};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn write(dir: &Path, path: &str, contents: &str) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+// A Cargo workspace with a root package and members core, util and an excluded old
+fn cargo_workspace(dir: &Path) {
+    write(
+        dir,
+        "Cargo.toml",
+        "[package]\nname = \"app\"\nversion = \"1.0.0\"\n\n[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n",
+    );
+    write(
+        dir,
+        "crates/core/Cargo.toml",
+        "[package]\nname = \"app-core\"\nversion.workspace = true\n\n[dependencies]\napp-util = { path = \"../util\" }\nserde = \"1\"\n",
+    );
+    write(dir, "crates/util/Cargo.toml", "[package]\nname = \"app-util\"\nversion = \"0.2.0\"\n");
+    write(dir, "crates/old/Cargo.toml", "[package]\nname = \"app-old\"\n");
+    write(dir, "crates/notes/README.md", "Not a crate\n");
+}
+
+#[test]
+fn test_cargo_workspace() {
+    let dir = temp_dir("cargo");
+    cargo_workspace(&dir);
+    let workspace = Workspace::detect(&dir).unwrap().unwrap();
+    let names: Vec<(&str, &str)> =
+        workspace.packages.iter().map(|package| (package.name.as_str(), package.dir.as_str())).collect();
+    assert_eq!(names, [("app", ""), ("app-core", "crates/core"), ("app-util", "crates/util")]);
+
+    let core = workspace.get("app-core").unwrap();
+    assert_eq!(
+        core.summary(&workspace).as_deref(),
+        Some("Cargo.toml: version from the workspace; depends on `app-util` (in the workspace), serde")
+    );
+    assert!(workspace.get("app-old").unwrap_err().to_string().contains("there are app, app-core, app-util"));
+    assert_eq!(workspace.owner("crates/core/src/lib.rs").unwrap().name, "app-core");
+    assert_eq!(workspace.owner("crates/core-extra/lib.rs").unwrap().name, "app");
+
+    assert_eq!(Workspace::detect(&dir.join("crates/util")).unwrap(), None);
+}
+
+#[test]
+fn test_npm_workspace() {
+    let dir = temp_dir("npm");
+    write(&dir, "package.json", r#"{"name": "web", "workspaces": {"packages": ["packages/**", "!packages/legacy"]}}"#);
+    write(&dir, "packages/ui/package.json", r#"{"name": "@web/ui", "version": "3.1.0", "dependencies": {"react": "^18"}}"#);
+    write(&dir, "packages/tools/lint/package.json", r#"{"name": "@web/lint"}"#);
+    write(&dir, "packages/legacy/package.json", r#"{"name": "@web/legacy"}"#);
+    write(&dir, "packages/ui/node_modules/react/package.json", r#"{"name": "react"}"#);
+    let workspace = Workspace::detect(&dir).unwrap().unwrap();
+    let names: Vec<&str> = workspace.packages.iter().map(|package| package.name.as_str()).collect();
+    assert_eq!(names, ["@web/lint", "@web/ui"]);
+    assert_eq!(
+        workspace.get("@web/ui").unwrap().summary(&workspace).as_deref(),
+        Some("package.json: version 3.1.0; depends on react")
+    );
+}
+
+#[test]
+fn test_packages() {
+    let dir = temp_dir("file");
+    write(&dir, "packages.toml", "[[package]]\nname = \"api\"\npath = \"services/api/\"\n\n[[package]]\nname = \"web\"\npath = \"./web\"\n");
+    let workspace = Workspace::load(&dir.join("packages.toml")).unwrap();
+    assert_eq!(workspace.get("api").unwrap().dir, "services/api");
+
+    let paths: Vec<String> =
+        ["web/app.js", "README.md", "services/api/main.go", "web/index.html"].iter().map(|path| path.to_string()).collect();
+    assert_eq!(
+        packages_section(&workspace, &paths).unwrap(),
+        "The diff changes 2 packages of this monorepo. Flag any coupling between packages that the change \
+         introduces, such as a new dependency between them or one package relying on another's internals.\n\n\
+         ### `api` in `services/api`\n- `services/api/main.go`\n\n\
+         ### `web` in `web`\n- `web/app.js`\n- `web/index.html`\n\n\
+         ### Outside any package\n- `README.md`"
+    );
+    assert!(packages_section(&workspace, &paths[1..3]).unwrap().starts_with("The diff changes one package"));
+    assert_eq!(packages_section(&workspace, &paths[1..2]), None);
+
+    // A file moved out of the package is still part of its review
+    let diff = "diff --git a/web/old.js b/shared/old.js\nsimilarity index 100%\nrename from web/old.js\nrename to shared/old.js\n\
+                diff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-a\n+b\n";
+    let kept = restrict_to_package(parse_diff(diff), workspace.get("web").unwrap());
+    assert_eq!(kept.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), ["shared/old.js"]);
+}
+
+#[test]
+fn test_review_packages() {
+    let dir = temp_dir("review");
+    init_repo(&dir);
+    cargo_workspace(&dir);
+    write(&dir, "crates/core/src/lib.rs", "pub fn run() {}\n");
+    write(&dir, "crates/util/src/lib.rs", "pub fn helper() {}\n");
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "one"]);
+    write(&dir, "crates/core/src/lib.rs", "pub fn run() {\n    app_util::internal::cache();\n}\n");
+    write(&dir, "crates/util/src/lib.rs", "pub fn helper() {}\npub mod internal;\n");
+
+    let review = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_llm_code_review"))
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .expect("failed to run llm_code_review");
+        (output.status.success(), format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout)))
+    };
+
+    let (success, prompt) = review(&[]);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("## Packages\nThe diff changes 2 packages of this monorepo. Flag any coupling"), "{}", prompt);
+    assert!(prompt.contains("### `app-core` in `crates/core`\nCargo.toml: version from the workspace"), "{}", prompt);
+    assert!(prompt.contains("- `crates/util/src/lib.rs`"), "{}", prompt);
+
+    let (success, prompt) = review(&["--package", "app-util"]);
+    assert!(success, "{}", prompt);
+    assert!(prompt.contains("+pub mod internal;"), "{}", prompt);
+    assert!(!prompt.contains("cache()"), "{}", prompt);
+    assert!(prompt.contains("The diff changes one package"), "{}", prompt);
+
+    let (success, output) = review(&["--package", "app-web"]);
+    assert!(!success);
+    assert!(output.contains("no package named `app-web` in the workspace"), "{}", output);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{git, init_repo};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// CI variables that would name a branch for the detached checkouts here
+const CI_BRANCH_VARS: &[&str] = &[
+    "GITHUB_HEAD_REF",
+    "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME",
+    "CI_COMMIT_BRANCH",
+    "BUILDKITE_BRANCH",
+    "CIRCLE_BRANCH",
+    "BITBUCKET_BRANCH",
+    "GIT_BRANCH",
+    "GITHUB_REF_NAME",
+];
+
+fn review(dir: &Path, env: &[(&str, &str)]) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_llm_code_review"));
+    command.current_dir(dir).arg("--no-wrap");
+    for var in CI_BRANCH_VARS {
+        command.env_remove(var);
+    }
+    let output = command.envs(env.iter().copied()).output().expect("failed to run llm_code_review");
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(output.status.success(), "{}", stdout);
+    stdout
+}
+
+#[test]
+fn test_detached_linked_worktree() {
+    let root = std::env::temp_dir().join(format!("llm_code_review_worktree_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let repo = root.join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    init_repo(&repo);
+    git(&repo, &["checkout", "-q", "-b", "main"]);
+    fs::write(repo.join("lib.rs"), "fn one() {}\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "first"]);
+    let base = git(&repo, &["rev-parse", "HEAD"]);
+    // A clone's record of the remote's default branch
+    git(&repo, &["update-ref", "refs/remotes/origin/trunk", "HEAD"]);
+    git(&repo, &["symbolic-ref", "refs/remotes/origin/HEAD", "refs/remotes/origin/trunk"]);
+    fs::write(repo.join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+    git(&repo, &["commit", "-q", "-a", "-m", "second"]);
+
+    // CI checks out a detached HEAD in a secondary worktree
+    let worktree = root.join("ci");
+    git(&repo, &["worktree", "add", "-q", "--detach", worktree.to_str().unwrap(), "HEAD"]);
+    fs::write(worktree.join("lib.rs"), "fn one() {}\nfn two() { todo!() }\n").unwrap();
+    let short = git(&worktree, &["rev-parse", "--short", "HEAD"]);
+
+    let stdout = review(&worktree, &[]);
+    assert!(stdout.contains("+fn two() { todo!() }"), "{}", stdout);
+    assert!(stdout.contains(&format!("- Branch: detached at {}\n", short)), "{}", stdout);
+    // With no base revision given, the merge base is with the branch origin/HEAD points at
+    assert!(stdout.contains(&format!("- Merge base: {}\n", base)), "{}", stdout);
+
+    let stdout = review(&worktree, &[("GITHUB_HEAD_REF", "feature/fix-timeouts"), ("GITHUB_REF_NAME", "12/merge")]);
+    assert!(stdout.contains("- Branch: feature/fix-timeouts\n"), "{}", stdout);
+    let stdout = review(&worktree, &[("GIT_BRANCH", "origin/release")]);
+    assert!(stdout.contains("- Branch: release\n"), "{}", stdout);
+
+    // State is kept in the git directory the worktrees share, not the worktree's own
+    let common = repo.join(".git").join("llm_code_review_languages.json");
+    assert!(common.is_file());
+    assert!(!repo.join(".git/worktrees/ci/llm_code_review_languages.json").exists());
+
+    fs::remove_dir_all(&root).unwrap();
+}
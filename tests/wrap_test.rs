@@ -0,0 +1,103 @@
+use llm_code_review::wrap::{output_width, wrap_markdown, wrap_prose_sections};
+
+#[test]
+fn test_wrap_prose_sections() {
+    let prompt = "\
+## Focus Areas
+- Check that every public function documents its error cases and panics
+Short line
+
+```
+a_very_long_code_line_in_the_context_that_must_not_be_wrapped(with, many, arguments);
+```
+
+# PR Code
+
++    let this_diff_line_is_long_enough_to_wrap = but_it_must_be_left_exactly_as_it_is();";
+
+    let wrapped = wrap_prose_sections(prompt, 40);
+    assert_eq!(
+        wrapped,
+        "\
+## Focus Areas
+- Check that every public function
+  documents its error cases and panics
+Short line
+
+```
+a_very_long_code_line_in_the_context_that_must_not_be_wrapped(with, many, arguments);
+```
+
+# PR Code
+
++    let this_diff_line_is_long_enough_to_wrap = but_it_must_be_left_exactly_as_it_is();"
+    );
+
+    // Nothing to do when every line already fits
+    assert_eq!(wrap_prose_sections(prompt, 200), prompt);
+}
+
+#[test]
+fn test_wrap_markdown_next_to_code_fences() {
+    // Paragraphs right against the fences, with no blank lines between
+    let review = "\
+The loop below reads the whole file into memory before it checks the size limit.
+```rust
+let contents = fs::read_to_string(path)?; // an unusually long line of code that must stay whole
+```
+Reading it in chunks would let the check stop early and keep memory use flat on large inputs.
+~~~
+``` not a closing fence inside a tilde block, and a long line that must stay whole
+~~~
+Done.";
+
+    assert_eq!(
+        wrap_markdown(review, 40),
+        "\
+The loop below reads the whole file into
+memory before it checks the size limit.
+```rust
+let contents = fs::read_to_string(path)?; // an unusually long line of code that must stay whole
+```
+Reading it in chunks would let the check
+stop early and keep memory use flat on
+large inputs.
+~~~
+``` not a closing fence inside a tilde block, and a long line that must stay whole
+~~~
+Done."
+    );
+}
+
+#[test]
+fn test_wrap_markdown_leaves_tables_and_long_words() {
+    let review = "\
+| File | Comment |
+|---|---|
+| `src/review.rs` | The error from the provider is dropped instead of being reported |
+
+  * See https://example.com/a/very/long/link/that/is/wider/than/the/limit for details";
+
+    assert_eq!(
+        wrap_markdown(review, 30),
+        "\
+| File | Comment |
+|---|---|
+| `src/review.rs` | The error from the provider is dropped instead of being reported |
+
+  * See
+    https://example.com/a/very/long/link/that/is/wider/than/the/limit
+    for details"
+    );
+}
+
+#[test]
+fn test_output_width() {
+    // Piped output isn't wrapped unless asked for
+    assert_eq!(output_width(false, Some("100"), || 80), None);
+    assert_eq!(output_width(true, None, || 80), Some(80));
+    assert_eq!(output_width(true, Some("100"), || 80), Some(100));
+    assert_eq!(output_width(true, Some(" 72 "), || 80), Some(72));
+    assert_eq!(output_width(true, Some("0"), || 80), Some(80));
+    assert_eq!(output_width(true, Some("wide"), || 80), Some(80));
+}
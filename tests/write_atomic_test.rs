@@ -0,0 +1,64 @@
+mod common;
+
+use common::temp_dir;
+use llm_code_review::write_atomic::{stage, write_atomic};
+use std::fs;
+use std::path::PathBuf;
+
+fn entries(dir: &PathBuf) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_write_atomic_replaces_file() {
+    let dir = temp_dir("atomic_replace");
+    let path = dir.join("report.json");
+
+    write_atomic(&path, "{\"old\": true}\n").unwrap();
+    write_atomic(&path, "{\"new\": true}\n").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"new\": true}\n");
+    assert_eq!(entries(&dir), vec!["report.json"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_interrupted_write_leaves_old_contents() {
+    let dir = temp_dir("atomic_interrupted");
+    let path = dir.join("baseline.json");
+    write_atomic(&path, "{\"complete\": true}\n").unwrap();
+
+    // Written but not yet renamed: the destination is untouched
+    let staged = stage(&path, b"{\"comp").unwrap();
+    assert_eq!(fs::read_to_string(staged.temp_path()).unwrap(), "{\"comp");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"complete\": true}\n");
+
+    // The run stops before the rename
+    drop(staged);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"complete\": true}\n");
+    assert_eq!(entries(&dir), vec!["baseline.json"]);
+
+    let staged = stage(&path, b"{\"complete\": false}\n").unwrap();
+    staged.commit().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"complete\": false}\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_write_error_names_path_and_kind() {
+    let dir = temp_dir("atomic_error");
+    let path = dir.join("missing").join("out.md");
+
+    let message = write_atomic(&path, "prompt").unwrap_err().to_string();
+    assert!(message.starts_with("Could not write "), "{}", message);
+    assert!(message.contains(&dir.join("missing").display().to_string()), "{}", message);
+    assert!(message.contains("entity not found"), "{}", message);
+
+    fs::remove_dir_all(&dir).unwrap();
+}